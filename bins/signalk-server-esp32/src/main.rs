@@ -46,18 +46,26 @@ use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
     hal::prelude::Peripherals,
     http::server::{ws::EspHttpWsDetachedSender, Configuration as HttpConfig, EspHttpServer},
-    io::Write,
+    io::{Read, Write},
+    nvs::{EspDefaultNvs, EspDefaultNvsPartition},
 };
 use log::{error, info, warn};
 use serde_json::json;
-use signalk_core::{Delta, MemoryStore, PathValue, SignalKStore, Update};
+use signalk_core::{Delta, MemoryStore, PathValue, SelfUrn, SignalKStore, Update};
 use signalk_esp32::{
-    config::ServerConfig,
+    config::{ServerConfig, WifiConfig, NVS_NAMESPACE},
+    config_api::{
+        error_status, update_config, write_config, ConfigResource, StdWriter, MAX_CONFIG_BODY_LEN,
+    },
+    health::{spawn_heap_monitor, HeapMonitorConfig},
     http::{
         create_discovery_json, create_hello_message, current_timestamp,
-        default_subscription_for_mode, get_path_json, process_client_message, ClientSubscription,
-        WsQueryParams,
+        default_subscription_for_mode, get_path_json, process_client_message, ApiPathQuery,
+        ClientSubscription, WsQueryParams,
     },
+    lock::{lock_recovering, lock_store},
+    outbox::ClientOutbox,
+    storage::NvsConfigStorage,
     wifi::connect_wifi,
 };
 use std::{
@@ -77,31 +85,40 @@ struct ClientState {
     sender: EspHttpWsDetachedSender,
     /// Client's subscription state.
     subscription: ClientSubscription,
+    /// Values waiting for the writer thread.
+    outbox: ClientOutbox,
 }
 
 /// Type alias for the collection of connected WebSocket clients.
 /// Key is the session ID (socket fd).
 type WsClients = Arc<Mutex<HashMap<i32, ClientState>>>;
 
-/// Check if a delta should be sent, respecting throttle limits.
-/// Returns a list of pattern indices that matched and should be marked as sent.
-fn should_send_delta_throttled(subscription: &ClientSubscription, delta: &Delta) -> Vec<usize> {
-    let mut matched_indices = Vec::new();
+/// Maximum distinct paths queued per client before the oldest is dropped.
+/// See `signalk_esp32::outbox` for the memory budget.
+const WS_OUTBOX_CAPACITY: usize = 16;
 
+/// Queue the subscribed, non-throttled values of a delta for a client.
+/// Returns true if anything was queued.
+fn queue_delta_throttled(client: &mut ClientState, delta: &Delta) -> bool {
     // If no subscription, don't send anything
-    if subscription.is_empty() {
-        return matched_indices;
+    if client.subscription.is_empty() {
+        return false;
     }
 
     // Check context filter
-    if !subscription.matches_context(delta.context.as_deref()) {
-        return matched_indices;
+    if !client
+        .subscription
+        .matches_context(delta.context.as_deref())
+    {
+        return false;
     }
 
     // Check each path in the delta against subscription with throttle check
+    let mut matched_indices = Vec::new();
     for update in &delta.updates {
         for pv in &update.values {
-            if let Some(idx) = subscription.should_send_path(&pv.path) {
+            if let Some(idx) = client.subscription.should_send_path(&pv.path) {
+                client.outbox.push(delta.context.as_deref(), update, pv);
                 if !matched_indices.contains(&idx) {
                     matched_indices.push(idx);
                 }
@@ -109,11 +126,69 @@ fn should_send_delta_throttled(subscription: &ClientSubscription, delta: &Delta)
         }
     }
 
-    matched_indices
+    // Mark matched patterns as sent (update throttle timers)
+    for idx in &matched_indices {
+        client.subscription.mark_sent(*idx);
+    }
+
+    !matched_indices.is_empty()
+}
+
+/// Drain every client's outbox and send the queued deltas.
+///
+/// Senders are cloned and the clients lock released before sending, so a
+/// slow socket only delays this thread, never the delta processor.
+fn flush_outboxes(ws_clients: &WsClients) {
+    let mut batches = Vec::new();
+    {
+        let mut clients = lock_recovering(ws_clients, "Clients");
+        for (client_id, client_state) in clients.iter_mut() {
+            let dropped = client_state.outbox.take_dropped();
+            if dropped > 0 {
+                warn!(
+                    "Client {} is slow: dropped {} values ({} total)",
+                    client_id,
+                    dropped,
+                    client_state.outbox.dropped_total()
+                );
+            }
+            if !client_state.outbox.is_empty() {
+                batches.push((
+                    *client_id,
+                    client_state.sender.clone(),
+                    client_state.outbox.drain(),
+                ));
+            }
+        }
+    }
+
+    let mut failed_clients = Vec::new();
+    for (client_id, mut sender, deltas) in batches {
+        for delta in deltas {
+            let Ok(json) = serde_json::to_string(&delta) else {
+                continue;
+            };
+            if let Err(e) = sender.send(FrameType::Text(false), json.as_bytes()) {
+                warn!("Failed to send delta to client {}: {:?}", client_id, e);
+                failed_clients.push(client_id);
+                break;
+            }
+        }
+    }
+
+    // Remove failed clients
+    if !failed_clients.is_empty() {
+        let mut clients = lock_recovering(ws_clients, "Clients");
+        for client_id in failed_clients {
+            clients.remove(&client_id);
+            info!("Removed disconnected client {}", client_id);
+        }
+    }
 }
 
-// WiFi credentials - set via environment variables at build time
+// Default WiFi credentials - set via environment variables at build time
 // Example: WIFI_SSID="MyNetwork" WIFI_PASSWORD="secret" cargo build
+// Only used when no WiFi configuration is stored in NVS.
 // Falls back to "unconfigured" if not set (will fail to connect)
 const WIFI_SSID: &str = match option_env!("WIFI_SSID") {
     Some(v) => v,
@@ -124,6 +199,12 @@ const WIFI_PASSWORD: &str = match option_env!("WIFI_PASSWORD") {
     None => "unconfigured",
 };
 
+/// Heap sampling interval for the health monitor.
+const HEAP_MONITOR_INTERVAL_SECS: u64 = 10;
+
+/// Free heap (bytes) below which a warning is logged.
+const HEAP_LOW_THRESHOLD: u32 = 20 * 1024;
+
 fn main() -> Result<()> {
     // Initialize ESP-IDF patches
     esp_idf_svc::sys::link_patches();
@@ -138,16 +219,38 @@ fn main() -> Result<()> {
     // Take peripherals
     let peripherals = Peripherals::take()?;
     let sysloop = EspSystemEventLoop::take()?;
+    let nvs_partition = EspDefaultNvsPartition::take()?;
+    let nvs = EspDefaultNvs::new(nvs_partition, NVS_NAMESPACE, true)?;
 
-    // Initialize WiFi using shared crate
+    // Initialize WiFi using shared crate (NVS credentials take precedence)
     info!("Initializing WiFi...");
-    let (_wifi, ip_addr) =
-        connect_wifi(WIFI_SSID, WIFI_PASSWORD, peripherals.modem, sysloop.clone())?;
+    let wifi_config = WifiConfig::load_or(&nvs, WifiConfig::new(WIFI_SSID, WIFI_PASSWORD));
+    let (_wifi, ip_addr) = connect_wifi(&wifi_config, peripherals.modem, sysloop.clone())?;
+
+    // Vessel identity is minted on first boot and persisted in NVS
+    let storage = Arc::new(NvsConfigStorage::new(nvs));
+    let self_urn = SelfUrn::load_or_generate(storage.as_ref()).unwrap_or_else(|e| {
+        warn!("Could not load persistent vessel UUID, using a temporary one: {e}");
+        SelfUrn::generate()
+    });
 
     // Server configuration using shared crate
-    let config = ServerConfig::new_with_uuid();
+    let config = ServerConfig::with_self_urn(&self_urn);
     info!("Server URN: {}", config.self_urn);
 
+    // Advertise the server over mDNS (must stay alive for the advertisement)
+    #[cfg(feature = "mdns")]
+    let _mdns = signalk_esp32::mdns::advertise(
+        wifi_config
+            .hostname
+            .as_deref()
+            .unwrap_or(signalk_esp32::mdns::DEFAULT_MDNS_HOSTNAME),
+        &config.name,
+        config.http_port,
+        &config.version,
+        &config.self_urn,
+    )?;
+
     // Create shared store (same as Linux, but with Mutex instead of RwLock)
     let store = Arc::new(Mutex::new(MemoryStore::new(&config.self_urn)));
 
@@ -157,8 +260,13 @@ fn main() -> Result<()> {
     // Channel for delta events
     let (delta_tx, delta_rx) = mpsc::channel::<Delta>();
 
+    // Wake-up signal for the writer thread. Capacity 1: a pending wake-up
+    // already covers everything queued since.
+    let (wake_tx, wake_rx) = mpsc::sync_channel::<()>(1);
+
     // Clone store and clients for delta processor
     let store_processor = Arc::clone(&store);
+    let store_recovery = config.store_poison_recovery;
     let clients_processor: WsClients = Arc::clone(&ws_clients);
 
     // Spawn delta processor thread
@@ -171,55 +279,43 @@ fn main() -> Result<()> {
             info!("Delta processor started");
             while let Ok(delta) = delta_rx.recv() {
                 // Apply delta to store
-                if let Ok(mut store) = store_processor.lock() {
-                    store.apply_delta(&delta);
-                }
-
-                // Broadcast delta to subscribed WebSocket clients with throttling
-                if let Ok(json) = serde_json::to_string(&delta) {
-                    if let Ok(mut clients) = clients_processor.lock() {
-                        // Collect failed client IDs for removal
-                        let mut failed_clients = Vec::new();
-
-                        for (client_id, client_state) in clients.iter_mut() {
-                            // Check subscription filter with throttling
-                            let matched_indices =
-                                should_send_delta_throttled(&client_state.subscription, &delta);
-
-                            // Skip if no patterns matched (either not subscribed or throttled)
-                            if matched_indices.is_empty() {
-                                continue;
-                            }
-
-                            // Send the delta
-                            if let Err(e) = client_state
-                                .sender
-                                .send(FrameType::Text(false), json.as_bytes())
-                            {
-                                warn!("Failed to send delta to client {}: {:?}", client_id, e);
-                                failed_clients.push(*client_id);
-                            } else {
-                                // Mark matched patterns as sent (update throttle timers)
-                                for idx in matched_indices {
-                                    client_state.subscription.mark_sent(idx);
-                                }
-                            }
-                        }
+                lock_store(&store_processor, store_recovery).apply_delta(&delta);
 
-                        // Remove failed clients
-                        for client_id in failed_clients {
-                            clients.remove(&client_id);
-                            info!("Removed disconnected client {}", client_id);
-                        }
-                    }
+                // Queue delta for subscribed WebSocket clients with throttling.
+                // Never blocks on a socket - the writer thread does the sending.
+                let mut queued = false;
+                for client_state in lock_recovering(&clients_processor, "Clients").values_mut() {
+                    queued |= queue_delta_throttled(client_state, &delta);
+                }
+                if queued {
+                    let _ = wake_tx.try_send(());
                 }
             }
             warn!("Delta processor stopped");
         })
         .expect("Failed to spawn delta processor thread");
 
+    // Spawn WebSocket writer thread
+    let clients_writer: WsClients = Arc::clone(&ws_clients);
+    std::thread::Builder::new()
+        .name("ws-writer".into())
+        .stack_size(16 * 1024) // 16KB - must match CONFIG_PTHREAD_STACK_MIN
+        .spawn(move || {
+            info!("WebSocket writer started");
+            while wake_rx.recv().is_ok() {
+                flush_outboxes(&clients_writer);
+            }
+            warn!("WebSocket writer stopped");
+        })
+        .expect("Failed to spawn WebSocket writer thread");
+
     // Start HTTP server with WebSocket support
-    let _server = start_http_server(&config, Arc::clone(&store), Arc::clone(&ws_clients))?;
+    let _server = start_http_server(
+        &config,
+        Arc::clone(&store),
+        Arc::clone(&ws_clients),
+        Arc::clone(&storage),
+    )?;
 
     // Start demo data generator
     let delta_tx_demo = delta_tx.clone();
@@ -231,6 +327,17 @@ fn main() -> Result<()> {
         })
         .expect("Failed to spawn demo generator thread");
 
+    // Publish free heap as signalk.server.freeHeap and warn when it runs low
+    spawn_heap_monitor(
+        HeapMonitorConfig {
+            interval: Duration::from_secs(HEAP_MONITOR_INTERVAL_SECS),
+            low_threshold: HEAP_LOW_THRESHOLD,
+            ..Default::default()
+        },
+        delta_tx.clone(),
+    )
+    .expect("Failed to spawn heap monitor thread");
+
     info!("========================================");
     info!("          Server Ready!");
     info!("========================================");
@@ -251,6 +358,7 @@ fn start_http_server(
     config: &ServerConfig,
     store: Arc<Mutex<MemoryStore>>,
     ws_clients: WsClients,
+    storage: Arc<NvsConfigStorage<EspDefaultNvs>>,
 ) -> Result<EspHttpServer<'static>> {
     let http_config = HttpConfig {
         http_port: config.http_port,
@@ -266,13 +374,16 @@ fn start_http_server(
     let config_version = config.version.clone();
     let config_self_urn = config.self_urn.clone();
     let config_port = config.http_port;
+    let store_recovery = config.store_poison_recovery;
 
     // Discovery endpoint: GET /signalk
     server.fn_handler("/signalk", esp_idf_svc::http::Method::Get, move |req| {
-        // Get local IP from the request
-        let host = req.host().unwrap_or("localhost");
-
-        let json = create_discovery_json(host, config_port)?;
+        // Advertise the address (and scheme) the client used to reach us
+        let json = create_discovery_json(
+            req.host(),
+            req.header("X-Forwarded-Proto"),
+            config_port,
+        )?;
 
         let mut response = req.into_ok_response()?;
         response.write_all(json.as_bytes())?;
@@ -285,11 +396,7 @@ fn start_http_server(
         "/signalk/v1/api",
         esp_idf_svc::http::Method::Get,
         move |req| {
-            let json = if let Ok(store) = api_store.lock() {
-                serde_json::to_string(store.full_model())?
-            } else {
-                r#"{"error": "Store locked"}"#.to_string()
-            };
+            let json = serde_json::to_string(lock_store(&api_store, store_recovery).full_model())?;
 
             let mut response = req.into_ok_response()?;
             response.write_all(json.as_bytes())?;
@@ -306,20 +413,14 @@ fn start_http_server(
         move |req| {
             // Extract path after /signalk/v1/api/
             let uri = req.uri();
-            let path = uri
-                .strip_prefix("/signalk/v1/api/")
-                .unwrap_or("")
-                .split('?')
-                .next()
-                .unwrap_or("");
+            let rest = uri.strip_prefix("/signalk/v1/api/").unwrap_or("");
+            let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
 
             if path.is_empty() {
                 // Should have been handled by the exact route above
-                let json = if let Ok(store) = api_path_store.lock() {
-                    serde_json::to_string(store.full_model())?
-                } else {
-                    r#"{"error": "Store locked"}"#.to_string()
-                };
+                let json = serde_json::to_string(
+                    lock_store(&api_path_store, store_recovery).full_model(),
+                )?;
                 let mut response = req.into_ok_response()?;
                 response.write_all(json.as_bytes())?;
                 return Ok::<(), SignalKError>(());
@@ -328,14 +429,16 @@ fn start_http_server(
             // Convert URL path (with /) to SignalK path (with .)
             let sk_path = path.replace('/', ".");
 
-            match get_path_json(&api_path_store, &sk_path) {
+            let query = ApiPathQuery::parse(query);
+
+            match get_path_json(&api_path_store, &sk_path, &query, store_recovery) {
                 Ok(json) => {
                     let mut response = req.into_ok_response()?;
                     response.write_all(json.as_bytes())?;
                 }
-                Err(_) => {
-                    // Return 404 for unknown paths
-                    let error_json = format!(r#"{{"error": "Path not found: {}"}}"#, sk_path);
+                Err(message) => {
+                    // Return 404 for unknown paths and sources
+                    let error_json = json!({ "error": message }).to_string();
                     let mut response = req.into_response(404, Some("Not Found"), &[])?;
                     response.write_all(error_json.as_bytes())?;
                 }
@@ -345,6 +448,56 @@ fn start_http_server(
         },
     )?;
 
+    // Config API: GET/PUT /skServer/vessel and /skServer/settings (stored in NVS)
+    for resource in [ConfigResource::Vessel, ConfigResource::Settings] {
+        let get_storage = Arc::clone(&storage);
+        server.fn_handler(resource.uri(), esp_idf_svc::http::Method::Get, move |req| {
+            let mut response =
+                req.into_response(200, Some("OK"), &[("Content-Type", "application/json")])?;
+            // Stream straight into the response to keep the handler stack small
+            if let Err(e) = write_config(get_storage.as_ref(), resource, StdWriter(&mut response)) {
+                error!("Failed to read {}: {}", resource.uri(), e);
+            }
+            Ok::<(), SignalKError>(())
+        })?;
+
+        let put_storage = Arc::clone(&storage);
+        server.fn_handler(
+            resource.uri(),
+            esp_idf_svc::http::Method::Put,
+            move |mut req| {
+                let len = req.content_len().unwrap_or(0) as usize;
+                if len > MAX_CONFIG_BODY_LEN {
+                    let mut response = req.into_response(413, Some("Payload Too Large"), &[])?;
+                    response.write_all(br#"{"error": "Body too large"}"#)?;
+                    return Ok::<(), SignalKError>(());
+                }
+
+                let mut body = vec![0u8; len];
+                if let Err(e) = req.read_exact(&mut body) {
+                    warn!("Failed to read {} body: {:?}", resource.uri(), e);
+                    let mut response = req.into_response(400, Some("Bad Request"), &[])?;
+                    response.write_all(br#"{"error": "Incomplete body"}"#)?;
+                    return Ok::<(), SignalKError>(());
+                }
+
+                match update_config(put_storage.as_ref(), resource, &body) {
+                    Ok(()) => {
+                        info!("Updated {}", resource.uri());
+                        req.into_ok_response()?;
+                    }
+                    Err(e) => {
+                        warn!("Rejected {} update: {}", resource.uri(), e);
+                        let error_json = serde_json::to_string(&json!({ "error": e.to_string() }))?;
+                        let mut response = req.into_response(error_status(&e), None, &[])?;
+                        response.write_all(error_json.as_bytes())?;
+                    }
+                }
+                Ok::<(), SignalKError>(())
+            },
+        )?;
+    }
+
     // WebSocket endpoint: GET /signalk/v1/stream
     let ws_name = config_name.clone();
     let ws_version = config_version.clone();
@@ -391,20 +544,20 @@ fn start_http_server(
             // This allows the delta processor thread to push updates to this client
             match ws.create_detached_sender() {
                 Ok(sender) => {
-                    if let Ok(mut clients) = ws_clients_handler.lock() {
-                        clients.insert(
-                            client_id,
-                            ClientState {
-                                sender,
-                                subscription,
-                            },
-                        );
-                        info!(
-                            "Registered client {} for delta streaming ({} total)",
-                            client_id,
-                            clients.len()
-                        );
-                    }
+                    let mut clients = lock_recovering(&ws_clients_handler, "Clients");
+                    clients.insert(
+                        client_id,
+                        ClientState {
+                            sender,
+                            subscription,
+                            outbox: ClientOutbox::new(WS_OUTBOX_CAPACITY),
+                        },
+                    );
+                    info!(
+                        "Registered client {} for delta streaming ({} total)",
+                        client_id,
+                        clients.len()
+                    );
                 }
                 Err(e) => {
                     error!(
@@ -421,14 +574,13 @@ fn start_http_server(
         // Handle closed connection
         if ws.is_closed() {
             // Remove client from broadcast list
-            if let Ok(mut clients) = ws_clients_handler.lock() {
-                clients.remove(&client_id);
-                info!(
-                    "WebSocket client {} disconnected ({} remaining)",
-                    client_id,
-                    clients.len()
-                );
-            }
+            let mut clients = lock_recovering(&ws_clients_handler, "Clients");
+            clients.remove(&client_id);
+            info!(
+                "WebSocket client {} disconnected ({} remaining)",
+                client_id,
+                clients.len()
+            );
             return Ok::<(), SignalKError>(());
         }
 
@@ -453,19 +605,18 @@ fn start_http_server(
                     info!("Received from client {}: {}", client_id, text);
 
                     // Try to parse and process subscription messages
-                    if let Ok(mut clients) = ws_clients_handler.lock() {
-                        if let Some(client_state) = clients.get_mut(&client_id) {
-                            if let Some(new_sub) =
-                                process_client_message(text, &client_state.subscription)
-                            {
-                                info!(
-                                    "Client {} subscription updated: context={:?}, patterns={}",
-                                    client_id,
-                                    new_sub.context,
-                                    new_sub.patterns.len()
-                                );
-                                client_state.subscription = new_sub;
-                            }
+                    let mut clients = lock_recovering(&ws_clients_handler, "Clients");
+                    if let Some(client_state) = clients.get_mut(&client_id) {
+                        if let Some(new_sub) =
+                            process_client_message(text, &client_state.subscription)
+                        {
+                            info!(
+                                "Client {} subscription updated: context={:?}, patterns={}",
+                                client_id,
+                                new_sub.context,
+                                new_sub.patterns.len()
+                            );
+                            client_state.subscription = new_sub;
                         }
                     }
                 }
@@ -473,9 +624,7 @@ fn start_http_server(
             FrameType::Close => {
                 info!("WebSocket close frame received from client {}", client_id);
                 // Remove client from broadcast list
-                if let Ok(mut clients) = ws_clients_handler.lock() {
-                    clients.remove(&client_id);
-                }
+                lock_recovering(&ws_clients_handler, "Clients").remove(&client_id);
             }
             _ => {}
         }
@@ -531,6 +680,7 @@ fn generate_demo_data(delta_tx: mpsc::Sender<Delta>) {
                     },
                 ],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -1,33 +1,112 @@
-use axum::extract::ws::{Message, WebSocket};
+use anyhow::Context as _;
 use axum::{
-    extract::{Path, Query, State, WebSocketUpgrade},
-    http::StatusCode,
-    response::{IntoResponse, Json},
+    extract::{ConnectInfo, Extension, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
     routing::get,
     Router,
 };
-use futures::{sink::SinkExt, stream::StreamExt};
+use futures::{
+    sink::SinkExt,
+    stream::{SplitSink, StreamExt},
+};
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
 use serde::Deserialize;
-use signalk_core::{Delta, MemoryStore, PathValue, SignalKStore, Update};
-use signalk_server::{ServerConfig, ServerEvent};
+use signalk_core::{
+    effective_config, full_fragment, to_display_units, zone_notifications, ConfigError,
+    ConfigStorage, Delta, DeltaSink, FileConfigStorage, MemoryStore, PathAcl, PathValue,
+    Permission, PositionCoalescer, SecurityConfig, SelfUrn, SentinelFilter, ServerSettings,
+    SharedStore, SignalKStore, StoreSnapshot, UnitSystem, Update, VesselInfo,
+};
+use signalk_plugins::{discover_plugins, DenoLauncher, PluginConfigStore, PluginManager};
+use signalk_protocol::{
+    decode_client_message, ClientMessage, DiscoveryResponse, DiscoveryServer, PutRequest,
+    PutResponse,
+};
+use signalk_providers::{
+    build_provider, DemoConfig, DemoScenario, DerivedEngine, DerivedRule, Provider, ProviderConfig,
+    TcpProvider, UdpProvider,
+};
+use signalk_server::{
+    chronological_order, log_subscription_event, negotiate_deflate, reject_oversized,
+    run_snapshots, shutdown_close_frame, shutdown_requested, DeltaCoalescer, EventSink, IdleTimer,
+    InflateStream, LagPolicy, MessageDeflater, OutboundDedup, ProviderRegistry, ProviderState,
+    SelfContext, ServerConfig, ServerEvent, SubscriptionManager, SUBSCRIPTION_DEBUG_KEY,
+    SUBSCRIPTION_LOG_TARGET,
+};
+use signalk_web::mdns::DEFAULT_MDNS_HOSTNAME;
+use signalk_web::routes::auth;
+use signalk_web::routes::backup::{self, DebugRequest};
+use signalk_web::routes::history::HistoryParams;
+use signalk_web::routes::plugins::{list_plugins, plugin_response, Plugin, PluginConfig};
 use signalk_web::{
-    DebugSettings, LoginStatus, ServerEvent as WebServerEvent, ServerStatistics, SourcePriorities,
+    discovery_for_headers, enforce_permissions, put_request, put_self_path, request_status,
+    select_leaf, ApiJson, ClientConnection, ClientHandle, DebugControl, DebugSettings,
+    HistoryStore, HistoryValues, LoginStatus, MdnsAdvertiser, ProviderStatus as WebProviderStatus,
+    PutBody, ResolvedPermission, Resources, ServerEvent as WebServerEvent, ServerStatistics,
     VesselInfoData, WebConfig, WebState,
 };
+use std::borrow::Cow;
 use std::net::SocketAddr;
+use std::path::{Path as FsPath, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock};
+use std::time::Instant;
+use tokio::sync::{broadcast, mpsc, watch, Notify, RwLock};
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tower::Service as _;
 use tower_http::services::ServeDir;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{
+    layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+/// An upgraded `/signalk/v1/stream` connection.
+type WebSocket = WebSocketStream<InflateStream<TokioIo<Upgraded>>>;
+
+/// A delta as broadcast to WebSocket connections, serialized once by the
+/// delta processor so each connection doesn't serialize it again.
+#[derive(Clone)]
+struct BroadcastDelta {
+    delta: Arc<Delta>,
+    encoded: Arc<str>,
+}
 
-type SharedStore = Arc<RwLock<MemoryStore>>;
+impl BroadcastDelta {
+    fn new(delta: Delta) -> Self {
+        let encoded = serde_json::to_string(&delta).unwrap_or_default();
+        Self {
+            delta: Arc::new(delta),
+            encoded: encoded.into(),
+        }
+    }
+}
 
 #[derive(Clone)]
 struct AppState {
-    store: SharedStore,
-    delta_tx: broadcast::Sender<Delta>,
+    store: Arc<SharedStore>,
+    delta_tx: broadcast::Sender<BroadcastDelta>,
     config: ServerConfig,
+    /// Stored settings after environment overrides.
+    settings: ServerSettings,
     web_state: Arc<WebState>,
+    /// Current self URN, which `config.self_urn` only starts out as.
+    self_context: SelfContext,
+    /// Compiled `config.acl_rules`, checked against each client's
+    /// resolved permission.
+    acl: Arc<PathAcl>,
+    /// Connection state of the NMEA providers.
+    providers: Arc<ProviderRegistry>,
+    /// Where settings and vessel info are saved; `None` if `~/.signalk`
+    /// is unusable, leaving only the cache in `web_state`.
+    storage: Option<FileConfigStorage>,
+    /// Set on Ctrl+C. Each WebSocket connection holds a receiver until it
+    /// has closed, so the sender sees when they are all gone.
+    shutdown: Arc<watch::Sender<bool>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,34 +119,335 @@ struct StreamQuery {
     send_cached_values: Option<bool>,
     #[serde(rename = "sendMeta", default)]
     send_meta: Option<String>,
+    /// `full` sends every delta as a full-format tree fragment.
+    #[serde(default)]
+    format: Option<String>,
+    /// Leave out values repeated within this many milliseconds.
+    #[serde(default)]
+    dedup: Option<u64>,
+}
+
+/// Query parameters shared by the data API endpoints.
+#[derive(Debug, Default, Deserialize)]
+struct ApiQuery {
+    /// Indent the JSON output (`?pretty=true`).
+    #[serde(default)]
+    pretty: bool,
+    /// Scope the full model to one context (`?context=vessels.self`).
+    #[serde(default)]
+    context: Option<String>,
+    /// Limit the full model to N levels of nesting (`?depth=N`).
+    #[serde(default)]
+    depth: Option<usize>,
+    /// Return only the node's value, without `$source`/`timestamp`
+    /// (`?value=true`). A trailing `/value` segment works too.
+    #[serde(default)]
+    value: bool,
+    /// Return a delta of the values under a context that changed after
+    /// this ISO 8601 timestamp (`?since=2024-01-17T10:00:00Z`).
+    #[serde(default)]
+    since: Option<String>,
+    /// Return the value reported by one source instead of the primary one
+    /// (`?source=<ref>`).
+    #[serde(default)]
+    source: Option<String>,
+    /// Include the path's metadata with a `?source=` selection
+    /// (`?meta=true`).
+    #[serde(default)]
+    meta: bool,
+    /// Convert values with a known `meta.units` to display units, e.g.
+    /// m/s to knots (`?units=display`).
+    #[serde(default)]
+    units: UnitSystem,
+}
+
+/// Log filter used without `RUST_LOG`. Subscription events stay quiet
+/// unless their debug key is enabled.
+const DEFAULT_LOG_FILTER: &str = "info,signalk_server=debug,signalk_server::subscriptions=info";
+
+/// Debug keys offered to the Admin UI, see [`debug_targets`].
+const DEBUG_KEYS: [&str; 4] = [
+    "signalk-server:*",
+    "signalk-server:interfaces:*",
+    "signalk-server:providers:*",
+    SUBSCRIPTION_DEBUG_KEY,
+];
+
+/// The `tracing` targets a debug key logs at debug level.
+fn debug_targets(key: &str) -> &'static [&'static str] {
+    match key {
+        "signalk-server:*" => &[
+            "signalk_server",
+            SUBSCRIPTION_LOG_TARGET,
+            "signalk_providers",
+            "signalk_web",
+        ],
+        "signalk-server:interfaces:*" => &["signalk_protocol", "signalk_server::server"],
+        "signalk-server:providers:*" => &["signalk_providers"],
+        SUBSCRIPTION_DEBUG_KEY => &[SUBSCRIPTION_LOG_TARGET],
+        _ => &[],
+    }
+}
+
+/// The log filter: the base directives, with those of the enabled debug
+/// keys after them, reloaded when the keys change.
+struct LogFilter {
+    base: String,
+    enabled: std::sync::Mutex<Vec<String>>,
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogFilter {
+    /// A filter layer with `base` directives and its control.
+    fn new(base: &str) -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new(base));
+        let filter = Self {
+            base: base.to_string(),
+            enabled: std::sync::Mutex::new(Vec::new()),
+            handle,
+        };
+        (layer, filter)
+    }
+}
+
+impl DebugControl for LogFilter {
+    fn keys(&self) -> Vec<String> {
+        DEBUG_KEYS.map(String::from).to_vec()
+    }
+
+    fn enabled(&self) -> Vec<String> {
+        self.enabled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn set_enabled(&self, keys: &[String]) -> Result<(), String> {
+        // Later directives for the same target win over the base ones
+        let directives = keys
+            .iter()
+            .flat_map(|key| debug_targets(key))
+            .map(|target| format!(",{target}=debug"));
+        let filter = std::iter::once(self.base.clone())
+            .chain(directives)
+            .collect::<String>();
+        let filter = EnvFilter::try_new(filter).map_err(|e| e.to_string())?;
+        self.handle.reload(filter).map_err(|e| e.to_string())?;
+        *self.enabled.lock().unwrap_or_else(|e| e.into_inner()) = keys.to_vec();
+        tracing::info!("Debug keys enabled: {:?}", keys);
+        Ok(())
+    }
+}
+
+/// Open the `~/.signalk` config directory.
+fn config_storage() -> Result<FileConfigStorage, ConfigError> {
+    FileConfigStorage::default_dir()
+        .ok_or_else(|| ConfigError::StorageUnavailable("HOME not set".into()))
+        .and_then(FileConfigStorage::new)
+}
+
+/// Manage the plugins installed in `~/.signalk/plugins`, sending their
+/// deltas to `events`.
+fn plugin_manager(events: mpsc::Sender<ServerEvent>) -> PluginManager {
+    let plugins = FileConfigStorage::default_dir()
+        .map(|dir| discover_plugins(&dir.join("plugins")))
+        .unwrap_or_default();
+    let storage = config_storage()
+        .map_err(|e| tracing::warn!("Plugin settings won't be saved: {e}"))
+        .ok()
+        .map(|storage| Arc::new(storage) as Arc<dyn PluginConfigStore>);
+    PluginManager::new(plugins, Arc::new(DenoLauncher::new(events)), storage)
+}
+
+/// Load the persistent self URN, generating it on first start.
+///
+/// Falls back to a fresh (non-persisted) URN if the config directory is
+/// unusable, so the server still starts.
+fn load_self_urn() -> SelfUrn {
+    match config_storage().and_then(|storage| SelfUrn::load_or_generate(&storage)) {
+        Ok(urn) => urn,
+        Err(e) => {
+            tracing::warn!("Could not load persistent vessel UUID, using a temporary one: {e}");
+            SelfUrn::generate()
+        }
+    }
+}
+
+/// Load server settings, using defaults if none are stored yet.
+fn load_settings() -> ServerSettings {
+    match config_storage().and_then(|storage| storage.load_settings()) {
+        Ok(settings) => settings,
+        Err(ConfigError::NotFound(_)) => ServerSettings::default(),
+        Err(e) => {
+            tracing::warn!("Could not load server settings, using defaults: {e}");
+            ServerSettings::default()
+        }
+    }
+}
+
+/// Load users and per-path ACL rules from `~/.signalk/security.json`, if
+/// present; without users, security is off.
+fn load_security() -> SecurityConfig {
+    match config_storage().and_then(|storage| storage.load_security()) {
+        Ok(security) => security,
+        Err(ConfigError::NotFound(_)) => SecurityConfig::default(),
+        Err(e) => {
+            tracing::warn!("Could not load security config, no users or path ACLs: {e}");
+            SecurityConfig::default()
+        }
+    }
+}
+
+/// Load derived-value rules from `~/.signalk/derived.json`, if present.
+///
+/// Missing or invalid rules leave derivation off rather than stopping the
+/// server.
+fn load_derived_engine() -> DerivedEngine {
+    let rules = match config_storage()
+        .and_then(|storage| storage.load_value::<Vec<DerivedRule>>(DerivedRule::STORAGE_KEY))
+    {
+        Ok(rules) => rules,
+        Err(ConfigError::NotFound(_)) => Vec::new(),
+        Err(e) => {
+            tracing::warn!("Could not load derived rules: {e}");
+            Vec::new()
+        }
+    };
+    DerivedEngine::new(&rules).unwrap_or_else(|e| {
+        tracing::warn!("{e}, derived values disabled");
+        DerivedEngine::default()
+    })
+}
+
+/// Load provider configs from `~/.signalk/providers.json`, if present.
+fn load_provider_configs() -> Vec<ProviderConfig> {
+    match config_storage()
+        .and_then(|storage| storage.load_value::<Vec<ProviderConfig>>(ProviderConfig::STORAGE_KEY))
+    {
+        Ok(configs) => configs,
+        Err(ConfigError::NotFound(_)) => Vec::new(),
+        Err(e) => {
+            tracing::warn!("Could not load provider configs: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Load the demo data settings from `~/.signalk/demo.json`, if present.
+fn load_demo_config() -> DemoConfig {
+    match config_storage()
+        .and_then(|storage| storage.load_value::<DemoConfig>(DemoConfig::STORAGE_KEY))
+    {
+        Ok(config) => config,
+        Err(ConfigError::NotFound(_)) => DemoConfig::default(),
+        Err(e) => {
+            tracing::warn!("Could not load demo settings: {e}");
+            DemoConfig::default()
+        }
+    }
+}
+
+/// Restore the store from `~/.signalk/store.snapshot`, if present, so the
+/// server comes back with the state it had before a restart.
+fn restore_snapshot(store: &mut MemoryStore) {
+    let snapshot = match config_storage()
+        .and_then(|storage| storage.load_value::<StoreSnapshot>(StoreSnapshot::STORAGE_KEY))
+    {
+        Ok(snapshot) => snapshot,
+        Err(ConfigError::NotFound(_)) => return,
+        Err(e) => {
+            tracing::warn!("Could not load store snapshot: {e}");
+            return;
+        }
+    };
+    match store.restore(snapshot) {
+        Ok(()) => tracing::info!("Restored store snapshot ({} paths)", store.path_count()),
+        Err(e) => tracing::warn!("Could not restore store snapshot: {e}"),
+    }
+}
+
+/// Provider statuses in the form the Admin UI Connections page reads.
+fn web_provider_statuses(providers: &ProviderRegistry) -> Vec<WebProviderStatus> {
+    providers
+        .provider_statuses()
+        .into_iter()
+        .map(|status| WebProviderStatus {
+            connected: status.is_connected(),
+            error: match status.state {
+                ProviderState::Error(e) => Some(e),
+                _ => None,
+            },
+            last_message: status
+                .last_message
+                .map(|at| at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+            id: status.id,
+            provider_type: status.provider_type,
+        })
+        .collect()
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
+    // Initialize tracing; debug keys add to the filter at runtime
+    let base_filter = std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_LOG_FILTER.into());
+    let (filter_layer, log_filter) = LogFilter::new(&base_filter);
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info,signalk_server=debug".into()),
-        ))
+        .with(filter_layer)
         .with(tracing_subscriber::fmt::layer())
         .init();
 
     tracing::info!("SignalK Server starting...");
 
-    // Configuration - single port for everything
-    let addr: SocketAddr = "0.0.0.0:4000".parse()?;
+    // Vessel identity is minted on first start and kept in ~/.signalk/uuid
+    let self_urn = load_self_urn();
+    tracing::info!("Self URN: {}", self_urn);
+    let settings = load_settings().with_env_overrides(|name| std::env::var(name).ok());
+    let security = load_security();
+
+    // Data and admin routes share one listener unless `adminAddress` is set
+    let addr = settings.data_addr();
 
     let config = ServerConfig {
         name: "signalk-server-rust".to_string(),
         version: "1.7.0".to_string(),
         bind_addr: addr,
         // self_urn must include "vessels." prefix per Signal K spec
-        self_urn: "vessels.urn:mrn:signalk:uuid:c0d79334-4e25-4245-8892-54e8ccc8021d".to_string(),
+        self_urn: self_urn.context(),
+        writable_paths: settings.writable_paths(),
+        merge_paths: settings.merge_paths(),
+        source_priorities: settings.source_priorities.clone().unwrap_or_default(),
+        acl_rules: security.acls.clone().unwrap_or_default(),
+        ws_compression_threshold: settings.ws_compression_threshold(),
+        // A client that fell behind reconnects and starts over, rather than
+        // silently missing deltas
+        lag_policy: LagPolicy::DisconnectClient,
+        ..Default::default()
     };
 
     // Create server components
-    let store = Arc::new(RwLock::new(MemoryStore::new(&config.self_urn)));
-    let (delta_tx, _delta_rx) = broadcast::channel::<Delta>(1024);
+    let mut store = MemoryStore::new(&config.self_urn);
+    // Retained values per path for `<path>/stats`
+    store.set_history_len(600);
+    if let Err(e) = store.set_merge_paths(&config.merge_paths) {
+        tracing::warn!("Invalid merge path pattern, using defaults: {}", e);
+    }
+    if let Err(e) = store.set_source_priorities(&config.source_priorities) {
+        tracing::warn!(
+            "Invalid source priority pattern, using the latest source: {}",
+            e
+        );
+    }
+    restore_snapshot(&mut store);
+    let store = Arc::new(SharedStore::new(store));
+    match (settings.snapshot_interval(), config_storage()) {
+        (Some(interval), Ok(storage)) => {
+            tokio::spawn(run_snapshots(store.clone(), storage, interval));
+        }
+        (Some(_), Err(e)) => tracing::warn!("Store snapshots disabled: {e}"),
+        (None, _) => {}
+    }
+    let (delta_tx, _delta_rx) =
+        broadcast::channel::<BroadcastDelta>(config.broadcast_capacity.max(1));
     let (event_tx, mut event_rx) = tokio::sync::mpsc::channel::<ServerEvent>(1024);
 
     // Create web state for Admin UI
@@ -76,78 +456,268 @@ async fn main() -> anyhow::Result<()> {
         version: config.version.clone(),
         self_urn: config.self_urn.clone(),
     };
-    let web_state = Arc::new(WebState::new(store.clone(), web_config));
+    let mut web_state = WebState::new(store.clone(), web_config);
+    web_state.plugins = Arc::new(plugin_manager(event_tx.clone()));
+    // v2 course changes go out like any other delta
+    web_state.delta_sink = Some(Arc::new(EventSink::new(event_tx.clone())));
+    web_state.debug = Some(Arc::new(log_filter));
+    match config_storage() {
+        Ok(storage) => web_state.resources = Resources::new(Arc::new(storage)),
+        Err(e) => tracing::warn!("Routes and waypoints won't be saved: {e}"),
+    }
+    let web_state = Arc::new(web_state);
+    let plugins = web_state.plugins.clone();
+    tokio::spawn(async move { plugins.start_enabled().await });
+
+    // Followed by every connection, so a self URN change reaches them all
+    let self_context = SelfContext::new(&config.self_urn);
 
     // Clone for processors
+    let self_context_clone = self_context.clone();
     let store_clone = store.clone();
     let delta_tx_clone = delta_tx.clone();
     let web_state_clone = web_state.clone();
 
+    let chronological_batches = config.chronological_batches;
+    let notification_methods = config.notification_methods.clone();
+    let treat_all_contexts_as_self = config.treat_all_contexts_as_self;
+    let max_value_bytes = config.max_value_bytes;
+    let mut coalescer = config
+        .coalesce_position_ms
+        .map(|ms| PositionCoalescer::new(std::time::Duration::from_millis(ms)));
+    let sentinels = SentinelFilter::new(&config.sentinel_rules).unwrap_or_else(|e| {
+        tracing::warn!("Invalid sentinel rule pattern, filtering disabled: {}", e);
+        SentinelFilter::default()
+    });
+    let mut derived = load_derived_engine();
+
     // Spawn delta processor
     tokio::spawn(async move {
         while let Some(event) = event_rx.recv().await {
-            match event {
-                ServerEvent::DeltaReceived(delta) => {
-                    // Record in statistics
-                    web_state_clone.statistics.record_delta();
-
-                    // Store delta
-                    {
-                        let mut st = store_clone.write().await;
-                        st.apply_delta(&delta);
-
-                        // Update path count
-                        web_state_clone.statistics.set_active_paths(st.path_count());
+            let deltas = match event {
+                ServerEvent::DeltaReceived(delta) => vec![delta],
+                ServerEvent::DeltaBatch(batch) if chronological_batches => {
+                    chronological_order(batch)
+                }
+                ServerEvent::DeltaBatch(batch) => batch,
+                ServerEvent::SelfUrnChanged(self_urn) => {
+                    tracing::info!("Self URN changed to {}", self_urn);
+                    store_clone.write().set_self_urn(&self_urn);
+                    self_context_clone.set(&self_urn);
+                    continue;
+                }
+            };
+
+            // Derived deltas are queued behind the delta they came from
+            let mut queue = std::collections::VecDeque::from(deltas);
+            while let Some(mut delta) = queue.pop_front() {
+                if treat_all_contexts_as_self {
+                    delta.promote_to_self();
+                }
+                let delta = match &mut coalescer {
+                    Some(coalescer) => coalescer.coalesce(delta, std::time::Instant::now()),
+                    None => Some(delta),
+                };
+                let Some(delta) = delta else {
+                    continue;
+                };
+                let Some(delta) = sentinels
+                    .filter(delta)
+                    .and_then(|delta| reject_oversized(delta, max_value_bytes))
+                else {
+                    web_state_clone.statistics.record_dropped();
+                    continue;
+                };
+
+                // Record in statistics
+                web_state_clone.statistics.record_delta(&delta);
+                web_state_clone
+                    .history
+                    .record_delta(&delta, &self_context_clone.get());
+                queue.extend(derived.process(&delta));
+
+                // Store delta, plus any notifications its zones raise
+                let notifications = {
+                    let mut st = store_clone.write();
+                    st.apply_delta(&delta);
+                    let notifications = zone_notifications(&st, &delta, &notification_methods);
+                    if let Some(notifications) = &notifications {
+                        st.apply_delta(notifications);
                     }
-                    // Broadcast to WebSocket clients
-                    let _ = delta_tx_clone.send(delta);
+
+                    // Update path count
+                    web_state_clone.statistics.set_active_paths(st.path_count());
+                    notifications
+                };
+                // Broadcast to WebSocket clients
+                let _ = delta_tx_clone.send(BroadcastDelta::new(delta));
+                if let Some(notifications) = notifications {
+                    let _ = delta_tx_clone.send(BroadcastDelta::new(notifications));
                 }
             }
         }
     });
 
-    // Spawn statistics broadcaster (1 Hz)
+    // Spawn statistics broadcaster (1 Hz), which also prunes stale values
+    // and sends provider status changes
+    let providers = Arc::new(ProviderRegistry::new());
+    let providers_stats = providers.clone();
     let web_state_stats = web_state.clone();
+    let prune_store = store.clone();
+    let prune_after = settings.prune_after();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+        // Pruning walks the whole store, so once a minute is plenty
+        let mut prune_interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
         loop {
-            interval.tick().await;
-
-            // Update rate calculation
-            web_state_stats.statistics.update_rate();
-
-            // Broadcast statistics to admin UI clients
-            let stats = web_state_stats.statistics.snapshot();
-            web_state_stats.broadcast_event(WebServerEvent::ServerStatistics {
-                from: "signalk-server".to_string(),
-                data: stats,
-            });
+            tokio::select! {
+                _ = interval.tick() => {
+                    // Update rate calculation
+                    web_state_stats.statistics.update_rate();
+
+                    // Broadcast statistics to admin UI clients
+                    let stats = web_state_stats.statistics.snapshot_detailed();
+                    web_state_stats.broadcast_event(WebServerEvent::ServerStatistics {
+                        from: "signalk-server".to_string(),
+                        data: stats,
+                    });
+                    if providers_stats.poll_changes() {
+                        web_state_stats.broadcast_event(WebServerEvent::ProviderStatus {
+                            from: "signalk-server".to_string(),
+                            data: web_provider_statuses(&providers_stats),
+                        });
+                    }
+                }
+                _ = prune_interval.tick(), if prune_after.is_some() => {
+                    let Some(older_than) = prune_after else { continue };
+                    let mut store = prune_store.write();
+                    let pruned = store.prune_stale(older_than, chrono::Utc::now());
+                    if pruned > 0 {
+                        tracing::debug!("Pruned {} stale values", pruned);
+                        web_state_stats.statistics.set_active_paths(store.path_count());
+                    }
+                }
+            }
         }
     });
 
+    let nmea0183_tcp_address = settings.nmea0183_tcp_address;
+    let nmea0183_udp_address = settings.nmea0183_udp_address;
+
+    // Let apps on the network find the server
+    let mdns = if settings.mdns_enabled() {
+        let instance_name = config.vessel_name.as_deref().unwrap_or(&config.name);
+        MdnsAdvertiser::advertise(
+            DEFAULT_MDNS_HOSTNAME,
+            instance_name,
+            settings.data_addr().port(),
+            settings.tls_addr().map(|addr| addr.port()),
+            &config.version,
+            &config.self_urn,
+        )
+        .map_err(|e| tracing::warn!("mDNS advertisement disabled: {}", e))
+        .ok()
+    } else {
+        None
+    };
+    let storage = config_storage()
+        .map_err(|e| tracing::warn!("Settings changes won't be saved: {e}"))
+        .ok();
+    *web_state.settings.write().await = settings.clone();
+    *web_state.security.write().await = security;
+    if let Some(vessel) = storage.as_ref().and_then(|s| s.load_vessel().ok()) {
+        *web_state.vessel_info.write().await = vessel;
+    }
+    let shutdown = Arc::new(watch::channel(false).0);
+    // Fail closed, as `SignalKServer` does: only admins get through a
+    // broken ACL
+    let acl = PathAcl::new(&config.acl_rules).unwrap_or_else(|e| {
+        tracing::warn!(
+            "Invalid ACL path pattern, restricting all paths to admins: {}",
+            e
+        );
+        PathAcl::admin_only()
+    });
     let app_state = AppState {
         store,
         delta_tx,
         config: config.clone(),
+        settings,
         web_state,
+        self_context,
+        acl: Arc::new(acl),
+        providers: providers.clone(),
+        storage,
+        shutdown: shutdown.clone(),
+    };
+
+    // A configured certificate that can't be used stops startup, rather
+    // than leaving the server up without the HTTPS it advertises
+    let tls = match app_state.settings.tls_addr() {
+        Some(_) => Some(load_tls_config(&app_state.settings)?),
+        None => None,
     };
 
-    // Start unified HTTP + WebSocket server
+    // Start HTTP + WebSocket server(s)
     let http_handle = tokio::spawn(async move {
-        if let Err(e) = start_unified_server(addr, app_state).await {
+        if let Err(e) = start_http_servers(app_state, tls).await {
             tracing::error!("Server error: {}", e);
         }
     });
 
+    // Read NMEA 0183 from a TCP source alongside the demo data
+    if let Some(addr) = nmea0183_tcp_address {
+        tracing::info!("Reading NMEA 0183 from {}", addr);
+        let sink = providers.register("nmea0183-tcp", "NMEA0183", EventSink::new(event_tx.clone()));
+        TcpProvider::connect(addr, "nmea0183", sink);
+    }
+    if let Some(addr) = nmea0183_udp_address {
+        let sink = providers.register("nmea0183-udp", "NMEA0183", EventSink::new(event_tx.clone()));
+        let reporter = sink.clone();
+        let provider = if addr.ip().is_multicast() {
+            UdpProvider::bind_multicast(addr, "nmea0183", sink).await
+        } else {
+            UdpProvider::bind(addr, "nmea0183", sink).await
+        };
+        match provider {
+            Ok(provider) => {
+                tracing::info!("Receiving NMEA 0183 on UDP {}", addr);
+                tokio::spawn(provider.run());
+            }
+            Err(e) => {
+                tracing::warn!("Could not listen for NMEA 0183 on UDP {}: {}", addr, e);
+                reporter.report_state(ProviderState::Error(e.to_string()));
+            }
+        }
+    }
+
+    // Providers configured from the Admin UI; stopped when dropped
+    let configured_providers: Vec<Box<dyn Provider>> = load_provider_configs()
+        .into_iter()
+        .map(|config| {
+            let mut provider = build_provider(config);
+            tracing::info!("Starting provider {}", provider.id());
+            let sink = providers.register(
+                provider.id(),
+                provider.config().provider_type(),
+                EventSink::new(event_tx.clone()),
+            );
+            provider.start(Arc::new(sink));
+            provider
+        })
+        .collect();
+
     // Start demo data generator
+    let demo = load_demo_config();
     let demo_handle = tokio::spawn(async move {
-        generate_demo_data(event_tx).await;
+        generate_demo_data(event_tx, demo.build_scenario(), demo.update_interval()).await;
     });
 
     tracing::info!("Server ready!");
     tracing::info!("");
     tracing::info!("   Admin UI:    http://localhost:4000/admin/");
     tracing::info!("   REST API:    http://localhost:4000/signalk/v1/api");
+    tracing::info!("   v2 API:      http://localhost:4000/signalk/v2/api");
     tracing::info!("   WebSocket:   ws://localhost:4000/signalk/v1/stream");
     tracing::info!("   Settings:    http://localhost:4000/skServer/settings");
     tracing::info!("");
@@ -166,24 +736,174 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    // Stop accepting connections and close the open WebSockets, giving
+    // them a moment to send what is queued
+    shutdown.send_replace(true);
+    let grace = std::time::Duration::from_millis(config.shutdown_grace_ms);
+    if tokio::time::timeout(grace, shutdown.closed())
+        .await
+        .is_err()
+    {
+        tracing::warn!(
+            "{} connections still open after {:?}, dropping them",
+            shutdown.receiver_count(),
+            grace
+        );
+    }
+
+    drop(configured_providers);
+    if let Some(mdns) = mdns {
+        mdns.shutdown();
+    }
     tracing::info!("Shutdown complete");
     Ok(())
 }
 
-async fn start_unified_server(addr: SocketAddr, state: AppState) -> anyhow::Result<()> {
-    // Serve admin UI from reference implementation
-    let admin_ui_path = "/home/vadian/signalk-server/packages/server-admin-ui/public";
-    let documentation_path = "/home/vadian/signalk-server/public";
+/// Serve the data and admin routes, on one listener or two, and the
+/// data routes over HTTPS too when `tls` is given.
+///
+/// With `adminAddress` set to a different address than the data routes
+/// (e.g. `127.0.0.1:4001`), admin routes are unreachable on the data
+/// listener and vice versa. The HTTPS listener serves what the data
+/// listener does.
+async fn start_http_servers(
+    state: AppState,
+    tls: Option<Arc<rustls::ServerConfig>>,
+) -> anyhow::Result<()> {
+    let data_addr = state.settings.data_addr();
+    let admin_addr = state.settings.admin_addr();
+    let shared = data_addr == admin_addr;
+    let data = data_routes()
+        .nest(
+            "/signalk/v2/api",
+            signalk_web::routes::v2::routes().with_state(state.web_state.clone()),
+        )
+        .merge(login_routes(&state));
+    let data = if shared {
+        data.merge(admin_routes())
+    } else {
+        data
+    };
 
-    // Build router with all routes defined inline
-    let app = Router::new()
+    let data_listener = tokio::net::TcpListener::bind(data_addr).await?;
+    let admin_listener = if shared {
+        tracing::info!("Server listening on {}", data_addr);
+        None
+    } else {
+        tracing::info!("Data routes listening on {}", data_addr);
+        tracing::info!("Admin routes listening on {}", admin_addr);
+        Some(tokio::net::TcpListener::bind(admin_addr).await?)
+    };
+    let tls_listener = match (tls, state.settings.tls_addr()) {
+        (Some(tls), Some(tls_addr)) => {
+            tracing::info!("HTTPS listening on {}", tls_addr);
+            Some((tokio::net::TcpListener::bind(tls_addr).await?, tls))
+        }
+        _ => None,
+    };
+
+    tokio::try_join!(
+        serve(data_listener, data.clone(), state.clone()),
+        async {
+            match admin_listener {
+                Some(listener) => {
+                    let admin = admin_routes().merge(login_routes(&state));
+                    serve(listener, admin, state.clone()).await
+                }
+                None => Ok(()),
+            }
+        },
+        async {
+            match tls_listener {
+                Some((listener, tls)) => serve_tls(listener, data, state.clone(), tls).await,
+                None => Ok(()),
+            }
+        },
+    )?;
+    Ok(())
+}
+
+/// Login and logout under `/signalk/v1/auth`, which both listeners serve.
+fn login_routes(state: &AppState) -> Router<AppState> {
+    Router::new().nest(
+        "/signalk/v1/auth",
+        auth::auth_routes().with_state(state.web_state.clone()),
+    )
+}
+
+/// Load the HTTPS certificate chain and private key named in `settings`,
+/// relative to the config directory.
+fn load_tls_config(settings: &ServerSettings) -> anyhow::Result<Arc<rustls::ServerConfig>> {
+    let dir = FileConfigStorage::default_dir().unwrap_or_default();
+    tls_config_from_files(
+        &dir.join(settings.ssl_cert_file()),
+        &dir.join(settings.ssl_key_file()),
+    )
+}
+
+/// A TLS server configuration from PEM certificate and key files.
+fn tls_config_from_files(
+    cert_path: &FsPath,
+    key_path: &FsPath,
+) -> anyhow::Result<Arc<rustls::ServerConfig>> {
+    let cert_pem = std::fs::read(cert_path)
+        .with_context(|| format!("Could not read SSL certificate {}", cert_path.display()))?;
+    let certs = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid SSL certificate {}", cert_path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found in {}", cert_path.display());
+    }
+
+    let key_pem = std::fs::read(key_path)
+        .with_context(|| format!("Could not read SSL key {}", key_path.display()))?;
+    let key = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .with_context(|| format!("Invalid SSL key {}", key_path.display()))?
+        .with_context(|| format!("No private key found in {}", key_path.display()))?;
+
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .with_context(|| {
+        format!(
+            "SSL key {} doesn't fit certificate {}",
+            key_path.display(),
+            cert_path.display()
+        )
+    })?;
+    // WebSocket upgrades need HTTP/1.1
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Signal K data routes: stream, REST API and discovery.
+fn data_routes() -> Router<AppState> {
+    Router::new()
         // WebSocket endpoint (handles both deltas and server events)
         .route("/signalk/v1/stream", get(websocket_handler))
         // REST API endpoints for SignalK data
         .route("/signalk/v1/api", get(full_api_handler))
-        .route("/signalk/v1/api/*path", get(path_handler))
+        .route("/signalk/v1/api/vessels/self/meta", get(self_meta_handler))
+        .route(
+            "/signalk/v1/api/*path",
+            get(path_handler).put(put_path_handler),
+        )
+        .route("/signalk/v1/requests/:id", get(request_status_handler))
+        .route("/signalk/v1/history/values", get(history_values_handler))
         // Discovery endpoint
         .route("/signalk", get(discovery_handler))
+}
+
+/// Admin UI and server management routes.
+fn admin_routes() -> Router<AppState> {
+    // Serve admin UI from reference implementation
+    let admin_ui_path = "/home/vadian/signalk-server/packages/server-admin-ui/public";
+    let documentation_path = "/home/vadian/signalk-server/public";
+
+    Router::new()
         // Sources list endpoint (for Data Browser)
         .route("/sources", get(sources_list_handler))
         // Admin UI REST API endpoints
@@ -197,6 +917,18 @@ async fn start_unified_server(addr: SocketAddr, state: AppState) -> anyhow::Resu
             get(get_vessel_handler).put(put_vessel_handler),
         )
         .route("/skServer/plugins", get(get_plugins_handler))
+        .route(
+            "/skServer/plugins/:id/config",
+            axum::routing::post(save_plugin_config_handler),
+        )
+        .route(
+            "/skServer/plugins/:id/enable",
+            axum::routing::post(enable_plugin_handler),
+        )
+        .route(
+            "/skServer/plugins/:id/disable",
+            axum::routing::post(disable_plugin_handler),
+        )
         .route("/skServer/webapps", get(get_webapps_handler))
         .route(
             "/skServer/security/config",
@@ -210,6 +942,11 @@ async fn start_unified_server(addr: SocketAddr, state: AppState) -> anyhow::Resu
         )
         .route("/skServer/restart", axum::routing::put(restart_handler))
         .route("/skServer/debugKeys", get(debug_keys_handler))
+        .route("/skServer/debug", axum::routing::post(debug_handler))
+        // Prometheus metrics, open even with security enabled
+        .route("/metrics", get(metrics_handler))
+        .route("/skServer/connections", get(connections_handler))
+        .route("/skServer/effectiveConfig", get(effective_config_handler))
         .route("/skServer/addons", get(get_addons_handler))
         .route(
             "/skServer/appstore/available",
@@ -229,32 +966,151 @@ async fn start_unified_server(addr: SocketAddr, state: AppState) -> anyhow::Resu
             "/",
             get(|| async { axum::response::Redirect::permanent("/admin/") }),
         )
-        .with_state(state);
+}
+
+/// Put `routes` behind the connection origin check and then the
+/// permission check of the logged-in user (see [`enforce_permissions`]).
+fn with_middleware(routes: Router<AppState>, state: AppState) -> Router {
+    routes
+        .layer(middleware::from_fn_with_state(
+            state.web_state.clone(),
+            enforce_permissions,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            connection_origin,
+        ))
+        .with_state(state)
+}
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    tracing::info!("Server listening on {}", addr);
-    axum::serve(listener, app).await?;
+/// Serve `routes` on `listener`, behind the connection origin and
+/// permission checks, until the server shuts down.
+async fn serve(
+    listener: tokio::net::TcpListener,
+    routes: Router<AppState>,
+    state: AppState,
+) -> anyhow::Result<()> {
+    let mut shutdown = state.shutdown.subscribe();
+    let app = with_middleware(routes, state);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move { shutdown_requested(&mut shutdown).await })
+    .await?;
     Ok(())
 }
 
+/// Serve `routes` over TLS on `listener`, like [`serve`] does over plain
+/// HTTP.
+///
+/// Requests are marked `X-Forwarded-Proto: https`, as a TLS-terminating
+/// proxy would, so discovery hands out `https`/`wss` URLs.
+async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    routes: Router<AppState>,
+    state: AppState,
+    tls: Arc<rustls::ServerConfig>,
+) -> anyhow::Result<()> {
+    let mut shutdown = state.shutdown.subscribe();
+    let app = with_middleware(routes, state);
+    let acceptor = TlsAcceptor::from(tls);
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            () = shutdown_requested(&mut shutdown) => return Ok(()),
+        };
+        let (stream, remote) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::warn!("Could not accept HTTPS connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::debug!("TLS handshake with {} failed: {}", remote, e);
+                    return;
+                }
+            };
+            let service = hyper::service::service_fn(
+                move |mut request: hyper::Request<hyper::body::Incoming>| {
+                    request.extensions_mut().insert(ConnectInfo(remote));
+                    request
+                        .headers_mut()
+                        .insert("x-forwarded-proto", HeaderValue::from_static("https"));
+                    // Routers are always ready
+                    app.clone().call(request)
+                },
+            );
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await
+            {
+                tracing::debug!("HTTPS connection from {} failed: {}", remote, e);
+            }
+        });
+    }
+}
+
+/// Log each request's origin and refuse clients outside the configured
+/// IP allow/deny lists (covers both REST and WebSocket upgrades).
+async fn connection_origin(
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .unwrap_or("-");
+
+    if !state.config.is_ip_allowed(remote.ip()) {
+        tracing::warn!(
+            "Refused {} {} from {} (user-agent: {})",
+            request.method(),
+            request.uri().path(),
+            remote,
+            user_agent
+        );
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    tracing::debug!(
+        "{} {} from {} (user-agent: {})",
+        request.method(),
+        request.uri().path(),
+        remote,
+        user_agent
+    );
+    next.run(request).await
+}
+
 // ============================================================================
 // REST API Handlers for Admin UI
 // ============================================================================
 
-async fn discovery_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "endpoints": {
-            "v1": {
-                "version": "1.7.0",
-                "signalk-http": "http://localhost:4000/signalk/v1/api",
-                "signalk-ws": "ws://localhost:4000/signalk/v1/stream"
-            }
-        },
-        "server": {
-            "id": state.config.name,
-            "version": "0.1.0"
-        }
-    }))
+async fn discovery_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Json<DiscoveryResponse> {
+    let server = DiscoveryServer {
+        id: state.config.name.clone(),
+        version: state.config.version.clone(),
+        vessel_name: state.web_state.vessel_info.read().await.name.clone(),
+    };
+    Json(discovery_for_headers(
+        &headers,
+        Some(state.settings.data_addr().port()),
+        server,
+    ))
 }
 
 async fn sources_list_handler() -> Json<Vec<serde_json::Value>> {
@@ -263,59 +1119,108 @@ async fn sources_list_handler() -> Json<Vec<serde_json::Value>> {
     Json(vec![])
 }
 
-async fn login_status_handler() -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "status": "notLoggedIn",
-        "readOnlyAccess": false,
-        "authenticationRequired": false,
-        "allowNewUserRegistration": false,
-        "allowDeviceAccessRequests": true
-    }))
+async fn login_status_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Json<auth::LoginStatus> {
+    Json(auth::login_status(&state.web_state, &headers).await)
 }
 
-async fn get_settings_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let settings = state.web_state.settings.read().await;
-    Json(serde_json::json!({
-        "interfaces": {
-            "appstore": true,
-            "plugins": true,
-            "rest": true,
-            "signalk-ws": true,
-            "tcp": false,
-            "webapps": true
-        },
-        "port": settings.port.unwrap_or(4000),
-        "ssl": settings.ssl.unwrap_or(false),
-        "wsCompression": false,
-        "accessLogging": false,
-        "mdns": true,
-        "pruneContextsMinutes": 60,
-        "loggingDirectory": "~/.signalk/logs",
-        "keepMostRecentLogsOnly": true,
-        "logCountToKeep": 24,
-        "enablePluginLogging": true
-    }))
+/// Current settings, with defaults for what was never set.
+async fn get_settings_handler(State(state): State<AppState>) -> Json<ServerSettings> {
+    Json(state.web_state.settings.read().await.with_defaults())
 }
 
-async fn put_settings_handler() -> StatusCode {
+/// Save new settings and tell admin UIs; most take effect after a restart.
+///
+/// Fields this server doesn't know are kept, not rejected.
+async fn put_settings_handler(
+    State(state): State<AppState>,
+    Json(settings): Json<ServerSettings>,
+) -> StatusCode {
+    if let Some(storage) = &state.storage {
+        if let Err(e) = storage.save_settings(&settings) {
+            tracing::error!("Could not save settings: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    let data = Box::new(settings.with_defaults());
+    *state.web_state.settings.write().await = settings;
+    state
+        .web_state
+        .broadcast_event(WebServerEvent::ServerSettings { data });
     StatusCode::OK
 }
 
 async fn get_vessel_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let vessel = state.web_state.vessel_info.read().await;
+    let vessel = match state.storage.as_ref().map(|s| s.load_vessel()) {
+        Some(Ok(vessel)) => vessel,
+        _ => state.web_state.vessel_info.read().await.clone(),
+    };
     Json(serde_json::json!({
         "name": vessel.name,
         "mmsi": vessel.mmsi,
-        "uuid": state.config.self_urn
+        "callsign": vessel.callsign,
+        "uuid": state.self_context.get()
     }))
 }
 
-async fn put_vessel_handler() -> StatusCode {
+/// Update the fields present in the request and save the vessel info.
+async fn put_vessel_handler(
+    State(state): State<AppState>,
+    Json(update): Json<VesselInfo>,
+) -> StatusCode {
+    let mut vessel = state.web_state.vessel_info.write().await;
+    let mut updated = vessel.clone();
+    if let Some(name) = update.name {
+        updated.name = Some(name);
+    }
+    if let Some(mmsi) = update.mmsi {
+        updated.mmsi = Some(mmsi);
+    }
+    if let Some(callsign) = update.callsign {
+        updated.callsign = Some(callsign);
+    }
+    if let Some(storage) = &state.storage {
+        if let Err(e) = storage.save_vessel(&updated) {
+            tracing::error!("Could not save vessel info: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    *vessel = updated;
     StatusCode::OK
 }
 
-async fn get_plugins_handler() -> Json<Vec<serde_json::Value>> {
-    Json(vec![])
+async fn get_plugins_handler(State(state): State<AppState>) -> Json<Vec<Plugin>> {
+    Json(list_plugins(&state.web_state.plugins).await)
+}
+
+async fn save_plugin_config_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(config): Json<PluginConfig>,
+) -> (StatusCode, String) {
+    plugin_response(
+        state
+            .web_state
+            .plugins
+            .save_settings(&id, config.into())
+            .await,
+    )
+}
+
+async fn enable_plugin_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, String) {
+    plugin_response(state.web_state.plugins.set_enabled(&id, true).await)
+}
+
+async fn disable_plugin_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, String) {
+    plugin_response(state.web_state.plugins.set_enabled(&id, false).await)
 }
 
 async fn get_webapps_handler() -> Json<Vec<serde_json::Value>> {
@@ -352,12 +1257,43 @@ async fn restart_handler() -> StatusCode {
     StatusCode::OK
 }
 
-async fn debug_keys_handler() -> Json<Vec<String>> {
-    Json(vec![
-        "signalk-server:*".to_string(),
-        "signalk-server:interfaces:*".to_string(),
-        "signalk-server:providers:*".to_string(),
-    ])
+/// Server statistics in the Prometheus text format.
+async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        state.web_state.statistics.prometheus(),
+    )
+}
+
+/// The connected WebSocket clients.
+async fn connections_handler(State(state): State<AppState>) -> Json<Vec<ClientConnection>> {
+    Json(state.web_state.statistics.connections())
+}
+
+async fn debug_keys_handler(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(backup::debug_keys(&state.web_state))
+}
+
+/// Enable or disable debug keys, reloading the log filter.
+async fn debug_handler(
+    State(state): State<AppState>,
+    Json(request): Json<DebugRequest>,
+) -> StatusCode {
+    backup::update_debug_keys(&state.web_state, &request)
+}
+
+/// The configuration the server is actually running with, secrets redacted.
+async fn effective_config_handler(
+    Query(query): Query<ApiQuery>,
+    State(state): State<AppState>,
+) -> ApiJson<serde_json::Value> {
+    ApiJson::new(
+        effective_config(&state.config, &state.settings),
+        query.pretty,
+    )
 }
 
 async fn app_list_handler() -> Json<Vec<serde_json::Value>> {
@@ -381,53 +1317,160 @@ async fn get_access_requests_handler() -> Json<Vec<serde_json::Value>> {
 // ============================================================================
 
 async fn websocket_handler(
-    ws: WebSocketUpgrade,
     Query(query): Query<StreamQuery>,
     State(state): State<AppState>,
-) -> impl IntoResponse {
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    request: Request,
+) -> axum::response::Response {
     let subscribe_mode = query
         .subscribe
         .clone()
         .unwrap_or_else(|| "self".to_string());
     let send_cached_values = query.send_cached_values.unwrap_or(true);
     let send_server_events = query.serverevents.as_deref() == Some("all");
-
-    ws.on_upgrade(move |socket| {
+    let full_format = query.format.as_deref() == Some("full");
+    let dedup = query
+        .dedup
+        .map(|ms| OutboundDedup::new(std::time::Duration::from_millis(ms)));
+    // The upgrade is a `GET`, so PUTs on the stream are checked one by one
+    let permission = request
+        .extensions()
+        .get::<ResolvedPermission>()
+        .map_or(Permission::ReadOnly, |resolved| resolved.0);
+
+    let compression_threshold = state.config.ws_compression_threshold;
+    upgrade_websocket(request, compression_threshold, move |socket, deflater| {
         handle_websocket(
             socket,
+            deflater,
             state,
+            remote,
+            permission,
             subscribe_mode,
             send_cached_values,
             send_server_events,
+            full_format,
+            dedup,
         )
     })
 }
 
+/// Answer a WebSocket handshake and run `on_upgrade` on the connection.
+///
+/// axum's `WebSocketUpgrade` can't negotiate extensions, so the upgrade is
+/// done here: with a `compression_threshold`, a `permessage-deflate` offer
+/// is accepted and `on_upgrade` gets a deflater for outgoing messages.
+fn upgrade_websocket<F, Fut>(
+    mut request: Request,
+    compression_threshold: Option<usize>,
+    on_upgrade: F,
+) -> axum::response::Response
+where
+    F: FnOnce(WebSocket, Option<MessageDeflater>) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send,
+{
+    let headers = request.headers();
+    let is_websocket = headers
+        .get(header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    let version_13 = headers
+        .get(header::SEC_WEBSOCKET_VERSION)
+        .is_some_and(|version| version == "13");
+    let Some(key) = headers.get(header::SEC_WEBSOCKET_KEY) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !is_websocket || !version_13 {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    let accept = derive_accept_key(key.as_bytes());
+    let extension = compression_threshold.and_then(|_| {
+        headers
+            .get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+            .iter()
+            .filter_map(|offers| offers.to_str().ok())
+            .find_map(negotiate_deflate)
+    });
+
+    let upgrade = hyper::upgrade::on(&mut request);
+    tokio::spawn(async move {
+        let upgraded = match upgrade.await {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                tracing::warn!("WebSocket upgrade failed: {}", e);
+                return;
+            }
+        };
+        let stream = InflateStream::new(TokioIo::new(upgraded), extension.is_some());
+        let socket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+        let deflater = extension
+            .and(compression_threshold)
+            .map(MessageDeflater::new);
+        on_upgrade(socket, deflater).await;
+    });
+
+    let mut response = (
+        StatusCode::SWITCHING_PROTOCOLS,
+        [
+            (header::CONNECTION, HeaderValue::from_static("upgrade")),
+            (header::UPGRADE, HeaderValue::from_static("websocket")),
+        ],
+    )
+        .into_response();
+    let headers = response.headers_mut();
+    if let Ok(accept) = HeaderValue::from_str(&accept) {
+        headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+    }
+    if let Some(extension) = extension {
+        headers.insert(
+            header::SEC_WEBSOCKET_EXTENSIONS,
+            HeaderValue::from_static(extension),
+        );
+    }
+    response
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_websocket(
     socket: WebSocket,
+    mut deflater: Option<MessageDeflater>,
     state: AppState,
-    _subscribe_mode: String,
+    remote: SocketAddr,
+    permission: Permission,
+    subscribe_mode: String,
     _send_cached_values: bool,
     send_server_events: bool,
+    full_format: bool,
+    mut dedup: Option<OutboundDedup>,
 ) {
     let (mut sender, mut receiver) = socket.split();
 
-    // Track client connection
-    state.web_state.statistics.client_connected();
+    // Held until the connection is done, which is what a shutdown waits for
+    let shutdown = state.shutdown.subscribe();
+
+    // Listed in the statistics until the connection's tasks are gone
+    let client = Arc::new(state.web_state.statistics.register_client(Some(remote)));
 
     // Send Hello message
     let hello = signalk_protocol::HelloMessage {
         name: state.config.name.clone(),
         version: state.config.version.clone(),
-        self_urn: state.config.self_urn.clone(),
+        self_urn: state.self_context.get(),
         roles: vec!["master".to_string(), "main".to_string()],
         timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        vessel_name: state.web_state.vessel_info.read().await.name.clone(),
+        capabilities: Some(signalk_protocol::Capabilities {
+            put: state.config.capabilities().put && permission >= Permission::ReadWrite,
+            ..state.config.capabilities()
+        }),
     };
 
     let hello_msg = signalk_protocol::ServerMessage::Hello(hello);
     if let Ok(json) = serde_json::to_string(&hello_msg) {
-        if sender.send(Message::Text(json)).await.is_err() {
-            state.web_state.statistics.client_disconnected();
+        if send_frame(&mut sender, &mut deflater, &client, Message::Text(json))
+            .await
+            .is_err()
+        {
             return;
         }
     }
@@ -435,11 +1478,10 @@ async fn handle_websocket(
     // Send initial server events if requested (for Admin UI Dashboard)
     if send_server_events {
         // Extract UUID from self_urn (remove "vessels." prefix)
-        let uuid = state
-            .config
-            .self_urn
+        let self_urn = state.self_context.get();
+        let uuid = self_urn
             .strip_prefix("vessels.")
-            .unwrap_or(&state.config.self_urn)
+            .unwrap_or(&self_urn)
             .to_string();
 
         // Get vessel name from state
@@ -453,37 +1495,47 @@ async fn handle_websocket(
             },
         };
         if let Ok(json) = serde_json::to_string(&vessel_info) {
-            if sender.send(Message::Text(json)).await.is_err() {
-                state.web_state.statistics.client_disconnected();
+            if send_frame(&mut sender, &mut deflater, &client, Message::Text(json))
+                .await
+                .is_err()
+            {
                 return;
             }
         }
 
-        // Send PROVIDERSTATUS (empty for now)
+        // Send PROVIDERSTATUS
         let provider_status = WebServerEvent::ProviderStatus {
             from: "signalk-server".to_string(),
-            data: vec![],
+            data: web_provider_statuses(&state.providers),
         };
         if let Ok(json) = serde_json::to_string(&provider_status) {
-            let _ = sender.send(Message::Text(json)).await;
+            let _ = send_frame(&mut sender, &mut deflater, &client, Message::Text(json)).await;
         }
 
         // Send SERVERSTATISTICS
-        let stats = state.web_state.statistics.snapshot();
+        let stats = state.web_state.statistics.snapshot_detailed();
         let server_stats = WebServerEvent::ServerStatistics {
             from: "signalk-server".to_string(),
             data: stats,
         };
         if let Ok(json) = serde_json::to_string(&server_stats) {
-            let _ = sender.send(Message::Text(json)).await;
+            let _ = send_frame(&mut sender, &mut deflater, &client, Message::Text(json)).await;
         }
 
         // Send DEBUG_SETTINGS
         let debug_settings = WebServerEvent::DebugSettings {
-            data: DebugSettings::default(),
+            data: DebugSettings {
+                debug_enabled: state
+                    .web_state
+                    .debug
+                    .as_ref()
+                    .map(|debug| debug.enabled().join(","))
+                    .unwrap_or_default(),
+                remember_debug: false,
+            },
         };
         if let Ok(json) = serde_json::to_string(&debug_settings) {
-            let _ = sender.send(Message::Text(json)).await;
+            let _ = send_frame(&mut sender, &mut deflater, &client, Message::Text(json)).await;
         }
 
         // Send RECEIVE_LOGIN_STATUS
@@ -491,37 +1543,239 @@ async fn handle_websocket(
             data: LoginStatus::default(),
         };
         if let Ok(json) = serde_json::to_string(&login_status) {
-            let _ = sender.send(Message::Text(json)).await;
+            let _ = send_frame(&mut sender, &mut deflater, &client, Message::Text(json)).await;
         }
 
         // Send SOURCEPRIORITIES
         let source_priorities = WebServerEvent::SourcePriorities {
-            data: SourcePriorities::default(),
+            data: state.config.source_priorities.clone(),
         };
         if let Ok(json) = serde_json::to_string(&source_priorities) {
-            let _ = sender.send(Message::Text(json)).await;
+            let _ = send_frame(&mut sender, &mut deflater, &client, Message::Text(json)).await;
         }
     }
 
     // Normal delta streaming mode
     let mut delta_rx = state.delta_tx.subscribe();
 
+    // The `subscribe` mode filters from the start, as a later `SetMode`
+    // does; an unknown mode means `self`
+    let self_context = state.self_context.clone();
+    let all_min_period = state.config.default_all_min_period_ms;
+    let mode_subscriptions = move |mode: &str| {
+        let mut subscriptions = SubscriptionManager::with_self_context(self_context.clone());
+        subscriptions.set_mode(mode, all_min_period);
+        subscriptions
+    };
+    let subscribe_mode = match subscribe_mode.as_str() {
+        "self" | "all" | "none" => subscribe_mode,
+        _ => "self".to_string(),
+    };
+    let mut subscriptions = mode_subscriptions(&subscribe_mode);
+    client.set_subscriptions(subscriptions.len());
+    log_subscription_event("subscribe", remote, subscriptions.len(), &[]);
+    let (mode_tx, mut mode_rx) = watch::channel(subscribe_mode);
+
+    // Inbound frames are seen by the receive task, deltas by the send task,
+    // which owns the idle deadline
+    let activity = Arc::new(Notify::new());
+    let inbound = activity.clone();
+    let mut idle = IdleTimer::new(
+        state
+            .config
+            .idle_timeout_ms
+            .map(std::time::Duration::from_millis),
+    );
+
+    // Frames go out through a bounded queue, so a slow client can't hold
+    // up the send task; deltas are coalesced while the queue is full
+    let (out_tx, out_rx) = mpsc::channel(state.config.client_queue_capacity.max(1));
+    let mut writer = tokio::spawn(write_messages(sender, out_rx, deflater, client.clone()));
+    let reply_tx = out_tx.clone();
+    let put_state = state.clone();
+    let mut coalescer = DeltaCoalescer::new();
+
+    // Deltas lose the values the ACL hides from this client
+    let acl = state.acl.clone();
+    let restricted = acl.restricts_reads(permission);
+
+    let self_context = state.self_context.clone();
+    let statistics = state.web_state.statistics.clone();
+    let lag_policy = state.config.lag_policy;
+    let store = state.store.clone();
+    let send_client = client.clone();
+    let mut send_shutdown = shutdown.clone();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(delta) = delta_rx.recv().await {
-            let msg = signalk_protocol::ServerMessage::Delta(delta);
-            if let Ok(json) = serde_json::to_string(&msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
+        loop {
+            let broadcast = tokio::select! {
+                () = activity.notified() => {
+                    idle.touch();
+                    continue;
+                }
+                () = idle.expired() => {
+                    tracing::info!("Closing idle WebSocket connection");
+                    let mut frames = Vec::new();
+                    if let Ok(json) = serde_json::to_string(&idle.warning()) {
+                        frames.push(Message::Text(json));
+                    }
+                    frames.push(Message::Close(None));
+                    let _ = out_tx.send(frames).await;
+                    break;
+                }
+                // Send what was coalesced once the queue has room again
+                permit = out_tx.reserve(), if !coalescer.is_empty() => {
+                    let Ok(permit) = permit else {
+                        break;
+                    };
+                    tracing::debug!("Sending {} coalesced values to slow client", coalescer.len());
+                    let frames = coalescer
+                        .drain()
+                        .iter()
+                        .filter_map(|delta| encode_delta(delta, full_format, &self_context).ok())
+                        .map(Message::Text)
+                        .collect();
+                    permit.send(frames);
+                    idle.touch();
+                    continue;
+                }
+                // The writer stops when sending to the client fails
+                () = out_tx.closed() => break,
+                // Send what was held back, then close
+                () = shutdown_requested(&mut send_shutdown) => {
+                    tracing::info!("Closing WebSocket connection for shutdown");
+                    let mut frames: Vec<Message> = coalescer
+                        .drain()
+                        .iter()
+                        .filter_map(|delta| encode_delta(delta, full_format, &self_context).ok())
+                        .map(Message::Text)
+                        .collect();
+                    frames.push(shutdown_close_frame());
+                    let _ = out_tx.send(frames).await;
                     break;
                 }
+                changed = mode_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let mode_subscriptions = mode_subscriptions(&mode_rx.borrow_and_update());
+                    send_client.set_subscriptions(mode_subscriptions.len());
+                    log_subscription_event("mode", remote, mode_subscriptions.len(), &[]);
+                    subscriptions = mode_subscriptions;
+                    continue;
+                }
+                broadcast = delta_rx.recv() => match broadcast {
+                    Ok(broadcast) => broadcast,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("WebSocket client lagged {} messages", n);
+                        statistics.record_lagged(n);
+                        match lag_policy {
+                            LagPolicy::SkipAndWarn => {}
+                            LagPolicy::DisconnectClient => {
+                                let _ = out_tx.send(vec![Message::Close(None)]).await;
+                                break;
+                            }
+                            LagPolicy::SendResync => {
+                                let resync = subscriptions.cached_deltas(&store.view());
+                                // Supersedes what was held back
+                                coalescer.drain();
+                                let frames = resync
+                                    .into_iter()
+                                    .filter_map(|delta| acl.filter_readable(permission, delta))
+                                    .filter_map(|delta| encode_delta(&delta, full_format, &self_context).ok())
+                                    .map(Message::Text)
+                                    .collect();
+                                if out_tx.send(frames).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            let now = Instant::now();
+            let Some(delta) = subscriptions.filter_delta_ref(&broadcast.delta, now) else {
+                continue;
+            };
+            let delta = match &mut dedup {
+                Some(dedup) => match dedup.filter(delta, now) {
+                    Some(delta) => delta,
+                    None => continue,
+                },
+                None => delta,
+            };
+            let delta = if restricted {
+                match acl.filter_readable(permission, delta.into_owned()) {
+                    Some(delta) => Cow::Owned(delta),
+                    None => continue,
+                }
+            } else {
+                delta
+            };
+            if !coalescer.is_empty() || out_tx.capacity() == 0 {
+                coalescer.push(delta.into_owned());
+                continue;
+            }
+            let json = match &delta {
+                _ if full_format => {
+                    serde_json::to_string(&full_fragment(&delta, &self_context.get()))
+                }
+                Cow::Borrowed(_) if !broadcast.encoded.is_empty() => {
+                    Ok(broadcast.encoded.to_string())
+                }
+                delta => serde_json::to_string(delta),
+            };
+            if let Ok(json) = json {
+                match send_or_coalesce(&out_tx, &mut coalescer, json, delta) {
+                    Ok(true) => idle.touch(),
+                    Ok(false) => {}
+                    Err(()) => break,
+                }
             }
         }
     });
 
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
+            // Pings keep the connection alive, but don't use it
+            if matches!(msg, Message::Text(_) | Message::Binary(_)) {
+                inbound.notify_one();
+            }
             if let Message::Text(text) = msg {
                 tracing::debug!("Received: {}", text);
-                // Handle subscribe/unsubscribe messages here
+                match decode_client_message(&text) {
+                    Ok(ClientMessage::SetMode { mode }) => {
+                        if matches!(mode.as_str(), "self" | "all" | "none") {
+                            let _ = mode_tx.send(mode);
+                            continue;
+                        }
+                        let warning = format!("Unknown subscribe mode {mode}, ignoring");
+                        tracing::warn!("Subscription warning: {}", warning);
+                        log_subscription_event(
+                            "mode",
+                            remote,
+                            client.subscriptions(),
+                            std::slice::from_ref(&warning),
+                        );
+                        if let Ok(json) = serde_json::to_string(&warning) {
+                            if reply_tx.send(vec![Message::Text(json)]).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Ok(ClientMessage::Put(req)) => {
+                        let response = websocket_put(&put_state, permission, req).await;
+                        if let Ok(json) = serde_json::to_string(&response) {
+                            if reply_tx.send(vec![Message::Text(json)]).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    // Subscriptions follow the `subscribe` mode on this stream
+                    Ok(_) => {}
+                    Err(e) => tracing::debug!("Ignoring message from {}: {}", remote, e),
+                }
             } else if let Message::Close(_) = msg {
                 break;
             }
@@ -533,26 +1787,137 @@ async fn handle_websocket(
         _ = (&mut recv_task) => send_task.abort(),
     }
 
-    state.web_state.statistics.client_disconnected();
+    // Let the writer send what is queued, unless the client has stopped
+    // reading altogether
+    if tokio::time::timeout(WRITER_DRAIN_TIMEOUT, &mut writer)
+        .await
+        .is_err()
+    {
+        writer.abort();
+    }
+    drop(shutdown);
+
     tracing::debug!("WebSocket connection closed");
 }
 
+/// Queue `json`, the encoded `delta`, without waiting.
+///
+/// The receive task's replies share the queue and may take its last slot
+/// after the send task saw room, so a full queue coalesces the delta rather
+/// than ending the connection. Returns whether the frame was queued, or
+/// `Err` once the writer is gone.
+fn send_or_coalesce(
+    out_tx: &mpsc::Sender<Vec<Message>>,
+    coalescer: &mut DeltaCoalescer,
+    json: String,
+    delta: Cow<'_, Delta>,
+) -> Result<bool, ()> {
+    match out_tx.try_send(vec![Message::Text(json)]) {
+        Ok(()) => Ok(true),
+        Err(mpsc::error::TrySendError::Full(_)) => {
+            coalescer.push(delta.into_owned());
+            Ok(false)
+        }
+        Err(mpsc::error::TrySendError::Closed(_)) => Err(()),
+    }
+}
+
+/// How long a closing connection may take to send its queued frames.
+const WRITER_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Send queued frames to the client until the queue closes or a send fails,
+/// compressing them if the connection negotiated it.
+async fn write_messages(
+    mut sender: SplitSink<WebSocket, Message>,
+    mut out_rx: mpsc::Receiver<Vec<Message>>,
+    mut deflater: Option<MessageDeflater>,
+    client: Arc<ClientHandle>,
+) {
+    while let Some(frames) = out_rx.recv().await {
+        for frame in frames {
+            if send_frame(&mut sender, &mut deflater, &client, frame)
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Send `message` to the client, compressed if the connection negotiated
+/// it, and count the bytes sent.
+async fn send_frame(
+    sender: &mut SplitSink<WebSocket, Message>,
+    deflater: &mut Option<MessageDeflater>,
+    client: &ClientHandle,
+    message: Message,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let message = compress(deflater, message);
+    let len = message.len();
+    sender.send(message).await?;
+    client.record_sent(len);
+    Ok(())
+}
+
+/// `message` compressed, if the connection negotiated it.
+fn compress(deflater: &mut Option<MessageDeflater>, message: Message) -> Message {
+    match deflater {
+        Some(deflater) => deflater.deflate(message),
+        None => message,
+    }
+}
+
+/// Encode a delta for a stream, as a full-format fragment if requested.
+fn encode_delta(
+    delta: &Delta,
+    full_format: bool,
+    self_context: &SelfContext,
+) -> serde_json::Result<String> {
+    if full_format {
+        serde_json::to_string(&full_fragment(delta, &self_context.get()))
+    } else {
+        serde_json::to_string(delta)
+    }
+}
+
 // ============================================================================
 // SignalK Data API Handlers
 // ============================================================================
 
 async fn full_api_handler(
+    Query(query): Query<ApiQuery>,
     State(state): State<AppState>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let store = state.store.read().await;
-    Ok(Json(store.full_model().clone()))
+    resolved: Option<Extension<ResolvedPermission>>,
+) -> Result<ApiJson<serde_json::Value>, StatusCode> {
+    if state.acl.restricts_reads(request_permission(resolved)) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let store = state.store.view();
+    let mut model = store
+        .model_slice(query.context.as_deref(), query.depth)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if query.units == UnitSystem::Display {
+        to_display_units(&mut model);
+    }
+    Ok(ApiJson::new(model, query.pretty))
 }
 
 async fn path_handler(
     Path(path): Path<String>,
+    Query(query): Query<ApiQuery>,
     State(state): State<AppState>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let store = state.store.read().await;
+    resolved: Option<Extension<ResolvedPermission>>,
+) -> Result<ApiJson<serde_json::Value>, StatusCode> {
+    let permission = request_permission(resolved);
+    let readable = |path: &str, leaf: bool| {
+        if can_read_node(&state.acl, permission, path, leaf) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    };
+    let store = state.store.view();
 
     // Remove leading slash if present
     let path = path.strip_prefix('/').unwrap_or(&path);
@@ -560,70 +1925,1296 @@ async fn path_handler(
     // Convert URL path separators to SignalK dot notation
     let path = path.replace('/', ".");
 
-    match store.get_path(&path) {
-        Some(value) => Ok(Json(value)),
-        None => Err(StatusCode::NOT_FOUND),
+    // `<path>/sources`: every source's value for a path
+    if let Some(value_path) = path
+        .strip_suffix(".sources")
+        .filter(|p| p.starts_with("vessels."))
+    {
+        readable(value_path, true)?;
+        let sources = store
+            .get_path_all_sources(value_path)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        return Ok(ApiJson::new(sources, query.pretty));
+    }
+
+    // `<path>/meta`: the path's metadata (units, zones, ...)
+    if let Some(value_path) = path
+        .strip_suffix(".meta")
+        .filter(|p| p.starts_with("vessels."))
+    {
+        readable(value_path, true)?;
+        let meta = store.get_meta(value_path).ok_or(StatusCode::NOT_FOUND)?;
+        return Ok(ApiJson::new(meta, query.pretty));
+    }
+
+    // `<path>/stats`: min/max/mean over the path's recent values
+    if let Some(value_path) = path
+        .strip_suffix(".stats")
+        .filter(|p| p.starts_with("vessels."))
+    {
+        readable(value_path, true)?;
+        let stats = store.path_stats(value_path).ok_or(StatusCode::NOT_FOUND)?;
+        let stats = serde_json::to_value(stats).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(ApiJson::new(stats, query.pretty));
+    }
+
+    if let Some(since) = &query.since {
+        let since = chrono::DateTime::parse_from_rfc3339(since)
+            .map_err(|_| StatusCode::BAD_REQUEST)?
+            .with_timezone(&chrono::Utc);
+        if store.get_context(&path).is_none() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let delta = store.changed_since(&path, since);
+        let context = delta.context.clone();
+        let delta = state
+            .acl
+            .filter_readable(permission, delta)
+            .unwrap_or(Delta {
+                context,
+                updates: Vec::new(),
+            });
+        let delta = serde_json::to_value(delta).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(ApiJson::new(delta, query.pretty));
+    }
+
+    let mut node = store.get_path(&path).ok_or(StatusCode::NOT_FOUND)?;
+    readable(&path, node.get("value").is_some())?;
+    // Before selecting a source, which may leave out the meta
+    if query.units == UnitSystem::Display {
+        to_display_units(&mut node);
+    }
+    let node = select_leaf(node, query.source.as_deref(), query.meta)?;
+    if query.value {
+        let value = node.get("value").cloned().ok_or(StatusCode::NOT_FOUND)?;
+        return Ok(ApiJson::new(value, query.pretty));
+    }
+    Ok(ApiJson::new(node, query.pretty))
+}
+
+/// Whether a client with `permission` may read the node at `path`, context
+/// first. Values are checked against the ACL; whole contexts and subtrees
+/// only if the ACL hides nothing from the client, as on a WebSocket GET.
+fn can_read_node(acl: &PathAcl, permission: Permission, path: &str, leaf: bool) -> bool {
+    match path.splitn(3, '.').nth(2) {
+        Some(relative) if leaf => acl.can_read(permission, relative),
+        _ => !acl.restricts_reads(permission),
+    }
+}
+
+/// The permission `enforce_permissions` resolved for a request; read-only
+/// if it didn't run.
+fn request_permission(resolved: Option<Extension<ResolvedPermission>>) -> Permission {
+    resolved.map_or(Permission::ReadOnly, |Extension(resolved)| resolved.0)
+}
+
+/// Historical values of some paths, averaged per resolution bucket.
+async fn history_values_handler(
+    Query(params): Query<HistoryParams>,
+    State(state): State<AppState>,
+    resolved: Option<Extension<ResolvedPermission>>,
+) -> Result<ApiJson<Vec<HistoryValues>>, StatusCode> {
+    let query = params.to_query(&state.self_context.get())?;
+    let permission = request_permission(resolved);
+    if !query
+        .paths
+        .iter()
+        .all(|path| state.acl.can_read(permission, path))
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let values = state.web_state.history.query(&query);
+    Ok(ApiJson::new(vec![values], params.pretty))
+}
+
+/// All metadata stored under the self vessel, as a tree keyed by path.
+async fn self_meta_handler(
+    Query(query): Query<ApiQuery>,
+    State(state): State<AppState>,
+    resolved: Option<Extension<ResolvedPermission>>,
+) -> Result<ApiJson<serde_json::Value>, StatusCode> {
+    if state.acl.restricts_reads(request_permission(resolved)) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let store = state.store.view();
+    Ok(ApiJson::new(store.meta_tree("vessels.self"), query.pretty))
+}
+
+/// PUT a value to a self path, as a WebSocket PUT would; other contexts
+/// aren't writable, nor are paths the ACL reserves to higher permissions.
+async fn put_path_handler(
+    Path(path): Path<String>,
+    State(state): State<AppState>,
+    resolved: Option<Extension<ResolvedPermission>>,
+    Json(body): Json<PutBody>,
+) -> Response {
+    let path = path.trim_start_matches('/');
+    let self_urn = state.self_context.get().replace('.', "/");
+    let Some(self_path) = ["vessels/self/", &format!("{self_urn}/")]
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+    else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let permission = request_permission(resolved);
+    if !state
+        .acl
+        .can_write(permission, &self_path.replace('/', "."))
+    {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    put_self_path(&state.web_state, self_path, body)
+        .await
+        .into_response()
+}
+
+/// Answer a PUT received on a WebSocket as the REST PUT would, if the
+/// client may write and the context is self.
+async fn websocket_put(state: &AppState, permission: Permission, req: PutRequest) -> PutResponse {
+    let context = req.context.as_deref().unwrap_or("vessels.self");
+    if !state.self_context.is_self(context) {
+        let message = format!("Context {context} is not writable");
+        return PutResponse::failed(&req, 403, message);
+    }
+    if !state.acl.can_write(permission, &req.put.path) {
+        tracing::warn!("Rejected PUT to {}: permission denied", req.put.path);
+        let message = format!("Permission denied for {}", req.put.path);
+        return PutResponse::failed(&req, 403, message);
     }
+    put_request(&state.web_state, req).await
+}
+
+/// The state of a pending PUT, polled at the `href` it answered with.
+async fn request_status_handler(Path(id): Path<String>, State(state): State<AppState>) -> Response {
+    request_status(&state.web_state, &id).await.into_response()
 }
 
 // ============================================================================
 // Demo Data Generator
 // ============================================================================
 
-async fn generate_demo_data(event_tx: tokio::sync::mpsc::Sender<ServerEvent>) {
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
-    let mut latitude = 52.0987654;
-    let mut longitude = 4.9876545;
+/// Submit a delta from `scenario` every `interval`, until the server stops.
+async fn generate_demo_data(
+    event_tx: tokio::sync::mpsc::Sender<ServerEvent>,
+    mut scenario: Box<dyn DemoScenario>,
+    interval: std::time::Duration,
+) {
+    tracing::info!("Generating demo data: {}", scenario.name());
+    let mut interval = tokio::time::interval(interval);
 
-    loop {
+    for tick in 0.. {
         interval.tick().await;
 
-        // Update position (move the boat)
-        latitude += 0.00001;
-        longitude += 0.00002;
+        let mut delta = scenario.next_delta(tick);
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        for update in &mut delta.updates {
+            update.timestamp = Some(timestamp.clone());
+        }
 
-        // Vary speed and course slightly
-        let sog = 3.85 + (tokio::time::Instant::now().elapsed().as_secs_f64().sin() * 0.5);
-        let cog = 1.52 + (tokio::time::Instant::now().elapsed().as_secs_f64().cos() * 0.1);
+        // Send to server
+        if event_tx
+            .send(ServerEvent::DeltaReceived(delta))
+            .await
+            .is_err()
+        {
+            tracing::error!("Failed to send demo delta - server may have stopped");
+            break;
+        }
+    }
+}
 
-        // Create delta message
-        let delta = Delta {
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn test_state() -> AppState {
+        let config = ServerConfig::default();
+        let store = Arc::new(SharedStore::new(MemoryStore::new(&config.self_urn)));
+        let web_config = WebConfig {
+            name: config.name.clone(),
+            version: config.version.clone(),
+            self_urn: config.self_urn.clone(),
+        };
+        AppState {
+            web_state: Arc::new(WebState::new(store.clone(), web_config)),
+            self_context: SelfContext::new(&config.self_urn),
+            acl: Arc::new(PathAcl::default()),
+            store,
+            delta_tx: broadcast::channel(16).0,
+            config,
+            settings: ServerSettings::default(),
+            providers: Arc::new(ProviderRegistry::new()),
+            storage: None,
+            shutdown: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Serve `routes` on an ephemeral localhost port.
+    async fn spawn_routes(routes: Router<AppState>) -> SocketAddr {
+        spawn_routes_with_state(routes, test_state()).await
+    }
+
+    async fn spawn_routes_with_state(routes: Router<AppState>, state: AppState) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, routes, state));
+        addr
+    }
+
+    /// Open `/signalk/v1/stream` with a raw handshake offering
+    /// `extensions`; returns the connection, the response head and the
+    /// first frame.
+    async fn open_stream(
+        addr: SocketAddr,
+        extensions: Option<&str>,
+    ) -> (tokio::net::TcpStream, String, Vec<u8>) {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let extensions = extensions
+            .map(|offer| format!("Sec-WebSocket-Extensions: {offer}\r\n"))
+            .unwrap_or_default();
+        let request = format!(
+            "GET /signalk/v1/stream?subscribe=none HTTP/1.1\r\nHost: localhost\r\n\
+             Upgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\
+             {extensions}\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut data = Vec::new();
+        let head_len = loop {
+            if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end + 4;
+            }
+            let mut chunk = [0; 1024];
+            let n = stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "connection closed during handshake");
+            data.extend_from_slice(&chunk[..n]);
+        };
+        let head = String::from_utf8(data.drain(..head_len).collect()).unwrap();
+
+        // Server frames are unmasked; the hello fits a 16-bit length
+        while data.len() < 4 {
+            let mut chunk = [0; 1024];
+            let n = stream.read(&mut chunk).await.unwrap();
+            data.extend_from_slice(&chunk[..n]);
+        }
+        let payload_len = match data[1] & 0x7f {
+            126 => usize::from(u16::from_be_bytes([data[2], data[3]])) + 4,
+            len => usize::from(len) + 2,
+        };
+        while data.len() < payload_len {
+            let mut chunk = [0; 1024];
+            let n = stream.read(&mut chunk).await.unwrap();
+            data.extend_from_slice(&chunk[..n]);
+        }
+        data.truncate(payload_len);
+        (stream, head, data)
+    }
+
+    /// Issue a bare HTTP GET and return the status code.
+    async fn get_status(addr: SocketAddr, path: &str) -> u16 {
+        get(addr, path).await.0
+    }
+
+    /// Issue a bare HTTP GET and return the status code and body.
+    async fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+        get_with_headers(addr, path, "Host: localhost\r\n").await
+    }
+
+    /// Issue a bare HTTP GET with `headers` (each ending in CRLF) and
+    /// return the status code and body.
+    async fn get_with_headers(addr: SocketAddr, path: &str, headers: &str) -> (u16, String) {
+        send_request(addr, "GET", path, headers, "").await
+    }
+
+    /// Issue a bare HTTP request with `headers` (each ending in CRLF) and a
+    /// JSON `body`, and return the status code and body.
+    async fn send_request(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &str,
+        body: &str,
+    ) -> (u16, String) {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\n{headers}Content-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .expect("HTTP status line");
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .unwrap_or_default();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_not_on_data_listener() {
+        let data = spawn_routes(data_routes()).await;
+        let admin = spawn_routes(admin_routes()).await;
+
+        assert_eq!(get_status(data, "/signalk").await, 200);
+        assert_eq!(get_status(data, "/skServer/settings").await, 404);
+        assert_eq!(get_status(data, "/skServer/effectiveConfig").await, 404);
+
+        assert_eq!(get_status(admin, "/skServer/settings").await, 200);
+        assert_eq!(get_status(admin, "/metrics").await, 200);
+        assert_eq!(get_status(admin, "/signalk").await, 404);
+    }
+
+    #[tokio::test]
+    async fn test_settings_and_vessel_saved() {
+        let dir = std::env::temp_dir().join(format!("signalk-linux-{}", std::process::id()));
+        let storage = FileConfigStorage::new(&dir).unwrap();
+        let state = AppState {
+            storage: Some(storage.clone()),
+            ..test_state()
+        };
+
+        let settings = ServerSettings {
+            port: Some(3000),
+            ..Default::default()
+        };
+        let status = put_settings_handler(State(state.clone()), Json(settings)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(storage.load_settings().unwrap().port, Some(3000));
+        let Json(body) = get_settings_handler(State(state.clone())).await;
+        assert_eq!(body.port, Some(3000));
+        assert_eq!(body.ssl, Some(false));
+
+        let vessel = VesselInfo {
+            name: Some("Albatross".to_string()),
+            ..Default::default()
+        };
+        let status = put_vessel_handler(State(state.clone()), Json(vessel)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            storage.load_vessel().unwrap().name.as_deref(),
+            Some("Albatross")
+        );
+        let Json(body) = get_vessel_handler(State(state)).await;
+        assert_eq!(body["name"], "Albatross");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_put_settings_broadcast() {
+        let state = test_state();
+        let mut events = state.web_state.subscribe_events();
+
+        let settings: ServerSettings =
+            serde_json::from_str(r#"{"port": 3100, "courseApi": {"apiOnly": true}}"#).unwrap();
+        let status = put_settings_handler(State(state.clone()), Json(settings)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let Json(body) = get_settings_handler(State(state)).await;
+        assert_eq!(body.port, Some(3100));
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json["courseApi"]["apiOnly"], true);
+
+        match events.try_recv().unwrap() {
+            WebServerEvent::ServerSettings { data } => assert_eq!(data.port, Some(3100)),
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discovery_from_request_headers() {
+        let state = test_state();
+        let (name, version) = (state.config.name.clone(), state.config.version.clone());
+        let addr = spawn_routes_with_state(data_routes(), state).await;
+
+        let (status, body) = get_with_headers(
+            addr,
+            "/signalk",
+            "Host: chartplotter.local:8080\r\nX-Forwarded-Proto: https\r\n",
+        )
+        .await;
+        assert_eq!(status, 200, "{body}");
+        let discovery: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let v1 = &discovery["endpoints"]["v1"];
+        assert_eq!(
+            v1["signalk-http"],
+            "https://chartplotter.local:8080/signalk/v1/api"
+        );
+        assert_eq!(
+            v1["signalk-ws"],
+            "wss://chartplotter.local:8080/signalk/v1/stream"
+        );
+        assert_eq!(discovery["server"]["id"], name);
+        assert_eq!(discovery["server"]["version"], version);
+
+        // A reverse proxy's host wins over the one it connected to
+        let (_, body) = get_with_headers(
+            addr,
+            "/signalk",
+            "Host: 127.0.0.1\r\nX-Forwarded-Host: boat.example.com\r\n",
+        )
+        .await;
+        let discovery: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            discovery["endpoints"]["v1"]["signalk-ws"],
+            "ws://boat.example.com/signalk/v1/stream"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_query_in_display_units() {
+        let state = test_state();
+        state.store.write().apply_delta(&Delta {
             context: Some("vessels.self".to_string()),
             updates: vec![Update {
-                source_ref: Some("demo.generator".to_string()),
+                source_ref: Some("gps1".to_string()),
                 source: None,
-                timestamp: Some(
-                    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
-                ),
-                values: vec![
-                    PathValue {
-                        path: "navigation.position".to_string(),
-                        value: serde_json::json!({
-                            "latitude": latitude,
-                            "longitude": longitude
-                        }),
-                    },
-                    PathValue {
-                        path: "navigation.speedOverGround".to_string(),
-                        value: serde_json::json!(sog),
-                    },
-                    PathValue {
-                        path: "navigation.courseOverGroundTrue".to_string(),
-                        value: serde_json::json!(cog),
-                    },
-                ],
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(5.0),
+                }],
+                meta: Some(vec![serde_json::from_value(serde_json::json!({
+                    "path": "navigation.speedOverGround",
+                    "value": { "units": "m/s" },
+                }))
+                .unwrap()]),
+                server_timestamp: None,
+            }],
+        });
+        let path = format!(
+            "/signalk/v1/api/{}/navigation/speedOverGround",
+            state.config.self_urn.replace('.', "/")
+        );
+        let addr = spawn_routes_with_state(data_routes(), state).await;
+        let knots = 5.0 * 3600.0 / 1852.0;
+
+        let (status, body) = get(addr, &format!("{path}?units=display")).await;
+        assert_eq!(status, 200, "{body}");
+        let node: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!((node["value"].as_f64().unwrap() - knots).abs() < 1e-9);
+        assert_eq!(node["meta"]["units"], "kn");
+
+        let (_, body) = get(
+            addr,
+            &format!("{path}?units=display&source=gps1&value=true"),
+        )
+        .await;
+        let value: f64 = serde_json::from_str(&body).unwrap();
+        assert!((value - knots).abs() < 1e-9);
+
+        // SI by default
+        let (_, body) = get(addr, &path).await;
+        let node: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(node["value"], 5.0);
+        assert_eq!(node["meta"]["units"], "m/s");
+
+        assert_eq!(
+            get_status(addr, &format!("{path}?units=furlongs")).await,
+            400
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_query_selects_source() {
+        let state = test_state();
+        {
+            let mut store = state.store.write();
+            for (source, sog) in [("gps1", 3.85), ("gps2", 3.90)] {
+                store.apply_delta(&Delta {
+                    context: Some("vessels.self".to_string()),
+                    updates: vec![Update {
+                        source_ref: Some(source.to_string()),
+                        source: None,
+                        timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                        values: vec![PathValue {
+                            path: "navigation.speedOverGround".to_string(),
+                            value: serde_json::json!(sog),
+                        }],
+                        meta: Some(vec![serde_json::from_value(serde_json::json!({
+                            "path": "navigation.speedOverGround",
+                            "value": { "units": "m/s" },
+                        }))
+                        .unwrap()]),
+                        server_timestamp: None,
+                    }],
+                });
+            }
+        }
+        let path = format!(
+            "{}/navigation/speedOverGround",
+            state.config.self_urn.replace('.', "/")
+        );
+        let query = |params: serde_json::Value| -> Query<ApiQuery> {
+            Query(serde_json::from_value(params).unwrap())
+        };
+
+        let body = path_handler(
+            Path(path.clone()),
+            query(serde_json::json!({ "source": "gps1" })),
+            State(state.clone()),
+            None,
+        )
+        .await
+        .unwrap()
+        .value;
+        assert_eq!(body["value"], 3.85);
+        assert_eq!(body["$source"], "gps1");
+        assert!(body.get("meta").is_none());
+
+        let body = path_handler(
+            Path(path.clone()),
+            query(serde_json::json!({ "source": "gps1", "meta": true })),
+            State(state.clone()),
+            None,
+        )
+        .await
+        .unwrap()
+        .value;
+        assert_eq!(body["meta"]["units"], "m/s");
+
+        let body = path_handler(
+            Path(path.clone()),
+            query(serde_json::json!({ "source": "gps1", "value": true })),
+            State(state.clone()),
+            None,
+        )
+        .await
+        .unwrap()
+        .value;
+        assert_eq!(body, 3.85);
+
+        let missing = path_handler(
+            Path(path),
+            query(serde_json::json!({ "source": "ais" })),
+            State(state),
+            None,
+        )
+        .await;
+        assert_eq!(missing.err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_put_over_rest() {
+        let state = test_state();
+        state
+            .store
+            .write()
+            .register_put_handler("steering.autopilot.state", Box::new(|_| Ok(())))
+            .unwrap();
+        let put = |path: &str| {
+            let state = state.clone();
+            let path = path.to_string();
+            async move {
+                let body = PutBody {
+                    value: serde_json::json!("auto"),
+                    source: None,
+                };
+                let resolved = Extension(ResolvedPermission(Permission::ReadWrite));
+                put_path_handler(Path(path), State(state), Some(resolved), Json(body))
+                    .await
+                    .status()
+            }
+        };
+
+        assert_eq!(
+            put("vessels/self/steering/autopilot/state").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            state
+                .store
+                .read()
+                .get_self_path("steering.autopilot.state")
+                .unwrap()["value"],
+            "auto"
+        );
+        let by_urn = format!(
+            "{}/steering/autopilot/state",
+            state.config.self_urn.replace('.', "/")
+        );
+        assert_eq!(put(&by_urn).await, StatusCode::OK);
+        assert_eq!(
+            put("vessels/self/navigation/anchor/maxRadius").await,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            put("vessels/urn:mrn:imo:mmsi:230000001/steering/autopilot/state").await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    type StreamClient = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Open a stream with `subscribe`, logged in with `token` if given, and
+    /// return it with the hello message.
+    async fn connect_stream(
+        addr: SocketAddr,
+        subscribe: &str,
+        token: Option<&str>,
+    ) -> (StreamClient, serde_json::Value) {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        let mut request = format!("ws://{addr}/signalk/v1/stream?subscribe={subscribe}")
+            .into_client_request()
+            .unwrap();
+        if let Some(token) = token {
+            request.headers_mut().insert(
+                header::AUTHORIZATION,
+                format!("Bearer {token}").parse().unwrap(),
+            );
+        }
+        let (mut ws, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+        let hello = next_json(&mut ws).await;
+        (ws, hello)
+    }
+
+    /// The next message on a stream, as JSON.
+    async fn next_json(ws: &mut StreamClient) -> serde_json::Value {
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), ws.next())
+            .await
+            .expect("Timed out waiting for a message")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(&message.into_text().unwrap()).unwrap()
+    }
+
+    /// A delta of `values` for the self vessel.
+    fn self_delta(values: &[(&str, serde_json::Value)]) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("test".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: values
+                    .iter()
+                    .map(|(path, value)| PathValue {
+                        path: path.to_string(),
+                        value: value.clone(),
+                    })
+                    .collect(),
                 meta: None,
+                server_timestamp: None,
             }],
+        }
+    }
+
+    /// A state with an admin and a read-only user, and a token for each.
+    async fn secured_state() -> (AppState, String, String) {
+        let state = test_state();
+        let user = |id: &str, user_type: &str| signalk_core::UserRecord {
+            user_id: id.to_string(),
+            user_type: user_type.to_string(),
+            password_hash: Some(signalk_web::jwt::hash_password("secret").unwrap()),
+        };
+        *state.web_state.security.write().await = SecurityConfig {
+            users: Some(vec![user("admin", "admin"), user("guest", "readonly")]),
+            ..Default::default()
         };
+        let token = |user_id: &str| {
+            let ttl = std::time::Duration::from_secs(60);
+            state
+                .web_state
+                .tokens
+                .issue(user_id, ttl, chrono::Utc::now())
+                .unwrap()
+        };
+        let (admin, guest) = (token("admin"), token("guest"));
+        (state, admin, guest)
+    }
 
-        // Send to server
-        if event_tx
-            .send(ServerEvent::DeltaReceived(delta))
+    #[tokio::test]
+    async fn test_admin_routes_need_permission() {
+        let (state, admin, guest) = secured_state().await;
+        let addr = spawn_routes_with_state(admin_routes(), state.clone()).await;
+        let bearer = |token: &str| format!("Host: localhost\r\nAuthorization: Bearer {token}\r\n");
+        let put_settings = |headers: String| async move {
+            send_request(
+                addr,
+                "PUT",
+                "/skServer/settings",
+                &headers,
+                r#"{"port": 3001}"#,
+            )
             .await
-            .is_err()
+            .0
+        };
+
+        assert_eq!(put_settings("Host: localhost\r\n".to_string()).await, 401);
+        assert_eq!(put_settings(bearer(&guest)).await, 403);
+        assert_eq!(state.web_state.settings.read().await.port, None);
+        assert_eq!(put_settings(bearer(&admin)).await, 200);
+        assert_eq!(state.web_state.settings.read().await.port, Some(3001));
+
+        // Read-only users may read, anonymous ones only log in
+        let (status, _) = get_with_headers(addr, "/skServer/settings", &bearer(&guest)).await;
+        assert_eq!(status, 200);
+        assert_eq!(get_status(addr, "/skServer/settings").await, 401);
+        assert_eq!(get_status(addr, "/skServer/loginStatus").await, 200);
+
+        // Scrapers need no token
+        let (status, body) = get_with_headers(addr, "/metrics", "Host: localhost\r\n").await;
+        assert_eq!(status, 200);
+        assert!(body.contains("signalk_"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn test_login_status_from_token() {
+        let (state, _, _) = secured_state().await;
+        let routes = admin_routes().merge(login_routes(&state));
+        let addr = spawn_routes_with_state(routes, state).await;
+        let host = "Host: localhost\r\n";
+
+        let (status, body) = get_with_headers(addr, "/skServer/loginStatus", host).await;
+        assert_eq!(status, 200);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["status"], "notLoggedIn");
+        assert_eq!(body["authenticationRequired"], true);
+
+        let login = r#"{"username": "guest", "password": "wrong"}"#;
+        let (status, _) = send_request(addr, "POST", "/signalk/v1/auth/login", host, login).await;
+        assert_eq!(status, 401);
+        let login = r#"{"username": "guest", "password": "secret"}"#;
+        let (status, body) =
+            send_request(addr, "POST", "/signalk/v1/auth/login", host, login).await;
+        assert_eq!(status, 200);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let token = body["token"].as_str().unwrap();
+
+        let headers = format!("{host}Authorization: Bearer {token}\r\n");
+        let (_, body) = get_with_headers(addr, "/skServer/loginStatus", &headers).await;
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["status"], "loggedIn");
+        assert_eq!(body["username"], "guest");
+        assert_eq!(body["userLevel"], "readonly");
+    }
+
+    #[tokio::test]
+    async fn test_path_acl_on_rest_and_stream() {
+        let (state, admin, guest) = secured_state().await;
+        let rules: Vec<signalk_core::AclRule> = serde_json::from_value(serde_json::json!([
+            { "path": "electrical.*", "read": "readwrite" },
+            { "path": "steering.autopilot.*", "write": "admin" },
+        ]))
+        .unwrap();
+        let state = AppState {
+            acl: Arc::new(PathAcl::new(&rules).unwrap()),
+            ..state
+        };
         {
-            tracing::error!("Failed to send demo delta - server may have stopped");
-            break;
+            let mut store = state.store.write();
+            store
+                .register_put_handler("steering.autopilot.state", Box::new(|_| Ok(())))
+                .unwrap();
+            store.apply_delta(&self_delta(&[
+                ("navigation.speedOverGround", serde_json::json!(3.5)),
+                (
+                    "electrical.batteries.house.voltage",
+                    serde_json::json!(12.6),
+                ),
+            ]));
+        }
+        let addr = spawn_routes_with_state(data_routes(), state.clone()).await;
+        let bearer = |token: &str| format!("Host: localhost\r\nAuthorization: Bearer {token}\r\n");
+        let get_as = |token: &str, path: &str| {
+            let headers = bearer(token);
+            let path = path.to_string();
+            async move { get_with_headers(addr, &path, &headers).await.0 }
+        };
+
+        let vessel = format!(
+            "/signalk/v1/api/{}",
+            state.config.self_urn.replace('.', "/")
+        );
+        let sog = &format!("{vessel}/navigation/speedOverGround");
+        let voltage = &format!("{vessel}/electrical/batteries/house/voltage");
+        assert_eq!(get_as(&guest, sog).await, 200);
+        assert_eq!(get_as(&guest, voltage).await, 403);
+        assert_eq!(get_as(&guest, &format!("{voltage}/meta")).await, 403);
+        assert_eq!(get_as(&guest, &vessel).await, 403);
+        assert_eq!(get_as(&guest, "/signalk/v1/api").await, 403);
+        assert_eq!(get_as(&admin, voltage).await, 200);
+        assert_eq!(get_as(&admin, &vessel).await, 200);
+
+        // Only admins may steer
+        let autopilot = "/signalk/v1/api/vessels/self/steering/autopilot/state";
+        let crew = AppState {
+            acl: state.acl.clone(),
+            ..test_state()
+        };
+        let resolved = Extension(ResolvedPermission(Permission::ReadWrite));
+        let body = PutBody {
+            value: serde_json::json!("auto"),
+            source: None,
+        };
+        let response = put_path_handler(
+            Path(autopilot.trim_start_matches("/signalk/v1/api/").to_string()),
+            State(crew),
+            Some(resolved),
+            Json(body),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let (status, _) = send_request(
+            addr,
+            "PUT",
+            autopilot,
+            &bearer(&admin),
+            r#"{"value": "auto"}"#,
+        )
+        .await;
+        assert_eq!(status, 200);
+
+        // The stream leaves out the hidden values
+        let (mut ws, _) = connect_stream(addr, "self", Some(&guest)).await;
+        let (mut admin_ws, _) = connect_stream(addr, "self", Some(&admin)).await;
+        let _ = state.delta_tx.send(BroadcastDelta::new(self_delta(&[
+            (
+                "electrical.batteries.house.voltage",
+                serde_json::json!(12.7),
+            ),
+            ("navigation.speedOverGround", serde_json::json!(3.6)),
+        ])));
+        let delta = next_json(&mut ws).await;
+        let values = &delta["updates"][0]["values"];
+        assert_eq!(values.as_array().unwrap().len(), 1);
+        assert_eq!(values[0]["path"], "navigation.speedOverGround");
+        let delta = next_json(&mut admin_ws).await;
+        assert_eq!(delta["updates"][0]["values"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pings_dont_keep_idle_stream_open() {
+        let mut state = test_state();
+        state.config.idle_timeout_ms = Some(300);
+        let addr = spawn_routes_with_state(data_routes(), state).await;
+        let (mut ws, _) = connect_stream(addr, "none", None).await;
+        let start = Instant::now();
+
+        // Pings more often than the timeout, and nothing else
+        let mut pings = tokio::time::interval(std::time::Duration::from_millis(100));
+        let warning = loop {
+            tokio::select! {
+                _ = pings.tick() => ws.send(Message::Ping(vec![1])).await.unwrap(),
+                msg = ws.next() => match msg {
+                    Some(Ok(Message::Pong(_))) => {}
+                    Some(Ok(Message::Text(text))) => break text,
+                    other => panic!("Expected pongs and an idle warning, got {other:?}"),
+                },
+            }
+        };
+        assert!(warning.contains("idle"), "{warning}");
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_full_queue_coalesces_delta() {
+        let (out_tx, mut out_rx) = mpsc::channel(1);
+        let mut coalescer = DeltaCoalescer::new();
+        let delta = self_delta(&[("navigation.speedOverGround", serde_json::json!(3.5))]);
+
+        // A reply took the last slot
+        out_tx
+            .try_send(vec![Message::Text("reply".into())])
+            .unwrap();
+        let sent = send_or_coalesce(&out_tx, &mut coalescer, "{}".into(), Cow::Borrowed(&delta));
+        assert_eq!(sent, Ok(false));
+        assert_eq!(coalescer.len(), 1);
+
+        out_rx.try_recv().unwrap();
+        let sent = send_or_coalesce(&out_tx, &mut coalescer, "{}".into(), Cow::Borrowed(&delta));
+        assert_eq!(sent, Ok(true));
+
+        drop(out_rx);
+        let sent = send_or_coalesce(&out_tx, &mut coalescer, "{}".into(), Cow::Borrowed(&delta));
+        assert_eq!(sent, Err(()));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_param_filters_like_set_mode() {
+        let state = test_state();
+        let addr = spawn_routes_with_state(data_routes(), state.clone()).await;
+        let (mut none, _) = connect_stream(addr, "none", None).await;
+        let (mut own, _) = connect_stream(addr, "self", None).await;
+
+        let mut other = self_delta(&[("navigation.speedOverGround", serde_json::json!(1.0))]);
+        other.context = Some("vessels.urn:mrn:imo:mmsi:244000000".to_string());
+        let _ = state.delta_tx.send(BroadcastDelta::new(other));
+        let own_delta = self_delta(&[("navigation.speedOverGround", serde_json::json!(3.5))]);
+        let _ = state.delta_tx.send(BroadcastDelta::new(own_delta));
+
+        // `self` skips the other vessel
+        let delta = next_json(&mut own).await;
+        assert_eq!(delta["updates"][0]["values"][0]["value"], 3.5);
+
+        // `none` gets nothing before the warning it asks for
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        none.send(Message::Text(r#"{"mode": "bogus"}"#.to_string()))
+            .await
+            .unwrap();
+        let warning = next_json(&mut none).await;
+        assert_eq!(warning, "Unknown subscribe mode bogus, ignoring");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_mode_warns_and_keeps_subscriptions() {
+        let state = test_state();
+        let addr = spawn_routes_with_state(data_routes(), state.clone()).await;
+        let (mut ws, _) = connect_stream(addr, "all", None).await;
+
+        ws.send(Message::Text(r#"{"mode": "bogus"}"#.to_string()))
+            .await
+            .unwrap();
+        let warning = next_json(&mut ws).await;
+        assert_eq!(warning, "Unknown subscribe mode bogus, ignoring");
+
+        // Still subscribed to everything
+        let delta = self_delta(&[("navigation.speedOverGround", serde_json::json!(3.5))]);
+        let _ = state.delta_tx.send(BroadcastDelta::new(delta));
+        let delta = next_json(&mut ws).await;
+        assert_eq!(delta["updates"][0]["values"][0]["value"], 3.5);
+    }
+
+    #[tokio::test]
+    async fn test_streams_follow_self_urn_change() {
+        let state = test_state();
+        let new_urn = "vessels.urn:mrn:signalk:uuid:c0d79334-4e25-4245-8892-54e8ccc8021d";
+        state.store.write().set_self_urn(new_urn);
+        state.self_context.set(new_urn);
+        let addr = spawn_routes_with_state(data_routes(), state.clone()).await;
+
+        let (mut ws, hello) = connect_stream(addr, "self", None).await;
+        assert_eq!(hello["self"], new_urn);
+        ws.send(Message::Text(r#"{"mode": "self"}"#.to_string()))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let mut delta = self_delta(&[("navigation.speedOverGround", serde_json::json!(3.5))]);
+        delta.context = Some(new_urn.to_string());
+        let _ = state.delta_tx.send(BroadcastDelta::new(delta));
+        let delta = next_json(&mut ws).await;
+        assert_eq!(delta["context"], new_urn);
+
+        // PUTs go to the new URN, not the one the server started with
+        let put = |urn: &str| {
+            let path = format!("{}/steering/autopilot/state", urn.replace('.', "/"));
+            let state = state.clone();
+            async move {
+                let resolved = Extension(ResolvedPermission(Permission::Admin));
+                let body = PutBody {
+                    value: serde_json::json!("auto"),
+                    source: None,
+                };
+                put_path_handler(Path(path), State(state), Some(resolved), Json(body))
+                    .await
+                    .status()
+            }
+        };
+        assert_eq!(put(&state.config.self_urn).await, StatusCode::FORBIDDEN);
+        assert_ne!(put(new_urn).await, StatusCode::FORBIDDEN);
+    }
+
+    /// Log output captured by a test's subscriber.
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
         }
     }
+
+    impl LogBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+        }
+
+        fn contains_subscription_events(&self) -> bool {
+            self.contents().contains("Subscription ")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_debug_key_enables_subscription_logs() {
+        let (filter_layer, log_filter) = LogFilter::new(DEFAULT_LOG_FILTER);
+        let logs = LogBuffer::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::registry().with(filter_layer).with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut state = test_state();
+        let log_filter = Arc::new(log_filter);
+        Arc::get_mut(&mut state.web_state).unwrap().debug = Some(log_filter.clone());
+        let addr = spawn_routes_with_state(data_routes().merge(admin_routes()), state).await;
+        let host = "Host: localhost\r\n";
+        let change_mode = || async {
+            let (mut ws, _) = connect_stream(addr, "none", None).await;
+            ws.send(Message::Text(r#"{"mode": "self"}"#.to_string()))
+                .await
+                .unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        };
+
+        // Quiet by default, although the rest of the server logs at debug
+        change_mode().await;
+        assert!(!logs.contains_subscription_events());
+
+        let (status, body) = get_with_headers(addr, "/skServer/debugKeys", host).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(SUBSCRIPTION_DEBUG_KEY), "{body}");
+        let enable = format!(r#"{{"enable": ["{SUBSCRIPTION_DEBUG_KEY}"]}}"#);
+        let (status, _) = send_request(addr, "POST", "/skServer/debug", host, &enable).await;
+        assert_eq!(status, 200);
+        assert_eq!(log_filter.enabled(), [SUBSCRIPTION_DEBUG_KEY]);
+
+        change_mode().await;
+        let output = logs.contents();
+        assert!(output.contains("Subscription subscribe"), "{output}");
+        assert!(output.contains("Subscription mode"), "{output}");
+
+        let unknown = r#"{"enable": ["signalk-server:nothing"]}"#;
+        let (status, _) = send_request(addr, "POST", "/skServer/debug", host, unknown).await;
+        assert_eq!(status, 400);
+        let disable = format!(r#"{{"disable": ["{SUBSCRIPTION_DEBUG_KEY}"]}}"#);
+        let (status, _) = send_request(addr, "POST", "/skServer/debug", host, &disable).await;
+        assert_eq!(status, 200);
+        assert!(log_filter.enabled().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_put_needs_readwrite() {
+        let (state, admin, guest) = secured_state().await;
+        let addr = spawn_routes_with_state(data_routes(), state.clone()).await;
+        let put = |token: String| async move {
+            let (mut ws, hello) = connect_stream(addr, "none", Some(&token)).await;
+            let put = serde_json::json!({
+                "requestId": "1",
+                "context": "vessels.self",
+                "put": {"path": "electrical.switches.deck.state", "value": 1}
+            });
+            ws.send(Message::Text(put.to_string())).await.unwrap();
+            let response = next_json(&mut ws).await;
+            (hello["capabilities"]["put"].clone(), response)
+        };
+
+        let (can_put, response) = put(guest).await;
+        assert_eq!(can_put, false);
+        assert_eq!(response["requestId"], "1");
+        assert_eq!(response["statusCode"], 403);
+        assert!(state
+            .store
+            .read()
+            .get_self_path("electrical.switches.deck.state")
+            .is_none());
+
+        let (can_put, response) = put(admin).await;
+        assert_eq!(can_put, true);
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(
+            state
+                .store
+                .read()
+                .get_self_path("electrical.switches.deck.state")
+                .unwrap()["value"],
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_negotiates_compression() {
+        let mut state = test_state();
+        state.config.ws_compression_threshold = Some(0);
+        let addr = spawn_routes_with_state(data_routes(), state).await;
+
+        let (_, head, frame) = open_stream(addr, Some("permessage-deflate")).await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        assert!(head.contains(signalk_server::DEFLATE_RESPONSE), "{head}");
+        // A compressed text frame (RSV1 set) holding the hello
+        assert_eq!(frame[0], 0x80 | 0x40 | 0x1);
+        let payload_start = if frame[1] & 0x7f == 126 { 4 } else { 2 };
+        let mut compressed = frame[payload_start..].to_vec();
+        compressed.extend_from_slice(&[0x00, 0x00, 0xff, 0xff]);
+        let mut hello = Vec::with_capacity(64 * 1024);
+        flate2::Decompress::new(false)
+            .decompress_vec(&compressed, &mut hello, flate2::FlushDecompress::Sync)
+            .unwrap();
+        let hello: serde_json::Value = serde_json::from_slice(&hello).unwrap();
+        assert_eq!(hello["name"], "signalk-server-rust");
+        assert_eq!(hello["capabilities"]["compression"], true);
+
+        // Clients that don't offer the extension get plain frames
+        let (_, head, frame) = open_stream(addr, None).await;
+        assert!(!head.contains("permessage-deflate"), "{head}");
+        assert_eq!(frame[0], 0x80 | 0x1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_compression_off_by_default() {
+        let addr = spawn_routes(data_routes()).await;
+        let (_, head, frame) = open_stream(addr, Some("permessage-deflate")).await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        assert!(!head.contains("permessage-deflate"), "{head}");
+        assert_eq!(frame[0], 0x80 | 0x1);
+    }
+
+    #[tokio::test]
+    async fn test_connections_list_clients() {
+        let state = test_state();
+        let addr = spawn_routes_with_state(data_routes(), state.clone()).await;
+
+        let (first, _, _) = open_stream(addr, None).await;
+        let (second, _, _) = open_stream(addr, None).await;
+        let Json(connections) = connections_handler(State(state.clone())).await;
+        let mut addresses: Vec<String> = connections
+            .iter()
+            .filter_map(|client| client.remote_address.clone())
+            .collect();
+        addresses.sort();
+        let mut expected = vec![
+            first.local_addr().unwrap().to_string(),
+            second.local_addr().unwrap().to_string(),
+        ];
+        expected.sort();
+        assert_eq!(addresses, expected);
+        assert_ne!(connections[0].id, connections[1].id);
+        // The hello has been sent to each
+        assert!(connections.iter().all(|client| client.bytes_sent > 0));
+
+        // A client that just goes away is removed
+        drop(first);
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while state.web_state.statistics.connections().len() > 1 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timed out waiting for the client to be removed");
+        let Json(connections) = connections_handler(State(state)).await;
+        assert_eq!(
+            connections[0].remote_address,
+            Some(second.local_addr().unwrap().to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_websockets() {
+        let state = test_state();
+        let addr = spawn_routes_with_state(data_routes(), state.clone()).await;
+        let (mut stream, _, _) = open_stream(addr, None).await;
+
+        state.shutdown.send_replace(true);
+        let mut frame = Vec::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            let mut chunk = [0; 256];
+            while frame.len() < 4 {
+                let n = stream.read(&mut chunk).await.unwrap();
+                assert!(n > 0, "connection closed without a close frame");
+                frame.extend_from_slice(&chunk[..n]);
+            }
+        })
+        .await
+        .expect("Timed out waiting for the close frame");
+        // Close opcode, then status 1001 (going away)
+        assert_eq!(frame[0], 0x88);
+        assert_eq!(u16::from_be_bytes([frame[2], frame[3]]), 1001);
+
+        // The connection lets go once it has closed
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while state.shutdown.receiver_count() > 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timed out waiting for the connection to finish");
+    }
+
+    #[tokio::test]
+    async fn test_https_listener() {
+        let dir = std::env::temp_dir().join(format!("signalk-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(dir.join("cert.pem"), certified.cert.pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), certified.key_pair.serialize_pem()).unwrap();
+        let tls = tls_config_from_files(&dir.join("cert.pem"), &dir.join("key.pem")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_tls(listener, data_routes(), test_state(), tls));
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(certified.cert.der().clone()).unwrap();
+        let client = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server_name = rustls::pki_types::ServerName::try_from("localhost").unwrap();
+        let mut stream = tokio_rustls::TlsConnector::from(Arc::new(client))
+            .connect(server_name, tcp)
+            .await
+            .expect("TLS handshake");
+
+        let request = format!(
+            "GET /signalk HTTP/1.1\r\nHost: localhost:{}\r\nConnection: close\r\n\r\n",
+            addr.port()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        // The server may close without a TLS close_notify
+        let _ = stream.read_to_string(&mut response).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        let port = addr.port();
+        assert!(
+            response.contains(&format!("wss://localhost:{port}/signalk/v1/stream")),
+            "{response}"
+        );
+        assert!(
+            response.contains(&format!("https://localhost:{port}/signalk/v1/api\"")),
+            "{response}"
+        );
+    }
+
+    #[test]
+    fn test_missing_certificate_is_an_error() {
+        let missing = std::env::temp_dir().join("signalk-no-such-cert.pem");
+        let error = tls_config_from_files(&missing, &missing).unwrap_err();
+        assert!(
+            error.to_string().contains("Could not read SSL certificate"),
+            "{error}"
+        );
+
+        let dir = std::env::temp_dir().join(format!("signalk-bad-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cert.pem"), "not a certificate").unwrap();
+        let error = tls_config_from_files(&dir.join("cert.pem"), &missing).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            error.to_string().contains("No certificate found"),
+            "{error}"
+        );
+    }
 }
@@ -0,0 +1,151 @@
+//! Concurrent read/write throughput: one `RwLock<MemoryStore>` vs
+//! [`SharedStore`].
+//!
+//! One writer feeds the self vessel, the others feed AIS targets, while
+//! reader threads serialize the full model (as REST clients and newly
+//! connected WebSocket clients do). Run with
+//! `cargo bench -p signalk-core --bench store_contention`.
+//!
+//! Contention only shows with several cores; on a single core the threads
+//! are time-sliced and the numbers mostly reflect scheduling.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signalk_core::{Delta, MemoryStore, PathValue, SharedStore, SignalKStore, Update};
+
+const SELF_URN: &str = "vessels.urn:mrn:signalk:uuid:bench";
+const AIS_WRITERS: usize = 3;
+const READERS: usize = 2;
+const RUN_FOR: Duration = Duration::from_secs(2);
+
+/// The operations the benchmark needs from either store.
+trait Store: Send + Sync + 'static {
+    fn write(&self, delta: &Delta);
+    /// Serialize the full model, returning its length.
+    fn read(&self) -> usize;
+}
+
+impl Store for RwLock<MemoryStore> {
+    fn write(&self, delta: &Delta) {
+        self.write().unwrap().apply_delta(delta);
+    }
+
+    fn read(&self) -> usize {
+        // Writers wait until the model is serialized
+        let store = self.read().unwrap();
+        serde_json::to_string(store.full_model()).unwrap().len()
+    }
+}
+
+impl Store for SharedStore {
+    fn write(&self, delta: &Delta) {
+        self.write().apply_delta(delta);
+    }
+
+    fn read(&self) -> usize {
+        let view = self.view();
+        serde_json::to_string(view.full_model()).unwrap().len()
+    }
+}
+
+fn delta(context: String, i: u64) -> Delta {
+    Delta {
+        context: Some(context),
+        updates: vec![Update {
+            source_ref: Some("bench.1".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(i as f64 * 0.01),
+                },
+                PathValue {
+                    path: "navigation.position".to_string(),
+                    value: serde_json::json!({ "latitude": 52.0, "longitude": 4.9 }),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    }
+}
+
+/// Run writers and readers against `store`; returns (writes/s, reads/s).
+fn run<S: Store>(store: Arc<S>) -> (f64, f64) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let writes = Arc::new(AtomicU64::new(0));
+    let reads = Arc::new(AtomicU64::new(0));
+    let mut threads = Vec::new();
+
+    for writer in 0..=AIS_WRITERS {
+        let (store, stop, writes) = (store.clone(), stop.clone(), writes.clone());
+        threads.push(thread::spawn(move || {
+            let mut i = 0;
+            while !stop.load(Ordering::Relaxed) {
+                let context = if writer == 0 {
+                    "vessels.self".to_string()
+                } else {
+                    // A few hundred AIS targets per writer
+                    format!(
+                        "vessels.urn:mrn:imo:mmsi:{}",
+                        writer as u64 * 1000 + i % 300
+                    )
+                };
+                store.write(&delta(context, i));
+                i += 1;
+            }
+            writes.fetch_add(i, Ordering::Relaxed);
+        }));
+    }
+
+    for _ in 0..READERS {
+        let (store, stop, reads) = (store.clone(), stop.clone(), reads.clone());
+        threads.push(thread::spawn(move || {
+            let mut n = 0;
+            while !stop.load(Ordering::Relaxed) {
+                std::hint::black_box(store.read());
+                n += 1;
+            }
+            reads.fetch_add(n, Ordering::Relaxed);
+        }));
+    }
+
+    let start = Instant::now();
+    thread::sleep(RUN_FOR);
+    stop.store(true, Ordering::Relaxed);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let secs = start.elapsed().as_secs_f64();
+
+    (
+        writes.load(Ordering::Relaxed) as f64 / secs,
+        reads.load(Ordering::Relaxed) as f64 / secs,
+    )
+}
+
+fn main() {
+    println!(
+        "{} writers ({} AIS + self), {} full-model readers, {:?} per run",
+        AIS_WRITERS + 1,
+        AIS_WRITERS,
+        READERS,
+        RUN_FOR
+    );
+
+    let (locked_writes, locked_reads) = run(Arc::new(RwLock::new(MemoryStore::new(SELF_URN))));
+    println!("RwLock<MemoryStore>: {locked_writes:>12.0} writes/s {locked_reads:>12.0} reads/s");
+
+    let (shared_writes, shared_reads) = run(Arc::new(SharedStore::new(MemoryStore::new(SELF_URN))));
+    println!("SharedStore:         {shared_writes:>12.0} writes/s {shared_reads:>12.0} reads/s");
+
+    println!(
+        "speedup: {:.2}x writes, {:.2}x reads",
+        shared_writes / locked_writes,
+        shared_reads / locked_reads
+    );
+}
@@ -0,0 +1,219 @@
+//! Per-path access control.
+//!
+//! Global permissions only say whether a client may read or write at all.
+//! A [`PathAcl`] additionally requires a minimum [`Permission`] to read or
+//! write paths matching a pattern, e.g. only admins may PUT to
+//! `steering.autopilot.*`, and read-only clients may not see
+//! `electrical.*`. Paths no rule matches need no more than the global
+//! permission.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::Delta;
+use crate::path::{PathPattern, PatternError};
+
+/// Permission level of a client, lowest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    ReadOnly,
+    ReadWrite,
+    Admin,
+}
+
+/// Minimum permissions for paths matching a pattern.
+///
+/// ```json
+/// { "path": "steering.autopilot.*", "write": "admin" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AclRule {
+    /// Path pattern, in subscription syntax.
+    pub path: String,
+    /// Permission needed to read matching paths.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read: Option<Permission>,
+    /// Permission needed to write matching paths.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write: Option<Permission>,
+}
+
+/// Compiled ACL table.
+#[derive(Debug, Clone, Default)]
+pub struct PathAcl {
+    rules: Vec<(PathPattern, AclRule)>,
+}
+
+impl PathAcl {
+    /// Compile the given rules.
+    pub fn new(rules: &[AclRule]) -> Result<Self, PatternError> {
+        let rules = rules
+            .iter()
+            .map(|rule| Ok((PathPattern::new(&rule.path)?, rule.clone())))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// A table that reserves every path to admins.
+    pub fn admin_only() -> Self {
+        Self::new(&[AclRule {
+            path: "*".to_string(),
+            read: Some(Permission::Admin),
+            write: Some(Permission::Admin),
+        }])
+        .expect("catch-all pattern is valid")
+    }
+
+    /// Check whether a client with `permission` may read `path`.
+    ///
+    /// Every matching rule must be satisfied.
+    pub fn can_read(&self, permission: Permission, path: &str) -> bool {
+        self.rules.iter().all(|(pattern, rule)| {
+            rule.read.map_or(true, |needed| permission >= needed) || !pattern.matches(path)
+        })
+    }
+
+    /// Check whether a client with `permission` may write `path`.
+    ///
+    /// Writing always needs [`Permission::ReadWrite`]; matching rules can
+    /// raise that.
+    pub fn can_write(&self, permission: Permission, path: &str) -> bool {
+        permission >= Permission::ReadWrite
+            && self.rules.iter().all(|(pattern, rule)| {
+                rule.write.map_or(true, |needed| permission >= needed) || !pattern.matches(path)
+            })
+    }
+
+    /// Whether some path is hidden from clients with `permission`.
+    pub fn restricts_reads(&self, permission: Permission) -> bool {
+        self.rules
+            .iter()
+            .any(|(_, rule)| rule.read.is_some_and(|needed| permission < needed))
+    }
+
+    /// Drop the values a client with `permission` may not read.
+    ///
+    /// Updates left without values (and without meta) are removed; returns
+    /// `None` if nothing is left.
+    pub fn filter_readable(&self, permission: Permission, mut delta: Delta) -> Option<Delta> {
+        if !self.restricts_reads(permission) {
+            return Some(delta);
+        }
+        for update in &mut delta.updates {
+            update
+                .values
+                .retain(|pv| self.can_read(permission, &pv.path));
+            if let Some(meta) = &mut update.meta {
+                meta.retain(|pm| self.can_read(permission, &pm.path));
+            }
+        }
+        delta.updates.retain(|update| {
+            !update.values.is_empty() || update.meta.as_ref().is_some_and(|m| !m.is_empty())
+        });
+        (!delta.updates.is_empty()).then_some(delta)
+    }
+
+    /// Drop the values a client with `permission` may not write, as
+    /// [`filter_readable`](Self::filter_readable) does for reads.
+    pub fn filter_writable(&self, permission: Permission, mut delta: Delta) -> Option<Delta> {
+        for update in &mut delta.updates {
+            update
+                .values
+                .retain(|pv| self.can_write(permission, &pv.path));
+            if let Some(meta) = &mut update.meta {
+                meta.retain(|pm| self.can_write(permission, &pm.path));
+            }
+        }
+        delta.updates.retain(|update| {
+            !update.values.is_empty() || update.meta.as_ref().is_some_and(|m| !m.is_empty())
+        });
+        (!delta.updates.is_empty()).then_some(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{PathValue, Update};
+
+    fn acl() -> PathAcl {
+        PathAcl::new(&[
+            AclRule {
+                path: "steering.autopilot.*".to_string(),
+                read: None,
+                write: Some(Permission::Admin),
+            },
+            AclRule {
+                path: "electrical.*".to_string(),
+                read: Some(Permission::ReadWrite),
+                write: None,
+            },
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_acl_permissions() {
+        let acl = acl();
+
+        assert!(acl.can_write(Permission::Admin, "steering.autopilot.target.headingTrue"));
+        assert!(!acl.can_write(
+            Permission::ReadWrite,
+            "steering.autopilot.target.headingTrue"
+        ));
+        assert!(acl.can_write(
+            Permission::ReadWrite,
+            "electrical.switches.anchorLight.state"
+        ));
+        assert!(!acl.can_write(Permission::ReadOnly, "navigation.anchor.maxRadius"));
+
+        assert!(acl.can_read(Permission::ReadOnly, "steering.autopilot.state"));
+        assert!(!acl.can_read(Permission::ReadOnly, "electrical.batteries.house.voltage"));
+        assert!(acl.can_read(Permission::ReadWrite, "electrical.batteries.house.voltage"));
+        assert!(acl.restricts_reads(Permission::ReadOnly));
+        assert!(!acl.restricts_reads(Permission::ReadWrite));
+    }
+
+    #[test]
+    fn test_filter_readable() {
+        let delta = Delta {
+            context: None,
+            updates: vec![Update {
+                source_ref: None,
+                source: None,
+                timestamp: None,
+                values: vec![
+                    PathValue {
+                        path: "electrical.batteries.house.voltage".to_string(),
+                        value: serde_json::json!(12.8),
+                    },
+                    PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(3.2),
+                    },
+                ],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+
+        let filtered = acl()
+            .filter_readable(Permission::ReadOnly, delta.clone())
+            .unwrap();
+        assert_eq!(filtered.updates[0].values.len(), 1);
+        assert_eq!(
+            filtered.updates[0].values[0].path,
+            "navigation.speedOverGround"
+        );
+        assert_eq!(
+            acl().filter_readable(Permission::Admin, delta.clone()),
+            Some(delta.clone())
+        );
+
+        assert_eq!(
+            acl().filter_writable(Permission::ReadWrite, delta.clone()),
+            Some(delta.clone())
+        );
+        assert_eq!(acl().filter_writable(Permission::ReadOnly, delta), None);
+    }
+}
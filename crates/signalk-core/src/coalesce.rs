@@ -0,0 +1,212 @@
+//! Coalescing of split latitude/longitude into `navigation.position`.
+//!
+//! Some providers report `navigation.position.latitude` and
+//! `navigation.position.longitude` as separate values, sometimes in separate
+//! deltas, so clients briefly see a position with one half updated.
+//! [`PositionCoalescer`] holds back each half until its counterpart from the
+//! same context and source arrives and emits both together as a single
+//! `navigation.position` object.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::model::{Delta, PathValue};
+
+/// Path of the combined position object.
+pub const POSITION_PATH: &str = "navigation.position";
+/// Path of a separately reported latitude.
+pub const LATITUDE_PATH: &str = "navigation.position.latitude";
+/// Path of a separately reported longitude.
+pub const LONGITUDE_PATH: &str = "navigation.position.longitude";
+
+/// One half of a position waiting for the other.
+#[derive(Debug)]
+struct PendingHalf {
+    latitude: Option<Value>,
+    longitude: Option<Value>,
+    at: Instant,
+}
+
+/// Combines separately reported latitude and longitude.
+///
+/// Halves are matched per (context, `$source`). A half that doesn't find its
+/// counterpart within the window is dropped, so a provider that only ever
+/// sends one half produces no position at all.
+#[derive(Debug)]
+pub struct PositionCoalescer {
+    window: Duration,
+    pending: HashMap<(String, Option<String>), PendingHalf>,
+}
+
+impl PositionCoalescer {
+    /// Create a coalescer pairing halves that arrive within `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// The pairing window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Rewrite a delta so latitude/longitude only appear as a combined
+    /// `navigation.position` value.
+    ///
+    /// Returns `None` if nothing is left to store, i.e. the delta only
+    /// carried a half that is now pending.
+    pub fn coalesce(&mut self, mut delta: Delta, now: Instant) -> Option<Delta> {
+        let context = delta.context.as_deref().unwrap_or("vessels.self");
+        // Halves that never found their counterpart
+        let window = self.window;
+        self.pending
+            .retain(|_, pending| now.duration_since(pending.at) <= window);
+
+        for update in &mut delta.updates {
+            let mut latitude = None;
+            let mut longitude = None;
+            update.values.retain(|pv| match pv.path.as_str() {
+                LATITUDE_PATH => {
+                    latitude = Some(pv.value.clone());
+                    false
+                }
+                LONGITUDE_PATH => {
+                    longitude = Some(pv.value.clone());
+                    false
+                }
+                _ => true,
+            });
+            if latitude.is_none() && longitude.is_none() {
+                continue;
+            }
+
+            let key = (context.to_string(), update.source_ref.clone());
+            let mut at = now;
+            if let Some(pending) = self.pending.remove(&key) {
+                // A repeated half replaces the pending one but keeps its age
+                if latitude.is_none() || longitude.is_none() {
+                    at = pending.at;
+                }
+                latitude = latitude.or(pending.latitude);
+                longitude = longitude.or(pending.longitude);
+            }
+
+            match (latitude, longitude) {
+                (Some(latitude), Some(longitude)) => update.values.push(PathValue {
+                    path: POSITION_PATH.to_string(),
+                    value: serde_json::json!({ "latitude": latitude, "longitude": longitude }),
+                }),
+                (latitude, longitude) => {
+                    self.pending.insert(
+                        key,
+                        PendingHalf {
+                            latitude,
+                            longitude,
+                            at,
+                        },
+                    );
+                }
+            }
+        }
+
+        delta
+            .updates
+            .retain(|update| !update.values.is_empty() || update.meta.is_some());
+        (!delta.updates.is_empty()).then_some(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Update;
+    use crate::store::{MemoryStore, SignalKStore};
+
+    fn half(path: &str, value: f64) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("nmea0183.GP".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: path.to_string(),
+                    value: serde_json::json!(value),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_split_lat_lon_coalesced() {
+        let mut coalescer = PositionCoalescer::new(Duration::from_millis(500));
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        let start = Instant::now();
+
+        // Latitude alone is held back
+        assert!(coalescer
+            .coalesce(half(LATITUDE_PATH, 52.1), start)
+            .is_none());
+
+        let delta = coalescer
+            .coalesce(
+                half(LONGITUDE_PATH, 4.9),
+                start + Duration::from_millis(100),
+            )
+            .unwrap();
+        store.apply_delta(&delta);
+
+        let position = store.get_self_path(POSITION_PATH).unwrap();
+        assert_eq!(
+            position["value"],
+            serde_json::json!({ "latitude": 52.1, "longitude": 4.9 })
+        );
+        assert_eq!(position["$source"], "nmea0183.GP");
+        assert!(store.get_self_path(LATITUDE_PATH).is_none());
+    }
+
+    #[test]
+    fn test_coalesce_same_delta_and_window() {
+        let mut coalescer = PositionCoalescer::new(Duration::from_millis(500));
+        let start = Instant::now();
+
+        // Both halves in one update combine immediately; other values stay
+        let mut delta = half(LATITUDE_PATH, 52.1);
+        delta.updates[0].values.extend([
+            PathValue {
+                path: LONGITUDE_PATH.to_string(),
+                value: serde_json::json!(4.9),
+            },
+            PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(3.2),
+            },
+        ]);
+        let values = &coalescer.coalesce(delta, start).unwrap().updates[0].values;
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].path, "navigation.speedOverGround");
+        assert_eq!(values[1].path, POSITION_PATH);
+
+        // A half outside the window is not paired
+        assert!(coalescer
+            .coalesce(half(LATITUDE_PATH, 52.1), start)
+            .is_none());
+        assert!(coalescer
+            .coalesce(half(LONGITUDE_PATH, 4.9), start + Duration::from_secs(1))
+            .is_none());
+
+        // An unpaired half expires even if its counterpart never comes
+        let mut other = half(LATITUDE_PATH, 53.0);
+        other.context = Some("vessels.urn:mrn:imo:mmsi:244000000".to_string());
+        assert!(coalescer
+            .coalesce(other, start + Duration::from_secs(2))
+            .is_none());
+        assert_eq!(coalescer.pending.len(), 1);
+    }
+}
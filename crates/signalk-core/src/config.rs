@@ -8,8 +8,12 @@
 //! By abstracting storage, REST API handler logic can be shared
 //! between platforms.
 
+use crate::priorities::SourcePriorities;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::time::Duration;
 
 /// Errors that can occur during configuration operations.
 #[derive(Debug)]
@@ -129,6 +133,17 @@ pub struct ServerSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub port: Option<u16>,
 
+    /// Listen address for the Signal K data routes (`/signalk/...`);
+    /// overrides `port`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_address: Option<SocketAddr>,
+
+    /// Listen address for the admin routes (`/skServer/...`, `/admin`),
+    /// e.g. `127.0.0.1:4001` to keep administration local. Shares the data
+    /// listener when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_address: Option<SocketAddr>,
+
     /// HTTPS port.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sslport: Option<u16>,
@@ -137,10 +152,25 @@ pub struct ServerSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ssl: Option<bool>,
 
+    /// PEM certificate chain for the HTTPS listener, relative to the
+    /// config directory (default [`DEFAULT_SSL_CERT_FILE`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssl_cert_file: Option<String>,
+
+    /// PEM private key for the HTTPS listener, relative to the config
+    /// directory (default [`DEFAULT_SSL_KEY_FILE`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssl_key_file: Option<String>,
+
     /// Enable WebSocket compression.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ws_compression: Option<bool>,
 
+    /// Smallest WebSocket message, in bytes, that is sent compressed
+    /// (default [`DEFAULT_WS_COMPRESSION_THRESHOLD`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws_compression_threshold: Option<usize>,
+
     /// Enable mDNS discovery.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mdns: Option<bool>,
@@ -168,6 +198,248 @@ pub struct ServerSettings {
     /// Enable plugin logging.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enable_plugin_logging: Option<bool>,
+
+    /// Path patterns clients may PUT to; `None` uses
+    /// [`DEFAULT_WRITABLE_PATHS`](crate::DEFAULT_WRITABLE_PATHS) and an
+    /// empty list makes everything read-only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub writable_paths: Option<Vec<String>>,
+
+    /// Path patterns whose object values are deep-merged instead of
+    /// replaced; `None` uses
+    /// [`DEFAULT_MERGE_PATHS`](crate::DEFAULT_MERGE_PATHS).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_paths: Option<Vec<String>>,
+
+    /// Preferred sources per path pattern, deciding which source's value is
+    /// primary when several report a path; none by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_priorities: Option<SourcePriorities>,
+
+    /// Seconds between store snapshots (default
+    /// [`DEFAULT_SNAPSHOT_INTERVAL_SECS`]); 0 disables them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_interval_secs: Option<u64>,
+
+    /// TCP server to read NMEA 0183 sentences from (e.g. a multiplexer on
+    /// port 10110).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nmea0183_tcp_address: Option<SocketAddr>,
+
+    /// UDP address to receive NMEA 0183 datagrams on; a multicast address
+    /// (e.g. `239.2.1.1:10110`) joins that group.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nmea0183_udp_address: Option<SocketAddr>,
+
+    /// Settings this server doesn't use, such as those of the Node.js
+    /// server; kept so saving the settings page doesn't drop them.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ServerSettings {
+    /// These settings with the defaults filled in for what was never set,
+    /// as the settings page shows them.
+    pub fn with_defaults(&self) -> Self {
+        Self {
+            interfaces: self.interfaces.clone().or(Some(InterfaceSettings {
+                appstore: Some(true),
+                plugins: Some(true),
+                rest: Some(true),
+                signalk_ws: Some(true),
+                tcp: Some(false),
+                webapps: Some(true),
+            })),
+            port: self.port.or(Some(DEFAULT_PORT)),
+            ssl: self.ssl.or(Some(false)),
+            ws_compression: self.ws_compression.or(Some(false)),
+            ws_compression_threshold: self
+                .ws_compression_threshold
+                .or(Some(DEFAULT_WS_COMPRESSION_THRESHOLD)),
+            access_logging: self.access_logging.or(Some(false)),
+            mdns: self.mdns.or(Some(true)),
+            prune_contexts_minutes: self.prune_contexts_minutes.or(Some(60)),
+            logging_directory: self
+                .logging_directory
+                .clone()
+                .or(Some("~/.signalk/logs".to_string())),
+            keep_most_recent_logs_only: self.keep_most_recent_logs_only.or(Some(true)),
+            log_count_to_keep: self.log_count_to_keep.or(Some(24)),
+            enable_plugin_logging: self.enable_plugin_logging.or(Some(true)),
+            writable_paths: Some(self.writable_paths()),
+            merge_paths: Some(self.merge_paths()),
+            snapshot_interval_secs: self
+                .snapshot_interval_secs
+                .or(Some(DEFAULT_SNAPSHOT_INTERVAL_SECS)),
+            ..self.clone()
+        }
+    }
+
+    /// The writable path patterns, falling back to the defaults.
+    pub fn writable_paths(&self) -> Vec<String> {
+        match &self.writable_paths {
+            Some(paths) => paths.clone(),
+            None => crate::DEFAULT_WRITABLE_PATHS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        }
+    }
+
+    /// The merge path patterns, falling back to the defaults.
+    pub fn merge_paths(&self) -> Vec<String> {
+        match &self.merge_paths {
+            Some(paths) => paths.clone(),
+            None => crate::DEFAULT_MERGE_PATHS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        }
+    }
+
+    /// Interval between store snapshots, `None` if disabled.
+    pub fn snapshot_interval(&self) -> Option<Duration> {
+        match self
+            .snapshot_interval_secs
+            .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECS)
+        {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Smallest WebSocket message to compress, `None` unless
+    /// `ws_compression` is on.
+    pub fn ws_compression_threshold(&self) -> Option<usize> {
+        (self.ws_compression == Some(true)).then(|| {
+            self.ws_compression_threshold
+                .unwrap_or(DEFAULT_WS_COMPRESSION_THRESHOLD)
+        })
+    }
+
+    /// Age after which values are pruned from the store, `None` unless
+    /// `prune_contexts_minutes` is set to a non-zero value.
+    pub fn prune_after(&self) -> Option<Duration> {
+        self.prune_contexts_minutes
+            .filter(|&minutes| minutes > 0)
+            .map(|minutes| Duration::from_secs(u64::from(minutes) * 60))
+    }
+
+    /// Whether to advertise the server over mDNS; on unless `mdns` is
+    /// `false`, like the settings page shows it.
+    pub fn mdns_enabled(&self) -> bool {
+        self.mdns.unwrap_or(true)
+    }
+
+    /// Address the data routes listen on: `data_address`, else all
+    /// interfaces on `port` (default [`DEFAULT_PORT`]).
+    pub fn data_addr(&self) -> SocketAddr {
+        self.data_address
+            .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], self.port.unwrap_or(DEFAULT_PORT))))
+    }
+
+    /// Address the admin routes listen on: `admin_address`, else the data
+    /// address (a single listener for both).
+    pub fn admin_addr(&self) -> SocketAddr {
+        self.admin_address.unwrap_or_else(|| self.data_addr())
+    }
+
+    /// Address the HTTPS listener binds, `None` unless `ssl` is on: all
+    /// interfaces on `sslport` (default [`DEFAULT_SSL_PORT`]).
+    pub fn tls_addr(&self) -> Option<SocketAddr> {
+        (self.ssl == Some(true))
+            .then(|| SocketAddr::from(([0, 0, 0, 0], self.sslport.unwrap_or(DEFAULT_SSL_PORT))))
+    }
+
+    /// The HTTPS certificate file, falling back to the default.
+    pub fn ssl_cert_file(&self) -> &str {
+        self.ssl_cert_file
+            .as_deref()
+            .unwrap_or(DEFAULT_SSL_CERT_FILE)
+    }
+
+    /// The HTTPS private key file, falling back to the default.
+    pub fn ssl_key_file(&self) -> &str {
+        self.ssl_key_file.as_deref().unwrap_or(DEFAULT_SSL_KEY_FILE)
+    }
+
+    /// Apply environment variable overrides on top of the stored settings.
+    ///
+    /// `var` looks up a variable (usually `|name| std::env::var(name).ok()`).
+    /// The names match the Node.js server: `PORT` and `SSLPORT`. Values that
+    /// don't parse are ignored.
+    pub fn with_env_overrides<F>(mut self, var: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(port) = var("PORT").and_then(|p| p.trim().parse().ok()) {
+            self.port = Some(port);
+        }
+        if let Some(port) = var("SSLPORT").and_then(|p| p.trim().parse().ok()) {
+            self.sslport = Some(port);
+        }
+        self
+    }
+}
+
+/// HTTP port used when the settings don't configure one.
+pub const DEFAULT_PORT: u16 = 4000;
+
+/// HTTPS port used when the settings don't configure one (as the Node.js
+/// server).
+pub const DEFAULT_SSL_PORT: u16 = 3443;
+
+/// HTTPS certificate file used when the settings don't name one.
+pub const DEFAULT_SSL_CERT_FILE: &str = "ssl-cert.pem";
+
+/// HTTPS private key file used when the settings don't name one.
+pub const DEFAULT_SSL_KEY_FILE: &str = "ssl-key.pem";
+
+/// Store snapshot interval used when the settings don't configure one.
+pub const DEFAULT_SNAPSHOT_INTERVAL_SECS: u64 = 60;
+
+/// WebSocket compression threshold used when the settings don't configure
+/// one; smaller messages gain little from compression.
+pub const DEFAULT_WS_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Placeholder for redacted secret values.
+pub const REDACTED: &str = "[redacted]";
+
+/// Build the effective configuration report served by
+/// `GET /skServer/effectiveConfig`.
+///
+/// `server` is the resolved runtime configuration and `settings` the stored
+/// settings after overrides. Values under keys that look like secrets
+/// (`password`, `secret`, `token`, `...key`) are replaced by [`REDACTED`].
+pub fn effective_config<T: Serialize>(server: &T, settings: &ServerSettings) -> serde_json::Value {
+    let mut config = serde_json::json!({
+        "server": serde_json::to_value(server).unwrap_or_default(),
+        "settings": serde_json::to_value(settings).unwrap_or_default(),
+    });
+    redact_secrets(&mut config);
+    config
+}
+
+/// Replace secret-looking values in a JSON tree with [`REDACTED`].
+pub fn redact_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                let secret = ["password", "secret", "token"]
+                    .iter()
+                    .any(|s| key.contains(s))
+                    || key.ends_with("key");
+                if secret && !child.is_null() {
+                    *child = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_secrets(child);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
 }
 
 /// Interface enable/disable settings.
@@ -237,6 +509,10 @@ pub struct SecurityConfig {
     /// Authorized devices.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub devices: Option<Vec<DeviceRecord>>,
+
+    /// Minimum permissions for reading or writing matching paths.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acls: Option<Vec<crate::AclRule>>,
 }
 
 /// User record in security configuration.
@@ -248,8 +524,9 @@ pub struct UserRecord {
     #[serde(rename = "type")]
     pub user_type: String,
 
-    /// Password hash (never serialized to clients).
-    #[serde(skip_serializing)]
+    /// Password hash, persisted with the security config but stripped by
+    /// [`ConfigHandlers`] before anything is returned to clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password_hash: Option<String>,
 }
 
@@ -307,13 +584,18 @@ impl ConfigHandlers {
     pub fn get_security_config<S: ConfigStorage>(
         storage: &S,
     ) -> Result<SecurityConfig, ConfigError> {
-        storage.load_security()
+        let mut config = storage.load_security()?;
+        for user in config.users.iter_mut().flatten() {
+            user.password_hash = None;
+        }
+        Ok(config)
     }
 
     /// Get list of users (without passwords).
     pub fn get_users<S: ConfigStorage>(storage: &S) -> Result<Vec<UserRecord>, ConfigError> {
-        let config = storage.load_security()?;
-        Ok(config.users.unwrap_or_default())
+        Ok(Self::get_security_config(storage)?
+            .users
+            .unwrap_or_default())
     }
 
     /// Get plugin configuration.
@@ -334,94 +616,106 @@ impl ConfigHandlers {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashMap;
-    use std::sync::RwLock;
-
-    /// In-memory storage for testing.
-    struct MemoryConfigStorage {
-        data: RwLock<HashMap<String, String>>,
-    }
+/// In-memory configuration storage.
+///
+/// Nothing is persisted; useful in tests and as a stand-in for platform
+/// storage in host-side handler tests.
+#[derive(Debug, Default)]
+pub struct MemoryConfigStorage {
+    data: RwLock<HashMap<String, String>>,
+}
 
-    impl MemoryConfigStorage {
-        fn new() -> Self {
-            Self {
-                data: RwLock::new(HashMap::new()),
-            }
+impl MemoryConfigStorage {
+    /// Create empty storage.
+    pub fn new() -> Self {
+        Self {
+            data: RwLock::new(HashMap::new()),
         }
     }
+}
 
-    impl ConfigStorage for MemoryConfigStorage {
-        fn load_settings(&self) -> Result<ServerSettings, ConfigError> {
-            self.load_value("settings")
-        }
+impl ConfigStorage for MemoryConfigStorage {
+    fn load_settings(&self) -> Result<ServerSettings, ConfigError> {
+        self.load_value("settings")
+    }
 
-        fn save_settings(&self, settings: &ServerSettings) -> Result<(), ConfigError> {
-            self.save_value("settings", settings)
-        }
+    fn save_settings(&self, settings: &ServerSettings) -> Result<(), ConfigError> {
+        self.save_value("settings", settings)
+    }
 
-        fn load_vessel(&self) -> Result<VesselInfo, ConfigError> {
-            self.load_value("vessel")
-        }
+    fn load_vessel(&self) -> Result<VesselInfo, ConfigError> {
+        self.load_value("vessel")
+    }
 
-        fn save_vessel(&self, vessel: &VesselInfo) -> Result<(), ConfigError> {
-            self.save_value("vessel", vessel)
-        }
+    fn save_vessel(&self, vessel: &VesselInfo) -> Result<(), ConfigError> {
+        self.save_value("vessel", vessel)
+    }
 
-        fn load_security(&self) -> Result<SecurityConfig, ConfigError> {
-            self.load_value("security")
-        }
+    fn load_security(&self) -> Result<SecurityConfig, ConfigError> {
+        self.load_value("security")
+    }
 
-        fn save_security(&self, config: &SecurityConfig) -> Result<(), ConfigError> {
-            self.save_value("security", config)
-        }
+    fn save_security(&self, config: &SecurityConfig) -> Result<(), ConfigError> {
+        self.save_value("security", config)
+    }
 
-        fn load_plugin_config(&self, plugin_id: &str) -> Result<serde_json::Value, ConfigError> {
-            self.load_value(&format!("plugin:{plugin_id}"))
-        }
+    fn load_plugin_config(&self, plugin_id: &str) -> Result<serde_json::Value, ConfigError> {
+        self.load_value(&format!("plugin:{plugin_id}"))
+    }
 
-        fn save_plugin_config(
-            &self,
-            plugin_id: &str,
-            config: &serde_json::Value,
-        ) -> Result<(), ConfigError> {
-            self.save_value(&format!("plugin:{plugin_id}"), config)
-        }
+    fn save_plugin_config(
+        &self,
+        plugin_id: &str,
+        config: &serde_json::Value,
+    ) -> Result<(), ConfigError> {
+        self.save_value(&format!("plugin:{plugin_id}"), config)
+    }
 
-        fn list_plugin_configs(&self) -> Result<Vec<String>, ConfigError> {
-            let data = self.data.read().unwrap();
-            Ok(data
-                .keys()
-                .filter_map(|k| k.strip_prefix("plugin:").map(String::from))
-                .collect())
-        }
+    fn list_plugin_configs(&self) -> Result<Vec<String>, ConfigError> {
+        let data = self.data.read().unwrap_or_else(|e| e.into_inner());
+        Ok(data
+            .keys()
+            .filter_map(|k| k.strip_prefix("plugin:").map(String::from))
+            .collect())
+    }
 
-        fn load_value<T: DeserializeOwned>(&self, key: &str) -> Result<T, ConfigError> {
-            let data = self.data.read().unwrap();
-            let json = data
-                .get(key)
-                .ok_or_else(|| ConfigError::NotFound(key.to_string()))?;
-            serde_json::from_str(json).map_err(|e| ConfigError::InvalidData(e.to_string()))
-        }
+    fn load_value<T: DeserializeOwned>(&self, key: &str) -> Result<T, ConfigError> {
+        let data = self.data.read().unwrap_or_else(|e| e.into_inner());
+        let json = data
+            .get(key)
+            .ok_or_else(|| ConfigError::NotFound(key.to_string()))?;
+        serde_json::from_str(json).map_err(|e| ConfigError::InvalidData(e.to_string()))
+    }
 
-        fn save_value<T: Serialize>(&self, key: &str, value: &T) -> Result<(), ConfigError> {
-            let json =
-                serde_json::to_string(value).map_err(|e| ConfigError::WriteError(e.to_string()))?;
-            self.data.write().unwrap().insert(key.to_string(), json);
-            Ok(())
-        }
+    fn save_value<T: Serialize>(&self, key: &str, value: &T) -> Result<(), ConfigError> {
+        let json =
+            serde_json::to_string(value).map_err(|e| ConfigError::WriteError(e.to_string()))?;
+        self.data
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.to_string(), json);
+        Ok(())
+    }
 
-        fn has_key(&self, key: &str) -> bool {
-            self.data.read().unwrap().contains_key(key)
-        }
+    fn has_key(&self, key: &str) -> bool {
+        self.data
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(key)
+    }
 
-        fn delete_key(&self, key: &str) -> Result<(), ConfigError> {
-            self.data.write().unwrap().remove(key);
-            Ok(())
-        }
+    fn delete_key(&self, key: &str) -> Result<(), ConfigError> {
+        self.data
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(key);
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
     fn test_settings_round_trip() {
@@ -440,6 +734,98 @@ mod tests {
         assert_eq!(loaded.mdns, Some(true));
     }
 
+    #[test]
+    fn test_settings_keep_unknown_fields() {
+        let settings: ServerSettings =
+            serde_json::from_str(r#"{"port": 3000, "courseApi": {"apiOnly": true}}"#).unwrap();
+        assert_eq!(settings.port, Some(3000));
+
+        let json = serde_json::to_value(settings.with_defaults()).unwrap();
+        assert_eq!(json["port"], 3000);
+        assert_eq!(json["courseApi"]["apiOnly"], true);
+        assert_eq!(json["mdns"], true);
+    }
+
+    #[test]
+    fn test_listen_addresses() {
+        let settings = ServerSettings::default();
+        assert_eq!(settings.data_addr(), "0.0.0.0:4000".parse().unwrap());
+        assert_eq!(settings.admin_addr(), settings.data_addr());
+
+        let settings: ServerSettings =
+            serde_json::from_str(r#"{"port": 3000, "adminAddress": "127.0.0.1:3001"}"#).unwrap();
+        assert_eq!(settings.data_addr(), "0.0.0.0:3000".parse().unwrap());
+        assert_eq!(settings.admin_addr(), "127.0.0.1:3001".parse().unwrap());
+
+        let settings = ServerSettings {
+            port: Some(3000),
+            data_address: Some("192.168.1.10:80".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(settings.data_addr(), "192.168.1.10:80".parse().unwrap());
+        assert_eq!(settings.admin_addr(), settings.data_addr());
+    }
+
+    #[test]
+    fn test_tls_settings() {
+        let settings = ServerSettings::default();
+        assert_eq!(settings.tls_addr(), None);
+        assert_eq!(settings.ssl_cert_file(), DEFAULT_SSL_CERT_FILE);
+        assert_eq!(settings.ssl_key_file(), DEFAULT_SSL_KEY_FILE);
+
+        let settings: ServerSettings =
+            serde_json::from_str(r#"{"ssl": true, "sslKeyFile": "/etc/ssl/boat.key"}"#).unwrap();
+        assert_eq!(settings.tls_addr(), Some("0.0.0.0:3443".parse().unwrap()));
+        assert_eq!(settings.ssl_key_file(), "/etc/ssl/boat.key");
+
+        let settings =
+            settings.with_env_overrides(|name| (name == "SSLPORT").then(|| "8443".into()));
+        assert_eq!(settings.tls_addr(), Some("0.0.0.0:8443".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_ws_compression_threshold() {
+        assert_eq!(ServerSettings::default().ws_compression_threshold(), None);
+
+        let settings: ServerSettings = serde_json::from_str(r#"{"wsCompression": true}"#).unwrap();
+        assert_eq!(
+            settings.ws_compression_threshold(),
+            Some(DEFAULT_WS_COMPRESSION_THRESHOLD)
+        );
+
+        let settings: ServerSettings =
+            serde_json::from_str(r#"{"wsCompression": true, "wsCompressionThreshold": 0}"#)
+                .unwrap();
+        assert_eq!(settings.ws_compression_threshold(), Some(0));
+    }
+
+    #[test]
+    fn test_effective_config_env_override() {
+        let stored = ServerSettings {
+            port: Some(3000),
+            mdns: Some(true),
+            ..Default::default()
+        };
+        let settings = stored.with_env_overrides(|name| match name {
+            "PORT" => Some("8080".to_string()),
+            "SSLPORT" => Some("not-a-port".to_string()),
+            _ => None,
+        });
+
+        let server = serde_json::json!({
+            "bindAddr": "0.0.0.0:8080",
+            "auth": { "jwtSecret": "hunter2", "sslKey": null },
+        });
+        let config = effective_config(&server, &settings);
+
+        assert_eq!(config["settings"]["port"], 8080);
+        assert!(config["settings"].get("sslport").is_none());
+        assert_eq!(config["settings"]["mdns"], true);
+        assert_eq!(config["server"]["bindAddr"], "0.0.0.0:8080");
+        assert_eq!(config["server"]["auth"]["jwtSecret"], REDACTED);
+        assert!(config["server"]["auth"]["sslKey"].is_null());
+    }
+
     #[test]
     fn test_vessel_round_trip() {
         let storage = MemoryConfigStorage::new();
@@ -457,6 +843,32 @@ mod tests {
         assert_eq!(loaded.mmsi, Some("123456789".to_string()));
     }
 
+    #[test]
+    fn test_password_hash_persisted_not_returned() {
+        let storage = MemoryConfigStorage::new();
+        let security = SecurityConfig {
+            users: Some(vec![UserRecord {
+                user_id: "admin".to_string(),
+                user_type: "admin".to_string(),
+                password_hash: Some("$argon2id$hash".to_string()),
+            }]),
+            ..Default::default()
+        };
+        storage.save_security(&security).unwrap();
+
+        let stored = storage.load_security().unwrap();
+        assert_eq!(
+            stored.users.unwrap()[0].password_hash.as_deref(),
+            Some("$argon2id$hash")
+        );
+
+        let users = ConfigHandlers::get_users(&storage).unwrap();
+        assert_eq!(users[0].user_id, "admin");
+        assert!(users[0].password_hash.is_none());
+        let config = ConfigHandlers::get_security_config(&storage).unwrap();
+        assert!(config.users.unwrap()[0].password_hash.is_none());
+    }
+
     #[test]
     fn test_plugin_config() {
         let storage = MemoryConfigStorage::new();
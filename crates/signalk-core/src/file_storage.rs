@@ -0,0 +1,312 @@
+//! File-based configuration storage.
+//!
+//! Stores each configuration type as a JSON file under a base directory
+//! (`~/.signalk/` by default):
+//! - `settings.json`, `vessel.json`, `security.json`
+//! - `plugin-config/<id>.json`
+//! - generic keys as `<key>` (e.g. `uuid`)
+//!
+//! Files are replaced atomically: each is written to a temporary file next
+//! to it, then renamed over it, so a crash mid-write leaves the previous
+//! version rather than a truncated one. Configuration files are
+//! pretty-printed for hand editing; generic keys, which may be as large as
+//! a store snapshot, are written compactly.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::{ConfigError, ConfigStorage, SecurityConfig, ServerSettings, VesselInfo};
+
+const SETTINGS_FILE: &str = "settings.json";
+const VESSEL_FILE: &str = "vessel.json";
+const SECURITY_FILE: &str = "security.json";
+const PLUGIN_CONFIG_DIR: &str = "plugin-config";
+
+/// Configuration storage backed by JSON files in a directory.
+#[derive(Debug, Clone)]
+pub struct FileConfigStorage {
+    base_dir: PathBuf,
+}
+
+impl FileConfigStorage {
+    /// Open storage in `base_dir`, creating the directory if missing.
+    pub fn new(base_dir: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let base_dir = base_dir.into();
+        fs::create_dir_all(&base_dir)
+            .map_err(|e| ConfigError::StorageUnavailable(format!("{}: {e}", base_dir.display())))?;
+        Ok(Self { base_dir })
+    }
+
+    /// Default configuration directory (`$HOME/.signalk`).
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".signalk"))
+    }
+
+    /// Base directory holding the configuration files.
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    fn plugin_path(&self, plugin_id: &str) -> PathBuf {
+        self.base_dir
+            .join(PLUGIN_CONFIG_DIR)
+            .join(format!("{plugin_id}.json"))
+    }
+
+    fn read_json<T: DeserializeOwned>(&self, path: &Path) -> Result<T, ConfigError> {
+        let data = fs::read(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => ConfigError::NotFound(path.display().to_string()),
+            _ => ConfigError::ReadError(format!("{}: {e}", path.display())),
+        })?;
+        serde_json::from_slice(&data).map_err(|e| ConfigError::InvalidData(e.to_string()))
+    }
+
+    fn write_json<T: Serialize>(&self, path: &Path, value: &T) -> Result<(), ConfigError> {
+        let data =
+            serde_json::to_vec_pretty(value).map_err(|e| ConfigError::WriteError(e.to_string()))?;
+        write_atomic(path, &data)
+    }
+}
+
+/// Replace the file at `path` with `data` through a temporary file in the
+/// same directory, so readers never see it half written.
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), ConfigError> {
+    let error =
+        |path: &Path, e: io::Error| ConfigError::WriteError(format!("{}: {e}", path.display()));
+    let parent = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent).map_err(|e| error(parent, e))?;
+
+    // Unique per write, so concurrent saves of one file don't share it
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(
+        ".tmp-{}-{}",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = parent.join(temp_name);
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(error(path, e));
+    }
+    Ok(())
+}
+
+impl ConfigStorage for FileConfigStorage {
+    fn load_settings(&self) -> Result<ServerSettings, ConfigError> {
+        self.read_json(&self.base_dir.join(SETTINGS_FILE))
+    }
+
+    fn save_settings(&self, settings: &ServerSettings) -> Result<(), ConfigError> {
+        self.write_json(&self.base_dir.join(SETTINGS_FILE), settings)
+    }
+
+    fn load_vessel(&self) -> Result<VesselInfo, ConfigError> {
+        self.read_json(&self.base_dir.join(VESSEL_FILE))
+    }
+
+    fn save_vessel(&self, vessel: &VesselInfo) -> Result<(), ConfigError> {
+        self.write_json(&self.base_dir.join(VESSEL_FILE), vessel)
+    }
+
+    fn load_security(&self) -> Result<SecurityConfig, ConfigError> {
+        self.read_json(&self.base_dir.join(SECURITY_FILE))
+    }
+
+    fn save_security(&self, config: &SecurityConfig) -> Result<(), ConfigError> {
+        self.write_json(&self.base_dir.join(SECURITY_FILE), config)
+    }
+
+    fn load_plugin_config(&self, plugin_id: &str) -> Result<serde_json::Value, ConfigError> {
+        self.read_json(&self.plugin_path(plugin_id))
+    }
+
+    fn save_plugin_config(
+        &self,
+        plugin_id: &str,
+        config: &serde_json::Value,
+    ) -> Result<(), ConfigError> {
+        self.write_json(&self.plugin_path(plugin_id), config)
+    }
+
+    fn list_plugin_configs(&self) -> Result<Vec<String>, ConfigError> {
+        let dir = self.base_dir.join(PLUGIN_CONFIG_DIR);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ConfigError::ReadError(format!("{}: {e}", dir.display()))),
+        };
+
+        let mut ids: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_suffix(".json"))
+                    .map(String::from)
+            })
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn load_value<T: DeserializeOwned>(&self, key: &str) -> Result<T, ConfigError> {
+        self.read_json(&self.base_dir.join(key))
+    }
+
+    fn save_value<T: Serialize>(&self, key: &str, value: &T) -> Result<(), ConfigError> {
+        let data = serde_json::to_vec(value).map_err(|e| ConfigError::WriteError(e.to_string()))?;
+        write_atomic(&self.base_dir.join(key), &data)
+    }
+
+    fn has_key(&self, key: &str) -> bool {
+        self.base_dir.join(key).is_file()
+    }
+
+    fn delete_key(&self, key: &str) -> Result<(), ConfigError> {
+        match fs::remove_file(self.base_dir.join(key)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(ConfigError::WriteError(format!("{key}: {e}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh storage directory under the system temp dir.
+    fn temp_storage(name: &str) -> FileConfigStorage {
+        let dir =
+            std::env::temp_dir().join(format!("signalk-config-{name}-{}", uuid::Uuid::new_v4()));
+        FileConfigStorage::new(dir).unwrap()
+    }
+
+    #[test]
+    fn test_creates_base_dir() {
+        let storage = temp_storage("create");
+        assert!(storage.base_dir().is_dir());
+        fs::remove_dir_all(storage.base_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_missing_files_not_found() {
+        let storage = temp_storage("missing");
+        assert!(matches!(
+            storage.load_settings(),
+            Err(ConfigError::NotFound(_))
+        ));
+        assert!(matches!(
+            storage.load_vessel(),
+            Err(ConfigError::NotFound(_))
+        ));
+        assert!(matches!(
+            storage.load_plugin_config("none"),
+            Err(ConfigError::NotFound(_))
+        ));
+        assert_eq!(storage.list_plugin_configs().unwrap(), Vec::<String>::new());
+        fs::remove_dir_all(storage.base_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let storage = temp_storage("round-trip");
+
+        let settings = ServerSettings {
+            port: Some(3000),
+            mdns: Some(false),
+            ..Default::default()
+        };
+        storage.save_settings(&settings).unwrap();
+        let loaded = storage.load_settings().unwrap();
+        assert_eq!(loaded.port, Some(3000));
+        assert_eq!(loaded.mdns, Some(false));
+        assert!(storage.base_dir().join("settings.json").is_file());
+
+        let vessel = VesselInfo {
+            name: Some("Albatross".to_string()),
+            ..Default::default()
+        };
+        storage.save_vessel(&vessel).unwrap();
+        assert_eq!(
+            storage.load_vessel().unwrap().name.as_deref(),
+            Some("Albatross")
+        );
+
+        let security = SecurityConfig {
+            expiration: Some("7d".to_string()),
+            ..Default::default()
+        };
+        storage.save_security(&security).unwrap();
+        assert_eq!(
+            storage.load_security().unwrap().expiration.as_deref(),
+            Some("7d")
+        );
+
+        fs::remove_dir_all(storage.base_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_configs() {
+        let storage = temp_storage("plugins");
+
+        storage
+            .save_plugin_config("zeta", &serde_json::json!({"enabled": false}))
+            .unwrap();
+        storage
+            .save_plugin_config("alpha", &serde_json::json!({"enabled": true}))
+            .unwrap();
+        assert!(storage
+            .base_dir()
+            .join("plugin-config/alpha.json")
+            .is_file());
+
+        assert_eq!(
+            storage.load_plugin_config("alpha").unwrap()["enabled"],
+            true
+        );
+        assert_eq!(storage.list_plugin_configs().unwrap(), ["alpha", "zeta"]);
+
+        fs::remove_dir_all(storage.base_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_values() {
+        let storage = temp_storage("values");
+
+        assert!(!storage.has_key("uuid"));
+        storage
+            .save_value("uuid", &"urn:mrn:signalk:uuid:x")
+            .unwrap();
+        assert!(storage.has_key("uuid"));
+        let uuid: String = storage.load_value("uuid").unwrap();
+        assert_eq!(uuid, "urn:mrn:signalk:uuid:x");
+
+        // Replaced in place, compactly, without leaving temporary files
+        storage.save_value("uuid", &["a", "b"]).unwrap();
+        let data = fs::read_to_string(storage.base_dir().join("uuid")).unwrap();
+        assert_eq!(data, r#"["a","b"]"#);
+        let files: Vec<_> = fs::read_dir(storage.base_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["uuid"]);
+
+        storage.delete_key("uuid").unwrap();
+        assert!(!storage.has_key("uuid"));
+        // Deleting again is fine
+        storage.delete_key("uuid").unwrap();
+
+        fs::remove_dir_all(storage.base_dir()).unwrap();
+    }
+}
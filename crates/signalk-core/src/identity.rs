@@ -0,0 +1,118 @@
+//! Persistent self vessel identity.
+//!
+//! A server mints a UUID on first boot and keeps it in its `ConfigStorage`,
+//! so clients see the same `vessels.urn:mrn:signalk:uuid:<uuid>` context
+//! across restarts.
+
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::config::{ConfigError, ConfigStorage};
+
+/// Prefix of a SignalK vessel UUID URN.
+const URN_PREFIX: &str = "urn:mrn:signalk:uuid:";
+
+/// The self vessel URN, backed by a UUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SelfUrn {
+    uuid: Uuid,
+}
+
+impl SelfUrn {
+    /// Storage key under which the UUID is persisted.
+    pub const STORAGE_KEY: &'static str = "uuid";
+
+    /// Generate a new random identity.
+    pub fn generate() -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+        }
+    }
+
+    /// Create an identity from an existing UUID.
+    pub fn from_uuid(uuid: Uuid) -> Self {
+        Self { uuid }
+    }
+
+    /// Load the persisted identity, or generate and persist a new one.
+    ///
+    /// Returns `ConfigError::InvalidData` if the stored value is not a UUID,
+    /// rather than silently replacing the vessel identity.
+    pub fn load_or_generate<S: ConfigStorage>(storage: &S) -> Result<Self, ConfigError> {
+        match storage.load_value::<String>(Self::STORAGE_KEY) {
+            Ok(stored) => Uuid::parse_str(&stored)
+                .map(Self::from_uuid)
+                .map_err(|e| ConfigError::InvalidData(format!("stored uuid '{stored}': {e}"))),
+            Err(ConfigError::NotFound(_)) => {
+                let urn = Self::generate();
+                storage.save_value(Self::STORAGE_KEY, &urn.uuid.to_string())?;
+                Ok(urn)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The underlying UUID.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// The URN without context prefix (`urn:mrn:signalk:uuid:<uuid>`).
+    pub fn urn(&self) -> String {
+        format!("{URN_PREFIX}{}", self.uuid)
+    }
+
+    /// The full self context (`vessels.urn:mrn:signalk:uuid:<uuid>`).
+    pub fn context(&self) -> String {
+        format!("vessels.{}", self.urn())
+    }
+}
+
+impl fmt::Display for SelfUrn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.context())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MemoryConfigStorage;
+
+    #[test]
+    fn test_load_or_generate_is_stable() {
+        let storage = MemoryConfigStorage::new();
+
+        let first = SelfUrn::load_or_generate(&storage).unwrap();
+        let second = SelfUrn::load_or_generate(&storage).unwrap();
+
+        assert_eq!(first, second);
+        assert!(storage.has_key(SelfUrn::STORAGE_KEY));
+    }
+
+    #[test]
+    fn test_context_format() {
+        let uuid = Uuid::parse_str("c0d79334-4e25-4245-8892-54e8ccc8021d").unwrap();
+        let urn = SelfUrn::from_uuid(uuid);
+
+        assert_eq!(
+            urn.context(),
+            "vessels.urn:mrn:signalk:uuid:c0d79334-4e25-4245-8892-54e8ccc8021d"
+        );
+        assert_eq!(urn.to_string(), urn.context());
+    }
+
+    #[test]
+    fn test_load_rejects_corrupt_uuid() {
+        let storage = MemoryConfigStorage::new();
+        storage
+            .save_value(SelfUrn::STORAGE_KEY, &"not-a-uuid")
+            .unwrap();
+
+        assert!(matches!(
+            SelfUrn::load_or_generate(&storage),
+            Err(ConfigError::InvalidData(_))
+        ));
+    }
+}
@@ -5,22 +5,65 @@
 //! This crate provides:
 //! - Data model types (Delta, Update, Value, Source, etc.)
 //! - Path parsing and wildcard matching
-//! - In-memory store implementation
+//! - In-memory store implementation, shared between threads with
+//!   copy-on-write views for readers
 //! - Subscription logic (without I/O)
-//! - Configuration storage abstraction
+//! - Configuration storage abstraction (with a file-based backend)
+//! - Persistent self vessel identity
+//! - Delta sink abstraction for providers
+//! - Allowlist of writable (PUT) paths
+//! - Zone evaluation into `notifications.*`
+//! - Coalescing of split latitude/longitude into `navigation.position`
+//! - Rejection of sentinel values (0, NaN, 0,0 positions) from faulty sensors
+//! - Per-path access control by permission level
+//! - Deep-merging of object values on `design.*` and sensor data paths
+//! - Conversion between SI and display units
+//! - Source priorities choosing the primary value of multi-source paths
 //!
 //! This crate is intentionally runtime-agnostic and contains no async code,
 //! making it usable on both Linux (tokio) and ESP32 (esp-idf) targets.
 
+pub mod acl;
+pub mod coalesce;
 pub mod config;
+#[cfg(feature = "std")]
+pub mod file_storage;
+pub mod identity;
 pub mod model;
+pub mod notifications;
 pub mod path;
+pub mod priorities;
+pub mod sentinel;
+pub mod shared;
+pub mod sink;
 pub mod store;
+pub mod units;
+pub mod writable;
 
+pub use acl::{AclRule, PathAcl, Permission};
+pub use coalesce::PositionCoalescer;
 pub use config::{
-    ConfigError, ConfigHandlers, ConfigStorage, InterfaceSettings, SecurityConfig, ServerSettings,
-    VesselInfo,
+    effective_config, redact_secrets, ConfigError, ConfigHandlers, ConfigStorage,
+    InterfaceSettings, MemoryConfigStorage, SecurityConfig, ServerSettings, UserRecord, VesselInfo,
+    DEFAULT_PORT, DEFAULT_SNAPSHOT_INTERVAL_SECS, DEFAULT_SSL_CERT_FILE, DEFAULT_SSL_KEY_FILE,
+    DEFAULT_SSL_PORT, DEFAULT_WS_COMPRESSION_THRESHOLD, REDACTED,
 };
+#[cfg(feature = "std")]
+pub use file_storage::FileConfigStorage;
+pub use identity::SelfUrn;
 pub use model::*;
-pub use path::{Path, PathPattern, PatternError};
-pub use store::{MemoryStore, SignalKStore};
+pub use notifications::{zone_for, zone_notifications, NotificationMethod, NotificationMethods};
+pub use path::{Path, PathPattern, PatternError, PatternOptions};
+pub use priorities::{SourcePriorities, SourcePriority};
+pub use sentinel::{Sentinel, SentinelFilter, SentinelRule};
+pub use shared::SharedStore;
+pub use sink::{DeltaSink, ProviderState};
+pub use store::{
+    full_fragment, select_source, truncate_depth, MemoryStore, MergeStrategy, PathNumericStats,
+    PutHandler, PutResult, SignalKStore, StoreError, StoreSnapshot, DEFAULT_MERGE_PATHS,
+    TRUNCATED_KEY,
+};
+pub use units::{
+    convert, display_conversion, to_display_units, Conversion, UnitSystem, CONVERSIONS,
+};
+pub use writable::{WritablePaths, DEFAULT_WRITABLE_PATHS};
@@ -44,6 +44,103 @@ pub struct Update {
     /// Metadata updates (separate from values)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<Vec<PathMeta>>,
+
+    /// When the server processed this update (ISO 8601, UTC).
+    ///
+    /// Only added to outgoing deltas when the server is configured to; the
+    /// source `timestamp` is left untouched so clients can compute latency.
+    #[serde(rename = "serverTimestamp", skip_serializing_if = "Option::is_none")]
+    pub server_timestamp: Option<String>,
+}
+
+impl Delta {
+    /// Rewrite any vessel context to `vessels.self`.
+    ///
+    /// For single-vessel installs where a provider mislabels its context.
+    /// Non-vessel contexts (`aton.*`, `shore.*`, ...) are left alone.
+    pub fn promote_to_self(&mut self) {
+        if self
+            .context
+            .as_deref()
+            .is_some_and(|context| context.starts_with("vessels."))
+        {
+            self.context = Some("vessels.self".to_string());
+        }
+    }
+
+    /// Remove values whose serialized JSON is larger than `max_bytes`.
+    ///
+    /// Updates left without values (and without meta) are removed. Returns
+    /// the paths of the removed values.
+    pub fn remove_oversized_values(&mut self, max_bytes: usize) -> Vec<String> {
+        let mut removed = Vec::new();
+        for update in &mut self.updates {
+            update.values.retain(|pv| {
+                let fits = json_size(&pv.value) <= max_bytes;
+                if !fits {
+                    removed.push(pv.path.clone());
+                }
+                fits
+            });
+        }
+        if !removed.is_empty() {
+            self.updates
+                .retain(|update| !update.values.is_empty() || update.meta.is_some());
+        }
+        removed
+    }
+}
+
+/// Length of the JSON serialization of `value`, without building it.
+fn json_size(value: &serde_json::Value) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // Writing to a counter can't fail
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+impl Update {
+    /// Group path values into updates by their `$source` and timestamp.
+    ///
+    /// Takes `(source_ref, timestamp, value)` triples and returns one update
+    /// per distinct source/timestamp pair, in order of first appearance, so
+    /// values from different sources aren't attributed to a single one.
+    pub fn group_by_source<I>(values: I) -> Vec<Update>
+    where
+        I: IntoIterator<Item = (Option<String>, Option<String>, PathValue)>,
+    {
+        let mut updates: Vec<Update> = Vec::new();
+        for (source_ref, timestamp, value) in values {
+            match updates
+                .iter_mut()
+                .find(|u| u.source_ref == source_ref && u.timestamp == timestamp)
+            {
+                Some(update) => update.values.push(value),
+                None => updates.push(Update {
+                    source_ref,
+                    source: None,
+                    timestamp,
+                    values: vec![value],
+                    meta: None,
+                    server_timestamp: None,
+                }),
+            }
+        }
+        updates
+    }
 }
 
 /// A single path-value pair within an update.
@@ -102,7 +199,7 @@ pub struct Source {
 }
 
 /// Metadata describing a SignalK path.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Meta {
     /// Human-readable description
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -189,7 +286,7 @@ pub struct Zone {
 }
 
 /// Alarm states in order of severity.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AlarmState {
     Nominal,
@@ -270,6 +367,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -278,6 +376,64 @@ mod tests {
         assert!(json.contains("3.85"));
     }
 
+    #[test]
+    fn test_remove_oversized_values() {
+        let value = |path: &str, value: serde_json::Value| PathValue {
+            path: path.to_string(),
+            value,
+        };
+        let mut delta = Delta {
+            context: None,
+            updates: vec![
+                Update {
+                    source_ref: Some("a".to_string()),
+                    source: None,
+                    timestamp: None,
+                    values: vec![
+                        value("navigation.speedOverGround", serde_json::json!(3.85)),
+                        value("notes.blob", serde_json::json!("x".repeat(100))),
+                    ],
+                    meta: None,
+                    server_timestamp: None,
+                },
+                Update {
+                    source_ref: Some("b".to_string()),
+                    source: None,
+                    timestamp: None,
+                    values: vec![value(
+                        "notes.other",
+                        serde_json::json!({"x": "y".repeat(100)}),
+                    )],
+                    meta: None,
+                    server_timestamp: None,
+                },
+            ],
+        };
+
+        let removed = delta.remove_oversized_values(64);
+        assert_eq!(removed, ["notes.blob", "notes.other"]);
+        assert_eq!(delta.updates.len(), 1);
+        assert_eq!(
+            delta.updates[0].values,
+            [value("navigation.speedOverGround", serde_json::json!(3.85))]
+        );
+        // Exactly at the limit is kept: `"xx"` is 4 bytes
+        let mut delta = Delta {
+            context: None,
+            updates: vec![Update {
+                source_ref: None,
+                source: None,
+                timestamp: None,
+                values: vec![value("notes.blob", serde_json::json!("xx"))],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+        assert!(delta.remove_oversized_values(4).is_empty());
+        assert_eq!(delta.remove_oversized_values(3), ["notes.blob"]);
+        assert!(delta.updates.is_empty());
+    }
+
     #[test]
     fn test_hello_serialize() {
         let hello = Hello {
@@ -293,4 +449,32 @@ mod tests {
         assert!(json.contains("signalk-server-rs"));
         assert!(json.contains("1.7.0"));
     }
+
+    #[test]
+    fn test_group_by_source() {
+        let pv = |path: &str| PathValue {
+            path: path.to_string(),
+            value: serde_json::json!(1.0),
+        };
+        let gps = Some("gps".to_string());
+        let ais = Some("ais".to_string());
+        let t1 = Some("2024-01-17T10:30:00.000Z".to_string());
+
+        let updates = Update::group_by_source(vec![
+            (gps.clone(), t1.clone(), pv("navigation.speedOverGround")),
+            (ais.clone(), t1.clone(), pv("navigation.position")),
+            (
+                gps.clone(),
+                t1.clone(),
+                pv("navigation.courseOverGroundTrue"),
+            ),
+            (gps.clone(), None, pv("navigation.headingTrue")),
+        ]);
+
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].source_ref, gps);
+        assert_eq!(updates[0].values.len(), 2);
+        assert_eq!(updates[1].source_ref, ais);
+        assert_eq!(updates[2].timestamp, None);
+    }
 }
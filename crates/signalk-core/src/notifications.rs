@@ -0,0 +1,375 @@
+//! Zone evaluation and `notifications.*` generation.
+//!
+//! When a numeric value enters one of the zones in its path's `meta.zones`,
+//! a notification is emitted at `notifications.<path>`:
+//!
+//! ```json
+//! { "state": "alarm", "method": ["visual", "sound"], "message": "Engine hot" }
+//! ```
+//!
+//! The `method` array comes from a configurable [`NotificationMethods`]
+//! mapping keyed by severity. Notifications are only emitted when the state
+//! changes, including the return to `normal` when the value leaves all zones.
+//! Where zones overlap, the most severe one containing the value wins.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::{AlarmState, Delta, PathValue, Update, Zone};
+use crate::store::{MemoryStore, SignalKStore};
+
+/// How a notification should be presented to the crew.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationMethod {
+    Visual,
+    Sound,
+}
+
+/// Severity → presentation methods for emitted notifications.
+///
+/// Serialized as a map, e.g. `{"alarm": ["visual", "sound"], "warn": ["visual"]}`.
+/// States without an entry get an empty `method` array.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NotificationMethods(BTreeMap<AlarmState, Vec<NotificationMethod>>);
+
+impl NotificationMethods {
+    /// A mapping with no methods for any state.
+    pub fn none() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Set the methods for `state`.
+    pub fn with(mut self, state: AlarmState, methods: &[NotificationMethod]) -> Self {
+        self.0.insert(state, methods.to_vec());
+        self
+    }
+
+    /// Methods used for notifications in `state`.
+    pub fn methods_for(&self, state: AlarmState) -> &[NotificationMethod] {
+        self.0.get(&state).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+impl Default for NotificationMethods {
+    /// Alarms and emergencies are seen and heard; alerts and warnings are
+    /// visual only.
+    fn default() -> Self {
+        use NotificationMethod::{Sound, Visual};
+        Self::none()
+            .with(AlarmState::Alert, &[Visual])
+            .with(AlarmState::Warn, &[Visual])
+            .with(AlarmState::Alarm, &[Visual, Sound])
+            .with(AlarmState::Emergency, &[Visual, Sound])
+    }
+}
+
+impl Zone {
+    /// Check whether `value` lies within this zone (bounds inclusive).
+    pub fn contains(&self, value: f64) -> bool {
+        self.lower.map_or(true, |lower| value >= lower)
+            && self.upper.map_or(true, |upper| value <= upper)
+    }
+}
+
+/// Find the most severe zone containing `value`.
+pub fn zone_for(zones: &[Zone], value: f64) -> Option<&Zone> {
+    zones
+        .iter()
+        .filter(|zone| zone.contains(value))
+        .max_by_key(|zone| zone.state)
+}
+
+/// Evaluate the zones for every numeric value in a delta that was just
+/// applied to `store`.
+///
+/// Returns a delta of `notifications.*` values for paths whose state
+/// changed, or `None` if nothing changed. The caller applies and
+/// broadcasts it like any other delta. A path that appears more than once
+/// in `delta` is compared against its state after the earlier values, so
+/// a burst within one zone yields a single notification.
+pub fn zone_notifications(
+    store: &MemoryStore,
+    delta: &Delta,
+    methods: &NotificationMethods,
+) -> Option<Delta> {
+    let context = delta.context.as_deref().unwrap_or("vessels.self");
+    let resolved = if context == "vessels.self" {
+        store.self_urn()
+    } else {
+        context
+    };
+
+    // States decided earlier in this delta, not yet in the store
+    let mut decided: HashMap<&str, AlarmState> = HashMap::new();
+    let mut updates = Vec::new();
+    for update in &delta.updates {
+        let mut values = Vec::new();
+        for pv in &update.values {
+            let Some(value) = pv.value.as_f64() else {
+                continue;
+            };
+            let Some(node) = store.get_path(&format!("{resolved}.{}", pv.path)) else {
+                continue;
+            };
+            let Some(zones) = node
+                .get("meta")
+                .and_then(|meta| meta.get("zones"))
+                .and_then(|zones| serde_json::from_value::<Vec<Zone>>(zones.clone()).ok())
+            else {
+                continue;
+            };
+
+            let zone = zone_for(&zones, value);
+            let state = zone.map_or(AlarmState::Normal, |zone| zone.state);
+
+            let notification_path = format!("notifications.{}", pv.path);
+            let previous = decided.get(pv.path.as_str()).copied().unwrap_or_else(|| {
+                store
+                    .get_value(&format!("{resolved}.{notification_path}"))
+                    .and_then(|n| serde_json::from_value::<AlarmState>(n["state"].clone()).ok())
+                    .unwrap_or(AlarmState::Normal)
+            });
+            decided.insert(&pv.path, state);
+            if previous == state {
+                continue;
+            }
+
+            let message = zone
+                .and_then(|zone| zone.message.clone())
+                .unwrap_or_else(|| format!("{} is {}", pv.path, state_name(state)));
+            values.push(PathValue {
+                path: notification_path,
+                value: serde_json::json!({
+                    "state": state,
+                    "method": methods.methods_for(state),
+                    "message": message,
+                }),
+            });
+        }
+
+        if !values.is_empty() {
+            updates.push(Update {
+                source_ref: update.source_ref.clone(),
+                source: None,
+                timestamp: update.timestamp.clone(),
+                values,
+                meta: None,
+                server_timestamp: None,
+            });
+        }
+    }
+
+    (!updates.is_empty()).then(|| Delta {
+        context: Some(context.to_string()),
+        updates,
+    })
+}
+
+fn state_name(state: AlarmState) -> String {
+    match serde_json::to_value(state) {
+        Ok(Value::String(name)) => name,
+        _ => format!("{state:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Meta, PathMeta};
+
+    const PATH: &str = "propulsion.main.temperature";
+
+    fn zone(lower: Option<f64>, upper: Option<f64>, state: AlarmState) -> Zone {
+        Zone {
+            lower,
+            upper,
+            state,
+            message: None,
+        }
+    }
+
+    fn store_with_zones() -> MemoryStore {
+        store_with(vec![
+            zone(Some(360.0), Some(370.0), AlarmState::Warn),
+            Zone {
+                message: Some("Engine hot".to_string()),
+                ..zone(Some(370.0), None, AlarmState::Alarm)
+            },
+        ])
+    }
+
+    /// A store with `zones` configured for the temperature.
+    fn store_with(zones: Vec<Zone>) -> MemoryStore {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        let meta = Meta {
+            zones: Some(zones),
+            ..Default::default()
+        };
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: None,
+                source: None,
+                timestamp: None,
+                values: vec![],
+                meta: Some(vec![PathMeta {
+                    path: PATH.to_string(),
+                    value: meta,
+                }]),
+                server_timestamp: None,
+            }],
+        });
+        store
+    }
+
+    fn readings(values: &[f64]) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: values
+                .iter()
+                .map(|value| Update {
+                    source_ref: Some("n2k.12".to_string()),
+                    source: None,
+                    timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                    values: vec![PathValue {
+                        path: PATH.to_string(),
+                        value: serde_json::json!(value),
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Apply a temperature reading and evaluate it like the server does.
+    fn reading(store: &mut MemoryStore, value: f64) -> Option<Value> {
+        let delta = readings(&[value]);
+        store.apply_delta(&delta);
+        let notifications = zone_notifications(store, &delta, &NotificationMethods::default())?;
+        store.apply_delta(&notifications);
+
+        let pv = &notifications.updates[0].values[0];
+        assert_eq!(pv.path, format!("notifications.{PATH}"));
+        Some(pv.value.clone())
+    }
+
+    #[test]
+    fn test_notification_methods_by_severity() {
+        let mut store = store_with_zones();
+
+        assert_eq!(reading(&mut store, 350.0), None);
+
+        let warn = reading(&mut store, 365.0).unwrap();
+        assert_eq!(warn["state"], "warn");
+        assert_eq!(warn["method"], serde_json::json!(["visual"]));
+
+        let alarm = reading(&mut store, 375.0).unwrap();
+        assert_eq!(alarm["state"], "alarm");
+        assert_eq!(alarm["method"], serde_json::json!(["visual", "sound"]));
+        assert_eq!(alarm["message"], "Engine hot");
+
+        // Unchanged state: nothing new to emit
+        assert_eq!(reading(&mut store, 380.0), None);
+
+        let normal = reading(&mut store, 350.0).unwrap();
+        assert_eq!(normal["state"], "normal");
+        assert_eq!(normal["method"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_notification_methods_configurable() {
+        let methods: NotificationMethods =
+            serde_json::from_str(r#"{"warn": ["visual", "sound"], "alarm": ["sound"]}"#).unwrap();
+
+        assert_eq!(
+            methods.methods_for(AlarmState::Warn),
+            &[NotificationMethod::Visual, NotificationMethod::Sound]
+        );
+        assert_eq!(
+            methods.methods_for(AlarmState::Alarm),
+            &[NotificationMethod::Sound]
+        );
+        assert!(methods.methods_for(AlarmState::Emergency).is_empty());
+    }
+
+    #[test]
+    fn test_value_crossing_zone_boundaries() {
+        let mut store = store_with_zones();
+        let states: Vec<Option<Value>> = [355.0, 360.0, 369.0, 370.5, 371.0, 362.0, 359.9]
+            .into_iter()
+            .map(|value| reading(&mut store, value).map(|n| n["state"].clone()))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                None,
+                // Bounds are inclusive
+                Some("warn".into()),
+                None,
+                Some("alarm".into()),
+                None,
+                Some("warn".into()),
+                Some("normal".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zones_with_one_bound() {
+        // Too cold or too hot, with a gap in between
+        let mut store = store_with(vec![
+            zone(None, Some(280.0), AlarmState::Alert),
+            zone(Some(380.0), None, AlarmState::Emergency),
+        ]);
+
+        assert_eq!(reading(&mut store, 250.0).unwrap()["state"], "alert");
+        assert_eq!(reading(&mut store, 330.0).unwrap()["state"], "normal");
+        assert_eq!(reading(&mut store, 1000.0).unwrap()["state"], "emergency");
+        assert_eq!(reading(&mut store, -50.0).unwrap()["state"], "alert");
+    }
+
+    #[test]
+    fn test_overlapping_zones_most_severe() {
+        let mut store = store_with(vec![
+            zone(Some(360.0), Some(390.0), AlarmState::Warn),
+            zone(Some(375.0), Some(380.0), AlarmState::Alarm),
+            zone(Some(350.0), None, AlarmState::Alert),
+        ]);
+
+        assert_eq!(reading(&mut store, 355.0).unwrap()["state"], "alert");
+        assert_eq!(reading(&mut store, 365.0).unwrap()["state"], "warn");
+        assert_eq!(reading(&mut store, 377.0).unwrap()["state"], "alarm");
+        assert_eq!(reading(&mut store, 385.0).unwrap()["state"], "warn");
+    }
+
+    #[test]
+    fn test_burst_in_one_delta() {
+        let mut store = store_with_zones();
+
+        // Several readings in one zone give one notification
+        let delta = readings(&[375.0, 376.0, 377.0]);
+        store.apply_delta(&delta);
+        let notifications =
+            zone_notifications(&store, &delta, &NotificationMethods::default()).unwrap();
+        assert_eq!(notifications.updates.len(), 1);
+        assert_eq!(notifications.updates[0].values[0].value["state"], "alarm");
+        store.apply_delta(&notifications);
+
+        // Out and back within the delta: each transition, in order
+        let delta = readings(&[350.0, 350.5, 375.0]);
+        store.apply_delta(&delta);
+        let notifications =
+            zone_notifications(&store, &delta, &NotificationMethods::default()).unwrap();
+        let states: Vec<&Value> = notifications
+            .updates
+            .iter()
+            .map(|update| &update.values[0].value["state"])
+            .collect();
+        assert_eq!(states, ["normal", "alarm"]);
+    }
+}
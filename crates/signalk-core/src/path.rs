@@ -73,24 +73,44 @@ enum PatternSegment {
     /// Single wildcard (*) - matches exactly one segment when mid-path,
     /// or any suffix when at the end
     Wildcard,
+    /// Double wildcard (**) - matches zero or more segments anywhere
+    DoubleWildcard,
 }
 
+/// Options for [`PathPattern::new_with_options`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PatternOptions {
+    /// Compare literal segments ignoring ASCII case, so
+    /// "navigation.speedOverGround" also matches "Navigation.SpeedOverGround".
+    pub case_insensitive: bool,
+    /// Expand `{a,b}` groups into alternatives, so
+    /// "environment.{wind,water}.temperature" matches either path.
+    pub brace_expansion: bool,
+}
+
+/// Most alternatives a brace-expanded pattern may produce.
+const MAX_ALTERNATIVES: usize = 64;
+
 /// A subscription pattern that may contain wildcards.
 ///
 /// Supported patterns:
 /// - Exact: "navigation.speedOverGround"
 /// - Suffix wildcard: "navigation.*"
 /// - Mid-path wildcard: "propulsion.*.revolutions"
+/// - Multi-segment wildcard: "propulsion.**.temperature"
 /// - Full wildcard: "*"
+/// - Alternatives, with [`PatternOptions::brace_expansion`]:
+///   "environment.{wind,water}.temperature"
 ///
 /// Uses simple segment-based matching instead of regex to minimize memory
 /// usage on embedded platforms like ESP32.
 #[derive(Debug, Clone)]
 pub struct PathPattern {
     raw: String,
-    segments: Vec<PatternSegment>,
-    /// True if the pattern ends with a wildcard (matches any suffix)
-    trailing_wildcard: bool,
+    /// One segment list per alternative; a single one without brace
+    /// expansion.
+    alternatives: Vec<Vec<PatternSegment>>,
+    case_insensitive: bool,
 }
 
 impl PathPattern {
@@ -100,80 +120,84 @@ impl PathPattern {
     /// - `*` at end matches any suffix (e.g., "navigation.*" matches "navigation.position.latitude")
     /// - `*` in middle matches exactly one segment (e.g., "propulsion.*.revolutions")
     /// - `*` alone matches any path
+    /// - `**` matches zero or more segments anywhere (e.g., "propulsion.**.temperature"
+    ///   matches "propulsion.port.exhaust.temperature" and "propulsion.temperature")
     pub fn new(pattern: &str) -> Result<Self, PatternError> {
-        let raw = pattern.to_string();
-        let parts: Vec<&str> = pattern.split('.').collect();
+        Self::new_with_options(pattern, PatternOptions::default())
+    }
 
-        // Check for empty pattern
-        if parts.is_empty() || (parts.len() == 1 && parts[0].is_empty()) {
+    /// Create a path pattern with matching options.
+    ///
+    /// With the default options this is [`PathPattern::new`]. Brace groups
+    /// may be nested and several may appear in one pattern; they may
+    /// expand to at most 64 alternatives.
+    pub fn new_with_options(pattern: &str, options: PatternOptions) -> Result<Self, PatternError> {
+        if pattern.is_empty() {
             return Err(PatternError::EmptyPattern);
         }
 
-        let trailing_wildcard = parts.last() == Some(&"*");
-
-        let segments: Vec<PatternSegment> = parts
-            .iter()
-            .map(|&s| {
-                if s == "*" {
-                    PatternSegment::Wildcard
-                } else {
-                    PatternSegment::Literal(s.to_string())
-                }
-            })
-            .collect();
+        let alternatives = if options.brace_expansion {
+            expand_braces(pattern)?
+                .iter()
+                .map(|alternative| parse_segments(alternative))
+                .collect()
+        } else {
+            vec![parse_segments(pattern)]
+        };
 
         Ok(Self {
-            raw,
-            segments,
-            trailing_wildcard,
+            raw: pattern.to_string(),
+            alternatives,
+            case_insensitive: options.case_insensitive,
         })
     }
 
     /// Check if a path matches this pattern.
     pub fn matches(&self, path: &str) -> bool {
         let path_parts: Vec<&str> = path.split('.').collect();
+        self.alternatives
+            .iter()
+            .any(|segments| self.match_segments(segments, &path_parts))
+    }
 
-        // Special case: single wildcard matches everything
-        if self.segments.len() == 1 && self.segments[0] == PatternSegment::Wildcard {
-            return true;
-        }
-
-        // If trailing wildcard, path must have at least (pattern_len - 1) segments
-        // If no trailing wildcard, path must have exactly pattern_len segments
-        if self.trailing_wildcard {
-            if path_parts.len() < self.segments.len() - 1 {
-                return false;
-            }
-        } else if path_parts.len() != self.segments.len() {
-            return false;
-        }
+    /// Match pattern segments against path segments.
+    ///
+    /// Works back from the ends of both, so each pair of positions is
+    /// decided once: `**` takes O(segments × parts) however many appear,
+    /// rather than backtracking over every split.
+    fn match_segments(&self, segments: &[PatternSegment], parts: &[&str]) -> bool {
+        // `matched[j]`: the pattern from the current segment on matches
+        // `parts[j..]`; starts as the empty pattern, matching only the end
+        let mut matched: Vec<bool> = (0..=parts.len()).map(|j| j == parts.len()).collect();
 
-        // Match each segment
-        for (i, segment) in self.segments.iter().enumerate() {
-            match segment {
-                PatternSegment::Literal(lit) => {
-                    if i >= path_parts.len() || path_parts[i] != lit {
-                        return false;
-                    }
-                }
-                PatternSegment::Wildcard => {
+        for (i, segment) in segments.iter().enumerate().rev() {
+            let trailing = i + 1 == segments.len();
+            // `next` is what `matched[j + 1]` held for the following segment
+            let mut next = false;
+            for j in (0..=parts.len()).rev() {
+                let rest = matched[j];
+                matched[j] = match (segment, parts.get(j)) {
+                    // Zero segments, or one more than from `j + 1`
+                    (PatternSegment::DoubleWildcard, Some(_)) => rest || matched[j + 1],
+                    (PatternSegment::DoubleWildcard, None) => rest,
                     // Trailing wildcard matches any remaining suffix
-                    if self.trailing_wildcard && i == self.segments.len() - 1 {
-                        return true;
-                    }
-                    // Mid-path wildcard must have a corresponding path segment
-                    if i >= path_parts.len() {
-                        return false;
+                    (PatternSegment::Wildcard, _) if trailing => true,
+                    // Mid-path wildcard matches any single (non-empty) segment
+                    (PatternSegment::Wildcard, Some(part)) => !part.is_empty() && next,
+                    (PatternSegment::Literal(lit), Some(part)) => {
+                        let equal = if self.case_insensitive {
+                            part.eq_ignore_ascii_case(lit)
+                        } else {
+                            part == lit
+                        };
+                        equal && next
                     }
-                    // Wildcard matches any single segment (non-empty)
-                    if path_parts[i].is_empty() {
-                        return false;
-                    }
-                }
+                    (_, None) => false,
+                };
+                next = rest;
             }
         }
-
-        true
+        matched[0]
     }
 
     /// Get the raw pattern string.
@@ -182,11 +206,77 @@ impl PathPattern {
     }
 }
 
+/// Split a pattern into its segments; consecutive `**` match the same as
+/// one and are collapsed.
+fn parse_segments(pattern: &str) -> Vec<PatternSegment> {
+    let mut segments: Vec<PatternSegment> = pattern
+        .split('.')
+        .map(|s| match s {
+            "*" => PatternSegment::Wildcard,
+            "**" => PatternSegment::DoubleWildcard,
+            _ => PatternSegment::Literal(s.to_string()),
+        })
+        .collect();
+    segments.dedup_by(|a, b| {
+        *a == PatternSegment::DoubleWildcard && *b == PatternSegment::DoubleWildcard
+    });
+    segments
+}
+
+/// Expand the `{a,b}` groups of a pattern into its alternatives, in order.
+fn expand_braces(pattern: &str) -> Result<Vec<String>, PatternError> {
+    let Some(open) = pattern.find('{') else {
+        if pattern.contains('}') {
+            return Err(PatternError::UnbalancedBraces);
+        }
+        return Ok(vec![pattern.to_string()]);
+    };
+
+    // Find the matching close brace and the commas at this level
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut close = None;
+    for (i, c) in pattern[open..].char_indices().map(|(i, c)| (open + i, c)) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => commas.push(i),
+            _ => {}
+        }
+    }
+    let close = close.ok_or(PatternError::UnbalancedBraces)?;
+
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    let mut bounds = vec![open];
+    bounds.extend(commas);
+    bounds.push(close);
+
+    let mut alternatives = Vec::new();
+    for window in bounds.windows(2) {
+        let choice = &pattern[window[0] + 1..window[1]];
+        alternatives.extend(expand_braces(&format!("{prefix}{choice}{suffix}"))?);
+        if alternatives.len() > MAX_ALTERNATIVES {
+            return Err(PatternError::TooManyAlternatives);
+        }
+    }
+    Ok(alternatives)
+}
+
 /// Errors that can occur when creating a path pattern.
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum PatternError {
     #[error("Empty pattern")]
     EmptyPattern,
+    #[error("Unbalanced braces in pattern")]
+    UnbalancedBraces,
+    #[error("Pattern expands to more than {MAX_ALTERNATIVES} alternatives")]
+    TooManyAlternatives,
 }
 
 #[cfg(test)]
@@ -242,4 +332,146 @@ mod tests {
         assert!(pattern.matches("anything.at.all"));
         assert!(pattern.matches("x"));
     }
+
+    #[test]
+    fn test_double_wildcard_middle() {
+        let pattern = PathPattern::new("propulsion.**.temperature").unwrap();
+        assert!(pattern.matches("propulsion.port.exhaust.temperature"));
+        assert!(pattern.matches("propulsion.port.temperature"));
+        assert!(pattern.matches("propulsion.temperature"));
+        assert!(!pattern.matches("propulsion.port.exhaust.pressure"));
+        assert!(!pattern.matches("electrical.batteries.house.temperature"));
+    }
+
+    #[test]
+    fn test_double_wildcard_start() {
+        let pattern = PathPattern::new("**.temperature").unwrap();
+        assert!(pattern.matches("temperature"));
+        assert!(pattern.matches("environment.inside.temperature"));
+        assert!(!pattern.matches("environment.inside.humidity"));
+        // Backtracks past earlier occurrences of the literal
+        assert!(pattern.matches("temperature.sensor.temperature"));
+        assert!(!pattern.matches("environment.temperature.offset"));
+    }
+
+    #[test]
+    fn test_double_wildcard_end() {
+        let pattern = PathPattern::new("navigation.**").unwrap();
+        assert!(pattern.matches("navigation"));
+        assert!(pattern.matches("navigation.speedOverGround"));
+        assert!(pattern.matches("navigation.course.rhumbline.nextPoint"));
+        assert!(!pattern.matches("propulsion.port.revolutions"));
+
+        assert!(PathPattern::new("**").unwrap().matches("anything.at.all"));
+    }
+
+    #[test]
+    fn test_many_double_wildcards_match_quickly() {
+        let path = ["a"; 18].join(".");
+        let started = std::time::Instant::now();
+
+        // Collapsed into one `**`
+        let pattern = PathPattern::new(&format!("{}.zz", ["**"; 12].join("."))).unwrap();
+        assert!(!pattern.matches(&path));
+        assert!(pattern.matches("a.zz"));
+        // Separated by literals, so nothing collapses
+        let pattern = PathPattern::new(&format!("{}.zz", ["**.a"; 12].join("."))).unwrap();
+        assert!(!pattern.matches(&path));
+        assert!(pattern.matches(&format!("{path}.zz")));
+
+        // Backtracking took close to a second per match in release builds
+        assert!(started.elapsed() < std::time::Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_mixed_wildcards() {
+        let pattern = PathPattern::new("propulsion.*.**.temperature").unwrap();
+        assert!(pattern.matches("propulsion.port.temperature"));
+        assert!(pattern.matches("propulsion.port.exhaust.temperature"));
+        assert!(pattern.matches("propulsion.port.coolant.inlet.temperature"));
+        // `*` still needs a segment of its own
+        assert!(!pattern.matches("propulsion.temperature"));
+
+        let pattern = PathPattern::new("**.batteries.*.voltage").unwrap();
+        assert!(pattern.matches("electrical.batteries.house.voltage"));
+        assert!(!pattern.matches("electrical.batteries.voltage"));
+    }
+
+    fn with_options(pattern: &str, case_insensitive: bool, brace_expansion: bool) -> PathPattern {
+        PathPattern::new_with_options(
+            pattern,
+            PatternOptions {
+                case_insensitive,
+                brace_expansion,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_brace_expansion() {
+        assert_eq!(
+            expand_braces("environment.{wind,water}.temperature").unwrap(),
+            [
+                "environment.wind.temperature",
+                "environment.water.temperature"
+            ]
+        );
+        assert_eq!(
+            expand_braces("{a,b}.{c,d}").unwrap(),
+            ["a.c", "a.d", "b.c", "b.d"]
+        );
+        assert_eq!(
+            expand_braces("electrical.{batteries.{1,2},solar}.voltage").unwrap(),
+            [
+                "electrical.batteries.1.voltage",
+                "electrical.batteries.2.voltage",
+                "electrical.solar.voltage"
+            ]
+        );
+        assert!(matches!(
+            expand_braces("navigation.{position"),
+            Err(PatternError::UnbalancedBraces)
+        ));
+        assert!(matches!(
+            expand_braces("a}"),
+            Err(PatternError::UnbalancedBraces)
+        ));
+        assert!(matches!(
+            expand_braces("{a,b}.{a,b}.{a,b}.{a,b}.{a,b}.{a,b}.{a,b}"),
+            Err(PatternError::TooManyAlternatives)
+        ));
+
+        let pattern = with_options("environment.{wind,water}.temperature", false, true);
+        assert!(pattern.matches("environment.wind.temperature"));
+        assert!(pattern.matches("environment.water.temperature"));
+        assert!(!pattern.matches("environment.inside.temperature"));
+
+        let pattern = with_options("propulsion.{port,starboard}.*", false, true);
+        assert!(pattern.matches("propulsion.port.revolutions"));
+        assert!(!pattern.matches("propulsion.center.revolutions"));
+
+        // Braces are literal without the option
+        let pattern = PathPattern::new("environment.{wind,water}.temperature").unwrap();
+        assert!(!pattern.matches("environment.wind.temperature"));
+        assert!(pattern.matches("environment.{wind,water}.temperature"));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let pattern = with_options("navigation.speedOverGround", true, false);
+        assert!(pattern.matches("Navigation.SpeedOverGround"));
+        assert!(pattern.matches("NAVIGATION.speedoverground"));
+        assert!(!pattern.matches("navigation.speedThroughWater"));
+
+        let pattern = with_options("propulsion.*.Revolutions", true, false);
+        assert!(pattern.matches("propulsion.port.revolutions"));
+
+        let pattern = with_options("Environment.{Wind,Water}.temperature", true, true);
+        assert!(pattern.matches("environment.water.Temperature"));
+
+        assert!(!PathPattern::new("navigation.speedOverGround")
+            .unwrap()
+            .matches("Navigation.SpeedOverGround"));
+    }
 }
@@ -0,0 +1,185 @@
+//! Source priorities for paths reported by several sources.
+//!
+//! The store keeps every source's value of a path, and normally the most
+//! recent one becomes the path's `value` and `$source`. With
+//! [`SourcePriorities`] configured for a path, a preferred source stays
+//! primary even if a less preferred one updates more often, until it hasn't
+//! been heard from for its timeout:
+//!
+//! ```json
+//! {
+//!   "navigation.position": [
+//!     { "sourceRef": "n2k.gps", "timeout": 10000 },
+//!     { "sourceRef": "nmea0183.GP", "timeout": 60000 }
+//!   ]
+//! }
+//! ```
+//!
+//! Sources that aren't listed rank below all listed ones. Timeouts are in
+//! milliseconds and measured between update timestamps.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::path::{PathPattern, PatternError};
+
+/// A source in a path's priority list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourcePriority {
+    pub source_ref: String,
+    /// Milliseconds without an update from this source after which less
+    /// preferred sources take over.
+    pub timeout: u64,
+}
+
+impl SourcePriority {
+    /// Prefer `source_ref` while it updates at least every `timeout_ms`.
+    pub fn new(source_ref: impl Into<String>, timeout_ms: u64) -> Self {
+        Self {
+            source_ref: source_ref.into(),
+            timeout: timeout_ms,
+        }
+    }
+}
+
+/// Preferred sources per path pattern (in subscription syntax), most
+/// preferred first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SourcePriorities(pub BTreeMap<String, Vec<SourcePriority>>);
+
+impl SourcePriorities {
+    /// Set the priority list for paths matching `path`.
+    pub fn with(mut self, path: impl Into<String>, sources: Vec<SourcePriority>) -> Self {
+        self.0.insert(path.into(), sources);
+        self
+    }
+}
+
+/// [`SourcePriorities`] with compiled patterns.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PriorityRules {
+    config: SourcePriorities,
+    rules: Vec<(PathPattern, Vec<SourcePriority>)>,
+}
+
+impl PriorityRules {
+    /// Compile `priorities`.
+    pub(crate) fn new(priorities: &SourcePriorities) -> Result<Self, PatternError> {
+        let rules = priorities
+            .0
+            .iter()
+            .map(|(path, sources)| Ok((PathPattern::new(path)?, sources.clone())))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            config: priorities.clone(),
+            rules,
+        })
+    }
+
+    /// The priorities as configured.
+    pub(crate) fn config(&self) -> &SourcePriorities {
+        &self.config
+    }
+
+    /// The priority list for the context-relative `path`: one configured
+    /// for the exact path wins, then the first matching pattern.
+    fn sources_for(&self, path: &str) -> Option<&[SourcePriority]> {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.as_str() == path)
+            .or_else(|| self.rules.iter().find(|(pattern, _)| pattern.matches(path)))
+            .map(|(_, sources)| sources.as_slice())
+    }
+
+    /// Check whether a value of `path` from `incoming` at `incoming_time`
+    /// becomes primary over the one from `current` at `current_time`.
+    ///
+    /// Without priorities for the path, the most recent value wins.
+    pub(crate) fn takes_over(
+        &self,
+        path: &str,
+        current: &str,
+        current_time: Option<&str>,
+        incoming: &str,
+        incoming_time: Option<&str>,
+    ) -> bool {
+        if current == incoming {
+            return true;
+        }
+        let Some(sources) = self.sources_for(path) else {
+            return true;
+        };
+        let rank = |source: &str| sources.iter().position(|p| p.source_ref == source);
+        let timeout = match (rank(current), rank(incoming)) {
+            (Some(current), Some(incoming)) if incoming < current => return true,
+            (Some(current), _) => sources[current].timeout,
+            (None, _) => return true,
+        };
+
+        // A less preferred source only takes over from a stale one
+        match (parse_time(current_time), parse_time(incoming_time)) {
+            (Some(current), Some(incoming)) => {
+                (incoming - current).num_milliseconds() > timeout as i64
+            }
+            _ => true,
+        }
+    }
+}
+
+fn parse_time(timestamp: Option<&str>) -> Option<DateTime<Utc>> {
+    timestamp
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> PriorityRules {
+        PriorityRules::new(&SourcePriorities::default().with(
+            "navigation.*",
+            vec![
+                SourcePriority::new("n2k.gps", 10_000),
+                SourcePriority::new("nmea0183.GP", 60_000),
+            ],
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_takes_over() {
+        let rules = rules();
+        let at = |secs: u32| format!("2024-01-17T10:00:{secs:02}.000Z");
+        let takes_over = |current, current_secs, incoming, incoming_secs| {
+            rules.takes_over(
+                "navigation.position",
+                current,
+                Some(&at(current_secs)),
+                incoming,
+                Some(&at(incoming_secs)),
+            )
+        };
+
+        // Preferred sources always take over
+        assert!(takes_over("nmea0183.GP", 0, "n2k.gps", 1));
+        assert!(takes_over("ais", 0, "nmea0183.GP", 1));
+        // Less preferred ones only once the current source is stale
+        assert!(!takes_over("n2k.gps", 0, "nmea0183.GP", 10));
+        assert!(takes_over("n2k.gps", 0, "nmea0183.GP", 11));
+        assert!(!takes_over("n2k.gps", 0, "ais", 5));
+        // Unlisted sources, and paths without priorities, go by recency
+        assert!(rules.takes_over("navigation.position", "ais", None, "other", None));
+        assert!(rules.takes_over("environment.depth", "n2k.gps", None, "ais", None));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let priorities = SourcePriorities::default().with("", vec![]);
+        assert!(PriorityRules::new(&priorities).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Rejection of sentinel values from faulty sensors.
+//!
+//! Some sensors report `0`, a `0,0` position or NaN (serialized as `null`)
+//! when they have no fix or reading. Stored as-is, these overwrite good data.
+//! A [`SentinelFilter`] drops such values for the paths it is configured for
+//! before the delta reaches the store.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::Delta;
+use crate::path::{PathPattern, PatternError};
+
+/// A value treated as "no reading".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Sentinel {
+    /// Exactly `0`.
+    Zero,
+    /// `null`, as sent for NaN.
+    Null,
+    /// A position with latitude and longitude both exactly `0`.
+    NullIsland,
+}
+
+impl Sentinel {
+    /// Check whether `value` is this sentinel.
+    pub fn matches(self, value: &Value) -> bool {
+        match self {
+            Sentinel::Zero => value.as_f64() == Some(0.0),
+            Sentinel::Null => value.is_null(),
+            Sentinel::NullIsland => {
+                value["latitude"].as_f64() == Some(0.0) && value["longitude"].as_f64() == Some(0.0)
+            }
+        }
+    }
+}
+
+/// Sentinels to reject for paths matching a pattern.
+///
+/// ```json
+/// { "path": "navigation.position", "reject": ["nullIsland", "null"] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SentinelRule {
+    /// Path pattern, in subscription syntax.
+    pub path: String,
+    pub reject: Vec<Sentinel>,
+}
+
+impl SentinelRule {
+    /// Reject `sentinels` for paths matching `path`.
+    pub fn new(path: impl Into<String>, sentinels: &[Sentinel]) -> Self {
+        Self {
+            path: path.into(),
+            reject: sentinels.to_vec(),
+        }
+    }
+}
+
+/// Compiled set of sentinel rules.
+#[derive(Debug, Clone, Default)]
+pub struct SentinelFilter {
+    rules: Vec<(PathPattern, Vec<Sentinel>)>,
+}
+
+impl SentinelFilter {
+    /// Compile the given rules.
+    pub fn new(rules: &[SentinelRule]) -> Result<Self, PatternError> {
+        let rules = rules
+            .iter()
+            .map(|rule| Ok((PathPattern::new(&rule.path)?, rule.reject.clone())))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Whether no rules are configured.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check whether `value` at `path` is a rejected sentinel.
+    pub fn rejects(&self, path: &str, value: &Value) -> bool {
+        self.rules.iter().any(|(pattern, sentinels)| {
+            pattern.matches(path) && sentinels.iter().any(|sentinel| sentinel.matches(value))
+        })
+    }
+
+    /// Drop rejected values from a delta.
+    ///
+    /// Updates left without values (and without meta) are removed; returns
+    /// `None` if nothing is left.
+    pub fn filter(&self, mut delta: Delta) -> Option<Delta> {
+        if self.is_empty() {
+            return Some(delta);
+        }
+        for update in &mut delta.updates {
+            update
+                .values
+                .retain(|pv| !self.rejects(&pv.path, &pv.value));
+        }
+        delta
+            .updates
+            .retain(|update| !update.values.is_empty() || update.meta.is_some());
+        (!delta.updates.is_empty()).then_some(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{PathValue, Update};
+
+    fn delta(values: Vec<(&str, Value)>) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("nmea0183.GP".to_string()),
+                source: None,
+                timestamp: None,
+                values: values
+                    .into_iter()
+                    .map(|(path, value)| PathValue {
+                        path: path.to_string(),
+                        value,
+                    })
+                    .collect(),
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_null_island_position_dropped() {
+        let filter = SentinelFilter::new(&[SentinelRule::new(
+            "navigation.position",
+            &[Sentinel::NullIsland],
+        )])
+        .unwrap();
+
+        let null_island = serde_json::json!({ "latitude": 0.0, "longitude": 0.0 });
+        assert!(filter
+            .filter(delta(vec![("navigation.position", null_island.clone())]))
+            .is_none());
+
+        // Valid positions pass, as does 0,0 on paths without the rule
+        let valid = serde_json::json!({ "latitude": 52.1, "longitude": 0.0 });
+        let kept = filter
+            .filter(delta(vec![
+                ("navigation.position", valid.clone()),
+                ("navigation.anchor.position", null_island),
+            ]))
+            .unwrap();
+        assert_eq!(kept.updates[0].values.len(), 2);
+        assert_eq!(kept.updates[0].values[0].value, valid);
+    }
+
+    #[test]
+    fn test_zero_and_null_rejected_by_pattern() {
+        let rules: Vec<SentinelRule> = serde_json::from_str(
+            r#"[{"path": "environment.depth.*", "reject": ["zero", "null"]}]"#,
+        )
+        .unwrap();
+        let filter = SentinelFilter::new(&rules).unwrap();
+
+        let kept = filter
+            .filter(delta(vec![
+                ("environment.depth.belowTransducer", serde_json::json!(0)),
+                ("environment.depth.belowKeel", Value::Null),
+                ("environment.depth.belowSurface", serde_json::json!(4.2)),
+                ("navigation.speedOverGround", serde_json::json!(0.0)),
+            ]))
+            .unwrap();
+        let paths: Vec<&str> = kept.updates[0]
+            .values
+            .iter()
+            .map(|pv| pv.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "environment.depth.belowSurface",
+                "navigation.speedOverGround"
+            ]
+        );
+
+        assert!(SentinelFilter::new(&[SentinelRule::new("", &[Sentinel::Zero])]).is_err());
+    }
+}
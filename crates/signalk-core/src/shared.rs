@@ -0,0 +1,138 @@
+//! A [`MemoryStore`] shared between threads, with copy-on-write views for
+//! readers of the whole model.
+//!
+//! Behind a single `RwLock<MemoryStore>`, a reader building the full model
+//! or priming a client holds off every writer for as long as it takes.
+//! [`SharedStore`] keeps the live store for writes and quick lookups, and
+//! hands readers of larger parts an immutable [`view`](SharedStore::view):
+//! a copy taken by the first reader after a change and shared by every
+//! reader until the next one. Writers only ever wait for that copy, never
+//! for what readers do with it.
+
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::store::MemoryStore;
+
+/// A store shared between writers and readers, safe to share as-is.
+#[derive(Debug)]
+pub struct SharedStore {
+    live: RwLock<MemoryStore>,
+    /// Copy of `live` as of its last write, if a reader has asked for one.
+    view: RwLock<Option<Arc<MemoryStore>>>,
+}
+
+impl SharedStore {
+    /// Share `store`.
+    pub fn new(store: MemoryStore) -> Self {
+        Self {
+            live: RwLock::new(store),
+            view: RwLock::new(None),
+        }
+    }
+
+    // A panicking writer can at worst leave a half-applied delta behind,
+    // which the next delta for that path overwrites, so poisoned locks are
+    // recovered rather than propagated.
+
+    /// Read access to the live store, for lookups short enough to hold off
+    /// writers; prefer [`view`](Self::view) for anything walking the model.
+    pub fn read(&self) -> RwLockReadGuard<'_, MemoryStore> {
+        self.live.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write access to the live store.
+    ///
+    /// Views taken from now on reflect the write, once the guard is dropped.
+    pub fn write(&self) -> RwLockWriteGuard<'_, MemoryStore> {
+        let live = self.live.write().unwrap_or_else(|e| e.into_inner());
+        *self.view.write().unwrap_or_else(|e| e.into_inner()) = None;
+        live
+    }
+
+    /// An immutable copy of the store as of its last write.
+    ///
+    /// The copy is made once per change, under the read lock, and then
+    /// shared, so readers can take as long as they like with it.
+    pub fn view(&self) -> Arc<MemoryStore> {
+        if let Some(view) = &*self.view.read().unwrap_or_else(|e| e.into_inner()) {
+            return view.clone();
+        }
+        // Cached before the read lock is released, so no write can clear
+        // the cache in between and leave a stale copy behind
+        let live = self.read();
+        let mut cached = self.view.write().unwrap_or_else(|e| e.into_inner());
+        cached.get_or_insert_with(|| Arc::new(live.clone())).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Delta, PathValue, Update};
+    use crate::store::SignalKStore;
+
+    fn sog(value: f64) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("test.1".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(value),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    fn sog_of(store: &MemoryStore) -> serde_json::Value {
+        store.get_self_path("navigation.speedOverGround").unwrap()["value"].clone()
+    }
+
+    #[test]
+    fn test_view_shared_until_write() {
+        let store = SharedStore::new(MemoryStore::new("vessels.urn:mrn:signalk:uuid:test"));
+        store.write().apply_delta(&sog(3.5));
+
+        let first = store.view();
+        assert!(Arc::ptr_eq(&first, &store.view()));
+        assert_eq!(sog_of(&first), 3.5);
+
+        // A write leaves earlier views alone and shows in later ones
+        store.write().apply_delta(&sog(4.0));
+        assert_eq!(sog_of(&first), 3.5);
+        assert_eq!(sog_of(&store.read()), 4.0);
+        let second = store.view();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(sog_of(&second), 4.0);
+    }
+
+    #[test]
+    fn test_view_while_writing() {
+        let store = Arc::new(SharedStore::new(MemoryStore::new(
+            "vessels.urn:mrn:signalk:uuid:test",
+        )));
+        store.write().apply_delta(&sog(0.0));
+
+        let writer = {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                for i in 1..=500 {
+                    store.write().apply_delta(&sog(f64::from(i)));
+                }
+            })
+        };
+        // Views only ever move forward
+        let mut last = 0.0;
+        while !writer.is_finished() {
+            let seen = sog_of(&store.view()).as_f64().unwrap();
+            assert!(seen >= last);
+            last = seen;
+        }
+        writer.join().unwrap();
+        assert_eq!(sog_of(&store.view()), 500.0);
+    }
+}
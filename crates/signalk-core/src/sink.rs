@@ -0,0 +1,69 @@
+//! Delta sink abstraction.
+//!
+//! Providers hand their deltas to a [`DeltaSink`] instead of a concrete
+//! channel type, so they stay independent of the async runtime and can be
+//! tested with a plain collecting sink such as `Mutex<Vec<Delta>>`.
+//!
+//! Providers that hold a connection also tell their sink about it through
+//! [`DeltaSink::report_state`], so servers can show which sources are up.
+
+use std::sync::{Arc, Mutex};
+
+use crate::model::Delta;
+
+/// Connection state of a provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderState {
+    /// Trying to reach its source.
+    Connecting,
+    /// Receiving from its source.
+    Connected,
+    /// Lost or unable to reach its source.
+    Error(String),
+}
+
+/// A destination for deltas produced by providers.
+pub trait DeltaSink: Send + Sync {
+    /// Submit a delta for application to the store and broadcast.
+    fn submit(&self, delta: Delta);
+
+    /// Note a change in the provider's connection state.
+    ///
+    /// Ignored unless the sink tracks provider status.
+    fn report_state(&self, _state: ProviderState) {}
+}
+
+impl<T: DeltaSink + ?Sized> DeltaSink for Arc<T> {
+    fn submit(&self, delta: Delta) {
+        (**self).submit(delta);
+    }
+
+    fn report_state(&self, state: ProviderState) {
+        (**self).report_state(state);
+    }
+}
+
+/// Collects deltas in memory (useful in tests).
+impl DeltaSink for Mutex<Vec<Delta>> {
+    fn submit(&self, delta: Delta) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).push(delta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collecting_sink() {
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let delta = Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![],
+        };
+
+        sink.submit(delta.clone());
+
+        assert_eq!(*sink.lock().unwrap(), vec![delta]);
+    }
+}
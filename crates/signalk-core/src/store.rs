@@ -31,10 +31,174 @@
 //!
 //! The store also maintains a `/sources` tree that tracks all data sources
 //! that have provided data. This is populated automatically from delta messages.
-
-use crate::model::{Delta, PathValue, Source, Update};
+//!
+//! ## Numeric History
+//!
+//! With [`MemoryStore::set_history_len`], the store keeps the last N numeric
+//! values of each path in a ring buffer, from which
+//! [`MemoryStore::path_stats`] computes min/max/mean for trend widgets.
+//!
+//! ## Snapshots
+//!
+//! [`MemoryStore::snapshot`] captures the data tree so a restarted server
+//! can [`restore`](MemoryStore::restore) it instead of starting empty.
+//!
+//! ## Source Priorities
+//!
+//! With [`MemoryStore::set_source_priorities`], a preferred source stays the
+//! primary `value`/`$source` of a path while it keeps reporting, even if
+//! others report more often; see [`SourcePriorities`].
+//!
+//! ## Merged Paths
+//!
+//! Values are normally replaced wholesale. Paths matching the merge patterns
+//! (by default [`DEFAULT_MERGE_PATHS`]) carry objects that sources report
+//! piecemeal, e.g. `design.length` split into `overall` and `hull`; object
+//! values there are deep-merged into the stored object instead. A store
+//! created with [`MergeStrategy::DeepMergeObjects`] merges object values at
+//! every path, so e.g. an `altitude`-only position keeps the last latitude
+//! and longitude.
+//!
+//! ## PUT Handlers
+//!
+//! Paths like `steering.autopilot.target.headingTrue` only change when the
+//! device behind them accepts the new value. A handler registered with
+//! [`MemoryStore::register_put_handler`] is asked first; only if it accepts
+//! does [`MemoryStore::apply_put`] store the value. A handler registered with
+//! [`MemoryStore::register_async_put_handler`] only starts the change; the
+//! value is stored when the device reports it.
+//!
+//! [`MemoryStore::resolve_put`] is the whole PUT decision shared by the
+//! WebSocket and REST handlers: handlers first, then the writable allowlist.
+
+use crate::model::{Delta, Meta, PathMeta, PathValue, Source, Update};
+use crate::path::{PathPattern, PatternError};
+use crate::priorities::{PriorityRules, SourcePriorities};
+use crate::writable::WritablePaths;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Paths whose object values are deep-merged when the settings don't
+/// configure any.
+pub const DEFAULT_MERGE_PATHS: &[&str] = &["design.*", "sensors.*.sensorData"];
+
+/// How object values are combined with the stored value of a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Replace values wholesale, except at the configured merge paths.
+    #[default]
+    ReplaceValues,
+    /// Deep-merge object values key by key at every path. Any other value,
+    /// including `null`, still replaces the stored one.
+    DeepMergeObjects,
+}
+
+/// Errors from restructuring the store.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StoreError {
+    #[error("Not a context: {0}")]
+    InvalidContext(String),
+    #[error("Context not found: {0}")]
+    ContextNotFound(String),
+    #[error("Context already exists: {0}")]
+    ContextExists(String),
+    #[error("Cannot move the self context")]
+    SelfContext,
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+}
+
+/// Applies a PUT value to the device behind a path, or explains why not.
+pub type PutHandler = dyn Fn(&Value) -> Result<(), String> + Send + Sync;
+
+/// Outcome of [`MemoryStore::apply_put`] and [`MemoryStore::resolve_put`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PutResult {
+    /// The handler accepted the value; carries the delta that was applied.
+    Completed(Delta),
+    /// An async handler accepted the value; the device reports it when the
+    /// change is done.
+    Pending,
+    /// No handler, but the path is writable: carries the delta to submit,
+    /// which hasn't been applied. Only from [`MemoryStore::resolve_put`].
+    Writable(Delta),
+    /// The handler rejected the value.
+    Failed(String),
+    /// No handler is registered for the path.
+    NotSupported,
+}
+
+impl PutResult {
+    /// HTTP status reported for the outcome.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::Completed(_) | Self::Writable(_) => 200,
+            Self::Pending => 202,
+            Self::Failed(_) => 400,
+            Self::NotSupported => 405,
+        }
+    }
+}
+
+/// A registered PUT handler.
+#[derive(Clone)]
+struct RegisteredPut {
+    pattern: PathPattern,
+    handler: Arc<PutHandler>,
+    /// Accepting only starts the change; see
+    /// [`MemoryStore::register_async_put_handler`].
+    is_async: bool,
+}
+
+/// Registered PUT handlers, in registration order.
+#[derive(Clone, Default)]
+struct PutHandlers(Vec<RegisteredPut>);
+
+impl std::fmt::Debug for PutHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|put| put.pattern.as_str()))
+            .finish()
+    }
+}
+
+/// A self delta setting `path` to a PUT `value`.
+fn put_delta(path: &str, value: &Value, source: Option<&str>) -> Delta {
+    Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: source.map(str::to_string),
+            source: None,
+            timestamp: Some(Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+            values: vec![PathValue {
+                path: path.to_string(),
+                value: value.clone(),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    }
+}
+
+/// The data of a [`MemoryStore`] at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreSnapshot {
+    /// Self URN of the store the snapshot was taken from.
+    pub self_urn: String,
+    /// The full data tree, including `/sources`.
+    pub data: Value,
+}
+
+impl StoreSnapshot {
+    /// Storage key snapshots are persisted under.
+    pub const STORAGE_KEY: &'static str = "store.snapshot";
+}
 
 /// Trait for SignalK data storage implementations.
 pub trait SignalKStore: Send + Sync {
@@ -58,6 +222,15 @@ pub trait SignalKStore: Send + Sync {
 
     /// Get all sources that have provided data.
     fn get_sources(&self) -> Option<Value>;
+
+    /// Remove the node at `path` within `context` (e.g. `vessels.self`),
+    /// along with any objects left empty. Returns whether it existed.
+    fn remove_path(&mut self, context: &str, path: &str) -> bool;
+
+    /// Remove values last updated more than `older_than` before `now`,
+    /// returning how many were removed. Values without a timestamp are
+    /// never pruned.
+    fn prune_stale(&mut self, older_than: Duration, now: DateTime<Utc>) -> usize;
 }
 
 /// In-memory SignalK store implementation.
@@ -71,6 +244,43 @@ pub struct MemoryStore {
     self_urn: String,
     /// SignalK version
     version: String,
+    /// Recent numeric values per absolute path (empty when disabled)
+    history: HashMap<String, VecDeque<f64>>,
+    /// Values kept per path in `history`; 0 disables it
+    history_len: usize,
+    /// Paths whose object values are merged rather than replaced
+    merge_paths: Vec<PathPattern>,
+    /// Whether object values are merged at all paths
+    merge_strategy: MergeStrategy,
+    /// Handlers for writable self paths
+    put_handlers: PutHandlers,
+    /// Which source's value is primary, for paths with several
+    source_priorities: PriorityRules,
+}
+
+/// Top-level groups whose children are contexts.
+const CONTEXT_GROUPS: &[&str] = &["vessels", "aircraft", "aton", "sar", "shore"];
+
+/// `(source_ref, timestamp, value)` triples, as for [`Update::group_by_source`].
+type SourcedValues = Vec<(Option<String>, Option<String>, PathValue)>;
+
+/// What [`MemoryStore::full_model_as_deltas`] collects from one context.
+#[derive(Default)]
+struct ModelValues {
+    /// The current value of each path.
+    current: SourcedValues,
+    /// Values from sources other than the current one.
+    other_sources: SourcedValues,
+    meta: Vec<PathMeta>,
+}
+
+/// Aggregate statistics over the retained numeric values of a path.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PathNumericStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub count: usize,
 }
 
 impl MemoryStore {
@@ -96,7 +306,290 @@ impl MemoryStore {
             data,
             self_urn: self_urn.to_string(),
             version: "1.7.0".to_string(),
+            history: HashMap::new(),
+            history_len: 0,
+            merge_paths: DEFAULT_MERGE_PATHS
+                .iter()
+                .map(|p| PathPattern::new(p).expect("default merge paths are valid"))
+                .collect(),
+            merge_strategy: MergeStrategy::default(),
+            put_handlers: PutHandlers::default(),
+            source_priorities: PriorityRules::default(),
+        }
+    }
+
+    /// Create a new empty store that combines object values per `strategy`.
+    pub fn new_with_strategy(self_urn: &str, strategy: MergeStrategy) -> Self {
+        Self {
+            merge_strategy: strategy,
+            ..Self::new(self_urn)
+        }
+    }
+
+    /// Keep the last `len` numeric values of each path for
+    /// [`path_stats`](Self::path_stats); 0 (the default) disables history.
+    ///
+    /// Shrinking drops the oldest retained values.
+    pub fn set_history_len(&mut self, len: usize) {
+        self.history_len = len;
+        if len == 0 {
+            self.history.clear();
+        }
+        for values in self.history.values_mut() {
+            while values.len() > len {
+                values.pop_front();
+            }
+        }
+    }
+
+    /// Replace the patterns of paths whose object values are deep-merged.
+    ///
+    /// On error the current patterns are kept.
+    pub fn set_merge_paths<S: AsRef<str>>(&mut self, patterns: &[S]) -> Result<(), PatternError> {
+        self.merge_paths = patterns
+            .iter()
+            .map(|p| PathPattern::new(p.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Replace the source priorities deciding the primary value of paths
+    /// that several sources report.
+    ///
+    /// On error the current priorities are kept.
+    pub fn set_source_priorities(
+        &mut self,
+        priorities: &SourcePriorities,
+    ) -> Result<(), PatternError> {
+        self.source_priorities = PriorityRules::new(priorities)?;
+        Ok(())
+    }
+
+    /// The configured source priorities.
+    pub fn source_priorities(&self) -> &SourcePriorities {
+        self.source_priorities.config()
+    }
+
+    /// Check whether object values at the context-relative `path` are merged.
+    fn is_merge_path(&self, path: &str) -> bool {
+        self.merge_strategy == MergeStrategy::DeepMergeObjects
+            || self.merge_paths.iter().any(|p| p.matches(path))
+    }
+
+    /// Register a handler for PUTs to self paths matching `path`.
+    ///
+    /// `path` is in subscription syntax, e.g. `steering.autopilot.*`. When
+    /// several handlers match, one registered for the exact path wins, then
+    /// the first registered.
+    pub fn register_put_handler(
+        &mut self,
+        path: &str,
+        handler: Box<PutHandler>,
+    ) -> Result<(), PatternError> {
+        self.add_put_handler(path, handler, false)
+    }
+
+    /// Register a handler for PUTs to self paths matching `path` whose
+    /// device takes time to act, e.g. a windlass. Accepting a value only
+    /// starts the change: [`apply_put`](Self::apply_put) answers
+    /// [`PutResult::Pending`] and stores nothing, leaving the value to
+    /// arrive from the device. Matching is as for
+    /// [`register_put_handler`](Self::register_put_handler).
+    pub fn register_async_put_handler(
+        &mut self,
+        path: &str,
+        handler: Box<PutHandler>,
+    ) -> Result<(), PatternError> {
+        self.add_put_handler(path, handler, true)
+    }
+
+    fn add_put_handler(
+        &mut self,
+        path: &str,
+        handler: Box<PutHandler>,
+        is_async: bool,
+    ) -> Result<(), PatternError> {
+        self.put_handlers.0.push(RegisteredPut {
+            pattern: PathPattern::new(path)?,
+            handler: Arc::from(handler),
+            is_async,
+        });
+        Ok(())
+    }
+
+    /// Check whether a PUT handler is registered for the self `path`.
+    pub fn has_put_handler(&self, path: &str) -> bool {
+        self.put_handler(path).is_some()
+    }
+
+    fn put_handler(&self, path: &str) -> Option<&RegisteredPut> {
+        let handlers = &self.put_handlers.0;
+        handlers
+            .iter()
+            .find(|put| put.pattern.as_str() == path)
+            .or_else(|| handlers.iter().find(|put| put.pattern.matches(path)))
+    }
+
+    /// Hand a PUT to the self `path` to its handler, and store the value if
+    /// the handler accepts it.
+    pub fn apply_put(&mut self, path: &str, value: &Value) -> PutResult {
+        let Some(put) = self.put_handler(path) else {
+            return PutResult::NotSupported;
+        };
+        if let Err(message) = (put.handler)(value) {
+            return PutResult::Failed(message);
+        }
+        if put.is_async {
+            return PutResult::Pending;
+        }
+
+        let delta = put_delta(path, value, None);
+        self.apply_delta(&delta);
+        PutResult::Completed(delta)
+    }
+
+    /// Decide a PUT of `value` to the self `path`: a registered handler
+    /// answers as in [`apply_put`](Self::apply_put); without one, a path
+    /// in `writable` gives [`PutResult::Writable`] with a delta sourced from
+    /// `source`, for the caller to submit like any other.
+    pub fn resolve_put(
+        &mut self,
+        path: &str,
+        value: &Value,
+        source: Option<&str>,
+        writable: &WritablePaths,
+    ) -> PutResult {
+        match self.apply_put(path, value) {
+            PutResult::NotSupported if writable.is_writable(path) => {
+                PutResult::Writable(put_delta(path, value, source))
+            }
+            result => result,
+        }
+    }
+
+    /// Min/max/mean over the retained numeric values of an absolute path.
+    ///
+    /// `vessels.self` resolves to the self URN. Returns `None` if history is
+    /// disabled or the path has no numeric values.
+    pub fn path_stats(&self, path: &str) -> Option<PathNumericStats> {
+        let values = self.history.get(self.resolve_path(path).as_ref())?;
+        let count = values.len();
+        if count == 0 {
+            return None;
+        }
+        let (min, max, sum) = values.iter().fold(
+            (f64::INFINITY, f64::NEG_INFINITY, 0.0),
+            |(min, max, sum), &value| (min.min(value), max.max(value), sum + value),
+        );
+        Some(PathNumericStats {
+            min,
+            max,
+            mean: sum / count as f64,
+            count,
+        })
+    }
+
+    /// Record a numeric value in the path's history ring buffer.
+    fn record_history(&mut self, path: String, value: f64) {
+        let values = self.history.entry(path).or_default();
+        if values.len() == self.history_len {
+            values.pop_front();
+        }
+        values.push_back(value);
+    }
+
+    /// Change the self vessel URN.
+    ///
+    /// Subsequent `vessels.self` deltas and lookups resolve to the new URN.
+    /// Data stored under the previous URN is kept and becomes an ordinary
+    /// vessel context.
+    pub fn set_self_urn(&mut self, self_urn: &str) {
+        let urn_key = self_urn.strip_prefix("vessels.").unwrap_or(self_urn);
+
+        self.data["self"] = Value::String(self_urn.to_string());
+        if let Some(vessels) = self.data["vessels"].as_object_mut() {
+            vessels
+                .entry(urn_key)
+                .or_insert_with(|| Value::Object(Default::default()));
+        }
+        self.self_urn = self_urn.to_string();
+    }
+
+    /// Move the whole subtree of context `from` (e.g.
+    /// `vessels.urn:mrn:imo:mmsi:230000001`) to context `to`, for when a
+    /// target's identity is corrected after the fact.
+    ///
+    /// Fails if `to` already exists; the self context can't be moved, nor
+    /// can another context be moved onto it (`vessels.self` included).
+    /// Numeric history follows the data.
+    pub fn move_context(&mut self, from: &str, to: &str) -> Result<(), StoreError> {
+        let split = |context: &str| {
+            context
+                .split_once('.')
+                .filter(|(group, key)| CONTEXT_GROUPS.contains(group) && !key.is_empty())
+                .map(|(group, key)| (group.to_string(), key.to_string()))
+                .ok_or_else(|| StoreError::InvalidContext(context.to_string()))
+        };
+        let from = self.resolve_context(from);
+        let to = self.resolve_context(to);
+        let (from_group, from_key) = split(&from)?;
+        let (to_group, to_key) = split(&to)?;
+        if from == self.self_urn || to == self.self_urn {
+            return Err(StoreError::SelfContext);
+        }
+        if self.data[&to_group].get(&to_key).is_some() {
+            return Err(StoreError::ContextExists(to));
+        }
+
+        let subtree = self.data[&from_group]
+            .as_object_mut()
+            .and_then(|group| group.remove(&from_key))
+            .ok_or_else(|| StoreError::ContextNotFound(from.clone()))?;
+        if !self.data[&to_group].is_object() {
+            self.data[&to_group] = Value::Object(Default::default());
+        }
+        self.data[&to_group][&to_key] = subtree;
+
+        let prefix = format!("{from}.");
+        let moved: Vec<String> = self
+            .history
+            .keys()
+            .filter(|path| path.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for path in moved {
+            if let Some(values) = self.history.remove(&path) {
+                self.history
+                    .insert(format!("{to}.{}", &path[prefix.len()..]), values);
+            }
+        }
+        Ok(())
+    }
+
+    /// Capture the current data tree (numeric history is not included).
+    pub fn snapshot(&self) -> StoreSnapshot {
+        StoreSnapshot {
+            self_urn: self.self_urn.clone(),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Replace the data tree with a snapshot's.
+    ///
+    /// The store keeps its own self URN: if the snapshot was taken under a
+    /// different one, that vessel's data stays as an ordinary context.
+    pub fn restore(&mut self, snapshot: StoreSnapshot) -> Result<(), StoreError> {
+        if !snapshot.data.get("vessels").is_some_and(Value::is_object) {
+            return Err(StoreError::InvalidSnapshot("no vessels object".to_string()));
         }
+        self.data = snapshot.data;
+        if !self.data.get("sources").is_some_and(Value::is_object) {
+            self.data["sources"] = serde_json::json!({});
+        }
+        self.history.clear();
+        let self_urn = self.self_urn.clone();
+        self.set_self_urn(&self_urn);
+        Ok(())
     }
 
     /// Resolve "vessels.self" to the actual vessel URN.
@@ -110,6 +603,14 @@ impl MemoryStore {
         }
     }
 
+    /// Resolve a leading `vessels.self` in an absolute path.
+    fn resolve_path<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        match path.strip_prefix("vessels.self.") {
+            Some(rest) => Cow::Owned(format!("{}.{rest}", self.self_urn)),
+            None => Cow::Borrowed(path),
+        }
+    }
+
     /// Set a value at a path, creating intermediate objects as needed.
     /// This is the low-level setter that doesn't handle multi-source values.
     fn set_path_value(&mut self, base_path: &str, path: &str, value: Value) {
@@ -146,6 +647,9 @@ impl MemoryStore {
     /// 1. Updates the primary value and $source
     /// 2. Stores the source-specific value in the `values` map
     /// 3. Preserves existing values from other sources
+    ///
+    /// With `merge`, an object value is deep-merged into the stored object
+    /// (both the primary value and the source's entry) instead of replacing it.
     fn set_signalk_value(
         &mut self,
         base_path: &str,
@@ -153,6 +657,7 @@ impl MemoryStore {
         value: &Value,
         source_ref: Option<&str>,
         timestamp: Option<&str>,
+        merge: bool,
     ) {
         let full_path = if path.is_empty() {
             base_path.to_string()
@@ -161,6 +666,7 @@ impl MemoryStore {
         };
 
         let segments: Vec<&str> = full_path.split('.').collect();
+        let priorities = &self.source_priorities;
         let mut current = &mut self.data;
 
         // Navigate to the parent of the leaf node
@@ -169,10 +675,18 @@ impl MemoryStore {
                 // Last segment: handle SignalK value structure
                 if let Value::Object(map) = current {
                     let existing = map.get(*segment);
+                    let merged = |previous: Option<&Value>| match previous {
+                        Some(previous @ Value::Object(_)) if merge && value.is_object() => {
+                            let mut merged = previous.clone();
+                            merge_json(&mut merged, value);
+                            merged
+                        }
+                        _ => value.clone(),
+                    };
 
                     // Build the new value object
                     let mut value_obj = serde_json::json!({
-                        "value": value
+                        "value": merged(existing.and_then(|e| e.get("value")))
                     });
 
                     if let Some(src) = source_ref {
@@ -183,11 +697,20 @@ impl MemoryStore {
                         value_obj["timestamp"] = Value::String(ts.to_string());
                     }
 
+                    // Metadata outlives value updates
+                    if let Some(meta) = existing.and_then(|e| e.get("meta")) {
+                        value_obj["meta"] = meta.clone();
+                    }
+
                     // Handle the `values` map for multi-source support
                     if let Some(src) = source_ref {
                         // Create source-specific entry
+                        let previous = existing
+                            .and_then(|e| e.get("values"))
+                            .and_then(|v| v.get(src))
+                            .and_then(|e| e.get("value"));
                         let source_entry = serde_json::json!({
-                            "value": value,
+                            "value": merged(previous),
                             "timestamp": timestamp
                         });
 
@@ -210,6 +733,33 @@ impl MemoryStore {
                         value_obj["values"] = values_map;
                     }
 
+                    // A preferred source keeps the primary value until stale
+                    let primary = existing.filter(|existing| {
+                        let current = existing.get("$source").and_then(Value::as_str);
+                        match (current, source_ref) {
+                            (Some(current), Some(incoming)) => !priorities.takes_over(
+                                path,
+                                current,
+                                existing.get("timestamp").and_then(Value::as_str),
+                                incoming,
+                                timestamp,
+                            ),
+                            _ => false,
+                        }
+                    });
+                    if let Some(primary) = primary {
+                        for key in ["value", "$source", "timestamp"] {
+                            match primary.get(key) {
+                                Some(kept) => value_obj[key] = kept.clone(),
+                                None => {
+                                    if let Some(obj) = value_obj.as_object_mut() {
+                                        obj.remove(key);
+                                    }
+                                }
+                            }
+                        }
+                    }
+
                     map.insert(segment.to_string(), value_obj);
                 }
             } else {
@@ -224,8 +774,81 @@ impl MemoryStore {
         }
     }
 
+    /// Merge metadata into the `meta` object of the node at a path.
+    ///
+    /// Fields present in `meta` replace existing ones; other fields are kept,
+    /// so a units-only update does not wipe configured zones.
+    fn set_meta(&mut self, base_path: &str, path: &str, meta: &Meta) {
+        let Ok(Value::Object(fields)) = serde_json::to_value(meta) else {
+            return;
+        };
+
+        let full_path = format!("{base_path}.{path}");
+        let mut current = &mut self.data;
+
+        for segment in full_path.split('.') {
+            let Value::Object(map) = current else {
+                return;
+            };
+            current = map
+                .entry(segment.to_string())
+                .or_insert_with(|| serde_json::json!({}));
+        }
+
+        if let Value::Object(node) = current {
+            let meta_entry = node
+                .entry("meta".to_string())
+                .or_insert_with(|| serde_json::json!({}));
+            if let Value::Object(meta_map) = meta_entry {
+                meta_map.extend(fields);
+            }
+        }
+    }
+
+    /// Collect `meta` objects below `node` into a nested tree keyed by path.
+    fn collect_meta(node: &Value) -> Option<Value> {
+        let Value::Object(map) = node else {
+            return None;
+        };
+
+        let mut tree = serde_json::Map::new();
+        if let Some(Value::Object(meta)) = map.get("meta") {
+            tree.extend(meta.clone());
+        }
+
+        for (key, child) in map {
+            if matches!(
+                key.as_str(),
+                "value" | "values" | "meta" | "$source" | "timestamp"
+            ) {
+                continue;
+            }
+            if let Some(child_meta) = Self::collect_meta(child) {
+                tree.insert(key.clone(), child_meta);
+            }
+        }
+
+        (!tree.is_empty()).then_some(Value::Object(tree))
+    }
+
+    /// Get all metadata stored under a context as a nested tree.
+    ///
+    /// For example `{"navigation": {"speedOverGround": {"units": "m/s"}}}`.
+    /// Returns an empty object if the context has no metadata.
+    pub fn meta_tree(&self, context: &str) -> Value {
+        self.get_path_value(&self.resolve_context(context))
+            .as_ref()
+            .and_then(Self::collect_meta)
+            .unwrap_or_else(|| serde_json::json!({}))
+    }
+
     /// Register a source in the /sources hierarchy.
-    fn register_source(&mut self, source_ref: Option<&str>, source: Option<&Source>) {
+    fn register_source(
+        &mut self,
+        source_ref: Option<&str>,
+        source: Option<&Source>,
+        timestamp: Option<&str>,
+    ) {
         // Get or create source label
         let label = if let Some(src_ref) = source_ref {
             // $source format is usually "label.qualifier" (e.g., "nmea0183.GP", "n2k.115")
@@ -258,15 +881,25 @@ impl MemoryStore {
                     sources_map.insert(label.clone(), source_entry);
                 }
 
-                // If there's a sub-source (e.g., "115" from "n2k.115"), register it
-                if let Some(src_ref) = source_ref {
-                    let parts: Vec<&str> = src_ref.split('.').collect();
-                    if parts.len() > 1 {
-                        let sub_source = parts[1..].join(".");
-                        if let Some(Value::Object(label_entry)) = sources_map.get_mut(&label) {
-                            label_entry
-                                .entry(&sub_source)
-                                .or_insert_with(|| serde_json::json!({}));
+                // If there's a sub-source (e.g., "115" from "n2k.115", or an
+                // NMEA 2000 `src`), register it
+                let sub_source = source.and_then(|src| src.src.clone()).or_else(|| {
+                    source_ref
+                        .and_then(|src_ref| src_ref.split_once('.'))
+                        .map(|(_, sub_source)| sub_source.to_string())
+                });
+                if let Some(sub_source) = sub_source {
+                    if let Some(Value::Object(label_entry)) = sources_map.get_mut(&label) {
+                        let sub_entry = label_entry
+                            .entry(&sub_source)
+                            .or_insert_with(|| serde_json::json!({}));
+                        // A sub-source named like a label field (e.g.
+                        // "type") isn't a device entry; leave it alone
+                        match source.filter(|src| src.src.is_some()) {
+                            Some(src) if sub_entry.is_object() => {
+                                Self::register_n2k_device(sub_entry, src, timestamp);
+                            }
+                            _ => {}
                         }
                     }
                 }
@@ -274,8 +907,32 @@ impl MemoryStore {
         }
     }
 
+    /// Record an NMEA 2000 device under its sub-source entry, in the
+    /// reference server's shape: `{"n2k": {"src", "canName"}, "pgns":
+    /// {"<pgn>": "<last seen>"}}`.
+    fn register_n2k_device(entry: &mut Value, source: &Source, timestamp: Option<&str>) {
+        for key in ["n2k", "pgns"] {
+            if !entry[key].is_object() {
+                entry[key] = serde_json::json!({});
+            }
+        }
+        let n2k = &mut entry["n2k"];
+        n2k["src"] = serde_json::json!(source.src);
+        if let Some(can_name) = &source.can_name {
+            n2k["canName"] = Value::String(can_name.clone());
+        }
+        if let Some(pgn) = source.pgn {
+            entry["pgns"][pgn.to_string()] = serde_json::json!(timestamp);
+        }
+    }
+
     /// Get a value at a path.
     fn get_path_value(&self, path: &str) -> Option<Value> {
+        self.path_ref(path).cloned()
+    }
+
+    /// Borrow the node at a path.
+    fn path_ref(&self, path: &str) -> Option<&Value> {
         let segments: Vec<&str> = path.split('.').collect();
         let mut current = &self.data;
 
@@ -288,91 +945,592 @@ impl MemoryStore {
             }
         }
 
-        Some(current.clone())
+        Some(current)
     }
 
-    /// Count the number of leaf paths (values) in the store.
-    fn count_paths_recursive(value: &Value) -> usize {
-        match value {
-            Value::Object(map) => {
-                // If this object has a "value" key, it's a leaf node
-                if map.contains_key("value") {
-                    1
-                } else {
-                    map.values().map(Self::count_paths_recursive).sum()
-                }
-            }
-            _ => 0,
-        }
+    /// Get just the value at an absolute path, without the node wrapper.
+    ///
+    /// Returns what is under the node's `value` key (a scalar or an object
+    /// such as a position), or `None` if the node or its value is absent.
+    pub fn get_value(&self, path: &str) -> Option<Value> {
+        self.path_ref(path)?.get("value").cloned()
     }
 
-    /// Get the number of unique paths with values in the store.
-    pub fn path_count(&self) -> usize {
-        if let Some(vessels) = self.data.get("vessels") {
-            Self::count_paths_recursive(vessels)
-        } else {
-            0
-        }
+    /// Get the per-source `values` map of the node at an absolute path.
+    ///
+    /// Entries are keyed by `$source` and hold that source's latest `value`
+    /// and `timestamp`, so conflict UIs can show every reading rather than
+    /// only the winning one. `vessels.self` resolves to the self URN. Returns
+    /// `None` if the path has no sourced values.
+    pub fn get_path_all_sources(&self, path: &str) -> Option<Value> {
+        self.path_ref(&self.resolve_path(path))?
+            .get("values")
+            .filter(|values| values.is_object())
+            .cloned()
     }
-}
 
-impl SignalKStore for MemoryStore {
-    fn apply_delta(&mut self, delta: &Delta) {
-        // Resolve context - "vessels.self" becomes the actual URN path
-        let context = delta
-            .context
-            .as_ref()
-            .map(|c| self.resolve_context(c))
-            .unwrap_or_else(|| self.self_urn.clone());
+    /// Get the `meta` object of the node at an absolute path, such as
+    /// `{"units": "m/s"}`.
+    ///
+    /// `vessels.self` resolves to the self URN. Returns `None` if the path
+    /// has no metadata.
+    pub fn get_meta(&self, path: &str) -> Option<Value> {
+        self.path_ref(&self.resolve_path(path))?
+            .get("meta")
+            .filter(|meta| meta.is_object())
+            .cloned()
+    }
 
-        for update in &delta.updates {
-            // Register the source in the /sources hierarchy
-            self.register_source(update.source_ref.as_deref(), update.source.as_ref());
+    /// Get a slice of the full model for memory-limited clients.
+    ///
+    /// `context` scopes the result to one context (`vessels.self` resolves to
+    /// the self URN); `None` returns the whole tree. With `depth`, objects
+    /// nested deeper than `depth` levels are replaced by a truncation marker
+    /// (see [`truncate_depth`]). Returns `None` if the context doesn't exist.
+    pub fn model_slice(&self, context: Option<&str>, depth: Option<usize>) -> Option<Value> {
+        let node = match context {
+            Some(context) => self.path_ref(&self.resolve_context(context))?,
+            None => &self.data,
+        };
 
-            for pv in &update.values {
-                // Store the value with multi-source support
-                self.set_signalk_value(
-                    &context,
-                    &pv.path,
-                    &pv.value,
-                    update.source_ref.as_deref(),
-                    update.timestamp.as_deref(),
-                );
-            }
-        }
+        Some(match depth {
+            Some(depth) => truncate_depth(node, depth),
+            None => node.clone(),
+        })
     }
 
-    fn get_path(&self, path: &str) -> Option<Value> {
-        self.get_path_value(path)
-    }
+    /// Re-express the stored model as deltas, one per context.
+    ///
+    /// Every value keeps its stored `$source` and `timestamp`. For paths
+    /// with several sources each source's value is emitted, the current one
+    /// last, and meta follows in a final update, so applying the deltas to
+    /// an empty store reproduces the tree. The self vessel's context is
+    /// `vessels.self`. `context_filter` selects contexts by their full
+    /// `vessels.<id>` context; self also matches as `vessels.self`.
+    pub fn full_model_as_deltas(&self, context_filter: Option<&PathPattern>) -> Vec<Delta> {
+        let mut deltas = Vec::new();
+        for group in CONTEXT_GROUPS {
+            let Some(Value::Object(contexts)) = self.data.get(*group) else {
+                continue;
+            };
+            for (id, node) in contexts {
+                let context = format!("{group}.{id}");
+                let is_self = context == self.self_urn;
+                let selected = context_filter.map_or(true, |filter| {
+                    filter.matches(&context) || (is_self && filter.matches("vessels.self"))
+                });
+                if !selected {
+                    continue;
+                }
 
-    fn get_self_path(&self, path: &str) -> Option<Value> {
-        // self_urn is already "vessels.urn:...", so just append the path
-        let full_path = format!("{}.{}", self.self_urn, path);
-        self.get_path_value(&full_path)
-    }
+                let mut collected = ModelValues::default();
+                Self::collect_model_values(node, "", &mut collected);
+                let mut updates = Update::group_by_source(collected.other_sources);
+                updates.extend(Update::group_by_source(collected.current));
+                if !collected.meta.is_empty() {
+                    updates.push(Update {
+                        source_ref: None,
+                        source: None,
+                        timestamp: None,
+                        values: Vec::new(),
+                        meta: Some(collected.meta),
+                        server_timestamp: None,
+                    });
+                }
+                if updates.is_empty() {
+                    continue;
+                }
 
-    fn get_context(&self, context: &str) -> Option<Value> {
-        let resolved = self.resolve_context(context);
-        self.get_path_value(&resolved)
+                deltas.push(Delta {
+                    context: Some(if is_self {
+                        "vessels.self".to_string()
+                    } else {
+                        context
+                    }),
+                    updates,
+                });
+            }
+        }
+        deltas
     }
 
-    fn self_urn(&self) -> &str {
-        &self.self_urn
+    /// The full model with only the paths `keep(context, path)` accepts.
+    ///
+    /// The tree has the full model's shape, with whole path nodes (value,
+    /// per-source values, meta) kept as stored; `sources` is copied as-is.
+    /// As for [`full_model_as_deltas`](Self::full_model_as_deltas), the self
+    /// vessel's context is passed as `vessels.self`.
+    pub fn full_model_filtered<F>(&self, keep: F) -> Value
+    where
+        F: Fn(&str, &str) -> bool,
+    {
+        let mut model = serde_json::json!({
+            "version": self.data["version"],
+            "self": self.data["self"],
+            "vessels": {},
+            "sources": self.data.get("sources").cloned().unwrap_or_else(|| serde_json::json!({})),
+        });
+        for group in CONTEXT_GROUPS {
+            let Some(Value::Object(contexts)) = self.data.get(*group) else {
+                continue;
+            };
+            for (id, node) in contexts {
+                let mut context = format!("{group}.{id}");
+                if context == self.self_urn {
+                    context = "vessels.self".to_string();
+                }
+                let keep_path = |path: &str| keep(&context, path);
+                if let Some(kept) = Self::filter_model_node(node, "", &keep_path) {
+                    if !model[*group].is_object() {
+                        model[*group] = serde_json::json!({});
+                    }
+                    model[*group][id] = kept;
+                }
+            }
+        }
+        model
     }
 
-    fn full_model(&self) -> &Value {
+    /// Copy of `node` with only the path nodes `keep` accepts, `None` if
+    /// none are left.
+    fn filter_model_node(node: &Value, path: &str, keep: &dyn Fn(&str) -> bool) -> Option<Value> {
+        let Value::Object(map) = node else {
+            return None;
+        };
+        if map.contains_key("value") {
+            return keep(path).then(|| node.clone());
+        }
+
+        let mut kept = serde_json::Map::new();
+        for (key, child) in map {
+            if key == "meta" {
+                continue;
+            }
+            let child_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{path}.{key}")
+            };
+            if let Some(child) = Self::filter_model_node(child, &child_path, keep) {
+                kept.insert(key.clone(), child);
+            }
+        }
+        (!kept.is_empty()).then_some(Value::Object(kept))
+    }
+
+    /// Collect the values, per-source values and meta below `node`.
+    fn collect_model_values(node: &Value, path: &str, collected: &mut ModelValues) {
+        let Value::Object(map) = node else {
+            return;
+        };
+
+        if !path.is_empty() {
+            if let Some(meta) = map
+                .get("meta")
+                .and_then(|meta| serde_json::from_value::<Meta>(meta.clone()).ok())
+            {
+                collected.meta.push(PathMeta {
+                    path: path.to_string(),
+                    value: meta,
+                });
+            }
+        }
+
+        if let Some(value) = map.get("value") {
+            let source_ref = map.get("$source").and_then(Value::as_str);
+            if let Some(Value::Object(by_source)) = map.get("values") {
+                for (source, entry) in by_source {
+                    if Some(source.as_str()) == source_ref {
+                        continue;
+                    }
+                    collected.other_sources.push((
+                        Some(source.clone()),
+                        entry
+                            .get("timestamp")
+                            .and_then(Value::as_str)
+                            .map(String::from),
+                        PathValue {
+                            path: path.to_string(),
+                            value: entry.get("value").cloned().unwrap_or(Value::Null),
+                        },
+                    ));
+                }
+            }
+            collected.current.push((
+                source_ref.map(String::from),
+                map.get("timestamp")
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                PathValue {
+                    path: path.to_string(),
+                    value: value.clone(),
+                },
+            ));
+            return;
+        }
+
+        for (key, child) in map {
+            if key == "meta" {
+                continue;
+            }
+            let child_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{path}.{key}")
+            };
+            Self::collect_model_values(child, &child_path, collected);
+        }
+    }
+
+    /// Get the values under a context whose timestamp is newer than `since`.
+    ///
+    /// Lets polling clients fetch only what changed since their last request.
+    /// Values are grouped into updates by `$source` and timestamp; values
+    /// without a parseable timestamp are never reported. The delta keeps
+    /// `context` as given (e.g. `vessels.self`).
+    pub fn changed_since(&self, context: &str, since: DateTime<Utc>) -> Delta {
+        let mut changed = Vec::new();
+        if let Some(node) = self.path_ref(&self.resolve_context(context)) {
+            Self::collect_changed(node, "", since, &mut changed);
+        }
+
+        Delta {
+            context: Some(context.to_string()),
+            updates: Update::group_by_source(changed),
+        }
+    }
+
+    /// Collect leaf values below `node` with a timestamp newer than `since`,
+    /// as `(source_ref, timestamp, value)`.
+    fn collect_changed(
+        node: &Value,
+        path: &str,
+        since: DateTime<Utc>,
+        changed: &mut Vec<(Option<String>, Option<String>, PathValue)>,
+    ) {
+        let Value::Object(map) = node else {
+            return;
+        };
+
+        if let Some(value) = map.get("value") {
+            let Some(timestamp) = map.get("timestamp").and_then(Value::as_str) else {
+                return;
+            };
+            let newer = DateTime::parse_from_rfc3339(timestamp)
+                .map(|ts| ts > since)
+                .unwrap_or(false);
+            if newer {
+                let source_ref = map.get("$source").and_then(Value::as_str).map(String::from);
+                changed.push((
+                    source_ref,
+                    Some(timestamp.to_string()),
+                    PathValue {
+                        path: path.to_string(),
+                        value: value.clone(),
+                    },
+                ));
+            }
+            return;
+        }
+
+        for (key, child) in map {
+            if key == "meta" {
+                continue;
+            }
+            let child_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{path}.{key}")
+            };
+            Self::collect_changed(child, &child_path, since, changed);
+        }
+    }
+
+    /// Remove the node at `segments` below `node`, then the objects left
+    /// empty on the way down. Returns whether the node existed.
+    fn remove_node(node: &mut Value, segments: &[&str]) -> bool {
+        let Value::Object(map) = node else {
+            return false;
+        };
+        match segments {
+            [] => false,
+            [last] => map.remove(*last).is_some(),
+            [first, rest @ ..] => {
+                let Some(child) = map.get_mut(*first) else {
+                    return false;
+                };
+                let removed = Self::remove_node(child, rest);
+                if removed && child.as_object().is_some_and(|m| m.is_empty()) {
+                    map.remove(*first);
+                }
+                removed
+            }
+        }
+    }
+
+    /// Remove leaf values below `node` last updated before `cutoff`, and the
+    /// objects left empty, collecting the absolute paths removed.
+    ///
+    /// Metadata outlives pruning, as it does value updates: a pruned leaf
+    /// with `meta` keeps just that.
+    fn prune_node(node: &mut Value, path: &str, cutoff: DateTime<Utc>, removed: &mut Vec<String>) {
+        let Value::Object(map) = node else {
+            return;
+        };
+        map.retain(|key, child| {
+            let Value::Object(child_map) = child else {
+                return true;
+            };
+            if key == "meta" || child_map.is_empty() {
+                return true;
+            }
+            let child_path = format!("{path}.{key}");
+
+            if child_map.contains_key("value") {
+                let stale = child_map
+                    .get("timestamp")
+                    .and_then(Value::as_str)
+                    .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                    .is_some_and(|ts| ts < cutoff);
+                if !stale {
+                    return true;
+                }
+                removed.push(child_path);
+                child_map.retain(|key, _| key == "meta");
+                return !child_map.is_empty();
+            }
+
+            Self::prune_node(child, &child_path, cutoff, removed);
+            child.as_object().is_some_and(|m| !m.is_empty())
+        });
+    }
+
+    /// Count the number of leaf paths (values) in the store.
+    fn count_paths_recursive(value: &Value) -> usize {
+        match value {
+            Value::Object(map) => {
+                // If this object has a "value" key, it's a leaf node
+                if map.contains_key("value") {
+                    1
+                } else {
+                    map.values().map(Self::count_paths_recursive).sum()
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    /// Get the number of unique paths with values in the store.
+    pub fn path_count(&self) -> usize {
+        if let Some(vessels) = self.data.get("vessels") {
+            Self::count_paths_recursive(vessels)
+        } else {
+            0
+        }
+    }
+}
+
+/// Render a delta as a full-format tree fragment.
+///
+/// The result has the same shape as the full model (`vessels.<urn>.<path>`
+/// nodes with `value`, `$source` and `timestamp`, plus `sources`) but only
+/// contains what the delta carries. `vessels.self` resolves to `self_urn`.
+pub fn full_fragment(delta: &Delta, self_urn: &str) -> Value {
+    let mut fragment = MemoryStore::new(self_urn);
+    fragment.apply_delta(delta);
+
+    // Drop the placeholder self vessel if the delta was for another context
+    let urn_key = self_urn.strip_prefix("vessels.").unwrap_or(self_urn);
+    if let Some(vessels) = fragment.data["vessels"].as_object_mut() {
+        if vessels
+            .get(urn_key)
+            .is_some_and(|v| v.as_object().is_some_and(|o| o.is_empty()))
+        {
+            vessels.remove(urn_key);
+        }
+    }
+    fragment.data
+}
+
+/// Key of the marker object that replaces nodes cut off by [`truncate_depth`].
+pub const TRUNCATED_KEY: &str = "$truncated";
+
+/// Copy `value`, keeping at most `depth` levels of nested objects.
+///
+/// Objects below the limit are replaced by `{"$truncated": <number of keys>}`
+/// so clients can tell a cut-off node from an empty one and fetch it
+/// separately. Scalars and arrays at the last level are kept as-is.
+pub fn truncate_depth(value: &Value, depth: usize) -> Value {
+    match value {
+        Value::Object(map) if depth == 0 => serde_json::json!({ TRUNCATED_KEY: map.len() }),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, child)| (key.clone(), truncate_depth(child, depth - 1)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The value one source reported for a leaf node, as
+/// `{value, $source, timestamp}`.
+///
+/// Looks the source up in the node's `values` map, falling back to the
+/// node's own value when it is the only source and has no map. Returns
+/// `None` when the node has no value from `source_ref`.
+pub fn select_source(node: &Value, source_ref: &str) -> Option<Value> {
+    let (value, timestamp) = match node.get("values").and_then(|v| v.get(source_ref)) {
+        Some(entry) => (entry.get("value")?, entry.get("timestamp")),
+        None if node.get("$source").and_then(Value::as_str) == Some(source_ref) => {
+            (node.get("value")?, node.get("timestamp"))
+        }
+        None => return None,
+    };
+    let mut selected = serde_json::Map::new();
+    selected.insert("value".to_string(), value.clone());
+    selected.insert("$source".to_string(), Value::String(source_ref.to_string()));
+    if let Some(timestamp) = timestamp {
+        selected.insert("timestamp".to_string(), timestamp.clone());
+    }
+    Some(Value::Object(selected))
+}
+
+/// Deep-merge `src` into `dst`; objects are merged key by key and any other
+/// value in `src` replaces the one in `dst`.
+fn merge_json(dst: &mut Value, src: &Value) {
+    match (dst, src) {
+        (Value::Object(dst), Value::Object(src)) => {
+            for (key, value) in src {
+                match dst.get_mut(key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        dst.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (dst, src) => *dst = src.clone(),
+    }
+}
+
+impl SignalKStore for MemoryStore {
+    fn apply_delta(&mut self, delta: &Delta) {
+        // Resolve context - "vessels.self" becomes the actual URN path
+        let context = delta
+            .context
+            .as_ref()
+            .map(|c| self.resolve_context(c))
+            .unwrap_or_else(|| self.self_urn.clone());
+
+        for update in &delta.updates {
+            // Register the source in the /sources hierarchy
+            self.register_source(
+                update.source_ref.as_deref(),
+                update.source.as_ref(),
+                update.timestamp.as_deref(),
+            );
+
+            for pv in &update.values {
+                // Store the value with multi-source support
+                let merge = self.is_merge_path(&pv.path);
+                self.set_signalk_value(
+                    &context,
+                    &pv.path,
+                    &pv.value,
+                    update.source_ref.as_deref(),
+                    update.timestamp.as_deref(),
+                    merge,
+                );
+
+                if self.history_len > 0 {
+                    if let Some(value) = pv.value.as_f64() {
+                        self.record_history(format!("{context}.{}", pv.path), value);
+                    }
+                }
+            }
+
+            for pm in update.meta.iter().flatten() {
+                self.set_meta(&context, &pm.path, &pm.value);
+            }
+        }
+    }
+
+    fn get_path(&self, path: &str) -> Option<Value> {
+        self.get_path_value(path)
+    }
+
+    fn get_self_path(&self, path: &str) -> Option<Value> {
+        // self_urn is already "vessels.urn:...", so just append the path
+        let full_path = format!("{}.{}", self.self_urn, path);
+        self.get_path_value(&full_path)
+    }
+
+    fn get_context(&self, context: &str) -> Option<Value> {
+        let resolved = self.resolve_context(context);
+        self.get_path_value(&resolved)
+    }
+
+    fn self_urn(&self) -> &str {
+        &self.self_urn
+    }
+
+    fn full_model(&self) -> &Value {
         &self.data
     }
 
     fn get_sources(&self) -> Option<Value> {
         self.data.get("sources").cloned()
     }
+
+    fn remove_path(&mut self, context: &str, path: &str) -> bool {
+        let context = self.resolve_context(context);
+        let Some((group, id)) = context.split_once('.') else {
+            return false;
+        };
+        let segments: Vec<&str> = path.split('.').collect();
+        let removed = match self.data.get_mut(group).and_then(|g| g.get_mut(id)) {
+            Some(node) if !path.is_empty() => Self::remove_node(node, &segments),
+            _ => false,
+        };
+
+        if removed {
+            let prefix = format!("{context}.{path}");
+            self.history.retain(|key, _| {
+                key != &prefix
+                    && !key
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            });
+        }
+        removed
+    }
+
+    fn prune_stale(&mut self, older_than: Duration, now: DateTime<Utc>) -> usize {
+        let Ok(older_than) = chrono::Duration::from_std(older_than) else {
+            return 0;
+        };
+        let cutoff = now - older_than;
+
+        let mut removed = Vec::new();
+        for group in CONTEXT_GROUPS {
+            let Some(Value::Object(contexts)) = self.data.get_mut(*group) else {
+                continue;
+            };
+            for (id, context) in contexts.iter_mut() {
+                Self::prune_node(context, &format!("{group}.{id}"), cutoff, &mut removed);
+            }
+        }
+        for path in &removed {
+            self.history.remove(path);
+        }
+        removed.len()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::PathMeta;
 
     #[test]
     fn test_new_store() {
@@ -389,6 +1547,239 @@ mod tests {
         assert!(full["sources"].is_object());
     }
 
+    #[test]
+    fn test_set_self_urn() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:old");
+        let delta = |value: f64| Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: None,
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(value),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+        store.apply_delta(&delta(1.0));
+
+        store.set_self_urn("vessels.urn:mrn:signalk:uuid:new");
+        store.apply_delta(&delta(2.0));
+
+        assert_eq!(store.self_urn(), "vessels.urn:mrn:signalk:uuid:new");
+        assert_eq!(
+            store.full_model()["self"],
+            "vessels.urn:mrn:signalk:uuid:new"
+        );
+        assert_eq!(
+            store.get_self_path("navigation.speedOverGround").unwrap()["value"],
+            2.0
+        );
+        // The previous self is kept as an ordinary vessel
+        assert_eq!(
+            store
+                .get_path("vessels.urn:mrn:signalk:uuid:old.navigation.speedOverGround")
+                .unwrap()["value"],
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_changed_since() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        for (path, timestamp) in [
+            ("navigation.speedOverGround", "2024-01-17T10:00:00.000Z"),
+            (
+                "navigation.courseOverGroundTrue",
+                "2024-01-17T10:05:00.000Z",
+            ),
+        ] {
+            store.apply_delta(&Delta {
+                context: Some("vessels.self".to_string()),
+                updates: vec![Update {
+                    source_ref: Some("nmea0183.GP".to_string()),
+                    source: None,
+                    timestamp: Some(timestamp.to_string()),
+                    values: vec![PathValue {
+                        path: path.to_string(),
+                        value: serde_json::json!(1.0),
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        }
+
+        let since = "2024-01-17T10:02:00Z".parse().unwrap();
+        let delta = store.changed_since("vessels.self", since);
+        assert_eq!(delta.context.as_deref(), Some("vessels.self"));
+        assert_eq!(delta.updates.len(), 1);
+        assert_eq!(
+            delta.updates[0].timestamp.as_deref(),
+            Some("2024-01-17T10:05:00.000Z")
+        );
+        assert_eq!(delta.updates[0].values.len(), 1);
+        assert_eq!(
+            delta.updates[0].values[0].path,
+            "navigation.courseOverGroundTrue"
+        );
+
+        let later = "2024-01-17T10:05:00Z".parse().unwrap();
+        assert!(store
+            .changed_since("vessels.self", later)
+            .updates
+            .is_empty());
+    }
+
+    #[test]
+    fn test_model_slice_by_context() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store.apply_delta(&Delta {
+            context: Some("vessels.urn:mrn:imo:mmsi:230099999".to_string()),
+            updates: vec![Update {
+                source_ref: Some("ais".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(4.2),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+
+        let target = store
+            .model_slice(Some("vessels.urn:mrn:imo:mmsi:230099999"), None)
+            .unwrap();
+        assert_eq!(target["navigation"]["speedOverGround"]["value"], 4.2);
+        assert!(target.get("vessels").is_none());
+
+        assert_eq!(
+            store.model_slice(Some("vessels.self"), None).unwrap(),
+            serde_json::json!({})
+        );
+        assert!(store.model_slice(Some("vessels.unknown"), None).is_none());
+        assert_eq!(&store.model_slice(None, None).unwrap(), store.full_model());
+    }
+
+    #[test]
+    fn test_model_slice_depth_limited() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: None,
+                source: None,
+                timestamp: None,
+                values: vec![
+                    PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(3.5),
+                    },
+                    PathValue {
+                        path: "navigation.courseOverGroundTrue".to_string(),
+                        value: serde_json::json!(1.2),
+                    },
+                ],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+
+        let sliced = store.model_slice(None, Some(2)).unwrap();
+        assert_eq!(sliced["version"], "1.7.0");
+        assert_eq!(
+            sliced["vessels"]["urn:mrn:signalk:uuid:self"],
+            serde_json::json!({ TRUNCATED_KEY: 1 })
+        );
+
+        let sliced = store.model_slice(Some("vessels.self"), Some(1)).unwrap();
+        assert_eq!(
+            sliced["navigation"],
+            serde_json::json!({ TRUNCATED_KEY: 2 })
+        );
+
+        let sliced = store.model_slice(Some("vessels.self"), Some(3)).unwrap();
+        assert_eq!(sliced["navigation"]["speedOverGround"]["value"], 3.5);
+    }
+
+    #[test]
+    fn test_get_value() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:30:00.000Z".to_string()),
+                values: vec![
+                    PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(3.85),
+                    },
+                    PathValue {
+                        path: "navigation.position".to_string(),
+                        value: serde_json::json!({"latitude": 60.1, "longitude": 24.9}),
+                    },
+                ],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+        let base = "vessels.urn:mrn:signalk:uuid:self.navigation";
+
+        assert_eq!(
+            store.get_value(&format!("{base}.speedOverGround")),
+            Some(serde_json::json!(3.85))
+        );
+        assert_eq!(
+            store.get_value(&format!("{base}.position")),
+            Some(serde_json::json!({"latitude": 60.1, "longitude": 24.9}))
+        );
+        // Branch nodes and missing paths have no value
+        assert_eq!(store.get_value(base), None);
+        assert_eq!(store.get_value(&format!("{base}.headingTrue")), None);
+    }
+
+    #[test]
+    fn test_full_fragment() {
+        let delta = Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("nmea0183.GP".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:30:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.85),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+
+        let fragment = full_fragment(&delta, "vessels.urn:mrn:signalk:uuid:self");
+        let node =
+            &fragment["vessels"]["urn:mrn:signalk:uuid:self"]["navigation"]["speedOverGround"];
+        assert_eq!(node["value"], 3.85);
+        assert_eq!(node["$source"], "nmea0183.GP");
+        assert_eq!(node["timestamp"], "2024-01-17T10:30:00.000Z");
+
+        // Other contexts don't drag in an empty self vessel
+        let other = Delta {
+            context: Some("vessels.urn:mrn:imo:mmsi:230099999".to_string()),
+            ..delta
+        };
+        let fragment = full_fragment(&other, "vessels.urn:mrn:signalk:uuid:self");
+        let vessels = fragment["vessels"].as_object().unwrap();
+        assert_eq!(vessels.len(), 1);
+        assert!(vessels.contains_key("urn:mrn:imo:mmsi:230099999"));
+    }
+
     #[test]
     fn test_apply_delta() {
         let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
@@ -404,6 +1795,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -436,6 +1828,7 @@ mod tests {
                     },
                 ],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -461,6 +1854,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -478,6 +1872,7 @@ mod tests {
                     value: serde_json::json!(4.12),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -503,6 +1898,7 @@ mod tests {
                     value: serde_json::json!(85.5),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -530,6 +1926,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -579,6 +1976,7 @@ mod tests {
                     },
                 ],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -613,6 +2011,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -630,6 +2029,7 @@ mod tests {
                     value: serde_json::Value::Null,
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -655,6 +2055,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -672,6 +2073,7 @@ mod tests {
                     value: serde_json::json!(5.2),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -708,6 +2110,7 @@ mod tests {
                     },
                 ],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -741,6 +2144,7 @@ mod tests {
                     value: serde_json::json!(1),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -763,6 +2167,7 @@ mod tests {
                     value: serde_json::json!(2),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -800,6 +2205,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -808,28 +2214,232 @@ mod tests {
             updates: vec![Update {
                 source_ref: Some("gps2".to_string()),
                 source: None,
-                timestamp: Some("2024-01-17T10:00:01.000Z".to_string()),
+                timestamp: Some("2024-01-17T10:00:01.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.90),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+
+        store.apply_delta(&delta1);
+        store.apply_delta(&delta2);
+
+        let value = store.get_self_path("navigation.speedOverGround").unwrap();
+
+        // Check timestamps are preserved per source
+        assert_eq!(
+            value["values"]["gps1"]["timestamp"],
+            "2024-01-17T10:00:00.000Z"
+        );
+        assert_eq!(
+            value["values"]["gps2"]["timestamp"],
+            "2024-01-17T10:00:01.000Z"
+        );
+    }
+
+    #[test]
+    fn test_full_model_as_deltas_round_trip() {
+        let self_urn = "vessels.urn:mrn:signalk:uuid:test-vessel";
+        let other = "vessels.urn:mrn:imo:mmsi:230099999";
+        let mut store = MemoryStore::new(self_urn);
+
+        let update =
+            |source: Option<&str>, timestamp: Option<&str>, path: &str, value: Value| Update {
+                source_ref: source.map(String::from),
+                source: None,
+                timestamp: timestamp.map(String::from),
+                values: vec![PathValue {
+                    path: path.to_string(),
+                    value,
+                }],
+                meta: None,
+                server_timestamp: None,
+            };
+        let mut with_meta = update(
+            Some("gps1"),
+            Some("2024-01-17T10:00:00.000Z"),
+            "navigation.speedOverGround",
+            serde_json::json!(3.85),
+        );
+        with_meta.meta = Some(vec![PathMeta {
+            path: "navigation.speedOverGround".to_string(),
+            value: Meta {
+                units: Some("m/s".to_string()),
+                ..Default::default()
+            },
+        }]);
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![
+                with_meta,
+                update(
+                    Some("gps2"),
+                    Some("2024-01-17T10:00:02.000Z"),
+                    "navigation.speedOverGround",
+                    serde_json::json!(3.9),
+                ),
+                // gps1 is current again, gps2 stays in `values`
+                update(
+                    Some("gps1"),
+                    Some("2024-01-17T10:00:03.000Z"),
+                    "navigation.speedOverGround",
+                    serde_json::json!(3.8),
+                ),
+                update(
+                    Some("gps1"),
+                    Some("2024-01-17T10:00:03.000Z"),
+                    "navigation.position",
+                    serde_json::json!({ "latitude": 52.1, "longitude": 4.9 }),
+                ),
+                update(None, None, "design.draft", serde_json::json!(1.8)),
+            ],
+        });
+        store.apply_delta(&Delta {
+            context: Some(other.to_string()),
+            updates: vec![update(
+                Some("ais.AI"),
+                Some("2024-01-17T10:00:01.000Z"),
+                "navigation.courseOverGroundTrue",
+                serde_json::json!(1.2),
+            )],
+        });
+
+        let deltas = store.full_model_as_deltas(None);
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].context.as_deref(), Some(other));
+        assert_eq!(deltas[1].context.as_deref(), Some("vessels.self"));
+
+        let mut copy = MemoryStore::new(self_urn);
+        for delta in &deltas {
+            copy.apply_delta(delta);
+        }
+        assert_eq!(copy.full_model()["vessels"], store.full_model()["vessels"]);
+        assert_eq!(
+            copy.get_self_path("navigation.speedOverGround").unwrap()["$source"],
+            "gps1"
+        );
+
+        // Filtered by context
+        let self_only = PathPattern::new("vessels.self").unwrap();
+        let deltas = store.full_model_as_deltas(Some(&self_only));
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].context.as_deref(), Some("vessels.self"));
+        let ais = PathPattern::new(other).unwrap();
+        let deltas = store.full_model_as_deltas(Some(&ais));
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].context.as_deref(), Some(other));
+    }
+
+    #[test]
+    fn test_get_path_all_sources() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
+        for (source, timestamp, sog) in [
+            ("gps1", "2024-01-17T10:00:00.000Z", 3.85),
+            ("gps2", "2024-01-17T10:00:01.000Z", 3.90),
+        ] {
+            store.apply_delta(&Delta {
+                context: Some("vessels.self".to_string()),
+                updates: vec![Update {
+                    source_ref: Some(source.to_string()),
+                    source: None,
+                    timestamp: Some(timestamp.to_string()),
+                    values: vec![PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(sog),
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        }
+
+        let sources = store
+            .get_path_all_sources("vessels.self.navigation.speedOverGround")
+            .unwrap();
+        assert_eq!(
+            sources,
+            serde_json::json!({
+                "gps1": { "value": 3.85, "timestamp": "2024-01-17T10:00:00.000Z" },
+                "gps2": { "value": 3.90, "timestamp": "2024-01-17T10:00:01.000Z" },
+            })
+        );
+        assert_eq!(
+            store.get_path_all_sources(
+                "vessels.urn:mrn:signalk:uuid:test-vessel.navigation.speedOverGround"
+            ),
+            Some(sources)
+        );
+
+        assert_eq!(store.get_path_all_sources("vessels.self.navigation"), None);
+        assert_eq!(
+            store.get_path_all_sources("vessels.self.navigation.headingTrue"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_path_stats() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
+        store.set_history_len(4);
+
+        let sog = |value: Value| Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: None,
                 values: vec![PathValue {
                     path: "navigation.speedOverGround".to_string(),
-                    value: serde_json::json!(3.90),
+                    value,
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
-        store.apply_delta(&delta1);
-        store.apply_delta(&delta2);
+        // The first value falls out of the 4-value window; null is not numeric
+        for value in [10.0, 2.0, 4.0, 6.0] {
+            store.apply_delta(&sog(serde_json::json!(value)));
+        }
+        store.apply_delta(&sog(Value::Null));
+        store.apply_delta(&sog(serde_json::json!(8.0)));
 
-        let value = store.get_self_path("navigation.speedOverGround").unwrap();
+        let stats = store
+            .path_stats("vessels.self.navigation.speedOverGround")
+            .unwrap();
+        assert_eq!(
+            stats,
+            PathNumericStats {
+                min: 2.0,
+                max: 8.0,
+                mean: 5.0,
+                count: 4
+            }
+        );
+        assert_eq!(
+            store.path_stats("vessels.urn:mrn:signalk:uuid:test-vessel.navigation.speedOverGround"),
+            Some(stats)
+        );
+        assert_eq!(
+            store.path_stats("vessels.self.navigation.headingTrue"),
+            None
+        );
 
-        // Check timestamps are preserved per source
+        store.set_history_len(2);
         assert_eq!(
-            value["values"]["gps1"]["timestamp"],
-            "2024-01-17T10:00:00.000Z"
+            store
+                .path_stats("vessels.self.navigation.speedOverGround")
+                .map(|stats| stats.mean),
+            Some(7.0)
         );
+
+        store.set_history_len(0);
         assert_eq!(
-            value["values"]["gps2"]["timestamp"],
-            "2024-01-17T10:00:01.000Z"
+            store.path_stats("vessels.self.navigation.speedOverGround"),
+            None
         );
     }
 
@@ -849,6 +2459,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -863,6 +2474,7 @@ mod tests {
                     value: serde_json::json!(4.00),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -899,6 +2511,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -926,6 +2539,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -941,6 +2555,7 @@ mod tests {
                     value: serde_json::json!(1.52),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -984,6 +2599,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -997,6 +2613,84 @@ mod tests {
         assert_eq!(sources["actisense"]["type"], "NMEA2000");
     }
 
+    #[test]
+    fn test_sources_record_n2k_pgns() {
+        use crate::model::Source;
+
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
+        let n2k = |pgn: u32, timestamp: &str| Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("actisense.115".to_string()),
+                source: Some(Source {
+                    label: "actisense".to_string(),
+                    source_type: Some("NMEA2000".to_string()),
+                    src: Some("115".to_string()),
+                    can_name: Some("c0788c00e7e04312".to_string()),
+                    pgn: Some(pgn),
+                    sentence: None,
+                    talker: None,
+                    ais_type: None,
+                }),
+                timestamp: Some(timestamp.to_string()),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.85),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+
+        store.apply_delta(&n2k(128259, "2024-01-17T10:00:00.000Z"));
+        store.apply_delta(&n2k(129026, "2024-01-17T10:00:01.000Z"));
+        store.apply_delta(&n2k(128259, "2024-01-17T10:00:02.000Z"));
+
+        let device = &store.get_sources().unwrap()["actisense"]["115"];
+        assert_eq!(device["n2k"]["src"], "115");
+        assert_eq!(device["n2k"]["canName"], "c0788c00e7e04312");
+        assert_eq!(
+            device["pgns"],
+            serde_json::json!({
+                "128259": "2024-01-17T10:00:02.000Z",
+                "129026": "2024-01-17T10:00:01.000Z",
+            })
+        );
+    }
+
+    #[test]
+    fn test_sources_src_named_like_label_field() {
+        use crate::model::Source;
+
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: None,
+                source: Some(Source {
+                    label: "can0".to_string(),
+                    source_type: Some("NMEA2000".to_string()),
+                    src: Some("type".to_string()),
+                    can_name: None,
+                    pgn: Some(129026),
+                    sentence: None,
+                    talker: None,
+                    ais_type: None,
+                }),
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.85),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+
+        assert_eq!(store.get_sources().unwrap()["can0"]["type"], "NMEA2000");
+        assert!(store.get_self_path("navigation.speedOverGround").is_some());
+    }
+
     #[test]
     fn test_path_count_with_multi_source() {
         let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
@@ -1013,6 +2707,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -1027,6 +2722,7 @@ mod tests {
                     value: serde_json::json!(3.90),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -1053,6 +2749,7 @@ mod tests {
                     value: serde_json::json!(3.85),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -1063,4 +2760,535 @@ mod tests {
         // $source should not be present when no source provided
         assert!(value.get("$source").is_none() || value["$source"].is_null());
     }
+
+    fn meta_delta(path: &str, units: &str) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: None,
+                source: None,
+                timestamp: None,
+                values: vec![],
+                meta: Some(vec![PathMeta {
+                    path: path.to_string(),
+                    value: Meta {
+                        units: Some(units.to_string()),
+                        ..Default::default()
+                    },
+                }]),
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_meta_tree() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
+
+        store.apply_delta(&meta_delta("navigation.speedOverGround", "m/s"));
+        store.apply_delta(&meta_delta("environment.depth.belowTransducer", "m"));
+
+        // A later value update must not drop the stored meta
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.85),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+
+        let tree = store.meta_tree("vessels.self");
+        assert_eq!(tree["navigation"]["speedOverGround"]["units"], "m/s");
+        assert_eq!(
+            tree["environment"]["depth"]["belowTransducer"]["units"],
+            "m"
+        );
+
+        let sog = store.get_self_path("navigation.speedOverGround").unwrap();
+        assert_eq!(sog["value"], serde_json::json!(3.85));
+        assert_eq!(sog["meta"]["units"], "m/s");
+    }
+
+    #[test]
+    fn test_meta_with_value() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
+
+        // Value and meta in the same update
+        let mut delta = meta_delta("navigation.speedOverGround", "m/s");
+        delta.updates[0].values.push(PathValue {
+            path: "navigation.speedOverGround".to_string(),
+            value: serde_json::json!(3.85),
+        });
+        store.apply_delta(&delta);
+
+        let sog = store.get_self_path("navigation.speedOverGround").unwrap();
+        assert_eq!(sog["value"], serde_json::json!(3.85));
+        assert_eq!(sog["meta"]["units"], "m/s");
+
+        let meta = store
+            .get_meta("vessels.self.navigation.speedOverGround")
+            .unwrap();
+        assert_eq!(meta["units"], "m/s");
+        assert!(store
+            .get_meta("vessels.self.navigation.courseOverGroundTrue")
+            .is_none());
+    }
+
+    #[test]
+    fn test_meta_tree_empty() {
+        let store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test-vessel");
+        assert_eq!(store.meta_tree("vessels.self"), serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_remove_path() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store.set_history_len(4);
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![
+                    PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(3.85),
+                    },
+                    PathValue {
+                        path: "environment.wind.speedApparent".to_string(),
+                        value: serde_json::json!(7.2),
+                    },
+                ],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+
+        assert!(store.remove_path("vessels.self", "environment.wind.speedApparent"));
+        assert!(!store.remove_path("vessels.self", "environment.wind.speedApparent"));
+        assert!(!store.remove_path("vessels.urn:mrn:imo:mmsi:230000001", "navigation"));
+
+        // The emptied parents go too
+        let context = store.get_context("vessels.self").unwrap();
+        assert!(context.get("environment").is_none());
+        assert!(store.get_self_path("navigation.speedOverGround").is_some());
+        assert!(store
+            .path_stats("vessels.self.environment.wind.speedApparent")
+            .is_none());
+    }
+
+    #[test]
+    fn test_prune_stale() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        let apply =
+            |store: &mut MemoryStore, context: &str, path: &str, timestamp: Option<&str>| {
+                store.apply_delta(&Delta {
+                    context: Some(context.to_string()),
+                    updates: vec![Update {
+                        source_ref: Some("test".to_string()),
+                        source: None,
+                        timestamp: timestamp.map(String::from),
+                        values: vec![PathValue {
+                            path: path.to_string(),
+                            value: serde_json::json!(1.0),
+                        }],
+                        meta: None,
+                        server_timestamp: None,
+                    }],
+                });
+            };
+        const AIS: &str = "vessels.urn:mrn:imo:mmsi:230000001";
+        apply(
+            &mut store,
+            "vessels.self",
+            "navigation.speedOverGround",
+            Some("2024-01-17T10:29:00.000Z"),
+        );
+        apply(
+            &mut store,
+            "vessels.self",
+            "environment.depth.belowTransducer",
+            Some("2024-01-17T10:00:00.000Z"),
+        );
+        apply(&mut store, "vessels.self", "design.draft", None);
+        apply(
+            &mut store,
+            AIS,
+            "navigation.courseOverGroundTrue",
+            Some("2024-01-17T09:00:00.000Z"),
+        );
+        store.set_meta(
+            "vessels.urn:mrn:signalk:uuid:self",
+            "environment.depth.belowTransducer",
+            &Meta {
+                units: Some("m".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let now = DateTime::parse_from_rfc3339("2024-01-17T10:30:00.000Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(store.prune_stale(Duration::from_secs(600), now), 2);
+
+        assert!(store.get_self_path("navigation.speedOverGround").is_some());
+        assert!(store.get_self_path("design.draft").is_some());
+        let depth = store
+            .get_self_path("environment.depth.belowTransducer")
+            .unwrap();
+        assert!(depth.get("value").is_none());
+        assert_eq!(depth["meta"]["units"], "m");
+        // The AIS context is left without data
+        assert_eq!(store.get_context(AIS), Some(serde_json::json!({})));
+
+        assert_eq!(store.prune_stale(Duration::from_secs(600), now), 0);
+    }
+
+    #[test]
+    fn test_deep_merge_strategy() {
+        let mut store = MemoryStore::new_with_strategy(
+            "vessels.urn:mrn:signalk:uuid:self",
+            MergeStrategy::DeepMergeObjects,
+        );
+        let apply = |store: &mut MemoryStore, source: &str, value: Value| {
+            store.apply_delta(&Delta {
+                context: Some("vessels.self".to_string()),
+                updates: vec![Update {
+                    source_ref: Some(source.to_string()),
+                    source: None,
+                    timestamp: None,
+                    values: vec![PathValue {
+                        path: "navigation.position".to_string(),
+                        value,
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        };
+
+        apply(
+            &mut store,
+            "gps",
+            serde_json::json!({"latitude": 60.1, "longitude": 24.9}),
+        );
+        apply(&mut store, "gps", serde_json::json!({"latitude": 60.2}));
+        apply(&mut store, "baro", serde_json::json!({"altitude": 3.0}));
+
+        let position = store.get_self_path("navigation.position").unwrap();
+        assert_eq!(
+            position["value"],
+            serde_json::json!({"latitude": 60.2, "longitude": 24.9, "altitude": 3.0})
+        );
+        // Each source keeps its own full object
+        assert_eq!(
+            position["values"]["gps"]["value"],
+            serde_json::json!({"latitude": 60.2, "longitude": 24.9})
+        );
+        assert_eq!(
+            position["values"]["baro"]["value"],
+            serde_json::json!({"altitude": 3.0})
+        );
+
+        // Null still clears
+        apply(&mut store, "gps", Value::Null);
+        let position = store.get_self_path("navigation.position").unwrap();
+        assert_eq!(position["value"], Value::Null);
+        assert_eq!(position["values"]["gps"]["value"], Value::Null);
+    }
+
+    #[test]
+    fn test_merge_paths() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        let apply = |store: &mut MemoryStore, path: &str, value: Value| {
+            store.apply_delta(&Delta {
+                context: Some("vessels.self".to_string()),
+                updates: vec![Update {
+                    source_ref: Some("defaults".to_string()),
+                    source: None,
+                    timestamp: None,
+                    values: vec![PathValue {
+                        path: path.to_string(),
+                        value,
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        };
+
+        apply(
+            &mut store,
+            "design.length",
+            serde_json::json!({"overall": 12.5}),
+        );
+        apply(
+            &mut store,
+            "design.length",
+            serde_json::json!({"hull": 11.8}),
+        );
+        let length = store.get_self_path("design.length").unwrap();
+        assert_eq!(
+            length["value"],
+            serde_json::json!({"overall": 12.5, "hull": 11.8})
+        );
+        assert_eq!(
+            length["values"]["defaults"]["value"],
+            serde_json::json!({"overall": 12.5, "hull": 11.8})
+        );
+
+        // Other paths are still replaced
+        apply(
+            &mut store,
+            "navigation.position",
+            serde_json::json!({"latitude": 60.1}),
+        );
+        apply(
+            &mut store,
+            "navigation.position",
+            serde_json::json!({"longitude": 24.9}),
+        );
+        assert_eq!(
+            store.get_self_path("navigation.position").unwrap()["value"],
+            serde_json::json!({"longitude": 24.9})
+        );
+
+        store.set_merge_paths(&["navigation.position"]).unwrap();
+        apply(
+            &mut store,
+            "navigation.position",
+            serde_json::json!({"latitude": 60.1}),
+        );
+        assert_eq!(
+            store.get_self_path("navigation.position").unwrap()["value"],
+            serde_json::json!({"latitude": 60.1, "longitude": 24.9})
+        );
+        assert!(store.set_merge_paths(&[""]).is_err());
+    }
+
+    #[test]
+    fn test_source_priorities() {
+        use crate::priorities::SourcePriority;
+
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store
+            .set_source_priorities(&SourcePriorities::default().with(
+                "navigation.speedOverGround",
+                vec![SourcePriority::new("n2k.gps", 5_000)],
+            ))
+            .unwrap();
+        let apply = |store: &mut MemoryStore, source: &str, secs: u32, sog: f64| {
+            store.apply_delta(&Delta {
+                context: Some("vessels.self".to_string()),
+                updates: vec![Update {
+                    source_ref: Some(source.to_string()),
+                    source: None,
+                    timestamp: Some(format!("2024-01-17T10:00:{secs:02}.000Z")),
+                    values: vec![PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(sog),
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        };
+        let primary = |store: &MemoryStore| {
+            let sog = store.get_self_path("navigation.speedOverGround").unwrap();
+            (sog["$source"].clone(), sog["value"].clone())
+        };
+
+        apply(&mut store, "n2k.gps", 0, 3.9);
+        // A chattier, less preferred source is kept alongside
+        for secs in 1..=5 {
+            apply(&mut store, "nmea0183.GP", secs, 4.0);
+        }
+        assert_eq!(primary(&store), ("n2k.gps".into(), 3.9.into()));
+        let sog = store.get_self_path("navigation.speedOverGround").unwrap();
+        assert_eq!(sog["timestamp"], "2024-01-17T10:00:00.000Z");
+        assert_eq!(sog["values"]["nmea0183.GP"]["value"], 4.0);
+
+        // Silent for longer than its timeout, the preferred source gives way
+        apply(&mut store, "nmea0183.GP", 6, 4.1);
+        assert_eq!(primary(&store), ("nmea0183.GP".into(), 4.1.into()));
+
+        // And takes over again as soon as it reports
+        apply(&mut store, "n2k.gps", 7, 4.2);
+        assert_eq!(primary(&store), ("n2k.gps".into(), 4.2.into()));
+
+        // Without priorities, the most recent value wins
+        store
+            .set_source_priorities(&SourcePriorities::default())
+            .unwrap();
+        apply(&mut store, "nmea0183.GP", 8, 4.3);
+        assert_eq!(primary(&store), ("nmea0183.GP".into(), 4.3.into()));
+    }
+
+    #[test]
+    fn test_apply_put() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store
+            .register_put_handler(
+                "steering.autopilot.*",
+                Box::new(|value| match value.as_str() {
+                    Some("auto" | "standby") => Ok(()),
+                    _ => Err("Unknown autopilot state".to_string()),
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.apply_put("steering.autopilot.state", &serde_json::json!("off")),
+            PutResult::Failed("Unknown autopilot state".to_string())
+        );
+        assert!(store.get_self_path("steering.autopilot.state").is_none());
+
+        assert!(matches!(
+            store.apply_put("steering.autopilot.state", &serde_json::json!("auto")),
+            PutResult::Completed(_)
+        ));
+        assert_eq!(
+            store.get_self_path("steering.autopilot.state").unwrap()["value"],
+            "auto"
+        );
+
+        assert_eq!(
+            store.apply_put("navigation.anchor.maxRadius", &serde_json::json!(50)),
+            PutResult::NotSupported
+        );
+    }
+
+    #[test]
+    fn test_resolve_put() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store
+            .register_async_put_handler("navigation.anchor.rodeLength", Box::new(|_| Ok(())))
+            .unwrap();
+        let writable = WritablePaths::new(&["electrical.switches.*"]).unwrap();
+
+        // Started, but stored only once the windlass reports it
+        let result = store.resolve_put(
+            "navigation.anchor.rodeLength",
+            &serde_json::json!(30),
+            None,
+            &writable,
+        );
+        assert_eq!(
+            (result.clone(), result.status_code()),
+            (PutResult::Pending, 202)
+        );
+        assert!(store
+            .get_self_path("navigation.anchor.rodeLength")
+            .is_none());
+
+        let result = store.resolve_put(
+            "electrical.switches.deck.state",
+            &serde_json::json!(1),
+            Some("app"),
+            &writable,
+        );
+        assert_eq!(result.status_code(), 200);
+        let PutResult::Writable(delta) = result else {
+            panic!("expected a writable path, got {result:?}");
+        };
+        assert_eq!(delta.updates[0].source_ref.as_deref(), Some("app"));
+        assert!(store
+            .get_self_path("electrical.switches.deck.state")
+            .is_none());
+
+        let result = store.resolve_put(
+            "navigation.anchor.maxRadius",
+            &serde_json::json!(50),
+            None,
+            &writable,
+        );
+        assert_eq!(
+            (result.clone(), result.status_code()),
+            (PutResult::NotSupported, 405)
+        );
+    }
+
+    #[test]
+    fn test_move_context() {
+        const WRONG: &str = "vessels.urn:mrn:imo:mmsi:230000001";
+        const RIGHT: &str = "vessels.urn:mrn:imo:mmsi:230000010";
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store.set_history_len(4);
+        for context in [WRONG, "vessels.self"] {
+            store.apply_delta(&Delta {
+                context: Some(context.to_string()),
+                updates: vec![Update {
+                    source_ref: Some("ais".to_string()),
+                    source: None,
+                    timestamp: None,
+                    values: vec![PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(6.1),
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        }
+
+        store.move_context(WRONG, RIGHT).unwrap();
+        assert!(store.get_context(WRONG).is_none());
+        assert_eq!(
+            store
+                .get_path(&format!("{RIGHT}.navigation.speedOverGround"))
+                .unwrap()["value"],
+            serde_json::json!(6.1)
+        );
+        assert!(store
+            .path_stats(&format!("{WRONG}.navigation.speedOverGround"))
+            .is_none());
+        assert_eq!(
+            store
+                .path_stats(&format!("{RIGHT}.navigation.speedOverGround"))
+                .unwrap()
+                .count,
+            1
+        );
+
+        assert_eq!(
+            store.move_context(WRONG, "vessels.urn:mrn:imo:mmsi:230000011"),
+            Err(StoreError::ContextNotFound(WRONG.to_string()))
+        );
+        assert_eq!(
+            store.move_context(RIGHT, "vessels.urn:mrn:signalk:uuid:self"),
+            Err(StoreError::SelfContext)
+        );
+        assert_eq!(
+            store.move_context("vessels.self", WRONG),
+            Err(StoreError::SelfContext)
+        );
+        assert_eq!(
+            store.move_context(RIGHT, "vessels.self"),
+            Err(StoreError::SelfContext)
+        );
+        assert!(store.get_context(RIGHT).is_some());
+        assert!(store.data["vessels"].get("self").is_none());
+        assert!(matches!(
+            store.move_context("navigation", RIGHT),
+            Err(StoreError::InvalidContext(_))
+        ));
+        store.set_self_urn(WRONG);
+        assert_eq!(
+            store.move_context(RIGHT, WRONG),
+            Err(StoreError::SelfContext)
+        );
+        store.set_self_urn("vessels.urn:mrn:signalk:uuid:self");
+        assert_eq!(
+            store.move_context(RIGHT, WRONG),
+            Err(StoreError::ContextExists(WRONG.to_string()))
+        );
+    }
 }
@@ -0,0 +1,236 @@
+//! Unit conversion between SI and display units.
+//!
+//! Signal K stores every value in SI units (`m/s`, `K`, `rad`, `Pa`) and
+//! names the unit in the path's `meta.units`. Clients mostly show values in
+//! other units, so [`CONVERSIONS`] lists the common display units for each
+//! SI unit and [`convert`] goes either way between two of them. For REST
+//! responses, [`to_display_units`] rewrites a model subtree into the
+//! default display unit of each path.
+//!
+//! Units that aren't in the table pass through unchanged.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A linear conversion from an SI unit: `display = si * factor + offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    /// SI unit, as in `meta.units`.
+    pub si: &'static str,
+    /// Display unit.
+    pub display: &'static str,
+    factor: f64,
+    offset: f64,
+}
+
+impl Conversion {
+    const fn new(si: &'static str, display: &'static str, factor: f64, offset: f64) -> Self {
+        Self {
+            si,
+            display,
+            factor,
+            offset,
+        }
+    }
+
+    /// `value` in SI units, converted to the display unit.
+    pub fn to_display(&self, value: f64) -> f64 {
+        value * self.factor + self.offset
+    }
+
+    /// `value` in the display unit, converted to SI units.
+    pub fn to_si(&self, value: f64) -> f64 {
+        (value - self.offset) / self.factor
+    }
+}
+
+/// Known display units. The first entry for an SI unit is its default.
+pub const CONVERSIONS: &[Conversion] = &[
+    Conversion::new("m/s", "kn", 3600.0 / 1852.0, 0.0),
+    Conversion::new("m/s", "km/h", 3.6, 0.0),
+    Conversion::new("K", "°C", 1.0, -273.15),
+    Conversion::new("K", "°F", 1.8, -459.67),
+    Conversion::new("rad", "deg", 180.0 / std::f64::consts::PI, 0.0),
+    Conversion::new("Pa", "hPa", 0.01, 0.0),
+    Conversion::new("Pa", "bar", 1e-5, 0.0),
+];
+
+/// Units REST values are returned in (`?units=`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// As stored.
+    #[default]
+    Si,
+    /// Each path's default display unit.
+    Display,
+}
+
+/// The default display conversion for `si`, if there is one.
+pub fn display_conversion(si: &str) -> Option<&'static Conversion> {
+    CONVERSIONS.iter().find(|conversion| conversion.si == si)
+}
+
+/// Convert `value` from unit `from` to unit `to`, each either an SI unit or
+/// one of its display units. `None` if the table doesn't connect them.
+pub fn convert(value: f64, from: &str, to: &str) -> Option<f64> {
+    if from == to {
+        return Some(value);
+    }
+    let si = match CONVERSIONS.iter().find(|c| c.display == from) {
+        Some(conversion) => (conversion.to_si(value), conversion.si),
+        None => (value, from),
+    };
+    if si.1 == to {
+        return Some(si.0);
+    }
+    CONVERSIONS
+        .iter()
+        .find(|c| c.si == si.1 && c.display == to)
+        .map(|conversion| conversion.to_display(si.0))
+}
+
+/// Rewrite every leaf under `node` whose `meta.units` has a display unit:
+/// its `value`, the values in its multi-source `values` map, and the bounds
+/// of its zones and display scale are converted, and `meta.units` becomes
+/// the display unit. Other nodes are left alone.
+pub fn to_display_units(node: &mut Value) {
+    let Some(object) = node.as_object_mut() else {
+        return;
+    };
+    let conversion = object
+        .get("meta")
+        .and_then(|meta| meta.get("units"))
+        .and_then(Value::as_str)
+        .and_then(display_conversion);
+    if let Some(conversion) = conversion {
+        convert_leaf(object, conversion);
+        return;
+    }
+    for (key, child) in object.iter_mut() {
+        if key != "meta" {
+            to_display_units(child);
+        }
+    }
+}
+
+fn convert_leaf(leaf: &mut serde_json::Map<String, Value>, conversion: &Conversion) {
+    convert_number(leaf.get_mut("value"), conversion);
+    if let Some(values) = leaf.get_mut("values").and_then(Value::as_object_mut) {
+        for entry in values.values_mut() {
+            convert_number(entry.get_mut("value"), conversion);
+        }
+    }
+    if let Some(meta) = leaf.get_mut("meta").and_then(Value::as_object_mut) {
+        if let Some(zones) = meta.get_mut("zones").and_then(Value::as_array_mut) {
+            for zone in zones {
+                convert_number(zone.get_mut("lower"), conversion);
+                convert_number(zone.get_mut("upper"), conversion);
+            }
+        }
+        if let Some(scale) = meta.get_mut("displayScale") {
+            convert_number(scale.get_mut("lower"), conversion);
+            convert_number(scale.get_mut("upper"), conversion);
+        }
+        meta.insert("units".to_string(), Value::from(conversion.display));
+    }
+}
+
+fn convert_number(value: Option<&mut Value>, conversion: &Conversion) {
+    if let Some(value) = value {
+        if let Some(converted) = value
+            .as_f64()
+            .and_then(|si| serde_json::Number::from_f64(conversion.to_display(si)))
+        {
+            *value = Value::Number(converted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("conversion");
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_conversions_both_ways() {
+        assert_close(convert(1852.0 / 3600.0, "m/s", "kn"), 1.0);
+        assert_close(convert(10.0, "kn", "m/s"), 10.0 * 1852.0 / 3600.0);
+        assert_close(convert(293.15, "K", "°C"), 20.0);
+        assert_close(convert(212.0, "°F", "K"), 373.15);
+        assert_close(convert(100.0, "°C", "°F"), 212.0);
+        assert_close(convert(std::f64::consts::PI, "rad", "deg"), 180.0);
+        assert_close(convert(101_325.0, "Pa", "hPa"), 1013.25);
+        assert_close(convert(2.5, "bar", "hPa"), 2500.0);
+        assert_close(convert(3.0, "V", "V"), 3.0);
+
+        assert_eq!(convert(3.0, "V", "mV"), None);
+        assert_eq!(convert(3.0, "m/s", "°C"), None);
+    }
+
+    #[test]
+    fn test_tree_to_display_units() {
+        let mut tree = json!({
+            "navigation": {
+                "speedOverGround": {
+                    "value": 5.0,
+                    "$source": "gps1",
+                    "values": {
+                        "gps1": { "value": 5.0 },
+                        "gps2": { "value": 6.0 },
+                    },
+                    "meta": { "units": "m/s", "displayScale": { "lower": 0.0, "upper": 10.0 } },
+                },
+                "position": {
+                    "value": { "latitude": 52.0, "longitude": 4.0 },
+                },
+            },
+            "propulsion": {
+                "main": {
+                    "temperature": {
+                        "value": 353.15,
+                        "meta": {
+                            "units": "K",
+                            "zones": [{ "lower": 373.15, "state": "alarm" }],
+                        },
+                    },
+                },
+            },
+            "electrical": {
+                "batteries": { "house": { "voltage": { "value": 12.6, "meta": { "units": "V" } } } },
+            },
+        });
+        let unchanged = tree.clone();
+        to_display_units(&mut tree);
+
+        let sog = &tree["navigation"]["speedOverGround"];
+        assert_close(sog["value"].as_f64(), 5.0 * 3600.0 / 1852.0);
+        assert_close(
+            sog["values"]["gps2"]["value"].as_f64(),
+            6.0 * 3600.0 / 1852.0,
+        );
+        assert_close(
+            sog["meta"]["displayScale"]["upper"].as_f64(),
+            10.0 * 3600.0 / 1852.0,
+        );
+        assert_eq!(sog["meta"]["units"], "kn");
+        assert_eq!(sog["$source"], "gps1");
+
+        let temperature = &tree["propulsion"]["main"]["temperature"];
+        assert_close(temperature["value"].as_f64(), 80.0);
+        assert_close(temperature["meta"]["zones"][0]["lower"].as_f64(), 100.0);
+        assert_eq!(temperature["meta"]["units"], "°C");
+
+        // No numeric value, or no known display unit
+        assert_eq!(
+            tree["navigation"]["position"],
+            unchanged["navigation"]["position"]
+        );
+        assert_eq!(tree["electrical"], unchanged["electrical"]);
+    }
+}
@@ -0,0 +1,70 @@
+//! Allowlist of paths clients may write with PUT.
+//!
+//! Patterns use the same syntax as subscriptions (see [`PathPattern`]) and
+//! are compiled once at startup. Both the WebSocket and HTTP PUT handlers
+//! consult the same [`WritablePaths`] so they can't disagree.
+
+use crate::path::{PathPattern, PatternError};
+
+/// Paths writable when the settings don't configure any.
+pub const DEFAULT_WRITABLE_PATHS: &[&str] = &["steering.autopilot.*", "electrical.switches.*"];
+
+/// Compiled set of writable path patterns.
+#[derive(Debug, Clone)]
+pub struct WritablePaths {
+    patterns: Vec<PathPattern>,
+}
+
+impl WritablePaths {
+    /// Compile the given patterns.
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, PatternError> {
+        let patterns = patterns
+            .iter()
+            .map(|p| PathPattern::new(p.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// An allowlist that rejects every path.
+    pub fn none() -> Self {
+        Self {
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Check whether clients may write to `path`.
+    pub fn is_writable(&self, path: &str) -> bool {
+        self.patterns.iter().any(|p| p.matches(path))
+    }
+
+    /// The configured patterns.
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(PathPattern::as_str)
+    }
+}
+
+impl Default for WritablePaths {
+    fn default() -> Self {
+        Self::new(DEFAULT_WRITABLE_PATHS).expect("default writable paths are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_writable_paths() {
+        let writable = WritablePaths::default();
+
+        assert!(writable.is_writable("steering.autopilot.target.headingTrue"));
+        assert!(writable.is_writable("electrical.switches.anchorLight.state"));
+        assert!(!writable.is_writable("navigation.position"));
+        assert!(!WritablePaths::none().is_writable("steering.autopilot.state"));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(WritablePaths::new(&["steering.*", ""]).is_err());
+    }
+}
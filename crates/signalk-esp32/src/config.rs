@@ -2,7 +2,15 @@
 //!
 //! Provides persistent configuration storage using ESP-IDF's NVS flash.
 
+use std::net::Ipv4Addr;
+
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use signalk_core::SelfUrn;
+
+use crate::lock::PoisonRecovery;
+use crate::storage::NvsBlobs;
 
 /// Server configuration stored in NVS.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +26,10 @@ pub struct ServerConfig {
 
     /// HTTP server port.
     pub http_port: u16,
+
+    /// How to recover the store after a panic poisoned its lock.
+    #[serde(default)]
+    pub store_poison_recovery: PoisonRecovery,
 }
 
 impl Default for ServerConfig {
@@ -27,78 +39,336 @@ impl Default for ServerConfig {
             version: "1.7.0".to_string(),
             self_urn: String::new(), // Must be set before use
             http_port: 80,
+            store_poison_recovery: PoisonRecovery::default(),
         }
     }
 }
 
 impl ServerConfig {
-    /// Create a new config with generated UUID.
+    /// Create a config with a freshly generated (non-persistent) UUID.
+    ///
+    /// Prefer [`ServerConfig::with_self_urn`] with a URN loaded through
+    /// `SelfUrn::load_or_generate` so the vessel identity survives reboots.
     pub fn new_with_uuid() -> Self {
-        // Note: uuid crate with v4 feature needed for this
-        // For now, use a placeholder that should be replaced with actual UUID generation
-        let uuid = generate_uuid();
+        Self::with_self_urn(&SelfUrn::generate())
+    }
+
+    /// Create a config for the given vessel identity.
+    pub fn with_self_urn(self_urn: &SelfUrn) -> Self {
         Self {
-            self_urn: format!("vessels.urn:mrn:signalk:uuid:{}", uuid),
+            self_urn: self_urn.context(),
             ..Default::default()
         }
     }
 }
 
+/// NVS namespace used for all SignalK configuration entries.
+pub const NVS_NAMESPACE: &str = "signalk";
+
+/// NVS key for the WiFi configuration blob.
+const WIFI_CONFIG_KEY: &str = "wifi";
+
+/// Maximum serialized size of the WiFi configuration.
+///
+/// SSID (32) + password (64) + hostname (30) + static IP settings plus JSON
+/// overhead fits comfortably; the buffer lives on the stack during load.
+const WIFI_CONFIG_MAX_LEN: usize = 512;
+
 /// WiFi configuration stored in NVS.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Stored as a JSON blob under the `wifi` key in the `signalk` namespace so
+/// that the device can be moved to a different network without reflashing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WifiConfig {
     /// WiFi network SSID.
     pub ssid: String,
 
     /// WiFi network password (empty for open networks).
     pub password: String,
+
+    /// DHCP hostname announced to the network (max 30 chars).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// Fixed IPv4 settings; DHCP is used when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub static_ip: Option<StaticIpConfig>,
 }
 
-impl Default for WifiConfig {
-    fn default() -> Self {
+impl WifiConfig {
+    /// Create a WiFi configuration without a hostname.
+    pub fn new(ssid: impl Into<String>, password: impl Into<String>) -> Self {
         Self {
-            ssid: String::new(),
-            password: String::new(),
+            ssid: ssid.into(),
+            password: password.into(),
+            hostname: None,
+            static_ip: None,
+        }
+    }
+
+    /// Check whether an SSID has been set.
+    pub fn is_configured(&self) -> bool {
+        !self.ssid.is_empty()
+    }
+
+    /// Load the WiFi configuration from NVS.
+    ///
+    /// Returns `Ok(None)` if no configuration has been stored yet.
+    pub fn load(nvs: &impl NvsBlobs) -> Result<Option<Self>> {
+        let mut buf = [0u8; WIFI_CONFIG_MAX_LEN];
+        match nvs
+            .get_blob(WIFI_CONFIG_KEY, &mut buf)
+            .map_err(|e| anyhow!("{e:?}"))?
+        {
+            Some(bytes) => Ok(Some(Self::from_bytes(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Load the WiFi configuration from NVS, or use `fallback` if NVS is empty.
+    ///
+    /// A corrupt entry is logged and treated as empty so the device can still
+    /// come up on the compile-time network.
+    pub fn load_or(nvs: &impl NvsBlobs, fallback: WifiConfig) -> Self {
+        match Self::load(nvs) {
+            Ok(Some(config)) if config.is_configured() => {
+                info!(
+                    "Loaded WiFi configuration from NVS (ssid='{}')",
+                    config.ssid
+                );
+                config
+            }
+            Ok(_) => {
+                info!("No WiFi configuration in NVS, using compile-time defaults");
+                fallback
+            }
+            Err(e) => {
+                warn!("Failed to load WiFi configuration from NVS: {:?}", e);
+                fallback
+            }
         }
     }
+
+    /// Save the WiFi configuration to NVS.
+    pub fn save(&self, nvs: &mut impl NvsBlobs) -> Result<()> {
+        let bytes = self.to_bytes()?;
+        if bytes.len() > WIFI_CONFIG_MAX_LEN {
+            bail!("WiFi configuration too large ({} bytes)", bytes.len());
+        }
+        nvs.set_blob(WIFI_CONFIG_KEY, &bytes)
+            .map_err(|e| anyhow!("{e:?}"))?;
+        Ok(())
+    }
+
+    /// Serialize to the JSON blob format stored in NVS.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Deserialize from the JSON blob format stored in NVS.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
 }
 
-/// Generate a simple UUID-like string.
+/// Static IPv4 configuration as stored in NVS.
 ///
-/// Note: This is a simple implementation. In production, use the `uuid` crate
-/// with proper entropy source, or read from ESP32's hardware RNG.
-fn generate_uuid() -> String {
-    // Use ESP32's random number generator if available
-    // For now, use a timestamp-based approach
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    let duration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-
-    let secs = duration.as_secs();
-    let nanos = duration.subsec_nanos();
-
-    // Format as UUID-like string (not cryptographically secure)
-    format!(
-        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
-        secs as u32,
-        (secs >> 32) as u16,
-        (nanos >> 16) as u16,
-        nanos as u16,
-        (secs ^ (nanos as u64)) & 0xFFFFFFFFFFFF
-    )
+/// Addresses are kept as dotted-quad strings so the blob stays human-editable;
+/// use [`StaticIpConfig::parse`] to validate them before applying.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaticIpConfig {
+    /// Device address, e.g. `192.168.1.50`.
+    pub ip: String,
+
+    /// Default gateway, e.g. `192.168.1.1`.
+    pub gateway: String,
+
+    /// Subnet mask, e.g. `255.255.255.0`.
+    pub netmask: String,
+
+    /// Optional DNS server (defaults to none).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<String>,
+}
+
+/// Validated static IPv4 settings ready to hand to the netif.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticIp {
+    /// Device address.
+    pub ip: Ipv4Addr,
+    /// Default gateway.
+    pub gateway: Ipv4Addr,
+    /// Subnet mask as a prefix length (e.g. 24 for `255.255.255.0`).
+    pub prefix_len: u8,
+    /// Optional DNS server.
+    pub dns: Option<Ipv4Addr>,
 }
 
-// Future: NVS storage implementation
-// pub struct NvsStorage {
-//     nvs: EspDefaultNvsPartition,
-// }
-//
-// impl NvsStorage {
-//     pub fn new() -> Result<Self> { ... }
-//     pub fn load_server_config(&self) -> Result<ServerConfig> { ... }
-//     pub fn save_server_config(&self, config: &ServerConfig) -> Result<()> { ... }
-//     pub fn load_wifi_config(&self) -> Result<WifiConfig> { ... }
-//     pub fn save_wifi_config(&self, config: &WifiConfig) -> Result<()> { ... }
-// }
+impl StaticIpConfig {
+    /// Parse and validate the addresses.
+    ///
+    /// Fails if any address is malformed, the netmask is not contiguous, the
+    /// device address is unusable, or the gateway is outside the subnet.
+    pub fn parse(&self) -> Result<StaticIp> {
+        let ip = parse_ipv4("ip", &self.ip)?;
+        let gateway = parse_ipv4("gateway", &self.gateway)?;
+        let netmask = parse_ipv4("netmask", &self.netmask)?;
+        let dns = self
+            .dns
+            .as_deref()
+            .map(|dns| parse_ipv4("dns", dns))
+            .transpose()?;
+
+        let prefix_len = netmask_prefix_len(netmask)
+            .ok_or_else(|| anyhow!("Invalid netmask '{}'", self.netmask))?;
+        if prefix_len == 0 {
+            bail!("Invalid netmask '{}'", self.netmask);
+        }
+
+        if ip.is_unspecified() || ip.is_broadcast() || ip.is_multicast() {
+            bail!("Invalid static ip '{}'", ip);
+        }
+
+        let mask = u32::from(netmask);
+        if u32::from(ip) & mask != u32::from(gateway) & mask {
+            bail!(
+                "Gateway {} is not in the subnet of {}/{}",
+                gateway,
+                ip,
+                prefix_len
+            );
+        }
+
+        Ok(StaticIp {
+            ip,
+            gateway,
+            prefix_len,
+            dns,
+        })
+    }
+}
+
+fn parse_ipv4(field: &str, value: &str) -> Result<Ipv4Addr> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Invalid {} address '{}'", field, value))
+}
+
+/// Convert a dotted netmask to a prefix length, rejecting non-contiguous masks.
+fn netmask_prefix_len(netmask: Ipv4Addr) -> Option<u8> {
+    let bits = u32::from(netmask);
+    let prefix_len = bits.leading_ones();
+    if bits.checked_shl(prefix_len).unwrap_or(0) != 0 {
+        return None;
+    }
+    Some(prefix_len as u8)
+}
+
+#[cfg(all(test, not(target_os = "espidf")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wifi_config_round_trip() {
+        let config = WifiConfig {
+            ssid: "Marina-Guest".to_string(),
+            password: "secret".to_string(),
+            hostname: Some("signalk-boat".to_string()),
+            static_ip: Some(StaticIpConfig {
+                ip: "192.168.100.200".to_string(),
+                gateway: "192.168.100.100".to_string(),
+                netmask: "255.255.255.0".to_string(),
+                dns: Some("192.168.100.100".to_string()),
+            }),
+        };
+
+        let bytes = config.to_bytes().unwrap();
+        assert!(bytes.len() <= WIFI_CONFIG_MAX_LEN);
+        assert_eq!(WifiConfig::from_bytes(&bytes).unwrap(), config);
+    }
+
+    #[test]
+    fn test_wifi_config_in_nvs() {
+        let mut nvs = crate::storage::tests::MemoryNvs::default();
+        let fallback = WifiConfig::new("Compiled-In", "pw");
+        assert_eq!(WifiConfig::load(&nvs).unwrap(), None);
+        assert_eq!(WifiConfig::load_or(&nvs, fallback.clone()), fallback);
+
+        let config = WifiConfig::new("Marina-Guest", "secret");
+        config.save(&mut nvs).unwrap();
+        assert_eq!(WifiConfig::load_or(&nvs, fallback.clone()), config);
+
+        // A corrupt entry falls back too
+        nvs.set_blob(WIFI_CONFIG_KEY, b"{not json").unwrap();
+        assert_eq!(WifiConfig::load_or(&nvs, fallback.clone()), fallback);
+    }
+
+    #[test]
+    fn test_wifi_config_without_hostname() {
+        let bytes = br#"{"ssid":"Boat","password":""}"#;
+        let config = WifiConfig::from_bytes(bytes).unwrap();
+
+        assert_eq!(config, WifiConfig::new("Boat", ""));
+        assert!(config.is_configured());
+        assert!(!WifiConfig::default().is_configured());
+    }
+
+    #[test]
+    fn test_static_ip_parse() {
+        let bytes = br#"{"ssid":"Boat","password":"pw","static_ip":{"ip":"10.0.1.50","gateway":"10.0.1.1","netmask":"255.255.254.0"}}"#;
+        let config = WifiConfig::from_bytes(bytes).unwrap();
+        let static_ip = config.static_ip.unwrap().parse().unwrap();
+
+        assert_eq!(
+            static_ip,
+            StaticIp {
+                ip: Ipv4Addr::new(10, 0, 1, 50),
+                gateway: Ipv4Addr::new(10, 0, 1, 1),
+                prefix_len: 23,
+                dns: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_static_ip_parse_rejects_invalid() {
+        let valid = StaticIpConfig {
+            ip: "192.168.1.50".to_string(),
+            gateway: "192.168.1.1".to_string(),
+            netmask: "255.255.255.0".to_string(),
+            dns: None,
+        };
+        assert!(valid.parse().is_ok());
+
+        let cases = [
+            StaticIpConfig {
+                ip: "192.168.1.500".to_string(),
+                ..valid.clone()
+            },
+            StaticIpConfig {
+                netmask: "255.0.255.0".to_string(),
+                ..valid.clone()
+            },
+            StaticIpConfig {
+                netmask: "0.0.0.0".to_string(),
+                ..valid.clone()
+            },
+            StaticIpConfig {
+                gateway: "192.168.2.1".to_string(),
+                ..valid.clone()
+            },
+            StaticIpConfig {
+                ip: "0.0.0.0".to_string(),
+                ..valid.clone()
+            },
+            StaticIpConfig {
+                dns: Some("dns.local".to_string()),
+                ..valid.clone()
+            },
+        ];
+        for case in cases {
+            assert!(case.parse().is_err(), "{:?} should be rejected", case);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! REST handler logic for `/skServer/vessel` and `/skServer/settings`.
+//!
+//! Wraps the shared `ConfigHandlers` so the esp-idf HTTP handlers only deal
+//! with request/response plumbing. Responses are serialized straight into
+//! the response writer instead of building a `String` first, keeping peak
+//! usage on the small httpd task stack and heap low.
+
+use std::io;
+
+use signalk_core::config::{ConfigError, ConfigHandlers, ConfigStorage};
+
+/// Maximum accepted PUT body size in bytes.
+pub const MAX_CONFIG_BODY_LEN: usize = 2048;
+
+/// Configuration documents exposed over REST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigResource {
+    /// `/skServer/vessel`
+    Vessel,
+    /// `/skServer/settings`
+    Settings,
+}
+
+impl ConfigResource {
+    /// URI the resource is served at.
+    pub fn uri(&self) -> &'static str {
+        match self {
+            ConfigResource::Vessel => "/skServer/vessel",
+            ConfigResource::Settings => "/skServer/settings",
+        }
+    }
+}
+
+/// Serialize the stored document as JSON into `writer`.
+///
+/// A document that was never saved is returned as its default (`{}`), so a
+/// freshly flashed device answers 200 rather than 404.
+pub fn write_config<S: ConfigStorage, W: io::Write>(
+    storage: &S,
+    resource: ConfigResource,
+    writer: W,
+) -> Result<(), ConfigError> {
+    let result = match resource {
+        ConfigResource::Vessel => {
+            let vessel = or_default(ConfigHandlers::get_vessel(storage))?;
+            serde_json::to_writer(writer, &vessel)
+        }
+        ConfigResource::Settings => {
+            let settings = or_default(ConfigHandlers::get_settings(storage))?;
+            serde_json::to_writer(writer, &settings)
+        }
+    };
+    result.map_err(|e| ConfigError::WriteError(e.to_string()))
+}
+
+/// Parse a JSON body and store it as the new document.
+pub fn update_config<S: ConfigStorage>(
+    storage: &S,
+    resource: ConfigResource,
+    body: &[u8],
+) -> Result<(), ConfigError> {
+    if body.len() > MAX_CONFIG_BODY_LEN {
+        return Err(ConfigError::InvalidData(format!(
+            "body exceeds {MAX_CONFIG_BODY_LEN} bytes"
+        )));
+    }
+
+    let invalid = |e: serde_json::Error| ConfigError::InvalidData(e.to_string());
+    match resource {
+        ConfigResource::Vessel => {
+            ConfigHandlers::put_vessel(storage, serde_json::from_slice(body).map_err(invalid)?)
+        }
+        ConfigResource::Settings => {
+            ConfigHandlers::put_settings(storage, serde_json::from_slice(body).map_err(invalid)?)
+        }
+    }
+}
+
+/// HTTP status code for a configuration error.
+pub fn error_status(error: &ConfigError) -> u16 {
+    match error {
+        ConfigError::NotFound(_) => 404,
+        ConfigError::InvalidData(_) => 400,
+        ConfigError::StorageUnavailable(_) => 503,
+        ConfigError::ReadError(_) | ConfigError::WriteError(_) => 500,
+    }
+}
+
+fn or_default<T: Default>(result: Result<T, ConfigError>) -> Result<T, ConfigError> {
+    match result {
+        Err(ConfigError::NotFound(_)) => Ok(T::default()),
+        other => other,
+    }
+}
+
+/// Adapts an esp-idf response (`embedded_io::Write`) to `std::io::Write`
+/// so serde_json can stream into it.
+pub struct StdWriter<W>(pub W);
+
+#[cfg(target_os = "espidf")]
+impl<W> io::Write for StdWriter<W>
+where
+    W: esp_idf_svc::io::Write,
+    W::Error: std::fmt::Debug,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write(buf)
+            .map_err(|e| io::Error::other(format!("{e:?}")))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0
+            .flush()
+            .map_err(|e| io::Error::other(format!("{e:?}")))
+    }
+}
+
+#[cfg(all(test, not(target_os = "espidf")))]
+mod tests {
+    use super::*;
+    use signalk_core::config::MemoryConfigStorage;
+
+    #[test]
+    fn test_get_unsaved_config_returns_default() {
+        let storage = MemoryConfigStorage::new();
+        let mut out = Vec::new();
+
+        write_config(&storage, ConfigResource::Vessel, &mut out).unwrap();
+
+        assert_eq!(out, b"{}");
+    }
+
+    #[test]
+    fn test_put_then_get_vessel() {
+        let storage = MemoryConfigStorage::new();
+        let body = br#"{"name":"Nightwatch","mmsi":"244123456"}"#;
+
+        update_config(&storage, ConfigResource::Vessel, body).unwrap();
+
+        let mut out = Vec::new();
+        write_config(&storage, ConfigResource::Vessel, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["name"], "Nightwatch");
+        assert_eq!(json["mmsi"], "244123456");
+    }
+
+    #[test]
+    fn test_put_settings_rejects_invalid_body() {
+        let storage = MemoryConfigStorage::new();
+
+        let err =
+            update_config(&storage, ConfigResource::Settings, b"{\"port\":\"x\"}").unwrap_err();
+        assert_eq!(error_status(&err), 400);
+
+        let oversized = vec![b' '; MAX_CONFIG_BODY_LEN + 1];
+        let err = update_config(&storage, ConfigResource::Settings, &oversized).unwrap_err();
+        assert_eq!(error_status(&err), 400);
+        assert!(!storage.has_key("settings"));
+    }
+}
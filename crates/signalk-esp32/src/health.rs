@@ -0,0 +1,183 @@
+//! Heap monitoring for ESP32.
+//!
+//! A background thread periodically samples free heap and publishes it on the
+//! normal delta stream under `signalk.server.*` of the self vessel, so device
+//! health is visible remotely to any subscribed client. A warning is logged
+//! whenever free heap drops below the configured threshold.
+
+#[cfg(target_os = "espidf")]
+use std::sync::mpsc;
+#[cfg(target_os = "espidf")]
+use std::thread;
+use std::time::Duration;
+
+#[cfg(target_os = "espidf")]
+use log::{info, warn};
+use serde_json::json;
+use signalk_core::{Delta, PathValue, Update};
+
+use crate::http::current_timestamp;
+
+/// Path for the current free heap in bytes.
+pub const FREE_HEAP_PATH: &str = "signalk.server.freeHeap";
+
+/// Path for the lowest free heap observed since boot, in bytes.
+pub const MIN_FREE_HEAP_PATH: &str = "signalk.server.minFreeHeap";
+
+/// Source label used for heap deltas.
+const HEAP_SOURCE: &str = "signalk-server.heap";
+
+/// Heap monitor configuration.
+#[derive(Debug, Clone)]
+pub struct HeapMonitorConfig {
+    /// How often to sample the heap.
+    pub interval: Duration,
+    /// Log a warning when free heap falls below this many bytes.
+    pub low_threshold: u32,
+    /// Publish heap values as deltas (otherwise only log).
+    pub emit_delta: bool,
+}
+
+impl Default for HeapMonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            low_threshold: 20 * 1024,
+            emit_delta: true,
+        }
+    }
+}
+
+/// Change in heap state between two samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapAlert {
+    /// Free heap just dropped below the threshold.
+    Low,
+    /// Free heap is back at or above the threshold.
+    Recovered,
+}
+
+/// Evaluate a heap sample against the threshold.
+///
+/// Only transitions are reported, so a device that stays low does not flood
+/// the log with one warning per sample.
+pub fn check_heap(free: u32, threshold: u32, was_low: bool) -> Option<HeapAlert> {
+    let is_low = free < threshold;
+    match (was_low, is_low) {
+        (false, true) => Some(HeapAlert::Low),
+        (true, false) => Some(HeapAlert::Recovered),
+        _ => None,
+    }
+}
+
+/// Build the delta reporting current and minimum free heap.
+pub fn heap_delta(free: u32, min_free: u32) -> Delta {
+    Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some(HEAP_SOURCE.to_string()),
+            source: None,
+            timestamp: Some(current_timestamp()),
+            values: vec![
+                PathValue {
+                    path: FREE_HEAP_PATH.to_string(),
+                    value: json!(free),
+                },
+                PathValue {
+                    path: MIN_FREE_HEAP_PATH.to_string(),
+                    value: json!(min_free),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    }
+}
+
+/// Current free heap in bytes.
+#[cfg(target_os = "espidf")]
+pub fn free_heap() -> u32 {
+    unsafe { esp_idf_svc::sys::esp_get_free_heap_size() }
+}
+
+/// Lowest free heap observed since boot, in bytes.
+#[cfg(target_os = "espidf")]
+pub fn min_free_heap() -> u32 {
+    unsafe { esp_idf_svc::sys::esp_get_minimum_free_heap_size() }
+}
+
+/// Spawn the heap monitor thread.
+///
+/// Deltas are sent through the same channel as every other data source, so
+/// they are stored and broadcast by the delta processor like normal data.
+#[cfg(target_os = "espidf")]
+pub fn spawn_heap_monitor(
+    config: HeapMonitorConfig,
+    delta_tx: mpsc::Sender<Delta>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+    // Stack must be >= CONFIG_PTHREAD_STACK_MIN (16KB in sdkconfig.defaults)
+    thread::Builder::new()
+        .name("heap-mon".into())
+        .stack_size(16 * 1024)
+        .spawn(move || run_heap_monitor(config, delta_tx))
+}
+
+#[cfg(target_os = "espidf")]
+fn run_heap_monitor(config: HeapMonitorConfig, delta_tx: mpsc::Sender<Delta>) {
+    info!(
+        "Heap monitor started (interval={:?}, threshold={} bytes)",
+        config.interval, config.low_threshold
+    );
+
+    let mut low = false;
+    loop {
+        let free = free_heap();
+        let min_free = min_free_heap();
+
+        match check_heap(free, config.low_threshold, low) {
+            Some(HeapAlert::Low) => {
+                low = true;
+                warn!(
+                    "Free heap low: {} bytes (threshold {}, minimum since boot {})",
+                    free, config.low_threshold, min_free
+                );
+            }
+            Some(HeapAlert::Recovered) => {
+                low = false;
+                info!("Free heap recovered: {} bytes", free);
+            }
+            None => {}
+        }
+
+        if config.emit_delta && delta_tx.send(heap_delta(free, min_free)).is_err() {
+            warn!("Heap monitor stopped: delta channel closed");
+            break;
+        }
+
+        thread::sleep(config.interval);
+    }
+}
+
+#[cfg(all(test, not(target_os = "espidf")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_heap_reports_transitions_only() {
+        assert_eq!(check_heap(50_000, 20_000, false), None);
+        assert_eq!(check_heap(19_999, 20_000, false), Some(HeapAlert::Low));
+        assert_eq!(check_heap(10_000, 20_000, true), None);
+        assert_eq!(check_heap(20_000, 20_000, true), Some(HeapAlert::Recovered));
+    }
+
+    #[test]
+    fn test_heap_delta_paths() {
+        let delta = heap_delta(42_000, 30_000);
+        let values = &delta.updates[0].values;
+
+        assert_eq!(values[0].path, FREE_HEAP_PATH);
+        assert_eq!(values[0].value, json!(42_000));
+        assert_eq!(values[1].path, MIN_FREE_HEAP_PATH);
+        assert_eq!(values[1].value, json!(30_000));
+    }
+}
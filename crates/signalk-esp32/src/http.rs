@@ -3,6 +3,7 @@
 //! Provides helper functions for building SignalK-compliant HTTP responses
 //! and WebSocket connection management.
 
+use crate::lock::{lock_store, PoisonRecovery};
 use signalk_core::{MemoryStore, PathPattern, SignalKStore};
 use signalk_protocol::{ClientMessage, DiscoveryResponse, HelloMessage, ServerMessage};
 use std::sync::{Arc, Mutex};
@@ -74,6 +75,36 @@ impl WsQueryParams {
     }
 }
 
+/// Query parameters for REST path requests (`/signalk/v1/api/<path>`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApiPathQuery {
+    /// Return this source's value instead of the primary one.
+    pub source: Option<String>,
+    /// Include the path's metadata with a `source` selection.
+    pub meta: bool,
+}
+
+impl ApiPathQuery {
+    /// Parse query parameters from a URI query string.
+    ///
+    /// Example: "source=gps1&meta=true"
+    pub fn parse(query: &str) -> Self {
+        let mut params = Self::default();
+
+        for pair in query.split('&') {
+            if let Some((key, value)) = pair.split_once('=') {
+                match key {
+                    "source" => params.source = Some(value.to_string()),
+                    "meta" => params.meta = value == "true",
+                    _ => {}
+                }
+            }
+        }
+
+        params
+    }
+}
+
 // ============================================================================
 // Throttling Support
 // ============================================================================
@@ -262,11 +293,11 @@ pub fn default_subscription_for_mode(mode: SubscribeMode) -> ClientSubscription
     match mode {
         SubscribeMode::Self_ => ClientSubscription::new(
             Some("vessels.self".to_string()),
-            vec![PathPattern::new("*").unwrap()],
+            PathPattern::new("*").into_iter().collect(),
         ),
         SubscribeMode::All => ClientSubscription::new(
             Some("*".to_string()),
-            vec![PathPattern::new("*").unwrap()],
+            PathPattern::new("*").into_iter().collect(),
         ),
         SubscribeMode::None => ClientSubscription {
             context: None,
@@ -352,8 +383,14 @@ pub fn process_client_message(
                 patterns,
             ))
         }
-        ClientMessage::Put(_) => {
-            // PUT requests don't affect subscriptions
+        ClientMessage::SetMode { mode } => match mode.as_str() {
+            "self" | "all" | "none" => {
+                Some(default_subscription_for_mode(SubscribeMode::from_str(&mode)))
+            }
+            _ => None,
+        },
+        ClientMessage::Put(_) | ClientMessage::GetFull(_) => {
+            // PUT and GET requests don't affect subscriptions
             None
         }
     }
@@ -370,28 +407,59 @@ pub fn create_hello_message(name: &str, version: &str, self_urn: &str) -> Server
 }
 
 /// Create a discovery response JSON string.
-pub fn create_discovery_json(host: &str, port: u16) -> Result<String, serde_json::Error> {
-    let discovery = DiscoveryResponse::new(host, port);
+///
+/// `host` is the request's `Host` header and `forwarded_proto` its
+/// `X-Forwarded-Proto` header, so the URLs match what the client used to
+/// reach us. Without a usable host the URLs point at `localhost` on `port`.
+pub fn create_discovery_json(
+    host: Option<&str>,
+    forwarded_proto: Option<&str>,
+    port: u16,
+) -> Result<String, serde_json::Error> {
+    let scheme = DiscoveryResponse::forwarded_scheme(forwarded_proto);
+    let discovery = host
+        .and_then(|host| DiscoveryResponse::for_request(host, scheme, None, "", None).ok())
+        .unwrap_or_else(|| DiscoveryResponse::new("localhost", port));
     serde_json::to_string(&discovery)
 }
 
 /// Get the full SignalK data model as JSON.
-pub fn get_full_model_json(store: &Arc<Mutex<MemoryStore>>) -> Result<String, String> {
-    match store.lock() {
-        Ok(store) => serde_json::to_string(store.full_model()).map_err(|e| e.to_string()),
-        Err(_) => Err("Store is locked".to_string()),
-    }
+pub fn get_full_model_json(
+    store: &Arc<Mutex<MemoryStore>>,
+    recovery: PoisonRecovery,
+) -> Result<String, String> {
+    let store = lock_store(store, recovery);
+    serde_json::to_string(store.full_model()).map_err(|e| e.to_string())
 }
 
 /// Get a specific path from the SignalK data model.
-pub fn get_path_json(store: &Arc<Mutex<MemoryStore>>, path: &str) -> Result<String, String> {
-    match store.lock() {
-        Ok(store) => match store.get_path(path) {
-            Some(value) => serde_json::to_string(&value).map_err(|e| e.to_string()),
-            None => Err(format!("Path not found: {}", path)),
-        },
-        Err(_) => Err("Store is locked".to_string()),
-    }
+///
+/// With `query.source`, returns that source's value from the node's
+/// multi-source `values` map (plus the node's meta with `query.meta`),
+/// the same way the Linux server does. A missing path or source is an error.
+pub fn get_path_json(
+    store: &Arc<Mutex<MemoryStore>>,
+    path: &str,
+    query: &ApiPathQuery,
+    recovery: PoisonRecovery,
+) -> Result<String, String> {
+    let node = lock_store(store, recovery)
+        .get_path(path)
+        .ok_or_else(|| format!("Path not found: {}", path))?;
+    let value = match &query.source {
+        None => node,
+        Some(source) => {
+            let mut selected = signalk_core::select_source(&node, source)
+                .ok_or_else(|| format!("No value from {} for {}", source, path))?;
+            if query.meta {
+                if let (Some(meta), Some(selected)) = (node.get("meta"), selected.as_object_mut()) {
+                    selected.insert("meta".to_string(), meta.clone());
+                }
+            }
+            selected
+        }
+    };
+    serde_json::to_string(&value).map_err(|e| e.to_string())
 }
 
 /// Get current timestamp in ISO 8601 format.
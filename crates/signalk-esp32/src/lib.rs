@@ -2,8 +2,13 @@
 //!
 //! This crate provides reusable components for ESP32-based SignalK implementations:
 //! - WiFi connection management
-//! - NVS (Non-Volatile Storage) configuration
+//! - NVS (Non-Volatile Storage) configuration and `ConfigStorage` backend
 //! - HTTP/WebSocket handler utilities
+//! - REST handlers for NVS-stored configuration
+//! - Per-client outbound queues for WebSocket streaming
+//! - Heap monitoring published as deltas
+//! - Recovery from poisoned locks
+//! - mDNS service advertisement (`mdns` feature)
 //!
 //! # Architecture
 //!
@@ -15,15 +20,23 @@
 //!
 //! ```ignore
 //! use signalk_esp32::wifi::connect_wifi;
-//! use signalk_esp32::config::NvsConfig;
+//! use signalk_esp32::config::WifiConfig;
 //!
-//! // Connect to WiFi
-//! let wifi = connect_wifi("ssid", "password", modem, sysloop)?;
+//! // Load WiFi credentials from NVS, falling back to compile-time defaults
+//! let wifi_config = WifiConfig::load_or(&nvs, WifiConfig::new("ssid", "password"));
 //!
-//! // Load configuration from NVS
-//! let config = NvsConfig::load()?;
+//! // Connect to WiFi
+//! let wifi = connect_wifi(&wifi_config, modem, sysloop)?;
 //! ```
 
+#[cfg(target_os = "espidf")]
 pub mod wifi;
 pub mod config;
+pub mod config_api;
+pub mod storage;
 pub mod http;
+pub mod outbox;
+pub mod health;
+pub mod lock;
+#[cfg(all(feature = "mdns", target_os = "espidf"))]
+pub mod mdns;
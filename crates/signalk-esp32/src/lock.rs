@@ -0,0 +1,127 @@
+//! Recovery from poisoned locks.
+//!
+//! A panic while a thread holds one of the server's mutexes poisons it, and
+//! every later `lock()` returns an error. Handlers that treated that as
+//! "store locked" kept the server up but serving nothing until reboot, and
+//! the only trace of the original panic was a stream of error responses.
+//!
+//! These helpers log the poisoning as an error, clear it and hand out the
+//! guard, so the first failure is visible and the server keeps working.
+
+use std::sync::{Mutex, MutexGuard};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use signalk_core::{MemoryStore, SignalKStore};
+
+/// What to do with the store's contents when its lock was poisoned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PoisonRecovery {
+    /// Keep the data. A panicking writer can at worst leave a half-applied
+    /// delta behind, which the next delta for those paths overwrites.
+    #[default]
+    KeepData,
+    /// Start over with an empty store for the same vessel.
+    Reset,
+}
+
+/// Lock `mutex`, recovering it if poisoned.
+///
+/// `name` identifies the lock in the log.
+pub fn lock_recovering<'a, T>(mutex: &'a Mutex<T>, name: &str) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        error!("{name} lock was poisoned by a panic, recovering");
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Lock the store, recovering it according to `recovery` if poisoned.
+pub fn lock_store(
+    store: &Mutex<MemoryStore>,
+    recovery: PoisonRecovery,
+) -> MutexGuard<'_, MemoryStore> {
+    match store.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            error!("Store lock was poisoned by a panic, recovering ({recovery:?})");
+            store.clear_poison();
+            let mut guard = poisoned.into_inner();
+            if recovery == PoisonRecovery::Reset {
+                let self_urn = guard.self_urn().to_string();
+                *guard = MemoryStore::new(&self_urn);
+            }
+            guard
+        }
+    }
+}
+
+#[cfg(all(test, not(target_os = "espidf")))]
+mod tests {
+    use super::*;
+    use signalk_core::{Delta, PathValue, Update};
+    use std::sync::Arc;
+
+    const SELF_URN: &str = "vessels.urn:mrn:signalk:uuid:test";
+
+    fn poisoned_store() -> Arc<Mutex<MemoryStore>> {
+        let store = Arc::new(Mutex::new(MemoryStore::new(SELF_URN)));
+        store.lock().unwrap().apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("test".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.2),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+
+        let panicking = Arc::clone(&store);
+        let _ = std::thread::spawn(move || {
+            let _guard = panicking.lock().unwrap();
+            panic!("handler crashed");
+        })
+        .join();
+        assert!(store.is_poisoned());
+        store
+    }
+
+    #[test]
+    fn test_poisoned_store_recovers() {
+        let store = poisoned_store();
+
+        let guard = lock_store(&store, PoisonRecovery::KeepData);
+        assert!(guard.get_self_path("navigation.speedOverGround").is_some());
+        drop(guard);
+        assert!(!store.is_poisoned());
+        assert!(store.lock().is_ok());
+
+        let store = poisoned_store();
+        let guard = lock_store(&store, PoisonRecovery::Reset);
+        assert!(guard.get_self_path("navigation.speedOverGround").is_none());
+        assert_eq!(guard.self_urn(), SELF_URN);
+        drop(guard);
+        assert!(!store.is_poisoned());
+    }
+
+    #[test]
+    fn test_lock_recovering() {
+        let clients = Arc::new(Mutex::new(vec![1, 2]));
+        let panicking = Arc::clone(&clients);
+        let _ = std::thread::spawn(move || {
+            let mut guard = panicking.lock().unwrap();
+            guard.push(3);
+            panic!("handler crashed");
+        })
+        .join();
+
+        assert_eq!(*lock_recovering(&clients, "clients"), [1, 2, 3]);
+        assert!(!clients.is_poisoned());
+    }
+}
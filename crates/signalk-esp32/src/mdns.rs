@@ -0,0 +1,70 @@
+//! mDNS service advertisement for ESP32.
+//!
+//! Advertises the SignalK HTTP and WebSocket endpoints using the esp-idf mDNS
+//! component so apps can find a headless device without knowing its IP:
+//! - `_signalk-http._tcp` - REST API and discovery
+//! - `_signalk-ws._tcp` - WebSocket delta stream
+//!
+//! The device answers to `<hostname>.local` (default `signalk.local`).
+//!
+//! # Memory Cost
+//!
+//! This module is behind the `mdns` feature because the mDNS component is not
+//! free on a 320KB-heap device:
+//! - Flash: roughly 20-30KB of additional code (check with `make esp-size`)
+//! - Heap: a dedicated FreeRTOS task (`CONFIG_MDNS_TASK_STACK_SIZE`, 4KB by
+//!   default) plus a few hundred bytes per service and TXT record
+//!
+//! The component itself must be pulled in through
+//! `package.metadata.esp-idf-sys.extra_components` in the binary crate.
+
+use anyhow::Result;
+use esp_idf_svc::mdns::EspMdns;
+use log::info;
+
+/// Default mDNS hostname (resolves as `signalk.local`).
+pub const DEFAULT_MDNS_HOSTNAME: &str = "signalk";
+
+/// Start advertising the SignalK services over mDNS.
+///
+/// The TXT records follow the SignalK discovery conventions (`txtvers`,
+/// `swname`, `swvers`, `roles`, `self`) so clients can identify the server
+/// before connecting.
+///
+/// The returned `EspMdns` must be kept alive for the advertisement to remain
+/// active.
+///
+/// # Example
+///
+/// ```ignore
+/// let _mdns = advertise("signalk", "My Boat", 80, "1.7.0", &config.self_urn)?;
+/// ```
+pub fn advertise(
+    hostname: &str,
+    instance_name: &str,
+    port: u16,
+    version: &str,
+    self_urn: &str,
+) -> Result<EspMdns> {
+    let mut mdns = EspMdns::take()?;
+    mdns.set_hostname(hostname)?;
+    mdns.set_instance_name(instance_name)?;
+
+    let txt = [
+        ("txtvers", "1"),
+        ("swname", env!("CARGO_PKG_NAME")),
+        ("swvers", version),
+        ("roles", "master,main"),
+        ("self", self_urn),
+    ];
+
+    mdns.add_service(Some(instance_name), "_signalk-http", "_tcp", port, &txt)?;
+    mdns.add_service(Some(instance_name), "_signalk-ws", "_tcp", port, &txt)?;
+
+    info!(
+        "mDNS advertising {}.local (_signalk-http/_signalk-ws on port {})",
+        hostname, port
+    );
+
+    Ok(mdns)
+}
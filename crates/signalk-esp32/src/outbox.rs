@@ -0,0 +1,232 @@
+//! Per-client outbound queue for WebSocket delta streaming.
+//!
+//! The delta processor must never block on a client's socket: a detached
+//! sender waits until the httpd task has written the frame, so one slow
+//! client would otherwise stall delivery (and store updates) for everyone.
+//! Instead the processor pushes values into a bounded [`ClientOutbox`] per
+//! client, and a writer thread drains the outboxes and does the sending.
+//!
+//! The outbox coalesces newest-wins per `(context, path)`: if a client falls
+//! behind it receives the latest value of each path rather than a backlog.
+//! When the outbox is full and a new path arrives, the oldest entry is
+//! dropped and counted.
+//!
+//! # Memory Budget
+//!
+//! An entry holds the path, context, source, timestamp and value, roughly
+//! 200-300 bytes of heap for typical navigation values. With a capacity of
+//! 16 entries that is about 4-5KB per slow client in the worst case, and
+//! nothing for clients that keep up (the queue is drained to empty).
+
+use serde_json::Value;
+use signalk_core::{Delta, PathValue, Update};
+
+/// A queued value awaiting delivery.
+#[derive(Debug, Clone, PartialEq)]
+struct OutboxEntry {
+    context: Option<String>,
+    path: String,
+    value: Value,
+    source_ref: Option<String>,
+    timestamp: Option<String>,
+}
+
+/// Bounded, path-coalescing outbound queue for one client.
+#[derive(Debug)]
+pub struct ClientOutbox {
+    /// Pending entries in arrival order (linear scan is cheaper than a map
+    /// at these sizes on ESP32).
+    entries: Vec<OutboxEntry>,
+    capacity: usize,
+    /// Values dropped because the outbox was full, not yet reported.
+    dropped_unreported: u64,
+    /// Total values dropped over the connection lifetime.
+    dropped_total: u64,
+}
+
+impl ClientOutbox {
+    /// Create an outbox holding at most `capacity` distinct paths.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity: capacity.max(1),
+            dropped_unreported: 0,
+            dropped_total: 0,
+        }
+    }
+
+    /// Queue a value, replacing any pending value for the same context/path.
+    pub fn push(&mut self, context: Option<&str>, update: &Update, pv: &PathValue) {
+        let entry = OutboxEntry {
+            context: context.map(String::from),
+            path: pv.path.clone(),
+            value: pv.value.clone(),
+            source_ref: update.source_ref.clone(),
+            timestamp: update.timestamp.clone(),
+        };
+
+        if let Some(existing) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.path == entry.path && e.context == entry.context)
+        {
+            *existing = entry;
+            return;
+        }
+
+        if self.entries.len() >= self.capacity {
+            self.entries.remove(0);
+            self.dropped_unreported += 1;
+            self.dropped_total += 1;
+        }
+        self.entries.push(entry);
+    }
+
+    /// Check if nothing is pending.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of pending values.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Total values dropped since the client connected.
+    pub fn dropped_total(&self) -> u64 {
+        self.dropped_total
+    }
+
+    /// Values dropped since the last call (for rate-limited logging).
+    pub fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped_unreported)
+    }
+
+    /// Remove all pending values as deltas, one per context.
+    ///
+    /// Values sharing a source and timestamp are grouped into one update.
+    pub fn drain(&mut self) -> Vec<Delta> {
+        let mut deltas: Vec<Delta> = Vec::new();
+
+        for entry in self.entries.drain(..) {
+            let delta = match deltas.iter_mut().find(|d| d.context == entry.context) {
+                Some(delta) => delta,
+                None => {
+                    deltas.push(Delta {
+                        context: entry.context.clone(),
+                        updates: Vec::new(),
+                    });
+                    deltas.last_mut().expect("just pushed")
+                }
+            };
+
+            let pv = PathValue {
+                path: entry.path,
+                value: entry.value,
+            };
+
+            match delta
+                .updates
+                .iter_mut()
+                .find(|u| u.source_ref == entry.source_ref && u.timestamp == entry.timestamp)
+            {
+                Some(update) => update.values.push(pv),
+                None => delta.updates.push(Update {
+                    source_ref: entry.source_ref,
+                    source: None,
+                    timestamp: entry.timestamp,
+                    values: vec![pv],
+                    meta: None,
+                    server_timestamp: None,
+                }),
+            }
+        }
+
+        deltas
+    }
+}
+
+#[cfg(all(test, not(target_os = "espidf")))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn update(ts: &str, values: &[(&str, Value)]) -> Update {
+        Update {
+            source_ref: Some("demo.generator".to_string()),
+            source: None,
+            timestamp: Some(ts.to_string()),
+            values: values
+                .iter()
+                .map(|(path, value)| PathValue {
+                    path: path.to_string(),
+                    value: value.clone(),
+                })
+                .collect(),
+            meta: None,
+            server_timestamp: None,
+        }
+    }
+
+    fn push_all(outbox: &mut ClientOutbox, update: &Update) {
+        for pv in &update.values {
+            outbox.push(Some("vessels.self"), update, pv);
+        }
+    }
+
+    #[test]
+    fn test_newest_value_wins() {
+        let mut outbox = ClientOutbox::new(4);
+        push_all(
+            &mut outbox,
+            &update("t1", &[("navigation.speedOverGround", json!(3.0))]),
+        );
+        push_all(
+            &mut outbox,
+            &update("t2", &[("navigation.speedOverGround", json!(4.0))]),
+        );
+
+        assert_eq!(outbox.len(), 1);
+        let deltas = outbox.drain();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].updates[0].timestamp.as_deref(), Some("t2"));
+        assert_eq!(deltas[0].updates[0].values[0].value, json!(4.0));
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_full_outbox_drops_oldest() {
+        let mut outbox = ClientOutbox::new(2);
+        push_all(
+            &mut outbox,
+            &update("t1", &[("a", json!(1)), ("b", json!(2)), ("c", json!(3))]),
+        );
+
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.take_dropped(), 1);
+        assert_eq!(outbox.take_dropped(), 0);
+        assert_eq!(outbox.dropped_total(), 1);
+
+        let paths: Vec<_> = outbox.drain()[0].updates[0]
+            .values
+            .iter()
+            .map(|pv| pv.path.clone())
+            .collect();
+        assert_eq!(paths, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_drain_groups_by_source_and_timestamp() {
+        let mut outbox = ClientOutbox::new(8);
+        push_all(
+            &mut outbox,
+            &update("t1", &[("a", json!(1)), ("b", json!(2))]),
+        );
+        push_all(&mut outbox, &update("t2", &[("c", json!(3))]));
+
+        let deltas = outbox.drain();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].updates.len(), 2);
+        assert_eq!(deltas[0].updates[0].values.len(), 2);
+    }
+}
@@ -0,0 +1,432 @@
+//! NVS-backed implementation of `signalk_core::ConfigStorage`.
+//!
+//! Each configuration value is stored as a JSON blob keyed by its config name
+//! in the `signalk` NVS namespace. NVS keys are limited to 15 characters.
+//!
+//! # Plugin keys
+//!
+//! Plugin configs are stored under `p:<plugin id>` when that fits. Longer
+//! (or non-ASCII) ids are stored under `p:` + their first 4 alphanumeric
+//! characters + `~` + the 32-bit FNV-1a hash of the whole id in hex, e.g.
+//! `signalk-derived-data` becomes `p:sign~` and eight hex digits. The hash
+//! is stable across builds, so configs survive firmware updates; the prefix
+//! keeps keys recognisable in an NVS dump. The full ids are kept in a separate index,
+//! since NVS handles cannot enumerate keys by prefix.
+//!
+//! # Testing
+//!
+//! The storage is generic over [`NvsBlobs`], the handful of raw NVS calls
+//! it needs. Firmware uses `EspDefaultNvs`, implemented only when building
+//! for esp-idf; host tests use an in-memory map, so keys, serialization and
+//! error mapping are tested with a plain `cargo test`.
+
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::nvs::EspDefaultNvs;
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::sys::{
+    esp_err_t, EspError, ESP_ERR_NVS_INVALID_LENGTH, ESP_ERR_NVS_NOT_ENOUGH_SPACE,
+    ESP_ERR_NVS_NOT_INITIALIZED, ESP_ERR_NVS_VALUE_TOO_LONG,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use signalk_core::config::{
+    ConfigError, ConfigStorage, SecurityConfig, ServerSettings, VesselInfo,
+};
+
+use crate::lock::lock_recovering;
+
+/// Maximum NVS key length (excluding the terminating NUL).
+const NVS_KEY_MAX_LEN: usize = 15;
+
+const SETTINGS_KEY: &str = "settings";
+const VESSEL_KEY: &str = "vessel";
+const SECURITY_KEY: &str = "security";
+
+/// Key holding the list of plugin IDs with saved configuration
+/// (NVS handles cannot enumerate keys by prefix).
+const PLUGIN_INDEX_KEY: &str = "plugins";
+
+/// Prefix for plugin configuration keys.
+const PLUGIN_KEY_PREFIX: &str = "p:";
+
+/// Characters of a long plugin id kept in front of its hash.
+const PLUGIN_KEY_ID_CHARS: usize = 4;
+
+/// A failed NVS operation, classified for mapping to [`ConfigError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NvsError {
+    /// The NVS partition has no room left for the value.
+    NotEnoughSpace,
+    /// The value is larger than NVS can store as one blob.
+    ValueTooLong,
+    /// The NVS partition was not initialised.
+    NotInitialized,
+    /// Any other failure, with its description.
+    Other(String),
+}
+
+#[cfg(target_os = "espidf")]
+impl From<EspError> for NvsError {
+    fn from(e: EspError) -> Self {
+        let code = e.code();
+        if code == ESP_ERR_NVS_NOT_ENOUGH_SPACE as esp_err_t {
+            NvsError::NotEnoughSpace
+        } else if code == ESP_ERR_NVS_VALUE_TOO_LONG as esp_err_t
+            || code == ESP_ERR_NVS_INVALID_LENGTH as esp_err_t
+        {
+            NvsError::ValueTooLong
+        } else if code == ESP_ERR_NVS_NOT_INITIALIZED as esp_err_t {
+            NvsError::NotInitialized
+        } else {
+            NvsError::Other(e.to_string())
+        }
+    }
+}
+
+/// Map an NVS failure on `key` to a `ConfigError`, as a read or a write
+/// failure unless it says more.
+fn config_error(key: &str, writing: bool, e: NvsError) -> ConfigError {
+    match e {
+        NvsError::NotInitialized => ConfigError::StorageUnavailable("NVS not initialised".into()),
+        NvsError::NotEnoughSpace => ConfigError::WriteError(format!("{key}: NVS is full")),
+        NvsError::ValueTooLong => {
+            ConfigError::InvalidData(format!("{key}: value too large for NVS"))
+        }
+        NvsError::Other(msg) if writing => ConfigError::WriteError(format!("{key}: {msg}")),
+        NvsError::Other(msg) => ConfigError::ReadError(format!("{key}: {msg}")),
+    }
+}
+
+/// The raw blob operations of an NVS namespace.
+pub trait NvsBlobs {
+    /// Length of the blob at `key`, `None` if absent.
+    fn blob_len(&self, key: &str) -> Result<Option<usize>, NvsError>;
+
+    /// Read the blob at `key` into `buf`.
+    fn get_blob<'a>(&self, key: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, NvsError>;
+
+    /// Write the blob at `key`.
+    fn set_blob(&mut self, key: &str, bytes: &[u8]) -> Result<(), NvsError>;
+
+    /// Whether `key` holds a value.
+    fn contains(&self, key: &str) -> Result<bool, NvsError>;
+
+    /// Remove `key`, if present.
+    fn remove(&mut self, key: &str) -> Result<(), NvsError>;
+}
+
+#[cfg(target_os = "espidf")]
+impl NvsBlobs for EspDefaultNvs {
+    fn blob_len(&self, key: &str) -> Result<Option<usize>, NvsError> {
+        Ok(EspDefaultNvs::blob_len(self, key)?)
+    }
+
+    fn get_blob<'a>(&self, key: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, NvsError> {
+        Ok(self.get_raw(key, buf)?)
+    }
+
+    fn set_blob(&mut self, key: &str, bytes: &[u8]) -> Result<(), NvsError> {
+        self.set_raw(key, bytes)?;
+        Ok(())
+    }
+
+    fn contains(&self, key: &str) -> Result<bool, NvsError> {
+        Ok(EspDefaultNvs::contains(self, key)?)
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), NvsError> {
+        EspDefaultNvs::remove(self, key)?;
+        Ok(())
+    }
+}
+
+/// Configuration storage in the ESP32's NVS flash, on an `EspDefaultNvs`
+/// in firmware.
+pub struct NvsConfigStorage<N> {
+    nvs: Mutex<N>,
+}
+
+impl<N: NvsBlobs> NvsConfigStorage<N> {
+    /// Wrap an open NVS namespace.
+    pub fn new(nvs: N) -> Self {
+        Self {
+            nvs: Mutex::new(nvs),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, N> {
+        lock_recovering(&self.nvs, "NVS")
+    }
+}
+
+/// The NVS key of a plugin's configuration (see the module docs).
+pub fn plugin_key(plugin_id: &str) -> String {
+    let key = format!("{PLUGIN_KEY_PREFIX}{plugin_id}");
+    if key.is_ascii() && key.len() <= NVS_KEY_MAX_LEN {
+        return key;
+    }
+    let prefix: String = plugin_id
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(PLUGIN_KEY_ID_CHARS)
+        .collect();
+    format!(
+        "{PLUGIN_KEY_PREFIX}{prefix}~{:08x}",
+        fnv1a(plugin_id.as_bytes())
+    )
+}
+
+/// 32-bit FNV-1a hash.
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn check_key(key: &str) -> Result<(), ConfigError> {
+    if key.is_empty() || key.len() > NVS_KEY_MAX_LEN {
+        return Err(ConfigError::InvalidData(format!(
+            "NVS key '{key}' must be 1-{NVS_KEY_MAX_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+impl<N: NvsBlobs + Send> ConfigStorage for NvsConfigStorage<N> {
+    fn load_settings(&self) -> Result<ServerSettings, ConfigError> {
+        self.load_value(SETTINGS_KEY)
+    }
+
+    fn save_settings(&self, settings: &ServerSettings) -> Result<(), ConfigError> {
+        self.save_value(SETTINGS_KEY, settings)
+    }
+
+    fn load_vessel(&self) -> Result<VesselInfo, ConfigError> {
+        self.load_value(VESSEL_KEY)
+    }
+
+    fn save_vessel(&self, vessel: &VesselInfo) -> Result<(), ConfigError> {
+        self.save_value(VESSEL_KEY, vessel)
+    }
+
+    fn load_security(&self) -> Result<SecurityConfig, ConfigError> {
+        self.load_value(SECURITY_KEY)
+    }
+
+    fn save_security(&self, config: &SecurityConfig) -> Result<(), ConfigError> {
+        self.save_value(SECURITY_KEY, config)
+    }
+
+    fn load_plugin_config(&self, plugin_id: &str) -> Result<serde_json::Value, ConfigError> {
+        self.load_value(&plugin_key(plugin_id))
+    }
+
+    fn save_plugin_config(
+        &self,
+        plugin_id: &str,
+        config: &serde_json::Value,
+    ) -> Result<(), ConfigError> {
+        self.save_value(&plugin_key(plugin_id), config)?;
+
+        let mut ids = self.list_plugin_configs()?;
+        if !ids.iter().any(|id| id == plugin_id) {
+            ids.push(plugin_id.to_string());
+            self.save_value(PLUGIN_INDEX_KEY, &ids)?;
+        }
+        Ok(())
+    }
+
+    fn list_plugin_configs(&self) -> Result<Vec<String>, ConfigError> {
+        match self.load_value(PLUGIN_INDEX_KEY) {
+            Err(ConfigError::NotFound(_)) => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    fn load_value<T: DeserializeOwned>(&self, key: &str) -> Result<T, ConfigError> {
+        check_key(key)?;
+        let nvs = self.lock();
+
+        let len = nvs
+            .blob_len(key)
+            .map_err(|e| config_error(key, false, e))?
+            .ok_or_else(|| ConfigError::NotFound(key.to_string()))?;
+
+        let mut buf = vec![0u8; len];
+        let bytes = nvs
+            .get_blob(key, &mut buf)
+            .map_err(|e| config_error(key, false, e))?
+            .ok_or_else(|| ConfigError::NotFound(key.to_string()))?;
+
+        serde_json::from_slice(bytes).map_err(|e| ConfigError::InvalidData(e.to_string()))
+    }
+
+    fn save_value<T: Serialize>(&self, key: &str, value: &T) -> Result<(), ConfigError> {
+        check_key(key)?;
+        let bytes =
+            serde_json::to_vec(value).map_err(|e| ConfigError::WriteError(e.to_string()))?;
+
+        self.lock()
+            .set_blob(key, &bytes)
+            .map_err(|e| config_error(key, true, e))
+    }
+
+    fn has_key(&self, key: &str) -> bool {
+        check_key(key).is_ok() && self.lock().contains(key).unwrap_or(false)
+    }
+
+    fn delete_key(&self, key: &str) -> Result<(), ConfigError> {
+        check_key(key)?;
+        self.lock()
+            .remove(key)
+            .map_err(|e| config_error(key, true, e))
+    }
+}
+
+#[cfg(all(test, not(target_os = "espidf")))]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// In-memory NVS namespace with a byte budget, like a small partition.
+    #[derive(Default)]
+    pub(crate) struct MemoryNvs {
+        blobs: HashMap<String, Vec<u8>>,
+        capacity: Option<usize>,
+    }
+
+    impl NvsBlobs for MemoryNvs {
+        fn blob_len(&self, key: &str) -> Result<Option<usize>, NvsError> {
+            Ok(self.blobs.get(key).map(Vec::len))
+        }
+
+        fn get_blob<'a>(&self, key: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, NvsError> {
+            Ok(self.blobs.get(key).map(|blob| {
+                buf[..blob.len()].copy_from_slice(blob);
+                &buf[..blob.len()]
+            }))
+        }
+
+        fn set_blob(&mut self, key: &str, bytes: &[u8]) -> Result<(), NvsError> {
+            let used: usize = self
+                .blobs
+                .iter()
+                .filter(|(k, _)| k.as_str() != key)
+                .map(|(_, blob)| blob.len())
+                .sum();
+            if self
+                .capacity
+                .is_some_and(|capacity| used + bytes.len() > capacity)
+            {
+                return Err(NvsError::NotEnoughSpace);
+            }
+            self.blobs.insert(key.to_string(), bytes.to_vec());
+            Ok(())
+        }
+
+        fn contains(&self, key: &str) -> Result<bool, NvsError> {
+            Ok(self.blobs.contains_key(key))
+        }
+
+        fn remove(&mut self, key: &str) -> Result<(), NvsError> {
+            self.blobs.remove(key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_plugin_key_short_id() {
+        assert_eq!(plugin_key("autopilot"), "p:autopilot");
+        assert_eq!(plugin_key("abcdefghijklm"), "p:abcdefghijklm");
+    }
+
+    #[test]
+    fn test_plugin_key_long_id() {
+        let key = plugin_key("signalk-derived-data");
+        assert_eq!(key.len(), NVS_KEY_MAX_LEN);
+        assert!(key.starts_with("p:sign~"));
+        // Stable, and distinct for ids sharing a prefix
+        assert_eq!(key, plugin_key("signalk-derived-data"));
+        assert_ne!(key, plugin_key("signalk-derived-date"));
+
+        let key = plugin_key("größe-anzeige");
+        assert!(key.is_ascii());
+        assert!(key.len() <= NVS_KEY_MAX_LEN);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let storage = NvsConfigStorage::new(MemoryNvs::default());
+        assert!(matches!(
+            storage.load_settings(),
+            Err(ConfigError::NotFound(_))
+        ));
+
+        let vessel = VesselInfo {
+            name: Some("Nightwatch".to_string()),
+            ..Default::default()
+        };
+        storage.save_vessel(&vessel).unwrap();
+        assert_eq!(
+            storage.load_vessel().unwrap().name.as_deref(),
+            Some("Nightwatch")
+        );
+        assert!(storage.has_key(VESSEL_KEY));
+
+        storage.delete_key(VESSEL_KEY).unwrap();
+        assert!(!storage.has_key(VESSEL_KEY));
+    }
+
+    #[test]
+    fn test_plugin_configs() {
+        let storage = NvsConfigStorage::new(MemoryNvs::default());
+        let long_id = "signalk-derived-data";
+
+        storage
+            .save_plugin_config(long_id, &serde_json::json!({"enabled": true}))
+            .unwrap();
+        storage
+            .save_plugin_config("anchor", &serde_json::json!({"radius": 30}))
+            .unwrap();
+        storage
+            .save_plugin_config(long_id, &serde_json::json!({"enabled": false}))
+            .unwrap();
+
+        assert_eq!(
+            storage.load_plugin_config(long_id).unwrap()["enabled"],
+            false
+        );
+        assert_eq!(storage.load_plugin_config("anchor").unwrap()["radius"], 30);
+        assert_eq!(storage.list_plugin_configs().unwrap(), [long_id, "anchor"]);
+    }
+
+    #[test]
+    fn test_errors_mapped() {
+        let storage = NvsConfigStorage::new(MemoryNvs {
+            capacity: Some(16),
+            ..Default::default()
+        });
+        let vessel = VesselInfo {
+            name: Some("A name longer than the partition".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            storage.save_vessel(&vessel),
+            Err(ConfigError::WriteError(_))
+        ));
+
+        assert!(matches!(
+            storage.load_value::<String>("a-key-too-long-for-nvs"),
+            Err(ConfigError::InvalidData(_))
+        ));
+        assert!(matches!(
+            config_error("k", false, NvsError::NotInitialized),
+            ConfigError::StorageUnavailable(_)
+        ));
+        assert!(matches!(
+            config_error("k", false, NvsError::Other("timeout".into())),
+            ConfigError::ReadError(_)
+        ));
+    }
+}
@@ -2,13 +2,17 @@
 //!
 //! Provides a simple interface for connecting to WiFi networks on ESP32.
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
     hal::peripheral,
+    ipv4::{self, ClientSettings, DHCPClientSettings, Mask, Subnet},
+    netif::{EspNetif, NetifConfiguration, NetifStack},
     wifi::{AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi},
 };
-use log::info;
+use log::{info, warn};
+
+use crate::config::{StaticIp, WifiConfig};
 
 /// Connect to a WiFi network.
 ///
@@ -16,12 +20,15 @@ use log::info;
 /// 1. Scans for available networks
 /// 2. Finds the target network and its channel
 /// 3. Connects with the provided credentials
-/// 4. Waits for DHCP lease
+/// 4. Waits for DHCP lease (or brings up the static address)
+///
+/// If `config.static_ip` is set and valid, the station netif is created with
+/// a fixed address instead of DHCP. Invalid static settings are logged and
+/// DHCP is used so the device stays reachable.
 ///
 /// # Arguments
 ///
-/// * `ssid` - Network name (cannot be empty)
-/// * `password` - Network password (empty for open networks)
+/// * `config` - Network credentials, optional DHCP hostname and static IP (SSID cannot be empty)
 /// * `modem` - ESP32 modem peripheral
 /// * `sysloop` - ESP system event loop
 ///
@@ -33,16 +40,19 @@ use log::info;
 /// # Example
 ///
 /// ```ignore
-/// let wifi = connect_wifi("MyNetwork", "password123", peripherals.modem, sysloop)?;
+/// let config = WifiConfig::new("MyNetwork", "password123");
+/// let wifi = connect_wifi(&config, peripherals.modem, sysloop)?;
 /// // WiFi is now connected
 /// // Keep `wifi` in scope to maintain connection
 /// ```
 pub fn connect_wifi(
-    ssid: &str,
-    password: &str,
+    config: &WifiConfig,
     modem: impl peripheral::Peripheral<P = esp_idf_svc::hal::modem::Modem> + 'static,
     sysloop: EspSystemEventLoop,
 ) -> Result<(Box<EspWifi<'static>>, String)> {
+    let ssid = config.ssid.as_str();
+    let password = config.password.as_str();
+
     if ssid.is_empty() {
         bail!("WiFi SSID cannot be empty");
     }
@@ -55,6 +65,31 @@ pub fn connect_wifi(
     };
 
     let mut esp_wifi = EspWifi::new(modem, sysloop.clone(), None)?;
+
+    // The DHCP hostname and static address are part of the netif
+    // configuration, so the default station netif has to be replaced before
+    // the driver is started.
+    let static_ip = config
+        .static_ip
+        .as_ref()
+        .and_then(|static_ip| match static_ip.parse() {
+            Ok(static_ip) => Some(static_ip),
+            Err(e) => {
+                warn!("Ignoring static IP configuration, using DHCP: {}", e);
+                None
+            }
+        });
+
+    if let Some(static_ip) = static_ip {
+        info!(
+            "Using static IP {}/{} (gateway {})",
+            static_ip.ip, static_ip.prefix_len, static_ip.gateway
+        );
+        esp_wifi.swap_netif_sta(sta_netif(fixed_ip_configuration(&static_ip))?)?;
+    } else if let Some(hostname) = &config.hostname {
+        info!("Using DHCP hostname '{}'", hostname);
+        esp_wifi.swap_netif_sta(sta_netif(dhcp_ip_configuration(hostname)?)?)?;
+    }
     let mut wifi = BlockingWifi::wrap(&mut esp_wifi, sysloop)?;
 
     // Initial configuration for scanning
@@ -90,7 +125,7 @@ pub fn connect_wifi(
     info!("Connecting to '{}'...", ssid);
     wifi.connect()?;
 
-    info!("Waiting for DHCP lease...");
+    info!("Waiting for network interface...");
     wifi.wait_netif_up()?;
 
     let ip_info = wifi.wifi().sta_netif().get_ip_info()?;
@@ -102,6 +137,43 @@ pub fn connect_wifi(
     Ok((Box::new(esp_wifi), ip_info.ip.to_string()))
 }
 
+/// DHCP client settings announcing `hostname` in its requests.
+fn dhcp_ip_configuration(hostname: &str) -> Result<ipv4::ClientConfiguration> {
+    let hostname = hostname
+        .try_into()
+        .map_err(|_| anyhow!("Hostname too long (max 30 chars)"))?;
+
+    Ok(ipv4::ClientConfiguration::DHCP(DHCPClientSettings {
+        hostname: Some(hostname),
+    }))
+}
+
+/// Fixed client settings; esp-idf stops its DHCP client for this netif and
+/// applies the address with `esp_netif_set_ip_info`.
+fn fixed_ip_configuration(static_ip: &StaticIp) -> ipv4::ClientConfiguration {
+    ipv4::ClientConfiguration::Fixed(ClientSettings {
+        ip: static_ip.ip,
+        subnet: Subnet {
+            gateway: static_ip.gateway,
+            mask: Mask(static_ip.prefix_len),
+        },
+        dns: static_ip.dns,
+        secondary_dns: None,
+    })
+}
+
+/// Build a station netif with the given IPv4 client configuration
+/// (`esp_netif_new` with the default WiFi station stack).
+fn sta_netif(ip_configuration: ipv4::ClientConfiguration) -> Result<EspNetif> {
+    let conf = NetifConfiguration {
+        ip_configuration: Some(ipv4::Configuration::Client(ip_configuration)),
+        stack: NetifStack::Sta,
+        ..NetifConfiguration::wifi_default_client()
+    };
+
+    Ok(EspNetif::new_with_conf(&conf)?)
+}
+
 /// WiFi connection status.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WifiStatus {
@@ -0,0 +1,103 @@
+// ServerAPI shim: runs one SignalK plugin inside Deno and talks to the Rust
+// host over stdin/stdout, one JSON message per line.
+//
+// Usage: deno run --no-prompt serverapi-shim.js <plugin module path> <plugin id>
+//
+// The plugin module's default export is called with the `app` object below
+// and returns the usual `{ id, name, start(options, restart), stop() }`.
+
+const [pluginPath, pluginId] = Deno.args;
+const encoder = new TextEncoder();
+
+// stdout carries the protocol, so plugin logging goes to stderr
+console.log = console.info = console.debug = (...args) => console.error(...args);
+
+function send(message) {
+  const bytes = encoder.encode(JSON.stringify(message) + "\n");
+  let written = 0;
+  while (written < bytes.length) {
+    written += Deno.stdout.writeSync(bytes.subarray(written));
+  }
+}
+
+let options = {};
+let started = false;
+
+const app = {
+  selfContext: "vessels.self",
+  emitDelta(delta) {
+    send({ type: "emitDelta", delta });
+  },
+  handleMessage(id, delta) {
+    send({ type: "handleMessage", id, delta });
+  },
+  setPluginStatus(message) {
+    send({ type: "setStatus", message: String(message) });
+  },
+  setPluginError(message) {
+    send({ type: "setError", message: String(message) });
+  },
+  debug(...args) {
+    send({ type: "debug", message: args.map(String).join(" ") });
+  },
+  error(...args) {
+    console.error(...args);
+  },
+  readPluginOptions() {
+    return options;
+  },
+};
+
+// An absolute path resolves to a file: URL
+const module = await import(pluginPath);
+const plugin = module.default(app);
+
+async function stopPlugin() {
+  if (started) {
+    started = false;
+    await plugin.stop?.();
+  }
+}
+
+async function handle(message) {
+  switch (message.type) {
+    case "config": {
+      await stopPlugin();
+      options = message.config ?? {};
+      const restart = (newOptions) => handle({ type: "config", config: newOptions });
+      await plugin.start?.(options, restart);
+      started = true;
+      break;
+    }
+    case "stop":
+      await stopPlugin();
+      Deno.exit(0);
+      break;
+    default:
+      console.error(`serverapi-shim: unknown message type ${message.type}`);
+  }
+}
+
+send({ type: "ready", id: plugin.id ?? pluginId });
+
+let buffered = "";
+for await (const chunk of Deno.stdin.readable.pipeThrough(new TextDecoderStream())) {
+  buffered += chunk;
+  let end;
+  while ((end = buffered.indexOf("\n")) >= 0) {
+    const line = buffered.slice(0, end).trim();
+    buffered = buffered.slice(end + 1);
+    if (!line) {
+      continue;
+    }
+    try {
+      await handle(JSON.parse(line));
+    } catch (e) {
+      app.setPluginError(e?.message ?? e);
+    }
+  }
+}
+
+// The host closed stdin
+await stopPlugin();
+Deno.exit(0);
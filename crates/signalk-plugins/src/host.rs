@@ -0,0 +1,354 @@
+//! Running a plugin in a Deno subprocess.
+//!
+//! [`PluginHost::spawn`] starts `deno run` on the bundled ServerAPI shim,
+//! which imports the plugin module and relays its calls as
+//! [`PluginMessage`]s on stdout. Deltas the plugin emits are forwarded to
+//! the server as [`ServerEvent::DeltaReceived`]; its status and error
+//! messages are kept for the Admin UI. The plugin's stderr is logged, and
+//! its last lines are kept so a plugin that fails to load can be diagnosed
+//! (see [`PluginHost::wait_ready`]).
+//!
+//! Deno runs with `--no-prompt` and may only read the plugin's directory
+//! (plus any extra paths and hosts in [`PluginHostConfig`]).
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::Value;
+use signalk_core::Delta;
+use signalk_server::ServerEvent;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::protocol::{HostMessage, PluginMessage};
+
+/// The ServerAPI shim run by Deno.
+const SHIM: &str = include_str!("../js/serverapi-shim.js");
+
+/// Number of stderr lines kept for [`PluginError::Exited`].
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Errors from running a plugin.
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("failed to start deno: {0}")]
+    Spawn(#[source] std::io::Error),
+
+    #[error("plugin I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("plugin message error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("plugin module not found: {0}")]
+    NotFound(PathBuf),
+
+    /// The subprocess exited before the plugin loaded.
+    #[error("plugin exited before it was ready: {stderr}")]
+    Exited { stderr: String },
+
+    #[error("plugin did not load within {0:?}")]
+    Timeout(Duration),
+
+    #[error("unknown plugin: {0}")]
+    UnknownPlugin(String),
+
+    #[error("plugin config storage error: {0}")]
+    Storage(String),
+}
+
+/// How to run a plugin.
+#[derive(Debug, Clone)]
+pub struct PluginHostConfig {
+    /// Plugin id, used as `$source` for deltas that don't name one.
+    pub id: String,
+    /// The plugin's JavaScript module.
+    pub module: PathBuf,
+    /// The Deno executable (default: `deno` on the `PATH`).
+    pub deno: PathBuf,
+    /// Extra paths the plugin may read, besides its own directory.
+    pub allow_read: Vec<PathBuf>,
+    /// Hosts the plugin may connect to; none by default.
+    pub allow_net: Vec<String>,
+}
+
+impl PluginHostConfig {
+    /// Run `module` as plugin `id` with default permissions.
+    pub fn new(id: impl Into<String>, module: impl Into<PathBuf>) -> Self {
+        Self {
+            id: id.into(),
+            module: module.into(),
+            deno: PathBuf::from("deno"),
+            allow_read: Vec::new(),
+            allow_net: Vec::new(),
+        }
+    }
+}
+
+/// Status and error most recently set by a plugin.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PluginStatus {
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+/// A plugin running in a Deno subprocess.
+pub struct PluginHost {
+    id: String,
+    child: Child,
+    stdin: ChildStdin,
+    status: Arc<Mutex<PluginStatus>>,
+    reader: JoinHandle<()>,
+    /// Resolved when the plugin module loaded; dropped if it never does.
+    ready: Option<oneshot::Receiver<()>>,
+    stderr: Arc<Mutex<VecDeque<String>>>,
+    stderr_reader: Option<JoinHandle<()>>,
+}
+
+impl PluginHost {
+    /// Start the plugin, forwarding its deltas to `events`.
+    ///
+    /// The plugin loads right away but only starts once it receives
+    /// [`send_config`](Self::send_config).
+    pub async fn spawn(
+        config: PluginHostConfig,
+        events: mpsc::Sender<ServerEvent>,
+    ) -> Result<Self, PluginError> {
+        let module = std::fs::canonicalize(&config.module)
+            .map_err(|_| PluginError::NotFound(config.module.clone()))?;
+        let shim = shim_path()?;
+
+        let mut allow_read = vec![module.parent().unwrap_or(&module).to_path_buf()];
+        allow_read.extend(config.allow_read.iter().cloned());
+        let mut command = Command::new(&config.deno);
+        command
+            .arg("run")
+            .arg("--no-prompt")
+            .arg("--no-config")
+            .arg(format!("--allow-read={}", join_paths(&allow_read)));
+        if !config.allow_net.is_empty() {
+            command.arg(format!("--allow-net={}", config.allow_net.join(",")));
+        }
+        let mut child = command
+            .arg(shim)
+            .arg(&module)
+            .arg(&config.id)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(PluginError::Spawn)?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+        let stderr_reader = child.stderr.take().map(|stderr| {
+            let id = config.id.clone();
+            let tail = stderr_tail.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    info!("[{}] {}", id, line);
+                    let mut tail = lock(&tail);
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            })
+        });
+
+        let status = Arc::new(Mutex::new(PluginStatus::default()));
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let reader = tokio::spawn(read_messages(
+            config.id.clone(),
+            stdout,
+            events,
+            status.clone(),
+            ready_tx,
+        ));
+        Ok(Self {
+            id: config.id,
+            child,
+            stdin,
+            status,
+            reader,
+            ready: Some(ready_rx),
+            stderr: stderr_tail,
+            stderr_reader,
+        })
+    }
+
+    /// The plugin id.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Start the plugin with `config` as its options, restarting it if it
+    /// is already running.
+    pub async fn send_config(&mut self, config: Value) -> Result<(), PluginError> {
+        self.send(&HostMessage::Config { config }).await
+    }
+
+    /// The status and error the plugin last set.
+    pub fn status(&self) -> PluginStatus {
+        lock(&self.status).clone()
+    }
+
+    /// Wait until the plugin module has loaded.
+    ///
+    /// If the subprocess exits first (a missing import, a syntax error,
+    /// a denied permission), the error carries its last lines of stderr.
+    /// Returns right away once the plugin was ready.
+    pub async fn wait_ready(&mut self, timeout: Duration) -> Result<(), PluginError> {
+        let Some(ready) = self.ready.take() else {
+            return Ok(());
+        };
+        match tokio::time::timeout(timeout, ready).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => {
+                // stdout closed, so stderr is about to; read what's left
+                if let Some(stderr_reader) = self.stderr_reader.take() {
+                    let _ = tokio::time::timeout(Duration::from_secs(1), stderr_reader).await;
+                }
+                Err(PluginError::Exited {
+                    stderr: self.stderr_tail(),
+                })
+            }
+            Err(_) => Err(PluginError::Timeout(timeout)),
+        }
+    }
+
+    /// The last lines the plugin wrote to stderr.
+    pub fn stderr_tail(&self) -> String {
+        lock(&self.stderr)
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Ask the plugin to stop and wait for the subprocess to exit.
+    pub async fn stop(mut self) -> Result<(), PluginError> {
+        // A plugin that already exited can't be told to stop
+        let _ = self.send(&HostMessage::Stop).await;
+        drop(self.stdin);
+        self.child.wait().await?;
+        let _ = self.reader.await;
+        Ok(())
+    }
+
+    async fn send(&mut self, message: &HostMessage) -> Result<(), PluginError> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.stdin.write_all(&line).await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+}
+
+/// Handle the plugin's messages until its stdout closes.
+async fn read_messages(
+    id: String,
+    stdout: ChildStdout,
+    events: mpsc::Sender<ServerEvent>,
+    status: Arc<Mutex<PluginStatus>>,
+    ready: oneshot::Sender<()>,
+) {
+    let mut ready = Some(ready);
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("[{}] Could not read plugin output: {}", id, e);
+                break;
+            }
+        };
+        let message = match serde_json::from_str::<PluginMessage>(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("[{}] Ignoring invalid plugin message: {} ({})", id, e, line);
+                continue;
+            }
+        };
+        let delta = match message {
+            PluginMessage::Ready { .. } => {
+                debug!("[{}] Plugin loaded", id);
+                if let Some(ready) = ready.take() {
+                    let _ = ready.send(());
+                }
+                continue;
+            }
+            PluginMessage::EmitDelta { delta } => with_source(delta, &id),
+            PluginMessage::HandleMessage { id: source, delta } => with_source(delta, &source),
+            PluginMessage::SetStatus { message } => {
+                info!("[{}] {}", id, message);
+                lock(&status).message = Some(message);
+                continue;
+            }
+            PluginMessage::SetError { message } => {
+                warn!("[{}] {}", id, message);
+                lock(&status).error = Some(message);
+                continue;
+            }
+            PluginMessage::Debug { message } => {
+                debug!("[{}] {}", id, message);
+                continue;
+            }
+        };
+        if events
+            .send(ServerEvent::DeltaReceived(delta))
+            .await
+            .is_err()
+        {
+            debug!("[{}] Server stopped, dropping plugin delta", id);
+            break;
+        }
+    }
+}
+
+/// Label updates that don't name a source with `source`.
+fn with_source(mut delta: Delta, source: &str) -> Delta {
+    for update in &mut delta.updates {
+        if update.source_ref.is_none() && update.source.is_none() {
+            update.source_ref = Some(source.to_string());
+        }
+    }
+    delta
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Deno's permission flags take comma-separated lists.
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Write the shim to a file Deno can run, once per process.
+fn shim_path() -> Result<PathBuf, PluginError> {
+    static SHIM_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+    let mut shim_path = SHIM_PATH.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(path) = &*shim_path {
+        return Ok(path.clone());
+    }
+    let path =
+        std::env::temp_dir().join(format!("signalk-serverapi-shim-{}.js", std::process::id()));
+    std::fs::write(&path, SHIM)?;
+    Ok(shim_path.insert(path).clone())
+}
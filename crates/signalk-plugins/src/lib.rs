@@ -5,6 +5,22 @@
 //! This crate provides compatibility with existing SignalK Node.js plugins
 //! by running them in a Deno subprocess with a ServerAPI shim.
 //!
+//! - [`PluginHost`] spawns Deno on the bundled `serverapi-shim.js` and
+//!   relays the plugin's deltas to the server
+//! - [`PluginManager`] discovers installed plugins, stores their settings
+//!   and starts, restarts and stops them
+//! - [`PluginMessage`] and [`HostMessage`] define the line-delimited JSON
+//!   protocol on the subprocess's stdin/stdout
+//!
 //! **Linux only** - not available on ESP32.
 
-// TODO: Plugin bridge implementation
+pub mod host;
+pub mod manager;
+pub mod protocol;
+
+pub use host::{PluginError, PluginHost, PluginHostConfig, PluginStatus};
+pub use manager::{
+    discover_plugins, BoxFuture, DenoLauncher, PluginConfigStore, PluginEntry, PluginInfo,
+    PluginLauncher, PluginManager, PluginSettings, RunningPlugin,
+};
+pub use protocol::{HostMessage, PluginMessage};
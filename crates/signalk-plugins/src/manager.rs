@@ -0,0 +1,414 @@
+//! The set of installed plugins and their lifecycle.
+//!
+//! [`PluginManager`] knows the installed plugins ([`PluginInfo`], found by
+//! [`discover_plugins`]), keeps each one's [`PluginSettings`] in a
+//! [`ConfigStorage`], and runs the enabled ones. Saving settings restarts
+//! a running plugin so it picks up its new configuration.
+//!
+//! Starting a plugin goes through a [`PluginLauncher`];
+//! [`DenoLauncher`] runs it in a [`PluginHost`] and waits for the module
+//! to load, so a plugin that crashes on startup is reported right away
+//! with its stderr. Tests substitute their own launcher.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use signalk_core::{ConfigError, ConfigStorage};
+use signalk_server::ServerEvent;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{info, warn};
+
+use crate::host::{PluginError, PluginHost, PluginHostConfig, PluginStatus};
+
+/// How long [`DenoLauncher`] waits for a plugin module to load.
+pub const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A boxed future, for the object-safe plugin traits.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An installed plugin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginInfo {
+    /// Plugin identifier (npm package name).
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    /// The plugin's JavaScript module.
+    pub module: PathBuf,
+    /// JSON schema of the plugin's configuration, if it ships one.
+    pub schema: Option<Value>,
+}
+
+/// Find the plugins installed in `dir`.
+///
+/// Each subdirectory with a `package.json` is a plugin: its `name`,
+/// `version`, `description` and `main` module (default `index.js`)
+/// describe it, and an optional `schema.json` next to it holds its
+/// configuration schema. Unreadable packages are skipped with a warning.
+pub fn discover_plugins(dir: &Path) -> Vec<PluginInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut plugins: Vec<PluginInfo> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join("package.json").is_file())
+        .filter_map(|path| match read_plugin(&path) {
+            Ok(plugin) => Some(plugin),
+            Err(e) => {
+                warn!("Skipping plugin {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    plugins.sort_by(|a, b| a.id.cmp(&b.id));
+    plugins
+}
+
+fn read_plugin(dir: &Path) -> Result<PluginInfo, PluginError> {
+    let package: Value = serde_json::from_slice(&std::fs::read(dir.join("package.json"))?)?;
+    let field = |name: &str| {
+        package
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let id = field("name").ok_or_else(|| PluginError::NotFound(dir.join("package.json")))?;
+    let schema = match std::fs::read(dir.join("schema.json")) {
+        Ok(schema) => Some(serde_json::from_slice(&schema)?),
+        Err(_) => None,
+    };
+    Ok(PluginInfo {
+        name: id.clone(),
+        id,
+        version: field("version").unwrap_or_default(),
+        description: field("description"),
+        module: dir.join(field("main").as_deref().unwrap_or("index.js")),
+        schema,
+    })
+}
+
+/// A plugin's stored settings, as in `plugin-config-data/<id>.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Options passed to the plugin's `start()`.
+    #[serde(default)]
+    pub configuration: Value,
+}
+
+/// A started plugin.
+pub trait RunningPlugin: Send {
+    /// Restart the plugin with new options.
+    fn send_config(&mut self, config: Value) -> BoxFuture<'_, Result<(), PluginError>>;
+
+    /// The status and error the plugin last set.
+    fn status(&self) -> PluginStatus;
+
+    /// Stop the plugin.
+    fn stop(self: Box<Self>) -> BoxFuture<'static, Result<(), PluginError>>;
+}
+
+impl RunningPlugin for PluginHost {
+    fn send_config(&mut self, config: Value) -> BoxFuture<'_, Result<(), PluginError>> {
+        Box::pin(PluginHost::send_config(self, config))
+    }
+
+    fn status(&self) -> PluginStatus {
+        PluginHost::status(self)
+    }
+
+    fn stop(self: Box<Self>) -> BoxFuture<'static, Result<(), PluginError>> {
+        Box::pin(PluginHost::stop(*self))
+    }
+}
+
+/// Starts plugins.
+pub trait PluginLauncher: Send + Sync {
+    /// Start `plugin` with `config` as its options.
+    ///
+    /// Fails if the plugin can't be started, including when it exits
+    /// while loading.
+    fn launch<'a>(
+        &'a self,
+        plugin: &'a PluginInfo,
+        config: Value,
+    ) -> BoxFuture<'a, Result<Box<dyn RunningPlugin>, PluginError>>;
+}
+
+/// Launches plugins in Deno subprocesses.
+#[derive(Debug, Clone)]
+pub struct DenoLauncher {
+    /// The Deno executable (default: `deno` on the `PATH`).
+    pub deno: PathBuf,
+    /// How long a plugin module may take to load.
+    pub ready_timeout: Duration,
+    events: mpsc::Sender<ServerEvent>,
+}
+
+impl DenoLauncher {
+    /// Launch plugins that send their deltas to `events`.
+    pub fn new(events: mpsc::Sender<ServerEvent>) -> Self {
+        Self {
+            deno: PathBuf::from("deno"),
+            ready_timeout: DEFAULT_READY_TIMEOUT,
+            events,
+        }
+    }
+}
+
+impl PluginLauncher for DenoLauncher {
+    fn launch<'a>(
+        &'a self,
+        plugin: &'a PluginInfo,
+        config: Value,
+    ) -> BoxFuture<'a, Result<Box<dyn RunningPlugin>, PluginError>> {
+        Box::pin(async move {
+            let mut host_config = PluginHostConfig::new(&plugin.id, &plugin.module);
+            host_config.deno = self.deno.clone();
+            let mut host = PluginHost::spawn(host_config, self.events.clone()).await?;
+            host.wait_ready(self.ready_timeout).await?;
+            host.send_config(config).await?;
+            Ok(Box::new(host) as Box<dyn RunningPlugin>)
+        })
+    }
+}
+
+/// Plugin settings storage.
+///
+/// [`ConfigStorage`] isn't object safe; this is the part of it the
+/// manager needs.
+pub trait PluginConfigStore: Send + Sync {
+    fn load_plugin_config(&self, plugin_id: &str) -> Result<Value, ConfigError>;
+    fn save_plugin_config(&self, plugin_id: &str, config: &Value) -> Result<(), ConfigError>;
+}
+
+impl<S: ConfigStorage + Send + Sync> PluginConfigStore for S {
+    fn load_plugin_config(&self, plugin_id: &str) -> Result<Value, ConfigError> {
+        ConfigStorage::load_plugin_config(self, plugin_id)
+    }
+
+    fn save_plugin_config(&self, plugin_id: &str, config: &Value) -> Result<(), ConfigError> {
+        ConfigStorage::save_plugin_config(self, plugin_id, config)
+    }
+}
+
+/// A plugin as listed by [`PluginManager::list`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginEntry {
+    pub info: PluginInfo,
+    pub settings: PluginSettings,
+    /// The status of the running plugin; `None` when it isn't running.
+    pub status: Option<PluginStatus>,
+}
+
+/// Runs the enabled plugins and applies settings changes.
+pub struct PluginManager {
+    plugins: Vec<PluginInfo>,
+    launcher: Arc<dyn PluginLauncher>,
+    storage: Option<Arc<dyn PluginConfigStore>>,
+    /// Running plugins by id. Held across starts and stops, so lifecycle
+    /// changes happen one at a time.
+    running: Mutex<HashMap<String, Box<dyn RunningPlugin>>>,
+}
+
+impl PluginManager {
+    /// Manage `plugins`, starting them with `launcher`.
+    ///
+    /// Without storage, settings are not persisted and every plugin
+    /// starts disabled.
+    pub fn new(
+        plugins: Vec<PluginInfo>,
+        launcher: Arc<dyn PluginLauncher>,
+        storage: Option<Arc<dyn PluginConfigStore>>,
+    ) -> Self {
+        Self {
+            plugins,
+            launcher,
+            storage,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The installed plugins with their settings and status.
+    pub async fn list(&self) -> Vec<PluginEntry> {
+        let running = self.running.lock().await;
+        self.plugins
+            .iter()
+            .map(|info| PluginEntry {
+                info: info.clone(),
+                settings: self.load_settings(&info.id),
+                status: running.get(&info.id).map(|plugin| plugin.status()),
+            })
+            .collect()
+    }
+
+    /// Store `settings` for plugin `id` and start, restart or stop it
+    /// to match.
+    ///
+    /// The settings are kept even if the plugin then fails to start.
+    pub async fn save_settings(
+        &self,
+        id: &str,
+        settings: PluginSettings,
+    ) -> Result<(), PluginError> {
+        let info = self.plugin(id)?;
+        if let Some(storage) = &self.storage {
+            let value = serde_json::to_value(&settings)?;
+            storage
+                .save_plugin_config(id, &value)
+                .map_err(|e| PluginError::Storage(e.to_string()))?;
+        }
+        self.apply(info, settings).await
+    }
+
+    /// Enable or disable plugin `id`, keeping its configuration.
+    pub async fn set_enabled(&self, id: &str, enabled: bool) -> Result<(), PluginError> {
+        self.plugin(id)?;
+        let settings = PluginSettings {
+            enabled,
+            ..self.load_settings(id)
+        };
+        self.save_settings(id, settings).await
+    }
+
+    /// Start every enabled plugin, logging those that fail.
+    pub async fn start_enabled(&self) {
+        for info in &self.plugins {
+            let settings = self.load_settings(&info.id);
+            if !settings.enabled {
+                continue;
+            }
+            if let Err(e) = self.apply(info, settings).await {
+                warn!("Plugin {} failed to start: {}", info.id, e);
+            }
+        }
+    }
+
+    /// Stop every running plugin.
+    pub async fn stop_all(&self) {
+        let running: Vec<_> = self.running.lock().await.drain().collect();
+        for (id, plugin) in running {
+            if let Err(e) = plugin.stop().await {
+                warn!("Plugin {} did not stop cleanly: {}", id, e);
+            }
+        }
+    }
+
+    fn plugin(&self, id: &str) -> Result<&PluginInfo, PluginError> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.id == id)
+            .ok_or_else(|| PluginError::UnknownPlugin(id.to_string()))
+    }
+
+    fn load_settings(&self, id: &str) -> PluginSettings {
+        let Some(storage) = &self.storage else {
+            return PluginSettings::default();
+        };
+        match storage.load_plugin_config(id) {
+            Ok(value) => serde_json::from_value(value).unwrap_or_else(|e| {
+                warn!("Ignoring invalid settings of plugin {}: {}", id, e);
+                PluginSettings::default()
+            }),
+            Err(ConfigError::NotFound(_)) => PluginSettings::default(),
+            Err(e) => {
+                warn!("Could not load settings of plugin {}: {}", id, e);
+                PluginSettings::default()
+            }
+        }
+    }
+
+    /// Stop the plugin if it runs, then start it if it is enabled.
+    async fn apply(&self, info: &PluginInfo, settings: PluginSettings) -> Result<(), PluginError> {
+        let mut running = self.running.lock().await;
+        if let Some(plugin) = running.remove(&info.id) {
+            info!("Stopping plugin {}", info.id);
+            if let Err(e) = plugin.stop().await {
+                warn!("Plugin {} did not stop cleanly: {}", info.id, e);
+            }
+        }
+        if settings.enabled {
+            info!("Starting plugin {}", info.id);
+            let plugin = self.launcher.launch(info, settings.configuration).await?;
+            running.insert(info.id.clone(), plugin);
+        }
+        Ok(())
+    }
+}
+
+impl Default for PluginManager {
+    /// A manager without plugins.
+    fn default() -> Self {
+        Self::new(Vec::new(), Arc::new(NoLauncher), None)
+    }
+}
+
+/// Launcher of a manager without plugins; never called.
+struct NoLauncher;
+
+impl PluginLauncher for NoLauncher {
+    fn launch<'a>(
+        &'a self,
+        plugin: &'a PluginInfo,
+        _config: Value,
+    ) -> BoxFuture<'a, Result<Box<dyn RunningPlugin>, PluginError>> {
+        Box::pin(async move { Err(PluginError::UnknownPlugin(plugin.id.clone())) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use signalk_core::MemoryConfigStorage;
+
+    #[test]
+    fn test_discover_plugins() {
+        let dir = std::env::temp_dir().join(format!("signalk-discover-{}", std::process::id()));
+        let plugin = dir.join("signalk-anchor-alarm");
+        std::fs::create_dir_all(&plugin).unwrap();
+        std::fs::create_dir_all(dir.join("not-a-plugin")).unwrap();
+        std::fs::write(
+            plugin.join("package.json"),
+            r#"{"name":"signalk-anchor-alarm","version":"1.0.0","main":"plugin.js"}"#,
+        )
+        .unwrap();
+        std::fs::write(plugin.join("schema.json"), r#"{"type":"object"}"#).unwrap();
+
+        let plugins = discover_plugins(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(plugins.len(), 1);
+        assert_eq!(plugins[0].id, "signalk-anchor-alarm");
+        assert_eq!(plugins[0].version, "1.0.0");
+        assert_eq!(plugins[0].module, plugin.join("plugin.js"));
+        assert_eq!(plugins[0].schema, Some(json!({ "type": "object" })));
+    }
+
+    #[test]
+    fn test_settings_format() {
+        // The format the TypeScript server stores
+        let settings: PluginSettings =
+            serde_json::from_value(json!({ "enabled": true, "configuration": { "a": 1 } }))
+                .unwrap();
+        assert!(settings.enabled);
+        assert_eq!(settings.configuration, json!({ "a": 1 }));
+        assert_eq!(
+            serde_json::from_value::<PluginSettings>(json!({})).unwrap(),
+            PluginSettings::default()
+        );
+        let storage: Arc<dyn PluginConfigStore> = Arc::new(MemoryConfigStorage::default());
+        assert!(matches!(
+            storage.load_plugin_config("missing"),
+            Err(ConfigError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+//! Messages exchanged with the Deno subprocess.
+//!
+//! Both directions use line-delimited JSON: one object per line, tagged by
+//! its `type` field. The plugin side is implemented by
+//! `js/serverapi-shim.js`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use signalk_core::Delta;
+
+/// A message from a plugin to the host.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PluginMessage {
+    /// The plugin module loaded and is waiting for its configuration.
+    Ready { id: String },
+    /// A delta from the plugin itself (`app.emitDelta(delta)`).
+    EmitDelta { delta: Delta },
+    /// A delta on behalf of a provider id (`app.handleMessage(id, delta)`).
+    HandleMessage { id: String, delta: Delta },
+    /// Status shown in the Admin UI (`app.setPluginStatus(msg)`).
+    SetStatus { message: String },
+    /// Error shown in the Admin UI (`app.setPluginError(msg)`).
+    SetError { message: String },
+    /// Debug output (`app.debug(msg)`).
+    Debug { message: String },
+}
+
+/// A message from the host to a plugin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HostMessage {
+    /// Start the plugin with these settings, restarting it if it runs.
+    Config { config: Value },
+    /// Stop the plugin and exit.
+    Stop,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_plugin_message_wire_format() {
+        let message: PluginMessage = serde_json::from_value(json!({
+            "type": "handleMessage",
+            "id": "my-plugin",
+            "delta": {
+                "updates": [{
+                    "values": [{ "path": "navigation.speedOverGround", "value": 3.5 }]
+                }]
+            }
+        }))
+        .unwrap();
+        let PluginMessage::HandleMessage { id, delta } = message else {
+            panic!("unexpected {message:?}");
+        };
+        assert_eq!(id, "my-plugin");
+        assert_eq!(delta.updates[0].values[0].value, json!(3.5));
+
+        assert_eq!(
+            serde_json::from_str::<PluginMessage>(r#"{"type":"setStatus","message":"Running"}"#)
+                .unwrap(),
+            PluginMessage::SetStatus {
+                message: "Running".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(HostMessage::Config {
+                config: json!({ "interval": 5 })
+            })
+            .unwrap(),
+            json!({ "type": "config", "config": { "interval": 5 } })
+        );
+        assert_eq!(
+            serde_json::to_value(HostMessage::Stop).unwrap(),
+            json!({ "type": "stop" })
+        );
+    }
+}
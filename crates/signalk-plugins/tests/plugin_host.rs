@@ -0,0 +1,89 @@
+//! Tests for running a plugin in Deno.
+//!
+//! Skipped when `deno` is not on the `PATH`.
+
+use std::time::Duration;
+
+use serde_json::json;
+use signalk_plugins::{PluginError, PluginHost, PluginHostConfig};
+use signalk_server::ServerEvent;
+use tokio::sync::mpsc;
+
+const PLUGIN: &str = r#"
+export default (app) => ({
+  id: "test-plugin",
+  start(options) {
+    console.log("starting");
+    app.setPluginStatus("Running");
+    app.handleMessage("test-plugin", {
+      updates: [{ values: [{ path: "navigation.speedOverGround", value: options.sog }] }],
+    });
+  },
+  stop() {},
+});
+"#;
+
+fn deno_available() -> bool {
+    std::process::Command::new("deno")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[tokio::test]
+async fn test_plugin_delta_arrives() {
+    if !deno_available() {
+        eprintln!("deno not found, skipping");
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("signalk-plugin-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("index.js");
+    std::fs::write(&module, PLUGIN).unwrap();
+
+    let (tx, mut rx) = mpsc::channel(16);
+    let mut host = PluginHost::spawn(PluginHostConfig::new("test-plugin", &module), tx)
+        .await
+        .unwrap();
+    host.wait_ready(Duration::from_secs(30)).await.unwrap();
+    host.send_config(json!({ "sog": 3.5 })).await.unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(30), rx.recv())
+        .await
+        .expect("Timed out waiting for the plugin's delta")
+        .unwrap();
+    let ServerEvent::DeltaReceived(delta) = event else {
+        panic!("unexpected event {event:?}");
+    };
+    let update = &delta.updates[0];
+    assert_eq!(update.source_ref.as_deref(), Some("test-plugin"));
+    assert_eq!(update.values[0].path, "navigation.speedOverGround");
+    assert_eq!(update.values[0].value, json!(3.5));
+    assert_eq!(host.status().message.as_deref(), Some("Running"));
+
+    host.stop().await.unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_crash_on_load_reports_stderr() {
+    if !deno_available() {
+        eprintln!("deno not found, skipping");
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("signalk-plugin-crash-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("index.js");
+    std::fs::write(&module, "throw new Error('missing serial port');\n").unwrap();
+
+    let (tx, _rx) = mpsc::channel(16);
+    let mut host = PluginHost::spawn(PluginHostConfig::new("crash-plugin", &module), tx)
+        .await
+        .unwrap();
+    let error = host.wait_ready(Duration::from_secs(30)).await.unwrap_err();
+    let PluginError::Exited { stderr } = error else {
+        panic!("unexpected {error:?}");
+    };
+    assert!(stderr.contains("missing serial port"), "{stderr}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,26 @@
+//! Feed arbitrary input to the `ClientMessage` parser.
+//!
+//! Run with `cargo +nightly fuzz run client_message` from
+//! `crates/signalk-protocol`. Parsing must never panic, and anything that
+//! parses must survive a serialize/parse round trip as the same variant.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use signalk_protocol::ClientMessage;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(message) = serde_json::from_str::<ClientMessage>(text) else {
+        return;
+    };
+
+    let json = serde_json::to_string(&message).expect("parsed messages serialize");
+    let reparsed: ClientMessage = serde_json::from_str(&json).expect("serialized messages parse");
+    assert_eq!(
+        std::mem::discriminant(&message),
+        std::mem::discriminant(&reparsed)
+    );
+});
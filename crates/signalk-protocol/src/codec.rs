@@ -3,9 +3,14 @@
 //! SignalK uses JSON messages over WebSocket text frames. This module provides
 //! encoding and decoding utilities for the protocol messages.
 
-use crate::messages::{ClientMessage, ServerMessage};
+use crate::messages::{ClientMessage, DeltaBatch, ServerMessage};
+use serde::Deserialize;
+use serde_json::Value;
 use thiserror::Error;
 
+/// Largest client message accepted by [`decode_client_message`], in bytes.
+pub const MAX_CLIENT_MESSAGE_LEN: usize = 1024 * 1024;
+
 /// Errors that can occur during message encoding/decoding.
 #[derive(Debug, Error)]
 pub enum CodecError {
@@ -20,6 +25,26 @@ pub enum CodecError {
     /// Message type could not be determined.
     #[error("Unknown message type")]
     UnknownMessage,
+
+    /// The message is longer than [`MAX_CLIENT_MESSAGE_LEN`].
+    #[error("Message of {0} bytes exceeds the limit of {MAX_CLIENT_MESSAGE_LEN} bytes")]
+    MessageTooLarge(usize),
+
+    /// The message isn't JSON at all.
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(#[source] serde_json::Error),
+
+    /// The message has the keys of more than one message type.
+    #[error("Ambiguous message with {}", .0.join(", "))]
+    AmbiguousMessage(Vec<&'static str>),
+
+    /// The message type is known but its fields don't fit it.
+    #[error("Invalid {kind} message: {source}")]
+    InvalidMessage {
+        kind: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
 }
 
 /// Encode a server message to JSON string for WebSocket transmission.
@@ -28,8 +53,93 @@ pub fn encode_server_message(msg: &ServerMessage) -> Result<String, CodecError>
 }
 
 /// Decode a client message from JSON string received over WebSocket.
+///
+/// The message type is told apart by its request key (see
+/// [`ClientMessage::REQUEST_KEYS`]), so a malformed subscribe is reported
+/// as [`CodecError::InvalidMessage`] of kind `subscribe` rather than as an
+/// unknown message.
 pub fn decode_client_message(text: &str) -> Result<ClientMessage, CodecError> {
-    serde_json::from_str(text).map_err(CodecError::from)
+    client_message_from_value(parse_message(text)?)
+}
+
+/// Decode one delta or an array of deltas received over WebSocket.
+pub fn decode_delta_batch(text: &str) -> Result<DeltaBatch, CodecError> {
+    delta_batch_from_value(parse_message(text)?)
+}
+
+/// A message received from a client: deltas to apply, or a request.
+#[derive(Debug, Clone)]
+pub enum InboundMessage {
+    Deltas(DeltaBatch),
+    Request(ClientMessage),
+}
+
+/// Decode any message a client may send, parsing it only once.
+///
+/// The message is told apart by its top-level shape (see
+/// [`is_delta_value`]), so a PUT of the string `"updates"` is still a PUT.
+pub fn decode_inbound_message(text: &str) -> Result<InboundMessage, CodecError> {
+    let value = parse_message(text)?;
+    if is_delta_value(&value) {
+        delta_batch_from_value(value).map(InboundMessage::Deltas)
+    } else {
+        client_message_from_value(value).map(InboundMessage::Request)
+    }
+}
+
+/// Parse a client message, refusing it if it is too large.
+fn parse_message(text: &str) -> Result<Value, CodecError> {
+    if text.len() > MAX_CLIENT_MESSAGE_LEN {
+        return Err(CodecError::MessageTooLarge(text.len()));
+    }
+    serde_json::from_str(text).map_err(CodecError::InvalidJson)
+}
+
+fn client_message_from_value(value: Value) -> Result<ClientMessage, CodecError> {
+    let Some(object) = value.as_object() else {
+        return Err(CodecError::UnknownMessage);
+    };
+
+    let keys: Vec<&'static str> = ClientMessage::REQUEST_KEYS
+        .iter()
+        .copied()
+        .filter(|key| object.contains_key(*key))
+        .collect();
+    match keys.as_slice() {
+        [] => Err(CodecError::UnknownMessage),
+        [kind] => ClientMessage::deserialize(value)
+            .map_err(|source| CodecError::InvalidMessage { kind, source }),
+        _ => Err(CodecError::AmbiguousMessage(keys)),
+    }
+}
+
+fn delta_batch_from_value(value: Value) -> Result<DeltaBatch, CodecError> {
+    DeltaBatch::deserialize(value).map_err(|source| CodecError::InvalidMessage {
+        kind: "delta",
+        source,
+    })
+}
+
+/// Check if a parsed message is a delta or a batch of deltas: an array, or
+/// an object with a top-level `updates` key and no request key (see
+/// [`ClientMessage::REQUEST_KEYS`]).
+pub fn is_delta_value(value: &Value) -> bool {
+    match value {
+        Value::Array(_) => true,
+        Value::Object(object) => {
+            object.contains_key("updates")
+                && !ClientMessage::REQUEST_KEYS
+                    .iter()
+                    .any(|key| object.contains_key(*key))
+        }
+        _ => false,
+    }
+}
+
+/// Check if a JSON message is a delta or a batch of deltas, as
+/// [`is_delta_value`] does.
+pub fn is_delta_message(text: &str) -> bool {
+    serde_json::from_str(text).is_ok_and(|value: Value| is_delta_value(&value))
 }
 
 /// Check if a JSON message appears to be a subscribe request.
@@ -44,6 +154,11 @@ pub fn is_unsubscribe_message(text: &str) -> bool {
     text.contains("\"unsubscribe\"")
 }
 
+/// Check if a JSON message appears to be a GET request.
+pub fn is_get_message(text: &str) -> bool {
+    text.contains("\"get\"")
+}
+
 /// Check if a JSON message appears to be a PUT request.
 pub fn is_put_message(text: &str) -> bool {
     text.contains("\"put\"") && text.contains("\"requestId\"")
@@ -78,6 +193,7 @@ mod tests {
                     value: serde_json::json!(3.5),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
         let msg = ServerMessage::Delta(delta);
@@ -114,12 +230,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_invalid_json() {
+        for text in ["", "{\"subscribe\":", "not json"] {
+            assert!(
+                matches!(decode_client_message(text), Err(CodecError::InvalidJson(_))),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_unknown_message() {
+        for text in ["{}", r#"{"context":"vessels.self"}"#, "[]", "42"] {
+            assert!(
+                matches!(decode_client_message(text), Err(CodecError::UnknownMessage)),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_ambiguous_message() {
+        let json = r#"{"requestId":"1","subscribe":[{"path":"*"}],"put":{"path":"a","value":1}}"#;
+        match decode_client_message(json) {
+            Err(CodecError::AmbiguousMessage(keys)) => assert_eq!(keys, ["subscribe", "put"]),
+            other => panic!("Expected AmbiguousMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid_message() {
+        let err =
+            decode_client_message(r#"{"context":"vessels.self","subscribe":"nav"}"#).unwrap_err();
+        assert!(matches!(
+            err,
+            CodecError::InvalidMessage {
+                kind: "subscribe",
+                ..
+            }
+        ));
+
+        // A PUT without its requestId
+        let err = decode_client_message(r#"{"put":{"path":"a","value":1}}"#).unwrap_err();
+        assert!(matches!(
+            err,
+            CodecError::InvalidMessage { kind: "put", .. }
+        ));
+        assert!(err.to_string().starts_with("Invalid put message: "));
+    }
+
+    #[test]
+    fn test_decode_delta_batch() {
+        let json =
+            r#"[{"context":"vessels.self","updates":[]},{"context":"atons.x","updates":[]}]"#;
+        assert_eq!(decode_delta_batch(json).unwrap().0.len(), 2);
+        assert_eq!(decode_delta_batch(r#"{"updates":[]}"#).unwrap().0.len(), 1);
+        assert!(matches!(
+            decode_delta_batch(r#"[{"updates":3}]"#),
+            Err(CodecError::InvalidMessage { kind: "delta", .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_too_large() {
+        let text = format!(
+            r#"{{"context":"{}","subscribe":[]}}"#,
+            "x".repeat(MAX_CLIENT_MESSAGE_LEN)
+        );
+        assert!(matches!(
+            decode_client_message(&text),
+            Err(CodecError::MessageTooLarge(len)) if len == text.len()
+        ));
+    }
+
     #[test]
     fn test_message_type_detection() {
         assert!(is_subscribe_message(r#"{"subscribe":[...]}"#));
         assert!(is_unsubscribe_message(r#"{"unsubscribe":[...]}"#));
         assert!(is_put_message(r#"{"requestId":"1","put":{...}}"#));
+        assert!(is_get_message(r#"{"requestId":"1","get":{...}}"#));
 
         assert!(!is_subscribe_message(r#"{"put":{...}}"#));
+        assert!(!is_delta_message(r#"{"subscribe":[...]}"#));
+    }
+
+    #[test]
+    fn test_delta_detection_by_shape() {
+        assert!(is_delta_message(
+            r#"{"context":"vessels.self","updates":[]}"#
+        ));
+        assert!(is_delta_message(r#" [{"updates":[]}]"#));
+
+        // "updates" only as a value or nested key doesn't make a delta
+        let put = r#"{"requestId":"1","put":{"path":"notifications.mob","value":"updates"}}"#;
+        assert!(!is_delta_message(put));
+        let subscribe = r#"{"context":"vessels.self","subscribe":[{"path":"updates"}]}"#;
+        assert!(!is_delta_message(subscribe));
+        let both = r#"{"requestId":"1","updates":[],"put":{"path":"a","value":1}}"#;
+        assert!(!is_delta_message(both));
+        assert!(!is_delta_message(r#""updates""#));
+        assert!(!is_delta_message(r#"{"updates""#));
+
+        match decode_inbound_message(put).unwrap() {
+            InboundMessage::Request(ClientMessage::Put(req)) => {
+                assert_eq!(req.put.value, "updates");
+            }
+            other => panic!("expected a PUT, got {other:?}"),
+        }
+        assert!(matches!(
+            decode_inbound_message(subscribe).unwrap(),
+            InboundMessage::Request(ClientMessage::Subscribe(_))
+        ));
+        match decode_inbound_message(r#"[{"updates":[]},{"updates":[]}]"#).unwrap() {
+            InboundMessage::Deltas(batch) => assert_eq!(batch.0.len(), 2),
+            other => panic!("expected deltas, got {other:?}"),
+        }
     }
 }
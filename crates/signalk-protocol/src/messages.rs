@@ -1,19 +1,83 @@
 //! Protocol message types for WebSocket communication.
 //!
 //! This module defines all message types exchanged over the SignalK WebSocket protocol:
-//! - Server → Client: Hello, Delta, PutResponse
-//! - Client → Server: Subscribe, Unsubscribe, Put
+//! - Server → Client: Hello, Delta, GetResponse, PutResponse, SubscribeResponse
+//! - Client → Server: Subscribe, Unsubscribe, Get, Put, SetMode, and deltas
+//!   (single or batched in a [`DeltaBatch`])
+//!
+//! GET and PUT requests carry a `requestId` that is echoed in the response,
+//! so a client can correlate several requests in flight on one socket.
 //!
 //! Messages are serialized as JSON over WebSocket text frames.
 
-use serde::{Deserialize, Serialize};
-use signalk_core::Delta;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use signalk_core::{Delta, PutResult};
 
 /// Subscription request message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubscribeRequest {
     pub context: String,
     pub subscribe: Vec<Subscription>,
+    /// Send the current values of the newly subscribed paths right away;
+    /// the server's default applies when absent.
+    #[serde(
+        rename = "sendCachedValues",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub send_cached_values: Option<bool>,
+    /// Asks for a [`SubscribeResponse`] even when there is nothing to
+    /// report, and is echoed in it.
+    #[serde(rename = "requestId", default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Response to a subscribe request.
+///
+/// Sent when the request is rejected (e.g. for an invalid path pattern,
+/// in which case none of its subscriptions are added), when it was
+/// accepted with warnings, or when it carries a `requestId`:
+///
+/// ```json
+/// { "requestId": "7", "state": "FAILED", "statusCode": 400, "message": "Invalid path pattern \"\": Empty pattern" }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscribeResponse {
+    #[serde(rename = "requestId", default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub state: PutState,
+    #[serde(rename = "statusCode")]
+    pub status_code: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Parameters that were ignored or adjusted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl SubscribeResponse {
+    /// The subscriptions were added, with `warnings` if any.
+    pub fn completed(req: &SubscribeRequest, warnings: Vec<String>) -> Self {
+        Self {
+            request_id: req.request_id.clone(),
+            state: PutState::Completed,
+            status_code: 200,
+            message: None,
+            warnings,
+        }
+    }
+
+    /// The request was rejected.
+    pub fn failed(req: &SubscribeRequest, status_code: u16, message: impl Into<String>) -> Self {
+        Self {
+            request_id: req.request_id.clone(),
+            state: PutState::Failed,
+            status_code,
+            message: Some(message.into()),
+            warnings: Vec::new(),
+        }
+    }
 }
 
 /// A single subscription specification.
@@ -31,7 +95,7 @@ pub struct Subscription {
 }
 
 /// Subscription format.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SubscriptionFormat {
     Delta,
@@ -89,6 +153,42 @@ pub struct PutResponse {
     pub status_code: u16,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Where to poll a pending request for its outcome.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+}
+
+impl PutResponse {
+    /// The response to `req` once it came to `result`.
+    pub fn for_result(req: &PutRequest, result: &PutResult) -> Self {
+        let (state, message) = match result {
+            PutResult::Completed(_) | PutResult::Writable(_) => (PutState::Completed, None),
+            PutResult::Pending => (PutState::Pending, None),
+            PutResult::Failed(message) => (PutState::Failed, Some(message.clone())),
+            PutResult::NotSupported => (
+                PutState::Failed,
+                Some(format!("PUT not supported for {}", req.put.path)),
+            ),
+        };
+        Self {
+            request_id: req.request_id.clone(),
+            state,
+            status_code: result.status_code(),
+            message,
+            href: None,
+        }
+    }
+
+    /// A failed response.
+    pub fn failed(req: &PutRequest, status_code: u16, message: impl Into<String>) -> Self {
+        Self {
+            request_id: req.request_id.clone(),
+            state: PutState::Failed,
+            status_code,
+            message: Some(message.into()),
+            href: None,
+        }
+    }
 }
 
 /// PUT request state.
@@ -100,6 +200,83 @@ pub enum PutState {
     Failed,
 }
 
+/// GET request message, asking for the current full-format state of a
+/// context or of a path within it.
+///
+/// ```json
+/// { "context": "vessels.self", "requestId": "42", "get": { "path": "navigation" } }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetRequest {
+    /// Context to read; defaults to `vessels.self`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Echoed in the response.
+    #[serde(rename = "requestId", default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub get: GetSpec,
+}
+
+/// GET specification.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GetSpec {
+    /// Path within the context; the whole context when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// GET response message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetResponse {
+    #[serde(rename = "requestId", default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub state: PutState,
+    #[serde(rename = "statusCode")]
+    pub status_code: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The context as requested.
+    pub context: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The full-format node, when found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+}
+
+impl GetResponse {
+    /// A successful response carrying `value`.
+    pub fn completed(req: &GetRequest, context: &str, value: serde_json::Value) -> Self {
+        Self {
+            request_id: req.request_id.clone(),
+            state: PutState::Completed,
+            status_code: 200,
+            message: None,
+            context: context.to_string(),
+            path: req.get.path.clone(),
+            value: Some(value),
+        }
+    }
+
+    /// A failed response.
+    pub fn failed(
+        req: &GetRequest,
+        context: &str,
+        status_code: u16,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            request_id: req.request_id.clone(),
+            state: PutState::Failed,
+            status_code,
+            message: Some(message.into()),
+            context: context.to_string(),
+            path: req.get.path.clone(),
+            value: None,
+        }
+    }
+}
+
 // ============================================================================
 // Hello Message (Server → Client on connect)
 // ============================================================================
@@ -135,6 +312,20 @@ pub struct HelloMessage {
 
     /// Current server timestamp in ISO 8601 format.
     pub timestamp: String,
+
+    /// Name of the self vessel, if configured (not part of the spec, so
+    /// omitted when unset).
+    #[serde(
+        rename = "vesselName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub vessel_name: Option<String>,
+
+    /// Features this server offers (not part of the spec, so omitted when
+    /// unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Capabilities>,
 }
 
 impl HelloMessage {
@@ -150,7 +341,93 @@ impl HelloMessage {
             self_urn: self_urn.into(),
             roles: vec!["main".to_string()],
             timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            vessel_name: None,
+            capabilities: None,
+        }
+    }
+
+    /// Include the self vessel's name.
+    pub fn with_vessel_name(mut self, vessel_name: Option<String>) -> Self {
+        self.vessel_name = vessel_name;
+        self
+    }
+
+    /// Advertise the server's capabilities.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+}
+
+/// Features a server advertises in its Hello so clients can adapt.
+///
+/// ```json
+/// { "put": true, "formats": ["delta", "full"], "maxSubscriptions": 50,
+///   "cbor": false, "compression": false }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// Whether PUT requests are accepted on this connection.
+    pub put: bool,
+
+    /// Available message formats (`?format=` on the stream URL).
+    pub formats: Vec<SubscriptionFormat>,
+
+    /// Maximum number of subscriptions per connection, if limited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_subscriptions: Option<usize>,
+
+    /// Whether CBOR-encoded messages are offered.
+    pub cbor: bool,
+
+    /// Whether WebSocket compression (permessage-deflate) is offered.
+    pub compression: bool,
+}
+
+// ============================================================================
+// Delta Batches (Client → Server)
+// ============================================================================
+
+/// Deltas sent together in one message, e.g. updates for several contexts.
+///
+/// Serialized as a JSON array of deltas. A single delta deserializes as a
+/// batch of one, so both forms are accepted wherever a batch is:
+///
+/// ```json
+/// [
+///   { "context": "vessels.self", "updates": [...] },
+///   { "context": "vessels.urn:mrn:imo:mmsi:230099999", "updates": [...] }
+/// ]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct DeltaBatch(pub Vec<Delta>);
+
+impl From<Delta> for DeltaBatch {
+    fn from(delta: Delta) -> Self {
+        Self(vec![delta])
+    }
+}
+
+impl IntoIterator for DeltaBatch {
+    type Item = Delta;
+    type IntoIter = std::vec::IntoIter<Delta>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'de> Deserialize<'de> for DeltaBatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        if value.is_array() {
+            Vec::<Delta>::deserialize(value).map(Self)
+        } else {
+            Delta::deserialize(value).map(Self::from)
         }
+        .map_err(D::Error::custom)
     }
 }
 
@@ -168,15 +445,22 @@ pub enum ServerMessage {
     /// Delta update with new data.
     Delta(Delta),
 
+    /// Response to a GET request (before `PutResponse`: only it has
+    /// `context`).
+    GetResponse(GetResponse),
+
     /// Response to a PUT request.
     PutResponse(PutResponse),
 }
 
 /// Messages that can be received from client.
 ///
-/// Uses untagged deserialization - the message type is determined by
-/// examining which fields are present.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The message type is determined by its request key (`subscribe`,
+/// `unsubscribe`, `put`, `get` or `mode`). Exactly one must be present:
+/// a message with several is rejected as ambiguous rather than being read
+/// as whichever variant happens to parse first, and anything else (such as
+/// a `login` request) is rejected as unknown.
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 pub enum ClientMessage {
     /// Subscribe to data paths.
@@ -187,16 +471,101 @@ pub enum ClientMessage {
 
     /// PUT request to modify data.
     Put(PutRequest),
+
+    /// GET request for the current state of a context or path.
+    GetFull(GetRequest),
+
+    /// Replace all subscriptions with the default set for a `subscribe`
+    /// mode (`self`, `all` or `none`), as if reconnecting with
+    /// `?subscribe=<mode>`.
+    ///
+    /// ```json
+    /// { "mode": "all" }
+    /// ```
+    SetMode { mode: String },
+}
+
+impl ClientMessage {
+    /// The request keys that identify each message type.
+    pub const REQUEST_KEYS: &'static [&'static str] =
+        &["subscribe", "unsubscribe", "put", "get", "mode"];
+}
+
+impl<'de> Deserialize<'de> for ClientMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct SetMode {
+            mode: String,
+        }
+
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let Some(object) = value.as_object() else {
+            return Err(D::Error::custom("client message must be a JSON object"));
+        };
+
+        let keys: Vec<&str> = Self::REQUEST_KEYS
+            .iter()
+            .copied()
+            .filter(|key| object.contains_key(*key))
+            .collect();
+        let message = match keys.as_slice() {
+            ["subscribe"] => SubscribeRequest::deserialize(value).map(Self::Subscribe),
+            ["unsubscribe"] => UnsubscribeRequest::deserialize(value).map(Self::Unsubscribe),
+            ["put"] => PutRequest::deserialize(value).map(Self::Put),
+            ["get"] => GetRequest::deserialize(value).map(Self::GetFull),
+            ["mode"] => SetMode::deserialize(value).map(|m| Self::SetMode { mode: m.mode }),
+            [] => {
+                return Err(D::Error::custom(format!(
+                    "unknown client message, expected one of: {}",
+                    Self::REQUEST_KEYS.join(", ")
+                )))
+            }
+            _ => {
+                return Err(D::Error::custom(format!(
+                    "ambiguous client message with {}",
+                    keys.join(", ")
+                )))
+            }
+        };
+        message.map_err(D::Error::custom)
+    }
 }
 
 // ============================================================================
 // Discovery Endpoint
 // ============================================================================
 
+/// Errors building a discovery document from request headers.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DiscoveryError {
+    /// The host is empty or not a bare `host[:port]`.
+    #[error("Invalid host: {0:?}")]
+    InvalidHost(String),
+    /// The scheme is neither `http` nor `https`.
+    #[error("Invalid scheme: {0:?}")]
+    InvalidScheme(String),
+}
+
 /// Discovery response for `/signalk` endpoint.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveryResponse {
     pub endpoints: DiscoveryEndpoints,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<DiscoveryServer>,
+}
+
+/// Server identification in discovery.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryServer {
+    pub id: String,
+    pub version: String,
+    /// Name of the self vessel, if configured.
+    #[serde(
+        rename = "vesselName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub vessel_name: Option<String>,
 }
 
 /// Endpoints advertised in discovery.
@@ -226,8 +595,81 @@ impl DiscoveryResponse {
                     signalk_ws: format!("ws://{host}:{port}/signalk/v1/stream"),
                 },
             },
+            server: None,
+        }
+    }
+
+    /// Create a discovery response with URLs the requesting client can use.
+    ///
+    /// `host` is the request's `Host` header (optionally with a port) and
+    /// `scheme` the scheme the client used (`X-Forwarded-Proto` behind a
+    /// proxy, see [`forwarded_scheme`](Self::forwarded_scheme)). `port` is
+    /// added when `host` has none and it isn't the scheme's default; pass
+    /// `None` when `host` came from the `Host` header, which already names
+    /// any non-default port. `base_path` is a prefix the server is mounted
+    /// under (e.g. `/boat`), or empty.
+    pub fn for_request(
+        host: &str,
+        scheme: &str,
+        port: Option<u16>,
+        base_path: &str,
+        server: Option<DiscoveryServer>,
+    ) -> Result<Self, DiscoveryError> {
+        let (http, ws, default_port) = match scheme.to_ascii_lowercase().as_str() {
+            "http" => ("http", "ws", 80),
+            "https" => ("https", "wss", 443),
+            _ => return Err(DiscoveryError::InvalidScheme(scheme.to_string())),
+        };
+        if host.is_empty()
+            || host
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '/' | '\\' | '@' | '?' | '#'))
+        {
+            return Err(DiscoveryError::InvalidHost(host.to_string()));
+        }
+
+        // `[::1]:3000` has a port, `[::1]` and `::1` don't
+        let has_port = match host.strip_prefix('[') {
+            Some(rest) => rest.contains("]:"),
+            None => host.matches(':').count() == 1,
+        };
+        let authority = match port {
+            Some(port) if !has_port && port != default_port => format!("{host}:{port}"),
+            _ => host.to_string(),
+        };
+        let base_path = base_path.trim_matches('/');
+        let base = if base_path.is_empty() {
+            String::new()
+        } else {
+            format!("/{base_path}")
+        };
+
+        Ok(Self {
+            endpoints: DiscoveryEndpoints {
+                v1: DiscoveryV1 {
+                    version: "1.7.0".to_string(),
+                    signalk_http: format!("{http}://{authority}{base}/signalk/v1/api"),
+                    signalk_ws: format!("{ws}://{authority}{base}/signalk/v1/stream"),
+                },
+            },
+            server,
+        })
+    }
+
+    /// The scheme a client used, from an `X-Forwarded-Proto` header value
+    /// (the first entry counts); `http` if absent or unrecognized.
+    pub fn forwarded_scheme(header: Option<&str>) -> &'static str {
+        match header.and_then(|value| value.split(',').next()) {
+            Some(proto) if proto.trim().eq_ignore_ascii_case("https") => "https",
+            _ => "http",
         }
     }
+
+    /// Identify the server (and optionally the self vessel).
+    pub fn with_server(mut self, server: DiscoveryServer) -> Self {
+        self.server = Some(server);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -283,6 +725,184 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_deserialization() {
+        let msg: ClientMessage = serde_json::from_str(
+            r#"{"context": "vessels.self", "requestId": "a1", "get": {"path": "navigation"}}"#,
+        )
+        .unwrap();
+        match msg {
+            ClientMessage::GetFull(req) => {
+                assert_eq!(req.request_id.as_deref(), Some("a1"));
+                assert_eq!(req.get.path.as_deref(), Some("navigation"));
+            }
+            other => panic!("Expected GetFull message, got {other:?}"),
+        }
+
+        // requestId and path are optional
+        let msg: ClientMessage = serde_json::from_str(r#"{"get": {}}"#).unwrap();
+        match msg {
+            ClientMessage::GetFull(req) => {
+                assert!(req.request_id.is_none());
+                assert!(req.context.is_none());
+                assert!(req.get.path.is_none());
+            }
+            other => panic!("Expected GetFull message, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_set_mode_deserialization() {
+        let msg: ClientMessage = serde_json::from_str(r#"{"mode": "all"}"#).unwrap();
+        match msg {
+            ClientMessage::SetMode { mode } => assert_eq!(mode, "all"),
+            other => panic!("Expected SetMode message, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_delta_batch_serialization() {
+        let json = r#"[
+            {"context": "vessels.self", "updates": [{"values": [{"path": "navigation.log", "value": 1}]}]},
+            {"context": "vessels.urn:mrn:imo:mmsi:230099999", "updates": []}
+        ]"#;
+        let batch: DeltaBatch = serde_json::from_str(json).unwrap();
+        assert_eq!(batch.0.len(), 2);
+        assert_eq!(
+            batch.0[1].context.as_deref(),
+            Some("vessels.urn:mrn:imo:mmsi:230099999")
+        );
+
+        let reparsed: serde_json::Value = serde_json::to_value(&batch).unwrap();
+        assert!(reparsed.is_array());
+        assert_eq!(
+            reparsed[0]["updates"][0]["values"][0]["path"],
+            "navigation.log"
+        );
+
+        // A single delta is a batch of one
+        let batch: DeltaBatch =
+            serde_json::from_str(r#"{"context": "vessels.self", "updates": []}"#).unwrap();
+        assert_eq!(batch.0.len(), 1);
+
+        assert!(serde_json::from_str::<DeltaBatch>(r#"[{"subscribe": []}]"#).is_err());
+    }
+
+    /// Variant name of a parsed client message, or the parse error.
+    fn parse_client(json: &str) -> Result<&'static str, String> {
+        serde_json::from_str::<ClientMessage>(json)
+            .map(|msg| match msg {
+                ClientMessage::Subscribe(_) => "subscribe",
+                ClientMessage::Unsubscribe(_) => "unsubscribe",
+                ClientMessage::Put(_) => "put",
+                ClientMessage::GetFull(_) => "get",
+                ClientMessage::SetMode { .. } => "mode",
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    const VALID_CLIENT_MESSAGES: &[(&str, &str)] = &[
+        (
+            r#"{"context": "vessels.self", "subscribe": [{"path": "navigation.*", "period": 1000}]}"#,
+            "subscribe",
+        ),
+        (
+            r#"{"context": "*", "unsubscribe": [{"path": "*"}]}"#,
+            "unsubscribe",
+        ),
+        (
+            r#"{"context": "vessels.self", "requestId": "1", "put": {"path": "a.b", "value": 1}}"#,
+            "put",
+        ),
+        (
+            r#"{"requestId": "1", "put": {"path": "a.b", "value": null, "source": "x"}}"#,
+            "put",
+        ),
+        (
+            r#"{"requestId": "2", "get": {"path": "navigation"}}"#,
+            "get",
+        ),
+        (r#"{"get": {}}"#, "get"),
+        (r#"{"mode": "none"}"#, "mode"),
+    ];
+
+    #[test]
+    fn test_client_message_matrix() {
+        for (json, expected) in VALID_CLIENT_MESSAGES {
+            assert_eq!(parse_client(json).as_deref(), Ok(*expected), "{json}");
+        }
+
+        // Several request keys: rejected, not read as the first that fits
+        for json in [
+            r#"{"context": "vessels.self", "subscribe": [{"path": "*"}], "unsubscribe": [{"path": "*"}]}"#,
+            r#"{"requestId": "1", "put": {"path": "a.b", "value": 1}, "get": {}}"#,
+            r#"{"context": "vessels.self", "subscribe": [{"path": "*"}], "mode": "all"}"#,
+        ] {
+            let error = parse_client(json).unwrap_err();
+            assert!(error.contains("ambiguous"), "{json}: {error}");
+        }
+
+        // Unknown requests, including ones sharing fields with known ones
+        for json in [
+            r#"{"requestId": "1", "login": {"username": "u", "password": "p"}}"#,
+            r#"{"context": "vessels.self", "requestId": "1"}"#,
+            r#"{}"#,
+        ] {
+            let error = parse_client(json).unwrap_err();
+            assert!(error.contains("unknown client message"), "{json}: {error}");
+        }
+
+        // Malformed requests
+        for json in [
+            r#"{"subscribe": [{"path": "*"}]}"#,
+            r#"{"context": "vessels.self", "subscribe": "navigation.*"}"#,
+            r#"{"put": {"path": "a.b", "value": 1}}"#,
+            r#"{"requestId": "1", "put": {"value": 1}}"#,
+            r#"{"get": "navigation"}"#,
+            r#"{"mode": 5}"#,
+            r#"[{"mode": "all"}]"#,
+            r#""subscribe""#,
+            "null",
+            "42",
+        ] {
+            assert!(parse_client(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_client_message_truncated_input() {
+        // Every prefix of a valid message must fail cleanly, never panic
+        for (json, _) in VALID_CLIENT_MESSAGES {
+            for end in (0..json.len()).filter(|&end| json.is_char_boundary(end)) {
+                assert!(parse_client(&json[..end]).is_err(), "{}", &json[..end]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_response_round_trip() {
+        let req: GetRequest =
+            serde_json::from_str(r#"{"requestId": "a1", "get": {"path": "navigation"}}"#).unwrap();
+        let response = GetResponse::completed(&req, "vessels.self", serde_json::json!({}));
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains(r#""requestId":"a1""#));
+        assert!(json.contains(r#""statusCode":200"#));
+
+        match serde_json::from_str::<ServerMessage>(&json).unwrap() {
+            ServerMessage::GetResponse(response) => {
+                assert_eq!(response.request_id.as_deref(), Some("a1"))
+            }
+            other => panic!("Expected GetResponse, got {other:?}"),
+        }
+
+        // A PUT response is not mistaken for a GET response
+        let put = r#"{"requestId": "p1", "state": "COMPLETED", "statusCode": 200}"#;
+        assert!(matches!(
+            serde_json::from_str::<ServerMessage>(put).unwrap(),
+            ServerMessage::PutResponse(_)
+        ));
+    }
+
     #[test]
     fn test_discovery_response() {
         let discovery = DiscoveryResponse::new("localhost", 3000);
@@ -290,5 +910,104 @@ mod tests {
 
         assert!(json.contains("http://localhost:3000/signalk/v1/api"));
         assert!(json.contains("ws://localhost:3000/signalk/v1/stream"));
+        assert!(!json.contains("server"));
+    }
+
+    #[test]
+    fn test_discovery_for_forwarded_request() {
+        let scheme = DiscoveryResponse::forwarded_scheme(Some("https, http"));
+        let discovery =
+            DiscoveryResponse::for_request("boat.example.com", scheme, None, "/", None).unwrap();
+        assert_eq!(
+            discovery.endpoints.v1.signalk_http,
+            "https://boat.example.com/signalk/v1/api"
+        );
+        assert_eq!(
+            discovery.endpoints.v1.signalk_ws,
+            "wss://boat.example.com/signalk/v1/stream"
+        );
+
+        // The Host header's port is kept, the fallback port only added when
+        // missing and not the default
+        let discovery =
+            DiscoveryResponse::for_request("10.0.0.5:3000", "http", Some(4000), "/boat/", None)
+                .unwrap();
+        assert_eq!(
+            discovery.endpoints.v1.signalk_ws,
+            "ws://10.0.0.5:3000/boat/signalk/v1/stream"
+        );
+        let discovery =
+            DiscoveryResponse::for_request("[fe80::1]", "HTTP", Some(4000), "", None).unwrap();
+        assert_eq!(
+            discovery.endpoints.v1.signalk_http,
+            "http://[fe80::1]:4000/signalk/v1/api"
+        );
+        let discovery =
+            DiscoveryResponse::for_request("10.0.0.5", "http", Some(80), "", None).unwrap();
+        assert_eq!(
+            discovery.endpoints.v1.signalk_http,
+            "http://10.0.0.5/signalk/v1/api"
+        );
+
+        assert_eq!(DiscoveryResponse::forwarded_scheme(None), "http");
+        assert!(matches!(
+            DiscoveryResponse::for_request("evil.com/x", "http", None, "", None),
+            Err(DiscoveryError::InvalidHost(_))
+        ));
+        assert!(matches!(
+            DiscoveryResponse::for_request("boat", "ftp", None, "", None),
+            Err(DiscoveryError::InvalidScheme(_))
+        ));
+    }
+
+    #[test]
+    fn test_vessel_name_is_optional() {
+        let hello = HelloMessage::new("test-server", "1.7.0", "vessels.self");
+        assert!(!serde_json::to_string(&hello)
+            .unwrap()
+            .contains("vesselName"));
+
+        let hello = hello.with_vessel_name(Some("Albatross".to_string()));
+        let json: serde_json::Value = serde_json::to_value(&hello).unwrap();
+        assert_eq!(json["vesselName"], "Albatross");
+
+        let discovery = DiscoveryResponse::new("localhost", 3000).with_server(DiscoveryServer {
+            id: "signalk-server-rust".to_string(),
+            version: "0.1.0".to_string(),
+            vessel_name: Some("Albatross".to_string()),
+        });
+        let json: serde_json::Value = serde_json::to_value(&discovery).unwrap();
+        assert_eq!(json["server"]["vesselName"], "Albatross");
+    }
+
+    #[test]
+    fn test_hello_capabilities() {
+        let hello = HelloMessage::new("test-server", "1.7.0", "vessels.self");
+        assert!(!serde_json::to_string(&hello)
+            .unwrap()
+            .contains("capabilities"));
+
+        let capabilities = Capabilities {
+            put: true,
+            formats: vec![SubscriptionFormat::Delta, SubscriptionFormat::Full],
+            max_subscriptions: Some(50),
+            cbor: false,
+            compression: false,
+        };
+        let hello = hello.with_capabilities(capabilities.clone());
+        let json = serde_json::to_value(&hello).unwrap();
+        assert_eq!(
+            json["capabilities"],
+            serde_json::json!({
+                "put": true,
+                "formats": ["delta", "full"],
+                "maxSubscriptions": 50,
+                "cbor": false,
+                "compression": false,
+            })
+        );
+
+        let parsed: HelloMessage = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.capabilities, Some(capabilities));
     }
 }
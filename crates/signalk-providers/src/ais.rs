@@ -0,0 +1,588 @@
+//! AIS message decoding.
+//!
+//! AIS reports arrive as `!AIVDM` (other vessels) and `!AIVDO` (own
+//! vessel) sentences. Their payload is "armored": each character carries
+//! six bits of the binary message, and messages longer than one sentence
+//! are split into fragments. [`AisDecoder`] reassembles fragments, unpacks
+//! the bits and turns position reports (types 1, 2, 3, 18 and 19) and
+//! static data (types 5, 19 and 24) into SignalK values for the vessel's
+//! own context, `vessels.urn:mrn:imo:mmsi:<mmsi>`.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use signalk_core::PathValue;
+
+use crate::nmea0183::{invalid_field, path_value, verify_checksum, Nmea0183Error};
+
+/// Knots to metres per second.
+const KNOTS_TO_MS: f64 = 1852.0 / 3600.0;
+
+/// Longitude meaning "not available", in 1/10000 minutes.
+const LONGITUDE_NOT_AVAILABLE: i64 = 181 * 600_000;
+
+/// Latitude meaning "not available", in 1/10000 minutes.
+const LATITUDE_NOT_AVAILABLE: i64 = 91 * 600_000;
+
+/// A decoded AIS message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AisMessage {
+    /// Message type (1-27).
+    pub message_type: u8,
+    /// MMSI of the vessel the message is about.
+    pub mmsi: u32,
+    /// Whether the message came from a VDO sentence, i.e. is about the
+    /// receiving vessel itself.
+    pub own_vessel: bool,
+    /// Talker ID of the sentence (usually `AI`).
+    pub talker: String,
+    /// SignalK values carried by the message.
+    pub values: Vec<PathValue>,
+}
+
+impl AisMessage {
+    /// SignalK context the values belong to.
+    pub fn context(&self) -> String {
+        if self.own_vessel {
+            "vessels.self".to_string()
+        } else {
+            format!("vessels.urn:mrn:imo:mmsi:{}", self.mmsi)
+        }
+    }
+}
+
+/// Decodes VDM/VDO sentences, reassembling multi-sentence messages.
+#[derive(Debug, Default)]
+pub struct AisDecoder {
+    /// Incomplete messages, keyed by channel and sequential message ID.
+    pending: HashMap<String, Fragments>,
+}
+
+#[derive(Debug)]
+struct Fragments {
+    count: u8,
+    received: u8,
+    payload: String,
+}
+
+impl AisDecoder {
+    /// Create a decoder with no pending fragments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode one sentence.
+    ///
+    /// Returns `None` for a fragment of a message that isn't complete yet.
+    /// A fragment that doesn't continue the pending message on its channel
+    /// discards that message.
+    pub fn decode(&mut self, line: &str) -> Result<Option<AisMessage>, Nmea0183Error> {
+        let line = line.trim();
+        if !line.is_ascii() {
+            return Err(Nmea0183Error::NonAscii);
+        }
+        let body = line.strip_prefix('!').ok_or(Nmea0183Error::MissingStart)?;
+        let body = match body.split_once('*') {
+            Some((data, checksum)) => {
+                verify_checksum(data, checksum)?;
+                data
+            }
+            None => body,
+        };
+
+        let fields: Vec<&str> = body.split(',').collect();
+        let address = fields[0];
+        if address.len() < 5 {
+            return Err(Nmea0183Error::TooShort);
+        }
+        let (talker, sentence_type) = address.split_at(address.len() - 3);
+        let own_vessel = match sentence_type {
+            "VDM" => false,
+            "VDO" => true,
+            other => return Err(Nmea0183Error::Unsupported(other.to_string())),
+        };
+        if fields.len() < 7 {
+            return Err(Nmea0183Error::TooShort);
+        }
+
+        let count = parse_u8(sentence_type, &fields, 1)?;
+        let number = parse_u8(sentence_type, &fields, 2)?;
+        let (payload, fill_bits) = (fields[5], parse_u8(sentence_type, &fields, 6)?);
+        if number == 0 || number > count || fill_bits > 5 {
+            return Err(invalid_field(sentence_type, 2, fields[2]));
+        }
+
+        let payload = if count == 1 {
+            payload.to_string()
+        } else {
+            let key = format!("{}{}", fields[4], fields[3]);
+            if number == 1 {
+                self.pending.insert(
+                    key.clone(),
+                    Fragments {
+                        count,
+                        received: 0,
+                        payload: String::new(),
+                    },
+                );
+            }
+            let Some(fragments) = self
+                .pending
+                .get_mut(&key)
+                .filter(|f| f.count == count && f.received + 1 == number)
+            else {
+                self.pending.remove(&key);
+                return Ok(None);
+            };
+            fragments.received = number;
+            fragments.payload.push_str(payload);
+            if number < count {
+                return Ok(None);
+            }
+            self.pending
+                .remove(&key)
+                .map(|f| f.payload)
+                .unwrap_or_default()
+        };
+
+        let bits = Bits::unarmor(&payload, fill_bits)?;
+        let message_type = bits.uint(0, 6)? as u8;
+        let mmsi = bits.uint(8, 30)? as u32;
+        let values = match message_type {
+            1..=3 => position_report(&bits)?,
+            5 => static_voyage_data(&bits)?,
+            18 => class_b_position_report(&bits)?,
+            19 => extended_class_b_position_report(&bits)?,
+            24 => static_data_report(&bits)?,
+            other => return Err(Nmea0183Error::Unsupported(format!("AIS message {other}"))),
+        };
+
+        Ok(Some(AisMessage {
+            message_type,
+            mmsi,
+            own_vessel,
+            talker: talker.to_string(),
+            values,
+        }))
+    }
+}
+
+/// Types 1, 2 and 3: class A position report.
+fn position_report(bits: &Bits) -> Result<Vec<PathValue>, Nmea0183Error> {
+    let mut values = Vec::new();
+    if let Some(state) = navigation_state(bits.uint(38, 4)?) {
+        values.push(path_value("navigation.state", json!(state)));
+    }
+    push_motion(&mut values, bits, 50, 61, 89, 116, 128)?;
+    Ok(values)
+}
+
+/// Type 5: class A static and voyage related data.
+fn static_voyage_data(bits: &Bits) -> Result<Vec<PathValue>, Nmea0183Error> {
+    let mut values = Vec::new();
+    push_mmsi(&mut values, bits)?;
+    let imo = bits.uint(40, 30)?;
+    if imo != 0 {
+        values.push(path_value("registrations.imo", json!(format!("IMO {imo}"))));
+    }
+    push_text(&mut values, "communication.callsignVhf", bits.text(70, 7)?);
+    push_text(&mut values, "name", bits.text(112, 20)?);
+    push_design(&mut values, bits, 232, 240)?;
+
+    let draught = bits.uint(294, 8)?;
+    if draught != 0 {
+        values.push(path_value(
+            "design.draft",
+            json!({ "current": draught as f64 / 10.0 }),
+        ));
+    }
+    // Some transmitters leave the destination off
+    if bits.len >= 422 {
+        push_text(
+            &mut values,
+            "navigation.destination.commonName",
+            bits.text(302, 20)?,
+        );
+    }
+    Ok(values)
+}
+
+/// Type 18: class B position report.
+fn class_b_position_report(bits: &Bits) -> Result<Vec<PathValue>, Nmea0183Error> {
+    let mut values = Vec::new();
+    push_motion(&mut values, bits, 46, 57, 85, 112, 124)?;
+    Ok(values)
+}
+
+/// Type 19: extended class B position report, with static data.
+fn extended_class_b_position_report(bits: &Bits) -> Result<Vec<PathValue>, Nmea0183Error> {
+    let mut values = Vec::new();
+    push_mmsi(&mut values, bits)?;
+    push_motion(&mut values, bits, 46, 57, 85, 112, 124)?;
+    push_text(&mut values, "name", bits.text(143, 20)?);
+    push_design(&mut values, bits, 263, 271)?;
+    Ok(values)
+}
+
+/// Type 24: class B static data, in two parts sent separately.
+fn static_data_report(bits: &Bits) -> Result<Vec<PathValue>, Nmea0183Error> {
+    let mut values = Vec::new();
+    push_mmsi(&mut values, bits)?;
+    match bits.uint(38, 2)? {
+        0 => push_text(&mut values, "name", bits.text(40, 20)?),
+        1 => {
+            push_text(&mut values, "communication.callsignVhf", bits.text(90, 7)?);
+            push_design(&mut values, bits, 40, 132)?;
+        }
+        part => return Err(invalid_payload(format!("type 24 part {part}"))),
+    }
+    Ok(values)
+}
+
+fn push_mmsi(values: &mut Vec<PathValue>, bits: &Bits) -> Result<(), Nmea0183Error> {
+    values.push(path_value("mmsi", json!(bits.uint(8, 30)?.to_string())));
+    Ok(())
+}
+
+fn push_text(values: &mut Vec<PathValue>, path: &str, text: String) {
+    if !text.is_empty() {
+        values.push(path_value(path, json!(text)));
+    }
+}
+
+/// Speed, position, course and heading, each skipped when the message
+/// marks it not available. Arguments are the fields' bit offsets.
+fn push_motion(
+    values: &mut Vec<PathValue>,
+    bits: &Bits,
+    sog: usize,
+    longitude: usize,
+    latitude: usize,
+    cog: usize,
+    heading: usize,
+) -> Result<(), Nmea0183Error> {
+    let longitude = bits.int(longitude, 28)?;
+    let latitude = bits.int(latitude, 27)?;
+    if longitude != LONGITUDE_NOT_AVAILABLE
+        && latitude != LATITUDE_NOT_AVAILABLE
+        && longitude.abs() <= 180 * 600_000
+        && latitude.abs() <= 90 * 600_000
+    {
+        values.push(path_value(
+            "navigation.position",
+            json!({
+                "latitude": latitude as f64 / 600_000.0,
+                "longitude": longitude as f64 / 600_000.0,
+            }),
+        ));
+    }
+
+    let sog = bits.uint(sog, 10)?;
+    if sog != 1023 {
+        values.push(path_value(
+            "navigation.speedOverGround",
+            json!(sog as f64 / 10.0 * KNOTS_TO_MS),
+        ));
+    }
+    let cog = bits.uint(cog, 12)?;
+    if cog < 3600 {
+        values.push(path_value(
+            "navigation.courseOverGroundTrue",
+            json!((cog as f64 / 10.0).to_radians()),
+        ));
+    }
+    let heading = bits.uint(heading, 9)?;
+    if heading < 360 {
+        values.push(path_value(
+            "navigation.headingTrue",
+            json!((heading as f64).to_radians()),
+        ));
+    }
+    Ok(())
+}
+
+/// Ship type at `ship_type` and the GNSS antenna's distances to bow,
+/// stern, port and starboard at `dimensions`.
+fn push_design(
+    values: &mut Vec<PathValue>,
+    bits: &Bits,
+    ship_type: usize,
+    dimensions: usize,
+) -> Result<(), Nmea0183Error> {
+    let ship_type = bits.uint(ship_type, 8)?;
+    if ship_type != 0 {
+        values.push(path_value("design.aisShipType", json!({ "id": ship_type })));
+    }
+
+    let bow = bits.uint(dimensions, 9)?;
+    let stern = bits.uint(dimensions + 9, 9)?;
+    let port = bits.uint(dimensions + 18, 6)?;
+    let starboard = bits.uint(dimensions + 24, 6)?;
+    if bow + stern > 0 {
+        values.push(path_value(
+            "design.length",
+            json!({ "overall": (bow + stern) as f64 }),
+        ));
+    }
+    if port + starboard > 0 {
+        values.push(path_value("design.beam", json!((port + starboard) as f64)));
+    }
+    Ok(())
+}
+
+/// SignalK `navigation.state` for an AIS navigational status.
+fn navigation_state(status: u64) -> Option<&'static str> {
+    Some(match status {
+        0 => "motoring",
+        1 => "anchored",
+        2 => "not under command",
+        3 => "restricted manouverability",
+        4 => "constrained by draft",
+        5 => "moored",
+        6 => "aground",
+        7 => "fishing",
+        8 => "sailing",
+        14 => "ais-sart",
+        _ => return None,
+    })
+}
+
+fn parse_u8(sentence: &str, fields: &[&str], index: usize) -> Result<u8, Nmea0183Error> {
+    fields[index]
+        .parse()
+        .map_err(|_| invalid_field(sentence, index, fields[index]))
+}
+
+fn invalid_payload(reason: impl Into<String>) -> Nmea0183Error {
+    Nmea0183Error::InvalidAisPayload(reason.into())
+}
+
+/// The binary message carried by an armored payload.
+struct Bits {
+    /// Six bits per payload character.
+    sextets: Vec<u8>,
+    /// Number of message bits, excluding fill bits.
+    len: usize,
+}
+
+impl Bits {
+    fn unarmor(payload: &str, fill_bits: u8) -> Result<Self, Nmea0183Error> {
+        let sextets = payload
+            .bytes()
+            .map(|c| match c {
+                b'0'..=b'W' => Ok(c - b'0'),
+                b'`'..=b'w' => Ok(c - b'0' - 8),
+                _ => Err(invalid_payload(format!("character '{}'", c as char))),
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        let len = (sextets.len() * 6).saturating_sub(usize::from(fill_bits));
+        Ok(Self { sextets, len })
+    }
+
+    /// Unsigned field of `len` bits (at most 64) starting at bit `start`.
+    fn uint(&self, start: usize, len: usize) -> Result<u64, Nmea0183Error> {
+        if start + len > self.len {
+            return Err(invalid_payload(format!(
+                "{} bits is too short for type {}",
+                self.len,
+                self.sextets.first().copied().unwrap_or_default()
+            )));
+        }
+        Ok((start..start + len).fold(0, |acc, bit| {
+            let sextet = self.sextets[bit / 6];
+            (acc << 1) | u64::from((sextet >> (5 - bit % 6)) & 1)
+        }))
+    }
+
+    /// Two's complement signed field.
+    fn int(&self, start: usize, len: usize) -> Result<i64, Nmea0183Error> {
+        let raw = self.uint(start, len)?;
+        let shift = 64 - len;
+        Ok(((raw << shift) as i64) >> shift)
+    }
+
+    /// Text of `chars` six-bit characters, without the `@` padding and
+    /// trailing spaces.
+    fn text(&self, start: usize, chars: usize) -> Result<String, Nmea0183Error> {
+        let mut text = String::with_capacity(chars);
+        for i in 0..chars {
+            let c = self.uint(start + i * 6, 6)? as u8;
+            text.push(if c < 32 { (c + 64) as char } else { c as char });
+        }
+        let end = text.find('@').unwrap_or(text.len());
+        text.truncate(end);
+        Ok(text.trim_end().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx(value: &serde_json::Value, expected: f64) {
+        let actual = value.as_f64().unwrap();
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    }
+
+    fn value<'a>(message: &'a AisMessage, path: &str) -> &'a serde_json::Value {
+        &message
+            .values
+            .iter()
+            .find(|pv| pv.path == path)
+            .unwrap_or_else(|| panic!("no {path} in {:?}", message.values))
+            .value
+    }
+
+    fn decode(line: &str) -> AisMessage {
+        AisDecoder::new().decode(line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_position_report() {
+        let message = decode("!AIVDM,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5C");
+
+        assert_eq!(message.message_type, 1);
+        assert_eq!(message.mmsi, 477553000);
+        assert_eq!(message.context(), "vessels.urn:mrn:imo:mmsi:477553000");
+        assert_eq!(value(&message, "navigation.state"), "moored");
+        let position = value(&message, "navigation.position");
+        approx(&position["latitude"], 47.582833);
+        approx(&position["longitude"], -122.345833);
+        approx(value(&message, "navigation.speedOverGround"), 0.0);
+        approx(
+            value(&message, "navigation.courseOverGroundTrue"),
+            51.0_f64.to_radians(),
+        );
+        approx(
+            value(&message, "navigation.headingTrue"),
+            181.0_f64.to_radians(),
+        );
+    }
+
+    #[test]
+    fn test_multi_fragment_static_data() {
+        let mut decoder = AisDecoder::new();
+        let first =
+            "!AIVDM,2,1,1,A,55?MbV02;H;s<HtKR20EHE:0@T4@Dn2222222216L961O5Gf0NSQEp6ClRp8,0*1C";
+        assert_eq!(decoder.decode(first).unwrap(), None);
+        let message = decoder
+            .decode("!AIVDM,2,2,1,A,88888888880,2*25")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(message.message_type, 5);
+        assert_eq!(message.mmsi, 351759000);
+        assert_eq!(value(&message, "name"), "EVER DIADEM");
+        assert_eq!(value(&message, "communication.callsignVhf"), "3FOF8");
+        assert_eq!(value(&message, "registrations.imo"), "IMO 9134270");
+        assert_eq!(value(&message, "design.aisShipType")["id"], 70);
+        approx(&value(&message, "design.length")["overall"], 295.0);
+        approx(value(&message, "design.beam"), 32.0);
+        approx(&value(&message, "design.draft")["current"], 12.2);
+        assert_eq!(
+            value(&message, "navigation.destination.commonName"),
+            "NEW YORK"
+        );
+        assert!(decoder.pending.is_empty());
+    }
+
+    #[test]
+    fn test_out_of_order_fragment_dropped() {
+        let mut decoder = AisDecoder::new();
+        assert_eq!(
+            decoder.decode("!AIVDM,2,2,1,A,88888888880,2*25").unwrap(),
+            None
+        );
+        assert!(decoder.pending.is_empty());
+    }
+
+    #[test]
+    fn test_class_b_reports() {
+        let message = decode("!AIVDM,1,1,,A,B52K>;h00Fc>jpUlNV@ikwpUoP06,0*4C");
+        assert_eq!(message.message_type, 18);
+        assert_eq!(message.mmsi, 338087471);
+        let position = value(&message, "navigation.position");
+        approx(&position["latitude"], 40.684540);
+        approx(&position["longitude"], -74.072132);
+        approx(
+            value(&message, "navigation.speedOverGround"),
+            0.1 * KNOTS_TO_MS,
+        );
+        // Heading not available
+        assert!(message
+            .values
+            .iter()
+            .all(|pv| pv.path != "navigation.headingTrue"));
+
+        let message =
+            decode("!AIVDM,1,1,,B,C5N3SRgPEnJGEBT>NhWAwwo862PaLELTBJ:V00000000S0D:R220,0*0B");
+        assert_eq!(message.message_type, 19);
+        assert_eq!(message.mmsi, 367059850);
+        assert_eq!(value(&message, "name"), "CAPT.J.RIMES");
+        approx(
+            &value(&message, "navigation.position")["latitude"],
+            29.543695,
+        );
+        approx(
+            value(&message, "navigation.speedOverGround"),
+            8.7 * KNOTS_TO_MS,
+        );
+        approx(&value(&message, "design.length")["overall"], 26.0);
+    }
+
+    #[test]
+    fn test_static_data_report_parts() {
+        let part_a = decode("!AIVDM,1,1,,A,H42O55i18tMET00000000000000,2*6D");
+        assert_eq!(part_a.message_type, 24);
+        assert_eq!(part_a.mmsi, 271041815);
+        assert_eq!(value(&part_a, "name"), "PROGUY");
+
+        let part_b = decode("!AIVDM,1,1,,A,H42O55lti4hhhilD3nink000?050,0*40");
+        assert_eq!(value(&part_b, "communication.callsignVhf"), "TC6163");
+        assert_eq!(value(&part_b, "design.aisShipType")["id"], 60);
+        approx(&value(&part_b, "design.length")["overall"], 15.0);
+    }
+
+    #[test]
+    fn test_own_vessel() {
+        let message = decode("!AIVDO,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5E");
+        assert!(message.own_vessel);
+        assert_eq!(message.context(), "vessels.self");
+    }
+
+    #[test]
+    fn test_malformed_sentences() {
+        let mut decoder = AisDecoder::new();
+        assert!(matches!(
+            decoder.decode("!AIVDM,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5D"),
+            Err(Nmea0183Error::ChecksumMismatch { .. })
+        ));
+        // Line noise decoded by `from_utf8_lossy`
+        assert_eq!(
+            decoder.decode("!AIV€M,1,1,,A,177KQJ5000G?tO`K>RA1wUbN0TKH,0"),
+            Err(Nmea0183Error::NonAscii)
+        );
+        assert_eq!(
+            decoder.decode("!AIVDM,1,1,,A,177KQJ5000G?tO`K>RA1w\u{fffd}bN0TKH,0"),
+            Err(Nmea0183Error::NonAscii)
+        );
+        // Truncated payload
+        assert!(matches!(
+            decoder.decode("!AIVDM,1,1,,B,177KQJ50,0"),
+            Err(Nmea0183Error::InvalidAisPayload(_))
+        ));
+        assert!(matches!(
+            decoder.decode("!AIVDM,1,1,,B,177KQ~J50,0"),
+            Err(Nmea0183Error::InvalidAisPayload(_))
+        ));
+        // Type 4 (base station report)
+        assert!(matches!(
+            decoder.decode("!AIVDM,1,1,,A,403OviQuMGCqWrRO9>E6fE700@GO,0"),
+            Err(Nmea0183Error::Unsupported(_))
+        ));
+        assert!(matches!(
+            decoder.decode("!AIVDM,1,1"),
+            Err(Nmea0183Error::TooShort)
+        ));
+    }
+}
@@ -0,0 +1,370 @@
+//! Demo data scenarios.
+//!
+//! Without instruments attached, the server can run a [`DemoScenario`]
+//! that makes up deltas for a typical situation. Each scenario exercises a
+//! different set of paths, which helps when trying subscriptions and the
+//! Admin UI against more than one moving boat. [`DemoConfig`] selects the
+//! scenario and how often it updates.
+//!
+//! Scenarios compute every value from the tick number, so the same tick
+//! always gives the same delta; the caller stamps the time.
+
+use std::f64::consts::TAU;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use signalk_core::{Delta, PathValue, Update};
+
+/// `$source` of demo deltas.
+pub const DEMO_SOURCE: &str = "demo.generator";
+
+/// Metres per degree of latitude.
+const METRES_PER_DEGREE: f64 = 1852.0 * 60.0;
+
+/// A source of made-up deltas for the self vessel.
+pub trait DemoScenario: Send {
+    /// Scenario name, for logs.
+    fn name(&self) -> &'static str;
+
+    /// The delta for update number `tick`, counting from 0.
+    fn next_delta(&mut self, tick: u64) -> Delta;
+}
+
+/// Which scenario [`DemoConfig`] selects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DemoScenarioKind {
+    #[default]
+    CircularTrack,
+    AnchoredWithWind,
+    EngineRoom,
+}
+
+/// Demo data settings.
+///
+/// ```json
+/// { "scenario": "anchoredWithWind", "updateIntervalMs": 500 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DemoConfig {
+    #[serde(default)]
+    pub scenario: DemoScenarioKind,
+
+    /// Time between deltas, in milliseconds.
+    #[serde(default = "default_update_interval_ms")]
+    pub update_interval_ms: u64,
+}
+
+fn default_update_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for DemoConfig {
+    fn default() -> Self {
+        Self {
+            scenario: DemoScenarioKind::default(),
+            update_interval_ms: default_update_interval_ms(),
+        }
+    }
+}
+
+impl DemoConfig {
+    /// Config storage key the Linux server loads the demo settings from.
+    pub const STORAGE_KEY: &'static str = "demo.json";
+
+    /// Time between deltas; at least a millisecond.
+    pub fn update_interval(&self) -> Duration {
+        Duration::from_millis(self.update_interval_ms.max(1))
+    }
+
+    /// Build the selected scenario, paced for the update interval.
+    pub fn build_scenario(&self) -> Box<dyn DemoScenario> {
+        let interval = self.update_interval();
+        match self.scenario {
+            DemoScenarioKind::CircularTrack => Box::new(CircularTrack::new(interval)),
+            DemoScenarioKind::AnchoredWithWind => Box::new(AnchoredWithWind::new(interval)),
+            DemoScenarioKind::EngineRoom => Box::new(EngineRoom::new(interval)),
+        }
+    }
+}
+
+/// A boat sailing a circle at constant speed.
+#[derive(Debug, Clone)]
+pub struct CircularTrack {
+    /// Centre of the circle, in degrees.
+    center: (f64, f64),
+    /// Radius in metres.
+    radius: f64,
+    /// Time for one lap.
+    lap: Duration,
+    interval: Duration,
+}
+
+impl CircularTrack {
+    /// A 500 m circle sailed in ten minutes, updated every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            center: (52.0987654, 4.9876545),
+            radius: 500.0,
+            lap: Duration::from_secs(600),
+            interval,
+        }
+    }
+}
+
+impl DemoScenario for CircularTrack {
+    fn name(&self) -> &'static str {
+        "circular track"
+    }
+
+    fn next_delta(&mut self, tick: u64) -> Delta {
+        let elapsed = elapsed(tick, self.interval);
+        let bearing = TAU * (elapsed / self.lap.as_secs_f64()).fract();
+        let (latitude, longitude) = offset(self.center, bearing, self.radius);
+        // Sailing clockwise, the course is a quarter turn on from the
+        // bearing from the centre
+        let course = (bearing + TAU / 4.0) % TAU;
+        let speed = TAU * self.radius / self.lap.as_secs_f64();
+
+        demo_delta(vec![
+            path_value(
+                "navigation.position",
+                json!({ "latitude": latitude, "longitude": longitude }),
+            ),
+            path_value("navigation.speedOverGround", json!(speed)),
+            path_value("navigation.courseOverGroundTrue", json!(course)),
+            // A little leeway
+            path_value(
+                "navigation.headingTrue",
+                json!((course - 0.05).rem_euclid(TAU)),
+            ),
+        ])
+    }
+}
+
+/// A boat at anchor, swinging in a shifting breeze.
+#[derive(Debug, Clone)]
+pub struct AnchoredWithWind {
+    anchor: (f64, f64),
+    interval: Duration,
+}
+
+impl AnchoredWithWind {
+    /// Anchored near the circular track, updated every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            anchor: (52.1012, 4.9841),
+            interval,
+        }
+    }
+}
+
+impl DemoScenario for AnchoredWithWind {
+    fn name(&self) -> &'static str {
+        "anchored with wind"
+    }
+
+    fn next_delta(&mut self, tick: u64) -> Delta {
+        let t = elapsed(tick, self.interval);
+        // The wind veers and backs 20° around south-west over ten minutes,
+        // gusting every half minute
+        let direction = (225.0 + 20.0 * (TAU * t / 600.0).sin()).to_radians();
+        let speed = 7.0 + 2.0 * (TAU * t / 30.0).sin().max(0.0);
+        // The boat lies downwind of the anchor, head to wind
+        let heading = direction;
+        let (latitude, longitude) = offset(self.anchor, (direction + TAU / 2.0) % TAU, 30.0);
+        // Yawing a few degrees either side of the wind
+        let apparent_angle = (6.0 * (TAU * t / 45.0).sin()).to_radians();
+
+        demo_delta(vec![
+            path_value(
+                "navigation.position",
+                json!({ "latitude": latitude, "longitude": longitude }),
+            ),
+            path_value("navigation.headingTrue", json!(heading)),
+            path_value("navigation.speedOverGround", json!(0.0)),
+            path_value("environment.wind.speedTrue", json!(speed)),
+            path_value("environment.wind.directionTrue", json!(direction)),
+            path_value("environment.wind.speedApparent", json!(speed)),
+            path_value("environment.wind.angleApparent", json!(apparent_angle)),
+            path_value(
+                "environment.depth.belowTransducer",
+                json!(6.2 + 0.4 * (TAU * t / 3600.0).sin()),
+            ),
+        ])
+    }
+}
+
+/// An engine running at cruising speed, with the house bank charging.
+#[derive(Debug, Clone)]
+pub struct EngineRoom {
+    interval: Duration,
+}
+
+impl EngineRoom {
+    /// Updated every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+impl DemoScenario for EngineRoom {
+    fn name(&self) -> &'static str {
+        "engine room"
+    }
+
+    fn next_delta(&mut self, tick: u64) -> Delta {
+        let t = elapsed(tick, self.interval);
+        // Warming up from 20 °C to 80 °C over the first ten minutes
+        let warm = (t / 600.0).min(1.0);
+        let rpm = 1800.0 + 50.0 * (TAU * t / 20.0).sin();
+        // 6 l/h from a 200 l tank
+        let fuel = (0.8 - t * 6.0 / 3600.0 / 200.0).max(0.0);
+
+        demo_delta(vec![
+            path_value("propulsion.main.revolutions", json!(rpm / 60.0)),
+            path_value(
+                "propulsion.main.temperature",
+                json!(273.15 + 20.0 + 60.0 * warm),
+            ),
+            path_value(
+                "propulsion.main.oilPressure",
+                json!(200_000.0 + 150_000.0 * warm),
+            ),
+            path_value("propulsion.main.runTime", json!(t.floor())),
+            path_value(
+                "electrical.batteries.house.voltage",
+                json!(13.4 + 0.8 * warm),
+            ),
+            path_value(
+                "electrical.batteries.house.current",
+                json!(25.0 * (1.0 - warm) + 4.0),
+            ),
+            path_value("tanks.fuel.0.currentLevel", json!(fuel)),
+        ])
+    }
+}
+
+/// Seconds since tick 0.
+fn elapsed(tick: u64, interval: Duration) -> f64 {
+    tick as f64 * interval.as_secs_f64()
+}
+
+/// The point `distance` metres from `origin` on `bearing` (radians), in
+/// degrees; flat-earth, which is plenty for a few hundred metres.
+fn offset(origin: (f64, f64), bearing: f64, distance: f64) -> (f64, f64) {
+    let (latitude, longitude) = origin;
+    let north = distance * bearing.cos() / METRES_PER_DEGREE;
+    let east = distance * bearing.sin() / (METRES_PER_DEGREE * latitude.to_radians().cos());
+    (latitude + north, longitude + east)
+}
+
+fn demo_delta(values: Vec<PathValue>) -> Delta {
+    Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some(DEMO_SOURCE.to_string()),
+            source: None,
+            timestamp: None,
+            values,
+            meta: None,
+            server_timestamp: None,
+        }],
+    }
+}
+
+fn path_value(path: &str, value: Value) -> PathValue {
+    PathValue {
+        path: path.to_string(),
+        value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(delta: &Delta) -> Vec<&str> {
+        delta.updates[0]
+            .values
+            .iter()
+            .map(|pv| pv.path.as_str())
+            .collect()
+    }
+
+    fn build(scenario: DemoScenarioKind) -> Box<dyn DemoScenario> {
+        DemoConfig {
+            scenario,
+            ..DemoConfig::default()
+        }
+        .build_scenario()
+    }
+
+    #[test]
+    fn test_scenario_path_prefixes() {
+        let cases = [
+            (DemoScenarioKind::CircularTrack, &["navigation."][..]),
+            (
+                DemoScenarioKind::AnchoredWithWind,
+                &["navigation.", "environment.wind.", "environment.depth."],
+            ),
+            (
+                DemoScenarioKind::EngineRoom,
+                &["propulsion.main.", "electrical.batteries.", "tanks.fuel."],
+            ),
+        ];
+        for (kind, prefixes) in cases {
+            let mut scenario = build(kind);
+            for tick in [0, 1, 1000] {
+                let delta = scenario.next_delta(tick);
+                assert_eq!(delta.context.as_deref(), Some("vessels.self"));
+                assert_eq!(delta.updates[0].source_ref.as_deref(), Some(DEMO_SOURCE));
+                let paths = paths(&delta);
+                assert!(
+                    paths
+                        .iter()
+                        .all(|path| prefixes.iter().any(|prefix| path.starts_with(prefix))),
+                    "{}: {paths:?}",
+                    scenario.name()
+                );
+                // Every prefix is exercised
+                for prefix in prefixes {
+                    assert!(paths.iter().any(|path| path.starts_with(prefix)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_circular_track_stays_on_circle() {
+        let mut track = CircularTrack::new(Duration::from_secs(1));
+        let (lat0, lon0) = track.center;
+        for tick in (0..600).step_by(37) {
+            let delta = track.next_delta(tick);
+            let position = &delta.updates[0].values[0].value;
+            let north = (position["latitude"].as_f64().unwrap() - lat0) * METRES_PER_DEGREE;
+            let east = (position["longitude"].as_f64().unwrap() - lon0)
+                * METRES_PER_DEGREE
+                * lat0.to_radians().cos();
+            let radius = north.hypot(east);
+            assert!((radius - 500.0).abs() < 0.01, "{radius}");
+        }
+        // Same tick, same delta
+        assert_eq!(track.next_delta(42), track.next_delta(42));
+    }
+
+    #[test]
+    fn test_config_from_json() {
+        let config: DemoConfig =
+            serde_json::from_str(r#"{"scenario": "engineRoom", "updateIntervalMs": 250}"#).unwrap();
+        assert_eq!(config.scenario, DemoScenarioKind::EngineRoom);
+        assert_eq!(config.update_interval(), Duration::from_millis(250));
+        assert_eq!(config.build_scenario().name(), "engine room");
+
+        let config: DemoConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, DemoConfig::default());
+        assert_eq!(config.update_interval(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,663 @@
+//! Derived values computed from configurable rules.
+//!
+//! Each [`DerivedRule`] binds input paths to variable names and computes an
+//! output path from them with a small arithmetic expression, e.g. true wind
+//! speed from apparent wind and speed through water:
+//!
+//! ```json
+//! {
+//!   "name": "trueWindSpeed",
+//!   "inputs": {
+//!     "aws": "environment.wind.speedApparent",
+//!     "awa": "environment.wind.angleApparent",
+//!     "stw": "navigation.speedThroughWater"
+//!   },
+//!   "expression": "sqrt(aws*aws + stw*stw - 2*aws*stw*cos(awa))",
+//!   "output": "environment.wind.speedTrue"
+//! }
+//! ```
+//!
+//! Expressions support numbers, the input variables, `+ - * /`, unary minus,
+//! parentheses and the functions `sin`, `cos`, `sqrt` and `atan2(y, x)`.
+//!
+//! [`DerivedEngine`] remembers the latest numeric value of every input per
+//! context. When a delta updates an input, each rule using it whose inputs
+//! are all known is evaluated and the results are returned as one delta for
+//! that context with `$source` [`DERIVED_SOURCE`]. Deltas from that source
+//! are ignored, so rules don't feed on their own (or each other's) output.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use signalk_core::{Delta, PathValue, Update};
+use thiserror::Error;
+
+/// `$source` of derived deltas.
+pub const DERIVED_SOURCE: &str = "derived";
+
+/// Errors from parsing a rule expression.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ExpressionError {
+    #[error("Unexpected character '{0}'")]
+    UnexpectedChar(char),
+
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+
+    #[error("Unexpected '{0}'")]
+    UnexpectedToken(String),
+
+    #[error("Invalid number '{0}'")]
+    InvalidNumber(String),
+
+    #[error("Unknown variable '{0}'")]
+    UnknownVariable(String),
+
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+
+    #[error("{function} takes {expected} argument(s), got {found}")]
+    Arity {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// A rule that failed to compile.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("Invalid derived rule '{rule}': {error}")]
+pub struct DerivedError {
+    pub rule: String,
+    pub error: ExpressionError,
+}
+
+/// A derived-value rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedRule {
+    /// Name used in errors and logs.
+    pub name: String,
+    /// Input paths keyed by the variable name used in the expression.
+    pub inputs: BTreeMap<String, String>,
+    /// Expression computing the output from the inputs.
+    pub expression: String,
+    /// Path the result is written to.
+    pub output: String,
+}
+
+impl DerivedRule {
+    /// Config storage key the Linux server loads rules from.
+    pub const STORAGE_KEY: &'static str = "derived.json";
+
+    /// Create a rule with no inputs.
+    pub fn new(
+        name: impl Into<String>,
+        expression: impl Into<String>,
+        output: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            inputs: BTreeMap::new(),
+            expression: expression.into(),
+            output: output.into(),
+        }
+    }
+
+    /// Bind `variable` to the value at `path`.
+    pub fn with_input(mut self, variable: impl Into<String>, path: impl Into<String>) -> Self {
+        self.inputs.insert(variable.into(), path.into());
+        self
+    }
+}
+
+/// Binary operators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Supported functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Sqrt,
+    Atan2,
+}
+
+impl Function {
+    fn lookup(name: &str) -> Option<Self> {
+        match name {
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "sqrt" => Some(Function::Sqrt),
+            "atan2" => Some(Function::Atan2),
+            _ => None,
+        }
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Function::Atan2 => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// A compiled expression; variables are indices into the input values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression(Node);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(usize),
+    Neg(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+impl Expression {
+    /// Parse `source`, resolving variable names to their index in
+    /// `variables`.
+    pub fn parse(source: &str, variables: &[&str]) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            variables,
+        };
+        let node = parser.expr()?;
+        match parser.next() {
+            None => Ok(Self(node)),
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    /// Evaluate with `values[i]` bound to the i-th variable.
+    pub fn eval(&self, values: &[f64]) -> f64 {
+        self.0.eval(values)
+    }
+}
+
+impl Node {
+    fn eval(&self, values: &[f64]) -> f64 {
+        match self {
+            Node::Number(n) => *n,
+            Node::Variable(i) => values[*i],
+            Node::Neg(node) => -node.eval(values),
+            Node::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(values), rhs.eval(values));
+                match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div => lhs / rhs,
+                }
+            }
+            Node::Call(function, args) => match function {
+                Function::Sin => args[0].eval(values).sin(),
+                Function::Cos => args[0].eval(values).cos(),
+                Function::Sqrt => args[0].eval(values).sqrt(),
+                Function::Atan2 => args[0].eval(values).atan2(args[1].eval(values)),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(s) | Token::Ident(s) => f.write_str(s),
+            Token::Symbol(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                number.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                ident.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(ident));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(ExpressionError::UnexpectedChar(c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over:
+///
+/// ```text
+/// expr    = term (("+" | "-") term)*
+/// term    = unary (("*" | "/") unary)*
+/// unary   = "-" unary | primary
+/// primary = number | ident | ident "(" expr ("," expr)* ")" | "(" expr ")"
+/// ```
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: &'a [&'a str],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ExpressionError> {
+        match self.next() {
+            Some(Token::Symbol(c)) if c == symbol => Ok(()),
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                Op::Add
+            } else if self.eat('-') {
+                Op::Sub
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                Op::Mul
+            } else if self.eat('/') {
+                Op::Div
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        if self.eat('-') {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        match self.next() {
+            Some(Token::Number(number)) => number
+                .parse()
+                .map(Node::Number)
+                .map_err(|_| ExpressionError::InvalidNumber(number)),
+            Some(Token::Ident(name)) if self.eat('(') => {
+                let function = Function::lookup(&name)
+                    .ok_or_else(|| ExpressionError::UnknownFunction(name.clone()))?;
+                let mut args = vec![self.expr()?];
+                while self.eat(',') {
+                    args.push(self.expr()?);
+                }
+                self.expect(')')?;
+                if args.len() != function.arity() {
+                    return Err(ExpressionError::Arity {
+                        function: name,
+                        expected: function.arity(),
+                        found: args.len(),
+                    });
+                }
+                Ok(Node::Call(function, args))
+            }
+            Some(Token::Ident(name)) => self
+                .variables
+                .iter()
+                .position(|v| *v == name)
+                .map(Node::Variable)
+                .ok_or(ExpressionError::UnknownVariable(name)),
+            Some(Token::Symbol('(')) => {
+                let node = self.expr()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+}
+
+/// A rule ready for evaluation.
+#[derive(Debug)]
+struct CompiledRule {
+    /// Input paths, in the order the expression indexes them.
+    inputs: Vec<String>,
+    expression: Expression,
+    output: String,
+}
+
+/// Evaluates derived rules against incoming deltas.
+#[derive(Debug, Default)]
+pub struct DerivedEngine {
+    rules: Vec<CompiledRule>,
+    /// Latest numeric input values per context and path.
+    inputs: HashMap<String, HashMap<String, f64>>,
+}
+
+impl DerivedEngine {
+    /// Compile `rules`; fails on the first invalid expression.
+    pub fn new(rules: &[DerivedRule]) -> Result<Self, DerivedError> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let variables: Vec<&str> = rule.inputs.keys().map(String::as_str).collect();
+                let expression =
+                    Expression::parse(&rule.expression, &variables).map_err(|error| {
+                        DerivedError {
+                            rule: rule.name.clone(),
+                            error,
+                        }
+                    })?;
+                Ok(CompiledRule {
+                    inputs: rule.inputs.values().cloned().collect(),
+                    expression,
+                    output: rule.output.clone(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            rules,
+            inputs: HashMap::new(),
+        })
+    }
+
+    /// Whether no rules are configured.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Record the inputs carried by `delta` and evaluate the rules they
+    /// affect.
+    ///
+    /// Returns the derived values as one delta for the same context, or
+    /// `None` if no rule fired. Results that aren't finite (e.g. division
+    /// by zero) are dropped.
+    pub fn process(&mut self, delta: &Delta) -> Option<Delta> {
+        if self.is_empty() {
+            return None;
+        }
+        let context = delta.context.as_deref().unwrap_or("vessels.self");
+
+        let mut updates = Vec::new();
+        for update in &delta.updates {
+            if update.source_ref.as_deref() == Some(DERIVED_SOURCE) {
+                continue;
+            }
+            let changed: Vec<&str> = update
+                .values
+                .iter()
+                .filter(|pv| self.is_input(&pv.path))
+                .map(|pv| pv.path.as_str())
+                .collect();
+            if changed.is_empty() {
+                continue;
+            }
+
+            let known = self.inputs.entry(context.to_string()).or_default();
+            for pv in update
+                .values
+                .iter()
+                .filter(|pv| changed.contains(&pv.path.as_str()))
+            {
+                match pv.value.as_f64() {
+                    Some(value) => {
+                        known.insert(pv.path.clone(), value);
+                    }
+                    // A non-numeric reading invalidates the previous one
+                    None => {
+                        known.remove(&pv.path);
+                    }
+                }
+            }
+
+            let values: Vec<PathValue> = self
+                .rules
+                .iter()
+                .filter(|rule| rule.inputs.iter().any(|i| changed.contains(&i.as_str())))
+                .filter_map(|rule| {
+                    let args: Option<Vec<f64>> =
+                        rule.inputs.iter().map(|i| known.get(i).copied()).collect();
+                    let result = rule.expression.eval(&args?);
+                    result.is_finite().then(|| PathValue {
+                        path: rule.output.clone(),
+                        value: Value::from(result),
+                    })
+                })
+                .collect();
+            if !values.is_empty() {
+                updates.push(Update {
+                    source_ref: Some(DERIVED_SOURCE.to_string()),
+                    source: None,
+                    timestamp: update.timestamp.clone(),
+                    values,
+                    meta: None,
+                    server_timestamp: None,
+                });
+            }
+        }
+
+        (!updates.is_empty()).then(|| Delta {
+            context: delta.context.clone(),
+            updates,
+        })
+    }
+
+    fn is_input(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.inputs.iter().any(|i| i == path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    const AWS: &str = "environment.wind.speedApparent";
+    const AWA: &str = "environment.wind.angleApparent";
+    const STW: &str = "navigation.speedThroughWater";
+
+    fn true_wind_rules() -> Vec<DerivedRule> {
+        vec![
+            DerivedRule::new(
+                "trueWindSpeed",
+                "sqrt(aws*aws + stw*stw - 2*aws*stw*cos(awa))",
+                "environment.wind.speedTrue",
+            )
+            .with_input("aws", AWS)
+            .with_input("awa", AWA)
+            .with_input("stw", STW),
+            DerivedRule::new(
+                "trueWindAngle",
+                "atan2(aws*sin(awa), aws*cos(awa) - stw)",
+                "environment.wind.angleTrueWater",
+            )
+            .with_input("aws", AWS)
+            .with_input("awa", AWA)
+            .with_input("stw", STW),
+        ]
+    }
+
+    fn delta(values: &[(&str, Value)]) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("nmea0183.II".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: values
+                    .iter()
+                    .map(|(path, value)| PathValue {
+                        path: path.to_string(),
+                        value: value.clone(),
+                    })
+                    .collect(),
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    fn approx(value: &Value, expected: f64) {
+        let actual = value.as_f64().unwrap();
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_true_wind() {
+        let mut engine = DerivedEngine::new(&true_wind_rules()).unwrap();
+
+        // Nothing until all inputs are known
+        assert!(engine
+            .process(&delta(&[(AWS, 10.0.into()), (AWA, FRAC_PI_2.into())]))
+            .is_none());
+
+        // 10 m/s apparent on the beam at 5 m/s through the water
+        let derived = engine.process(&delta(&[(STW, 5.0.into())])).unwrap();
+        let update = &derived.updates[0];
+        assert_eq!(update.source_ref.as_deref(), Some(DERIVED_SOURCE));
+        assert_eq!(
+            update.timestamp.as_deref(),
+            Some("2024-01-17T10:00:00.000Z")
+        );
+        assert_eq!(update.values[0].path, "environment.wind.speedTrue");
+        approx(&update.values[0].value, 125.0_f64.sqrt());
+        approx(&update.values[1].value, 10.0_f64.atan2(-5.0));
+
+        // Derived output is not fed back in
+        assert!(engine.process(&derived).is_none());
+        // A non-numeric input stops the rules until it is valid again
+        assert!(engine.process(&delta(&[(STW, Value::Null)])).is_none());
+    }
+
+    #[test]
+    fn test_rules_from_config() {
+        let rules: Vec<DerivedRule> = serde_json::from_str(
+            r#"[{
+                "name": "depthBelowSurface",
+                "inputs": { "d": "environment.depth.belowTransducer" },
+                "expression": "d + 0.4",
+                "output": "environment.depth.belowSurface"
+            }, {
+                "name": "halfSpeed",
+                "inputs": { "sog": "navigation.speedOverGround" },
+                "expression": "-(sog / -2)",
+                "output": "navigation.halfSpeed"
+            }]"#,
+        )
+        .unwrap();
+        let mut engine = DerivedEngine::new(&rules).unwrap();
+
+        let derived = engine
+            .process(&delta(&[
+                ("environment.depth.belowTransducer", 3.1.into()),
+                ("navigation.speedOverGround", 4.0.into()),
+            ]))
+            .unwrap();
+        let values = &derived.updates[0].values;
+        assert_eq!(values.len(), 2);
+        approx(&values[0].value, 3.5);
+        approx(&values[1].value, 2.0);
+
+        // Division by zero yields no value
+        let mut engine = DerivedEngine::new(&[
+            DerivedRule::new("inverse", "1 / x", "x.inverse").with_input("x", "x")
+        ])
+        .unwrap();
+        assert!(engine.process(&delta(&[("x", 0.0.into())])).is_none());
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        let parse = |source| Expression::parse(source, &["a", "b"]).map(|e| e.eval(&[2.0, 3.0]));
+
+        assert_eq!(parse("a + b * 2"), Ok(8.0));
+        assert_eq!(parse("(a + b) * 2"), Ok(10.0));
+        assert_eq!(parse("a - b - 1"), Ok(-2.0));
+        assert_eq!(parse("a +"), Err(ExpressionError::UnexpectedEnd));
+        assert_eq!(
+            parse("a b"),
+            Err(ExpressionError::UnexpectedToken("b".to_string()))
+        );
+        assert_eq!(parse("a ^ b"), Err(ExpressionError::UnexpectedChar('^')));
+        assert_eq!(
+            parse("c"),
+            Err(ExpressionError::UnknownVariable("c".to_string()))
+        );
+        assert_eq!(
+            parse("tan(a)"),
+            Err(ExpressionError::UnknownFunction("tan".to_string()))
+        );
+        assert_eq!(
+            parse("atan2(a)"),
+            Err(ExpressionError::Arity {
+                function: "atan2".to_string(),
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            parse("1.2.3"),
+            Err(ExpressionError::InvalidNumber("1.2.3".to_string()))
+        );
+
+        let error = DerivedEngine::new(&[DerivedRule::new("broken", "x +", "y")]).unwrap_err();
+        assert_eq!(error.rule, "broken");
+    }
+}
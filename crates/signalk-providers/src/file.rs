@@ -0,0 +1,114 @@
+//! Replay of recorded data files.
+//!
+//! [`FileReplayProvider`] reads a log file line by line at a steady pace,
+//! as if the lines were arriving from a live source. Lines holding a JSON
+//! delta (as `DeltaRecorder` writes them) are submitted as they are; other
+//! lines are parsed as NMEA 0183 sentences. Useful for demos and for
+//! testing against a recorded trip without the boat.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use signalk_core::{Delta, DeltaSink, ProviderState};
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{debug, info, warn};
+
+use crate::nmea0183::{Nmea0183Driver, Nmea0183Error};
+
+/// Pause between lines used unless configured otherwise.
+pub const DEFAULT_LINE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Replays a file of deltas or NMEA 0183 sentences into a [`DeltaSink`].
+pub struct FileReplayProvider<S> {
+    path: PathBuf,
+    driver: Nmea0183Driver<S>,
+    line_interval: Duration,
+    looping: bool,
+}
+
+impl<S: DeltaSink + 'static> FileReplayProvider<S> {
+    /// Create a provider for `path`, labelling NMEA 0183 deltas with
+    /// `source_label` (deltas keep their own sources).
+    pub fn new(path: impl Into<PathBuf>, source_label: &str, sink: S) -> Self {
+        Self {
+            path: path.into(),
+            driver: Nmea0183Driver::new(source_label, sink),
+            line_interval: DEFAULT_LINE_INTERVAL,
+            looping: false,
+        }
+    }
+
+    /// Pause `interval` after each line.
+    pub fn with_line_interval(mut self, interval: Duration) -> Self {
+        self.line_interval = interval;
+        self
+    }
+
+    /// Start over from the first line after reaching the end.
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Replay the file, once or until stopped if looping.
+    ///
+    /// The open file is reported to the sink as connected; a file that
+    /// can't be read is reported as an error.
+    pub async fn run(self) {
+        loop {
+            match self.replay().await {
+                Ok(lines) => info!("Replayed {} lines of {}", lines, self.path.display()),
+                Err(e) => {
+                    warn!("Could not replay {}: {}", self.path.display(), e);
+                    self.driver
+                        .sink()
+                        .report_state(ProviderState::Error(e.to_string()));
+                    return;
+                }
+            }
+            if !self.looping {
+                return;
+            }
+        }
+    }
+
+    /// Feed every line of the file to the sink, returning how many lines
+    /// were read.
+    async fn replay(&self) -> std::io::Result<usize> {
+        let file = File::open(&self.path).await?;
+        self.driver.sink().report_state(ProviderState::Connected);
+        let mut lines = BufReader::new(file).lines();
+        let mut count = 0;
+        while let Some(line) = lines.next_line().await? {
+            count += 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            self.handle_line(line);
+            tokio::time::sleep(self.line_interval).await;
+        }
+        Ok(count)
+    }
+
+    fn handle_line(&self, line: &str) {
+        if line.starts_with('{') {
+            match serde_json::from_str::<Delta>(line) {
+                Ok(delta) => self.driver.sink().submit(delta),
+                Err(e) => warn!("{}: not a delta, skipped: {}", self.path.display(), e),
+            }
+            return;
+        }
+        match self.driver.handle_line(line) {
+            Ok(()) => {}
+            Err(e @ Nmea0183Error::Unsupported(_)) => debug!("{}: {}", self.path.display(), e),
+            Err(e) => warn!(
+                "Skipping sentence in {}: {} ({})",
+                self.path.display(),
+                e,
+                line
+            ),
+        }
+    }
+}
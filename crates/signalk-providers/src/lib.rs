@@ -3,8 +3,51 @@
 //! Data providers for SignalK server.
 //!
 //! This crate provides parsers and handlers for various marine data sources:
-//! - NMEA 0183
+//! - NMEA 0183, including AIS targets
 //! - NMEA 2000 (future)
-//! - TCP/UDP streams
+//! - TCP streams and UDP datagrams of NMEA 0183 (with the `tokio-runtime`
+//!   feature)
+//! - Replay of recorded files (with the `tokio-runtime` feature)
+//! - NMEA 0183 from serial ports (with the `serial` feature)
+//! - Derived values computed from configurable rules
+//! - Demo data scenarios, for running without instruments
+//!
+//! Providers submit deltas through `signalk_core::DeltaSink`, so they don't
+//! depend on the server's channel types or async runtime. With the
+//! `tokio-runtime` feature, [`build_provider`] creates a [`Provider`] from a
+//! persisted [`ProviderConfig`], so servers can manage sources by config.
+
+pub mod ais;
+pub mod demo;
+pub mod derived;
+#[cfg(feature = "tokio-runtime")]
+pub mod file;
+pub mod nmea0183;
+#[cfg(feature = "tokio-runtime")]
+pub mod provider;
+pub mod serial;
+#[cfg(feature = "tokio-runtime")]
+pub mod tcp;
+#[cfg(feature = "tokio-runtime")]
+pub mod udp;
 
-// TODO: Provider implementations
+pub use ais::{AisDecoder, AisMessage};
+pub use demo::{
+    AnchoredWithWind, CircularTrack, DemoConfig, DemoScenario, DemoScenarioKind, EngineRoom,
+    DEMO_SOURCE,
+};
+pub use derived::{
+    DerivedEngine, DerivedError, DerivedRule, Expression, ExpressionError, DERIVED_SOURCE,
+};
+#[cfg(feature = "tokio-runtime")]
+pub use file::FileReplayProvider;
+pub use nmea0183::{parse_sentence, Nmea0183Config, Nmea0183Driver, Nmea0183Error, Sentence};
+#[cfg(feature = "tokio-runtime")]
+pub use provider::{build_provider, Provider, ProviderConfig};
+#[cfg(feature = "serial")]
+pub use serial::SerialProvider;
+pub use serial::{Parity, SerialLineReader};
+#[cfg(feature = "tokio-runtime")]
+pub use tcp::TcpProvider;
+#[cfg(feature = "tokio-runtime")]
+pub use udp::UdpProvider;
@@ -0,0 +1,799 @@
+//! NMEA 0183 sentence parsing.
+//!
+//! Converts sentences such as `$GPRMC,...*hh` into SignalK path values with
+//! SI units (knots → m/s, degrees → radians). RMC, GGA, GLL, VTG, HDG, MWV
+//! and DBT are supported. [`Nmea0183Driver`] wraps the parser and submits
+//! the resulting deltas to a [`DeltaSink`]; it also decodes AIS sentences
+//! (see [`crate::ais`]).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use signalk_core::{Delta, DeltaSink, PathValue, Source, Update};
+use thiserror::Error;
+
+use crate::ais::{AisDecoder, AisMessage};
+
+/// Knots to metres per second.
+const KNOTS_TO_MS: f64 = 1852.0 / 3600.0;
+
+/// Kilometres per hour to metres per second.
+const KMH_TO_MS: f64 = 1.0 / 3.6;
+
+/// Statute miles per hour to metres per second.
+const MPH_TO_MS: f64 = 0.44704;
+
+/// Feet to metres.
+const FEET_TO_M: f64 = 0.3048;
+
+/// Fathoms to metres.
+const FATHOMS_TO_M: f64 = 1.8288;
+
+/// Errors from parsing an NMEA 0183 sentence.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum Nmea0183Error {
+    #[error("Sentence must start with '$' or '!'")]
+    MissingStart,
+
+    #[error("Sentence too short")]
+    TooShort,
+
+    #[error("Sentence contains non-ASCII characters")]
+    NonAscii,
+
+    #[error("Checksum mismatch: expected {expected:02X}, calculated {calculated:02X}")]
+    ChecksumMismatch { expected: u8, calculated: u8 },
+
+    #[error("Invalid checksum field '{0}'")]
+    InvalidChecksum(String),
+
+    #[error("Unsupported sentence type '{0}'")]
+    Unsupported(String),
+
+    #[error("Invalid field {index} in {sentence}: '{value}'")]
+    InvalidField {
+        sentence: String,
+        index: usize,
+        value: String,
+    },
+
+    #[error("Invalid AIS payload: {0}")]
+    InvalidAisPayload(String),
+}
+
+/// A parsed sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sentence {
+    /// Talker ID (e.g. `GP`, `II`).
+    pub talker: String,
+    /// Sentence type (e.g. `RMC`).
+    pub sentence_type: String,
+    /// SignalK values carried by the sentence.
+    pub values: Vec<PathValue>,
+}
+
+/// Parse a single NMEA 0183 sentence.
+///
+/// The checksum after `*` is validated when present. Sentences without a
+/// valid fix (e.g. RMC status `V`) parse successfully with no values.
+/// Sentences are ASCII only; line noise decoded as anything else is
+/// rejected.
+pub fn parse_sentence(line: &str) -> Result<Sentence, Nmea0183Error> {
+    let line = line.trim();
+    if !line.is_ascii() {
+        return Err(Nmea0183Error::NonAscii);
+    }
+    let body = line
+        .strip_prefix('$')
+        .or_else(|| line.strip_prefix('!'))
+        .ok_or(Nmea0183Error::MissingStart)?;
+
+    let body = match body.split_once('*') {
+        Some((data, checksum)) => {
+            verify_checksum(data, checksum)?;
+            data
+        }
+        None => body,
+    };
+
+    let fields: Vec<&str> = body.split(',').collect();
+    let address = fields[0];
+    if address.len() < 5 {
+        return Err(Nmea0183Error::TooShort);
+    }
+    let (talker, sentence_type) = address.split_at(address.len() - 3);
+
+    let values = match sentence_type {
+        "RMC" => parse_rmc(&fields)?,
+        "GGA" => parse_gga(&fields)?,
+        "GLL" => parse_gll(&fields)?,
+        "VTG" => parse_vtg(&fields)?,
+        "HDG" => parse_hdg(&fields)?,
+        "MWV" => parse_mwv(&fields)?,
+        "DBT" => parse_dbt(&fields)?,
+        other => return Err(Nmea0183Error::Unsupported(other.to_string())),
+    };
+
+    Ok(Sentence {
+        talker: talker.to_string(),
+        sentence_type: sentence_type.to_string(),
+        values,
+    })
+}
+
+pub(crate) fn verify_checksum(data: &str, checksum: &str) -> Result<(), Nmea0183Error> {
+    let expected = u8::from_str_radix(checksum.trim(), 16)
+        .map_err(|_| Nmea0183Error::InvalidChecksum(checksum.to_string()))?;
+    let calculated = data.bytes().fold(0u8, |acc, b| acc ^ b);
+
+    if expected != calculated {
+        return Err(Nmea0183Error::ChecksumMismatch {
+            expected,
+            calculated,
+        });
+    }
+    Ok(())
+}
+
+/// RMC - Recommended Minimum Navigation Information.
+///
+/// `$GPRMC,time,status,lat,N/S,lon,E/W,sog,cog,date,magvar,E/W*hh`
+fn parse_rmc(fields: &[&str]) -> Result<Vec<PathValue>, Nmea0183Error> {
+    if fields.len() < 10 {
+        return Err(Nmea0183Error::TooShort);
+    }
+    if fields[2] != "A" {
+        return Ok(Vec::new());
+    }
+
+    let mut values = Vec::new();
+    if let Some(position) = parse_position("RMC", fields, 3)? {
+        values.push(position);
+    }
+    if let Some(sog) = parse_optional_f64("RMC", fields, 7)? {
+        values.push(path_value(
+            "navigation.speedOverGround",
+            json!(sog * KNOTS_TO_MS),
+        ));
+    }
+    if let Some(cog) = parse_optional_f64("RMC", fields, 8)? {
+        values.push(path_value(
+            "navigation.courseOverGroundTrue",
+            json!(cog.to_radians()),
+        ));
+    }
+    Ok(values)
+}
+
+/// GGA - Global Positioning System Fix Data.
+///
+/// `$GPGGA,time,lat,N/S,lon,E/W,quality,satellites,hdop,alt,M,...*hh`
+fn parse_gga(fields: &[&str]) -> Result<Vec<PathValue>, Nmea0183Error> {
+    if fields.len() < 10 {
+        return Err(Nmea0183Error::TooShort);
+    }
+    if fields[6].is_empty() || fields[6] == "0" {
+        return Ok(Vec::new());
+    }
+
+    let mut values = Vec::new();
+    if let Some(position) = parse_position("GGA", fields, 2)? {
+        values.push(position);
+    }
+    if let Some(satellites) = parse_optional_f64("GGA", fields, 7)? {
+        values.push(path_value(
+            "navigation.gnss.satellites",
+            json!(satellites as u32),
+        ));
+    }
+    if let Some(altitude) = parse_optional_f64("GGA", fields, 9)? {
+        values.push(path_value(
+            "navigation.gnss.antennaAltitude",
+            json!(altitude),
+        ));
+    }
+    Ok(values)
+}
+
+/// GLL - Geographic Position, Latitude/Longitude.
+///
+/// `$GPGLL,lat,N/S,lon,E/W,time,status,mode*hh`
+fn parse_gll(fields: &[&str]) -> Result<Vec<PathValue>, Nmea0183Error> {
+    if fields.len() < 5 {
+        return Err(Nmea0183Error::TooShort);
+    }
+    // Status was only added in NMEA 2.0; older sentences are always valid
+    if fields.get(6).is_some_and(|status| *status != "A") {
+        return Ok(Vec::new());
+    }
+
+    Ok(parse_position("GLL", fields, 1)?.into_iter().collect())
+}
+
+/// VTG - Track Made Good and Ground Speed.
+///
+/// `$GPVTG,cogT,T,cogM,M,sog,N,sog,K,mode*hh`
+fn parse_vtg(fields: &[&str]) -> Result<Vec<PathValue>, Nmea0183Error> {
+    if fields.len() < 9 {
+        return Err(Nmea0183Error::TooShort);
+    }
+    if fields.get(9).is_some_and(|mode| *mode == "N") {
+        return Ok(Vec::new());
+    }
+
+    let mut values = Vec::new();
+    if let Some(cog) = parse_optional_f64("VTG", fields, 1)? {
+        values.push(path_value(
+            "navigation.courseOverGroundTrue",
+            json!(cog.to_radians()),
+        ));
+    }
+    if let Some(cog) = parse_optional_f64("VTG", fields, 3)? {
+        values.push(path_value(
+            "navigation.courseOverGroundMagnetic",
+            json!(cog.to_radians()),
+        ));
+    }
+    let sog = match parse_optional_f64("VTG", fields, 5)? {
+        Some(knots) => Some(knots * KNOTS_TO_MS),
+        None => parse_optional_f64("VTG", fields, 7)?.map(|kmh| kmh * KMH_TO_MS),
+    };
+    if let Some(sog) = sog {
+        values.push(path_value("navigation.speedOverGround", json!(sog)));
+    }
+    Ok(values)
+}
+
+/// HDG - Heading, Deviation and Variation.
+///
+/// `$HCHDG,heading,deviation,E/W,variation,E/W*hh`
+fn parse_hdg(fields: &[&str]) -> Result<Vec<PathValue>, Nmea0183Error> {
+    if fields.len() < 6 {
+        return Err(Nmea0183Error::TooShort);
+    }
+
+    let mut values = Vec::new();
+    if let Some(heading) = parse_optional_f64("HDG", fields, 1)? {
+        values.push(path_value(
+            "navigation.headingMagnetic",
+            json!(heading.to_radians()),
+        ));
+    }
+    if let Some(deviation) = parse_signed_angle("HDG", fields, 2)? {
+        values.push(path_value("navigation.magneticDeviation", json!(deviation)));
+    }
+    if let Some(variation) = parse_signed_angle("HDG", fields, 4)? {
+        values.push(path_value("navigation.magneticVariation", json!(variation)));
+    }
+    Ok(values)
+}
+
+/// MWV - Wind Speed and Angle.
+///
+/// `$IIMWV,angle,R/T,speed,K/M/N/S,status*hh`
+fn parse_mwv(fields: &[&str]) -> Result<Vec<PathValue>, Nmea0183Error> {
+    if fields.len() < 6 {
+        return Err(Nmea0183Error::TooShort);
+    }
+    if fields[5] != "A" {
+        return Ok(Vec::new());
+    }
+    let (angle_path, speed_path) = match fields[2] {
+        "R" => (
+            "environment.wind.angleApparent",
+            "environment.wind.speedApparent",
+        ),
+        "T" => (
+            "environment.wind.angleTrueWater",
+            "environment.wind.speedTrue",
+        ),
+        other => return Err(invalid_field("MWV", 2, other)),
+    };
+
+    let mut values = Vec::new();
+    if let Some(angle) = parse_optional_f64("MWV", fields, 1)? {
+        // SignalK wind angles are -π..π, positive to starboard
+        let angle = if angle > 180.0 { angle - 360.0 } else { angle };
+        values.push(path_value(angle_path, json!(angle.to_radians())));
+    }
+    if let Some(speed) = parse_optional_f64("MWV", fields, 3)? {
+        let to_ms = match fields[4] {
+            "K" => KMH_TO_MS,
+            "M" => 1.0,
+            "N" => KNOTS_TO_MS,
+            "S" => MPH_TO_MS,
+            other => return Err(invalid_field("MWV", 4, other)),
+        };
+        values.push(path_value(speed_path, json!(speed * to_ms)));
+    }
+    Ok(values)
+}
+
+/// DBT - Depth Below Transducer.
+///
+/// `$IIDBT,feet,f,metres,M,fathoms,F*hh`
+fn parse_dbt(fields: &[&str]) -> Result<Vec<PathValue>, Nmea0183Error> {
+    if fields.len() < 7 {
+        return Err(Nmea0183Error::TooShort);
+    }
+
+    // Prefer metres; some sounders only fill in one of the units
+    let depth = match parse_optional_f64("DBT", fields, 3)? {
+        Some(metres) => Some(metres),
+        None => match parse_optional_f64("DBT", fields, 1)? {
+            Some(feet) => Some(feet * FEET_TO_M),
+            None => parse_optional_f64("DBT", fields, 5)?.map(|fathoms| fathoms * FATHOMS_TO_M),
+        },
+    };
+    Ok(depth
+        .map(|depth| path_value("environment.depth.belowTransducer", json!(depth)))
+        .into_iter()
+        .collect())
+}
+
+/// Parse an angle in degrees followed by its `E`/`W` field into radians,
+/// west negative.
+fn parse_signed_angle(
+    sentence: &str,
+    fields: &[&str],
+    index: usize,
+) -> Result<Option<f64>, Nmea0183Error> {
+    let Some(degrees) = parse_optional_f64(sentence, fields, index)? else {
+        return Ok(None);
+    };
+    match fields[index + 1] {
+        "E" => Ok(Some(degrees.to_radians())),
+        "W" => Ok(Some(-degrees.to_radians())),
+        other => Err(invalid_field(sentence, index + 1, other)),
+    }
+}
+
+/// Parse `ddmm.mmmm,N,dddmm.mmmm,E` starting at `index` into a position.
+fn parse_position(
+    sentence: &str,
+    fields: &[&str],
+    index: usize,
+) -> Result<Option<PathValue>, Nmea0183Error> {
+    if fields[index].is_empty() || fields[index + 2].is_empty() {
+        return Ok(None);
+    }
+
+    let latitude = parse_coordinate(sentence, fields, index, 2, 'S')?;
+    let longitude = parse_coordinate(sentence, fields, index + 2, 3, 'W')?;
+
+    Ok(Some(path_value(
+        "navigation.position",
+        json!({ "latitude": latitude, "longitude": longitude }),
+    )))
+}
+
+/// Parse a `(d)ddmm.mmmm` coordinate followed by its hemisphere field.
+fn parse_coordinate(
+    sentence: &str,
+    fields: &[&str],
+    index: usize,
+    degree_digits: usize,
+    negative: char,
+) -> Result<f64, Nmea0183Error> {
+    let raw = fields[index];
+    let invalid = || invalid_field(sentence, index, raw);
+
+    if raw.len() <= degree_digits || !raw.is_char_boundary(degree_digits) {
+        return Err(invalid());
+    }
+    let (degrees, minutes) = raw.split_at(degree_digits);
+    let degrees: f64 = degrees.parse().map_err(|_| invalid())?;
+    let minutes: f64 = minutes.parse().map_err(|_| invalid())?;
+    let value = degrees + minutes / 60.0;
+
+    match fields[index + 1].chars().next() {
+        Some(h) if h == negative => Ok(-value),
+        Some(_) => Ok(value),
+        None => Err(invalid_field(sentence, index + 1, fields[index + 1])),
+    }
+}
+
+fn parse_optional_f64(
+    sentence: &str,
+    fields: &[&str],
+    index: usize,
+) -> Result<Option<f64>, Nmea0183Error> {
+    match fields.get(index) {
+        None | Some(&"") => Ok(None),
+        Some(raw) => raw
+            .parse()
+            .map(Some)
+            .map_err(|_| invalid_field(sentence, index, raw)),
+    }
+}
+
+pub(crate) fn invalid_field(sentence: &str, index: usize, value: &str) -> Nmea0183Error {
+    Nmea0183Error::InvalidField {
+        sentence: sentence.to_string(),
+        index,
+        value: value.to_string(),
+    }
+}
+
+pub(crate) fn path_value(path: &str, value: serde_json::Value) -> PathValue {
+    PathValue {
+        path: path.to_string(),
+        value,
+    }
+}
+
+/// Configuration for one NMEA 0183 input (typically one physical port).
+///
+/// ```json
+/// { "label": "nmea0183", "talkerLabels": { "GP": "GPS-bow" } }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Nmea0183Config {
+    /// Source label for this input, e.g. `nmea0183` or `serial-COM1`.
+    pub label: String,
+
+    /// Friendly source labels keyed by talker ID.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub talker_labels: HashMap<String, String>,
+}
+
+impl Nmea0183Config {
+    /// Create a configuration without talker mappings.
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            talker_labels: HashMap::new(),
+        }
+    }
+
+    /// Map `talker` to a friendly source label.
+    pub fn with_talker_label(
+        mut self,
+        talker: impl Into<String>,
+        label: impl Into<String>,
+    ) -> Self {
+        self.talker_labels.insert(talker.into(), label.into());
+        self
+    }
+
+    /// Source label for sentences from `talker`.
+    ///
+    /// Mapped talkers use their configured label as-is; others fall back to
+    /// `<label>.<talker>` (e.g. `nmea0183.GP`).
+    pub fn source_label(&self, talker: &str) -> String {
+        match self.talker_labels.get(talker) {
+            Some(label) => label.clone(),
+            None => format!("{}.{}", self.label, talker),
+        }
+    }
+}
+
+/// Feeds parsed sentences into a [`DeltaSink`].
+///
+/// Each sentence with values becomes one delta for `vessels.self` with
+/// `$source` set by [`Nmea0183Config::source_label`]. The talker ID is kept
+/// in the `source` object either way. AIS sentences (`!AIVDM`) become
+/// deltas for the vessel they report on.
+pub struct Nmea0183Driver<S> {
+    config: Nmea0183Config,
+    sink: S,
+    ais: Mutex<AisDecoder>,
+}
+
+impl<S: DeltaSink> Nmea0183Driver<S> {
+    /// Create a driver labelling its deltas with `source_label`.
+    pub fn new(source_label: impl Into<String>, sink: S) -> Self {
+        Self::with_config(Nmea0183Config::new(source_label), sink)
+    }
+
+    /// Create a driver from a full configuration.
+    pub fn with_config(config: Nmea0183Config, sink: S) -> Self {
+        Self {
+            config,
+            sink,
+            ais: Mutex::new(AisDecoder::new()),
+        }
+    }
+
+    /// The sink deltas are submitted to.
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Parse one line and submit the resulting delta, if any.
+    pub fn handle_line(&self, line: &str) -> Result<(), Nmea0183Error> {
+        if line.trim_start().starts_with('!') {
+            return self.handle_ais(line);
+        }
+        let sentence = parse_sentence(line)?;
+        if sentence.values.is_empty() {
+            return Ok(());
+        }
+
+        let label = match self.config.talker_labels.get(&sentence.talker) {
+            Some(label) => label.clone(),
+            None => self.config.label.clone(),
+        };
+        self.sink.submit(Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some(self.config.source_label(&sentence.talker)),
+                source: Some(Source {
+                    label,
+                    source_type: Some("NMEA0183".to_string()),
+                    src: None,
+                    can_name: None,
+                    pgn: None,
+                    sentence: Some(sentence.sentence_type),
+                    talker: Some(sentence.talker),
+                    ais_type: None,
+                }),
+                timestamp: None,
+                values: sentence.values,
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+        Ok(())
+    }
+
+    fn handle_ais(&self, line: &str) -> Result<(), Nmea0183Error> {
+        let message = self
+            .ais
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .decode(line)?;
+        let Some(message) = message else {
+            return Ok(());
+        };
+        if message.values.is_empty() {
+            return Ok(());
+        }
+
+        let context = message.context();
+        let AisMessage {
+            message_type,
+            talker,
+            values,
+            own_vessel,
+            ..
+        } = message;
+        let label = match self.config.talker_labels.get(&talker) {
+            Some(label) => label.clone(),
+            None => self.config.label.clone(),
+        };
+        self.sink.submit(Delta {
+            context: Some(context),
+            updates: vec![Update {
+                source_ref: Some(self.config.source_label(&talker)),
+                source: Some(Source {
+                    label,
+                    source_type: Some("NMEA0183".to_string()),
+                    src: None,
+                    can_name: None,
+                    pgn: None,
+                    sentence: Some(if own_vessel { "VDO" } else { "VDM" }.to_string()),
+                    talker: Some(talker),
+                    ais_type: Some(message_type),
+                }),
+                timestamp: None,
+                values,
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const RMC: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+
+    fn approx(value: &serde_json::Value, expected: f64) {
+        let actual = value.as_f64().unwrap();
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_parse_rmc() {
+        let sentence = parse_sentence(RMC).unwrap();
+
+        assert_eq!(sentence.talker, "GP");
+        assert_eq!(sentence.sentence_type, "RMC");
+        assert_eq!(sentence.values[0].path, "navigation.position");
+        approx(&sentence.values[0].value["latitude"], 48.1173);
+        approx(&sentence.values[0].value["longitude"], 11.516_666_666);
+        approx(&sentence.values[1].value, 22.4 * KNOTS_TO_MS);
+        approx(&sentence.values[2].value, 84.4_f64.to_radians());
+    }
+
+    #[test]
+    fn test_parse_gga() {
+        let sentence = parse_sentence(GGA).unwrap();
+
+        assert_eq!(sentence.values[1].path, "navigation.gnss.satellites");
+        assert_eq!(sentence.values[1].value, json!(8));
+        approx(&sentence.values[2].value, 545.4);
+    }
+
+    #[test]
+    fn test_parse_gll() {
+        let sentence = parse_sentence("$GPGLL,4916.45,N,12311.12,W,225444,A,A*5C").unwrap();
+
+        assert_eq!(sentence.values[0].path, "navigation.position");
+        approx(&sentence.values[0].value["latitude"], 49.274_166_666);
+        approx(&sentence.values[0].value["longitude"], -123.185_333_333);
+        let void = parse_sentence("$GPGLL,4916.45,N,12311.12,W,225444,V,N*44").unwrap();
+        assert!(void.values.is_empty());
+    }
+
+    #[test]
+    fn test_parse_vtg() {
+        let sentence = parse_sentence("$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K,A*25").unwrap();
+
+        assert_eq!(sentence.values[0].path, "navigation.courseOverGroundTrue");
+        approx(&sentence.values[0].value, 54.7_f64.to_radians());
+        assert_eq!(
+            sentence.values[1].path,
+            "navigation.courseOverGroundMagnetic"
+        );
+        approx(&sentence.values[1].value, 34.4_f64.to_radians());
+        assert_eq!(sentence.values[2].path, "navigation.speedOverGround");
+        approx(&sentence.values[2].value, 5.5 * KNOTS_TO_MS);
+    }
+
+    #[test]
+    fn test_parse_hdg() {
+        let sentence = parse_sentence("$HCHDG,101.1,,,7.1,W*3C").unwrap();
+
+        assert_eq!(sentence.talker, "HC");
+        assert_eq!(sentence.values.len(), 2);
+        assert_eq!(sentence.values[0].path, "navigation.headingMagnetic");
+        approx(&sentence.values[0].value, 101.1_f64.to_radians());
+        assert_eq!(sentence.values[1].path, "navigation.magneticVariation");
+        approx(&sentence.values[1].value, -7.1_f64.to_radians());
+    }
+
+    #[test]
+    fn test_parse_mwv() {
+        let apparent = parse_sentence("$IIMWV,214.8,R,10.5,N,A*06").unwrap();
+        assert_eq!(apparent.values[0].path, "environment.wind.angleApparent");
+        approx(&apparent.values[0].value, (214.8_f64 - 360.0).to_radians());
+        assert_eq!(apparent.values[1].path, "environment.wind.speedApparent");
+        approx(&apparent.values[1].value, 10.5 * KNOTS_TO_MS);
+
+        let true_wind = parse_sentence("$WIMWV,045.0,T,5.1,M,A*23").unwrap();
+        assert_eq!(true_wind.values[0].path, "environment.wind.angleTrueWater");
+        approx(&true_wind.values[0].value, 45.0_f64.to_radians());
+        assert_eq!(true_wind.values[1].path, "environment.wind.speedTrue");
+        approx(&true_wind.values[1].value, 5.1);
+    }
+
+    #[test]
+    fn test_parse_dbt() {
+        let sentence = parse_sentence("$IIDBT,036.41,f,011.10,M,005.99,F*25").unwrap();
+        assert_eq!(sentence.values[0].path, "environment.depth.belowTransducer");
+        approx(&sentence.values[0].value, 11.1);
+
+        // Feet only
+        let sentence = parse_sentence("$IIDBT,036.41,f,,,,*2A").unwrap();
+        approx(&sentence.values[0].value, 36.41 * FEET_TO_M);
+    }
+
+    #[test]
+    fn test_invalid_checksum() {
+        let line = RMC.replace("*6A", "*00");
+        assert!(matches!(
+            parse_sentence(&line),
+            Err(Nmea0183Error::ChecksumMismatch { expected: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_malformed_sentences() {
+        assert_eq!(parse_sentence("GPRMC,1"), Err(Nmea0183Error::MissingStart));
+        assert_eq!(parse_sentence("$GP"), Err(Nmea0183Error::TooShort));
+        assert!(matches!(
+            parse_sentence("$GPXXX,1,2"),
+            Err(Nmea0183Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_non_ascii_sentences() {
+        // Line noise decoded by `from_utf8_lossy` must not panic
+        assert_eq!(parse_sentence("$GPR€X,1"), Err(Nmea0183Error::NonAscii));
+        assert_eq!(
+            parse_sentence("$GPGLL,4\u{fffd}16.45,N,12311.12,W,225444,A,A"),
+            Err(Nmea0183Error::NonAscii)
+        );
+    }
+
+    #[test]
+    fn test_driver_submits_to_sink() {
+        let driver = Nmea0183Driver::new("nmea0183", Mutex::new(Vec::new()));
+
+        driver.handle_line(RMC).unwrap();
+        driver.handle_line(GGA).unwrap();
+        // No fix: parsed but nothing to submit
+        driver
+            .handle_line("$GPRMC,123519,V,,,,,,,230394,,*33")
+            .unwrap();
+        assert!(driver.handle_line("$GPRMC,garbage*00").is_err());
+
+        let deltas = driver.sink.lock().unwrap();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(
+            deltas[0].updates[0].source_ref.as_deref(),
+            Some("nmea0183.GP")
+        );
+        assert_eq!(
+            deltas[1].updates[0]
+                .source
+                .as_ref()
+                .unwrap()
+                .sentence
+                .as_deref(),
+            Some("GGA")
+        );
+    }
+
+    #[test]
+    fn test_talker_label_mapping() {
+        let config: Nmea0183Config =
+            serde_json::from_str(r#"{"label":"serial-COM1","talkerLabels":{"GP":"GPS-bow"}}"#)
+                .unwrap();
+        assert_eq!(
+            config,
+            Nmea0183Config::new("serial-COM1").with_talker_label("GP", "GPS-bow")
+        );
+
+        let driver = Nmea0183Driver::with_config(config, Mutex::new(Vec::new()));
+        driver.handle_line(RMC).unwrap();
+        driver
+            .handle_line("$IIRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*7D")
+            .unwrap();
+
+        let deltas = driver.sink.lock().unwrap();
+        let update = &deltas[0].updates[0];
+        assert_eq!(update.source_ref.as_deref(), Some("GPS-bow"));
+        let source = update.source.as_ref().unwrap();
+        assert_eq!(source.label, "GPS-bow");
+        assert_eq!(source.talker.as_deref(), Some("GP"));
+
+        // Unmapped talkers keep the default label
+        assert_eq!(
+            deltas[1].updates[0].source_ref.as_deref(),
+            Some("serial-COM1.II")
+        );
+    }
+
+    #[test]
+    fn test_driver_submits_ais_targets() {
+        let driver = Nmea0183Driver::new("nmea0183", Mutex::new(Vec::new()));
+        driver
+            .handle_line("!AIVDM,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5C")
+            .unwrap();
+
+        let deltas = driver.sink.lock().unwrap();
+        assert_eq!(
+            deltas[0].context.as_deref(),
+            Some("vessels.urn:mrn:imo:mmsi:477553000")
+        );
+        let update = &deltas[0].updates[0];
+        assert_eq!(update.source_ref.as_deref(), Some("nmea0183.AI"));
+        assert_eq!(update.source.as_ref().unwrap().ais_type, Some(1));
+        assert!(update
+            .values
+            .iter()
+            .any(|pv| pv.path == "navigation.position"));
+    }
+}
@@ -0,0 +1,423 @@
+//! Providers configured at runtime.
+//!
+//! [`ProviderConfig`] is the persisted form of one data source, as the
+//! Admin UI's connections page edits it. [`build_provider`] turns it into a
+//! [`Provider`] the server can start, stop and ask for its status, without
+//! knowing what kind of source it reads.
+//!
+//! Like the providers they wrap, configured providers hand their deltas to
+//! a [`DeltaSink`]; the server passes its event sink, usually wrapped by its
+//! provider registry so the connections page sees their state as well.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use signalk_core::{Delta, DeltaSink, ProviderState};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::file::{FileReplayProvider, DEFAULT_LINE_INTERVAL};
+use crate::serial::Parity;
+use crate::tcp::TcpProvider;
+use crate::udp::UdpProvider;
+
+/// Configuration of one data source.
+///
+/// Serialized with a `type` tag, e.g.
+/// `{"type": "nmea0183Tcp", "id": "mux", "address": "192.168.1.5:10110"}`.
+/// The `id` also labels the deltas of NMEA 0183 sources (`$source`
+/// `mux.GP`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ProviderConfig {
+    /// NMEA 0183 sentences from a TCP server.
+    Nmea0183Tcp { id: String, address: SocketAddr },
+    /// NMEA 0183 datagrams received on `address`; a multicast address
+    /// joins that group.
+    Nmea0183Udp { id: String, address: SocketAddr },
+    /// NMEA 0183 sentences from a serial port.
+    Nmea0183Serial {
+        id: String,
+        /// Device path, e.g. `/dev/ttyUSB0`.
+        device: String,
+        #[serde(default = "default_baud_rate")]
+        baud_rate: u32,
+        #[serde(default)]
+        parity: Parity,
+    },
+    /// Deltas or NMEA 0183 sentences replayed from a file.
+    FileReplay {
+        id: String,
+        path: PathBuf,
+        /// Pause between lines, in milliseconds.
+        #[serde(default = "default_line_interval_ms")]
+        line_interval_ms: u64,
+        /// Start over at the end of the file.
+        #[serde(default, rename = "loop")]
+        looping: bool,
+    },
+}
+
+fn default_baud_rate() -> u32 {
+    4800
+}
+
+fn default_line_interval_ms() -> u64 {
+    DEFAULT_LINE_INTERVAL.as_millis() as u64
+}
+
+impl ProviderConfig {
+    /// Config storage key the Linux server loads provider configs from.
+    pub const STORAGE_KEY: &'static str = "providers.json";
+
+    /// The provider's identifier.
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Nmea0183Tcp { id, .. }
+            | Self::Nmea0183Udp { id, .. }
+            | Self::Nmea0183Serial { id, .. }
+            | Self::FileReplay { id, .. } => id,
+        }
+    }
+
+    /// Provider type as the connections page shows it.
+    pub fn provider_type(&self) -> &'static str {
+        match self {
+            Self::Nmea0183Tcp { .. } | Self::Nmea0183Udp { .. } | Self::Nmea0183Serial { .. } => {
+                "NMEA0183"
+            }
+            Self::FileReplay { .. } => "FileReplay",
+        }
+    }
+}
+
+/// A data source the server can start and stop.
+///
+/// `start` spawns onto the current tokio runtime.
+pub trait Provider: Send {
+    /// Identifier, unique among the server's providers.
+    fn id(&self) -> &str;
+
+    /// The configuration the provider was built from.
+    fn config(&self) -> &ProviderConfig;
+
+    /// Start reading from the source into `sink`, restarting if running.
+    fn start(&mut self, sink: Arc<dyn DeltaSink>);
+
+    /// Stop reading; does nothing if not running.
+    fn stop(&mut self);
+
+    /// Connection state, `None` while stopped.
+    fn status(&self) -> Option<ProviderState>;
+}
+
+/// Build the provider for `config`. It is stopped until started.
+pub fn build_provider(config: ProviderConfig) -> Box<dyn Provider> {
+    Box::new(ConfiguredProvider {
+        config,
+        running: None,
+    })
+}
+
+/// A provider running its source in a task of its own.
+struct ConfiguredProvider {
+    config: ProviderConfig,
+    running: Option<Running>,
+}
+
+struct Running {
+    /// `None` if the source failed before there was anything to run.
+    task: Option<JoinHandle<()>>,
+    state: Arc<Mutex<ProviderState>>,
+    /// Set on stop, for sources reading on a blocking thread that
+    /// aborting the task can't interrupt.
+    stop: Arc<AtomicBool>,
+}
+
+impl Provider for ConfiguredProvider {
+    fn id(&self) -> &str {
+        self.config.id()
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.config
+    }
+
+    fn start(&mut self, sink: Arc<dyn DeltaSink>) {
+        self.stop();
+        let state = Arc::new(Mutex::new(ProviderState::Connecting));
+        let sink = StatusSink {
+            inner: sink,
+            state: state.clone(),
+        };
+        sink.report_state(ProviderState::Connecting);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let task = match self.config.clone() {
+            ProviderConfig::Nmea0183Tcp { id, address } => {
+                Some(TcpProvider::connect(address, &id, sink))
+            }
+            ProviderConfig::Nmea0183Udp { id, address } => Some(tokio::spawn(async move {
+                let provider = if address.ip().is_multicast() {
+                    UdpProvider::bind_multicast(address, &id, sink.clone()).await
+                } else {
+                    UdpProvider::bind(address, &id, sink.clone()).await
+                };
+                match provider {
+                    Ok(provider) => provider.run().await,
+                    Err(e) => {
+                        warn!("Could not listen for NMEA 0183 on UDP {}: {}", address, e);
+                        sink.report_state(ProviderState::Error(e.to_string()));
+                    }
+                }
+            })),
+            #[cfg(feature = "serial")]
+            ProviderConfig::Nmea0183Serial {
+                id,
+                device,
+                baud_rate,
+                parity,
+            } => {
+                let stop = stop.clone();
+                Some(tokio::task::spawn_blocking(move || {
+                    crate::serial::SerialProvider::new(&device, baud_rate, &id, sink)
+                        .with_parity(parity)
+                        .run(&stop)
+                }))
+            }
+            #[cfg(not(feature = "serial"))]
+            ProviderConfig::Nmea0183Serial { device, .. } => {
+                warn!("Serial NMEA 0183 input is not built in ({})", device);
+                sink.report_state(ProviderState::Error(
+                    "Serial port support requires the `serial` feature".to_string(),
+                ));
+                None
+            }
+            ProviderConfig::FileReplay {
+                id,
+                path,
+                line_interval_ms,
+                looping,
+            } => {
+                let provider = FileReplayProvider::new(path, &id, sink)
+                    .with_line_interval(Duration::from_millis(line_interval_ms))
+                    .looping(looping);
+                Some(tokio::spawn(provider.run()))
+            }
+        };
+        self.running = Some(Running { task, state, stop });
+    }
+
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.stop.store(true, Ordering::Relaxed);
+            if let Some(task) = running.task {
+                task.abort();
+            }
+        }
+    }
+
+    fn status(&self) -> Option<ProviderState> {
+        self.running
+            .as_ref()
+            .map(|running| lock_state(&running.state).clone())
+    }
+}
+
+impl Drop for ConfiguredProvider {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Passes deltas on, keeping the last reported state for
+/// [`Provider::status`].
+#[derive(Clone)]
+struct StatusSink {
+    inner: Arc<dyn DeltaSink>,
+    state: Arc<Mutex<ProviderState>>,
+}
+
+impl DeltaSink for StatusSink {
+    fn submit(&self, delta: Delta) {
+        self.inner.submit(delta);
+    }
+
+    fn report_state(&self, state: ProviderState) {
+        *lock_state(&self.state) = state.clone();
+        self.inner.report_state(state);
+    }
+}
+
+/// Lock a provider's state (a plain value, so a poisoned lock is still
+/// usable).
+fn lock_state(state: &Mutex<ProviderState>) -> std::sync::MutexGuard<'_, ProviderState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(json: serde_json::Value) -> Box<dyn Provider> {
+        build_provider(serde_json::from_value(json).unwrap())
+    }
+
+    #[test]
+    fn test_build_from_json() {
+        let tcp = build(serde_json::json!({
+            "type": "nmea0183Tcp", "id": "mux", "address": "192.168.1.5:10110"
+        }));
+        assert_eq!(tcp.id(), "mux");
+        assert!(matches!(
+            tcp.config(),
+            ProviderConfig::Nmea0183Tcp { address, .. } if address.port() == 10110
+        ));
+        assert_eq!(tcp.config().provider_type(), "NMEA0183");
+
+        let udp = build(serde_json::json!({
+            "type": "nmea0183Udp", "id": "gateway", "address": "239.2.1.1:10110"
+        }));
+        assert_eq!(udp.id(), "gateway");
+        assert!(matches!(udp.config(), ProviderConfig::Nmea0183Udp { .. }));
+
+        let serial = build(serde_json::json!({
+            "type": "nmea0183Serial", "id": "gps", "device": "/dev/ttyUSB0"
+        }));
+        assert_eq!(serial.id(), "gps");
+        assert!(matches!(
+            serial.config(),
+            ProviderConfig::Nmea0183Serial {
+                baud_rate: 4800,
+                parity: Parity::None,
+                ..
+            }
+        ));
+
+        let serial = build(serde_json::json!({
+            "type": "nmea0183Serial", "id": "ais", "device": "/dev/ttyS1",
+            "baudRate": 38400, "parity": "even"
+        }));
+        assert!(matches!(
+            serial.config(),
+            ProviderConfig::Nmea0183Serial {
+                baud_rate: 38400,
+                parity: Parity::Even,
+                ..
+            }
+        ));
+
+        let replay = build(serde_json::json!({
+            "type": "fileReplay", "id": "trip", "path": "/tmp/trip.log", "loop": true
+        }));
+        assert_eq!(replay.id(), "trip");
+        assert_eq!(replay.config().provider_type(), "FileReplay");
+        assert_eq!(
+            replay.config(),
+            &ProviderConfig::FileReplay {
+                id: "trip".to_string(),
+                path: "/tmp/trip.log".into(),
+                line_interval_ms: 100,
+                looping: true,
+            }
+        );
+        assert_eq!(replay.status(), None);
+    }
+
+    #[test]
+    fn test_unknown_type_rejected() {
+        let result = serde_json::from_value::<ProviderConfig>(serde_json::json!({
+            "type": "nmea2000Canbus", "id": "n2k"
+        }));
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_replay_start_stop() {
+        let path = std::env::temp_dir().join(format!("signalk-replay-{}.log", std::process::id()));
+        let delta = r#"{"context":"vessels.self","updates":[{"values":[{"path":"navigation.speedOverGround","value":3.85}]}]}"#;
+        std::fs::write(
+            &path,
+            format!("$IIDBT,036.41,f,011.10,M,005.99,F*25\n\n{delta}\n"),
+        )
+        .unwrap();
+
+        let mut provider = build_provider(ProviderConfig::FileReplay {
+            id: "trip".to_string(),
+            path: path.clone(),
+            line_interval_ms: 0,
+            looping: false,
+        });
+        let sink = Arc::new(Mutex::new(Vec::<Delta>::new()));
+        provider.start(sink.clone());
+
+        let deltas = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let deltas = sink.lock().unwrap().clone();
+                if deltas.len() == 2 {
+                    return deltas;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timed out waiting for deltas");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(deltas[0].updates[0].source_ref.as_deref(), Some("trip.II"));
+        assert_eq!(
+            deltas[1].updates[0].values[0].path,
+            "navigation.speedOverGround"
+        );
+        assert_eq!(provider.status(), Some(ProviderState::Connected));
+
+        provider.stop();
+        assert_eq!(provider.status(), None);
+    }
+
+    #[tokio::test]
+    async fn test_missing_file_reports_error() {
+        let mut provider = build_provider(ProviderConfig::FileReplay {
+            id: "trip".to_string(),
+            path: "/nonexistent/trip.log".into(),
+            line_interval_ms: 0,
+            looping: false,
+        });
+        provider.start(Arc::new(Mutex::new(Vec::<Delta>::new())));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !matches!(provider.status(), Some(ProviderState::Error(_))) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timed out waiting for the error");
+    }
+
+    #[cfg(feature = "serial")]
+    #[tokio::test]
+    async fn test_missing_serial_device_reports_error() {
+        let mut provider = build_provider(ProviderConfig::Nmea0183Serial {
+            id: "gps".to_string(),
+            device: "/nonexistent/ttyUSB0".to_string(),
+            baud_rate: 4800,
+            parity: Parity::None,
+        });
+        provider.start(Arc::new(Mutex::new(Vec::<Delta>::new())));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !matches!(provider.status(), Some(ProviderState::Error(_))) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timed out waiting for the error");
+        provider.stop();
+    }
+}
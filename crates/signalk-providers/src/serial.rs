@@ -0,0 +1,317 @@
+//! NMEA 0183 over a serial port.
+//!
+//! USB and RS-422 GPS receivers and instrument gateways send sentences
+//! over a serial line, usually at 4800 or 38400 baud. [`SerialLineReader`]
+//! splits what a port delivers into lines, holding on to a partial line
+//! across reads and read errors; it works over any [`Read`], so it is
+//! tested without hardware. [`SerialProvider`] (with the `serial` feature)
+//! opens the device, feeds the lines through an [`Nmea0183Driver`] and
+//! reopens the device whenever it fails.
+//!
+//! [`Nmea0183Driver`]: crate::nmea0183::Nmea0183Driver
+
+use std::io::{self, Read};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Longest incomplete line kept. NMEA 0183 sentences are at most 82
+/// characters; anything longer is noise, e.g. from a wrong baud rate.
+const MAX_PENDING: usize = 1024;
+
+/// Parity of a serial line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+/// Reads newline-terminated lines from a byte stream such as a serial
+/// port.
+pub struct SerialLineReader<R> {
+    reader: R,
+    /// Bytes read after the last complete line.
+    pending: Vec<u8>,
+}
+
+impl<R: Read> SerialLineReader<R> {
+    /// Read lines from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The next non-empty line, trimmed, or `None` once the stream ends.
+    ///
+    /// Read errors are returned as they are, but what was read of the
+    /// current line is kept, so after a transient error (such as a read
+    /// timeout) the caller can just call again.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.pending.drain(..=end).collect();
+                // Line noise shouldn't stop the stream
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                return Ok(Some(line.to_string()));
+            }
+            if self.pending.len() > MAX_PENDING {
+                debug!(
+                    "Discarding {} bytes of unterminated serial data",
+                    self.pending.len()
+                );
+                self.pending.clear();
+            }
+
+            let mut chunk = [0u8; 256];
+            match self.reader.read(&mut chunk) {
+                Ok(0) => return Ok(None),
+                Ok(len) => self.pending.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+}
+
+/// Whether a read error only means no data arrived in time.
+pub fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    )
+}
+
+#[cfg(feature = "serial")]
+pub use provider::SerialProvider;
+
+#[cfg(feature = "serial")]
+mod provider {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    use signalk_core::{DeltaSink, ProviderState};
+    use tracing::{debug, info, warn};
+
+    use super::{is_transient, Parity, SerialLineReader};
+    use crate::nmea0183::{Nmea0183Driver, Nmea0183Error};
+
+    /// How long a read waits for data, which is also how quickly a stop
+    /// request is noticed.
+    const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// Delay before the first attempt to reopen the device.
+    const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+    /// Longest delay between attempts to reopen the device.
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    impl From<Parity> for serialport::Parity {
+        fn from(parity: Parity) -> Self {
+            match parity {
+                Parity::None => serialport::Parity::None,
+                Parity::Odd => serialport::Parity::Odd,
+                Parity::Even => serialport::Parity::Even,
+            }
+        }
+    }
+
+    /// Reads NMEA 0183 sentences from a serial device into a
+    /// [`DeltaSink`].
+    pub struct SerialProvider<S> {
+        device: String,
+        baud_rate: u32,
+        parity: Parity,
+        driver: Nmea0183Driver<S>,
+    }
+
+    impl<S: DeltaSink + 'static> SerialProvider<S> {
+        /// Create a provider for `device` (e.g. `/dev/ttyUSB0`) at
+        /// `baud_rate`, labelling its deltas with `source_label` (e.g.
+        /// `serial0` gives `$source` `serial0.GP`).
+        pub fn new(device: &str, baud_rate: u32, source_label: &str, sink: S) -> Self {
+            Self {
+                device: device.to_string(),
+                baud_rate,
+                parity: Parity::None,
+                driver: Nmea0183Driver::new(source_label, sink),
+            }
+        }
+
+        /// Use `parity` instead of none.
+        pub fn with_parity(mut self, parity: Parity) -> Self {
+            self.parity = parity;
+            self
+        }
+
+        /// Read from the device until `stop` is set, reopening it with
+        /// backoff whenever it can't be opened or fails.
+        ///
+        /// Blocks the calling thread (run it with `spawn_blocking`). Each
+        /// attempt, connection and failure is reported to the sink.
+        pub fn run(self, stop: &AtomicBool) {
+            let mut backoff = INITIAL_BACKOFF;
+            while !stop.load(Ordering::Relaxed) {
+                self.report(ProviderState::Connecting);
+                let port = serialport::new(&self.device, self.baud_rate)
+                    .parity(self.parity.into())
+                    .timeout(READ_TIMEOUT)
+                    .open();
+                match port {
+                    Ok(port) => {
+                        info!("Opened NMEA 0183 serial device {}", self.device);
+                        self.report(ProviderState::Connected);
+                        backoff = INITIAL_BACKOFF;
+                        let error = self.read_sentences(SerialLineReader::new(port), stop);
+                        match error {
+                            Some(e) => {
+                                warn!("Lost NMEA 0183 serial device {}: {}", self.device, e);
+                                self.report(ProviderState::Error(e));
+                            }
+                            None => return,
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Could not open serial device {}: {}", self.device, e);
+                        self.report(ProviderState::Error(e.to_string()));
+                    }
+                }
+
+                debug!("Reopening {} in {:?}", self.device, backoff);
+                let until = Instant::now() + backoff;
+                while Instant::now() < until && !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(READ_TIMEOUT.min(until - Instant::now()));
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+
+        fn report(&self, state: ProviderState) {
+            self.driver.sink().report_state(state);
+        }
+
+        /// Feed lines to the driver until the device fails (returning why)
+        /// or `stop` is set (returning `None`).
+        fn read_sentences(
+            &self,
+            mut reader: SerialLineReader<Box<dyn serialport::SerialPort>>,
+            stop: &AtomicBool,
+        ) -> Option<String> {
+            while !stop.load(Ordering::Relaxed) {
+                let line = match reader.read_line() {
+                    Ok(Some(line)) => line,
+                    Ok(None) => return Some("Device closed".to_string()),
+                    // Quiet lines and glitches don't cost the port
+                    Err(e) if is_transient(&e) => continue,
+                    Err(e) => return Some(e.to_string()),
+                };
+                match self.driver.handle_line(&line) {
+                    Ok(()) => {}
+                    Err(e @ Nmea0183Error::Unsupported(_)) => debug!("{}: {}", self.device, e),
+                    Err(e) => warn!("Skipping sentence from {}: {} ({})", self.device, e, line),
+                }
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Hands out scripted reads, like a serial port would.
+    struct ScriptedReader(VecDeque<io::Result<&'static [u8]>>);
+
+    impl Read for ScriptedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Ok(data)) => {
+                    let len = data.len().min(buf.len());
+                    buf[..len].copy_from_slice(&data[..len]);
+                    if len < data.len() {
+                        self.0.push_front(Ok(&data[len..]));
+                    }
+                    Ok(len)
+                }
+                Some(Err(e)) => Err(e),
+                None => Ok(0),
+            }
+        }
+    }
+
+    fn reader(reads: Vec<io::Result<&'static [u8]>>) -> SerialLineReader<ScriptedReader> {
+        SerialLineReader::new(ScriptedReader(reads.into()))
+    }
+
+    #[test]
+    fn test_lines_split_across_reads() {
+        let mut lines = reader(vec![
+            Ok(b"$IIDBT,036.41,f,011.10,M,00"),
+            Ok(b"5.99,F*25\r\n$IIMWV,214.8,R,10.5,N,A*06\r\n\r\n$GPRMC"),
+        ]);
+        assert_eq!(
+            lines.read_line().unwrap().as_deref(),
+            Some("$IIDBT,036.41,f,011.10,M,005.99,F*25")
+        );
+        assert_eq!(
+            lines.read_line().unwrap().as_deref(),
+            Some("$IIMWV,214.8,R,10.5,N,A*06")
+        );
+        // The unterminated tail isn't a line
+        assert_eq!(lines.read_line().unwrap(), None);
+    }
+
+    #[test]
+    fn test_partial_line_survives_transient_errors() {
+        let mut lines = reader(vec![
+            Ok(b"$IIMWV,214.8,"),
+            Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")),
+            Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted")),
+            Ok(b"R,10.5,N,A*06\n"),
+        ]);
+        let error = lines.read_line().unwrap_err();
+        assert!(is_transient(&error));
+        assert_eq!(
+            lines.read_line().unwrap().as_deref(),
+            Some("$IIMWV,214.8,R,10.5,N,A*06")
+        );
+    }
+
+    #[test]
+    fn test_noise_discarded() {
+        static NOISE: [u8; 2000] = [0x55; 2000];
+        let mut lines = reader(vec![Ok(&NOISE), Ok(b"\n$IIMWV,214.8,R,10.5,N,A*06\n")]);
+        // The noise is dropped once it outgrows any sentence, so the line
+        // that ends it is only its tail
+        let first = lines.read_line().unwrap().unwrap();
+        assert!(first.len() < NOISE.len());
+        assert_eq!(
+            lines.read_line().unwrap().as_deref(),
+            Some("$IIMWV,214.8,R,10.5,N,A*06")
+        );
+    }
+
+    #[test]
+    fn test_parity_from_json() {
+        assert_eq!(
+            serde_json::from_str::<Parity>(r#""even""#).unwrap(),
+            Parity::Even
+        );
+        assert_eq!(Parity::default(), Parity::None);
+    }
+}
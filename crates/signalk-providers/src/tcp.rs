@@ -0,0 +1,119 @@
+//! NMEA 0183 over TCP.
+//!
+//! Multiplexers and chartplotters commonly serve NMEA 0183 on a TCP port
+//! (10110 by convention). [`TcpProvider`] reads sentences from such a port
+//! line by line and feeds them through an [`Nmea0183Driver`], reconnecting
+//! with exponential backoff whenever the connection drops.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use signalk_core::{DeltaSink, ProviderState};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::nmea0183::{Nmea0183Driver, Nmea0183Error};
+
+/// Delay before the first reconnection attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest delay between reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Reads NMEA 0183 sentences from a TCP server into a [`DeltaSink`].
+pub struct TcpProvider<S> {
+    addr: SocketAddr,
+    driver: Nmea0183Driver<S>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl<S: DeltaSink + 'static> TcpProvider<S> {
+    /// Create a provider for `addr`, labelling its deltas with
+    /// `source_label` (e.g. `nmea0183` gives `$source` `nmea0183.GP`).
+    pub fn new(addr: SocketAddr, source_label: &str, sink: S) -> Self {
+        Self {
+            addr,
+            driver: Nmea0183Driver::new(source_label, sink),
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+        }
+    }
+
+    /// Reconnect after `initial`, doubling up to `max` while attempts fail.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Spawn a provider for `addr` on the current tokio runtime.
+    pub fn connect(addr: SocketAddr, source_label: &str, sink: S) -> JoinHandle<()> {
+        tokio::spawn(Self::new(addr, source_label, sink).run())
+    }
+
+    /// Read from the server forever, reconnecting whenever the connection
+    /// fails or closes.
+    ///
+    /// Each attempt, connection and failure is reported to the sink.
+    pub async fn run(self) {
+        let mut backoff = self.initial_backoff;
+        loop {
+            self.report(ProviderState::Connecting);
+            match TcpStream::connect(self.addr).await {
+                Ok(stream) => {
+                    info!("Connected to NMEA 0183 source {}", self.addr);
+                    self.report(ProviderState::Connected);
+                    backoff = self.initial_backoff;
+                    match self.read_sentences(stream).await {
+                        Ok(()) => {
+                            info!("NMEA 0183 source {} closed the connection", self.addr);
+                            self.report(ProviderState::Error("Connection closed".to_string()));
+                        }
+                        Err(e) => {
+                            warn!("Lost NMEA 0183 source {}: {}", self.addr, e);
+                            self.report(ProviderState::Error(e.to_string()));
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("Could not connect to NMEA 0183 source {}: {}", self.addr, e);
+                    self.report(ProviderState::Error(e.to_string()));
+                }
+            }
+
+            debug!("Reconnecting to {} in {:?}", self.addr, backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+
+    fn report(&self, state: ProviderState) {
+        self.driver.sink().report_state(state);
+    }
+
+    /// Feed lines to the driver until the connection closes.
+    ///
+    /// Sentences that fail to parse are logged and skipped; only I/O errors
+    /// end the connection.
+    async fn read_sentences(&self, stream: TcpStream) -> std::io::Result<()> {
+        let mut lines = BufReader::new(stream).split(b'\n');
+        while let Some(line) = lines.next_segment().await? {
+            // Line noise shouldn't cost the connection
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match self.driver.handle_line(line) {
+                Ok(()) => {}
+                // Sources send plenty of sentences we don't map
+                Err(e @ Nmea0183Error::Unsupported(_)) => debug!("{}: {}", self.addr, e),
+                Err(e) => warn!("Skipping sentence from {}: {} ({})", self.addr, e, line),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! NMEA 0183 over UDP.
+//!
+//! Many gateways broadcast NMEA 0183 as UDP datagrams, often to a multicast
+//! group. A datagram may carry several newline-separated sentences, and a
+//! sentence may be split across datagrams, so [`UdpProvider`] buffers the
+//! incomplete tail of each sender's stream until the rest arrives.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use signalk_core::{DeltaSink, ProviderState};
+use tokio::net::UdpSocket;
+use tracing::{debug, warn};
+
+use crate::nmea0183::{Nmea0183Driver, Nmea0183Error};
+
+/// Largest datagram read at once.
+const MAX_DATAGRAM: usize = 65_536;
+
+/// Longest incomplete line kept per sender. NMEA 0183 sentences are at most
+/// 82 characters; anything longer is noise.
+const MAX_PENDING: usize = 1024;
+
+/// Reads NMEA 0183 sentences from UDP datagrams into a [`DeltaSink`].
+pub struct UdpProvider<S> {
+    socket: UdpSocket,
+    driver: Nmea0183Driver<S>,
+    /// Incomplete trailing line per sender.
+    pending: HashMap<SocketAddr, Vec<u8>>,
+}
+
+impl<S: DeltaSink + 'static> UdpProvider<S> {
+    /// Listen on `addr`, labelling deltas with `source_label` (e.g.
+    /// `nmea0183` gives `$source` `nmea0183.GP`).
+    pub async fn bind(addr: SocketAddr, source_label: &str, sink: S) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr).await?,
+            driver: Nmea0183Driver::new(source_label, sink),
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Listen on `group`'s port on all interfaces and join the multicast
+    /// group (e.g. `239.2.1.1:10110`).
+    pub async fn bind_multicast(
+        group: SocketAddr,
+        source_label: &str,
+        sink: S,
+    ) -> io::Result<Self> {
+        let unspecified: IpAddr = match group.ip() {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let provider = Self::bind(
+            SocketAddr::new(unspecified, group.port()),
+            source_label,
+            sink,
+        )
+        .await?;
+        provider.join_multicast(group.ip())?;
+        Ok(provider)
+    }
+
+    /// Join multicast `group` on the default interface.
+    pub fn join_multicast(&self, group: IpAddr) -> io::Result<()> {
+        match group {
+            IpAddr::V4(group) => self.socket.join_multicast_v4(group, Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(group) => self.socket.join_multicast_v6(&group, 0),
+        }
+    }
+
+    /// The address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Read datagrams forever.
+    ///
+    /// Sentences that fail to parse are logged and skipped; receive errors
+    /// (e.g. ICMP port unreachable on some platforms) are logged too. The
+    /// bound socket is reported to the sink as connected.
+    pub async fn run(mut self) {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        self.driver.sink().report_state(ProviderState::Connected);
+        loop {
+            match self.socket.recv_from(&mut buf).await {
+                Ok((len, sender)) => self.handle_datagram(sender, &buf[..len]),
+                Err(e) => warn!("UDP receive error: {}", e),
+            }
+        }
+    }
+
+    /// Parse the complete lines of a datagram, keeping any incomplete tail
+    /// for the sender's next datagram.
+    fn handle_datagram(&mut self, sender: SocketAddr, datagram: &[u8]) {
+        let pending = self.pending.entry(sender).or_default();
+        pending.extend_from_slice(datagram);
+
+        let complete = match pending.iter().rposition(|&b| b == b'\n') {
+            Some(end) => pending.drain(..=end).collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        if pending.len() > MAX_PENDING {
+            debug!(
+                "Discarding {} bytes of unterminated data from {}",
+                pending.len(),
+                sender
+            );
+            pending.clear();
+        }
+        if pending.is_empty() {
+            self.pending.remove(&sender);
+        }
+
+        for line in complete.split(|&b| b == b'\n') {
+            let line = String::from_utf8_lossy(line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match self.driver.handle_line(line) {
+                Ok(()) => {}
+                // Sources send plenty of sentences we don't map
+                Err(e @ Nmea0183Error::Unsupported(_)) => debug!("{}: {}", sender, e),
+                Err(e) => warn!("Skipping sentence from {}: {} ({})", sender, e, line),
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Tests for the NMEA 0183 TCP provider against a local TCP server.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use signalk_core::Delta;
+use signalk_providers::TcpProvider;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+const RMC: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+const DBT: &str = "$IIDBT,036.41,f,011.10,M,005.99,F*25";
+
+/// Wait until `sink` holds at least `count` deltas.
+async fn wait_for(sink: &Mutex<Vec<Delta>>, count: usize) -> Vec<Delta> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let deltas = sink.lock().unwrap().clone();
+            if deltas.len() >= count {
+                return deltas;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for deltas")
+}
+
+#[tokio::test]
+async fn test_tcp_provider_reads_and_reconnects() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let sink = Arc::new(Mutex::new(Vec::new()));
+
+    let provider = TcpProvider::new(addr, "nmea0183", sink.clone())
+        .with_backoff(Duration::from_millis(10), Duration::from_millis(100));
+    let handle = tokio::spawn(provider.run());
+
+    // A bad checksum and an unsupported sentence don't end the connection
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let bad = RMC.replace("*6A", "*00");
+    for line in [bad.as_str(), "$GPGSV,1,1,00*79", RMC, DBT] {
+        stream
+            .write_all(format!("{line}\r\n").as_bytes())
+            .await
+            .unwrap();
+    }
+    let deltas = wait_for(&sink, 2).await;
+    assert_eq!(deltas.len(), 2);
+    let update = &deltas[0].updates[0];
+    assert_eq!(update.source_ref.as_deref(), Some("nmea0183.GP"));
+    assert_eq!(update.values[0].path, "navigation.position");
+    let update = &deltas[1].updates[0];
+    assert_eq!(update.source_ref.as_deref(), Some("nmea0183.II"));
+    assert_eq!(update.values[0].path, "environment.depth.belowTransducer");
+
+    // Reconnects once the source goes away
+    drop(stream);
+    let (mut stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+        .await
+        .expect("Provider should reconnect")
+        .unwrap();
+    stream
+        .write_all(format!("{RMC}\r\n").as_bytes())
+        .await
+        .unwrap();
+    assert_eq!(wait_for(&sink, 3).await.len(), 3);
+
+    handle.abort();
+}
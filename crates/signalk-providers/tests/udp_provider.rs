@@ -0,0 +1,79 @@
+//! Tests for the NMEA 0183 UDP provider.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use signalk_core::Delta;
+use signalk_providers::UdpProvider;
+use tokio::net::UdpSocket;
+
+const RMC: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+const DBT: &str = "$IIDBT,036.41,f,011.10,M,005.99,F*25";
+const MWV: &str = "$IIMWV,214.8,R,10.5,N,A*06";
+
+/// Wait until `sink` holds at least `count` deltas.
+async fn wait_for(sink: &Mutex<Vec<Delta>>, count: usize) -> Vec<Delta> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let deltas = sink.lock().unwrap().clone();
+            if deltas.len() >= count {
+                return deltas;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for deltas")
+}
+
+#[tokio::test]
+async fn test_udp_provider_splits_and_reassembles() {
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let provider = UdpProvider::bind("127.0.0.1:0".parse().unwrap(), "nmea0183", sink.clone())
+        .await
+        .unwrap();
+    let addr = provider.local_addr().unwrap();
+    let handle = tokio::spawn(provider.run());
+
+    let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    // Two sentences in one datagram
+    first
+        .send_to(format!("{RMC}\r\n{DBT}\r\n").as_bytes(), addr)
+        .await
+        .unwrap();
+    let deltas = wait_for(&sink, 2).await;
+    assert_eq!(deltas[0].updates[0].values[0].path, "navigation.position");
+    assert_eq!(
+        deltas[1].updates[0].values[0].path,
+        "environment.depth.belowTransducer"
+    );
+
+    // A sentence split across datagrams, interleaved with another sender's
+    let (head, tail) = MWV.split_at(12);
+    first.send_to(head.as_bytes(), addr).await.unwrap();
+    second.send_to(b"$IIDBT,036.41", addr).await.unwrap();
+    first
+        .send_to(format!("{tail}\r\n").as_bytes(), addr)
+        .await
+        .unwrap();
+    let deltas = wait_for(&sink, 3).await;
+    assert_eq!(
+        deltas[2].updates[0].values[0].path,
+        "environment.wind.angleApparent"
+    );
+    assert_eq!(
+        deltas[2].updates[0].source_ref.as_deref(),
+        Some("nmea0183.II")
+    );
+
+    second
+        .send_to(b",f,011.10,M,005.99,F*25\r\n", addr)
+        .await
+        .unwrap();
+    let deltas = wait_for(&sink, 4).await;
+    assert_eq!(deltas[3].updates[0].values[0].value, 11.1);
+
+    handle.abort();
+}
@@ -0,0 +1,95 @@
+//! Per-delta CPU cost of broadcasting to 100 clients.
+//!
+//! Compares serializing the delta once per client with serializing it once
+//! and sharing the payload with every client whose subscriptions pass it
+//! unchanged. Run with `cargo bench -p signalk-server --bench fanout`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use signalk_core::{Delta, PathValue, Update};
+use signalk_server::SubscriptionManager;
+
+const CLIENTS: usize = 100;
+const ITERATIONS: u32 = 2_000;
+const SELF_URN: &str = "vessels.urn:mrn:signalk:uuid:bench";
+
+fn delta() -> Delta {
+    Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("nmea0183.GP".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "navigation.position".to_string(),
+                    value: serde_json::json!({ "latitude": 52.0987654, "longitude": 4.9876545 }),
+                },
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.85),
+                },
+                PathValue {
+                    path: "navigation.courseOverGroundTrue".to_string(),
+                    value: serde_json::json!(1.52),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    }
+}
+
+fn clients() -> Vec<SubscriptionManager> {
+    (0..CLIENTS)
+        .map(|_| {
+            let mut subscriptions = SubscriptionManager::new(SELF_URN);
+            subscriptions.subscribe_self_all();
+            subscriptions
+        })
+        .collect()
+}
+
+/// Average time to deliver one delta to every client.
+fn per_delta(mut deliver: impl FnMut(&Delta)) -> Duration {
+    let delta = delta();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        deliver(&delta);
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    // Each client filters and serializes its own copy
+    let mut per_client_clients = clients();
+    let per_client = per_delta(|delta| {
+        for subscriptions in &mut per_client_clients {
+            if let Some(filtered) = subscriptions.filter_delta(delta) {
+                black_box(serde_json::to_string(&filtered).unwrap());
+            }
+        }
+    });
+
+    // Serialized once; clients that pass it unchanged copy the payload
+    let mut shared_clients = clients();
+    let shared = per_delta(|delta| {
+        let encoded: std::sync::Arc<str> = serde_json::to_string(delta).unwrap().into();
+        for subscriptions in &mut shared_clients {
+            match subscriptions.filter_delta_ref(delta, Instant::now()) {
+                Some(std::borrow::Cow::Borrowed(_)) => black_box(encoded.to_string()),
+                Some(filtered) => black_box(serde_json::to_string(&filtered).unwrap()),
+                None => continue,
+            };
+        }
+    });
+
+    println!("{CLIENTS} clients, {ITERATIONS} deltas");
+    println!("serialize per client: {per_client:>10.2?} per delta");
+    println!("serialize once:       {shared:>10.2?} per delta");
+    println!(
+        "speedup: {:.2}x",
+        per_client.as_secs_f64() / shared.as_secs_f64()
+    );
+}
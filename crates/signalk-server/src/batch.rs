@@ -0,0 +1,112 @@
+//! Ordering of batched deltas.
+//!
+//! A batch (e.g. from file replay) may carry updates out of timestamp order.
+//! With chronological ordering enabled, the batch is split into one delta per
+//! update and stably sorted by update timestamp, so the store and clients see
+//! the same deterministic sequence regardless of how the batch was assembled.
+
+use chrono::{DateTime, FixedOffset};
+use signalk_core::Delta;
+
+/// Split deltas into single-update deltas sorted by timestamp.
+///
+/// Ties keep arrival order. Updates without a (parseable) timestamp sort
+/// after all timestamped ones, as they would be stamped on arrival.
+pub fn chronological_order(batch: Vec<Delta>) -> Vec<Delta> {
+    let mut split: Vec<(Option<DateTime<FixedOffset>>, Delta)> = batch
+        .into_iter()
+        .flat_map(|delta| {
+            let context = delta.context;
+            delta.updates.into_iter().map(move |update| {
+                let timestamp = update
+                    .timestamp
+                    .as_deref()
+                    .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok());
+                let delta = Delta {
+                    context: context.clone(),
+                    updates: vec![update],
+                };
+                (timestamp, delta)
+            })
+        })
+        .collect();
+
+    // `sort_by` is stable, which provides the arrival-order tiebreak
+    split.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+
+    split.into_iter().map(|(_, delta)| delta).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signalk_core::{PathValue, Update};
+
+    fn update(timestamp: Option<&str>, value: u64) -> Update {
+        Update {
+            source_ref: Some("replay".to_string()),
+            source: None,
+            timestamp: timestamp.map(String::from),
+            values: vec![PathValue {
+                path: "navigation.log".to_string(),
+                value: serde_json::json!(value),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }
+    }
+
+    fn values(deltas: &[Delta]) -> Vec<u64> {
+        deltas
+            .iter()
+            .map(|d| d.updates[0].values[0].value.as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sorts_updates_across_deltas() {
+        let batch = vec![
+            Delta {
+                context: Some("vessels.self".to_string()),
+                updates: vec![
+                    update(Some("2024-01-17T10:00:02.000Z"), 3),
+                    update(Some("2024-01-17T10:00:00.000Z"), 1),
+                ],
+            },
+            Delta {
+                context: Some("vessels.urn:mrn:imo:mmsi:230099999".to_string()),
+                updates: vec![update(Some("2024-01-17T10:00:01.000Z"), 2)],
+            },
+        ];
+
+        let ordered = chronological_order(batch);
+
+        assert_eq!(values(&ordered), vec![1, 2, 3]);
+        assert_eq!(
+            ordered[1].context.as_deref(),
+            Some("vessels.urn:mrn:imo:mmsi:230099999")
+        );
+    }
+
+    #[test]
+    fn test_ties_and_missing_timestamps_keep_arrival_order() {
+        let batch = vec![Delta {
+            context: None,
+            updates: vec![
+                update(None, 4),
+                update(Some("2024-01-17T10:00:00Z"), 1),
+                // Same instant in another offset
+                update(Some("2024-01-17T11:00:00+01:00"), 2),
+                update(None, 5),
+                update(Some("2024-01-17T10:00:00.000Z"), 3),
+            ],
+        }];
+
+        assert_eq!(values(&chronological_order(batch)), vec![1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,236 @@
+//! Coalescing of deltas held back for a slow client.
+//!
+//! Each connection queues its outgoing messages on a bounded channel. When
+//! the client reads too slowly for the queue to drain, further deltas are
+//! merged into a [`DeltaCoalescer`] instead: every path keeps only its
+//! newest value (per context and source), while distinct paths are all
+//! kept. Once the queue has room the merged deltas are sent in one go, so
+//! a slow client catches up on current values instead of a backlog, and no
+//! path is lost the way a dropped message would lose it.
+
+use std::collections::HashMap;
+
+use signalk_core::{Delta, PathMeta, PathValue, Source, Update};
+
+/// What a held-back value is kept per: context, source, path, and whether
+/// it is metadata.
+type Key = (String, Option<String>, String, bool);
+
+#[derive(Debug)]
+enum Item {
+    Value(PathValue),
+    Meta(PathMeta),
+}
+
+/// The newest value held back for one key, with its update's details.
+#[derive(Debug)]
+struct Pending {
+    context: String,
+    source_ref: Option<String>,
+    source: Option<Source>,
+    timestamp: Option<String>,
+    server_timestamp: Option<String>,
+    item: Item,
+}
+
+impl Pending {
+    fn same_update(&self, update: &Update) -> bool {
+        self.source_ref == update.source_ref
+            && self.source == update.source
+            && self.timestamp == update.timestamp
+            && self.server_timestamp == update.server_timestamp
+    }
+}
+
+/// Deltas merged to the newest value per path.
+#[derive(Debug, Default)]
+pub struct DeltaCoalescer {
+    index: HashMap<Key, usize>,
+    pending: Vec<Pending>,
+}
+
+impl DeltaCoalescer {
+    /// An empty coalescer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether nothing is held back.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Number of values (and meta entries) held back.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Merge a delta, replacing older values of the same paths.
+    pub fn push(&mut self, delta: Delta) {
+        let context = delta.context.unwrap_or_else(|| "vessels.self".to_string());
+        for update in delta.updates {
+            let source = update
+                .source_ref
+                .clone()
+                .or_else(|| update.source.as_ref().map(|s| s.label.clone()));
+            let items = update
+                .values
+                .into_iter()
+                .map(Item::Value)
+                .chain(update.meta.into_iter().flatten().map(Item::Meta));
+            for item in items {
+                let key = match &item {
+                    Item::Value(pv) => (context.clone(), source.clone(), pv.path.clone(), false),
+                    Item::Meta(pm) => (context.clone(), source.clone(), pm.path.clone(), true),
+                };
+                let pending = Pending {
+                    context: context.clone(),
+                    source_ref: update.source_ref.clone(),
+                    source: update.source.clone(),
+                    timestamp: update.timestamp.clone(),
+                    server_timestamp: update.server_timestamp.clone(),
+                    item,
+                };
+                match self.index.get(&key) {
+                    Some(&i) => self.pending[i] = pending,
+                    None => {
+                        self.index.insert(key, self.pending.len());
+                        self.pending.push(pending);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Take everything held back, as one delta per context.
+    ///
+    /// Values keep the order their paths were first held back in; runs
+    /// with the same source and timestamp share an update.
+    pub fn drain(&mut self) -> Vec<Delta> {
+        self.index.clear();
+        let mut deltas: Vec<Delta> = Vec::new();
+        for pending in self.pending.drain(..) {
+            let delta = match deltas
+                .iter_mut()
+                .position(|d| d.context.as_deref() == Some(pending.context.as_str()))
+            {
+                Some(i) => &mut deltas[i],
+                None => {
+                    deltas.push(Delta {
+                        context: Some(pending.context.clone()),
+                        updates: Vec::new(),
+                    });
+                    deltas.last_mut().expect("just pushed")
+                }
+            };
+            let update = match delta.updates.last_mut() {
+                Some(update) if pending.same_update(update) => update,
+                _ => {
+                    delta.updates.push(Update {
+                        source_ref: pending.source_ref,
+                        source: pending.source,
+                        timestamp: pending.timestamp,
+                        values: Vec::new(),
+                        meta: None,
+                        server_timestamp: pending.server_timestamp,
+                    });
+                    delta.updates.last_mut().expect("just pushed")
+                }
+            };
+            match pending.item {
+                Item::Value(pv) => update.values.push(pv),
+                Item::Meta(pm) => update.meta.get_or_insert_with(Vec::new).push(pm),
+            }
+        }
+        deltas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn delta(source: &str, timestamp: &str, values: &[(&str, f64)]) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some(source.to_string()),
+                source: None,
+                timestamp: Some(timestamp.to_string()),
+                values: values
+                    .iter()
+                    .map(|(path, value)| PathValue {
+                        path: path.to_string(),
+                        value: json!(value),
+                    })
+                    .collect(),
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_flood_keeps_latest_per_path() {
+        let mut coalescer = DeltaCoalescer::new();
+        for i in 0..1000 {
+            let ts = format!("2024-01-01T00:00:{:02}.{:03}Z", i / 1000, i % 1000);
+            let sog = f64::from(i);
+            coalescer.push(delta("gps", &ts, &[("navigation.speedOverGround", sog)]));
+            if i % 10 == 0 {
+                coalescer.push(delta(
+                    "gps",
+                    &ts,
+                    &[("navigation.courseOverGroundTrue", sog)],
+                ));
+            }
+        }
+        coalescer.push(delta(
+            "wind",
+            "2024-01-01T00:00:01.000Z",
+            &[("environment.wind.speedApparent", 7.5)],
+        ));
+        assert_eq!(coalescer.len(), 3);
+
+        let deltas = coalescer.drain();
+        assert!(coalescer.is_empty());
+        assert_eq!(deltas.len(), 1);
+        let values: Vec<(&str, &serde_json::Value)> = deltas[0]
+            .updates
+            .iter()
+            .flat_map(|u| u.values.iter().map(|pv| (pv.path.as_str(), &pv.value)))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("navigation.speedOverGround", &json!(999.0)),
+                ("navigation.courseOverGroundTrue", &json!(990.0)),
+                ("environment.wind.speedApparent", &json!(7.5)),
+            ]
+        );
+        // Each value keeps its own update's source and timestamp
+        assert_eq!(deltas[0].updates.len(), 3);
+        assert_eq!(
+            deltas[0].updates[0].timestamp.as_deref(),
+            Some("2024-01-01T00:00:00.999Z")
+        );
+        assert_eq!(deltas[0].updates[2].source_ref.as_deref(), Some("wind"));
+    }
+
+    #[test]
+    fn test_sources_and_contexts_kept_apart() {
+        let mut coalescer = DeltaCoalescer::new();
+        let ts = "2024-01-01T00:00:00Z";
+        coalescer.push(delta("gps1", ts, &[("navigation.speedOverGround", 1.0)]));
+        coalescer.push(delta("gps2", ts, &[("navigation.speedOverGround", 2.0)]));
+        let mut other = delta("ais", ts, &[("navigation.speedOverGround", 3.0)]);
+        other.context = Some("vessels.urn:mrn:imo:mmsi:244000001".to_string());
+        coalescer.push(other);
+
+        let deltas = coalescer.drain();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].updates.len(), 2);
+        assert_eq!(deltas[1].updates[0].values[0].value, json!(3.0));
+    }
+}
@@ -0,0 +1,176 @@
+//! Connection limiting and counting.
+//!
+//! `ServerConfig::max_connections` caps concurrent WebSocket connections with
+//! a semaphore. A connection over the limit is accepted only long enough to
+//! answer `503 Service Unavailable` and closed, so clients see a clear
+//! "try later" instead of hanging in the listen backlog.
+//!
+//! [`IdleTimer`] closes connections that have gone quiet at the application
+//! level.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::time::Sleep;
+
+/// Response sent to connections over the limit.
+const REFUSAL: &[u8] = b"HTTP/1.1 503 Service Unavailable\r\n\
+Retry-After: 5\r\n\
+Content-Length: 0\r\n\
+Connection: close\r\n\r\n";
+
+/// How long to wait for a refused client to take the response.
+const REFUSAL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Current and peak number of open connections.
+///
+/// Cloning shares the counters.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    current: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+}
+
+impl ConnectionStats {
+    /// Connections open right now.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Highest number of simultaneous connections since start.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Count a connection until the returned guard is dropped.
+    ///
+    /// The guard also holds the connection's limit permit, if any.
+    pub(crate) fn track(&self, permit: Option<OwnedSemaphorePermit>) -> ConnectionGuard {
+        let current = self.current.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+        ConnectionGuard {
+            stats: self.clone(),
+            _permit: permit,
+        }
+    }
+}
+
+/// Keeps a connection counted (and its permit held) while alive.
+pub(crate) struct ConnectionGuard {
+    stats: ConnectionStats,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.stats.current.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Answer a connection over the limit with 503 and close it.
+pub(crate) async fn refuse(mut stream: TcpStream) {
+    let _ = tokio::time::timeout(REFUSAL_TIMEOUT, async {
+        stream.write_all(REFUSAL).await?;
+        stream.shutdown().await
+    })
+    .await;
+}
+
+/// Application-level idle deadline for a connection.
+///
+/// Unlike a ping/pong liveness check, which only proves the peer is still
+/// there, this tracks whether the connection is used: the deadline moves on
+/// every inbound text or binary frame ([`touch`](Self::touch)) and every
+/// delta sent. A connection that subscribes to nothing and sends nothing
+/// expires even if it sends or answers pings.
+pub struct IdleTimer {
+    timeout: Option<Duration>,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl IdleTimer {
+    /// Start a timer expiring after `timeout` without activity; `None`
+    /// never expires.
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            sleep: Box::pin(tokio::time::sleep(timeout.unwrap_or_default())),
+        }
+    }
+
+    /// The configured timeout.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Record activity, moving the deadline.
+    pub fn touch(&mut self) {
+        if let Some(timeout) = self.timeout {
+            self.sleep
+                .as_mut()
+                .reset(tokio::time::Instant::now() + timeout);
+        }
+    }
+
+    /// Wait until the deadline passes; pending forever without a timeout.
+    pub async fn expired(&mut self) {
+        match self.timeout {
+            Some(_) => self.sleep.as_mut().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// The warning sent to a client before closing it.
+    pub fn warning(&self) -> String {
+        format!(
+            "Connection idle for {}s, closing",
+            self.timeout.unwrap_or_default().as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Semaphore;
+
+    #[test]
+    fn test_connection_stats() {
+        let stats = ConnectionStats::default();
+        let limit = Arc::new(Semaphore::new(2));
+
+        let first = stats.track(limit.clone().try_acquire_owned().ok());
+        let second = stats.track(limit.clone().try_acquire_owned().ok());
+        assert_eq!((stats.current(), stats.peak()), (2, 2));
+        assert!(limit.clone().try_acquire_owned().is_err());
+
+        drop(first);
+        assert_eq!((stats.current(), stats.peak()), (1, 2));
+        assert!(limit.clone().try_acquire_owned().is_ok());
+        drop(second);
+        assert_eq!(stats.current(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timer() {
+        let timeout = Duration::from_millis(200);
+        let mut idle = IdleTimer::new(Some(timeout));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        idle.touch();
+
+        // The deadline moved with the touch
+        let early = tokio::time::timeout(Duration::from_millis(100), idle.expired()).await;
+        assert!(early.is_err());
+        let expired = tokio::time::timeout(timeout, idle.expired()).await;
+        assert!(expired.is_ok());
+
+        let mut never = IdleTimer::new(None);
+        let expired = tokio::time::timeout(timeout, never.expired()).await;
+        assert!(expired.is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Per-client suppression of repeated values.
+//!
+//! Throttling limits how often a client is sent a path; some clients only
+//! care about changes at all. With `dedup=<ms>` on the stream URL, a value
+//! equal to the last one that client was sent for the same path within the
+//! window is left out. This is per connection: other clients, and the
+//! store, still see every value.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use signalk_core::Delta;
+
+/// Last values sent to one client, per context and path.
+#[derive(Debug)]
+pub struct OutboundDedup {
+    window: Duration,
+    sent: HashMap<(String, String), (Value, Instant)>,
+}
+
+impl OutboundDedup {
+    /// Suppress values repeated within `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Drop values equal to what was last sent for their path within the
+    /// window, and record the rest as sent at `now`.
+    ///
+    /// Updates left without values (and without meta) are removed; returns
+    /// `None` if nothing is left. The delta is only copied if something is
+    /// dropped.
+    pub fn filter<'a>(&mut self, delta: Cow<'a, Delta>, now: Instant) -> Option<Cow<'a, Delta>> {
+        let context = delta.context.as_deref().unwrap_or("vessels.self");
+        let mut repeated = Vec::new();
+        for (u, update) in delta.updates.iter().enumerate() {
+            for (v, pv) in update.values.iter().enumerate() {
+                let key = (context.to_string(), pv.path.clone());
+                match self.sent.get_mut(&key) {
+                    Some((value, sent_at))
+                        if *value == pv.value && now.duration_since(*sent_at) < self.window =>
+                    {
+                        repeated.push((u, v));
+                    }
+                    Some(last) => *last = (pv.value.clone(), now),
+                    None => {
+                        self.sent.insert(key, (pv.value.clone(), now));
+                    }
+                }
+            }
+        }
+        if repeated.is_empty() {
+            return Some(delta);
+        }
+
+        let mut delta = delta.into_owned();
+        // Back to front, so earlier indices stay valid
+        for (u, v) in repeated.into_iter().rev() {
+            delta.updates[u].values.remove(v);
+        }
+        delta
+            .updates
+            .retain(|update| !update.values.is_empty() || update.meta.is_some());
+        (!delta.updates.is_empty()).then_some(Cow::Owned(delta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signalk_core::{PathValue, Update};
+
+    fn delta(sog: f64, heading: f64) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![
+                    PathValue {
+                        path: "navigation.speedOverGround".to_string(),
+                        value: serde_json::json!(sog),
+                    },
+                    PathValue {
+                        path: "navigation.headingTrue".to_string(),
+                        value: serde_json::json!(heading),
+                    },
+                ],
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_repeated_values_dropped() {
+        let mut dedup = OutboundDedup::new(Duration::from_secs(10));
+        let start = Instant::now();
+
+        let first = delta(3.5, 1.0);
+        assert!(matches!(
+            dedup.filter(Cow::Borrowed(&first), start),
+            Some(Cow::Borrowed(_))
+        ));
+
+        // Only the changed heading is left
+        let second = delta(3.5, 1.1);
+        let filtered = dedup
+            .filter(Cow::Borrowed(&second), start + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(filtered.updates[0].values.len(), 1);
+        assert_eq!(filtered.updates[0].values[0].path, "navigation.headingTrue");
+
+        assert!(dedup
+            .filter(Cow::Borrowed(&second), start + Duration::from_secs(2))
+            .is_none());
+
+        // Sent again once the window has passed
+        let later = dedup
+            .filter(Cow::Borrowed(&second), start + Duration::from_secs(12))
+            .unwrap();
+        assert_eq!(later.updates[0].values.len(), 2);
+    }
+}
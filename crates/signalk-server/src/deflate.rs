@@ -0,0 +1,456 @@
+//! WebSocket compression (`permessage-deflate`, RFC 7692).
+//!
+//! tungstenite doesn't implement the extension, so it is handled around
+//! it: [`negotiate_deflate`] answers the client's
+//! `Sec-WebSocket-Extensions` offer, [`MessageDeflater`] compresses
+//! outgoing messages into raw frames with the RSV1 bit set, and
+//! [`InflateStream`] sits between the socket and tungstenite, rewriting
+//! compressed client frames into plain ones.
+//!
+//! Both directions are negotiated without context takeover, so every
+//! message is compressed on its own. That costs some ratio on small
+//! deltas, but a connection keeps no window between messages and the
+//! deflater can skip messages below its threshold.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data as OpData, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+use tokio_tungstenite::tungstenite::Message;
+
+/// The extension response accepting a `permessage-deflate` offer.
+pub const DEFLATE_RESPONSE: &str =
+    "permessage-deflate; server_no_context_takeover; client_no_context_takeover";
+
+/// Largest client frame accepted, as tungstenite's default.
+const MAX_FRAME_SIZE: u64 = 16 << 20;
+
+/// Largest inflated client message, as tungstenite's default.
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// The empty block a sync flush ends with, which the extension leaves out.
+const SYNC_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// The response to a `Sec-WebSocket-Extensions` request header, if it
+/// offers `permessage-deflate` with parameters this server supports.
+///
+/// Offers asking the server for a window smaller than 32 KiB
+/// (`server_max_window_bits` below 15) are declined, as is any offer with
+/// an unknown parameter; a later offer in the header may still match.
+pub fn negotiate_deflate(offers: &str) -> Option<&'static str> {
+    offers
+        .split(',')
+        .any(|offer| {
+            let mut params = offer.split(';').map(str::trim);
+            params.next() == Some("permessage-deflate")
+                && params.all(|param| {
+                    let (name, value) = match param.split_once('=') {
+                        Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                        None => (param, None),
+                    };
+                    match name {
+                        "server_no_context_takeover" | "client_no_context_takeover" => {
+                            value.is_none()
+                        }
+                        // Any client window is fine to inflate
+                        "client_max_window_bits" => true,
+                        "server_max_window_bits" => value == Some("15"),
+                        _ => false,
+                    }
+                })
+        })
+        .then_some(DEFLATE_RESPONSE)
+}
+
+/// Compresses one connection's outgoing messages.
+pub struct MessageDeflater {
+    threshold: usize,
+    compress: Compress,
+}
+
+impl MessageDeflater {
+    /// Compress text and binary messages of at least `threshold` bytes.
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            compress: Compress::new(Compression::default(), false),
+        }
+    }
+
+    /// `message` as a compressed frame, or unchanged if it is a control
+    /// message, below the threshold or doesn't get any smaller.
+    pub fn deflate(&mut self, message: Message) -> Message {
+        let (data, opcode) = match message {
+            Message::Text(text) if text.len() >= self.threshold => {
+                (text.into_bytes(), OpData::Text)
+            }
+            Message::Binary(data) if data.len() >= self.threshold => (data, OpData::Binary),
+            message => return message,
+        };
+        match self.compress_message(&data) {
+            Some(compressed) if compressed.len() < data.len() => {
+                let mut frame = Frame::message(compressed, OpCode::Data(opcode), true);
+                frame.header_mut().rsv1 = true;
+                Message::Frame(frame)
+            }
+            _ => match opcode {
+                OpData::Text => Message::Text(String::from_utf8(data).expect("was a String")),
+                _ => Message::Binary(data),
+            },
+        }
+    }
+
+    fn compress_message(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        // No context takeover: each message starts a fresh stream
+        self.compress.reset();
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        let mut input = data;
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(4096);
+            }
+            let before = self.compress.total_in();
+            self.compress
+                .compress_vec(input, &mut out, FlushCompress::Sync)
+                .ok()?;
+            input = &input[(self.compress.total_in() - before) as usize..];
+            // Done once everything is in and the flush left room to spare
+            if input.is_empty() && out.len() < out.capacity() {
+                break;
+            }
+        }
+        if out.ends_with(&SYNC_TRAILER) {
+            out.truncate(out.len() - SYNC_TRAILER.len());
+        }
+        Some(out)
+    }
+}
+
+/// A socket whose incoming compressed WebSocket frames are inflated.
+///
+/// Frames are read whole; compressed ones are passed on decompressed,
+/// with RSV1 cleared and a zero mask, so tungstenite sees a plain
+/// (possibly fragmented) message. Other frames pass through unchanged.
+/// Writes go straight to the socket.
+///
+/// Wrap the socket only after the HTTP handshake: everything read is
+/// parsed as frames. A disabled stream passes everything through, for
+/// connections that didn't negotiate compression.
+pub struct InflateStream<S> {
+    inner: S,
+    enabled: bool,
+    /// Bytes read that don't make up a whole frame yet.
+    input: Vec<u8>,
+    /// Frames ready for the reader, from `output_pos` on.
+    output: Vec<u8>,
+    output_pos: usize,
+    decompress: Decompress,
+    /// Size so far of the compressed message being received, if any.
+    message_size: Option<usize>,
+    eof: bool,
+}
+
+impl<S> InflateStream<S> {
+    /// Wrap `inner`, inflating frames if `enabled`.
+    pub fn new(inner: S, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            input: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            decompress: Decompress::new(false),
+            message_size: None,
+            eof: false,
+        }
+    }
+
+    /// Start inflating frames, once the handshake negotiated compression.
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    /// Move the next whole frame from `input` to `output`, inflating it if
+    /// compressed. Returns whether there was one.
+    fn next_frame(&mut self) -> io::Result<bool> {
+        let Some((header_len, payload_len)) = frame_size(&self.input)? else {
+            return Ok(false);
+        };
+        let frame_len = header_len + payload_len;
+        if self.input.len() < frame_len {
+            return Ok(false);
+        }
+
+        let first = self.input[0];
+        let is_final = first & 0x80 != 0;
+        let compressed = match first & 0x0f {
+            // Text or binary: RSV1 marks a compressed message
+            0x1 | 0x2 => first & 0x40 != 0,
+            0x0 => self.message_size.is_some(),
+            _ => false,
+        };
+        if !compressed {
+            self.output.extend(self.input.drain(..frame_len));
+            return Ok(true);
+        }
+
+        let masked = self.input[1] & 0x80 != 0;
+        let mut payload = self.input[header_len..frame_len].to_vec();
+        if masked {
+            let mask: [u8; 4] = self.input[header_len - 4..header_len]
+                .try_into()
+                .expect("4 bytes");
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+        if first & 0x0f != 0 {
+            self.decompress.reset(false);
+            self.message_size = Some(0);
+        }
+        let mut data = Vec::with_capacity(payload.len() * 2 + 64);
+        self.inflate(&payload, &mut data)?;
+        if is_final {
+            self.inflate(&SYNC_TRAILER, &mut data)?;
+            self.message_size = None;
+        }
+        self.input.drain(..frame_len);
+
+        self.output.push(first & !0x40);
+        let mask_bit = if masked { 0x80 } else { 0 };
+        match data.len() {
+            len @ 0..=125 => self.output.push(mask_bit | len as u8),
+            len @ 126..=0xffff => {
+                self.output.push(mask_bit | 126);
+                self.output.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                self.output.push(mask_bit | 127);
+                self.output.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        if masked {
+            // A zero mask leaves the payload as is
+            self.output.extend_from_slice(&[0; 4]);
+        }
+        self.output.extend_from_slice(&data);
+        Ok(true)
+    }
+
+    fn inflate(&mut self, mut input: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(4096);
+            }
+            let (before_in, before_out) = (self.decompress.total_in(), out.len());
+            let status = self
+                .decompress
+                .decompress_vec(input, out, FlushDecompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let consumed = (self.decompress.total_in() - before_in) as usize;
+            input = &input[consumed..];
+
+            let size = self.message_size.unwrap_or(0) + out.len() - before_out;
+            if size > MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "inflated message too large",
+                ));
+            }
+            self.message_size = Some(size);
+
+            let stalled = consumed == 0 && out.len() == before_out;
+            if status == Status::StreamEnd
+                || (input.is_empty() && out.len() < out.capacity())
+                || stalled
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Header and payload length of the frame `data` starts with, once the
+/// header is complete.
+fn frame_size(data: &[u8]) -> io::Result<Option<(usize, usize)>> {
+    if data.len() < 2 {
+        return Ok(None);
+    }
+    let mask_len = if data[1] & 0x80 != 0 { 4 } else { 0 };
+    let (len_bytes, payload_len) = match data[1] & 0x7f {
+        126 => match data.get(2..4) {
+            Some(len) => (2, u64::from(u16::from_be_bytes([len[0], len[1]]))),
+            None => return Ok(None),
+        },
+        127 => match data.get(2..10) {
+            Some(len) => (8, u64::from_be_bytes(len.try_into().expect("8 bytes"))),
+            None => return Ok(None),
+        },
+        len => (0, u64::from(len)),
+    };
+    if payload_len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+    let header_len = 2 + len_bytes + mask_len;
+    if data.len() < header_len {
+        return Ok(None);
+    }
+    Ok(Some((header_len, payload_len as usize)))
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for InflateStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.enabled {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        loop {
+            if this.output_pos < this.output.len() {
+                let available = &this.output[this.output_pos..];
+                let len = available.len().min(buf.remaining());
+                buf.put_slice(&available[..len]);
+                this.output_pos += len;
+                if this.output_pos == this.output.len() {
+                    this.output.clear();
+                    this.output_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if this.eof {
+                return Poll::Ready(Ok(()));
+            }
+            if this.next_frame()? {
+                continue;
+            }
+
+            let mut chunk = [0; 8192];
+            let mut read = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+            if read.filled().is_empty() {
+                // Pass on a partial frame; tungstenite reports it
+                this.eof = true;
+                this.output.append(&mut this.input);
+            } else {
+                this.input.extend_from_slice(read.filled());
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for InflateStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::protocol::Role;
+    use tokio_tungstenite::WebSocketStream;
+
+    fn large_text() -> String {
+        let value = r#"{"path":"navigation.speedOverGround","value":3.85},"#;
+        format!("[{}]", value.repeat(100))
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(
+            negotiate_deflate("permessage-deflate; client_max_window_bits"),
+            Some(DEFLATE_RESPONSE)
+        );
+        assert_eq!(negotiate_deflate("x-webkit-deflate-frame"), None);
+        assert_eq!(
+            negotiate_deflate("permessage-deflate; server_max_window_bits=10"),
+            None
+        );
+        // The second offer is acceptable
+        assert_eq!(
+            negotiate_deflate("permessage-deflate; server_max_window_bits=10, permessage-deflate"),
+            Some(DEFLATE_RESPONSE)
+        );
+        assert_eq!(negotiate_deflate("permessage-deflate; unknown"), None);
+    }
+
+    #[test]
+    fn test_deflate_threshold() {
+        let mut deflater = MessageDeflater::new(1024);
+        let small = Message::Text("{}".to_string());
+        assert_eq!(deflater.deflate(small.clone()), small);
+
+        let text = large_text();
+        let Message::Frame(frame) = deflater.deflate(Message::Text(text.clone())) else {
+            panic!("expected a compressed frame");
+        };
+        assert!(frame.header().rsv1);
+        assert!(frame.payload().len() < text.len() / 4);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_round_trip() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let mut server = WebSocketStream::from_raw_socket(
+            InflateStream::new(server_io, true),
+            Role::Server,
+            None,
+        )
+        .await;
+        let mut client = WebSocketStream::from_raw_socket(
+            InflateStream::new(client_io, true),
+            Role::Client,
+            None,
+        )
+        .await;
+        let mut server_deflater = MessageDeflater::new(64);
+        let mut client_deflater = MessageDeflater::new(64);
+        let text = large_text();
+
+        // Masked, compressed client frames arrive as plain messages
+        for message in [Message::Text(text.clone()), Message::Text("{}".to_string())] {
+            client.send(client_deflater.deflate(message)).await.unwrap();
+        }
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::Text(text.clone())
+        );
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::Text("{}".to_string())
+        );
+
+        // Twice, as each message starts a fresh stream
+        for _ in 0..2 {
+            let message = server_deflater.deflate(Message::Text(text.clone()));
+            server.send(message).await.unwrap();
+            assert_eq!(
+                client.next().await.unwrap().unwrap(),
+                Message::Text(text.clone())
+            );
+        }
+    }
+}
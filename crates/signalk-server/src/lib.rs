@@ -29,14 +29,54 @@
 //! }
 //! ```
 
-pub use signalk_core::{Delta, MemoryStore, PathPattern, SignalKStore};
+pub use signalk_core::{Delta, DeltaSink, MemoryStore, PathPattern, ProviderState, SignalKStore};
 
+mod providers;
+
+pub use providers::{ProviderRegistry, ProviderReporter, ProviderStatus};
+
+#[cfg(feature = "tokio-runtime")]
+mod batch;
+#[cfg(feature = "tokio-runtime")]
+mod coalesce;
+#[cfg(feature = "tokio-runtime")]
+mod connections;
+#[cfg(feature = "tokio-runtime")]
+mod dedup;
+#[cfg(feature = "tokio-runtime")]
+mod deflate;
+#[cfg(feature = "tokio-runtime")]
+mod recording;
+#[cfg(feature = "tokio-runtime")]
+mod replay;
 #[cfg(feature = "tokio-runtime")]
 mod server;
 #[cfg(feature = "tokio-runtime")]
+mod snapshot;
+#[cfg(feature = "tokio-runtime")]
 mod subscription;
 
 #[cfg(feature = "tokio-runtime")]
-pub use server::{ServerConfig, ServerEvent, SignalKServer};
+pub use batch::chronological_order;
+#[cfg(feature = "tokio-runtime")]
+pub use coalesce::DeltaCoalescer;
+#[cfg(feature = "tokio-runtime")]
+pub use connections::{ConnectionStats, IdleTimer};
+#[cfg(feature = "tokio-runtime")]
+pub use dedup::OutboundDedup;
+#[cfg(feature = "tokio-runtime")]
+pub use deflate::{negotiate_deflate, InflateStream, MessageDeflater, DEFLATE_RESPONSE};
+#[cfg(feature = "tokio-runtime")]
+pub use recording::{DeltaPlayer, DeltaRecorder};
+#[cfg(feature = "tokio-runtime")]
+pub use server::{
+    reject_oversized, shutdown_close_frame, shutdown_requested, EventSink, LagPolicy, ServerConfig,
+    ServerEvent, ShutdownHandle, SignalKServer,
+};
+#[cfg(feature = "tokio-runtime")]
+pub use snapshot::{run_snapshots, save_snapshot};
 #[cfg(feature = "tokio-runtime")]
-pub use subscription::{ClientSubscription, SubscriptionManager};
+pub use subscription::{
+    log_subscription_event, ClientSubscription, InvalidSubscription, SelfContext,
+    SubscriptionManager, SUBSCRIPTION_DEBUG_KEY, SUBSCRIPTION_LOG_TARGET,
+};
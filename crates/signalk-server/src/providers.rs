@@ -0,0 +1,234 @@
+//! Status of the running providers.
+//!
+//! Each provider's sink is wrapped in a [`ProviderReporter`] by
+//! [`ProviderRegistry::register`]. The reporter passes deltas on unchanged
+//! and sends the provider's state transitions and message times down a
+//! channel; the registry applies them whenever it is read, so
+//! [`ProviderRegistry::provider_statuses`] always reflects the latest
+//! reports. The Admin UI Connections page shows these statuses.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use signalk_core::{Delta, DeltaSink, ProviderState};
+
+/// The current status of one provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderStatus {
+    /// Provider identifier, unique within the registry.
+    pub id: String,
+    /// Provider type (e.g., "NMEA0183").
+    pub provider_type: String,
+    pub state: ProviderState,
+    /// When the provider last submitted a delta.
+    pub last_message: Option<DateTime<Utc>>,
+}
+
+impl ProviderStatus {
+    /// Whether the provider is receiving from its source.
+    pub fn is_connected(&self) -> bool {
+        self.state == ProviderState::Connected
+    }
+}
+
+#[derive(Debug)]
+enum Report {
+    State(ProviderState),
+    Message(DateTime<Utc>),
+}
+
+#[derive(Debug)]
+struct ProviderEvent {
+    id: String,
+    report: Report,
+}
+
+#[derive(Debug, Default)]
+struct Providers {
+    statuses: BTreeMap<String, ProviderStatus>,
+    /// Whether anything changed since the last [`ProviderRegistry::poll_changes`].
+    changed: bool,
+}
+
+/// Tracks the state of every registered provider.
+#[derive(Debug)]
+pub struct ProviderRegistry {
+    providers: Mutex<Providers>,
+    tx: mpsc::Sender<ProviderEvent>,
+    rx: Mutex<mpsc::Receiver<ProviderEvent>>,
+}
+
+impl ProviderRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            providers: Mutex::new(Providers::default()),
+            tx,
+            rx: Mutex::new(rx),
+        }
+    }
+
+    /// Register a provider as connecting and wrap its sink to report to
+    /// this registry.
+    ///
+    /// Registering an id again resets its status.
+    pub fn register<S: DeltaSink>(
+        &self,
+        id: impl Into<String>,
+        provider_type: impl Into<String>,
+        sink: S,
+    ) -> ProviderReporter<S> {
+        let id = id.into();
+        let mut providers = self.lock_providers();
+        providers.statuses.insert(
+            id.clone(),
+            ProviderStatus {
+                id: id.clone(),
+                provider_type: provider_type.into(),
+                state: ProviderState::Connecting,
+                last_message: None,
+            },
+        );
+        providers.changed = true;
+        ProviderReporter {
+            id,
+            tx: self.tx.clone(),
+            sink,
+        }
+    }
+
+    /// The status of every provider, ordered by id.
+    pub fn provider_statuses(&self) -> Vec<ProviderStatus> {
+        let mut providers = self.lock_providers();
+        self.apply_reports(&mut providers);
+        providers.statuses.values().cloned().collect()
+    }
+
+    /// Whether any status changed since the last call.
+    ///
+    /// Also applies pending reports, so calling this periodically keeps
+    /// the report channel short.
+    pub fn poll_changes(&self) -> bool {
+        let mut providers = self.lock_providers();
+        self.apply_reports(&mut providers);
+        std::mem::take(&mut providers.changed)
+    }
+
+    fn apply_reports(&self, providers: &mut Providers) {
+        let rx = self.rx.lock().unwrap_or_else(|e| e.into_inner());
+        for event in rx.try_iter() {
+            let Some(status) = providers.statuses.get_mut(&event.id) else {
+                continue;
+            };
+            match event.report {
+                Report::State(state) => status.state = state,
+                Report::Message(at) => status.last_message = Some(at),
+            }
+            providers.changed = true;
+        }
+    }
+
+    fn lock_providers(&self) -> std::sync::MutexGuard<'_, Providers> {
+        self.providers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A provider's sink, reporting its state and messages to a
+/// [`ProviderRegistry`].
+#[derive(Debug, Clone)]
+pub struct ProviderReporter<S> {
+    id: String,
+    tx: mpsc::Sender<ProviderEvent>,
+    sink: S,
+}
+
+impl<S> ProviderReporter<S> {
+    /// The id the provider was registered under.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn send(&self, report: Report) {
+        // The registry is gone only during shutdown
+        let _ = self.tx.send(ProviderEvent {
+            id: self.id.clone(),
+            report,
+        });
+    }
+}
+
+impl<S: DeltaSink> DeltaSink for ProviderReporter<S> {
+    fn submit(&self, delta: Delta) {
+        self.send(Report::Message(Utc::now()));
+        self.sink.submit(delta);
+    }
+
+    fn report_state(&self, state: ProviderState) {
+        self.send(Report::State(state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn delta() -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![],
+        }
+    }
+
+    #[test]
+    fn test_state_transitions() {
+        let registry = ProviderRegistry::new();
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let tcp = registry.register("nmea0183-tcp", "NMEA0183", sink.clone());
+        let _udp = registry.register("nmea0183-udp", "NMEA0183", sink.clone());
+        assert!(registry.poll_changes());
+        assert!(!registry.poll_changes());
+
+        let statuses = registry.provider_statuses();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0].id, "nmea0183-tcp");
+        assert_eq!(statuses[0].state, ProviderState::Connecting);
+        assert_eq!(statuses[0].last_message, None);
+
+        tcp.report_state(ProviderState::Connected);
+        tcp.submit(delta());
+        assert!(registry.poll_changes());
+        let statuses = registry.provider_statuses();
+        assert!(statuses[0].is_connected());
+        assert!(statuses[0].last_message.is_some());
+        assert!(!statuses[1].is_connected());
+        // Deltas still reach the wrapped sink
+        assert_eq!(sink.lock().unwrap().len(), 1);
+
+        tcp.report_state(ProviderState::Error("Connection refused".to_string()));
+        assert_eq!(
+            registry.provider_statuses()[0].state,
+            ProviderState::Error("Connection refused".to_string())
+        );
+    }
+
+    #[test]
+    fn test_reports_through_shared_sink() {
+        // Providers hold their sink behind an Arc
+        let registry = ProviderRegistry::new();
+        let reporter = Arc::new(registry.register("mock", "Mock", Mutex::new(Vec::new())));
+        let shared: Arc<dyn DeltaSink> = reporter.clone();
+        shared.report_state(ProviderState::Connected);
+        assert_eq!(reporter.id(), "mock");
+        assert!(registry.provider_statuses()[0].is_connected());
+    }
+}
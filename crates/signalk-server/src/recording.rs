@@ -0,0 +1,320 @@
+//! Recording deltas to disk and playing them back.
+//!
+//! [`DeltaRecorder`] appends every broadcast delta to a file as one JSON
+//! line (newline-delimited JSON, the format other SignalK tools read and
+//! write). When the file reaches its size limit it is rotated to `<file>.1`,
+//! `<file>.2`, ..., keeping a fixed number of old files.
+//!
+//! [`DeltaPlayer::play`] feeds such a file back into a server as
+//! [`ServerEvent::DeltaReceived`] events, paced by the recorded update
+//! timestamps, so a trip captured on board can be replayed at home.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use signalk_core::Delta;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::server::ServerEvent;
+
+/// Appends deltas to a newline-delimited JSON file with size-based rotation.
+#[derive(Debug)]
+pub struct DeltaRecorder {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: BufWriter<File>,
+    written: u64,
+}
+
+impl DeltaRecorder {
+    /// Append to `path` (created if missing), rotating it once it holds
+    /// `max_bytes` and keeping `keep` rotated files.
+    pub fn create(path: impl Into<PathBuf>, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            keep,
+            file: BufWriter::new(file),
+            written,
+        })
+    }
+
+    /// Append one delta and flush it, so a crash loses at most the line
+    /// being written.
+    pub fn record(&mut self, delta: &Delta) -> io::Result<()> {
+        let mut line = serde_json::to_vec(delta)?;
+        line.push(b'\n');
+        if self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(&line)?;
+        self.file.flush()?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    /// Record the deltas from a broadcast channel until it closes.
+    ///
+    /// Deltas skipped because the recorder lagged are logged; a write
+    /// error ends recording.
+    pub async fn run<T>(mut self, mut deltas: broadcast::Receiver<T>) -> io::Result<()>
+    where
+        T: Clone + AsRef<Delta>,
+    {
+        loop {
+            match deltas.recv().await {
+                Ok(delta) => self.record(delta.as_ref())?,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Delta recorder lagged, {} deltas not recorded", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    /// Path of the `n`th rotated file.
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    /// Shift the rotated files up by one, dropping the oldest, and start
+    /// a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = BufWriter::new(File::create(&self.path)?);
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// Plays a recorded delta file back into a server.
+#[derive(Debug)]
+pub struct DeltaPlayer;
+
+impl DeltaPlayer {
+    /// Gap before a delta without a timestamp (or following one), at
+    /// normal speed.
+    pub const UNTIMED_GAP: Duration = Duration::from_millis(100);
+
+    /// Send the deltas recorded in `path` to `tx`, returning how many were
+    /// sent.
+    ///
+    /// Deltas are spaced as their update timestamps were, divided by
+    /// `speed_factor` (2.0 plays twice as fast); a factor that isn't
+    /// positive plays without pauses. Where either neighbour has no
+    /// timestamp, [`UNTIMED_GAP`](Self::UNTIMED_GAP) is used instead, and
+    /// timestamps going backwards don't pause. Lines that aren't deltas are
+    /// skipped, as is a final line cut short by an interrupted recording.
+    /// Playback stops early if the server goes away.
+    pub async fn play(
+        path: impl AsRef<Path>,
+        tx: &mpsc::Sender<ServerEvent>,
+        speed_factor: f64,
+    ) -> io::Result<usize> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(tokio::fs::File::open(path).await?);
+        let mut line = String::new();
+        let mut line_no = 0;
+        let mut sent = 0;
+        let mut due = Instant::now();
+        let mut last_time: Option<Option<DateTime<Utc>>> = None;
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(sent);
+            }
+            line_no += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let delta: Delta = match serde_json::from_str(&line) {
+                Ok(delta) => delta,
+                Err(_) if !line.ends_with('\n') => {
+                    warn!("{}: ignoring truncated last line", path.display());
+                    return Ok(sent);
+                }
+                Err(e) => {
+                    warn!(
+                        "{}:{}: not a delta, skipped: {}",
+                        path.display(),
+                        line_no,
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            let time = delta_time(&delta);
+            if let Some(previous) = last_time {
+                due += scaled(gap(previous, time), speed_factor);
+                tokio::time::sleep_until(due).await;
+            }
+            last_time = Some(time);
+
+            if tx.send(ServerEvent::DeltaReceived(delta)).await.is_err() {
+                return Ok(sent);
+            }
+            sent += 1;
+        }
+    }
+}
+
+/// The first update timestamp of a delta.
+fn delta_time(delta: &Delta) -> Option<DateTime<Utc>> {
+    delta
+        .updates
+        .iter()
+        .find_map(|update| update.timestamp.as_deref())
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// Recorded time between two deltas.
+fn gap(previous: Option<DateTime<Utc>>, next: Option<DateTime<Utc>>) -> Duration {
+    match (previous, next) {
+        (Some(previous), Some(next)) => (next - previous).to_std().unwrap_or(Duration::ZERO),
+        _ => DeltaPlayer::UNTIMED_GAP,
+    }
+}
+
+/// `gap` at `speed_factor` times normal speed.
+fn scaled(gap: Duration, speed_factor: f64) -> Duration {
+    if speed_factor > 0.0 && speed_factor.is_finite() {
+        gap.div_f64(speed_factor)
+    } else {
+        Duration::ZERO
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signalk_core::{PathValue, Update};
+
+    fn delta(timestamp: Option<&str>, sog: f64) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: timestamp.map(str::to_string),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(sog),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("signalk-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_record_and_play_back() {
+        let dir = temp_dir("recording");
+        let path = dir.join("trip.jsonl");
+        let recorded = [
+            delta(Some("2024-01-17T10:30:00.000Z"), 3.0),
+            delta(Some("2024-01-17T10:30:01.000Z"), 3.5),
+            delta(Some("2024-01-17T10:30:03.000Z"), 4.0),
+        ];
+
+        let (broadcast_tx, broadcast_rx) = broadcast::channel::<std::sync::Arc<Delta>>(16);
+        let recorder = DeltaRecorder::create(&path, 1 << 20, 1).unwrap();
+        let recording = tokio::spawn(recorder.run(broadcast_rx));
+        for delta in &recorded {
+            broadcast_tx
+                .send(std::sync::Arc::new(delta.clone()))
+                .unwrap();
+        }
+        drop(broadcast_tx);
+        recording.await.unwrap().unwrap();
+
+        // Twice as fast: 1.5s instead of 3s
+        let (tx, mut rx) = mpsc::channel(16);
+        let start = Instant::now();
+        let sent = DeltaPlayer::play(&path, &tx, 2.0).await.unwrap();
+        assert_eq!(sent, 3);
+        assert_eq!(start.elapsed(), Duration::from_millis(1500));
+
+        for expected in &recorded {
+            match rx.recv().await.unwrap() {
+                ServerEvent::DeltaReceived(delta) => assert_eq!(&delta, expected),
+                other => panic!("unexpected event {other:?}"),
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_untimed_and_truncated_lines() {
+        let dir = temp_dir("playback");
+        let path = dir.join("trip.jsonl");
+        let mut contents = String::new();
+        for delta in [delta(None, 1.0), delta(None, 2.0)] {
+            contents.push_str(&serde_json::to_string(&delta).unwrap());
+            contents.push('\n');
+        }
+        contents.push_str("not json\n");
+        contents.push_str(r#"{"context":"vessels.self","upd"#);
+        fs::write(&path, contents).unwrap();
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let start = Instant::now();
+        assert_eq!(DeltaPlayer::play(&path, &tx, 1.0).await.unwrap(), 2);
+        assert_eq!(start.elapsed(), DeltaPlayer::UNTIMED_GAP);
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = temp_dir("rotation");
+        let path = dir.join("trip.jsonl");
+        let line_len = serde_json::to_vec(&delta(None, 1.0)).unwrap().len() as u64 + 1;
+
+        // Room for two lines per file, two old files kept
+        let mut recorder = DeltaRecorder::create(&path, 2 * line_len, 2).unwrap();
+        for i in 0..7 {
+            recorder.record(&delta(None, f64::from(i))).unwrap();
+        }
+
+        let lines = |path: &Path| fs::read_to_string(path).unwrap().lines().count();
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&recorder.rotated(1)), 2);
+        assert_eq!(lines(&recorder.rotated(2)), 2);
+        assert!(!recorder.rotated(3).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! Replay buffer for closing the connect-time gap.
+//!
+//! A new connection reads a snapshot of the store and only then subscribes
+//! to the broadcast channel. Deltas applied in between would be neither in
+//! the snapshot nor in the receiver, so the server keeps the last K
+//! broadcast deltas, tagged with a sequence number, and replays those newer
+//! than the snapshot.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use signalk_core::Delta;
+
+/// A delta tagged with its position in the store's update order.
+///
+/// The delta is serialized once here and shared by every connection, so a
+/// broadcast to N clients doesn't serialize it N times. Cloning is cheap.
+#[derive(Debug, Clone)]
+pub(crate) struct SequencedDelta {
+    /// Monotonic sequence number, starting at 1.
+    pub seq: u64,
+    pub delta: Arc<Delta>,
+    /// The delta as a JSON stream message.
+    pub encoded: Arc<str>,
+}
+
+impl AsRef<Delta> for SequencedDelta {
+    fn as_ref(&self) -> &Delta {
+        &self.delta
+    }
+}
+
+/// Ring buffer of the most recently applied deltas.
+#[derive(Debug)]
+pub(crate) struct ReplayBuffer {
+    capacity: usize,
+    last_seq: u64,
+    deltas: VecDeque<SequencedDelta>,
+}
+
+impl ReplayBuffer {
+    /// Create a buffer keeping the last `capacity` deltas (0 disables replay).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            last_seq: 0,
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Sequence number of the most recently applied delta (0 if none).
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Record an applied delta and return it with its sequence number.
+    pub fn push(&mut self, delta: Delta) -> SequencedDelta {
+        self.last_seq += 1;
+        let encoded = serde_json::to_string(&delta).unwrap_or_default();
+        let sequenced = SequencedDelta {
+            seq: self.last_seq,
+            delta: Arc::new(delta),
+            encoded: encoded.into(),
+        };
+
+        if self.capacity > 0 {
+            if self.deltas.len() == self.capacity {
+                self.deltas.pop_front();
+            }
+            self.deltas.push_back(sequenced.clone());
+        }
+        sequenced
+    }
+
+    /// Deltas applied after `seq`, oldest first.
+    ///
+    /// Returns `None` if some of them have already been evicted, i.e. the
+    /// window is too small to close the gap.
+    pub fn since(&self, seq: u64) -> Option<Vec<SequencedDelta>> {
+        if seq >= self.last_seq {
+            return Some(Vec::new());
+        }
+
+        match self.deltas.front() {
+            Some(oldest) if oldest.seq <= seq + 1 => Some(
+                self.deltas
+                    .iter()
+                    .filter(|d| d.seq > seq)
+                    .cloned()
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta() -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![],
+        }
+    }
+
+    fn seqs(deltas: Option<Vec<SequencedDelta>>) -> Option<Vec<u64>> {
+        deltas.map(|d| d.iter().map(|d| d.seq).collect())
+    }
+
+    #[test]
+    fn test_since_returns_newer_deltas() {
+        let mut buffer = ReplayBuffer::new(4);
+        for _ in 0..3 {
+            buffer.push(delta());
+        }
+
+        assert_eq!(buffer.last_seq(), 3);
+        assert_eq!(seqs(buffer.since(1)), Some(vec![2, 3]));
+        assert_eq!(seqs(buffer.since(0)), Some(vec![1, 2, 3]));
+        assert_eq!(seqs(buffer.since(3)), Some(vec![]));
+    }
+
+    #[test]
+    fn test_since_detects_evicted_gap() {
+        let mut buffer = ReplayBuffer::new(2);
+        for _ in 0..5 {
+            buffer.push(delta());
+        }
+
+        assert_eq!(seqs(buffer.since(3)), Some(vec![4, 5]));
+        assert_eq!(seqs(buffer.since(2)), None);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_replay() {
+        let mut buffer = ReplayBuffer::new(0);
+        buffer.push(delta());
+
+        assert_eq!(seqs(buffer.since(1)), Some(vec![]));
+        assert_eq!(seqs(buffer.since(0)), None);
+    }
+}
@@ -5,30 +5,69 @@
 //! - Hello message on connect
 //! - Delta broadcasting
 //! - Subscription management
+//! - Graceful shutdown (see [`ShutdownHandle`])
 
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc, RwLock};
-use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio::sync::{broadcast, mpsc, watch, Semaphore};
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::handshake::server::{
+    Callback, ErrorResponse, Request, Response,
+};
+use tokio_tungstenite::tungstenite::http::header::{HeaderValue, SEC_WEBSOCKET_EXTENSIONS};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
 use tracing::{debug, error, info, warn};
 
-use signalk_core::{Delta, MemoryStore, SignalKStore};
+use signalk_core::{
+    full_fragment, zone_notifications, AclRule, Delta, DeltaSink, MemoryStore, NotificationMethods,
+    PathAcl, PathValue, Permission, PositionCoalescer, PutResult, SentinelFilter, SentinelRule,
+    SharedStore, SignalKStore, SourcePriorities, Update, WritablePaths,
+};
 use signalk_protocol::{
-    encode_server_message, ClientMessage, HelloMessage, ServerMessage, SubscribeRequest,
-    Subscription,
+    decode_inbound_message, encode_server_message, Capabilities, ClientMessage, DeltaBatch,
+    GetRequest, GetResponse, HelloMessage, InboundMessage, PutRequest, PutResponse, PutState,
+    ServerMessage, SubscribeRequest, SubscribeResponse, Subscription, SubscriptionFormat,
+};
+
+use crate::batch::chronological_order;
+use crate::coalesce::DeltaCoalescer;
+use crate::connections::{self, ConnectionStats, IdleTimer};
+use crate::dedup::OutboundDedup;
+use crate::deflate::{negotiate_deflate, InflateStream, MessageDeflater};
+use crate::replay::{ReplayBuffer, SequencedDelta};
+use crate::subscription::{
+    log_subscription_event, ClientSubscription, SelfContext, SubscriptionManager,
 };
 
-use crate::subscription::{ClientSubscription, SubscriptionManager};
+/// What to do when a client falls so far behind that the broadcast channel
+/// overwrites deltas it hasn't received yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LagPolicy {
+    /// Log a warning and carry on; the client misses the skipped deltas.
+    #[default]
+    SkipAndWarn,
+    /// Close the connection, so the client reconnects and starts over.
+    DisconnectClient,
+    /// Send the current values of the client's subscriptions, so its state
+    /// is consistent again.
+    SendResync,
+}
 
 /// Configuration for the SignalK server.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ServerConfig {
     /// Server name sent in Hello message.
     pub name: String,
@@ -36,8 +75,121 @@ pub struct ServerConfig {
     pub version: String,
     /// Self vessel URN.
     pub self_urn: String,
+    /// Self vessel name, included in Hello when set.
+    pub vessel_name: Option<String>,
     /// Address to bind to.
     pub bind_addr: SocketAddr,
+    /// Number of recent deltas kept to replay to clients that connect while
+    /// deltas are flowing (0 disables replay).
+    pub replay_buffer_size: usize,
+    /// Apply and broadcast `DeltaBatch` events one update at a time in
+    /// timestamp order (stable on arrival order) instead of as received.
+    pub chronological_batches: bool,
+    /// Add a `serverTimestamp` (processing time) to every update of
+    /// broadcast deltas, alongside the source `timestamp`.
+    pub server_timestamps: bool,
+    /// Path patterns clients may write with PUT (usually loaded from
+    /// `ServerSettings::writable_paths`).
+    pub writable_paths: Vec<String>,
+    /// Path patterns whose object values are deep-merged into the stored
+    /// object instead of replacing it (usually loaded from
+    /// `ServerSettings::merge_paths`).
+    pub merge_paths: Vec<String>,
+    /// Preferred sources per path, deciding which source's value is
+    /// primary (usually loaded from `ServerSettings::source_priorities`).
+    pub source_priorities: SourcePriorities,
+    /// If set, only these client addresses may connect.
+    pub ip_allowlist: Option<Vec<IpAddr>>,
+    /// Client addresses that are always refused (checked before the
+    /// allowlist).
+    pub ip_denylist: Vec<IpAddr>,
+    /// Presentation methods (`visual`, `sound`) for notifications raised by
+    /// `meta.zones`, keyed by severity.
+    pub notification_methods: NotificationMethods,
+    /// Default `minPeriod` (milliseconds) for the subscription created by
+    /// `subscribe=all`, so a catch-all client isn't sent every update.
+    /// Explicit subscriptions from the client are not throttled by it.
+    pub default_all_min_period_ms: Option<u64>,
+    /// Maximum number of subscriptions per connection; further subscribe
+    /// entries are ignored with a warning.
+    pub max_subscriptions: Option<usize>,
+    /// Maximum number of concurrent connections; connections over the
+    /// limit are answered with `503 Service Unavailable` and closed.
+    pub max_connections: Option<usize>,
+    /// Combine separately reported `navigation.position.latitude` and
+    /// `.longitude` into one `navigation.position` value, pairing halves
+    /// that arrive within this many milliseconds. Off when `None`.
+    pub coalesce_position_ms: Option<u64>,
+    /// Sentinel values (0, NaN, 0,0 positions) to drop per path pattern
+    /// before they reach the store.
+    pub sentinel_rules: Vec<SentinelRule>,
+    /// Treat every vessel context as self (for single-vessel installs
+    /// where a provider mislabels its context).
+    pub treat_all_contexts_as_self: bool,
+    /// Close WebSocket connections after this many milliseconds without
+    /// inbound frames or outbound deltas, after sending a warning. This is
+    /// application idleness, separate from ping/pong liveness. Off when
+    /// `None`.
+    pub idle_timeout_ms: Option<u64>,
+    /// Send the current values of newly subscribed paths in response to a
+    /// subscribe message, unless the message sets `sendCachedValues`.
+    pub prime_on_subscribe: bool,
+    /// Largest serialized size (bytes) of a single value; larger values
+    /// are dropped before reaching the store. Unlimited when `None`.
+    pub max_value_bytes: Option<usize>,
+    /// Minimum permissions for reading or writing matching paths (usually
+    /// loaded from `SecurityConfig::acls`).
+    pub acl_rules: Vec<AclRule>,
+    /// Permission of connecting clients. Clients don't authenticate on
+    /// this server yet, so every connection gets this level.
+    pub client_permission: Permission,
+    /// Deltas the broadcast channel holds for clients that haven't caught
+    /// up; a client further behind has lagged.
+    pub broadcast_capacity: usize,
+    /// How lagged clients are handled.
+    pub lag_policy: LagPolicy,
+    /// Messages queued for each client before further deltas are
+    /// coalesced to the newest value per path until it catches up.
+    pub client_queue_capacity: usize,
+    /// Negotiate `permessage-deflate` with clients that offer it, and
+    /// compress messages of at least this many bytes (usually loaded from
+    /// `ServerSettings::ws_compression_threshold`). Off when `None`.
+    pub ws_compression_threshold: Option<usize>,
+    /// How long a shutdown waits for clients to be sent what is queued for
+    /// them and their close frame, in milliseconds; connections still open
+    /// after that are dropped.
+    pub shutdown_grace_ms: u64,
+}
+
+impl ServerConfig {
+    /// Check a client address against the deny- and allowlist.
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) are compared as IPv4.
+    pub fn is_ip_allowed(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        if self.ip_denylist.iter().any(|d| d.to_canonical() == ip) {
+            return false;
+        }
+        match &self.ip_allowlist {
+            Some(allowed) => allowed.iter().any(|a| a.to_canonical() == ip),
+            None => true,
+        }
+    }
+
+    /// The capabilities advertised in Hello.
+    ///
+    /// PUT is offered when any path is writable, compression when
+    /// `permessage-deflate` is enabled. CBOR is not implemented by this
+    /// server.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            put: !self.writable_paths.is_empty(),
+            formats: vec![SubscriptionFormat::Delta, SubscriptionFormat::Full],
+            max_subscriptions: self.max_subscriptions,
+            cbor: false,
+            compression: self.ws_compression_threshold.is_some(),
+        }
+    }
 }
 
 impl Default for ServerConfig {
@@ -47,7 +199,39 @@ impl Default for ServerConfig {
             version: "1.7.0".to_string(),
             self_urn: "vessels.urn:mrn:signalk:uuid:00000000-0000-0000-0000-000000000000"
                 .to_string(),
+            vessel_name: None,
             bind_addr: "0.0.0.0:3000".parse().unwrap(),
+            replay_buffer_size: 64,
+            chronological_batches: false,
+            server_timestamps: false,
+            writable_paths: signalk_core::DEFAULT_WRITABLE_PATHS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            merge_paths: signalk_core::DEFAULT_MERGE_PATHS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            source_priorities: SourcePriorities::default(),
+            ip_allowlist: None,
+            ip_denylist: Vec::new(),
+            notification_methods: NotificationMethods::default(),
+            default_all_min_period_ms: None,
+            max_subscriptions: None,
+            max_connections: None,
+            coalesce_position_ms: None,
+            sentinel_rules: Vec::new(),
+            treat_all_contexts_as_self: false,
+            idle_timeout_ms: None,
+            prime_on_subscribe: false,
+            max_value_bytes: Some(64 * 1024),
+            acl_rules: Vec::new(),
+            client_permission: Permission::Admin,
+            broadcast_capacity: 1024,
+            lag_policy: LagPolicy::SkipAndWarn,
+            client_queue_capacity: 64,
+            ws_compression_threshold: None,
+            shutdown_grace_ms: 5000,
         }
     }
 }
@@ -57,86 +241,335 @@ impl Default for ServerConfig {
 pub enum ServerEvent {
     /// A delta was received from a provider.
     DeltaReceived(Delta),
+    /// Several deltas received together (e.g. file replay), applied in order.
+    DeltaBatch(Vec<Delta>),
+    /// The self URN changed (e.g. regenerated after a config reset).
+    ///
+    /// Existing `vessels.self` subscriptions follow the new URN without
+    /// reconnecting; data under the old URN stays as another vessel.
+    SelfUrnChanged(String),
+}
+
+/// [`DeltaSink`] that feeds deltas into a server's event channel.
+///
+/// Lets providers submit deltas without depending on tokio channel types.
+/// `submit` never blocks: if the event queue is full the delta is dropped
+/// and a warning logged, so a runaway provider can't stall its caller.
+#[derive(Debug, Clone)]
+pub struct EventSink {
+    tx: mpsc::Sender<ServerEvent>,
+}
+
+impl EventSink {
+    /// Wrap an event sender.
+    pub fn new(tx: mpsc::Sender<ServerEvent>) -> Self {
+        Self { tx }
+    }
+}
+
+impl DeltaSink for EventSink {
+    fn submit(&self, delta: Delta) {
+        match self.tx.try_send(ServerEvent::DeltaReceived(delta)) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                warn!("Server event queue full, dropping delta");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                debug!("Server stopped, dropping delta");
+            }
+        }
+    }
+}
+
+/// Asks a running [`SignalKServer`] to shut down.
+///
+/// Cloneable, so a signal handler and the code that owns the server can
+/// each hold one.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    /// Start a graceful shutdown; `run` returns once it is done. Does
+    /// nothing if the server has already stopped.
+    pub fn shutdown(&self) {
+        self.tx.send_replace(true);
+    }
+}
+
+/// The close frame sent to clients when the server shuts down.
+pub fn shutdown_close_frame() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: Cow::Borrowed("Server shutting down"),
+    }))
+}
+
+/// Wait until `shutdown` is set. Pending forever if its sender is gone,
+/// since nothing can start a shutdown then.
+pub async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    if shutdown.wait_for(|requested| *requested).await.is_err() {
+        std::future::pending::<()>().await;
+    }
 }
 
 /// The SignalK WebSocket server.
 pub struct SignalKServer {
     config: ServerConfig,
-    store: Arc<RwLock<MemoryStore>>,
+    /// Current self URN, shared with every connection's subscriptions.
+    self_context: SelfContext,
+    store: Arc<SharedStore>,
+    /// Recently applied deltas, replayed to newly connected clients.
+    replay: Arc<Mutex<ReplayBuffer>>,
     /// Channel for broadcasting deltas to all connection handlers.
-    delta_tx: broadcast::Sender<Delta>,
+    delta_tx: broadcast::Sender<SequencedDelta>,
     /// Channel for receiving events from providers.
     event_tx: mpsc::Sender<ServerEvent>,
     event_rx: mpsc::Receiver<ServerEvent>,
+    /// Compiled `config.writable_paths`.
+    writable_paths: Arc<WritablePaths>,
+    /// Compiled `config.acl_rules`.
+    acl: Arc<PathAcl>,
+    /// Open and peak connection counts.
+    connections: ConnectionStats,
+    /// Set by [`ShutdownHandle::shutdown`].
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+/// Server state handed to each connection handler.
+#[derive(Clone)]
+struct ConnectionShared {
+    config: ServerConfig,
+    self_context: SelfContext,
+    store: Arc<SharedStore>,
+    replay: Arc<Mutex<ReplayBuffer>>,
+    delta_tx: broadcast::Sender<SequencedDelta>,
+    event_tx: mpsc::Sender<ServerEvent>,
+    writable_paths: Arc<WritablePaths>,
+    acl: Arc<PathAcl>,
+    /// Set once queued events are applied and clients are to be closed.
+    closing: watch::Receiver<bool>,
 }
 
 impl SignalKServer {
     /// Create a new SignalK server with the given configuration.
     pub fn new(config: ServerConfig) -> Self {
-        let store = MemoryStore::new(&config.self_urn);
-        let (delta_tx, _) = broadcast::channel(1024);
+        let mut store = MemoryStore::new(&config.self_urn);
+        if let Err(e) = store.set_merge_paths(&config.merge_paths) {
+            warn!("Invalid merge path pattern, using defaults: {}", e);
+        }
+        if let Err(e) = store.set_source_priorities(&config.source_priorities) {
+            warn!(
+                "Invalid source priority pattern, using the latest source: {}",
+                e
+            );
+        }
+        let replay = ReplayBuffer::new(config.replay_buffer_size);
+        // A zero capacity would panic
+        let (delta_tx, _) = broadcast::channel(config.broadcast_capacity.max(1));
         let (event_tx, event_rx) = mpsc::channel(1024);
+        // Fail closed: a broken allowlist makes everything read-only
+        let writable_paths = WritablePaths::new(&config.writable_paths).unwrap_or_else(|e| {
+            warn!("Invalid writable path pattern, PUT disabled: {}", e);
+            WritablePaths::none()
+        });
+        // Fail closed here too: only admins get through a broken ACL
+        let acl = PathAcl::new(&config.acl_rules).unwrap_or_else(|e| {
+            warn!(
+                "Invalid ACL path pattern, restricting all paths to admins: {}",
+                e
+            );
+            PathAcl::admin_only()
+        });
 
         Self {
+            self_context: SelfContext::new(&config.self_urn),
             config,
-            store: Arc::new(RwLock::new(store)),
+            store: Arc::new(SharedStore::new(store)),
+            replay: Arc::new(Mutex::new(replay)),
             delta_tx,
             event_tx,
             event_rx,
+            writable_paths: Arc::new(writable_paths),
+            acl: Arc::new(acl),
+            connections: ConnectionStats::default(),
+            shutdown: Arc::new(watch::channel(false).0),
         }
     }
 
+    /// Get a handle that shuts the server down once it runs.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            tx: self.shutdown.clone(),
+        }
+    }
+
+    /// Get the connection counters (shared, so they stay live after `run`).
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.connections.clone()
+    }
+
     /// Get a sender for submitting events to the server.
     pub fn event_sender(&self) -> mpsc::Sender<ServerEvent> {
         self.event_tx.clone()
     }
 
+    /// Get a runtime-agnostic sink for providers.
+    pub fn delta_sink(&self) -> EventSink {
+        EventSink::new(self.event_tx.clone())
+    }
+
     /// Get the current self URN.
-    pub fn self_urn(&self) -> &str {
-        &self.config.self_urn
+    pub fn self_urn(&self) -> String {
+        self.self_context.get()
     }
 
     /// Get a reference to the data store for reading.
-    pub fn store(&self) -> Arc<RwLock<MemoryStore>> {
+    pub fn store(&self) -> Arc<SharedStore> {
         self.store.clone()
     }
 
-    /// Run the server, listening for WebSocket connections.
-    pub async fn run(mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// Receive the deltas broadcast to clients once they are applied, e.g.
+    /// for a [`DeltaRecorder`](crate::DeltaRecorder).
+    pub fn subscribe_deltas(
+        &self,
+    ) -> broadcast::Receiver<impl AsRef<Delta> + Clone + Send + 'static> {
+        self.delta_tx.subscribe()
+    }
+
+    /// Run the server, listening for WebSocket connections, until shut
+    /// down through a [`ShutdownHandle`].
+    ///
+    /// A shutdown stops accepting connections and applies the events
+    /// already queued. Then every client is sent its queued messages and a
+    /// close frame, and is given `shutdown_grace_ms` to finish.
+    pub async fn run(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let listener = TcpListener::bind(&self.config.bind_addr).await?;
         info!("SignalK server listening on {}", self.config.bind_addr);
 
         // Spawn the event processor
         let store = self.store.clone();
+        let replay = self.replay.clone();
         let delta_tx = self.delta_tx.clone();
-        tokio::spawn(async move {
-            while let Some(event) = self.event_rx.recv().await {
-                match event {
-                    ServerEvent::DeltaReceived(delta) => {
-                        // Apply delta to store
-                        {
-                            let mut store = store.write().await;
-                            store.apply_delta(&delta);
-                        }
-                        // Broadcast to all clients
-                        let _ = delta_tx.send(delta);
+        let chronological_batches = self.config.chronological_batches;
+        let config = self.config.clone();
+        let self_context = self.self_context.clone();
+        let mut coalescer = self
+            .config
+            .coalesce_position_ms
+            .map(|ms| PositionCoalescer::new(Duration::from_millis(ms)));
+        // Fail open: a broken rule set lets data through rather than drop it
+        let sentinels = SentinelFilter::new(&self.config.sentinel_rules).unwrap_or_else(|e| {
+            warn!("Invalid sentinel rule pattern, filtering disabled: {}", e);
+            SentinelFilter::default()
+        });
+        let mut shutdown = self.shutdown.subscribe();
+        let mut event_rx = self.event_rx;
+        let mut processor_shutdown = shutdown.clone();
+        let processor = tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    event = event_rx.recv() => match event {
+                        Some(event) => event,
+                        None => break,
+                    },
+                    // Apply what has already been submitted, then stop
+                    () = shutdown_requested(&mut processor_shutdown) => match event_rx.try_recv() {
+                        Ok(event) => event,
+                        Err(_) => break,
+                    },
+                };
+                let deltas = match event {
+                    ServerEvent::DeltaReceived(delta) => vec![delta],
+                    ServerEvent::DeltaBatch(batch) if chronological_batches => {
+                        chronological_order(batch)
+                    }
+                    ServerEvent::DeltaBatch(batch) => batch,
+                    ServerEvent::SelfUrnChanged(self_urn) => {
+                        info!("Self URN changed to {}", self_urn);
+                        // Under the store lock so deltas applied afterwards
+                        // resolve against the new URN everywhere.
+                        let mut store = store.write();
+                        store.set_self_urn(&self_urn);
+                        self_context.set(&self_urn);
+                        continue;
+                    }
+                };
+
+                for mut delta in deltas {
+                    if config.treat_all_contexts_as_self {
+                        delta.promote_to_self();
+                    }
+                    let delta = match &mut coalescer {
+                        Some(coalescer) => coalescer.coalesce(delta, Instant::now()),
+                        None => Some(delta),
+                    };
+                    if let Some(delta) = delta
+                        .and_then(|delta| sentinels.filter(delta))
+                        .and_then(|delta| reject_oversized(delta, config.max_value_bytes))
+                    {
+                        apply_and_broadcast(&store, &replay, &delta_tx, delta, &config).await;
                     }
                 }
             }
         });
+        let (closing_tx, closing) = watch::channel(false);
+        let mut open_connections = JoinSet::new();
+
+        let connection_limit = self
+            .config
+            .max_connections
+            .map(|max| Arc::new(Semaphore::new(max)));
 
         // Accept connections
         loop {
-            match listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                () = shutdown_requested(&mut shutdown) => break,
+                // Reap finished connections
+                Some(_) = open_connections.join_next(), if !open_connections.is_empty() => continue,
+            };
+            match accepted {
                 Ok((stream, addr)) => {
-                    let config = self.config.clone();
-                    let store = self.store.clone();
-                    let delta_rx = self.delta_tx.subscribe();
-
-                    tokio::spawn(async move {
-                        if let Err(e) =
-                            handle_connection(stream, addr, config, store, delta_rx).await
-                        {
+                    // Refuse before the handshake; dropping the stream closes it
+                    if !self.config.is_ip_allowed(addr.ip()) {
+                        warn!("Refused connection from {}", addr);
+                        continue;
+                    }
+
+                    let permit = match &connection_limit {
+                        Some(limit) => match limit.clone().try_acquire_owned() {
+                            Ok(permit) => Some(permit),
+                            Err(_) => {
+                                warn!(
+                                    "Connection limit reached ({} open), refusing {}",
+                                    self.connections.current(),
+                                    addr
+                                );
+                                tokio::spawn(connections::refuse(stream));
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
+                    let guard = self.connections.track(permit);
+
+                    let shared = ConnectionShared {
+                        config: self.config.clone(),
+                        self_context: self.self_context.clone(),
+                        store: self.store.clone(),
+                        replay: self.replay.clone(),
+                        delta_tx: self.delta_tx.clone(),
+                        event_tx: self.event_tx.clone(),
+                        writable_paths: self.writable_paths.clone(),
+                        acl: self.acl.clone(),
+                        closing: closing.clone(),
+                    };
+
+                    open_connections.spawn(async move {
+                        let _guard = guard;
+                        if let Err(e) = handle_connection(stream, addr, shared).await {
                             error!("Connection error from {}: {}", addr, e);
                         }
                     });
@@ -146,6 +579,188 @@ impl SignalKServer {
                 }
             }
         }
+
+        info!(
+            "Shutting down, closing {} connections",
+            open_connections.len()
+        );
+        drop(listener);
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_millis(self.config.shutdown_grace_ms);
+        if tokio::time::timeout_at(deadline, processor).await.is_err() {
+            warn!("Queued events not applied within the shutdown grace period");
+        }
+        closing_tx.send_replace(true);
+        let drained = tokio::time::timeout_at(deadline, async {
+            while open_connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                "Dropping {} connections still open after the shutdown grace period",
+                open_connections.len()
+            );
+            open_connections.shutdown().await;
+        }
+        info!("SignalK server stopped");
+        Ok(())
+    }
+}
+
+/// Drop values larger than `max_bytes` from a delta, logging each.
+///
+/// Returns `None` if that leaves nothing.
+pub fn reject_oversized(mut delta: Delta, max_bytes: Option<usize>) -> Option<Delta> {
+    let Some(max_bytes) = max_bytes else {
+        return Some(delta);
+    };
+    let removed = delta.remove_oversized_values(max_bytes);
+    if removed.is_empty() {
+        return Some(delta);
+    }
+    for path in removed {
+        warn!(
+            "Dropped value for {} in {}: larger than {} bytes",
+            path,
+            delta.context.as_deref().unwrap_or("vessels.self"),
+            max_bytes
+        );
+    }
+    (!delta.updates.is_empty()).then_some(delta)
+}
+
+/// Apply a delta to the store and broadcast it to all connections.
+///
+/// Zone notifications raised by the delta are applied and broadcast right
+/// after it.
+async fn apply_and_broadcast(
+    store: &SharedStore,
+    replay: &Mutex<ReplayBuffer>,
+    delta_tx: &broadcast::Sender<SequencedDelta>,
+    delta: Delta,
+    config: &ServerConfig,
+) {
+    let sequenced = {
+        let mut store = store.write();
+        store.apply_delta(&delta);
+        sequence_applied(&mut store, replay, delta, config)
+    };
+    // Broadcast to all clients
+    for sequenced in sequenced {
+        let _ = delta_tx.send(sequenced);
+    }
+}
+
+/// Raise zone notifications for a delta already applied to `store`, and
+/// number both for the replay buffer.
+///
+/// Must be called under the store lock, so the sequence numbers match the
+/// order snapshots observe.
+fn sequence_applied(
+    store: &mut MemoryStore,
+    replay: &Mutex<ReplayBuffer>,
+    delta: Delta,
+    config: &ServerConfig,
+) -> Vec<SequencedDelta> {
+    let notifications = zone_notifications(store, &delta, &config.notification_methods);
+    if let Some(notifications) = &notifications {
+        store.apply_delta(notifications);
+    }
+
+    let mut replay = lock_replay(replay);
+    std::iter::once(delta)
+        .chain(notifications)
+        .map(|mut delta| {
+            // Stamped after applying: the stamp is for clients only
+            if config.server_timestamps {
+                let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+                for update in &mut delta.updates {
+                    update.server_timestamp = Some(now.clone());
+                }
+            }
+            replay.push(delta)
+        })
+        .collect()
+}
+
+/// Lock the replay buffer, recovering from a poisoned lock (the buffer holds
+/// no invariants a panicking holder could break).
+fn lock_replay(replay: &Mutex<ReplayBuffer>) -> std::sync::MutexGuard<'_, ReplayBuffer> {
+    replay.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Query parameters and negotiated compression of a WebSocket handshake.
+#[derive(Debug)]
+struct HandshakeParams {
+    subscribe_mode: String,
+    send_cached: bool,
+    full_format: bool,
+    dedup_ms: Option<u64>,
+    deflate: bool,
+}
+
+impl Default for HandshakeParams {
+    fn default() -> Self {
+        Self {
+            subscribe_mode: String::from("self"),
+            send_cached: true,
+            full_format: false,
+            dedup_ms: None,
+            deflate: false,
+        }
+    }
+}
+
+/// Handshake callback recording the [`HandshakeParams`] of a connection.
+struct Handshake {
+    addr: SocketAddr,
+    compression_threshold: Option<usize>,
+    params: Arc<Mutex<HandshakeParams>>,
+}
+
+impl Callback for Handshake {
+    fn on_request(self, req: &Request, mut resp: Response) -> Result<Response, ErrorResponse> {
+        let user_agent = req
+            .headers()
+            .get("user-agent")
+            .and_then(|ua| ua.to_str().ok())
+            .unwrap_or("-");
+        info!(
+            "WebSocket handshake from {} (user-agent: {})",
+            self.addr, user_agent
+        );
+        let mut params = self.params.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Extract query parameters from the URI
+        if let Some(query) = req.uri().query() {
+            for param in query.split('&') {
+                if let Some((key, value)) = param.split_once('=') {
+                    match key {
+                        "subscribe" => params.subscribe_mode = value.to_string(),
+                        "sendCachedValues" => params.send_cached = value == "true",
+                        "format" => params.full_format = value == "full",
+                        "dedup" => params.dedup_ms = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let extension = self.compression_threshold.and_then(|_| {
+            req.headers()
+                .get_all(SEC_WEBSOCKET_EXTENSIONS)
+                .iter()
+                .filter_map(|offers| offers.to_str().ok())
+                .find_map(negotiate_deflate)
+        });
+        if let Some(extension) = extension {
+            params.deflate = true;
+            resp.headers_mut().insert(
+                SEC_WEBSOCKET_EXTENSIONS,
+                HeaderValue::from_static(extension),
+            );
+        }
+        Ok(resp)
     }
 }
 
@@ -153,74 +768,130 @@ impl SignalKServer {
 async fn handle_connection(
     stream: TcpStream,
     addr: SocketAddr,
-    config: ServerConfig,
-    store: Arc<RwLock<MemoryStore>>,
-    mut delta_rx: broadcast::Receiver<Delta>,
+    shared: ConnectionShared,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let ConnectionShared {
+        config,
+        self_context,
+        store,
+        replay,
+        delta_tx,
+        mut closing,
+        ..
+    } = shared.clone();
     info!("New connection from {}", addr);
 
-    // Parse query parameters from WebSocket handshake
-    let subscribe_mode = Arc::new(RwLock::new(String::from("self")));
-    let send_cached = Arc::new(RwLock::new(true));
-
-    let subscribe_mode_clone = subscribe_mode.clone();
-    let send_cached_clone = send_cached.clone();
-
-    // Perform WebSocket handshake with callback to extract query params
-    let ws_stream =
-        tokio_tungstenite::accept_hdr_async(stream, move |req: &Request, resp: Response| {
-            // Extract query parameters from the URI
-            if let Some(query) = req.uri().query() {
-                for param in query.split('&') {
-                    if let Some((key, value)) = param.split_once('=') {
-                        match key {
-                            "subscribe" => {
-                                if let Ok(mut mode) = subscribe_mode_clone.try_write() {
-                                    *mode = value.to_string();
-                                }
-                            }
-                            "sendCachedValues" => {
-                                if let Ok(mut cached) = send_cached_clone.try_write() {
-                                    *cached = value == "true";
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-            Ok(resp)
-        })
-        .await?;
+    // Perform WebSocket handshake, reading the query parameters
+    let params = Arc::new(Mutex::new(HandshakeParams::default()));
+    let handshake = Handshake {
+        addr,
+        compression_threshold: config.ws_compression_threshold,
+        params: params.clone(),
+    };
+    let stream = InflateStream::new(stream, false);
+    let mut ws_stream = tokio_tungstenite::accept_hdr_async(stream, handshake).await?;
+    let HandshakeParams {
+        subscribe_mode,
+        send_cached,
+        full_format,
+        dedup_ms,
+        deflate,
+    } = std::mem::take(&mut *params.lock().unwrap_or_else(|e| e.into_inner()));
+    let compression_threshold = config.ws_compression_threshold;
+
+    let deflater = match (deflate, compression_threshold) {
+        (true, Some(threshold)) => {
+            debug!("Compressing messages to {}", addr);
+            ws_stream.get_mut().enable();
+            Some(MessageDeflater::new(threshold))
+        }
+        _ => None,
+    };
+    let (ws_tx, mut ws_rx) = ws_stream.split();
 
-    let (mut ws_tx, mut ws_rx) = ws_stream.split();
+    // Messages go out through a bounded queue, so a slow client can't block
+    // this loop; deltas are coalesced while the queue is full
+    let (out_tx, out_rx) = mpsc::channel(config.client_queue_capacity.max(1));
+    let mut writer = tokio::spawn(write_messages(ws_tx, out_rx, addr, deflater));
+    let mut coalescer = DeltaCoalescer::new();
 
     // Send Hello message
-    let hello = HelloMessage::new(&config.name, &config.version, &config.self_urn);
+    let hello = HelloMessage::new(&config.name, &config.version, self_context.get())
+        .with_vessel_name(config.vessel_name.clone())
+        .with_capabilities(config.capabilities());
     let hello_msg = encode_server_message(&ServerMessage::Hello(hello))?;
-    ws_tx.send(Message::Text(hello_msg)).await?;
+    out_tx.send(vec![Message::Text(hello_msg)]).await?;
     debug!("Sent Hello to {}", addr);
 
     // Initialize subscription manager for this client
-    let mut subscriptions = SubscriptionManager::new(&config.self_urn);
+    let mut subscriptions = SubscriptionManager::with_self_context(self_context.clone());
 
     // Apply initial subscription based on query parameter
-    let subscribe_mode_value = subscribe_mode.read().await.clone();
-    match subscribe_mode_value.as_str() {
-        "all" => subscriptions.subscribe_all(),
+    match subscribe_mode.as_str() {
+        "all" => subscriptions.subscribe_all(config.default_all_min_period_ms),
         "none" => {}                             // No default subscriptions
         _ => subscriptions.subscribe_self_all(), // "self" or default
     }
 
+    // Take the snapshot (cached values) and note which delta it reflects
+    let mut dedup = dedup_ms.map(|ms| OutboundDedup::new(Duration::from_millis(ms)));
+    let (initial_delta, snapshot_seq) = {
+        let store = store.read();
+        let initial = send_cached
+            .then(|| subscriptions.get_initial_delta(&store))
+            .flatten()
+            .and_then(|delta| shared.acl.filter_readable(config.client_permission, delta));
+        (initial, lock_replay(&replay).last_seq())
+    };
+
+    // Subscribe only now, then replay what was applied since the snapshot so
+    // that nothing falls between the snapshot and the live stream.
+    let mut delta_rx = delta_tx.subscribe();
+    let missed = lock_replay(&replay).since(snapshot_seq);
+
     // Send cached values for initial subscription if requested
-    let send_cached_value = *send_cached.read().await;
-    if send_cached_value {
-        let store = store.read().await;
-        if let Some(delta) = subscriptions.get_initial_delta(&store) {
-            let msg = encode_server_message(&ServerMessage::Delta(delta))?;
-            ws_tx.send(Message::Text(msg)).await?;
+    if let Some(delta) = initial_delta {
+        out_tx
+            .send(text_messages(encode_delta(
+                delta,
+                &subscriptions,
+                full_format,
+                &self_context,
+            )?))
+            .await?;
+    }
+
+    let mut idle = IdleTimer::new(config.idle_timeout_ms.map(Duration::from_millis));
+    let mut last_seq = snapshot_seq;
+    let mut resume_warnings = Vec::new();
+    match missed {
+        Some(missed) => {
+            for sequenced in missed {
+                last_seq = sequenced.seq;
+                let messages = encode_for_client(
+                    &sequenced,
+                    &mut subscriptions,
+                    &mut dedup,
+                    full_format,
+                    &shared,
+                )?;
+                if !messages.is_empty() {
+                    out_tx.send(text_messages(messages)).await?;
+                }
+            }
+        }
+        None => {
+            warn!(
+                "Client {} connected during a burst larger than the replay buffer ({}); some deltas were missed",
+                addr, config.replay_buffer_size
+            );
+            resume_warnings.push(format!(
+                "Replay buffer ({}) overflowed, some deltas were missed",
+                config.replay_buffer_size
+            ));
         }
     }
+    log_subscription_event("resume", addr, subscriptions.len(), &resume_warnings);
 
     loop {
         tokio::select! {
@@ -228,7 +899,8 @@ async fn handle_connection(
             msg = ws_rx.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
-                        if let Err(e) = handle_client_message(&text, &mut subscriptions, &mut ws_tx).await {
+                        idle.touch();
+                        if let Err(e) = handle_client_message(&text, addr, &shared, &mut subscriptions, full_format, &out_tx).await {
                             warn!("Error handling message from {}: {}", addr, e);
                         }
                     }
@@ -236,9 +908,11 @@ async fn handle_connection(
                         info!("Client {} closed connection", addr);
                         break;
                     }
+                    // Pings keep the connection alive, but don't use it
                     Some(Ok(Message::Ping(data))) => {
-                        ws_tx.send(Message::Pong(data)).await?;
+                        out_tx.send(vec![Message::Pong(data)]).await?;
                     }
+                    Some(Ok(Message::Binary(_))) => idle.touch(),
                     Some(Err(e)) => {
                         error!("WebSocket error from {}: {}", addr, e);
                         break;
@@ -254,18 +928,57 @@ async fn handle_connection(
             // Handle deltas broadcast from server
             delta = delta_rx.recv() => {
                 match delta {
-                    Ok(delta) => {
+                    Ok(sequenced) => {
+                        // Already delivered through the replay
+                        if sequenced.seq <= last_seq {
+                            continue;
+                        }
+                        last_seq = sequenced.seq;
+
                         // Filter delta based on client subscriptions
-                        if let Some(filtered) = subscriptions.filter_delta(&delta) {
-                            let msg = encode_server_message(&ServerMessage::Delta(filtered))?;
-                            if let Err(e) = ws_tx.send(Message::Text(msg)).await {
-                                error!("Failed to send delta to {}: {}", addr, e);
-                                break;
+                        if congested(&out_tx, &coalescer) {
+                            if let Some(delta) = filter_for_client(&sequenced.delta, &mut subscriptions, &mut dedup, &shared) {
+                                coalescer.push(delta);
+                            }
+                            continue;
+                        }
+                        let messages = encode_for_client(&sequenced, &mut subscriptions, &mut dedup, full_format, &shared)?;
+                        if !messages.is_empty() {
+                            if out_tx.try_send(text_messages(messages)).is_err() {
+                                // The writer has stopped after a failed send
+                                return Ok(());
                             }
+                            idle.touch();
                         }
                     }
                     Err(broadcast::error::RecvError::Lagged(n)) => {
                         warn!("Client {} lagged {} messages", addr, n);
+                        match config.lag_policy {
+                            LagPolicy::SkipAndWarn => {}
+                            LagPolicy::DisconnectClient => {
+                                info!("Disconnecting lagged client {}", addr);
+                                out_tx.send(vec![Message::Close(None)]).await?;
+                                break;
+                            }
+                            LagPolicy::SendResync => {
+                                // Deltas up to the snapshot are in it already
+                                let resync = {
+                                    let store = store.read();
+                                    last_seq = lock_replay(&replay).last_seq();
+                                    subscriptions
+                                        .get_initial_delta(&store)
+                                        .and_then(|delta| shared.acl.filter_readable(config.client_permission, delta))
+                                };
+                                if let Some(delta) = resync {
+                                    debug!("Resyncing lagged client {}", addr);
+                                    // Supersedes what was held back
+                                    coalescer.drain();
+                                    let messages = encode_delta(delta, &subscriptions, full_format, &self_context)?;
+                                    out_tx.send(text_messages(messages)).await?;
+                                    idle.touch();
+                                }
+                            }
+                        }
                     }
                     Err(broadcast::error::RecvError::Closed) => {
                         info!("Delta channel closed");
@@ -273,52 +986,473 @@ async fn handle_connection(
                     }
                 }
             }
+
+            // Send the fixed-period values that are due
+            () = sleep_until_due(subscriptions.next_fixed_due()) => {
+                for delta in subscriptions.flush_fixed(Instant::now()) {
+                    if let Some(delta) = shared.acl.filter_readable(config.client_permission, delta) {
+                        if congested(&out_tx, &coalescer) {
+                            coalescer.push(delta);
+                            continue;
+                        }
+                        let messages = encode_delta(delta, &subscriptions, full_format, &self_context)?;
+                        if out_tx.try_send(text_messages(messages)).is_err() {
+                            return Ok(());
+                        }
+                        idle.touch();
+                    }
+                }
+            }
+
+            // Send what was coalesced once the queue has room again
+            permit = out_tx.reserve(), if !coalescer.is_empty() => {
+                let Ok(permit) = permit else {
+                    break;
+                };
+                debug!("Sending {} coalesced values to slow client {}", coalescer.len(), addr);
+                let mut messages = Vec::new();
+                for delta in coalescer.drain() {
+                    messages.extend(encode_delta(delta, &subscriptions, full_format, &self_context)?);
+                }
+                permit.send(text_messages(messages));
+                idle.touch();
+            }
+
+            // The writer stops when sending to the client fails
+            () = out_tx.closed() => {
+                break;
+            }
+
+            // Send what was broadcast before the shutdown, then close
+            () = shutdown_requested(&mut closing) => {
+                while let Ok(sequenced) = delta_rx.try_recv() {
+                    if sequenced.seq <= last_seq {
+                        continue;
+                    }
+                    last_seq = sequenced.seq;
+                    if let Some(delta) = filter_for_client(&sequenced.delta, &mut subscriptions, &mut dedup, &shared) {
+                        coalescer.push(delta);
+                    }
+                }
+                let mut messages = Vec::new();
+                for delta in coalescer.drain() {
+                    messages.extend(encode_delta(delta, &subscriptions, full_format, &self_context)?);
+                }
+                let mut outgoing = text_messages(messages);
+                outgoing.push(shutdown_close_frame());
+                info!("Closing connection from {} for shutdown", addr);
+                out_tx.send(outgoing).await?;
+                break;
+            }
+
+            // Close connections that have gone quiet
+            () = idle.expired() => {
+                let warning = idle.warning();
+                info!("Closing idle connection from {}", addr);
+                out_tx
+                    .send(vec![
+                        Message::Text(serde_json::to_string(&warning)?),
+                        Message::Close(None),
+                    ])
+                    .await?;
+                break;
+            }
+        }
+    }
+
+    // Let the writer send what is queued, unless the client has stopped
+    // reading altogether
+    drop(out_tx);
+    if tokio::time::timeout(WRITER_DRAIN_TIMEOUT, &mut writer)
+        .await
+        .is_err()
+    {
+        writer.abort();
+    }
+
+    Ok(())
+}
+
+/// How long a closing connection may take to send its queued messages.
+const WRITER_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Messages queued for a client's writer; each entry is sent in order.
+type Outgoing = Vec<Message>;
+
+/// Send queued messages to the client until the queue closes or a send
+/// fails, compressing them if the connection negotiated it.
+async fn write_messages(
+    mut ws_tx: SplitSink<WebSocketStream<InflateStream<TcpStream>>, Message>,
+    mut out_rx: mpsc::Receiver<Outgoing>,
+    addr: SocketAddr,
+    mut deflater: Option<MessageDeflater>,
+) {
+    while let Some(messages) = out_rx.recv().await {
+        for msg in messages {
+            let msg = match &mut deflater {
+                Some(deflater) => deflater.deflate(msg),
+                None => msg,
+            };
+            if let Err(e) = ws_tx.send(msg).await {
+                error!("Failed to send to {}: {}", addr, e);
+                return;
+            }
+        }
+    }
+}
+
+/// Wrap encoded messages as text frames.
+fn text_messages(messages: Vec<String>) -> Outgoing {
+    messages.into_iter().map(Message::Text).collect()
+}
+
+/// Whether new deltas for a client have to be coalesced: its queue is
+/// full, or deltas are already held back (which go out first).
+fn congested(out_tx: &mpsc::Sender<Outgoing>, coalescer: &DeltaCoalescer) -> bool {
+    !coalescer.is_empty() || out_tx.capacity() == 0
+}
+
+/// Wait until `due`; pending forever without one.
+async fn sleep_until_due(due: Option<Instant>) {
+    match due {
+        Some(due) => tokio::time::sleep_until(due.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Encode an outgoing delta for a client.
+///
+/// Connections opened with `?format=full` get deltas as full-format tree
+/// fragments instead. The query parameter is the connection-wide default;
+/// values of subscriptions with a `format` of their own are sent in that
+/// format, so a delta may become a delta message and a full-format one.
+fn encode_delta(
+    delta: Delta,
+    subscriptions: &SubscriptionManager,
+    full_format: bool,
+    self_context: &SelfContext,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let (as_delta, as_full) = subscriptions.split_by_format(delta, full_format);
+    let mut messages = Vec::new();
+    if let Some(delta) = as_delta {
+        messages.push(encode_server_message(&ServerMessage::Delta(delta))?);
+    }
+    if let Some(delta) = as_full {
+        messages.push(serde_json::to_string(&full_fragment(
+            &delta,
+            &self_context.get(),
+        ))?);
+    }
+    Ok(messages)
+}
+
+/// Filter a broadcast delta for one client and encode it.
+///
+/// When the subscriptions, the client's dedup and the ACL pass the whole
+/// delta, the serialization shared by all connections is reused instead of
+/// encoding it again.
+fn encode_for_client(
+    sequenced: &SequencedDelta,
+    subscriptions: &mut SubscriptionManager,
+    dedup: &mut Option<OutboundDedup>,
+    full_format: bool,
+    shared: &ConnectionShared,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let permission = shared.config.client_permission;
+    let restricted = shared.acl.restricts_reads(permission);
+    match subscribed_for_client(&sequenced.delta, subscriptions, dedup) {
+        None => Ok(Vec::new()),
+        Some(Cow::Borrowed(_))
+            if !full_format
+                && !restricted
+                && !sequenced.encoded.is_empty()
+                && subscriptions.single_format(full_format) =>
+        {
+            Ok(vec![sequenced.encoded.to_string()])
         }
+        Some(filtered) => match shared
+            .acl
+            .filter_readable(permission, filtered.into_owned())
+        {
+            Some(delta) => encode_delta(delta, subscriptions, full_format, &shared.self_context),
+            None => Ok(Vec::new()),
+        },
+    }
+}
+
+/// The part of a broadcast delta a client subscribed to and its dedup
+/// lets through; borrowed if that is all of it.
+fn subscribed_for_client<'a>(
+    delta: &'a Delta,
+    subscriptions: &mut SubscriptionManager,
+    dedup: &mut Option<OutboundDedup>,
+) -> Option<Cow<'a, Delta>> {
+    let now = Instant::now();
+    subscriptions
+        .filter_delta_ref(delta, now)
+        .and_then(|delta| match dedup {
+            Some(dedup) => dedup.filter(delta, now),
+            None => Some(delta),
+        })
+}
+
+/// Filter a broadcast delta for one client without encoding it.
+fn filter_for_client(
+    delta: &Delta,
+    subscriptions: &mut SubscriptionManager,
+    dedup: &mut Option<OutboundDedup>,
+    shared: &ConnectionShared,
+) -> Option<Delta> {
+    subscribed_for_client(delta, subscriptions, dedup).and_then(|delta| {
+        shared
+            .acl
+            .filter_readable(shared.config.client_permission, delta.into_owned())
+    })
+}
+
+/// Apply a delta, or an array of deltas for any contexts, sent by a client.
+///
+/// Values the client may not write are dropped.
+async fn handle_client_deltas(
+    batch: DeltaBatch,
+    addr: SocketAddr,
+    shared: &ConnectionShared,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let deltas: Vec<Delta> = batch
+        .into_iter()
+        .filter_map(|delta| {
+            shared
+                .acl
+                .filter_writable(shared.config.client_permission, delta)
+        })
+        .collect();
+    if deltas.is_empty() {
+        warn!("Ignored deltas from {}: nothing writable", addr);
+        return Ok(());
     }
 
+    debug!("Received {} deltas from {}", deltas.len(), addr);
+    shared
+        .event_tx
+        .send(ServerEvent::DeltaBatch(deltas))
+        .await?;
     Ok(())
 }
 
 /// Handle a message received from a client.
 async fn handle_client_message(
     text: &str,
+    addr: SocketAddr,
+    shared: &ConnectionShared,
     subscriptions: &mut SubscriptionManager,
-    ws_tx: &mut SplitSink<WebSocketStream<TcpStream>, Message>,
+    full_format: bool,
+    out_tx: &mpsc::Sender<Outgoing>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let msg: ClientMessage = serde_json::from_str(text)?;
+    let msg = match decode_inbound_message(text)? {
+        InboundMessage::Deltas(batch) => return handle_client_deltas(batch, addr, shared).await,
+        InboundMessage::Request(msg) => msg,
+    };
 
     match msg {
-        ClientMessage::Subscribe(req) => {
-            debug!("Client subscribed to {:?}", req.subscribe);
-            let warnings = subscriptions.add_subscriptions(&req.context, &req.subscribe);
+        ClientMessage::Subscribe(mut req) => {
+            let mut warnings = Vec::new();
+            if let Some(max) = shared.config.max_subscriptions {
+                let available = max.saturating_sub(subscriptions.len());
+                if req.subscribe.len() > available {
+                    warnings.push(format!(
+                        "Subscription limit of {max} reached, ignoring {} subscriptions",
+                        req.subscribe.len() - available
+                    ));
+                    req.subscribe.truncate(available);
+                }
+            }
+            match subscriptions.add_subscriptions(&req.context, &req.subscribe) {
+                Ok(added) => warnings.extend(added),
+                Err(e) => {
+                    warn!("Rejected subscribe from {}: {}", addr, e);
+                    log_subscription_event(
+                        "subscribe",
+                        addr,
+                        subscriptions.len(),
+                        &[e.to_string()],
+                    );
+                    let response = SubscribeResponse::failed(&req, 400, e.to_string());
+                    out_tx
+                        .send(vec![Message::Text(serde_json::to_string(&response)?)])
+                        .await?;
+                    return Ok(());
+                }
+            }
+            log_subscription_event("subscribe", addr, subscriptions.len(), &warnings);
 
-            // Send any warning messages back to the client
-            for warning in warnings {
+            // Report warnings, and acknowledge when the client asked to
+            for warning in &warnings {
                 warn!("Subscription warning: {}", warning);
-                // Send as a plain text message (matching reference implementation behavior)
-                let warning_json = serde_json::to_string(&warning)?;
-                ws_tx.send(Message::Text(warning_json)).await?;
+            }
+            if !warnings.is_empty() || req.request_id.is_some() {
+                let response = SubscribeResponse::completed(&req, warnings);
+                out_tx
+                    .send(vec![Message::Text(serde_json::to_string(&response)?)])
+                    .await?;
+            }
+
+            // Prime the client with the current values of what it just
+            // subscribed to
+            if req
+                .send_cached_values
+                .unwrap_or(shared.config.prime_on_subscribe)
+            {
+                let mut added = SubscriptionManager::with_self_context(shared.self_context.clone());
+                added.add_subscriptions(&req.context, &req.subscribe)?;
+                let cached = added.cached_deltas(&shared.store.view());
+                let permission = shared.config.client_permission;
+                for delta in cached
+                    .into_iter()
+                    .filter_map(|delta| shared.acl.filter_readable(permission, delta))
+                {
+                    let messages = encode_delta(delta, &added, full_format, &shared.self_context)?;
+                    out_tx.send(text_messages(messages)).await?;
+                }
             }
         }
         ClientMessage::Unsubscribe(req) => {
-            debug!("Client unsubscribed from {:?}", req.unsubscribe);
             for spec in &req.unsubscribe {
                 subscriptions.remove_subscription(&req.context, &spec.path);
             }
+            log_subscription_event("unsubscribe", addr, subscriptions.len(), &[]);
         }
         ClientMessage::Put(req) => {
-            // PUT requests are not yet implemented
-            warn!("PUT request not implemented: {:?}", req);
-            let response = signalk_protocol::PutResponse {
-                request_id: req.request_id,
-                state: signalk_protocol::PutState::Failed,
-                status_code: 501,
-                message: Some("PUT not implemented".to_string()),
-            };
+            let response = handle_put(shared, req).await;
             let msg = serde_json::to_string(&response)?;
-            ws_tx.send(Message::Text(msg)).await?;
+            out_tx.send(vec![Message::Text(msg)]).await?;
+        }
+        ClientMessage::GetFull(req) => {
+            let response = handle_get(shared, &req).await;
+            let msg = serde_json::to_string(&response)?;
+            out_tx.send(vec![Message::Text(msg)]).await?;
+        }
+        ClientMessage::SetMode { mode } => {
+            if subscriptions.set_mode(&mode, shared.config.default_all_min_period_ms) {
+                log_subscription_event("mode", addr, subscriptions.len(), &[]);
+            } else {
+                let warning = format!("Unknown subscribe mode {mode}, ignoring");
+                warn!("Subscription warning: {}", warning);
+                log_subscription_event(
+                    "mode",
+                    addr,
+                    subscriptions.len(),
+                    std::slice::from_ref(&warning),
+                );
+                out_tx
+                    .send(vec![Message::Text(serde_json::to_string(&warning)?)])
+                    .await?;
+            }
         }
     }
 
     Ok(())
 }
+
+/// Handle a GET request with the full-format node of a context or path.
+///
+/// Paths the ACL hides from the client are refused with 403; so are whole
+/// contexts if the ACL hides anything from it.
+async fn handle_get(shared: &ConnectionShared, req: &GetRequest) -> GetResponse {
+    let context = req.context.as_deref().unwrap_or("vessels.self");
+    let permission = shared.config.client_permission;
+    let path = req.get.path.as_deref().filter(|path| !path.is_empty());
+    let readable = match path {
+        Some(path) => shared.acl.can_read(permission, path),
+        None => !shared.acl.restricts_reads(permission),
+    };
+    if !readable {
+        return GetResponse::failed(req, context, 403, "Permission denied");
+    }
+
+    let store = shared.store.view();
+    let node = match path {
+        Some(path) => {
+            let resolved = if context == "vessels.self" {
+                store.self_urn()
+            } else {
+                context
+            };
+            store.get_path(&format!("{resolved}.{path}"))
+        }
+        None => store.get_context(context),
+    };
+
+    match node {
+        Some(value) => GetResponse::completed(req, context, value),
+        None => GetResponse::failed(req, context, 404, "No data at the requested path"),
+    }
+}
+
+/// Handle a PUT request.
+///
+/// The store decides with [`MemoryStore::resolve_put`], as for a PUT over
+/// REST: self paths with a PUT handler registered are stored once the
+/// handler accepts the value (or left to the device, for async handlers);
+/// other paths on the writable allowlist are applied as a delta (sourced
+/// from the request's `source`, if any); the rest are answered with 405.
+async fn handle_put(shared: &ConnectionShared, req: PutRequest) -> PutResponse {
+    let context = req.context.as_deref().unwrap_or("vessels.self");
+    let failed = |status_code, message: String| {
+        warn!("Rejected PUT to {}: {}", req.put.path, message);
+        PutResponse::failed(&req, status_code, message)
+    };
+
+    if !shared.self_context.is_self(context) {
+        return failed(403, format!("Context {context} is not writable"));
+    }
+    if !shared
+        .acl
+        .can_write(shared.config.client_permission, &req.put.path)
+    {
+        return failed(403, format!("Permission denied for {}", req.put.path));
+    }
+
+    let result = {
+        let mut store = shared.store.write();
+        let result = store.resolve_put(
+            &req.put.path,
+            &req.put.value,
+            req.put.source.as_deref(),
+            &shared.writable_paths,
+        );
+        // Paths with a handler are only stored once the handler accepts
+        if let PutResult::Completed(delta) = &result {
+            for sequenced in
+                sequence_applied(&mut store, &shared.replay, delta.clone(), &shared.config)
+            {
+                let _ = shared.delta_tx.send(sequenced);
+            }
+        }
+        result
+    };
+    let response = PutResponse::for_result(&req, &result);
+    match result {
+        PutResult::Completed(_) => debug!("Applied PUT to {} via handler", req.put.path),
+        PutResult::Pending => debug!("PUT to {} pending on its device", req.put.path),
+        PutResult::Writable(delta) => {
+            // Without a handler, allowlisted paths are stored as-is
+            if shared
+                .event_tx
+                .send(ServerEvent::DeltaReceived(delta))
+                .await
+                .is_err()
+            {
+                return failed(503, "Server is shutting down".to_string());
+            }
+            debug!("Applied PUT to {}", req.put.path);
+        }
+        PutResult::Failed(_) | PutResult::NotSupported => {
+            warn!(
+                "Rejected PUT to {}: {}",
+                req.put.path,
+                response.message.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    response
+}
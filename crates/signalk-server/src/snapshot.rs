@@ -0,0 +1,100 @@
+//! Periodic store snapshots.
+//!
+//! The store lives in memory only, so a restart used to come back empty
+//! until every provider had reported again. [`run_snapshots`] persists a
+//! [`StoreSnapshot`] at a fixed interval; on startup the server restores
+//! it with [`MemoryStore::restore`].
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use signalk_core::{ConfigError, ConfigStorage, MemoryStore, SharedStore, StoreSnapshot};
+use tracing::warn;
+
+/// Persist a snapshot of `store` under [`StoreSnapshot::STORAGE_KEY`].
+///
+/// Serializing and writing happen on the blocking thread pool, so a large
+/// store doesn't stall the runtime.
+pub async fn save_snapshot<S: ConfigStorage + Clone + 'static>(
+    store: &SharedStore,
+    storage: &S,
+) -> Result<(), ConfigError> {
+    // Serialize outside the lock so writers aren't held up by the disk
+    let snapshot = store.view().snapshot();
+    let storage = storage.clone();
+    tokio::task::spawn_blocking(move || storage.save_value(StoreSnapshot::STORAGE_KEY, &snapshot))
+        .await
+        .map_err(|e| ConfigError::WriteError(e.to_string()))?
+}
+
+/// Save a snapshot of `store` every `interval`, forever.
+///
+/// A failed write is logged and retried at the next tick; the previous
+/// snapshot, if any, stays in place.
+pub async fn run_snapshots<S: ConfigStorage + Clone + 'static>(
+    store: Arc<SharedStore>,
+    storage: S,
+    interval: Duration,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    // The first tick completes immediately; there's nothing to save yet
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        if let Err(e) = save_snapshot(&store, &storage).await {
+            warn!("Could not save store snapshot: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signalk_core::{Delta, FileConfigStorage, PathValue, SignalKStore, Update};
+
+    const SELF_URN: &str = "vessels.urn:mrn:signalk:uuid:self";
+
+    #[tokio::test]
+    async fn test_periodic_snapshot_restores() {
+        let dir = std::env::temp_dir().join(format!("signalk-snapshot-{}", std::process::id()));
+        let storage = FileConfigStorage::new(&dir).unwrap();
+
+        let store = Arc::new(SharedStore::new(MemoryStore::new(SELF_URN)));
+        store.write().apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("nmea0183.GP".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:30:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.85),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+
+        let task = tokio::spawn(run_snapshots(
+            store.clone(),
+            storage.clone(),
+            Duration::from_millis(20),
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        task.abort();
+
+        assert!(dir.join(StoreSnapshot::STORAGE_KEY).is_file());
+        let snapshot: StoreSnapshot = storage.load_value(StoreSnapshot::STORAGE_KEY).unwrap();
+        let mut restored = MemoryStore::new(SELF_URN);
+        restored.restore(snapshot).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(restored.full_model(), store.read().full_model());
+        assert_eq!(
+            restored
+                .get_self_path("navigation.speedOverGround")
+                .unwrap()["value"],
+            serde_json::json!(3.85)
+        );
+    }
+}
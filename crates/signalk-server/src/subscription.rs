@@ -2,9 +2,106 @@
 //!
 //! This module handles per-client subscriptions, filtering deltas
 //! based on subscribed paths and contexts.
+//!
+//! `minPeriod` throttles a path to at most one value per period. A `period`
+//! without `minPeriod` (policy `fixed`) sends the latest value once per
+//! period, repeating it if nothing new arrived: the first value goes out at
+//! once, later ones are held for [`SubscriptionManager::flush_fixed`].
+//!
+//! A subscription's `format` overrides the connection's `?format=` for its
+//! paths; [`SubscriptionManager::split_by_format`] separates the values to
+//! send as deltas from those to send as full-format fragments.
+//!
+//! Subscription lifecycle events (subscribe, unsubscribe, mode changes and
+//! the replay that resumes a new connection) are logged at debug level to
+//! [`SUBSCRIPTION_LOG_TARGET`], which the `signalk-server:subscriptions`
+//! debug key enables.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use signalk_core::{
+    Delta, MemoryStore, PathPattern, PathValue, PatternError, SignalKStore, Update,
+};
+use signalk_protocol::{Subscription, SubscriptionFormat, SubscriptionPolicy};
+use tracing::{debug, warn};
+
+/// `tracing` target of subscription lifecycle events.
+pub const SUBSCRIPTION_LOG_TARGET: &str = "signalk_server::subscriptions";
+
+/// Debug key that enables [`SUBSCRIPTION_LOG_TARGET`].
+pub const SUBSCRIPTION_DEBUG_KEY: &str = "signalk-server:subscriptions";
+
+/// How often `minPeriod` send times are swept for entries that no longer
+/// throttle anything.
+const THROTTLE_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Log a subscription lifecycle event of the client at `client`.
+///
+/// `matchers` is the number of subscriptions the client has afterwards and
+/// `warnings` are those sent back to it.
+pub fn log_subscription_event(
+    event: &str,
+    client: SocketAddr,
+    matchers: usize,
+    warnings: &[String],
+) {
+    debug!(
+        target: SUBSCRIPTION_LOG_TARGET,
+        event,
+        client = %client,
+        matchers,
+        warnings = ?warnings,
+        "Subscription {}",
+        event
+    );
+}
+
+/// Shared handle to the server's current self URN.
+///
+/// Subscriptions to `vessels.self` resolve against this handle on every
+/// match, so they keep following self when the URN changes mid-stream.
+#[derive(Debug, Clone)]
+pub struct SelfContext {
+    urn: Arc<RwLock<String>>,
+}
 
-use signalk_core::{Delta, MemoryStore, PathPattern, PathValue, SignalKStore, Update};
-use signalk_protocol::{Subscription, SubscriptionPolicy};
+impl SelfContext {
+    /// Create a handle for the given self URN (`vessels.urn:...`).
+    pub fn new(self_urn: &str) -> Self {
+        Self {
+            urn: Arc::new(RwLock::new(self_urn.to_string())),
+        }
+    }
+
+    /// The current self URN.
+    pub fn get(&self) -> String {
+        self.urn.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the self URN for every holder of this handle.
+    pub fn set(&self, self_urn: &str) {
+        *self.urn.write().unwrap_or_else(|e| e.into_inner()) = self_urn.to_string();
+    }
+
+    /// Check whether a delta context refers to self.
+    pub fn is_self(&self, context: &str) -> bool {
+        context == "vessels.self" || *self.urn.read().unwrap_or_else(|e| e.into_inner()) == context
+    }
+}
+
+/// A subscription whose path isn't a valid pattern.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Invalid path pattern {path:?}: {source}")]
+pub struct InvalidSubscription {
+    pub path: String,
+    #[source]
+    pub source: PatternError,
+}
 
 /// Represents a client's subscription to a specific path pattern.
 #[derive(Debug, Clone)]
@@ -19,67 +116,148 @@ pub struct ClientSubscription {
     pub min_period: Option<u64>,
     /// Subscription policy
     pub policy: SubscriptionPolicy,
-    /// Compiled path pattern for efficiency
-    matcher: PathPattern,
+    /// Message format; the connection's `?format=` applies when `None`
+    pub format: Option<SubscriptionFormat>,
+    /// Compiled path pattern for efficiency; `None` for an invalid path,
+    /// which matches nothing
+    matcher: Option<PathPattern>,
+    /// Compiled context pattern (e.g. "vessels.*"), compared literally if
+    /// it doesn't compile
+    context_matcher: Option<PathPattern>,
 }
 
 impl ClientSubscription {
     /// Create a new subscription.
+    ///
+    /// A `path` that isn't a valid pattern (such as an empty one) makes a
+    /// subscription that matches nothing; use
+    /// [`from_protocol`](Self::from_protocol) to reject it instead.
     pub fn new(context: &str, path: &str) -> Self {
+        let matcher = PathPattern::new(path)
+            .map_err(|e| {
+                warn!(
+                    "Invalid subscription path {:?}, matching nothing: {}",
+                    path, e
+                )
+            })
+            .ok();
         Self {
             context: context.to_string(),
             path: path.to_string(),
             period: None,
             min_period: None,
             policy: SubscriptionPolicy::Instant,
-            matcher: PathPattern::new(path).expect("Invalid path pattern"),
+            format: None,
+            matcher,
+            context_matcher: PathPattern::new(context).ok(),
         }
     }
 
-    /// Create from a protocol Subscription.
-    pub fn from_protocol(context: &str, sub: &Subscription) -> Self {
-        Self {
+    /// Create from a protocol Subscription, as sent by a client.
+    pub fn from_protocol(context: &str, sub: &Subscription) -> Result<Self, InvalidSubscription> {
+        let matcher = PathPattern::new(&sub.path).map_err(|source| InvalidSubscription {
+            path: sub.path.clone(),
+            source,
+        })?;
+        Ok(Self {
             context: context.to_string(),
             path: sub.path.clone(),
             period: sub.period,
             min_period: sub.min_period,
             policy: sub.policy.clone().unwrap_or(SubscriptionPolicy::Instant),
-            matcher: PathPattern::new(&sub.path).expect("Invalid path pattern"),
+            format: sub.format.clone(),
+            matcher: Some(matcher),
+            context_matcher: PathPattern::new(context).ok(),
+        })
+    }
+
+    /// The `period` in milliseconds, if set. Without a `minPeriod` it makes
+    /// this a `fixed` subscription.
+    fn fixed_period(&self) -> Option<u64> {
+        self.period.filter(|&period| period > 0)
+    }
+
+    /// Whether values are sent in the full format, given the connection's.
+    fn wants_full(&self, full_default: bool) -> bool {
+        match self.format {
+            Some(SubscriptionFormat::Full) => true,
+            Some(SubscriptionFormat::Delta) => false,
+            None => full_default,
         }
     }
 
     /// Check if this subscription matches a given context and path.
+    ///
+    /// Only the literal `vessels.self` context counts as self; use
+    /// [`ClientSubscription::matches_with_self`] to also recognise the URN.
     pub fn matches(&self, context: &str, path: &str) -> bool {
-        self.matches_context(context) && self.matcher.matches(path)
+        self.matches_with_self(context, path, "vessels.self")
+    }
+
+    /// Check if this subscription matches, treating `self_urn` as self.
+    pub fn matches_with_self(&self, context: &str, path: &str, self_urn: &str) -> bool {
+        self.matches_context(context, self_urn)
+            && self
+                .matcher
+                .as_ref()
+                .is_some_and(|matcher| matcher.matches(path))
     }
 
     /// Check if the context matches.
-    fn matches_context(&self, context: &str) -> bool {
+    fn matches_context(&self, context: &str, self_urn: &str) -> bool {
         if self.context == "*" {
             return true;
         }
-        if self.context == "vessels.self" {
-            // Match both "vessels.self" and the actual self URN
-            return context == "vessels.self" || context.starts_with("vessels.urn:");
+        if self.context == "vessels.self" || self.context == self_urn {
+            // Match both "vessels.self" and the current self URN
+            return context == "vessels.self" || context == self_urn;
+        }
+        match &self.context_matcher {
+            Some(matcher) => matcher.matches(context),
+            None => self.context == context,
         }
-        self.context == context
     }
 }
 
+/// Latest value of a path under a fixed `period` subscription.
+struct FixedValue {
+    source_ref: Option<String>,
+    timestamp: Option<String>,
+    value: PathValue,
+    period: Duration,
+    /// When the value is next sent.
+    next_due: Instant,
+}
+
 /// Manages subscriptions for a single client connection.
 pub struct SubscriptionManager {
-    /// The self URN for this server.
-    self_urn: String,
+    /// The server's current self URN.
+    self_context: SelfContext,
     /// Active subscriptions.
     subscriptions: Vec<ClientSubscription>,
+    /// When each `(context, path)` was last sent under a `minPeriod`
+    /// throttle.
+    last_sent: HashMap<(String, String), Instant>,
+    /// When `last_sent` was last swept.
+    last_pruned: Instant,
+    /// Latest value of each `(context, path)` under a fixed `period`.
+    fixed: HashMap<(String, String), FixedValue>,
 }
 
 impl SubscriptionManager {
-    /// Create a new subscription manager.
+    /// Create a new subscription manager with a fixed self URN.
     pub fn new(self_urn: &str) -> Self {
+        Self::with_self_context(SelfContext::new(self_urn))
+    }
+
+    /// Create a subscription manager that follows a shared self URN.
+    pub fn with_self_context(self_context: SelfContext) -> Self {
         Self {
-            self_urn: self_urn.to_string(),
+            self_context,
             subscriptions: Vec::new(),
+            last_sent: HashMap::new(),
+            last_pruned: Instant::now(),
+            fixed: HashMap::new(),
         }
     }
 
@@ -95,16 +273,51 @@ impl SubscriptionManager {
     }
 
     /// Subscribe to all contexts and paths.
-    pub fn subscribe_all(&mut self) {
+    ///
+    /// `min_period` (milliseconds) throttles each path to at most one
+    /// update per period, as `minPeriod` does for explicit subscriptions.
+    pub fn subscribe_all(&mut self, min_period: Option<u64>) {
         self.subscriptions.clear();
-        self.subscriptions.push(ClientSubscription::new("*", "*"));
+        let mut all = ClientSubscription::new("*", "*");
+        all.min_period = min_period;
+        self.subscriptions.push(all);
+    }
+
+    /// Replace all subscriptions with the default set for a `subscribe`
+    /// mode (`self`, `all` or `none`), as on connect.
+    ///
+    /// `all_min_period` is the `minPeriod` for `all` (see
+    /// [`subscribe_all`](Self::subscribe_all)). Returns `false`, leaving the
+    /// subscriptions untouched, for an unknown mode.
+    pub fn set_mode(&mut self, mode: &str, all_min_period: Option<u64>) -> bool {
+        match mode {
+            "self" => {
+                self.subscriptions.clear();
+                self.subscribe_self_all();
+            }
+            "all" => self.subscribe_all(all_min_period),
+            "none" => self.subscribe_none(),
+            _ => return false,
+        }
+        self.last_sent.clear();
+        self.fixed.clear();
+        true
     }
 
     /// Add subscriptions from a subscribe request.
     ///
     /// Returns a list of warning messages for inconsistent subscription parameters
-    /// (e.g., minPeriod with non-instant policy).
-    pub fn add_subscriptions(&mut self, context: &str, subs: &[Subscription]) -> Vec<String> {
+    /// (e.g., minPeriod with non-instant policy). If any path is invalid,
+    /// none of the subscriptions are added.
+    pub fn add_subscriptions(
+        &mut self,
+        context: &str,
+        subs: &[Subscription],
+    ) -> Result<Vec<String>, InvalidSubscription> {
+        let added = subs
+            .iter()
+            .map(|sub| ClientSubscription::from_protocol(context, sub))
+            .collect::<Result<Vec<_>, _>>()?;
         let mut warnings = Vec::new();
 
         for sub in subs {
@@ -132,187 +345,417 @@ impl SubscriptionManager {
                     }
                 }
             }
-
-            self.subscriptions
-                .push(ClientSubscription::from_protocol(context, sub));
         }
 
-        warnings
+        self.subscriptions.extend(added);
+        Ok(warnings)
     }
 
-    /// Remove a subscription by context and path.
+    /// Remove subscriptions by context and path.
+    ///
+    /// `path` may be a pattern: every subscription in `context` whose path
+    /// it subsumes is removed, so unsubscribing `navigation.*` also drops an
+    /// earlier `navigation.speedOverGround`.
     pub fn remove_subscription(&mut self, context: &str, path: &str) {
         if path == "*" && context == "*" {
             // Unsubscribe from everything
             self.subscriptions.clear();
-        } else {
-            self.subscriptions
-                .retain(|s| !(s.context == context && s.path == path));
+            self.last_sent.clear();
+            return;
         }
+
+        match PathPattern::new(path) {
+            Ok(pattern) => self
+                .subscriptions
+                .retain(|s| !(s.context == context && pattern.matches(&s.path))),
+            Err(_) => self
+                .subscriptions
+                .retain(|s| !(s.context == context && s.path == path)),
+        }
+        self.prune_throttles(Instant::now());
+    }
+
+    /// Forget `minPeriod` send times that no longer hold anything back:
+    /// those of paths without a matching `minPeriod` subscription and those
+    /// whose period has elapsed.
+    fn prune_throttles(&mut self, now: Instant) {
+        let self_urn = self.self_context.get();
+        let subscriptions = &self.subscriptions;
+        self.last_sent.retain(|(context, path), last| {
+            subscriptions
+                .iter()
+                .filter(|sub| sub.matches_with_self(context, path, &self_urn))
+                .filter_map(|sub| sub.min_period.filter(|&period| period > 0))
+                .min()
+                .is_some_and(|period| now.duration_since(*last) < Duration::from_millis(period))
+        });
+        self.last_pruned = now;
+    }
+
+    /// Number of active subscriptions.
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Check whether there are no active subscriptions.
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
     }
 
     /// Check if any subscription matches a given context and path.
     pub fn matches(&self, context: &str, path: &str) -> bool {
-        self.subscriptions.iter().any(|s| s.matches(context, path))
+        self.matches_for(context, path, &self.self_context.get())
+    }
+
+    fn matches_for(&self, context: &str, path: &str, self_urn: &str) -> bool {
+        self.subscriptions
+            .iter()
+            .any(|s| s.matches_with_self(context, path, self_urn))
+    }
+
+    /// Check a matching value against the `minPeriod` and `period`
+    /// throttles.
+    ///
+    /// A value passes if any matching subscription is unthrottled, or if the
+    /// shortest matching `minPeriod` has elapsed since it was last sent.
+    /// Under only fixed `period` subscriptions it passes when due, and is
+    /// otherwise kept as the latest value for [`flush_fixed`](Self::flush_fixed).
+    fn throttle_allows(
+        &mut self,
+        context: &str,
+        update: &Update,
+        pv: &PathValue,
+        self_urn: &str,
+        now: Instant,
+    ) -> bool {
+        let mut min_period: Option<u64> = None;
+        let mut fixed_period: Option<u64> = None;
+        for sub in &self.subscriptions {
+            if !sub.matches_with_self(context, &pv.path, self_urn) {
+                continue;
+            }
+            match (sub.min_period, sub.fixed_period()) {
+                (Some(period), _) if period > 0 => {
+                    min_period = Some(min_period.map_or(period, |p| p.min(period)));
+                }
+                (_, Some(period)) => {
+                    fixed_period = Some(fixed_period.map_or(period, |p| p.min(period)));
+                }
+                _ => return true,
+            }
+        }
+
+        let key = (context.to_string(), pv.path.clone());
+        if let Some(min_period) = min_period {
+            return match self.last_sent.get(&key) {
+                Some(last) if now.duration_since(*last) < Duration::from_millis(min_period) => {
+                    false
+                }
+                _ => {
+                    self.last_sent.insert(key, now);
+                    true
+                }
+            };
+        }
+        let Some(period) = fixed_period else {
+            return false;
+        };
+
+        let period = Duration::from_millis(period);
+        let latest = FixedValue {
+            source_ref: update.source_ref.clone(),
+            timestamp: update.timestamp.clone(),
+            value: pv.clone(),
+            period,
+            next_due: now + period,
+        };
+        match self.fixed.get_mut(&key) {
+            Some(fixed) if now < fixed.next_due => {
+                // Held until due, keeping the schedule
+                *fixed = FixedValue {
+                    next_due: fixed.next_due,
+                    ..latest
+                };
+                false
+            }
+            Some(fixed) => {
+                *fixed = latest;
+                true
+            }
+            None => {
+                self.fixed.insert(key, latest);
+                true
+            }
+        }
+    }
+
+    /// When the next fixed `period` value is due, if any.
+    pub fn next_fixed_due(&self) -> Option<Instant> {
+        self.fixed.values().map(|fixed| fixed.next_due).min()
+    }
+
+    /// The latest values of fixed `period` subscriptions that are due at
+    /// `now`, as one delta per context; each is then due again a period
+    /// later.
+    ///
+    /// Values of paths no longer subscribed with a `period` are forgotten.
+    pub fn flush_fixed(&mut self, now: Instant) -> Vec<Delta> {
+        let self_urn = self.self_context.get();
+        let subscriptions = &self.subscriptions;
+        self.fixed.retain(|(context, path), _| {
+            subscriptions.iter().any(|sub| {
+                sub.fixed_period().is_some() && sub.matches_with_self(context, path, &self_urn)
+            })
+        });
+
+        let mut due: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+        for ((context, _), fixed) in &mut self.fixed {
+            if fixed.next_due > now {
+                continue;
+            }
+            fixed.next_due += fixed.period;
+            if fixed.next_due <= now {
+                // Fell behind; don't send a burst to catch up
+                fixed.next_due = now + fixed.period;
+            }
+            due.entry(context).or_default().push((
+                fixed.source_ref.clone(),
+                fixed.timestamp.clone(),
+                fixed.value.clone(),
+            ));
+        }
+
+        due.into_iter()
+            .map(|(context, values)| Delta {
+                context: Some(context.to_string()),
+                updates: Update::group_by_source(values),
+            })
+            .collect()
     }
 
     /// Filter a delta to only include paths the client is subscribed to.
     ///
     /// Returns None if no paths match any subscription.
-    pub fn filter_delta(&self, delta: &Delta) -> Option<Delta> {
+    pub fn filter_delta(&mut self, delta: &Delta) -> Option<Delta> {
+        self.filter_delta_at(delta, Instant::now())
+    }
+
+    /// [`SubscriptionManager::filter_delta`] with an explicit clock, applying
+    /// `minPeriod` throttles relative to `now`.
+    pub fn filter_delta_at(&mut self, delta: &Delta, now: Instant) -> Option<Delta> {
+        self.filter_delta_ref(delta, now).map(Cow::into_owned)
+    }
+
+    /// Filter a delta, borrowing it when every value passes.
+    ///
+    /// `Cow::Borrowed` tells the caller the delta is unchanged, so a
+    /// serialization shared by all connections can be sent as-is.
+    pub fn filter_delta_ref<'a>(
+        &mut self,
+        delta: &'a Delta,
+        now: Instant,
+    ) -> Option<Cow<'a, Delta>> {
         let context = delta.context.as_deref().unwrap_or("vessels.self");
+        let self_urn = self.self_context.get();
+        if now.duration_since(self.last_pruned) >= THROTTLE_PRUNE_INTERVAL {
+            self.prune_throttles(now);
+        }
 
         // Check if any subscription could match this context
         if !self
             .subscriptions
             .iter()
-            .any(|s| s.matches_context(context))
+            .any(|s| s.matches_context(context, &self_urn))
         {
             return None;
         }
 
-        // Filter updates to only include matching paths
-        let filtered_updates: Vec<Update> = delta
+        // Decide per value first, so an unchanged delta needs no copy
+        let kept: Vec<Vec<bool>> = delta
             .updates
             .iter()
-            .filter_map(|update| {
-                let filtered_values: Vec<PathValue> = update
+            .map(|update| {
+                update
                     .values
                     .iter()
-                    .filter(|pv| self.matches(context, &pv.path))
-                    .cloned()
-                    .collect();
-
-                if filtered_values.is_empty() {
-                    None
-                } else {
-                    Some(Update {
-                        source_ref: update.source_ref.clone(),
-                        source: update.source.clone(),
-                        timestamp: update.timestamp.clone(),
-                        values: filtered_values,
-                        meta: update.meta.clone(),
-                    })
-                }
+                    .map(|pv| self.throttle_allows(context, update, pv, &self_urn, now))
+                    .collect()
             })
             .collect();
+        if kept
+            .iter()
+            .all(|values| !values.is_empty() && values.iter().all(|&keep| keep))
+        {
+            return (!delta.updates.is_empty()).then_some(Cow::Borrowed(delta));
+        }
+
+        // Filter updates to only include matching paths
+        let mut filtered_updates: Vec<Update> = Vec::new();
+        for (update, kept) in delta.updates.iter().zip(&kept) {
+            let filtered_values: Vec<PathValue> = update
+                .values
+                .iter()
+                .zip(kept)
+                .filter(|(_, &keep)| keep)
+                .map(|(pv, _)| pv.clone())
+                .collect();
+
+            if !filtered_values.is_empty() {
+                filtered_updates.push(Update {
+                    source_ref: update.source_ref.clone(),
+                    source: update.source.clone(),
+                    timestamp: update.timestamp.clone(),
+                    values: filtered_values,
+                    meta: update.meta.clone(),
+                    server_timestamp: update.server_timestamp.clone(),
+                });
+            }
+        }
 
         if filtered_updates.is_empty() {
             None
         } else {
-            Some(Delta {
+            Some(Cow::Owned(Delta {
                 context: delta.context.clone(),
                 updates: filtered_updates,
-            })
+            }))
         }
     }
 
-    /// Get an initial delta with all current values matching subscriptions.
+    /// Whether every subscription uses the connection's format, so deltas
+    /// need no [`split_by_format`](Self::split_by_format).
+    pub fn single_format(&self, full_default: bool) -> bool {
+        self.subscriptions
+            .iter()
+            .all(|sub| sub.wants_full(full_default) == full_default)
+    }
+
+    /// Split a filtered delta by the format its values were subscribed
+    /// with, into the delta-format and the full-format part.
     ///
-    /// This is sent when a client first connects with `sendCachedValues=true`.
-    /// Returns None if there are no cached values to send.
-    pub fn get_initial_delta(&self, store: &MemoryStore) -> Option<Delta> {
-        if self.subscriptions.is_empty() {
-            return None;
+    /// Subscriptions without a `format` of their own use the connection's
+    /// (`full_default`). A value subscribed in both formats ends up in both
+    /// parts; one matching no subscription goes by the connection's.
+    pub fn split_by_format(
+        &self,
+        delta: Delta,
+        full_default: bool,
+    ) -> (Option<Delta>, Option<Delta>) {
+        let wants_full = |sub: &ClientSubscription| sub.wants_full(full_default);
+        if self.single_format(full_default) {
+            return if full_default {
+                (None, Some(delta))
+            } else {
+                (Some(delta), None)
+            };
         }
 
-        // Collect values from the store that match our subscriptions
-        let mut path_values = Vec::new();
-        let mut source_ref: Option<String> = None;
-        let mut timestamp: Option<String> = None;
-
-        // Get the self vessel data from the store
-        let self_urn = store.self_urn();
-        let urn_key = self_urn.strip_prefix("vessels.").unwrap_or(self_urn);
-
-        if let Some(vessel_data) = store
-            .full_model()
-            .get("vessels")
-            .and_then(|v| v.get(urn_key))
-        {
-            self.collect_matching_paths(
-                vessel_data,
-                "",
-                "vessels.self",
-                &mut path_values,
-                &mut source_ref,
-                &mut timestamp,
-            );
+        let context = delta.context.as_deref().unwrap_or("vessels.self");
+        let self_urn = self.self_context.get();
+        let mut as_delta = Delta {
+            context: delta.context.clone(),
+            updates: Vec::new(),
+        };
+        let mut as_full = as_delta.clone();
+        for update in &delta.updates {
+            let (mut delta_values, mut full_values) = (Vec::new(), Vec::new());
+            for pv in &update.values {
+                let (mut delta_wanted, mut full_wanted) = (false, false);
+                for sub in &self.subscriptions {
+                    if sub.matches_with_self(context, &pv.path, &self_urn) {
+                        if wants_full(sub) {
+                            full_wanted = true;
+                        } else {
+                            delta_wanted = true;
+                        }
+                    }
+                }
+                if !delta_wanted && !full_wanted {
+                    delta_wanted = !full_default;
+                    full_wanted = full_default;
+                }
+                if delta_wanted {
+                    delta_values.push(pv.clone());
+                }
+                if full_wanted {
+                    full_values.push(pv.clone());
+                }
+            }
+            for (part, values) in [(&mut as_delta, delta_values), (&mut as_full, full_values)] {
+                if !values.is_empty() {
+                    part.updates.push(Update {
+                        source_ref: update.source_ref.clone(),
+                        source: update.source.clone(),
+                        timestamp: update.timestamp.clone(),
+                        values,
+                        meta: update.meta.clone(),
+                        server_timestamp: update.server_timestamp.clone(),
+                    });
+                }
+            }
         }
 
-        if path_values.is_empty() {
-            return None;
-        }
+        let non_empty = |delta: Delta| (!delta.updates.is_empty()).then_some(delta);
+        (non_empty(as_delta), non_empty(as_full))
+    }
 
-        Some(Delta {
-            context: Some("vessels.self".to_string()),
-            updates: vec![Update {
-                source_ref,
-                source: None,
-                timestamp,
-                values: path_values,
-                meta: None,
-            }],
-        })
+    /// Current values in the store matching these subscriptions, in any
+    /// context, as one delta per context.
+    ///
+    /// Used to prime a client with the values of the paths it just
+    /// subscribed to.
+    pub fn cached_deltas(&self, store: &MemoryStore) -> Vec<Delta> {
+        if self.subscriptions.is_empty() {
+            return Vec::new();
+        }
+        let self_urn = self.self_context.get();
+        store
+            .full_model_as_deltas(None)
+            .into_iter()
+            .filter_map(|mut delta| {
+                let context = delta.context.clone().unwrap_or_default();
+                for update in &mut delta.updates {
+                    update
+                        .values
+                        .retain(|pv| self.matches_for(&context, &pv.path, &self_urn));
+                }
+                delta.updates.retain(|update| !update.values.is_empty());
+                (!delta.updates.is_empty()).then_some(delta)
+            })
+            .collect()
     }
 
-    /// Recursively collect paths and values from a JSON object that match subscriptions.
-    fn collect_matching_paths(
-        &self,
-        value: &serde_json::Value,
-        current_path: &str,
-        context: &str,
-        path_values: &mut Vec<PathValue>,
-        source_ref: &mut Option<String>,
-        timestamp: &mut Option<String>,
-    ) {
-        if let serde_json::Value::Object(map) = value {
-            // Check if this is a leaf value node (has "value" key)
-            if map.contains_key("value") {
-                // This is a SignalK value node
-                if self.matches(context, current_path) {
-                    path_values.push(PathValue {
-                        path: current_path.to_string(),
-                        value: map.get("value").cloned().unwrap_or(serde_json::Value::Null),
-                    });
+    /// The full model restricted to the paths matching these subscriptions,
+    /// in any context.
+    ///
+    /// Lets a reconnecting client fetch the current state of just what it
+    /// subscribes to.
+    pub fn full_model(&self, store: &MemoryStore) -> Value {
+        let self_urn = self.self_context.get();
+        store.full_model_filtered(|context, path| self.matches_for(context, path, &self_urn))
+    }
 
-                    // Capture source and timestamp from the first matching value
-                    if source_ref.is_none() {
-                        if let Some(src) = map.get("$source").and_then(|s| s.as_str()) {
-                            *source_ref = Some(src.to_string());
-                        }
-                    }
-                    if timestamp.is_none() {
-                        if let Some(ts) = map.get("timestamp").and_then(|t| t.as_str()) {
-                            *timestamp = Some(ts.to_string());
-                        }
-                    }
-                }
-            } else {
-                // Recurse into child objects
-                for (key, child) in map {
-                    // Skip "values" map - we only want the primary value
-                    if key == "values" {
-                        continue;
-                    }
+    /// Get an initial delta with all current values matching subscriptions.
+    ///
+    /// This is sent when a client first connects with `sendCachedValues=true`.
+    /// Returns None if there are no cached values to send.
+    pub fn get_initial_delta(&self, store: &MemoryStore) -> Option<Delta> {
+        if self.subscriptions.is_empty() {
+            return None;
+        }
 
-                    let child_path = if current_path.is_empty() {
-                        key.to_string()
-                    } else {
-                        format!("{current_path}.{key}")
-                    };
-
-                    self.collect_matching_paths(
-                        child,
-                        &child_path,
-                        context,
-                        path_values,
-                        source_ref,
-                        timestamp,
-                    );
-                }
-            }
+        // The self vessel's values that match our subscriptions, keeping
+        // each value's own source and timestamp
+        let self_only = PathPattern::new("vessels.self").ok()?;
+        let mut delta = store.full_model_as_deltas(Some(&self_only)).pop()?;
+        for update in &mut delta.updates {
+            update
+                .values
+                .retain(|pv| self.matches("vessels.self", &pv.path));
         }
+        delta.updates.retain(|update| !update.values.is_empty());
+
+        (!delta.updates.is_empty()).then_some(delta)
     }
 }
 
@@ -339,6 +782,14 @@ mod tests {
         assert!(!sub.matches("vessels.self", "navigation.speedOverGround"));
     }
 
+    #[test]
+    fn test_invalid_path_matches_nothing() {
+        let sub = ClientSubscription::new("vessels.self", "");
+
+        assert!(!sub.matches("vessels.self", "navigation.position"));
+        assert!(!sub.matches("vessels.self", ""));
+    }
+
     #[test]
     fn test_subscription_manager() {
         let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
@@ -362,7 +813,8 @@ mod tests {
                 policy: None,
                 min_period: None,
             }],
-        );
+        )
+        .unwrap();
 
         assert!(mgr.matches("vessels.self", "navigation.position"));
         assert!(!mgr.matches("vessels.self", "environment.wind.speedApparent"));
@@ -380,7 +832,8 @@ mod tests {
                 policy: None,
                 min_period: None,
             }],
-        );
+        )
+        .unwrap();
 
         let delta = Delta {
             context: Some("vessels.self".to_string()),
@@ -399,6 +852,7 @@ mod tests {
                     },
                 ],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -423,7 +877,8 @@ mod tests {
                 policy: Some(SubscriptionPolicy::Instant),
                 min_period: Some(100),
             }],
-        );
+        )
+        .unwrap();
 
         // Verify subscription was added
         assert!(mgr.matches("vessels.self", "navigation.speedOverGround"));
@@ -451,7 +906,8 @@ mod tests {
                     min_period: None,
                 },
             ],
-        );
+        )
+        .unwrap();
 
         assert!(mgr.matches("vessels.self", "navigation.speedOverGround"));
         assert!(mgr.matches("vessels.self", "environment.wind.speedApparent"));
@@ -464,7 +920,103 @@ mod tests {
     }
 
     #[test]
-    fn test_filter_delta_no_match() {
+    fn test_unsubscribe_by_pattern() {
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        let subs: Vec<Subscription> = [
+            "navigation.speedOverGround",
+            "navigation.courseOverGroundTrue",
+            "navigation.position",
+            "navigation.gnss.*",
+            "environment.depth.belowTransducer",
+        ]
+        .iter()
+        .map(|path| Subscription {
+            path: path.to_string(),
+            period: None,
+            format: None,
+            policy: None,
+            min_period: None,
+        })
+        .collect();
+        mgr.add_subscriptions("vessels.self", &subs).unwrap();
+        mgr.add_subscriptions("vessels.*", &subs[..1]).unwrap();
+
+        mgr.remove_subscription("vessels.self", "navigation.*");
+
+        assert!(!mgr.matches("vessels.self", "navigation.position"));
+        assert!(!mgr.matches("vessels.self", "navigation.gnss.satellites"));
+        assert!(mgr.matches("vessels.self", "environment.depth.belowTransducer"));
+        // Subscriptions in other contexts are untouched
+        assert!(mgr.matches("vessels.self", "navigation.speedOverGround"));
+        assert_eq!(mgr.subscriptions.len(), 2);
+    }
+
+    #[test]
+    fn test_subscribe_all_min_period() {
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        mgr.subscribe_all(Some(1000));
+
+        let delta = |path: &str| Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: None,
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: path.to_string(),
+                    value: serde_json::json!(1.0),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+        let start = Instant::now();
+        let sog = delta("navigation.speedOverGround");
+
+        assert!(mgr.filter_delta_at(&sog, start).is_some());
+        assert!(mgr
+            .filter_delta_at(&sog, start + Duration::from_millis(500))
+            .is_none());
+        assert!(mgr
+            .filter_delta_at(&sog, start + Duration::from_millis(1000))
+            .is_some());
+
+        // An explicit subscription without minPeriod overrides the default
+        mgr.add_subscriptions(
+            "vessels.self",
+            &[Subscription {
+                path: "environment.*".to_string(),
+                period: None,
+                format: None,
+                policy: None,
+                min_period: None,
+            }],
+        )
+        .unwrap();
+        let wind = delta("environment.wind.speedApparent");
+        assert!(mgr.filter_delta_at(&wind, start).is_some());
+        assert!(mgr.filter_delta_at(&wind, start).is_some());
+    }
+
+    fn sog(value: f64) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(value),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_min_period_throttles_rapid_updates() {
         let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
         mgr.add_subscriptions(
             "vessels.self",
@@ -473,10 +1025,165 @@ mod tests {
                 period: None,
                 format: None,
                 policy: None,
+                min_period: Some(500),
+            }],
+        )
+        .unwrap();
+        let start = Instant::now();
+
+        assert!(mgr.filter_delta_at(&sog(3.1), start).is_some());
+        assert!(mgr
+            .filter_delta_at(&sog(3.2), start + Duration::from_millis(100))
+            .is_none());
+        assert!(mgr
+            .filter_delta_at(&sog(3.3), start + Duration::from_millis(500))
+            .is_some());
+        // minPeriod values are dropped, not held
+        assert_eq!(mgr.next_fixed_due(), None);
+    }
+
+    #[test]
+    fn test_min_period_send_times_pruned() {
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        let throttled = |path: &str| Subscription {
+            path: path.to_string(),
+            period: None,
+            format: None,
+            policy: None,
+            min_period: Some(500),
+        };
+        mgr.add_subscriptions("vessels.self", &[throttled("navigation.*")])
+            .unwrap();
+        let start = Instant::now();
+        assert!(mgr.filter_delta_at(&sog(3.1), start).is_some());
+        assert_eq!(mgr.last_sent.len(), 1);
+
+        // Send times are swept once their period has elapsed
+        mgr.filter_delta_at(&sog(3.2), start + THROTTLE_PRUNE_INTERVAL);
+        assert_eq!(mgr.last_sent.len(), 1);
+        assert!(mgr
+            .filter_delta_at(&sog(3.3), start + THROTTLE_PRUNE_INTERVAL * 2)
+            .is_some());
+        mgr.prune_throttles(start + THROTTLE_PRUNE_INTERVAL * 3);
+        assert!(mgr.last_sent.is_empty());
+
+        // and dropped with the subscription
+        assert!(mgr.filter_delta_at(&sog(3.4), Instant::now()).is_some());
+        assert_eq!(mgr.last_sent.len(), 1);
+        mgr.remove_subscription("vessels.self", "navigation.*");
+        assert!(mgr.last_sent.is_empty());
+    }
+
+    #[test]
+    fn test_fixed_period_flushes_latest_value() {
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        mgr.add_subscriptions(
+            "vessels.self",
+            &[Subscription {
+                path: "navigation.*".to_string(),
+                period: Some(1000),
+                format: None,
+                policy: Some(SubscriptionPolicy::Fixed),
                 min_period: None,
             }],
+        )
+        .unwrap();
+        let start = Instant::now();
+        let period = Duration::from_millis(1000);
+
+        // The first value goes out at once, the next is held
+        assert!(mgr.filter_delta_at(&sog(3.1), start).is_some());
+        assert!(mgr
+            .filter_delta_at(&sog(3.2), start + Duration::from_millis(100))
+            .is_none());
+        assert_eq!(mgr.next_fixed_due(), Some(start + period));
+        assert!(mgr
+            .flush_fixed(start + Duration::from_millis(500))
+            .is_empty());
+
+        let flushed = mgr.flush_fixed(start + period);
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].context.as_deref(), Some("vessels.self"));
+        assert_eq!(flushed[0].updates[0].source_ref.as_deref(), Some("gps"));
+        assert_eq!(flushed[0].updates[0].values[0].value, 3.2);
+
+        // Repeated without new deltas
+        let flushed = mgr.flush_fixed(start + period * 2);
+        assert_eq!(flushed[0].updates[0].values[0].value, 3.2);
+
+        mgr.remove_subscription("vessels.self", "navigation.*");
+        assert!(mgr.flush_fixed(start + period * 3).is_empty());
+        assert_eq!(mgr.next_fixed_due(), None);
+    }
+
+    #[test]
+    fn test_split_by_format() {
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        let spec = |path: &str, format| Subscription {
+            path: path.to_string(),
+            period: None,
+            format,
+            policy: None,
+            min_period: None,
+        };
+        mgr.add_subscriptions(
+            "vessels.self",
+            &[
+                spec("navigation.*", Some(SubscriptionFormat::Full)),
+                spec("navigation.speedOverGround", None),
+            ],
+        )
+        .unwrap();
+        assert!(!mgr.single_format(false));
+
+        let mut delta = sog(3.0);
+        delta.updates[0].values.push(PathValue {
+            path: "navigation.courseOverGroundTrue".to_string(),
+            value: serde_json::json!(1.5),
+        });
+
+        // Subscribed in both formats, speed goes out as both
+        let (as_delta, as_full) = mgr.split_by_format(delta.clone(), false);
+        let paths = |delta: Option<Delta>| -> Vec<String> {
+            delta
+                .unwrap()
+                .updates
+                .into_iter()
+                .flat_map(|update| update.values)
+                .map(|pv| pv.path)
+                .collect()
+        };
+        assert_eq!(paths(as_delta), ["navigation.speedOverGround"]);
+        assert_eq!(
+            paths(as_full),
+            [
+                "navigation.speedOverGround",
+                "navigation.courseOverGroundTrue"
+            ]
         );
 
+        // With a full-format connection everything is full
+        assert!(mgr.single_format(true));
+        let (as_delta, as_full) = mgr.split_by_format(delta, true);
+        assert!(as_delta.is_none());
+        assert_eq!(as_full.unwrap().updates[0].values.len(), 2);
+    }
+
+    #[test]
+    fn test_filter_delta_no_match() {
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        mgr.add_subscriptions(
+            "vessels.self",
+            &[Subscription {
+                path: "navigation.*".to_string(),
+                period: None,
+                format: None,
+                policy: None,
+                min_period: None,
+            }],
+        )
+        .unwrap();
+
         let delta = Delta {
             context: Some("vessels.self".to_string()),
             updates: vec![Update {
@@ -488,6 +1195,7 @@ mod tests {
                     value: serde_json::json!(5.0),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -507,7 +1215,8 @@ mod tests {
                 policy: None,
                 min_period: None,
             }],
-        );
+        )
+        .unwrap();
 
         let delta = Delta {
             context: Some("vessels.self".to_string()),
@@ -520,6 +1229,7 @@ mod tests {
                     value: serde_json::json!(3.5),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -554,7 +1264,8 @@ mod tests {
                     min_period: None,
                 },
             ],
-        );
+        )
+        .unwrap();
 
         // Should match (via either subscription)
         assert!(mgr.matches("vessels.self", "navigation.speedOverGround"));
@@ -563,15 +1274,43 @@ mod tests {
     #[test]
     fn test_context_resolution_with_urn() {
         let sub = ClientSubscription::new("vessels.self", "navigation.*");
-
-        // Should match actual URN as well as "vessels.self"
-        assert!(sub.matches("vessels.self", "navigation.speedOverGround"));
-        assert!(sub.matches(
-            "vessels.urn:mrn:signalk:uuid:test",
-            "navigation.speedOverGround"
+        let self_urn = "vessels.urn:mrn:signalk:uuid:test";
+
+        // Should match the self URN as well as "vessels.self"
+        assert!(sub.matches_with_self("vessels.self", "navigation.speedOverGround", self_urn));
+        assert!(sub.matches_with_self(self_urn, "navigation.speedOverGround", self_urn));
+        // ...but not other vessels
+        assert!(!sub.matches_with_self(
+            "vessels.urn:mrn:signalk:uuid:other",
+            "navigation.speedOverGround",
+            self_urn
         ));
     }
 
+    #[test]
+    fn test_context_pattern() {
+        let sub = ClientSubscription::new("vessels.*", "navigation.position");
+
+        assert!(sub.matches("vessels.self", "navigation.position"));
+        assert!(sub.matches("vessels.urn:mrn:imo:mmsi:230099999", "navigation.position"));
+        assert!(!sub.matches("aircraft.urn:mrn:imo:mmsi:111", "navigation.position"));
+    }
+
+    #[test]
+    fn test_self_context_follows_urn_change() {
+        let self_context = SelfContext::new("vessels.urn:mrn:signalk:uuid:old");
+        let mut mgr = SubscriptionManager::with_self_context(self_context.clone());
+        mgr.subscribe_self_all();
+
+        assert!(mgr.matches("vessels.urn:mrn:signalk:uuid:old", "navigation.position"));
+
+        self_context.set("vessels.urn:mrn:signalk:uuid:new");
+
+        assert!(mgr.matches("vessels.urn:mrn:signalk:uuid:new", "navigation.position"));
+        assert!(mgr.matches("vessels.self", "navigation.position"));
+        assert!(!mgr.matches("vessels.urn:mrn:signalk:uuid:old", "navigation.position"));
+    }
+
     #[test]
     fn test_wildcard_all_contexts() {
         let sub = ClientSubscription::new("*", "*");
@@ -593,7 +1332,8 @@ mod tests {
                 policy: None,
                 min_period: None,
             }],
-        );
+        )
+        .unwrap();
 
         let delta = Delta {
             context: Some("vessels.self".to_string()),
@@ -607,6 +1347,7 @@ mod tests {
                         value: serde_json::json!(3.5),
                     }],
                     meta: None,
+                    server_timestamp: None,
                 },
                 Update {
                     source_ref: Some("wind".to_string()),
@@ -617,6 +1358,7 @@ mod tests {
                         value: serde_json::json!(10.0),
                     }],
                     meta: None,
+                    server_timestamp: None,
                 },
             ],
         };
@@ -659,6 +1401,7 @@ mod tests {
                     value: serde_json::json!(3.5),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
         store.apply_delta(&delta);
@@ -686,6 +1429,7 @@ mod tests {
                     value: serde_json::json!(3.5),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
         store.apply_delta(&delta);
@@ -719,7 +1463,8 @@ mod tests {
                 policy: None,
                 min_period: None,
             }],
-        );
+        )
+        .unwrap();
 
         // Add data to multiple paths
         let delta = Delta {
@@ -739,6 +1484,7 @@ mod tests {
                     },
                 ],
                 meta: None,
+                server_timestamp: None,
             }],
         };
         store.apply_delta(&delta);
@@ -773,6 +1519,7 @@ mod tests {
                     value: serde_json::json!(3.5),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
         store.apply_delta(&delta);
@@ -818,6 +1565,7 @@ mod tests {
                     },
                 ],
                 meta: None,
+                server_timestamp: None,
             }],
         };
         store.apply_delta(&delta);
@@ -827,4 +1575,234 @@ mod tests {
         // Should contain all three paths
         assert_eq!(initial.updates[0].values.len(), 3);
     }
+
+    #[test]
+    fn test_get_initial_delta_groups_by_source() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test");
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        mgr.subscribe_self_all();
+
+        for (source, path) in [
+            ("nmea0183.GP", "navigation.speedOverGround"),
+            ("nmea2000.115", "environment.depth.belowTransducer"),
+        ] {
+            store.apply_delta(&Delta {
+                context: Some("vessels.self".to_string()),
+                updates: vec![Update {
+                    source_ref: Some(source.to_string()),
+                    source: None,
+                    timestamp: Some("2024-01-17T10:30:00.000Z".to_string()),
+                    values: vec![PathValue {
+                        path: path.to_string(),
+                        value: serde_json::json!(3.5),
+                    }],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        }
+
+        let initial = mgr.get_initial_delta(&store).unwrap();
+
+        assert_eq!(initial.updates.len(), 2);
+        for update in &initial.updates {
+            assert_eq!(update.values.len(), 1);
+            let expected = if update.values[0].path == "navigation.speedOverGround" {
+                "nmea0183.GP"
+            } else {
+                "nmea2000.115"
+            };
+            assert_eq!(update.source_ref.as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_cached_deltas_across_contexts() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test");
+        for (context, sog) in [
+            ("vessels.self", 3.5),
+            ("vessels.urn:mrn:imo:mmsi:230099999", 7.1),
+        ] {
+            store.apply_delta(&Delta {
+                context: Some(context.to_string()),
+                updates: vec![Update {
+                    source_ref: Some("test".to_string()),
+                    source: None,
+                    timestamp: Some("2024-01-01T00:00:00Z".to_string()),
+                    values: vec![
+                        PathValue {
+                            path: "navigation.speedOverGround".to_string(),
+                            value: serde_json::json!(sog),
+                        },
+                        PathValue {
+                            path: "navigation.headingTrue".to_string(),
+                            value: serde_json::json!(1.0),
+                        },
+                    ],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        }
+
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        assert!(mgr.cached_deltas(&store).is_empty());
+        mgr.add_subscriptions(
+            "vessels.*",
+            &[Subscription {
+                path: "navigation.speedOverGround".to_string(),
+                period: None,
+                format: None,
+                policy: None,
+                min_period: None,
+            }],
+        )
+        .unwrap();
+
+        let cached = mgr.cached_deltas(&store);
+        assert_eq!(cached.len(), 2);
+        for delta in &cached {
+            assert_eq!(delta.updates[0].values.len(), 1);
+            assert_eq!(
+                delta.updates[0].values[0].path,
+                "navigation.speedOverGround"
+            );
+        }
+    }
+
+    #[test]
+    fn test_full_model_for_subscriptions() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:test");
+        for context in ["vessels.self", "vessels.urn:mrn:imo:mmsi:230099999"] {
+            store.apply_delta(&Delta {
+                context: Some(context.to_string()),
+                updates: vec![Update {
+                    source_ref: Some("test".to_string()),
+                    source: None,
+                    timestamp: Some("2024-01-01T00:00:00Z".to_string()),
+                    values: vec![
+                        PathValue {
+                            path: "navigation.speedOverGround".to_string(),
+                            value: serde_json::json!(3.5),
+                        },
+                        PathValue {
+                            path: "environment.depth.belowKeel".to_string(),
+                            value: serde_json::json!(12.0),
+                        },
+                    ],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            });
+        }
+
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:test");
+        mgr.add_subscriptions(
+            "vessels.self",
+            &[Subscription {
+                path: "navigation.*".to_string(),
+                period: None,
+                format: None,
+                policy: None,
+                min_period: None,
+            }],
+        )
+        .unwrap();
+
+        let model = mgr.full_model(&store);
+        assert_eq!(model["self"], "vessels.urn:mrn:signalk:uuid:test");
+        let vessels = model["vessels"].as_object().unwrap();
+        assert_eq!(vessels.len(), 1);
+        let own = &vessels["urn:mrn:signalk:uuid:test"];
+        assert_eq!(own["navigation"]["speedOverGround"]["value"], 3.5);
+        assert_eq!(own["navigation"]["speedOverGround"]["$source"], "test");
+        assert!(own.get("environment").is_none());
+        assert!(model["sources"].get("test").is_some());
+    }
+
+    /// Collects the fields of every event it sees, formatted with `Debug`.
+    #[derive(Clone, Default)]
+    struct CapturedEvents(Arc<std::sync::Mutex<Vec<HashMap<String, String>>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for CapturedEvents {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            struct Fields<'a>(&'a mut HashMap<String, String>);
+            impl tracing::field::Visit for Fields<'_> {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    self.0
+                        .insert(field.name().to_string(), format!("{value:?}"));
+                }
+            }
+            let mut fields = HashMap::new();
+            event.record(&mut Fields(&mut fields));
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    #[test]
+    fn test_invalid_subscription_adds_nothing() {
+        let mut mgr = SubscriptionManager::new("vessels.urn:mrn:signalk:uuid:self");
+        let subs: Vec<Subscription> = ["navigation.*", ""]
+            .iter()
+            .map(|path| Subscription {
+                path: path.to_string(),
+                period: None,
+                format: None,
+                policy: None,
+                min_period: None,
+            })
+            .collect();
+
+        let err = mgr.add_subscriptions("vessels.self", &subs).unwrap_err();
+        assert_eq!(err.path, "");
+        assert!(matches!(err.source, PatternError::EmptyPattern));
+        assert_eq!(mgr.len(), 0);
+        assert!(!mgr.matches("vessels.self", "navigation.position"));
+
+        assert_eq!(err.to_string(), "Invalid path pattern \"\": Empty pattern");
+    }
+
+    #[test]
+    fn test_subscription_event_logging() {
+        use tracing_subscriber::{filter::Targets, layer::SubscriberExt};
+
+        let client: SocketAddr = "192.168.1.20:51234".parse().unwrap();
+        let warnings = vec!["Subscription limit of 1 reached".to_string()];
+        let log = |targets: Targets| {
+            let events = CapturedEvents::default();
+            let subscriber = tracing_subscriber::registry()
+                .with(events.clone())
+                .with(targets);
+            tracing::subscriber::with_default(subscriber, || {
+                log_subscription_event("subscribe", client, 3, &warnings);
+            });
+            Arc::try_unwrap(events.0).unwrap().into_inner().unwrap()
+        };
+
+        // Debug key enabled
+        let events =
+            log(Targets::new().with_target(SUBSCRIPTION_LOG_TARGET, tracing::Level::DEBUG));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "\"subscribe\"");
+        assert_eq!(events[0]["client"], "192.168.1.20:51234");
+        assert_eq!(events[0]["matchers"], "3");
+        assert_eq!(
+            events[0]["warnings"],
+            "[\"Subscription limit of 1 reached\"]"
+        );
+
+        // Debug key disabled
+        assert!(
+            log(Targets::new().with_target(SUBSCRIPTION_LOG_TARGET, tracing::Level::INFO))
+                .is_empty()
+        );
+    }
 }
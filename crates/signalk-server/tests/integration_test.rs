@@ -4,17 +4,22 @@
 //! to verify end-to-end functionality.
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use futures::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
 use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::MaybeTlsStream;
 use tokio_tungstenite::WebSocketStream;
 
-use signalk_core::{PathValue, Update};
-use signalk_server::{Delta, ServerConfig, ServerEvent, SignalKServer};
+use signalk_core::{AclRule, PathValue, Permission, Update};
+use signalk_server::{
+    Delta, InflateStream, LagPolicy, MessageDeflater, ServerConfig, ServerEvent, SignalKServer,
+    DEFLATE_RESPONSE,
+};
 
 /// Find an available port for testing.
 async fn find_available_port() -> SocketAddr {
@@ -28,15 +33,28 @@ async fn start_test_server() -> (
     SocketAddr,
     tokio::sync::mpsc::Sender<ServerEvent>,
     tokio::task::JoinHandle<()>,
+) {
+    start_test_server_with(|_| {}).await
+}
+
+/// Start a test server with adjusted configuration.
+async fn start_test_server_with(
+    configure: impl FnOnce(&mut ServerConfig),
+) -> (
+    SocketAddr,
+    tokio::sync::mpsc::Sender<ServerEvent>,
+    tokio::task::JoinHandle<()>,
 ) {
     let addr = find_available_port().await;
 
-    let config = ServerConfig {
+    let mut config = ServerConfig {
         name: "test-server".to_string(),
         version: "1.7.0".to_string(),
         self_urn: "vessels.urn:mrn:signalk:uuid:test-vessel".to_string(),
         bind_addr: addr,
+        ..Default::default()
     };
+    configure(&mut config);
 
     let server = SignalKServer::new(config);
     let event_tx = server.event_sender();
@@ -133,6 +151,7 @@ async fn test_delta_broadcast() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -204,6 +223,7 @@ async fn test_subscription_filtering() {
                 },
             ],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -265,6 +285,7 @@ async fn test_multiple_clients() {
                 value: serde_json::json!({"latitude": 45.0, "longitude": -123.0}),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -324,6 +345,7 @@ async fn test_unsubscribe() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -349,7 +371,61 @@ async fn test_unsubscribe() {
 }
 
 #[tokio::test]
-async fn test_put_request_returns_not_implemented() {
+async fn test_put_handler_applies_value() {
+    let addr = find_available_port().await;
+    let server = SignalKServer::new(ServerConfig {
+        self_urn: "vessels.urn:mrn:signalk:uuid:test-vessel".to_string(),
+        bind_addr: addr,
+        writable_paths: Vec::new(),
+        ..Default::default()
+    });
+    let accepted = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = accepted.clone();
+    server
+        .store()
+        .write()
+        .register_put_handler(
+            "steering.autopilot.*",
+            Box::new(move |value| {
+                seen.lock().unwrap().push(value.clone());
+                Ok(())
+            }),
+        )
+        .unwrap();
+    let handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let put = serde_json::json!({
+        "requestId": "autopilot",
+        "put": { "path": "steering.autopilot.state", "value": "auto" }
+    });
+    ws.send(Message::Text(put.to_string())).await.unwrap();
+
+    let resp: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("PUT response")).unwrap();
+    assert_eq!(resp["requestId"], "autopilot");
+    assert_eq!(resp["state"], "COMPLETED");
+    assert_eq!(resp["statusCode"], 200);
+    assert_eq!(*accepted.lock().unwrap(), vec![serde_json::json!("auto")]);
+
+    // The accepted value is broadcast to self subscribers
+    let delta: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Delta")).unwrap();
+    let value = &delta["updates"][0]["values"][0];
+    assert_eq!(value["path"], "steering.autopilot.state");
+    assert_eq!(value["value"], "auto");
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_put_to_read_only_path_rejected() {
     let (addr, _event_tx, handle) = start_test_server().await;
 
     // Connect client
@@ -358,11 +434,11 @@ async fn test_put_request_returns_not_implemented() {
     // Skip Hello
     let _ = recv_text(&mut ws).await.expect("Hello");
 
-    // Send a PUT request
+    // Send a PUT request to a path outside the allowlist
     let put_request = serde_json::json!({
         "requestId": "test-put-123",
         "put": {
-            "path": "steering.autopilot.target.headingTrue",
+            "path": "navigation.speedOverGround",
             "value": 1.5
         }
     });
@@ -379,13 +455,179 @@ async fn test_put_request_returns_not_implemented() {
 
     assert_eq!(resp["requestId"], "test-put-123");
     assert_eq!(resp["state"], "FAILED");
-    assert_eq!(resp["statusCode"], 501);
+    assert_eq!(resp["statusCode"], 405);
 
     // Clean up
     ws.close(None).await.ok();
     handle.abort();
 }
 
+#[tokio::test]
+async fn test_put_to_writable_path() {
+    let (addr, _event_tx, handle) = start_test_server_with(|config| {
+        config.writable_paths = vec!["electrical.switches.*".to_string()];
+    })
+    .await;
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let put = |request_id: &str, path: &str| {
+        serde_json::json!({
+            "requestId": request_id,
+            "put": { "path": path, "value": true }
+        })
+        .to_string()
+    };
+    ws.send(Message::Text(put(
+        "allowed",
+        "electrical.switches.anchorLight.state",
+    )))
+    .await
+    .unwrap();
+
+    let resp: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("PUT response")).unwrap();
+    assert_eq!(resp["requestId"], "allowed");
+    assert_eq!(resp["state"], "COMPLETED");
+    assert_eq!(resp["statusCode"], 200);
+
+    // The written value is applied and broadcast to self subscribers
+    let delta: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Delta")).unwrap();
+    let value = &delta["updates"][0]["values"][0];
+    assert_eq!(value["path"], "electrical.switches.anchorLight.state");
+    assert_eq!(value["value"], true);
+
+    // Not in this server's allowlist, although it is a default
+    ws.send(Message::Text(put(
+        "denied",
+        "steering.autopilot.target.headingTrue",
+    )))
+    .await
+    .unwrap();
+
+    let resp: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("PUT response")).unwrap();
+    assert_eq!(resp["requestId"], "denied");
+    assert_eq!(resp["state"], "FAILED");
+    assert_eq!(resp["statusCode"], 405);
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+fn restricted_acl() -> Vec<AclRule> {
+    vec![
+        AclRule {
+            path: "steering.autopilot.*".to_string(),
+            read: None,
+            write: Some(Permission::Admin),
+        },
+        AclRule {
+            path: "electrical.*".to_string(),
+            read: Some(Permission::ReadWrite),
+            write: None,
+        },
+    ]
+}
+
+#[tokio::test]
+async fn test_acl_allows_admin_put() {
+    let (addr, _event_tx, handle) = start_test_server_with(|config| {
+        config.acl_rules = restricted_acl();
+        config.client_permission = Permission::Admin;
+    })
+    .await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let put = serde_json::json!({
+        "requestId": "admin",
+        "put": { "path": "steering.autopilot.target.headingTrue", "value": 1.52 }
+    });
+    ws.send(Message::Text(put.to_string())).await.unwrap();
+    let resp: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("PUT response")).unwrap();
+    assert_eq!(resp["state"], "COMPLETED");
+    assert_eq!(resp["statusCode"], 200);
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_acl_denies_readonly_read() {
+    let (addr, event_tx, handle) = start_test_server_with(|config| {
+        config.acl_rules = restricted_acl();
+        config.client_permission = Permission::ReadOnly;
+    })
+    .await;
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "electrical.batteries.house.voltage".to_string(),
+                    value: serde_json::json!(12.8),
+                },
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(5.5),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .expect("Should send delta");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Cached values leave out the restricted path
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+    let cached: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Cached values")).unwrap();
+    let values = cached["updates"][0]["values"].as_array().unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0]["path"], "navigation.speedOverGround");
+
+    for (path, status) in [
+        ("electrical.batteries.house.voltage", 403),
+        ("navigation.speedOverGround", 200),
+    ] {
+        let get = serde_json::json!({
+            "context": "vessels.self",
+            "get": { "path": path }
+        });
+        ws.send(Message::Text(get.to_string())).await.unwrap();
+        let response: serde_json::Value =
+            serde_json::from_str(&recv_text(&mut ws).await.unwrap()).unwrap();
+        assert_eq!(response["statusCode"], status, "{path}");
+    }
+
+    // Read-only clients can't write at all
+    let put = serde_json::json!({
+        "requestId": "readonly",
+        "put": { "path": "electrical.switches.anchorLight.state", "value": true }
+    });
+    ws.send(Message::Text(put.to_string())).await.unwrap();
+    let resp: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("PUT response")).unwrap();
+    assert_eq!(resp["statusCode"], 403);
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
 #[tokio::test]
 async fn test_query_param_subscribe_none() {
     let (addr, event_tx, handle) = start_test_server().await;
@@ -410,6 +652,7 @@ async fn test_query_param_subscribe_none() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -456,6 +699,7 @@ async fn test_query_param_subscribe_all() {
                 value: serde_json::json!({"latitude": 45.0, "longitude": -123.0}),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -507,6 +751,155 @@ async fn test_error_handling_malformed_json() {
     handle.abort();
 }
 
+/// A subscribe with an invalid path is answered with an error and adds
+/// nothing, and the connection carries on.
+#[tokio::test]
+async fn test_invalid_subscription_reported() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let subscribe = serde_json::json!({
+        "requestId": "1",
+        "context": "vessels.self",
+        "subscribe": [{"path": "navigation.*"}, {"path": ""}]
+    });
+    ws.send(Message::Text(subscribe.to_string())).await.unwrap();
+    let response: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Should receive error")).unwrap();
+    assert_eq!(response["requestId"], "1");
+    assert_eq!(response["state"], "FAILED");
+    assert_eq!(response["statusCode"], 400);
+    assert!(
+        response["message"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid path pattern"),
+        "{response}"
+    );
+
+    // Still connected; a valid subscribe with a requestId is acknowledged
+    let subscribe = serde_json::json!({
+        "requestId": "2",
+        "context": "vessels.self",
+        "subscribe": [{"path": "environment.*"}]
+    });
+    ws.send(Message::Text(subscribe.to_string())).await.unwrap();
+    let response: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Should receive ack")).unwrap();
+    assert_eq!(
+        response,
+        serde_json::json!({ "requestId": "2", "state": "COMPLETED", "statusCode": 200 })
+    );
+
+    // Only the accepted subscription is active
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(5.5),
+                },
+                PathValue {
+                    path: "environment.depth.belowKeel".to_string(),
+                    value: serde_json::json!(4.2),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .expect("Should send delta");
+    let msg: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Should receive delta")).unwrap();
+    let values = msg["updates"][0]["values"].as_array().unwrap();
+    assert_eq!(values.len(), 1, "{msg}");
+    assert_eq!(values[0]["path"], "environment.depth.belowKeel");
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_client_delta_batch() {
+    let (addr, _event_tx, handle) = start_test_server().await;
+
+    let mut listener = connect_client_with_params(addr, "subscribe=all").await;
+    let _ = recv_text(&mut listener).await.expect("Hello");
+    let mut sender = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut sender).await.expect("Hello");
+
+    let batch = serde_json::json!([
+        {
+            "context": "vessels.self",
+            "updates": [{
+                "$source": "client",
+                "timestamp": "2024-01-17T12:00:00.000Z",
+                "values": [{"path": "navigation.speedOverGround", "value": 5.5}]
+            }]
+        },
+        {
+            "context": "vessels.urn:mrn:imo:mmsi:230099999",
+            "updates": [{
+                "$source": "client",
+                "timestamp": "2024-01-17T12:00:00.000Z",
+                "values": [{"path": "navigation.courseOverGroundTrue", "value": 1.2}]
+            }]
+        }
+    ]);
+    sender.send(Message::Text(batch.to_string())).await.unwrap();
+
+    // Both are broadcast, in order
+    let mut contexts = Vec::new();
+    for _ in 0..2 {
+        let delta: serde_json::Value = serde_json::from_str(
+            &recv_text(&mut listener)
+                .await
+                .expect("Should receive delta"),
+        )
+        .unwrap();
+        contexts.push(delta["context"].as_str().unwrap().to_string());
+    }
+    assert_eq!(
+        contexts,
+        ["vessels.self", "vessels.urn:mrn:imo:mmsi:230099999"]
+    );
+
+    // And stored
+    for (context, path, value) in [
+        ("vessels.self", "navigation.speedOverGround", 5.5),
+        (
+            "vessels.urn:mrn:imo:mmsi:230099999",
+            "navigation.courseOverGroundTrue",
+            1.2,
+        ),
+    ] {
+        let get =
+            serde_json::json!({ "context": context, "requestId": path, "get": { "path": path } });
+        sender.send(Message::Text(get.to_string())).await.unwrap();
+        let response: serde_json::Value = serde_json::from_str(
+            &recv_text(&mut sender)
+                .await
+                .expect("Should receive GET response"),
+        )
+        .unwrap();
+        assert_eq!(response["statusCode"], 200, "{response}");
+        assert_eq!(response["value"]["value"], value);
+    }
+
+    listener.close(None).await.ok();
+    sender.close(None).await.ok();
+    handle.abort();
+}
+
 #[tokio::test]
 async fn test_initial_cached_values() {
     let (addr, event_tx, handle) = start_test_server().await;
@@ -523,6 +916,7 @@ async fn test_initial_cached_values() {
                 value: serde_json::json!(7.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -613,6 +1007,7 @@ async fn test_complex_wildcard_pattern() {
                 },
             ],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -663,6 +1058,7 @@ async fn test_store_integration() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -677,6 +1073,7 @@ async fn test_store_integration() {
                 value: serde_json::json!(12.3),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -735,6 +1132,7 @@ async fn test_non_self_context() {
                 value: serde_json::json!({"latitude": 47.0, "longitude": -122.0}),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -834,6 +1232,7 @@ async fn test_multiple_subscriptions_additive() {
                 },
             ],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -896,6 +1295,7 @@ async fn test_meta_data_handling() {
                     supports_put: None,
                 },
             }]),
+            server_timestamp: None,
         }],
     };
 
@@ -954,6 +1354,7 @@ async fn test_source_tracking() {
                 }),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -997,6 +1398,7 @@ async fn test_multiple_sources_same_path() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1011,6 +1413,7 @@ async fn test_multiple_sources_same_path() {
                 value: serde_json::json!(5.7),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1056,6 +1459,7 @@ async fn test_timestamp_preservation() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1096,6 +1500,7 @@ async fn test_empty_values_array() {
             timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
             values: vec![],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1145,6 +1550,7 @@ async fn test_null_value_handling() {
                 value: serde_json::Value::Null,
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1194,6 +1600,7 @@ async fn test_large_delta_message() {
             timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
             values,
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1241,6 +1648,7 @@ async fn test_rapid_delta_stream() {
                     value: serde_json::json!(5.0 + i as f64 * 0.1),
                 }],
                 meta: None,
+                server_timestamp: None,
             }],
         };
 
@@ -1346,6 +1754,7 @@ async fn test_subscription_with_specific_period() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1360,14 +1769,64 @@ async fn test_subscription_with_specific_period() {
     let received: serde_json::Value = serde_json::from_str(&msg).expect("Valid JSON");
     assert!(received["updates"].is_array());
 
-    // Note: Period throttling is not yet implemented, so this just verifies
-    // the subscription is accepted and deltas are delivered
+    // minPeriod takes precedence over period, so the first value is sent
+    // straight away
 
     // Clean up
     ws.close(None).await.ok();
     handle.abort();
 }
 
+#[tokio::test]
+async fn test_subscription_fixed_period_repeats_value() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let subscribe = serde_json::json!({
+        "context": "vessels.self",
+        "subscribe": [{
+            "path": "navigation.*",
+            "period": 100,
+            "policy": "fixed"
+        }]
+    });
+    ws.send(Message::Text(subscribe.to_string())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test".to_string()),
+            source: None,
+            timestamp: None,
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(5.5),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .unwrap();
+
+    // Sent on arrival, then again every period without new deltas
+    for _ in 0..3 {
+        let received: serde_json::Value =
+            serde_json::from_str(&recv_text(&mut ws).await.expect("Delta")).unwrap();
+        let value = &received["updates"][0]["values"][0];
+        assert_eq!(value["path"], "navigation.speedOverGround");
+        assert_eq!(value["value"], 5.5);
+    }
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
 #[tokio::test]
 async fn test_subscription_policy_instant() {
     let (addr, event_tx, handle) = start_test_server().await;
@@ -1402,6 +1861,7 @@ async fn test_subscription_policy_instant() {
                 value: serde_json::json!(5.5),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1492,6 +1952,7 @@ async fn test_concurrent_clients_independent_subscriptions() {
                 },
             ],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1547,6 +2008,7 @@ async fn test_subscription_policy_warning_minperiod_with_non_instant() {
                 value: serde_json::json!(172.9),
             }],
             meta: None,
+            server_timestamp: None,
         }],
     };
 
@@ -1628,3 +2090,1136 @@ async fn test_subscription_policy_warning_period_with_non_fixed() {
     ws.close(None).await.ok();
     handle.abort();
 }
+
+#[tokio::test]
+async fn test_no_gap_when_connecting_during_delta_stream() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let counter_delta = |n: u64| Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test".to_string()),
+            source: None,
+            timestamp: None,
+            values: vec![PathValue {
+                path: "navigation.log".to_string(),
+                value: serde_json::json!(n),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+
+    // Make sure the store has a cached value before the client connects
+    event_tx
+        .send(ServerEvent::DeltaReceived(counter_delta(0)))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Keep deltas flowing while the client connects
+    const LAST: u64 = 500;
+    let producer_tx = event_tx.clone();
+    let producer = tokio::spawn(async move {
+        for n in 1..=LAST {
+            producer_tx
+                .send(ServerEvent::DeltaReceived(counter_delta(n)))
+                .await
+                .unwrap();
+            if n % 5 == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+    });
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    // First message is the snapshot, everything after must be consecutive
+    let mut seen = Vec::new();
+    while seen.last() != Some(&LAST) {
+        let msg = recv_text(&mut ws).await.expect("Delta");
+        let delta: serde_json::Value = serde_json::from_str(&msg).unwrap();
+        for update in delta["updates"].as_array().unwrap() {
+            for value in update["values"].as_array().unwrap() {
+                if value["path"] == "navigation.log" {
+                    seen.push(value["value"].as_u64().unwrap());
+                }
+            }
+        }
+    }
+
+    for pair in seen.windows(2) {
+        assert_eq!(pair[1], pair[0] + 1, "gap or duplicate in {seen:?}");
+    }
+
+    producer.await.unwrap();
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_chronological_batch_order() {
+    let (addr, event_tx, handle) =
+        start_test_server_with(|config| config.chronological_batches = true).await;
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let depth_update = |timestamp: &str, depth: f64| Update {
+        source_ref: Some("replay".to_string()),
+        source: None,
+        timestamp: Some(timestamp.to_string()),
+        values: vec![PathValue {
+            path: "environment.depth.belowKeel".to_string(),
+            value: serde_json::json!(depth),
+        }],
+        meta: None,
+        server_timestamp: None,
+    };
+
+    // Out of order, split across deltas
+    let batch = vec![
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![
+                depth_update("2024-01-17T10:00:02.000Z", 3.0),
+                depth_update("2024-01-17T10:00:00.000Z", 1.0),
+            ],
+        },
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![depth_update("2024-01-17T10:00:01.000Z", 2.0)],
+        },
+    ];
+    event_tx
+        .send(ServerEvent::DeltaBatch(batch))
+        .await
+        .expect("Should send batch");
+
+    let mut received = Vec::new();
+    while received.len() < 3 {
+        let msg = recv_text(&mut ws).await.expect("Delta");
+        let delta: serde_json::Value = serde_json::from_str(&msg).unwrap();
+        for update in delta["updates"].as_array().unwrap() {
+            received.push(update["timestamp"].as_str().unwrap().to_string());
+        }
+    }
+    assert_eq!(
+        received,
+        vec![
+            "2024-01-17T10:00:00.000Z",
+            "2024-01-17T10:00:01.000Z",
+            "2024-01-17T10:00:02.000Z",
+        ]
+    );
+
+    // The store ends up with the chronologically latest value
+    let mut late = connect_client(addr).await;
+    let _ = recv_text(&mut late).await.expect("Hello");
+    let cached: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut late).await.expect("Cached values")).unwrap();
+    let values = &cached["updates"][0]["values"];
+    assert_eq!(values[0]["path"], "environment.depth.belowKeel");
+    assert_eq!(values[0]["value"], serde_json::json!(3.0));
+
+    ws.close(None).await.ok();
+    late.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_self_subscription_follows_urn_change() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    // Default subscription is vessels.self
+    let mut ws = connect_client_with_params(addr, "subscribe=self&sendCachedValues=false").await;
+    let hello = recv_text(&mut ws).await.expect("Hello");
+    let hello: serde_json::Value = serde_json::from_str(&hello).unwrap();
+    assert_eq!(hello["self"], "vessels.urn:mrn:signalk:uuid:test-vessel");
+
+    let sog_delta = |context: &str, value: f64| Delta {
+        context: Some(context.to_string()),
+        updates: vec![Update {
+            source_ref: Some("test".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(value),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+
+    let new_urn = "vessels.urn:mrn:signalk:uuid:regenerated";
+    event_tx
+        .send(ServerEvent::SelfUrnChanged(new_urn.to_string()))
+        .await
+        .unwrap();
+    // The old URN is now just another vessel, so only the second delta is self
+    event_tx
+        .send(ServerEvent::DeltaReceived(sog_delta(
+            "vessels.urn:mrn:signalk:uuid:test-vessel",
+            1.0,
+        )))
+        .await
+        .unwrap();
+    event_tx
+        .send(ServerEvent::DeltaReceived(sog_delta(new_urn, 2.0)))
+        .await
+        .unwrap();
+
+    let msg = recv_text(&mut ws).await.expect("Should receive self delta");
+    let received: serde_json::Value = serde_json::from_str(&msg).unwrap();
+    assert_eq!(received["context"], new_urn);
+    assert_eq!(received["updates"][0]["values"][0]["value"], 2.0);
+
+    // New connections are greeted with the new URN
+    let mut ws2 = connect_client(addr).await;
+    let hello = recv_text(&mut ws2).await.expect("Hello");
+    let hello: serde_json::Value = serde_json::from_str(&hello).unwrap();
+    assert_eq!(hello["self"], new_urn);
+
+    ws.close(None).await.ok();
+    ws2.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_server_timestamps() {
+    let (addr, event_tx, handle) =
+        start_test_server_with(|config| config.server_timestamps = true).await;
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(3.85),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .unwrap();
+
+    let msg = recv_text(&mut ws).await.expect("Should receive delta");
+    let received: serde_json::Value = serde_json::from_str(&msg).unwrap();
+    let update = &received["updates"][0];
+    assert_eq!(update["timestamp"], "2024-01-17T12:00:00.000Z");
+    let server_timestamp = update["serverTimestamp"]
+        .as_str()
+        .expect("serverTimestamp should be set");
+    assert!(chrono::DateTime::parse_from_rfc3339(server_timestamp).is_ok());
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_query_param_format_full() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let mut ws = connect_client_with_params(addr, "format=full&sendCachedValues=false").await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(3.85),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .unwrap();
+
+    let msg = recv_text(&mut ws)
+        .await
+        .expect("Should receive full fragment");
+    let received: serde_json::Value = serde_json::from_str(&msg).unwrap();
+    assert!(received.get("updates").is_none());
+    assert_eq!(
+        received["vessels"]["urn:mrn:signalk:uuid:test-vessel"]["navigation"]["speedOverGround"]
+            ["value"],
+        3.85
+    );
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_subscription_format_full() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    // Mixed: navigation as full-format, environment as deltas
+    let subscribe = serde_json::json!({
+        "context": "vessels.self",
+        "subscribe": [
+            { "path": "navigation.*", "format": "full" },
+            { "path": "environment.*", "format": "delta" }
+        ]
+    });
+    ws.send(Message::Text(subscribe.to_string())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(3.85),
+                },
+                PathValue {
+                    path: "environment.depth.belowKeel".to_string(),
+                    value: serde_json::json!(12.5),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .unwrap();
+
+    let as_delta: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Delta")).unwrap();
+    let values = as_delta["updates"][0]["values"].as_array().unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0]["path"], "environment.depth.belowKeel");
+
+    let as_full: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Full fragment")).unwrap();
+    assert!(as_full.get("updates").is_none());
+    let vessel = &as_full["vessels"]["urn:mrn:signalk:uuid:test-vessel"];
+    assert_eq!(vessel["navigation"]["speedOverGround"]["value"], 3.85);
+    assert!(vessel.get("environment").is_none());
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_subscribe_all_default_min_period() {
+    let (addr, event_tx, handle) =
+        start_test_server_with(|config| config.default_all_min_period_ms = Some(200)).await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=all&sendCachedValues=false").await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    // ~100 updates/s of one path for 600ms
+    let start = tokio::time::Instant::now();
+    for i in 0..60 {
+        let delta = Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("test".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(i),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+        event_tx
+            .send(ServerEvent::DeltaReceived(delta))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let elapsed = start.elapsed();
+
+    let mut received = 0;
+    while timeout(Duration::from_millis(300), ws.next()).await.is_ok() {
+        received += 1;
+    }
+
+    // One update per 200ms window, plus the first one
+    let max = elapsed.as_millis() / 200 + 1;
+    assert!(
+        (2..=max as usize).contains(&received),
+        "received {received} deltas in {elapsed:?}"
+    );
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_hello_advertises_capabilities() {
+    let (addr, _event_tx, handle) = start_test_server_with(|config| {
+        config.max_subscriptions = Some(2);
+        config.writable_paths.clear();
+    })
+    .await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let hello: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Hello")).unwrap();
+    assert_eq!(
+        hello["capabilities"],
+        serde_json::json!({
+            "put": false,
+            "formats": ["delta", "full"],
+            "maxSubscriptions": 2,
+            "cbor": false,
+            "compression": false,
+        })
+    );
+
+    // The advertised limit is enforced
+    let subscribe = serde_json::json!({
+        "context": "vessels.self",
+        "subscribe": [
+            {"path": "navigation.*"},
+            {"path": "environment.*"},
+            {"path": "electrical.*"},
+        ]
+    });
+    ws.send(Message::Text(subscribe.to_string())).await.unwrap();
+    let warning = recv_text(&mut ws).await.expect("Should receive warning");
+    assert!(warning.contains("Subscription limit of 2"), "{warning}");
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_denied_ip_is_refused() {
+    let localhost: std::net::IpAddr = "127.0.0.1".parse().unwrap();
+    let (addr, _event_tx, handle) =
+        start_test_server_with(|config| config.ip_denylist = vec![localhost]).await;
+
+    let url = format!("ws://{addr}/signalk/v1/stream");
+    let result = timeout(
+        Duration::from_secs(5),
+        tokio_tungstenite::connect_async(&url),
+    )
+    .await
+    .expect("Refusal should not hang");
+    assert!(
+        result.is_err(),
+        "Denied client should not complete handshake"
+    );
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_allowlisted_ip_proceeds() {
+    let localhost: std::net::IpAddr = "127.0.0.1".parse().unwrap();
+    let (addr, _event_tx, handle) =
+        start_test_server_with(|config| config.ip_allowlist = Some(vec![localhost])).await;
+
+    let mut ws = connect_client(addr).await;
+    let hello = recv_text(&mut ws).await.expect("Allowed client gets Hello");
+    assert!(hello.contains("\"self\""));
+
+    ws.close(None).await.ok();
+    handle.abort();
+
+    // A server allowing only another address refuses localhost
+    let other: std::net::IpAddr = "192.0.2.1".parse().unwrap();
+    let (addr, _event_tx, handle) =
+        start_test_server_with(|config| config.ip_allowlist = Some(vec![other])).await;
+    let url = format!("ws://{addr}/signalk/v1/stream");
+    assert!(tokio_tungstenite::connect_async(&url).await.is_err());
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_max_connections_refuses_extra_client() {
+    let (addr, event_tx, handle) =
+        start_test_server_with(|config| config.max_connections = Some(2)).await;
+
+    let mut first = connect_client(addr).await;
+    let mut second = connect_client(addr).await;
+    recv_text(&mut first)
+        .await
+        .expect("First client gets Hello");
+    recv_text(&mut second)
+        .await
+        .expect("Second client gets Hello");
+
+    // The third client is turned away with 503
+    let url = format!("ws://{addr}/signalk/v1/stream");
+    let result = timeout(
+        Duration::from_secs(5),
+        tokio_tungstenite::connect_async(&url),
+    )
+    .await
+    .expect("Refusal should not hang");
+    match result {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), 503);
+        }
+        other => panic!("Expected HTTP 503, got {other:?}"),
+    }
+
+    // Earlier clients are unaffected
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(5.5),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .expect("Should send delta");
+    for ws in [&mut first, &mut second] {
+        let msg = recv_text(ws).await.expect("Should receive delta");
+        assert!(msg.contains("navigation.speedOverGround"));
+    }
+
+    // Closing one frees a slot
+    first.close(None).await.ok();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut third = connect_client(addr).await;
+    recv_text(&mut third)
+        .await
+        .expect("Third client gets Hello");
+
+    second.close(None).await.ok();
+    third.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_request_ids_are_echoed() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(5.5),
+                },
+                PathValue {
+                    path: "navigation.headingTrue".to_string(),
+                    value: serde_json::json!(1.2),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .expect("Should send delta");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+
+    // Both requests go out before either response is read
+    for (id, path) in [
+        ("sog", "navigation.speedOverGround"),
+        ("hdg", "navigation.headingTrue"),
+    ] {
+        let get = serde_json::json!({
+            "context": "vessels.self",
+            "requestId": id,
+            "get": { "path": path }
+        });
+        ws.send(Message::Text(get.to_string())).await.unwrap();
+    }
+
+    let mut responses = std::collections::HashMap::new();
+    for _ in 0..2 {
+        let msg = recv_text(&mut ws)
+            .await
+            .expect("Should receive GET response");
+        let response: serde_json::Value = serde_json::from_str(&msg).unwrap();
+        assert_eq!(response["statusCode"], 200);
+        responses.insert(
+            response["requestId"].as_str().unwrap().to_string(),
+            response,
+        );
+    }
+    assert_eq!(responses["sog"]["path"], "navigation.speedOverGround");
+    assert_eq!(responses["sog"]["value"]["value"], 5.5);
+    assert_eq!(responses["hdg"]["path"], "navigation.headingTrue");
+    assert_eq!(responses["hdg"]["value"]["value"], 1.2);
+
+    // Unknown paths fail with the id still attached
+    let get = r#"{"requestId": "missing", "get": {"path": "navigation.nothing"}}"#;
+    ws.send(Message::Text(get.to_string())).await.unwrap();
+    let response: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.unwrap()).unwrap();
+    assert_eq!(response["requestId"], "missing");
+    assert_eq!(response["statusCode"], 404);
+    assert_eq!(response["state"], "FAILED");
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_set_mode_switches_to_all() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+
+    let sog = |context: &str, value: f64| Delta {
+        context: Some(context.to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(value),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    let other = "vessels.urn:mrn:imo:mmsi:230099999";
+
+    // In self mode, only the self delta arrives
+    for delta in [sog(other, 1.0), sog("vessels.self", 2.0)] {
+        event_tx
+            .send(ServerEvent::DeltaReceived(delta))
+            .await
+            .expect("Should send delta");
+    }
+    let msg: serde_json::Value = serde_json::from_str(&recv_text(&mut ws).await.unwrap()).unwrap();
+    assert_eq!(msg["updates"][0]["values"][0]["value"], 2.0);
+
+    ws.send(Message::Text(r#"{"mode": "all"}"#.to_string()))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    event_tx
+        .send(ServerEvent::DeltaReceived(sog(other, 3.0)))
+        .await
+        .expect("Should send delta");
+    let msg: serde_json::Value = serde_json::from_str(&recv_text(&mut ws).await.unwrap()).unwrap();
+    assert_eq!(msg["context"], other);
+    assert_eq!(msg["updates"][0]["values"][0]["value"], 3.0);
+
+    // Unknown modes are reported and leave subscriptions as they are
+    ws.send(Message::Text(r#"{"mode": "bogus"}"#.to_string()))
+        .await
+        .unwrap();
+    let warning = recv_text(&mut ws).await.expect("Should receive warning");
+    assert!(warning.contains("Unknown subscribe mode bogus"));
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_treat_all_contexts_as_self() {
+    let (addr, event_tx, handle) =
+        start_test_server_with(|config| config.treat_all_contexts_as_self = true).await;
+
+    let delta = Delta {
+        context: Some("vessels.urn:mrn:imo:mmsi:230099999".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(5.5),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .expect("Should send delta");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+
+    for (context, status) in [
+        ("vessels.self", 200),
+        ("vessels.urn:mrn:imo:mmsi:230099999", 404),
+    ] {
+        let get = serde_json::json!({
+            "context": context,
+            "get": { "path": "navigation.speedOverGround" }
+        });
+        ws.send(Message::Text(get.to_string())).await.unwrap();
+        let response: serde_json::Value =
+            serde_json::from_str(&recv_text(&mut ws).await.unwrap()).unwrap();
+        assert_eq!(response["statusCode"], status, "{context}");
+    }
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_oversized_value_dropped() {
+    let (addr, event_tx, handle) =
+        start_test_server_with(|config| config.max_value_bytes = Some(1024)).await;
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(5.5),
+                },
+                PathValue {
+                    path: "notifications.blob".to_string(),
+                    value: serde_json::json!("x".repeat(4096)),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .expect("Should send delta");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+
+    for (path, status) in [
+        ("navigation.speedOverGround", 200),
+        ("notifications.blob", 404),
+    ] {
+        let get = serde_json::json!({
+            "context": "vessels.self",
+            "get": { "path": path }
+        });
+        ws.send(Message::Text(get.to_string())).await.unwrap();
+        let response: serde_json::Value =
+            serde_json::from_str(&recv_text(&mut ws).await.unwrap()).unwrap();
+        assert_eq!(response["statusCode"], status, "{path}");
+    }
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_dedup_drops_repeated_values() {
+    let (addr, event_tx, handle) = start_test_server().await;
+
+    let mut ws = connect_client_with_params(addr, "dedup=60000").await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+
+    for sog in [3.5, 3.5, 3.5, 3.6] {
+        let delta = Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("test.source".to_string()),
+                source: None,
+                timestamp: None,
+                values: vec![PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(sog),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        };
+        event_tx
+            .send(ServerEvent::DeltaReceived(delta))
+            .await
+            .expect("Should send delta");
+    }
+
+    // The repeats in between are never sent
+    for expected in [3.5, 3.6] {
+        let delta: serde_json::Value =
+            serde_json::from_str(&recv_text(&mut ws).await.expect("Should receive delta")).unwrap();
+        assert_eq!(delta["updates"][0]["values"][0]["value"], expected);
+    }
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_idle_connection_closed() {
+    let (addr, _event_tx, handle) =
+        start_test_server_with(|config| config.idle_timeout_ms = Some(300)).await;
+
+    // Subscribes to nothing and sends nothing
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+    let start = std::time::Instant::now();
+
+    let warning = recv_text(&mut ws)
+        .await
+        .expect("Should receive idle warning");
+    assert!(warning.contains("idle"), "{warning}");
+    assert!(start.elapsed() >= Duration::from_millis(250));
+
+    match timeout(Duration::from_secs(5), ws.next()).await {
+        Ok(Some(Ok(Message::Close(_)))) | Ok(None) => {}
+        other => panic!("Expected close after idle warning, got {other:?}"),
+    }
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_pings_dont_keep_idle_connection_open() {
+    let (addr, _event_tx, handle) =
+        start_test_server_with(|config| config.idle_timeout_ms = Some(300)).await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none").await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+    let start = std::time::Instant::now();
+
+    // Pings more often than the timeout, and nothing else
+    let mut pings = tokio::time::interval(Duration::from_millis(100));
+    let warning = loop {
+        tokio::select! {
+            _ = pings.tick() => {
+                ws.send(Message::Ping(vec![1])).await.expect("Should send ping");
+            }
+            msg = timeout(Duration::from_secs(5), ws.next()) => match msg {
+                Ok(Some(Ok(Message::Pong(_)))) => {}
+                Ok(Some(Ok(Message::Text(text)))) => break text,
+                other => panic!("Expected pongs and an idle warning, got {other:?}"),
+            },
+        }
+    };
+    assert!(warning.contains("idle"), "{warning}");
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_subscribe_primes_current_values() {
+    let (addr, event_tx, handle) =
+        start_test_server_with(|config| config.prime_on_subscribe = true).await;
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![
+                PathValue {
+                    path: "navigation.speedOverGround".to_string(),
+                    value: serde_json::json!(5.5),
+                },
+                PathValue {
+                    path: "navigation.headingTrue".to_string(),
+                    value: serde_json::json!(1.2),
+                },
+            ],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .expect("Should send delta");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut ws = connect_client_with_params(addr, "subscribe=none&sendCachedValues=false").await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+
+    // Subscribing sends the current value straight away
+    let subscribe = serde_json::json!({
+        "context": "vessels.self",
+        "subscribe": [{ "path": "navigation.speedOverGround" }]
+    });
+    ws.send(Message::Text(subscribe.to_string())).await.unwrap();
+    let primed: serde_json::Value =
+        serde_json::from_str(&recv_text(&mut ws).await.expect("Should receive value")).unwrap();
+    let values = primed["updates"][0]["values"].as_array().unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0]["path"], "navigation.speedOverGround");
+    assert_eq!(values[0]["value"], 5.5);
+    assert_eq!(primed["updates"][0]["$source"], "test.source");
+
+    // The subscribe message can opt out
+    let subscribe = serde_json::json!({
+        "context": "vessels.self",
+        "subscribe": [{ "path": "navigation.headingTrue" }],
+        "sendCachedValues": false
+    });
+    ws.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert!(timeout(Duration::from_millis(300), ws.next())
+        .await
+        .is_err());
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_lagged_client_resynced() {
+    let (addr, event_tx, handle) = start_test_server_with(|config| {
+        config.broadcast_capacity = 4;
+        config.lag_policy = LagPolicy::SendResync;
+    })
+    .await;
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Hello");
+
+    let delta = |path: &str, value: f64| Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: None,
+            values: vec![PathValue {
+                path: path.to_string(),
+                value: serde_json::json!(value),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta(
+            "environment.depth.belowKeel",
+            4.2,
+        )))
+        .await
+        .unwrap();
+    let _ = recv_text(&mut ws).await.expect("Depth");
+
+    // Far more deltas at once than the channel holds
+    let burst = (0..200)
+        .map(|i| delta("navigation.speedOverGround", f64::from(i)))
+        .collect();
+    event_tx.send(ServerEvent::DeltaBatch(burst)).await.unwrap();
+
+    // The resync carries the current value of every subscribed path,
+    // including the depth sent before the burst; the live stream then
+    // carries on to the end of the burst
+    let mut resynced = false;
+    loop {
+        let msg = recv_text(&mut ws).await.expect("Delta");
+        let delta: serde_json::Value = serde_json::from_str(&msg).unwrap();
+        let values: Vec<_> = delta["updates"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|update| update["values"].as_array().unwrap().clone())
+            .collect();
+        let value_of = |path: &str| {
+            values
+                .iter()
+                .find(|value| value["path"] == path)
+                .map(|value| value["value"].clone())
+        };
+        if let Some(depth) = value_of("environment.depth.belowKeel") {
+            assert_eq!(depth, 4.2);
+            resynced = true;
+        }
+        if value_of("navigation.speedOverGround") == Some(serde_json::json!(199.0)) {
+            break;
+        }
+    }
+    assert!(resynced);
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+/// Wait for a text message on a compressed connection.
+async fn recv_inflated(ws: &mut WebSocketStream<InflateStream<TcpStream>>) -> String {
+    match timeout(Duration::from_secs(5), ws.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => text,
+        other => panic!("expected a text message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_permessage_deflate() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    let (addr, event_tx, handle) = start_test_server_with(|config| {
+        config.ws_compression_threshold = Some(0);
+    })
+    .await;
+
+    // Handshake by hand, reading no further than the response head, so
+    // the first frames go through the InflateStream
+    let mut tcp = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET /signalk/v1/stream?subscribe=none HTTP/1.1\r\nHost: {addr}\r\n\
+         Upgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n\r\n"
+    );
+    tcp.write_all(request.as_bytes()).await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(tcp.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+    assert!(
+        head.to_ascii_lowercase()
+            .contains(&format!("sec-websocket-extensions: {DEFLATE_RESPONSE}")),
+        "{head}"
+    );
+    let mut ws =
+        WebSocketStream::from_raw_socket(InflateStream::new(tcp, true), Role::Client, None).await;
+    let mut deflater = MessageDeflater::new(0);
+
+    let hello: serde_json::Value = serde_json::from_str(&recv_inflated(&mut ws).await).unwrap();
+    assert_eq!(hello["capabilities"]["compression"], true);
+
+    // A compressed subscribe is understood
+    let subscribe = serde_json::json!({
+        "context": "vessels.self",
+        "subscribe": [{"path": "navigation.*"}]
+    });
+    ws.send(deflater.deflate(Message::Text(subscribe.to_string())))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(5.5),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .unwrap();
+
+    let received: serde_json::Value = serde_json::from_str(&recv_inflated(&mut ws).await).unwrap();
+    assert_eq!(
+        received["updates"][0]["values"][0]["path"],
+        "navigation.speedOverGround"
+    );
+
+    ws.close(None).await.ok();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_shutdown_closes_clients() {
+    let addr = find_available_port().await;
+    let server = SignalKServer::new(ServerConfig {
+        bind_addr: addr,
+        ..Default::default()
+    });
+    let event_tx = server.event_sender();
+    let shutdown = server.shutdown_handle();
+    let handle = tokio::spawn(server.run());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut ws = connect_client(addr).await;
+    let _ = recv_text(&mut ws).await.expect("Should receive Hello");
+
+    // Queued just before the shutdown, so it still reaches the client
+    let delta = Delta {
+        context: Some("vessels.self".to_string()),
+        updates: vec![Update {
+            source_ref: Some("test.source".to_string()),
+            source: None,
+            timestamp: Some("2024-01-17T12:00:00.000Z".to_string()),
+            values: vec![PathValue {
+                path: "navigation.speedOverGround".to_string(),
+                value: serde_json::json!(4.2),
+            }],
+            meta: None,
+            server_timestamp: None,
+        }],
+    };
+    event_tx
+        .send(ServerEvent::DeltaReceived(delta))
+        .await
+        .unwrap();
+    shutdown.shutdown();
+
+    let text = recv_text(&mut ws).await.expect("Should receive delta");
+    assert!(text.contains("navigation.speedOverGround"), "{text}");
+    match timeout(Duration::from_secs(5), ws.next()).await {
+        Ok(Some(Ok(Message::Close(Some(frame))))) => {
+            assert_eq!(frame.code, CloseCode::Away);
+        }
+        other => panic!("Expected a close frame, got {other:?}"),
+    }
+
+    timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("Server should stop")
+        .unwrap()
+        .unwrap();
+    // No longer accepting connections
+    assert!(TcpStream::connect(addr).await.is_err());
+}
@@ -0,0 +1,84 @@
+//! Shared handling of `/signalk/v1/api/<path>` query parameters.
+//!
+//! Servers look the node up in their store and pass it through
+//! [`select_leaf`], so `?source=` and `?meta=` behave the same everywhere.
+
+use axum::http::StatusCode;
+use serde_json::Value;
+
+/// Shape a leaf node for a REST path query.
+///
+/// With `source`, the node is replaced by that source's entry from its
+/// multi-source `values` map (`{value, $source, timestamp}`); a source that
+/// never reported the path is `404 Not Found`. With `include_meta`, the
+/// node's `meta` is added to the selected value. Without `source` the node
+/// is returned whole, meta included.
+pub fn select_leaf(
+    node: Value,
+    source: Option<&str>,
+    include_meta: bool,
+) -> Result<Value, StatusCode> {
+    let Some(source) = source else {
+        return Ok(node);
+    };
+    let mut selected = signalk_core::select_source(&node, source).ok_or(StatusCode::NOT_FOUND)?;
+    if include_meta {
+        if let (Some(meta), Some(selected)) = (node.get("meta"), selected.as_object_mut()) {
+            selected.insert("meta".to_string(), meta.clone());
+        }
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn leaf() -> Value {
+        json!({
+            "value": 3.85,
+            "$source": "gps1",
+            "timestamp": "2024-01-17T10:00:00.000Z",
+            "values": {
+                "gps1": { "value": 3.85, "timestamp": "2024-01-17T10:00:00.000Z" },
+                "gps2": { "value": 3.90, "timestamp": "2024-01-17T10:00:01.000Z" },
+            },
+            "meta": { "units": "m/s" },
+        })
+    }
+
+    #[test]
+    fn test_select_source_from_multi_source_leaf() {
+        assert_eq!(
+            select_leaf(leaf(), Some("gps2"), false).unwrap(),
+            json!({
+                "value": 3.90,
+                "$source": "gps2",
+                "timestamp": "2024-01-17T10:00:01.000Z",
+            })
+        );
+        let with_meta = select_leaf(leaf(), Some("gps1"), true).unwrap();
+        assert_eq!(with_meta["value"], 3.85);
+        assert_eq!(with_meta["meta"]["units"], "m/s");
+    }
+
+    #[test]
+    fn test_missing_source_is_not_found() {
+        assert_eq!(
+            select_leaf(leaf(), Some("ais"), false),
+            Err(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(select_leaf(leaf(), None, false).unwrap(), leaf());
+    }
+
+    #[test]
+    fn test_single_source_leaf_without_values_map() {
+        let node =
+            json!({ "value": 1.5, "$source": "nmea0183.GP", "timestamp": "2024-01-17T10:00:00Z" });
+        assert_eq!(
+            select_leaf(node.clone(), Some("nmea0183.GP"), false).unwrap(),
+            node
+        );
+    }
+}
@@ -0,0 +1,19 @@
+//! Debug keys: extra logging switched on and off at runtime.
+//!
+//! The Admin UI lists the keys from `GET /skServer/debugKeys` and toggles
+//! them with `POST /skServer/debug`. What a key turns on is up to the
+//! server, typically more verbose `tracing` targets through a reloadable
+//! filter, so the web layer only sees a [`DebugControl`].
+
+/// Turns debug keys on and off.
+pub trait DebugControl: Send + Sync {
+    /// Every key that can be enabled.
+    fn keys(&self) -> Vec<String>;
+
+    /// The keys currently enabled.
+    fn enabled(&self) -> Vec<String>;
+
+    /// Enable exactly `keys`, all of them from [`keys`](Self::keys), and
+    /// disable the rest.
+    fn set_enabled(&self, keys: &[String]) -> Result<(), String>;
+}
@@ -0,0 +1,323 @@
+//! Historical values for dashboards.
+//!
+//! The delta processor feeds numeric values into a [`HistoryStore`], and
+//! `GET /signalk/v1/history/values` reads them back in the Signal K history
+//! format (as served by signalk-to-influxdb and friends): one
+//! [`HistoryValues`] per context, with a row of averaged values per
+//! resolution bucket.
+//!
+//! [`InMemoryHistory`] keeps a fixed number of samples per path in memory;
+//! a server backed by a time-series database can implement the trait
+//! instead.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::Serialize;
+use signalk_core::Delta;
+
+/// Default number of samples [`InMemoryHistory`] keeps per path: an hour
+/// at 1 Hz.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 3600;
+
+/// A request for the history of some paths in one context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryQuery {
+    /// Context to read, such as `vessels.self`.
+    pub context: String,
+    /// Context-relative paths, one column each.
+    pub paths: Vec<String>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Width of the buckets values are averaged over.
+    pub resolution: Duration,
+}
+
+/// Time range of a [`HistoryValues`] response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryRange {
+    pub from: String,
+    pub to: String,
+}
+
+/// A column of a [`HistoryValues`] response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryPath {
+    pub path: String,
+    /// How samples within a bucket were combined.
+    pub method: String,
+}
+
+/// The history of one context, in the Signal K history format.
+///
+/// Each row of `data` is `[timestamp, v1, v2, ...]` with one value per
+/// entry of `values`, `null` where a path has no samples in that bucket.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryValues {
+    pub context: String,
+    pub range: HistoryRange,
+    pub values: Vec<HistoryPath>,
+    pub data: Vec<Vec<serde_json::Value>>,
+}
+
+/// Storage for numeric path history.
+pub trait HistoryStore: Send + Sync {
+    /// Record one sample of `path` in `context`.
+    fn record(&self, context: &str, path: &str, timestamp: DateTime<Utc>, value: f64);
+
+    /// Read the samples matching `query`, down-sampled to its resolution.
+    fn query(&self, query: &HistoryQuery) -> HistoryValues;
+
+    /// Record the numeric values of a delta.
+    ///
+    /// The self vessel is stored as `vessels.self` whether the delta names
+    /// it that way or by `self_urn`. Updates without a timestamp are
+    /// recorded at the current time; non-numeric values are skipped.
+    fn record_delta(&self, delta: &Delta, self_urn: &str) {
+        let context = self_context(delta.context.as_deref().unwrap_or("vessels.self"), self_urn);
+        for update in &delta.updates {
+            let timestamp = update
+                .timestamp
+                .as_deref()
+                .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                .map_or_else(Utc::now, |ts| ts.with_timezone(&Utc));
+            for pv in &update.values {
+                if let Some(value) = pv.value.as_f64() {
+                    self.record(context, &pv.path, timestamp, value);
+                }
+            }
+        }
+    }
+}
+
+/// `context`, with the self vessel's URN replaced by `vessels.self`.
+pub fn self_context<'a>(context: &'a str, self_urn: &str) -> &'a str {
+    if context == self_urn {
+        "vessels.self"
+    } else {
+        context
+    }
+}
+
+/// Samples of one path, oldest first.
+type Series = VecDeque<(DateTime<Utc>, f64)>;
+
+/// A [`HistoryStore`] keeping the latest samples of each path in memory.
+pub struct InMemoryHistory {
+    capacity: usize,
+    /// Samples per (context, path).
+    series: Mutex<HashMap<(String, String), Series>>,
+}
+
+impl InMemoryHistory {
+    /// Keep up to `capacity` samples per path, dropping the oldest.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            series: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for InMemoryHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl HistoryStore for InMemoryHistory {
+    fn record(&self, context: &str, path: &str, timestamp: DateTime<Utc>, value: f64) {
+        if self.capacity == 0 || !value.is_finite() {
+            return;
+        }
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let samples = series
+            .entry((context.to_string(), path.to_string()))
+            .or_default();
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back((timestamp, value));
+    }
+
+    fn query(&self, query: &HistoryQuery) -> HistoryValues {
+        let resolution_ms = query.resolution.num_milliseconds().max(1);
+        // Bucket index -> (sum, count) per path
+        let mut buckets: BTreeMap<i64, Vec<(f64, usize)>> = BTreeMap::new();
+        {
+            let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+            for (column, path) in query.paths.iter().enumerate() {
+                let Some(samples) = series.get(&(query.context.clone(), path.clone())) else {
+                    continue;
+                };
+                for &(timestamp, value) in samples {
+                    if timestamp < query.from || timestamp > query.to {
+                        continue;
+                    }
+                    let bucket = (timestamp - query.from).num_milliseconds() / resolution_ms;
+                    let sums = buckets
+                        .entry(bucket)
+                        .or_insert_with(|| vec![(0.0, 0); query.paths.len()]);
+                    sums[column].0 += value;
+                    sums[column].1 += 1;
+                }
+            }
+        }
+
+        let data = buckets
+            .into_iter()
+            .map(|(bucket, sums)| {
+                let start = query.from + Duration::milliseconds(bucket * resolution_ms);
+                std::iter::once(serde_json::Value::String(format_time(start)))
+                    .chain(sums.into_iter().map(|(sum, count)| {
+                        if count == 0 {
+                            serde_json::Value::Null
+                        } else {
+                            serde_json::json!(sum / count as f64)
+                        }
+                    }))
+                    .collect()
+            })
+            .collect();
+
+        HistoryValues {
+            context: query.context.clone(),
+            range: HistoryRange {
+                from: format_time(query.from),
+                to: format_time(query.to),
+            },
+            values: query
+                .paths
+                .iter()
+                .map(|path| HistoryPath {
+                    path: path.clone(),
+                    method: "average".to_string(),
+                })
+                .collect(),
+            data,
+        }
+    }
+}
+
+/// RFC 3339 with milliseconds, as in delta timestamps.
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use signalk_core::{PathValue, Update};
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn query(paths: &[&str], resolution_secs: i64) -> HistoryQuery {
+        HistoryQuery {
+            context: "vessels.self".to_string(),
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            from: at("2024-01-17T10:00:00Z"),
+            to: at("2024-01-17T10:01:00Z"),
+            resolution: Duration::seconds(resolution_secs),
+        }
+    }
+
+    #[test]
+    fn test_samples_averaged_per_bucket() {
+        let history = InMemoryHistory::default();
+        let sog = "navigation.speedOverGround";
+        let depth = "environment.depth.belowTransducer";
+        for (time, value) in [
+            ("2024-01-17T10:00:01Z", 3.0),
+            ("2024-01-17T10:00:05Z", 4.0),
+            ("2024-01-17T10:00:12Z", 5.0),
+            ("2024-01-17T10:00:31Z", 6.0),
+            // Outside the range
+            ("2024-01-17T09:59:59Z", 100.0),
+            ("2024-01-17T10:02:00Z", 100.0),
+        ] {
+            history.record("vessels.self", sog, at(time), value);
+        }
+        history.record("vessels.self", depth, at("2024-01-17T10:00:14Z"), 12.5);
+        history.record("vessels.other", sog, at("2024-01-17T10:00:02Z"), 9.0);
+
+        let values = history.query(&query(&[sog, depth], 10));
+        assert_eq!(values.context, "vessels.self");
+        assert_eq!(values.range.from, "2024-01-17T10:00:00.000Z");
+        assert_eq!(values.values[1].path, depth);
+        assert_eq!(
+            values.data,
+            vec![
+                vec![json!("2024-01-17T10:00:00.000Z"), json!(3.5), json!(null)],
+                vec![json!("2024-01-17T10:00:10.000Z"), json!(5.0), json!(12.5)],
+                vec![json!("2024-01-17T10:00:30.000Z"), json!(6.0), json!(null)],
+            ]
+        );
+
+        let json = serde_json::to_value(&values).unwrap();
+        assert_eq!(
+            json["values"][0],
+            json!({ "path": sog, "method": "average" })
+        );
+    }
+
+    #[test]
+    fn test_ring_buffer_keeps_latest() {
+        let history = InMemoryHistory::new(2);
+        let sog = "navigation.speedOverGround";
+        for (second, value) in [(1, 1.0), (2, 2.0), (3, 3.0)] {
+            let time = at("2024-01-17T10:00:00Z") + Duration::seconds(second);
+            history.record("vessels.self", sog, time, value);
+        }
+        let values = history.query(&query(&[sog], 1));
+        let sogs: Vec<_> = values.data.iter().map(|row| row[1].clone()).collect();
+        assert_eq!(sogs, [json!(2.0), json!(3.0)]);
+    }
+
+    #[test]
+    fn test_record_delta() {
+        let self_urn = "vessels.urn:mrn:signalk:uuid:test";
+        let history = InMemoryHistory::default();
+        history.record_delta(
+            &Delta {
+                context: Some(self_urn.to_string()),
+                updates: vec![Update {
+                    source_ref: Some("gps".to_string()),
+                    source: None,
+                    timestamp: Some("2024-01-17T10:00:20.000Z".to_string()),
+                    values: vec![
+                        PathValue {
+                            path: "navigation.speedOverGround".to_string(),
+                            value: json!(3.85),
+                        },
+                        PathValue {
+                            path: "navigation.position".to_string(),
+                            value: json!({ "latitude": 52.0, "longitude": 4.0 }),
+                        },
+                    ],
+                    meta: None,
+                    server_timestamp: None,
+                }],
+            },
+            self_urn,
+        );
+
+        let values = history.query(&query(
+            &["navigation.speedOverGround", "navigation.position"],
+            60,
+        ));
+        assert_eq!(
+            values.data,
+            vec![vec![
+                json!("2024-01-17T10:00:00.000Z"),
+                json!(3.85),
+                json!(null)
+            ]]
+        );
+    }
+}
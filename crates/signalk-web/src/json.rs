@@ -0,0 +1,76 @@
+//! JSON responses with optional pretty-printing.
+//!
+//! REST handlers accept `?pretty=true` to return indented JSON, which is
+//! easier to read when poking at the API from a browser. Output is compact
+//! by default.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+/// A JSON response body, compact or indented.
+#[derive(Debug, Clone)]
+pub struct ApiJson<T> {
+    pub value: T,
+    pub pretty: bool,
+}
+
+impl<T> ApiJson<T> {
+    /// Compact output unless `pretty` is set.
+    pub fn new(value: T, pretty: bool) -> Self {
+        Self { value, pretty }
+    }
+}
+
+impl<T: Serialize> IntoResponse for ApiJson<T> {
+    fn into_response(self) -> Response {
+        let body = if self.pretty {
+            serde_json::to_string_pretty(&self.value)
+        } else {
+            serde_json::to_string(&self.value)
+        };
+
+        match body {
+            Ok(body) => ([(header::CONTENT_TYPE, "application/json")], body).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn body_of(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pretty_output_matches_compact() {
+        let value = json!({
+            "navigation": {
+                "speedOverGround": { "value": 3.85 }
+            }
+        });
+
+        let compact = body_of(ApiJson::new(&value, false).into_response()).await;
+        let pretty = body_of(ApiJson::new(&value, true).into_response()).await;
+
+        assert!(!compact.contains('\n'));
+        assert!(pretty.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+            value
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&compact).unwrap(),
+            value
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Token-based authentication.
+//!
+//! Users log in with a username and password checked against the argon2
+//! hashes in [`SecurityConfig::users`], and receive a signed JWT (HS256)
+//! that expires after [`SecurityConfig::expiration`]. [`TokenKeys::validate_token`]
+//! checks a token presented later, from a route or a middleware.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use signalk_core::{SecurityConfig, UserRecord};
+use std::time::Duration;
+
+/// Token lifetime when `expiration` is unset or unparseable.
+pub const DEFAULT_EXPIRATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Claims carried by an issued token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user's id.
+    pub id: String,
+    /// Issued at, in seconds since the epoch.
+    pub iat: i64,
+    /// Expiry, in seconds since the epoch.
+    pub exp: i64,
+}
+
+/// Keys for signing and validating tokens.
+pub struct TokenKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl TokenKeys {
+    /// Keys from a shared secret.
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    /// Keys from a random secret; tokens don't survive a restart.
+    pub fn generate() -> Self {
+        let secret: Vec<u8> = (0..2)
+            .flat_map(|_| *uuid::Uuid::new_v4().as_bytes())
+            .collect();
+        Self::new(&secret)
+    }
+
+    /// Issue a token for `user_id`, valid for `ttl` from `now`.
+    pub fn issue(
+        &self,
+        user_id: &str,
+        ttl: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let iat = now.timestamp();
+        let claims = Claims {
+            id: user_id.to_string(),
+            iat,
+            exp: iat.saturating_add(i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX)),
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+    }
+
+    /// The claims of a token signed with these keys, unless it is invalid or
+    /// has expired.
+    pub fn validate_token(&self, token: &str) -> Option<Claims> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        jsonwebtoken::decode::<Claims>(token, &self.decoding, &validation)
+            .ok()
+            .map(|data| data.claims)
+    }
+}
+
+/// Hash a password for storage in [`UserRecord::password_hash`].
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes())?;
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// Check a password against a hash from [`hash_password`].
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// The user with these credentials, if any.
+pub fn authenticate<'a>(
+    security: &'a SecurityConfig,
+    username: &str,
+    password: &str,
+) -> Option<&'a UserRecord> {
+    security
+        .users
+        .iter()
+        .flatten()
+        .find(|user| user.user_id == username)
+        .filter(|user| {
+            user.password_hash
+                .as_deref()
+                .is_some_and(|hash| verify_password(password, hash))
+        })
+}
+
+/// Parse a token lifetime such as `"1d"`, `"12h"`, `"30m"` or `"90s"`; a
+/// bare number is seconds.
+pub fn parse_expiration(expiration: &str) -> Option<Duration> {
+    let expiration = expiration.trim();
+    let (amount, unit) = match expiration.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => expiration.split_at(split),
+        None => (expiration, "s"),
+    };
+    let amount: u64 = amount.parse().ok()?;
+    let unit_secs = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    amount.checked_mul(unit_secs).map(Duration::from_secs)
+}
+
+/// The lifetime of tokens issued under `security`.
+pub fn token_lifetime(security: &SecurityConfig) -> Duration {
+    security
+        .expiration
+        .as_deref()
+        .and_then(parse_expiration)
+        .unwrap_or(DEFAULT_EXPIRATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expiration() {
+        assert_eq!(parse_expiration("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_expiration("12h"), Some(Duration::from_secs(43200)));
+        assert_eq!(parse_expiration("30m"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_expiration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_expiration("1y"), None);
+        assert_eq!(parse_expiration("d"), None);
+    }
+
+    #[test]
+    fn test_password_hash_round_trip() {
+        let hash = hash_password("secret").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("secret", &hash));
+        assert!(!verify_password("wrong", &hash));
+        assert!(!verify_password("secret", "not a hash"));
+    }
+
+    #[test]
+    fn test_token_round_trip() {
+        let keys = TokenKeys::generate();
+        let token = keys
+            .issue("admin", Duration::from_secs(60), Utc::now())
+            .unwrap();
+
+        let claims = keys.validate_token(&token).unwrap();
+        assert_eq!(claims.id, "admin");
+        assert_eq!(claims.exp - claims.iat, 60);
+
+        // Signed with another secret
+        assert!(TokenKeys::generate().validate_token(&token).is_none());
+        assert!(keys.validate_token("garbage").is_none());
+    }
+
+    #[test]
+    fn test_expired_token_rejected() {
+        let keys = TokenKeys::generate();
+        let issued = Utc::now() - chrono::Duration::hours(2);
+        let token = keys
+            .issue("admin", Duration::from_secs(3600), issued)
+            .unwrap();
+        assert!(keys.validate_token(&token).is_none());
+    }
+}
@@ -6,7 +6,19 @@
 //! - REST API endpoints compatible with the TypeScript Signal K server
 //! - WebSocket server events for real-time dashboard updates
 //! - Static file serving for the Admin UI
-//! - Server statistics collection and broadcasting
+//! - Server statistics collection and broadcasting, and the list of
+//!   connected WebSocket clients
+//! - JSON responses with optional `?pretty=true` output
+//! - `?source=` and `?meta=` selection on REST path queries
+//! - Path history for dashboards (`/signalk/v1/history/values`)
+//! - mDNS advertisement of the HTTP and WebSocket services
+//! - JWT login against the users in the security config
+//! - Plugin listing, configuration and enable/disable via a
+//!   [`PluginManager`]
+//! - Read/write/admin permission checks on every route
+//! - PUT over REST, decided like a WebSocket PUT
+//! - Debug keys toggled at runtime through a [`DebugControl`]
+//! - The Signal K v2 course and resources (routes, waypoints) APIs
 //!
 //! ## Architecture
 //!
@@ -14,6 +26,7 @@
 //!
 //! - `/admin/` - Static files for React Admin UI
 //! - `/signalk/v1/` - Signal K REST API and WebSocket
+//! - `/signalk/v2/api/` - Signal K v2 course and resources APIs
 //! - `/skServer/` - Server management endpoints
 //!
 //! ## Usage
@@ -25,19 +38,39 @@
 //! let routes = create_web_routes();
 //! ```
 
+pub mod api;
+pub mod debug;
+pub mod history;
+pub mod json;
+pub mod jwt;
+pub mod mdns;
+pub mod permissions;
 pub mod routes;
 pub mod server_events;
 pub mod statistics;
 
 // Re-exports
-pub use routes::create_router;
+pub use api::select_leaf;
+pub use debug::DebugControl;
+pub use history::{HistoryQuery, HistoryStore, HistoryValues, InMemoryHistory};
+pub use json::ApiJson;
+pub use jwt::{Claims, TokenKeys};
+pub use mdns::{MdnsAdvertiser, MdnsError};
+pub use permissions::{enforce_permissions, ResolvedPermission};
+pub use routes::put::{put_request, put_self_path, request_status, PendingPuts, PutBody};
+pub use routes::v2::resources::{ResourceStore, ResourceType, Resources};
+pub use routes::{create_router, discovery_for_headers};
 pub use server_events::{
-    DebugSettings, LogEntry, LoginStatus, ProviderStatus, ServerEvent, ServerStatistics,
-    SourcePriorities, VesselInfoData,
+    ClientConnection, DebugSettings, LogEntry, LoginStatus, ProviderStatus, RateStatistics,
+    ServerEvent, ServerStatistics, SourcePriorities, VesselInfoData,
 };
-pub use statistics::StatisticsCollector;
+pub use statistics::{ClientHandle, StatisticsCollector};
 
-use signalk_core::{MemoryStore, ServerSettings, VesselInfo};
+use signalk_core::{
+    Delta, DeltaSink, MemoryConfigStorage, SecurityConfig, ServerSettings, SharedStore,
+    SignalKStore, VesselInfo,
+};
+use signalk_plugins::PluginManager;
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
 
@@ -66,7 +99,7 @@ impl Default for WebConfig {
 /// This is wrapped in Arc and shared across all Axum handlers.
 pub struct WebState {
     /// Reference to the SignalK data store.
-    pub store: Arc<RwLock<MemoryStore>>,
+    pub store: Arc<SharedStore>,
 
     /// Broadcast channel for server events (statistics, logs).
     pub server_events_tx: broadcast::Sender<ServerEvent>,
@@ -82,11 +115,37 @@ pub struct WebState {
 
     /// Server settings (cached).
     pub settings: RwLock<ServerSettings>,
+
+    /// Security configuration: users, token expiration, ACLs.
+    pub security: RwLock<SecurityConfig>,
+
+    /// Keys that sign and validate login tokens.
+    pub tokens: TokenKeys,
+
+    /// Numeric path history, fed by the delta processor.
+    pub history: Arc<dyn HistoryStore>,
+
+    /// Installed plugins; none unless the server sets a manager up.
+    pub plugins: Arc<PluginManager>,
+
+    /// Routes and waypoints of the v2 resources API; kept in memory unless
+    /// the server sets up persistent storage.
+    pub resources: Resources,
+
+    /// Where deltas made by the web layer (such as course changes) are
+    /// submitted for broadcast; they reach only the store if unset.
+    pub delta_sink: Option<Arc<dyn DeltaSink>>,
+
+    /// REST PUTs still waiting on their device.
+    pub pending_puts: PendingPuts,
+
+    /// Switches the debug keys; they can't be toggled if unset.
+    pub debug: Option<Arc<dyn DebugControl>>,
 }
 
 impl WebState {
     /// Create new server state.
-    pub fn new(store: Arc<RwLock<MemoryStore>>, config: WebConfig) -> Self {
+    pub fn new(store: Arc<SharedStore>, config: WebConfig) -> Self {
         let (server_events_tx, _) = broadcast::channel(256);
 
         Self {
@@ -99,6 +158,30 @@ impl WebState {
                 ..Default::default()
             }),
             settings: RwLock::new(ServerSettings::default()),
+            security: RwLock::new(SecurityConfig::default()),
+            tokens: TokenKeys::generate(),
+            history: Arc::new(InMemoryHistory::default()),
+            plugins: Arc::new(PluginManager::default()),
+            resources: Resources::new(Arc::new(MemoryConfigStorage::default())),
+            delta_sink: None,
+            pending_puts: PendingPuts::default(),
+            debug: None,
+        }
+    }
+
+    /// Apply a delta made by the web layer to the store, so it reads back
+    /// at once, and submit it to the [`delta_sink`](Self::delta_sink) to
+    /// reach stream clients.
+    pub async fn apply_delta(&self, delta: Delta) {
+        self.store.write().apply_delta(&delta);
+        self.publish_delta(delta);
+    }
+
+    /// Submit a delta already in the store to the
+    /// [`delta_sink`](Self::delta_sink).
+    pub fn publish_delta(&self, delta: Delta) {
+        if let Some(sink) = &self.delta_sink {
+            sink.submit(delta);
         }
     }
 
@@ -0,0 +1,162 @@
+//! mDNS service advertisement.
+//!
+//! Advertises the SignalK HTTP and WebSocket endpoints so apps (such as the
+//! iOS and Android SignalK apps) can find the server without knowing its IP:
+//! - `_signalk-http._tcp` - REST API and discovery
+//! - `_signalk-ws._tcp` - WebSocket delta stream
+//! - `_signalk-https._tcp` / `_signalk-wss._tcp` - the same over TLS, when
+//!   the server has an HTTPS listener
+//!
+//! The TXT records match the ESP32 advertiser's, so clients see the same
+//! records whichever server they find.
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use tracing::{info, warn};
+
+pub use mdns_sd::Error as MdnsError;
+
+/// Default mDNS hostname (resolves as `signalk.local`).
+pub const DEFAULT_MDNS_HOSTNAME: &str = "signalk";
+
+/// Service types advertised, in `ServiceDaemon` notation.
+pub const SERVICE_TYPES: [&str; 2] = ["_signalk-http._tcp.local.", "_signalk-ws._tcp.local."];
+
+/// Service types advertised on the HTTPS port.
+pub const TLS_SERVICE_TYPES: [&str; 2] = ["_signalk-https._tcp.local.", "_signalk-wss._tcp.local."];
+
+/// A running mDNS advertisement.
+///
+/// The services are announced until [`MdnsAdvertiser::shutdown`] is called or
+/// the advertiser is dropped, which sends goodbye packets so clients forget
+/// the server right away.
+pub struct MdnsAdvertiser {
+    daemon: ServiceDaemon,
+    fullnames: Vec<String>,
+}
+
+impl MdnsAdvertiser {
+    /// Start advertising the SignalK services on every interface.
+    ///
+    /// The TXT records follow the SignalK discovery conventions (`txtvers`,
+    /// `swname`, `swvers`, `roles`, `self`); `self` carries the vessel URN
+    /// without the `vessels.` prefix. With a `tls_port`, the secure services
+    /// are advertised on it as well.
+    pub fn advertise(
+        hostname: &str,
+        instance_name: &str,
+        port: u16,
+        tls_port: Option<u16>,
+        version: &str,
+        self_urn: &str,
+    ) -> Result<Self, MdnsError> {
+        let daemon = ServiceDaemon::new()?;
+        let host = format!("{hostname}.local.");
+        let urn = self_urn.strip_prefix("vessels.").unwrap_or(self_urn);
+        let txt = [
+            ("txtvers", "1"),
+            ("swname", env!("CARGO_PKG_NAME")),
+            ("swvers", version),
+            ("roles", "master,main"),
+            ("self", urn),
+        ];
+
+        let mut advertiser = Self {
+            daemon,
+            fullnames: Vec::new(),
+        };
+        let services = SERVICE_TYPES.map(|service_type| (service_type, port));
+        let tls_services = tls_port.map(|tls_port| TLS_SERVICE_TYPES.map(|t| (t, tls_port)));
+        for (service_type, port) in services
+            .into_iter()
+            .chain(tls_services.into_iter().flatten())
+        {
+            let service = ServiceInfo::new(service_type, instance_name, &host, "", port, &txt[..])?
+                .enable_addr_auto();
+            advertiser
+                .fullnames
+                .push(service.get_fullname().to_string());
+            advertiser.daemon.register(service)?;
+        }
+
+        info!(
+            "mDNS advertising {}.local (_signalk-http/_signalk-ws on port {})",
+            hostname, port
+        );
+        if let Some(tls_port) = tls_port {
+            info!(
+                "mDNS advertising {}.local (_signalk-https/_signalk-wss on port {})",
+                hostname, tls_port
+            );
+        }
+        Ok(advertiser)
+    }
+
+    /// Withdraw the services and stop the mDNS daemon.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        for fullname in self.fullnames.drain(..) {
+            if let Err(e) = self.daemon.unregister(&fullname) {
+                warn!("Could not withdraw mDNS service {}: {}", fullname, e);
+            }
+        }
+        // Already stopped if the daemon shut down on its own
+        let _ = self.daemon.shutdown();
+    }
+}
+
+impl Drop for MdnsAdvertiser {
+    fn drop(&mut self) {
+        if !self.fullnames.is_empty() {
+            self.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdns_sd::ServiceEvent;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_advertise_and_resolve() {
+        let advertiser = MdnsAdvertiser::advertise(
+            "signalk-test",
+            "Test Vessel",
+            3999,
+            None,
+            "1.7.0",
+            "vessels.urn:mrn:signalk:uuid:test-vessel",
+        )
+        .expect("start advertiser");
+
+        let browser = ServiceDaemon::new().expect("start browser");
+        let events = browser.browse(SERVICE_TYPES[0]).expect("browse");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let resolved = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match events.recv_timeout(remaining) {
+                Ok(ServiceEvent::ServiceResolved(service))
+                    if service.get_fullname().starts_with("Test Vessel.") =>
+                {
+                    break service;
+                }
+                Ok(_) => continue,
+                Err(e) => panic!("service not resolved: {e}"),
+            }
+        };
+
+        assert_eq!(resolved.get_port(), 3999);
+        assert_eq!(resolved.get_property_val_str("txtvers"), Some("1"));
+        assert_eq!(
+            resolved.get_property_val_str("self"),
+            Some("urn:mrn:signalk:uuid:test-vessel")
+        );
+
+        let _ = browser.shutdown();
+        advertiser.shutdown();
+    }
+}
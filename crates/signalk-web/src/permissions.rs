@@ -0,0 +1,305 @@
+//! Permission enforcement for the REST routes.
+//!
+//! [`enforce_permissions`] resolves the permission of each request from its
+//! login token (see [`request_token`]) and the user it names, and refuses
+//! requests the permission doesn't cover:
+//!
+//! | Request | Needs |
+//! |---------|-------|
+//! | `GET`, `HEAD`, `OPTIONS` | `readonly`, or no token with `allowReadOnly` |
+//! | other methods on `/skServer/*` | `admin` |
+//! | other methods elsewhere | `readwrite` |
+//!
+//! Requests without a valid token get `401 Unauthorized`, those with too
+//! low a permission `403 Forbidden`. Login, logout, device access requests,
+//! discovery and the Admin UI's static files stay public. Until a user exists (see `enableSecurity`)
+//! security is off and every request is treated as `admin`.
+//!
+//! The resolved permission is attached to the request as a
+//! [`ResolvedPermission`] extension, so handlers can restrict further; a
+//! WebSocket handler can check it against each PUT it receives, since the
+//! stream upgrade itself is a `GET`.
+
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use signalk_core::{Permission, SecurityConfig};
+
+use crate::routes::auth::request_token;
+use crate::AppState;
+
+/// Paths anyone may use, logged in or not; `/metrics` stays open for
+/// Prometheus scrapers.
+const PUBLIC_PATHS: [&str; 7] = [
+    "/signalk",
+    "/signalk/v1/auth/login",
+    "/signalk/v1/auth/logout",
+    "/signalk/v1/access/requests",
+    "/skServer/loginStatus",
+    "/skServer/enableSecurity",
+    "/metrics",
+];
+
+/// Path prefixes anyone may use: request polling and the static Admin UI
+/// and documentation, which the login page is part of.
+const PUBLIC_PREFIXES: [&str; 3] = ["/signalk/v1/requests/", "/admin/", "/documentation/"];
+
+/// Permission level a request was granted, in its extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedPermission(pub Permission);
+
+/// The permission of a user `type` (`admin`, `readwrite`, `readonly`);
+/// unknown types are read-only.
+pub fn user_permission(user_type: &str) -> Permission {
+    match user_type {
+        "admin" => Permission::Admin,
+        "readwrite" => Permission::ReadWrite,
+        _ => Permission::ReadOnly,
+    }
+}
+
+/// The permission a request needs, `None` for public paths.
+pub fn required_permission(method: &Method, path: &str) -> Option<Permission> {
+    let public = matches!(path, "/" | "/admin")
+        || PUBLIC_PATHS.contains(&path)
+        || PUBLIC_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix));
+    if public {
+        None
+    } else if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        Some(Permission::ReadOnly)
+    } else if path.starts_with("/skServer/") {
+        Some(Permission::Admin)
+    } else {
+        Some(Permission::ReadWrite)
+    }
+}
+
+/// Resolve the permission of a request carrying `token`.
+///
+/// `Ok(None)` is an anonymous request; a token that is invalid, expired or
+/// names an unknown user is an error.
+fn resolve(
+    state: &AppState,
+    security: &SecurityConfig,
+    token: Option<&str>,
+) -> Result<Option<Permission>, StatusCode> {
+    let Some(token) = token else {
+        return Ok(None);
+    };
+    let claims = state
+        .tokens
+        .validate_token(token)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    security
+        .users
+        .iter()
+        .flatten()
+        .find(|user| user.user_id == claims.id)
+        .map(|user| Some(user_permission(&user.user_type)))
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// Middleware refusing requests the caller's permission doesn't cover.
+///
+/// Use with `axum::middleware::from_fn_with_state`.
+pub async fn enforce_permissions(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let granted = {
+        let security = state.security.read().await;
+        let secured = security
+            .users
+            .as_ref()
+            .is_some_and(|users| !users.is_empty());
+        let required = required_permission(request.method(), request.uri().path());
+        let resolved = resolve(&state, &security, request_token(request.headers()));
+
+        match (secured, required, resolved) {
+            (false, _, _) => Some(Permission::Admin),
+            // Public paths ignore bad tokens
+            (true, None, resolved) => resolved.ok().flatten(),
+            (true, Some(_), Err(status)) => return status.into_response(),
+            (true, Some(required), Ok(permission)) => {
+                let anonymous_read =
+                    required == Permission::ReadOnly && security.allow_read_only.unwrap_or(false);
+                let permission = match permission {
+                    Some(permission) => permission,
+                    None if anonymous_read => Permission::ReadOnly,
+                    None => return StatusCode::UNAUTHORIZED.into_response(),
+                };
+                if permission < required {
+                    return StatusCode::FORBIDDEN.into_response();
+                }
+                Some(permission)
+            }
+        }
+    };
+
+    if let Some(permission) = granted {
+        request
+            .extensions_mut()
+            .insert(ResolvedPermission(permission));
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwt::hash_password;
+    use crate::routes::create_router;
+    use crate::{WebConfig, WebState};
+    use axum::body::Body;
+    use axum::http::header;
+    use signalk_core::{MemoryStore, SharedStore, UserRecord};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn secured_state(allow_read_only: bool) -> AppState {
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let state = Arc::new(WebState::new(store, WebConfig::default()));
+        let user = |id: &str, user_type: &str| UserRecord {
+            user_id: id.to_string(),
+            user_type: user_type.to_string(),
+            password_hash: Some(hash_password("secret").unwrap()),
+        };
+        *state.security.write().await = SecurityConfig {
+            allow_read_only: Some(allow_read_only),
+            users: Some(vec![user("admin", "admin"), user("guest", "readonly")]),
+            ..Default::default()
+        };
+        state
+    }
+
+    fn token(state: &AppState, user_id: &str) -> String {
+        state
+            .tokens
+            .issue(user_id, Duration::from_secs(60), chrono::Utc::now())
+            .unwrap()
+    }
+
+    async fn send(state: &AppState, method: Method, path: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = request
+            .body(Body::from(r#"{"name": "Albatross"}"#))
+            .unwrap();
+        create_router(state.clone())
+            .oneshot(request)
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_protected_put() {
+        let state = secured_state(false).await;
+        let admin = token(&state, "admin");
+        let guest = token(&state, "guest");
+
+        assert_eq!(
+            send(&state, Method::PUT, "/skServer/vessel", Some(&admin)).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&state, Method::PUT, "/skServer/vessel", Some(&guest)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send(&state, Method::PUT, "/skServer/vessel", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send(&state, Method::PUT, "/skServer/vessel", Some("garbage")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            state.vessel_info.read().await.name.as_deref(),
+            Some("Albatross")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_reads() {
+        let state = secured_state(false).await;
+        assert_eq!(
+            send(&state, Method::GET, "/skServer/vessel", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let guest = token(&state, "guest");
+        assert_eq!(
+            send(&state, Method::GET, "/skServer/vessel", Some(&guest)).await,
+            StatusCode::OK
+        );
+        // Public whatever the token
+        assert_eq!(
+            send(
+                &state,
+                Method::GET,
+                "/skServer/loginStatus",
+                Some("garbage")
+            )
+            .await,
+            StatusCode::OK
+        );
+
+        let state = secured_state(true).await;
+        assert_eq!(
+            send(&state, Method::GET, "/skServer/vessel", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&state, Method::PUT, "/skServer/vessel", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_security_off_without_users() {
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let state = Arc::new(WebState::new(store, WebConfig::default()));
+        assert_eq!(
+            send(&state, Method::PUT, "/skServer/vessel", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn test_required_permission() {
+        assert_eq!(
+            required_permission(&Method::POST, "/signalk/v1/auth/login"),
+            None
+        );
+        assert_eq!(
+            required_permission(&Method::GET, "/signalk/v1/requests/abc"),
+            None
+        );
+        assert_eq!(required_permission(&Method::GET, "/admin/index.html"), None);
+        assert_eq!(required_permission(&Method::GET, "/metrics"), None);
+        assert_eq!(
+            required_permission(&Method::GET, "/skServer/settings"),
+            Some(Permission::ReadOnly)
+        );
+        assert_eq!(
+            required_permission(&Method::PUT, "/skServer/settings"),
+            Some(Permission::Admin)
+        );
+        assert_eq!(
+            required_permission(&Method::POST, "/signalk/v1/apps/x"),
+            Some(Permission::ReadWrite)
+        );
+    }
+}
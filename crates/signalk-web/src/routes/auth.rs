@@ -7,8 +7,10 @@
 //!
 //! 1. Client checks `/skServer/loginStatus` to see if auth is required
 //! 2. If security enabled, client posts credentials to `/signalk/v1/auth/login`
-//! 3. Server returns JWT token on success
+//! 3. Server returns JWT token on success, also set as the `JAUTHENTICATION`
+//!    cookie
 //! 4. Client includes token in subsequent requests via `Authorization: Bearer <token>`
+//!    or the cookie
 //!
 //! # Device Access Flow
 //!
@@ -64,10 +66,14 @@
 //! }
 //! ```
 //!
+//! The token expires after the security config's `expiration` (default
+//! `1d`).
+//!
 //! **Response (failure):** `401 Unauthorized`
 //!
 //! ### `PUT /signalk/v1/auth/logout`
-//! Invalidate the current session.
+//! Clear the authentication cookie. Tokens are stateless, so one already
+//! handed out stays valid until it expires.
 //!
 //! **Response:** `200 OK`
 //!
@@ -93,7 +99,8 @@
 //! ```
 //!
 //! ### `GET /signalk/v1/requests/:id`
-//! Check status of an access request.
+//! Check status of an access request, or of a pending PUT (see
+//! [`put`](super::put)).
 //!
 //! **Response (pending):**
 //! ```json
@@ -117,14 +124,19 @@
 
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put},
     Router,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::AppState;
+use super::put::request_status;
+use crate::jwt::{authenticate, token_lifetime};
+use crate::{AppState, WebState};
+
+/// Cookie carrying the login token, as set by the TypeScript server.
+pub const AUTH_COOKIE: &str = "JAUTHENTICATION";
 
 /// Login status response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -218,33 +230,113 @@ pub fn access_routes() -> Router<AppState> {
         .route("/requests/:id", get(get_request_status))
 }
 
-/// GET /skServer/loginStatus
-async fn get_login_status(State(_state): State<AppState>) -> Json<LoginStatus> {
-    // TODO: Check actual authentication state
-    Json(LoginStatus {
-        status: "notLoggedIn".to_string(),
-        username: None,
-        user_level: None,
-        read_only_access: Some(false),
-        authentication_required: Some(false),
-        allow_new_user_registration: Some(false),
-        allow_device_access_requests: Some(true),
+/// The token a request carries, from `Authorization: Bearer` or the
+/// [`AUTH_COOKIE`] cookie.
+pub fn request_token(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer.or_else(|| {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|cookies| cookies.split(';'))
+            .find_map(|cookie| {
+                cookie
+                    .trim()
+                    .strip_prefix(AUTH_COOKIE)
+                    .and_then(|rest| rest.strip_prefix('='))
+            })
     })
 }
 
+/// GET /skServer/loginStatus
+async fn get_login_status(State(state): State<AppState>, headers: HeaderMap) -> Json<LoginStatus> {
+    Json(login_status(&state, &headers).await)
+}
+
+/// The login status of a request, from the token it carries.
+pub async fn login_status(state: &WebState, headers: &HeaderMap) -> LoginStatus {
+    let security = state.security.read().await;
+    let user = request_token(headers)
+        .and_then(|token| state.tokens.validate_token(token))
+        .and_then(|claims| {
+            security
+                .users
+                .iter()
+                .flatten()
+                .find(|user| user.user_id == claims.id)
+        });
+
+    match user {
+        Some(user) => LoginStatus {
+            status: "loggedIn".to_string(),
+            username: Some(user.user_id.clone()),
+            user_level: Some(user.user_type.clone()),
+            read_only_access: None,
+            authentication_required: None,
+            allow_new_user_registration: None,
+            allow_device_access_requests: None,
+        },
+        None => LoginStatus {
+            status: "notLoggedIn".to_string(),
+            username: None,
+            user_level: None,
+            read_only_access: Some(security.allow_read_only.unwrap_or(false)),
+            authentication_required: Some(
+                security
+                    .users
+                    .as_ref()
+                    .is_some_and(|users| !users.is_empty()),
+            ),
+            allow_new_user_registration: Some(
+                security.allow_new_user_registration.unwrap_or(false),
+            ),
+            allow_device_access_requests: Some(
+                security.allow_device_access_requests.unwrap_or(true),
+            ),
+        },
+    }
+}
+
 /// POST /signalk/v1/auth/login
 async fn post_login(
-    State(_state): State<AppState>,
-    Json(_request): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
-    // TODO: Implement authentication
-    Err(StatusCode::NOT_IMPLEMENTED)
+    State(state): State<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let security = state.security.read().await;
+    let user = authenticate(&security, &request.username, &request.password)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let lifetime = token_lifetime(&security);
+    let token = state
+        .tokens
+        .issue(&user.user_id, lifetime, chrono::Utc::now())
+        .map_err(|e| {
+            tracing::error!("Could not issue token for {}: {}", user.user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let cookie = format!(
+        "{AUTH_COOKIE}={token}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+        lifetime.as_secs()
+    );
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Json(LoginResponse { token }),
+    ))
 }
 
 /// PUT /signalk/v1/auth/logout
-async fn put_logout(State(_state): State<AppState>) -> StatusCode {
-    // TODO: Invalidate session
-    StatusCode::OK
+async fn put_logout(State(_state): State<AppState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(
+            header::SET_COOKIE,
+            format!("{AUTH_COOKIE}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0"),
+        )],
+    )
 }
 
 /// POST /signalk/v1/access/requests
@@ -261,14 +353,123 @@ async fn post_access_request(
 }
 
 /// GET /signalk/v1/requests/:id
-async fn get_request_status(
-    State(_state): State<AppState>,
-    Path(id): Path<String>,
-) -> Json<RequestStatus> {
+///
+/// Pending PUTs share the requests endpoint with access requests.
+async fn get_request_status(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    if state.pending_puts.contains(&id) {
+        return request_status(&state, &id).await.into_response();
+    }
     // TODO: Look up actual request status
     Json(RequestStatus {
         state: "PENDING".to_string(),
         request_id: id,
         access_request: None,
     })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwt::hash_password;
+    use crate::WebConfig;
+    use signalk_core::{MemoryStore, SecurityConfig, SharedStore, UserRecord};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn secured_state() -> AppState {
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let state = Arc::new(WebState::new(store, WebConfig::default()));
+        *state.security.write().await = SecurityConfig {
+            expiration: Some("1h".to_string()),
+            users: Some(vec![UserRecord {
+                user_id: "admin".to_string(),
+                user_type: "admin".to_string(),
+                password_hash: Some(hash_password("secret").unwrap()),
+            }]),
+            ..Default::default()
+        };
+        state
+    }
+
+    fn login(username: &str, password: &str) -> Json<LoginRequest> {
+        Json(LoginRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_login_success() {
+        let state = secured_state().await;
+
+        let response = post_login(State(state.clone()), login("admin", "secret"))
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with("JAUTHENTICATION="));
+        assert!(cookie.contains("Max-Age=3600"));
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let token = body["token"].as_str().unwrap();
+        let claims = state.tokens.validate_token(token).unwrap();
+        assert_eq!(claims.id, "admin");
+        assert_eq!(claims.exp - claims.iat, 3600);
+
+        let Json(status) = get_login_status(State(state.clone()), bearer(token)).await;
+        assert_eq!(status.status, "loggedIn");
+        assert_eq!(status.username.as_deref(), Some("admin"));
+        assert_eq!(status.user_level.as_deref(), Some("admin"));
+
+        // The cookie works as well
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            format!("other=1; JAUTHENTICATION={token}").parse().unwrap(),
+        );
+        let Json(status) = get_login_status(State(state), headers).await;
+        assert_eq!(status.status, "loggedIn");
+    }
+
+    #[tokio::test]
+    async fn test_login_wrong_password() {
+        let state = secured_state().await;
+
+        let result = post_login(State(state.clone()), login("admin", "wrong")).await;
+        assert_eq!(result.err(), Some(StatusCode::UNAUTHORIZED));
+        let result = post_login(State(state.clone()), login("nobody", "secret")).await;
+        assert_eq!(result.err(), Some(StatusCode::UNAUTHORIZED));
+
+        let Json(status) = get_login_status(State(state), HeaderMap::new()).await;
+        assert_eq!(status.status, "notLoggedIn");
+        assert_eq!(status.authentication_required, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_not_logged_in() {
+        let state = secured_state().await;
+        let issued = chrono::Utc::now() - chrono::Duration::hours(2);
+        let token = state
+            .tokens
+            .issue("admin", Duration::from_secs(3600), issued)
+            .unwrap();
+
+        let Json(status) = get_login_status(State(state), bearer(&token)).await;
+        assert_eq!(status.status, "notLoggedIn");
+        assert!(status.username.is_none());
+    }
 }
@@ -72,17 +72,20 @@
 //! ```
 //!
 //! ### `POST /skServer/debug`
-//! Enable or disable debug logging for namespaces.
+//! Enable or disable debug logging for namespaces, through the server's
+//! [`DebugControl`].
 //!
 //! **Request:**
 //! ```json
 //! {
 //!   "enable": ["signalk-server:*"],
-//!   "disable": ["other:*"]
+//!   "disable": ["signalk-server:subscriptions"]
 //! }
 //! ```
 //!
-//! **Response:** `200 OK`
+//! **Response:** `200 OK`, `400 Bad Request` for a key not in
+//! `debugKeys`, or `501 Not Implemented` if the server has no
+//! [`DebugControl`].
 
 use axum::{
     extract::State,
@@ -93,7 +96,16 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::AppState;
+use crate::{AppState, DebugControl, WebState};
+
+/// Debug keys listed when the server has no [`DebugControl`].
+const DEFAULT_DEBUG_KEYS: [&str; 5] = [
+    "signalk-server:*",
+    "signalk-server:interfaces:*",
+    "signalk-server:providers:*",
+    "signalk-server:plugins:*",
+    "signalk-server:subscriptions",
+];
 
 /// Backup creation response.
 #[derive(Debug, Clone, Serialize)]
@@ -173,21 +185,50 @@ async fn restart_server(State(_state): State<AppState>) -> StatusCode {
 
 /// POST /skServer/debug
 /// Enable/disable debug namespaces.
-async fn set_debug(
-    State(_state): State<AppState>,
-    Json(_request): Json<DebugRequest>,
-) -> StatusCode {
-    // TODO: Update tracing filter
-    StatusCode::OK
+async fn set_debug(State(state): State<AppState>, Json(request): Json<DebugRequest>) -> StatusCode {
+    update_debug_keys(&state, &request)
 }
 
 /// GET /skServer/debugKeys
 /// List available debug namespaces.
-async fn get_debug_keys(State(_state): State<AppState>) -> Json<Vec<String>> {
-    Json(vec![
-        "signalk-server:*".to_string(),
-        "signalk-server:interfaces:*".to_string(),
-        "signalk-server:providers:*".to_string(),
-        "signalk-server:plugins:*".to_string(),
-    ])
+async fn get_debug_keys(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(debug_keys(&state))
+}
+
+/// The debug keys that can be enabled.
+pub fn debug_keys(state: &WebState) -> Vec<String> {
+    match &state.debug {
+        Some(control) => control.keys(),
+        None => DEFAULT_DEBUG_KEYS.map(String::from).to_vec(),
+    }
+}
+
+/// Enable and disable the debug keys in `request`, leaving the others as
+/// they are.
+pub fn update_debug_keys(state: &WebState, request: &DebugRequest) -> StatusCode {
+    let Some(control) = &state.debug else {
+        return StatusCode::NOT_IMPLEMENTED;
+    };
+    let keys = control.keys();
+    let enable = request.enable.as_deref().unwrap_or_default();
+    let disable = request.disable.as_deref().unwrap_or_default();
+    if let Some(unknown) = enable.iter().find(|key| !keys.contains(key)) {
+        tracing::warn!("Unknown debug key {}", unknown);
+        return StatusCode::BAD_REQUEST;
+    }
+
+    let mut enabled = control.enabled();
+    enabled.retain(|key| !disable.contains(key));
+    for key in enable {
+        if !enabled.contains(key) {
+            enabled.push(key.clone());
+        }
+    }
+    match control.set_enabled(&enabled) {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!("Could not apply debug keys: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
 }
@@ -34,9 +34,9 @@ use axum::{
     Router,
 };
 use serde::{Deserialize, Serialize};
-use signalk_core::{InterfaceSettings, ServerSettings, VesselInfo as CoreVesselInfo};
+use signalk_core::{ServerSettings, VesselInfo as CoreVesselInfo};
 
-use crate::AppState;
+use crate::{AppState, ServerEvent};
 
 /// Vessel information for API (includes design/communication)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,32 +92,7 @@ pub fn routes() -> Router<AppState> {
 
 /// GET /skServer/settings
 async fn get_settings(State(state): State<AppState>) -> Json<ServerSettings> {
-    let settings = state.settings.read().await;
-    // Return settings with defaults filled in
-    Json(ServerSettings {
-        interfaces: settings.interfaces.clone().or(Some(InterfaceSettings {
-            appstore: Some(true),
-            plugins: Some(true),
-            rest: Some(true),
-            signalk_ws: Some(true),
-            tcp: Some(false),
-            webapps: Some(true),
-        })),
-        port: settings.port.or(Some(3001)),
-        sslport: settings.sslport,
-        ssl: settings.ssl.or(Some(false)),
-        ws_compression: settings.ws_compression.or(Some(false)),
-        access_logging: settings.access_logging.or(Some(false)),
-        mdns: settings.mdns.or(Some(true)),
-        prune_contexts_minutes: settings.prune_contexts_minutes.or(Some(60)),
-        logging_directory: settings
-            .logging_directory
-            .clone()
-            .or(Some("~/.signalk/logs".to_string())),
-        keep_most_recent_logs_only: settings.keep_most_recent_logs_only.or(Some(true)),
-        log_count_to_keep: settings.log_count_to_keep.or(Some(24)),
-        enable_plugin_logging: settings.enable_plugin_logging.or(Some(true)),
-    })
+    Json(state.settings.read().await.with_defaults())
 }
 
 /// PUT /skServer/settings
@@ -125,9 +100,12 @@ async fn put_settings(
     State(state): State<AppState>,
     Json(new_settings): Json<ServerSettings>,
 ) -> StatusCode {
-    let mut settings = state.settings.write().await;
-    *settings = new_settings;
+    let data = new_settings.with_defaults();
+    *state.settings.write().await = new_settings;
     // TODO: Persist to file and trigger restart if needed
+    state.broadcast_event(ServerEvent::ServerSettings {
+        data: Box::new(data),
+    });
     StatusCode::OK
 }
 
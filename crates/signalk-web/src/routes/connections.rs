@@ -0,0 +1,34 @@
+//! Connected client routes.
+//!
+//! # Endpoints
+//!
+//! ### `GET /skServer/connections`
+//! The WebSocket clients connected to the server, oldest first.
+//!
+//! **Response:**
+//! ```json
+//! [
+//!   {
+//!     "id": 3,
+//!     "remoteAddress": "192.168.1.20:50412",
+//!     "connectedAt": "2024-01-17T10:00:00.000Z",
+//!     "subscriptions": 2,
+//!     "bytesSent": 48213
+//!   }
+//! ]
+//! ```
+
+use axum::{extract::State, response::Json, routing::get, Router};
+
+use crate::server_events::ClientConnection;
+use crate::AppState;
+
+/// Create the connections route.
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/connections", get(get_connections))
+}
+
+/// GET /skServer/connections
+async fn get_connections(State(state): State<AppState>) -> Json<Vec<ClientConnection>> {
+    Json(state.statistics.connections())
+}
@@ -0,0 +1,178 @@
+//! History API routes.
+//!
+//! # Endpoints
+//!
+//! ### `GET /signalk/v1/history/values`
+//! Historical values of some paths, down-sampled to a resolution, in the
+//! format signalk-to-influxdb serves.
+//!
+//! **Query parameters:**
+//! - `paths` - comma-separated, context-relative paths (required)
+//! - `from` - ISO 8601 start of the range (required)
+//! - `to` - ISO 8601 end of the range (default: now)
+//! - `resolution` - bucket width in seconds (default: 1)
+//! - `context` - context to read (default: `vessels.self`)
+//!
+//! **Response:**
+//! ```json
+//! [
+//!   {
+//!     "context": "vessels.self",
+//!     "range": { "from": "2024-01-17T10:00:00.000Z", "to": "2024-01-17T11:00:00.000Z" },
+//!     "values": [{ "path": "navigation.speedOverGround", "method": "average" }],
+//!     "data": [["2024-01-17T10:00:00.000Z", 3.85], ...]
+//!   }
+//! ]
+//! ```
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::history::{self_context, HistoryQuery, HistoryValues};
+use crate::{ApiJson, AppState};
+
+/// Query parameters of `GET /signalk/v1/history/values`.
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryParams {
+    #[serde(default)]
+    pub paths: Option<String>,
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    /// Bucket width in seconds.
+    #[serde(default)]
+    pub resolution: Option<f64>,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default)]
+    pub pretty: bool,
+}
+
+impl HistoryParams {
+    /// Turn the parameters into a [`HistoryQuery`], with the self vessel's
+    /// URN as `vessels.self`.
+    ///
+    /// Missing `paths` or `from`, unparsable times, a range ending before it
+    /// starts and a resolution that isn't positive are `400 Bad Request`.
+    pub fn to_query(&self, self_urn: &str) -> Result<HistoryQuery, StatusCode> {
+        let paths: Vec<String> = self
+            .paths
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect();
+        if paths.is_empty() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let from = parse_time(self.from.as_deref().ok_or(StatusCode::BAD_REQUEST)?)?;
+        let to = match self.to.as_deref() {
+            Some(to) => parse_time(to)?,
+            None => Utc::now(),
+        };
+        if to < from {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let resolution = match self.resolution {
+            Some(secs) if secs.is_finite() && secs > 0.0 => {
+                Duration::milliseconds((secs * 1000.0).ceil() as i64)
+            }
+            Some(_) => return Err(StatusCode::BAD_REQUEST),
+            None => Duration::seconds(1),
+        };
+        let context = self.context.as_deref().unwrap_or("vessels.self");
+        Ok(HistoryQuery {
+            context: self_context(context, self_urn).to_string(),
+            paths,
+            from,
+            to,
+            resolution,
+        })
+    }
+}
+
+fn parse_time(time: &str) -> Result<DateTime<Utc>, StatusCode> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+/// Create the history routes, nested under `/signalk/v1/history`.
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/values", get(get_values))
+}
+
+/// Handler for `GET /signalk/v1/history/values`.
+async fn get_values(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryParams>,
+) -> Result<ApiJson<Vec<HistoryValues>>, StatusCode> {
+    let query = params.to_query(&state.config.self_urn)?;
+    Ok(ApiJson::new(
+        vec![state.history.query(&query)],
+        params.pretty,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{WebConfig, WebState};
+    use signalk_core::{MemoryStore, SharedStore};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_get_values() {
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let state = Arc::new(WebState::new(store, WebConfig::default()));
+        let from = parse_time("2024-01-17T10:00:00Z").unwrap();
+        for (offset, sog) in [(0, 3.0), (1, 4.0), (30, 5.0), (70, 7.0)] {
+            state.history.record(
+                "vessels.self",
+                "navigation.speedOverGround",
+                from + Duration::seconds(offset),
+                sog,
+            );
+        }
+
+        let params = HistoryParams {
+            paths: Some("navigation.speedOverGround".to_string()),
+            from: Some("2024-01-17T10:00:00Z".to_string()),
+            to: Some("2024-01-17T10:05:00Z".to_string()),
+            resolution: Some(60.0),
+            // The self vessel by URN reads the same series as vessels.self
+            context: Some(state.config.self_urn.clone()),
+            ..Default::default()
+        };
+        let response = get_values(State(state.clone()), Query(params))
+            .await
+            .unwrap();
+        let json = serde_json::to_value(&response.value).unwrap();
+        assert_eq!(json[0]["context"], "vessels.self");
+        assert_eq!(
+            json[0]["data"],
+            serde_json::json!([
+                ["2024-01-17T10:00:00.000Z", 4.0],
+                ["2024-01-17T10:01:00.000Z", 7.0],
+            ])
+        );
+
+        let missing_from = HistoryParams {
+            paths: Some("navigation.speedOverGround".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_values(State(state), Query(missing_from)).await.err(),
+            Some(StatusCode::BAD_REQUEST)
+        );
+    }
+}
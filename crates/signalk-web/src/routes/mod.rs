@@ -6,26 +6,48 @@
 pub mod auth;
 pub mod backup;
 pub mod config;
+pub mod connections;
+pub mod history;
 pub mod plugins;
+pub mod put;
 pub mod security;
+pub mod v2;
 
+use crate::permissions::enforce_permissions;
 use crate::AppState;
-use axum::{extract::State, response::Json, routing::get, Router};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    middleware,
+    response::Json,
+    routing::get,
+    Router,
+};
+use signalk_protocol::{DiscoveryResponse, DiscoveryServer};
 
 /// Create the main Axum router with all routes.
 ///
 /// Routes are organized as:
 /// - `/signalk/v1/` - Signal K API (auth, stream, API)
+/// - `/signalk/v2/api/` - Signal K v2 API (course, resources)
 /// - `/skServer/` - Server management
 /// - `/admin/` - Static Admin UI files
+///
+/// Every route is behind [`enforce_permissions`].
 pub fn create_router(state: AppState) -> Router {
     Router::new()
         // Discovery endpoint
         .route("/signalk", get(discovery_handler))
         // SignalK v1 API routes
         .nest("/signalk/v1", signalk_v1_routes())
+        // SignalK v2 API routes
+        .nest("/signalk/v2/api", v2::routes())
         // Server management routes
         .nest("/skServer", sk_server_routes())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            enforce_permissions,
+        ))
         .with_state(state)
 }
 
@@ -38,6 +60,10 @@ fn signalk_v1_routes() -> Router<AppState> {
         .merge(auth::access_routes())
         // Plugin/app routes
         .merge(plugins::api_routes())
+        // Historical values
+        .nest("/history", history::routes())
+        // PUT over REST and pending request status
+        .merge(put::routes())
 }
 
 /// Create /skServer management routes.
@@ -54,23 +80,116 @@ fn sk_server_routes() -> Router<AppState> {
         .merge(plugins::server_routes())
         // Backup, restore, restart
         .merge(backup::routes())
+        // Connected WebSocket clients
+        .merge(connections::routes())
+}
+
+/// Build the discovery document for a request from its `Host` and
+/// `X-Forwarded-Proto` headers, so the advertised URLs work for the client
+/// that asked (e.g. one behind NAT or a TLS-terminating proxy). A reverse
+/// proxy's `X-Forwarded-Host` takes precedence over `Host`.
+///
+/// Without a usable `Host` header the URLs point at `localhost` on
+/// `fallback_port`.
+pub fn discovery_for_headers(
+    headers: &HeaderMap,
+    fallback_port: Option<u16>,
+    server: DiscoveryServer,
+) -> DiscoveryResponse {
+    let scheme = DiscoveryResponse::forwarded_scheme(
+        headers
+            .get("x-forwarded-proto")
+            .and_then(|value| value.to_str().ok()),
+    );
+    // Behind chained proxies, the first entry is the client's
+    let forwarded_host = headers
+        .get("x-forwarded-host")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|hosts| hosts.split(',').next())
+        .map(str::trim)
+        .filter(|host| !host.is_empty());
+    forwarded_host
+        .or_else(|| {
+            headers
+                .get(header::HOST)
+                .and_then(|value| value.to_str().ok())
+        })
+        .and_then(|host| {
+            DiscoveryResponse::for_request(host, scheme, None, "", Some(server.clone())).ok()
+        })
+        .unwrap_or_else(|| {
+            DiscoveryResponse::for_request("localhost", scheme, fallback_port, "", Some(server))
+                .expect("localhost is a valid host")
+        })
 }
 
 /// Handler for `/signalk` discovery endpoint.
 ///
-/// Returns the Signal K discovery document with available endpoints.
-async fn discovery_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "endpoints": {
-            "v1": {
-                "version": "1.7.0",
-                "signalk-http": "/signalk/v1/api",
-                "signalk-ws": "/signalk/v1/stream"
-            }
-        },
-        "server": {
-            "id": state.config.name,
-            "version": state.config.version
-        }
-    }))
+/// Returns the Signal K discovery document with available endpoints. The
+/// server object carries the vessel name when one is configured.
+async fn discovery_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Json<DiscoveryResponse> {
+    let server = DiscoveryServer {
+        id: state.config.name.clone(),
+        version: state.config.version.clone(),
+        vessel_name: state.vessel_info.read().await.name.clone(),
+    };
+    Json(discovery_for_headers(&headers, None, server))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{WebConfig, WebState};
+    use signalk_core::{MemoryStore, SharedStore};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_discovery_includes_vessel_name() {
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let state = Arc::new(WebState::new(store, WebConfig::default()));
+        state.vessel_info.write().await.name = Some("Albatross".to_string());
+
+        let Json(discovery) = discovery_handler(State(state), HeaderMap::new()).await;
+        let discovery = serde_json::to_value(discovery).unwrap();
+
+        assert_eq!(discovery["server"]["vesselName"], "Albatross");
+        assert_eq!(discovery["server"]["id"], "signalk-server-rust");
+    }
+
+    #[test]
+    fn test_discovery_behind_reverse_proxy() {
+        let server = DiscoveryServer {
+            id: "signalk-server-rust".to_string(),
+            version: "1.2.3".to_string(),
+            vessel_name: None,
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "127.0.0.1:3000".parse().unwrap());
+        headers.insert(
+            "x-forwarded-host",
+            "boat.example.com, proxy.lan".parse().unwrap(),
+        );
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+
+        let discovery = discovery_for_headers(&headers, Some(3000), server.clone());
+        assert_eq!(
+            discovery.endpoints.v1.signalk_http,
+            "https://boat.example.com/signalk/v1/api"
+        );
+        assert_eq!(
+            discovery.endpoints.v1.signalk_ws,
+            "wss://boat.example.com/signalk/v1/stream"
+        );
+
+        // Without the proxy's header the Host header is used
+        headers.remove("x-forwarded-host");
+        let discovery = discovery_for_headers(&headers, Some(3000), server);
+        assert_eq!(
+            discovery.endpoints.v1.signalk_ws,
+            "wss://127.0.0.1:3000/signalk/v1/stream"
+        );
+    }
 }
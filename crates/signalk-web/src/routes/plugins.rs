@@ -29,7 +29,8 @@
 //!     "description": "Converts SignalK data to NMEA 2000",
 //!     "enabled": true,
 //!     "statusMessage": "Running",
-//!     "data": { ... }
+//!     "data": { ... },
+//!     "schema": { "type": "object", ... }
 //!   }
 //! ]
 //! ```
@@ -37,7 +38,8 @@
 //! ## Plugin Configuration
 //!
 //! ### `POST /skServer/plugins/:id/config`
-//! Save plugin configuration.
+//! Save plugin configuration and start, restart or stop the plugin to
+//! match it.
 //!
 //! **Request:**
 //! ```json
@@ -52,6 +54,18 @@
 //!
 //! **Response:** `200 OK`
 //!
+//! ### `POST /skServer/plugins/:id/enable`
+//! ### `POST /skServer/plugins/:id/disable`
+//! Enable and start, or disable and stop, a plugin, keeping its
+//! configuration.
+//!
+//! **Response:** `200 OK`
+//!
+//! These endpoints answer `404 Not Found` for a plugin that isn't
+//! installed. A plugin that fails to start is `500 Internal Server Error`,
+//! with the plugin's last stderr output as the body when it crashed while
+//! loading; its settings are saved regardless.
+//!
 //! ## App Store
 //!
 //! ### `GET /signalk/v1/apps/list`
@@ -97,6 +111,7 @@ use axum::{
     Router,
 };
 use serde::{Deserialize, Serialize};
+use signalk_plugins::{PluginError, PluginManager, PluginSettings};
 
 use crate::AppState;
 
@@ -127,6 +142,10 @@ pub struct Plugin {
     /// Plugin-specific configuration.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<serde_json::Value>,
+
+    /// JSON schema of the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<serde_json::Value>,
 }
 
 /// Plugin configuration update.
@@ -138,6 +157,15 @@ pub struct PluginConfig {
     pub configuration: Option<serde_json::Value>,
 }
 
+impl From<PluginConfig> for PluginSettings {
+    fn from(config: PluginConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            configuration: config.configuration.unwrap_or_default(),
+        }
+    }
+}
+
 /// App store entry.
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -172,6 +200,8 @@ pub fn server_routes() -> Router<AppState> {
     Router::new()
         .route("/plugins", get(get_plugins))
         .route("/plugins/:id/config", post(save_plugin_config))
+        .route("/plugins/:id/enable", post(enable_plugin))
+        .route("/plugins/:id/disable", post(disable_plugin))
         .route("/webapps", get(get_webapps))
 }
 
@@ -180,21 +210,73 @@ pub fn api_routes() -> Router<AppState> {
     Router::new().route("/apps/list", get(get_app_list))
 }
 
+/// The installed plugins of `manager`, as listed by `GET /skServer/plugins`.
+pub async fn list_plugins(manager: &PluginManager) -> Vec<Plugin> {
+    manager
+        .list()
+        .await
+        .into_iter()
+        .map(|entry| {
+            let status = entry.status.unwrap_or_default();
+            Plugin {
+                id: entry.info.id,
+                name: entry.info.name,
+                version: entry.info.version,
+                description: entry.info.description,
+                enabled: entry.settings.enabled,
+                // An error outranks the last status
+                status_message: status.error.or(status.message),
+                data: Some(entry.settings.configuration),
+                schema: entry.info.schema,
+            }
+        })
+        .collect()
+}
+
+/// The response to a plugin lifecycle change.
+pub fn plugin_response(result: Result<(), PluginError>) -> (StatusCode, String) {
+    match result {
+        Ok(()) => (StatusCode::OK, String::new()),
+        Err(PluginError::UnknownPlugin(_)) => (StatusCode::NOT_FOUND, String::new()),
+        Err(PluginError::Exited { stderr }) => {
+            tracing::error!("Plugin exited while starting: {stderr}");
+            (StatusCode::INTERNAL_SERVER_ERROR, stderr)
+        }
+        Err(e) => {
+            tracing::error!("Plugin error: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+    }
+}
+
 /// GET /skServer/plugins
-async fn get_plugins(State(_state): State<AppState>) -> Json<Vec<Plugin>> {
-    // TODO: Load actual plugin list
-    Json(vec![])
+async fn get_plugins(State(state): State<AppState>) -> Json<Vec<Plugin>> {
+    Json(list_plugins(&state.plugins).await)
 }
 
 /// POST /skServer/plugins/:id/config
 async fn save_plugin_config(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Path(id): Path<String>,
     Json(config): Json<PluginConfig>,
-) -> StatusCode {
-    // TODO: Save plugin configuration
-    // Configuration is stored in ~/.signalk/plugin-config-data/{id}.json
-    StatusCode::OK
+) -> (StatusCode, String) {
+    plugin_response(state.plugins.save_settings(&id, config.into()).await)
+}
+
+/// POST /skServer/plugins/:id/enable
+async fn enable_plugin(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, String) {
+    plugin_response(state.plugins.set_enabled(&id, true).await)
+}
+
+/// POST /skServer/plugins/:id/disable
+async fn disable_plugin(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, String) {
+    plugin_response(state.plugins.set_enabled(&id, false).await)
 }
 
 /// GET /skServer/webapps
@@ -208,3 +290,154 @@ async fn get_app_list(State(_state): State<AppState>) -> Json<Vec<AppStoreEntry>
     // TODO: Query npm registry for signalk plugins/webapps
     Json(vec![])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{WebConfig, WebState};
+    use serde_json::{json, Value};
+    use signalk_core::{MemoryConfigStorage, MemoryStore, SharedStore};
+    use signalk_plugins::{BoxFuture, PluginInfo, PluginLauncher, PluginStatus, RunningPlugin};
+    use std::sync::{Arc, Mutex};
+
+    /// Plugin lifecycle calls seen by the mock.
+    type Calls = Arc<Mutex<Vec<String>>>;
+
+    /// Launches mock plugins, or fails as if the subprocess crashed.
+    struct MockLauncher {
+        calls: Calls,
+        stderr: Option<String>,
+    }
+
+    impl PluginLauncher for MockLauncher {
+        fn launch<'a>(
+            &'a self,
+            plugin: &'a PluginInfo,
+            config: Value,
+        ) -> BoxFuture<'a, Result<Box<dyn RunningPlugin>, PluginError>> {
+            Box::pin(async move {
+                if let Some(stderr) = &self.stderr {
+                    return Err(PluginError::Exited {
+                        stderr: stderr.clone(),
+                    });
+                }
+                self.calls
+                    .lock()
+                    .unwrap()
+                    .push(format!("start {} {}", plugin.id, config));
+                Ok(Box::new(MockPlugin {
+                    id: plugin.id.clone(),
+                    calls: self.calls.clone(),
+                }) as Box<dyn RunningPlugin>)
+            })
+        }
+    }
+
+    struct MockPlugin {
+        id: String,
+        calls: Calls,
+    }
+
+    impl RunningPlugin for MockPlugin {
+        fn send_config(&mut self, _config: Value) -> BoxFuture<'_, Result<(), PluginError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn status(&self) -> PluginStatus {
+            PluginStatus {
+                message: Some("Running".to_string()),
+                error: None,
+            }
+        }
+
+        fn stop(self: Box<Self>) -> BoxFuture<'static, Result<(), PluginError>> {
+            self.calls.lock().unwrap().push(format!("stop {}", self.id));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn state_with_plugins(stderr: Option<&str>) -> (AppState, Calls) {
+        let calls = Calls::default();
+        let launcher = MockLauncher {
+            calls: calls.clone(),
+            stderr: stderr.map(str::to_string),
+        };
+        let plugin = PluginInfo {
+            id: "anchor-alarm".to_string(),
+            name: "Anchor Alarm".to_string(),
+            version: "1.0.0".to_string(),
+            description: None,
+            module: "index.js".into(),
+            schema: Some(json!({ "type": "object" })),
+        };
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let mut state = WebState::new(store, WebConfig::default());
+        state.plugins = Arc::new(PluginManager::new(
+            vec![plugin],
+            Arc::new(launcher),
+            Some(Arc::new(MemoryConfigStorage::default())),
+        ));
+        (Arc::new(state), calls)
+    }
+
+    fn id() -> Path<String> {
+        Path("anchor-alarm".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_plugin_lifecycle() {
+        let (state, calls) = state_with_plugins(None);
+        let Json(plugins) = get_plugins(State(state.clone())).await;
+        assert_eq!(plugins.len(), 1);
+        assert!(!plugins[0].enabled);
+        assert_eq!(plugins[0].schema, Some(json!({ "type": "object" })));
+        assert_eq!(plugins[0].status_message, None);
+
+        let config = PluginConfig {
+            enabled: true,
+            configuration: Some(json!({ "radius": 50 })),
+        };
+        let (status, _) = save_plugin_config(State(state.clone()), id(), Json(config)).await;
+        assert_eq!(status, StatusCode::OK);
+        let Json(plugins) = get_plugins(State(state.clone())).await;
+        assert!(plugins[0].enabled);
+        assert_eq!(plugins[0].data, Some(json!({ "radius": 50 })));
+        assert_eq!(plugins[0].status_message.as_deref(), Some("Running"));
+
+        // A new configuration restarts the plugin
+        let config = PluginConfig {
+            enabled: true,
+            configuration: Some(json!({ "radius": 80 })),
+        };
+        save_plugin_config(State(state.clone()), id(), Json(config)).await;
+        let (status, _) = disable_plugin(State(state.clone()), id()).await;
+        assert_eq!(status, StatusCode::OK);
+        // Enabling again keeps the configuration
+        enable_plugin(State(state.clone()), id()).await;
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                r#"start anchor-alarm {"radius":50}"#,
+                "stop anchor-alarm",
+                r#"start anchor-alarm {"radius":80}"#,
+                "stop anchor-alarm",
+                r#"start anchor-alarm {"radius":80}"#,
+            ]
+        );
+
+        let unknown = enable_plugin(State(state), Path("unknown".to_string())).await;
+        assert_eq!(unknown.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_enable_reports_startup_crash() {
+        let (state, _) = state_with_plugins(Some("Error: Cannot find module 'serialport'"));
+        let (status, body) = enable_plugin(State(state.clone()), id()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body, "Error: Cannot find module 'serialport'");
+
+        let Json(plugins) = get_plugins(State(state)).await;
+        assert!(plugins[0].enabled);
+        assert_eq!(plugins[0].status_message, None);
+    }
+}
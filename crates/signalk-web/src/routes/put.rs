@@ -0,0 +1,335 @@
+//! PUT over REST.
+//!
+//! A REST PUT is decided exactly like a WebSocket PUT, by
+//! [`MemoryStore::resolve_put`](signalk_core::MemoryStore::resolve_put):
+//! a PUT handler registered for the path first, then the writable
+//! allowlist from the settings.
+//!
+//! # Endpoints
+//!
+//! ### `PUT /signalk/v1/api/vessels/self/*path`
+//! Set a self path, e.g. `/signalk/v1/api/vessels/self/steering/autopilot/state`.
+//!
+//! **Request:**
+//! ```json
+//! { "value": "auto", "source": "my-app" }
+//! ```
+//!
+//! **Response:** `200` when done, `202` when the device is still acting on
+//! it, `400` when the handler rejects the value and `405` when the path
+//! isn't writable:
+//! ```json
+//! { "requestId": "6b0e...", "state": "PENDING", "statusCode": 202, "href": "/signalk/v1/requests/6b0e..." }
+//! ```
+//!
+//! A pending PUT's `href` is polled at `GET /signalk/v1/requests/:id`
+//! (routed with the access requests), which answers `COMPLETED` once the
+//! device reports the value that was put and `PENDING` until then.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::put,
+    Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use signalk_core::{PutResult, SignalKStore, WritablePaths};
+use signalk_protocol::{PutRequest, PutResponse, PutSpec, PutState};
+
+use crate::{AppState, WebState};
+
+/// Pending requests remembered for polling; the oldest are forgotten first.
+const MAX_PENDING: usize = 256;
+
+/// Create the PUT route.
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/api/vessels/self/*path", put(put_path))
+}
+
+/// Body of a REST PUT.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PutBody {
+    pub value: Value,
+    /// Source the value is reported under, for paths without a handler.
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// A PUT accepted by an async handler, waiting on its device.
+#[derive(Debug, Clone)]
+struct PendingPut {
+    request_id: String,
+    path: String,
+    value: Value,
+}
+
+/// PUTs that answered `202`, for `GET /signalk/v1/requests/:id`.
+#[derive(Debug, Default)]
+pub struct PendingPuts(Mutex<VecDeque<PendingPut>>);
+
+impl PendingPuts {
+    fn insert(&self, pending: PendingPut) {
+        let mut requests = self.0.lock().unwrap();
+        if requests.len() == MAX_PENDING {
+            requests.pop_front();
+        }
+        requests.push_back(pending);
+    }
+
+    /// Check whether `request_id` is a pending PUT.
+    pub fn contains(&self, request_id: &str) -> bool {
+        self.get(request_id).is_some()
+    }
+
+    fn get(&self, request_id: &str) -> Option<PendingPut> {
+        let requests = self.0.lock().unwrap();
+        requests
+            .iter()
+            .find(|pending| pending.request_id == request_id)
+            .cloned()
+    }
+}
+
+/// Where to poll the request `request_id`.
+fn request_href(request_id: &str) -> String {
+    format!("/signalk/v1/requests/{request_id}")
+}
+
+/// PUT `body` to the self `path` (slash or dot separated) and answer as
+/// the WebSocket PUT would.
+pub async fn put_self_path(
+    state: &WebState,
+    path: &str,
+    body: PutBody,
+) -> (StatusCode, Json<PutResponse>) {
+    let req = PutRequest {
+        context: Some("vessels.self".to_string()),
+        request_id: uuid::Uuid::new_v4().to_string(),
+        put: PutSpec {
+            path: path.trim_matches('/').replace('/', "."),
+            value: body.value,
+            source: body.source,
+        },
+    };
+    let response = put_request(state, req).await;
+    (status(response.status_code), Json(response))
+}
+
+/// Decide the PUT `req` to a self path and apply it.
+///
+/// The caller checks the context and the client's permission first. A
+/// pending PUT's response carries the `href` to poll.
+pub async fn put_request(state: &WebState, req: PutRequest) -> PutResponse {
+    let writable = WritablePaths::new(&state.settings.read().await.writable_paths())
+        .unwrap_or_else(|e| {
+            tracing::warn!("Invalid writable path pattern, rejecting PUTs: {}", e);
+            WritablePaths::none()
+        });
+
+    let result = state.store.write().resolve_put(
+        &req.put.path,
+        &req.put.value,
+        req.put.source.as_deref(),
+        &writable,
+    );
+    let mut response = PutResponse::for_result(&req, &result);
+    match result {
+        // Already stored by the handler
+        PutResult::Completed(delta) => state.publish_delta(delta),
+        PutResult::Writable(delta) => state.apply_delta(delta).await,
+        PutResult::Pending => {
+            response.href = Some(request_href(&req.request_id));
+            state.pending_puts.insert(PendingPut {
+                request_id: req.request_id,
+                path: req.put.path,
+                value: req.put.value,
+            });
+        }
+        PutResult::Failed(_) | PutResult::NotSupported => {
+            tracing::warn!(
+                "Rejected PUT to {}: {}",
+                req.put.path,
+                response.message.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    response
+}
+
+/// The state of the PUT `request_id`, if it went pending.
+pub async fn request_status(state: &WebState, request_id: &str) -> (StatusCode, Json<PutResponse>) {
+    let Some(pending) = state.pending_puts.get(request_id) else {
+        let response = PutResponse {
+            request_id: request_id.to_string(),
+            state: PutState::Failed,
+            status_code: 404,
+            message: Some("Unknown request".to_string()),
+            href: None,
+        };
+        return (StatusCode::NOT_FOUND, Json(response));
+    };
+
+    let done = state
+        .store
+        .read()
+        .get_self_path(&pending.path)
+        .is_some_and(|node| node.get("value") == Some(&pending.value));
+    let response = if done {
+        PutResponse {
+            request_id: pending.request_id,
+            state: PutState::Completed,
+            status_code: 200,
+            message: None,
+            href: None,
+        }
+    } else {
+        PutResponse {
+            href: Some(request_href(&pending.request_id)),
+            request_id: pending.request_id,
+            state: PutState::Pending,
+            status_code: 202,
+            message: None,
+        }
+    };
+    (status(response.status_code), Json(response))
+}
+
+fn status(code: u16) -> StatusCode {
+    StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// PUT /signalk/v1/api/vessels/self/*path
+async fn put_path(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Json(body): Json<PutBody>,
+) -> (StatusCode, Json<PutResponse>) {
+    put_self_path(&state, &path, body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WebConfig;
+    use serde_json::json;
+    use signalk_core::{Delta, MemoryStore, PathValue, SharedStore, Update};
+    use std::sync::Arc;
+
+    fn state(store: MemoryStore) -> (AppState, Arc<Mutex<Vec<Delta>>>) {
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let mut state = WebState::new(Arc::new(SharedStore::new(store)), WebConfig::default());
+        state.delta_sink = Some(sink.clone());
+        (Arc::new(state), sink)
+    }
+
+    fn body(value: Value) -> PutBody {
+        PutBody {
+            value,
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_put_registered_path() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store
+            .register_put_handler(
+                "steering.autopilot.state",
+                Box::new(|value| match value.as_str() {
+                    Some("auto" | "standby") => Ok(()),
+                    _ => Err("Unknown autopilot state".to_string()),
+                }),
+            )
+            .unwrap();
+        let (state, sink) = state(store);
+
+        let (status, Json(response)) =
+            put_self_path(&state, "steering/autopilot/state", body(json!("auto"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(matches!(response.state, PutState::Completed));
+        assert_eq!(
+            state
+                .store
+                .read()
+                .get_self_path("steering.autopilot.state")
+                .unwrap()["value"],
+            "auto"
+        );
+        assert_eq!(
+            sink.lock().unwrap()[0].updates[0].values[0].path,
+            "steering.autopilot.state"
+        );
+
+        let (status, Json(response)) =
+            put_self_path(&state, "steering/autopilot/state", body(json!("off"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.message.as_deref(), Some("Unknown autopilot state"));
+    }
+
+    #[tokio::test]
+    async fn test_put_unregistered_path() {
+        let (state, sink) = state(MemoryStore::new("vessels.urn:mrn:signalk:uuid:self"));
+
+        let (status, Json(response)) =
+            put_self_path(&state, "navigation/anchor/maxRadius", body(json!(50))).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert!(matches!(response.state, PutState::Failed));
+        assert!(state
+            .store
+            .read()
+            .get_self_path("navigation.anchor.maxRadius")
+            .is_none());
+        assert!(sink.lock().unwrap().is_empty());
+
+        // No handler either, but on the default allowlist
+        let (status, _) =
+            put_self_path(&state, "electrical/switches/deck/state", body(json!(1))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(sink.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_put_pending_until_reported() {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store
+            .register_async_put_handler("navigation.anchor.rodeLength", Box::new(|_| Ok(())))
+            .unwrap();
+        let (state, _) = state(store);
+
+        let (status, Json(response)) =
+            put_self_path(&state, "navigation/anchor/rodeLength", body(json!(30))).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let href = response.href.unwrap();
+        assert_eq!(href, request_href(&response.request_id));
+
+        let (status, _) = request_status(&state, &response.request_id).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        // The windlass reports the new length
+        state.store.write().apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("windlass".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.anchor.rodeLength".to_string(),
+                    value: json!(30),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+        let (status, Json(response)) = request_status(&state, &response.request_id).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(matches!(response.state, PutState::Completed));
+
+        let (status, _) = request_status(&state, "unknown").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -129,16 +129,20 @@
 //!   "type": "admin"
 //! }
 //! ```
+//!
+//! The password is stored as an argon2 hash. Returns `409 Conflict` once
+//! users exist.
 
 use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::Json,
-    routing::{delete, get, post, put},
+    routing::{get, post, put},
     Router,
 };
 use serde::{Deserialize, Serialize};
 
+use crate::jwt::hash_password;
 use crate::AppState;
 
 /// Security configuration.
@@ -208,8 +212,11 @@ pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/config", get(get_config).put(put_config))
         .route("/users", get(get_users))
-        .route("/users/:id", post(create_user).put(update_user))
-        .route("/users/:username", delete(delete_user))
+        // One parameter name per segment, or the router panics
+        .route(
+            "/users/:id",
+            post(create_user).put(update_user).delete(delete_user),
+        )
         .route("/user/:username/password", put(change_password))
         .route("/devices", get(get_devices))
         .route("/devices/:uuid", put(update_device).delete(delete_device))
@@ -335,7 +342,65 @@ async fn handle_access_request(
 }
 
 /// POST /skServer/enableSecurity
-async fn enable_security(State(_state): State<AppState>, Json(_user): Json<User>) -> StatusCode {
-    // TODO: Enable security with initial admin user
-    StatusCode::OK
+async fn enable_security(State(state): State<AppState>, Json(user): Json<User>) -> StatusCode {
+    let Some(password) = user.password.as_deref().filter(|p| !p.is_empty()) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let mut security = state.security.write().await;
+    if security
+        .users
+        .as_ref()
+        .is_some_and(|users| !users.is_empty())
+    {
+        return StatusCode::CONFLICT;
+    }
+
+    match hash_password(password) {
+        Ok(hash) => {
+            security.users = Some(vec![signalk_core::UserRecord {
+                user_id: user.user_id,
+                user_type: user.user_type,
+                password_hash: Some(hash),
+            }]);
+            StatusCode::OK
+        }
+        Err(e) => {
+            tracing::error!("Could not hash password: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwt::authenticate;
+    use crate::{WebConfig, WebState};
+    use signalk_core::{MemoryStore, SharedStore};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_enable_security_hashes_password() {
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let state = Arc::new(WebState::new(store, WebConfig::default()));
+        let admin = || User {
+            user_id: "admin".to_string(),
+            user_type: "admin".to_string(),
+            password: Some("secret".to_string()),
+        };
+
+        let status = enable_security(State(state.clone()), Json(admin())).await;
+        assert_eq!(status, StatusCode::OK);
+        {
+            let security = state.security.read().await;
+            let users = security.users.as_ref().unwrap();
+            let hash = users[0].password_hash.as_deref().unwrap();
+            assert_ne!(hash, "secret");
+            assert!(authenticate(&security, "admin", "secret").is_some());
+        }
+
+        // Only on a fresh install
+        let status = enable_security(State(state), Json(admin())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
 }
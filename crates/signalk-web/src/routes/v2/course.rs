@@ -0,0 +1,590 @@
+//! Signal K v2 course API.
+//!
+//! The course is kept in the store under `navigation.course` of the self
+//! vessel (`nextPoint`, `previousPoint`, `activeRoute`, `startTime`,
+//! `targetArrivalTime`, `arrivalCircle`), so stream clients see it change
+//! like any other value.
+//!
+//! # Endpoints
+//!
+//! ### `GET /signalk/v2/api/vessels/self/navigation/course`
+//! The current course.
+//!
+//! **Response:**
+//! ```json
+//! {
+//!   "startTime": "2024-01-17T10:00:00.000Z",
+//!   "targetArrivalTime": null,
+//!   "arrivalCircle": 50,
+//!   "activeRoute": null,
+//!   "nextPoint": {
+//!     "type": "Location",
+//!     "position": { "latitude": 52.0987, "longitude": 4.9876 }
+//!   },
+//!   "previousPoint": {
+//!     "type": "VesselPosition",
+//!     "position": { "latitude": 52.1012, "longitude": 4.9841 }
+//!   }
+//! }
+//! ```
+//!
+//! ### `DELETE /signalk/v2/api/vessels/self/navigation/course`
+//! Clear the course.
+//!
+//! ### `PUT /signalk/v2/api/vessels/self/navigation/course/destination`
+//! Steer to a position or a waypoint resource.
+//!
+//! ```json
+//! { "position": { "latitude": 52.0987, "longitude": 4.9876 } }
+//! { "href": "/resources/waypoints/<id>", "arrivalCircle": 50 }
+//! ```
+//!
+//! ### `PUT /signalk/v2/api/vessels/self/navigation/course/activeRoute`
+//! Follow a route resource, from point `pointIndex` (0 by default), end to
+//! start if `reverse` is set. `DELETE` clears the course.
+//!
+//! ```json
+//! { "href": "/resources/routes/<id>", "pointIndex": 0, "reverse": false }
+//! ```
+//!
+//! ### `PUT /signalk/v2/api/vessels/self/navigation/course/arrivalCircle`
+//! Set the arrival circle radius in metres: `{ "value": 50 }`.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+    routing::{get, put},
+    Router,
+};
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use signalk_core::{Delta, PathValue, SignalKStore, Update};
+
+use super::ActionResponse;
+use crate::AppState;
+
+/// `$source` of course changes.
+pub const COURSE_SOURCE: &str = "courseApi";
+
+/// A latitude/longitude in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// What a course point is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PointType {
+    /// A position given directly.
+    Location,
+    /// A waypoint resource.
+    Waypoint,
+    /// A point of the active route.
+    RoutePoint,
+    /// Where the vessel was when the course was set.
+    VesselPosition,
+}
+
+/// The next or previous point of a course.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoursePoint {
+    /// The resource the point came from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    #[serde(rename = "type")]
+    pub point_type: PointType,
+    pub position: Position,
+}
+
+/// The route being followed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveRoute {
+    pub href: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Index of the next point, counted in the direction travelled.
+    pub point_index: usize,
+    pub point_total: usize,
+    pub reverse: bool,
+}
+
+/// The current course.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CourseInfo {
+    pub start_time: Option<String>,
+    pub target_arrival_time: Option<String>,
+    /// Radius in metres around the next point that counts as arrived.
+    pub arrival_circle: f64,
+    pub active_route: Option<ActiveRoute>,
+    pub next_point: Option<CoursePoint>,
+    pub previous_point: Option<CoursePoint>,
+}
+
+impl CourseInfo {
+    /// Read the course from the store.
+    fn load(store: &impl SignalKStore) -> Self {
+        fn field<T: serde::de::DeserializeOwned>(
+            store: &impl SignalKStore,
+            name: &str,
+        ) -> Option<T> {
+            store
+                .get_self_path(&format!("navigation.course.{name}"))
+                .and_then(|node| node.get("value").cloned())
+                .and_then(|value| serde_json::from_value(value).ok())
+        }
+        Self {
+            start_time: field(store, "startTime"),
+            target_arrival_time: field(store, "targetArrivalTime"),
+            arrival_circle: field(store, "arrivalCircle").unwrap_or_default(),
+            active_route: field(store, "activeRoute"),
+            next_point: field(store, "nextPoint"),
+            previous_point: field(store, "previousPoint"),
+        }
+    }
+
+    /// The course as a delta on `navigation.course`.
+    fn to_delta(&self, timestamp: &str) -> Delta {
+        let value = |path: &str, value: Value| PathValue {
+            path: format!("navigation.course.{path}"),
+            value,
+        };
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some(COURSE_SOURCE.to_string()),
+                source: None,
+                timestamp: Some(timestamp.to_string()),
+                values: vec![
+                    value("startTime", json(&self.start_time)),
+                    value("targetArrivalTime", json(&self.target_arrival_time)),
+                    value("arrivalCircle", json(&self.arrival_circle)),
+                    value("activeRoute", json(&self.active_route)),
+                    value("nextPoint", json(&self.next_point)),
+                    value("previousPoint", json(&self.previous_point)),
+                ],
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+}
+
+/// Body of `PUT .../course/destination`: an `href` or a `position`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDestination {
+    #[serde(default)]
+    pub href: Option<String>,
+    #[serde(default)]
+    pub position: Option<Position>,
+    #[serde(default)]
+    pub arrival_circle: Option<f64>,
+}
+
+/// Body of `PUT .../course/activeRoute`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetActiveRoute {
+    pub href: String,
+    #[serde(default)]
+    pub point_index: usize,
+    #[serde(default)]
+    pub reverse: bool,
+    #[serde(default)]
+    pub arrival_circle: Option<f64>,
+}
+
+/// Body of `PUT .../course/arrivalCircle`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetArrivalCircle {
+    pub value: f64,
+}
+
+/// Create the course routes.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_course).delete(clear_course))
+        .route("/destination", put(set_destination))
+        .route("/activeRoute", put(set_active_route).delete(clear_course))
+        .route("/arrivalCircle", put(set_arrival_circle))
+}
+
+type ApiResult = Result<Json<ActionResponse>, (StatusCode, Json<ActionResponse>)>;
+
+/// GET /signalk/v2/api/vessels/self/navigation/course
+async fn get_course(State(state): State<AppState>) -> Json<CourseInfo> {
+    Json(CourseInfo::load(&*state.store.read()))
+}
+
+/// DELETE /signalk/v2/api/vessels/self/navigation/course
+async fn clear_course(State(state): State<AppState>) -> ApiResult {
+    let arrival_circle = CourseInfo::load(&*state.store.read()).arrival_circle;
+    save(
+        &state,
+        CourseInfo {
+            arrival_circle,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// PUT /signalk/v2/api/vessels/self/navigation/course/destination
+async fn set_destination(
+    State(state): State<AppState>,
+    Json(request): Json<SetDestination>,
+) -> ApiResult {
+    let next_point = match (&request.href, request.position) {
+        (Some(href), _) => {
+            let waypoint = state
+                .resources
+                .waypoint(href)
+                .await
+                .map_err(storage_error)?;
+            let Some((latitude, longitude)) = waypoint.and_then(|waypoint| waypoint.position())
+            else {
+                return Err(ActionResponse::failed(
+                    StatusCode::BAD_REQUEST,
+                    format!("{href} is not a waypoint"),
+                ));
+            };
+            CoursePoint {
+                href: Some(href.clone()),
+                point_type: PointType::Waypoint,
+                position: Position {
+                    latitude,
+                    longitude,
+                },
+            }
+        }
+        (None, Some(position)) => CoursePoint {
+            href: None,
+            point_type: PointType::Location,
+            position,
+        },
+        (None, None) => {
+            return Err(ActionResponse::failed(
+                StatusCode::BAD_REQUEST,
+                "A destination needs an href or a position".to_string(),
+            ))
+        }
+    };
+    if !valid(next_point.position) {
+        return Err(ActionResponse::failed(
+            StatusCode::BAD_REQUEST,
+            "Position out of range".to_string(),
+        ));
+    }
+
+    let course = {
+        let store = state.store.read();
+        let current = CourseInfo::load(&*store);
+        CourseInfo {
+            start_time: Some(now()),
+            target_arrival_time: None,
+            arrival_circle: request.arrival_circle.unwrap_or(current.arrival_circle),
+            active_route: None,
+            next_point: Some(next_point),
+            previous_point: vessel_position(&*store),
+        }
+    };
+    save(&state, course).await
+}
+
+/// PUT /signalk/v2/api/vessels/self/navigation/course/activeRoute
+async fn set_active_route(
+    State(state): State<AppState>,
+    Json(request): Json<SetActiveRoute>,
+) -> ApiResult {
+    let route = state
+        .resources
+        .route(&request.href)
+        .await
+        .map_err(storage_error)?
+        .ok_or_else(|| {
+            ActionResponse::failed(
+                StatusCode::BAD_REQUEST,
+                format!("{} is not a route", request.href),
+            )
+        })?;
+    let mut points = route.points();
+    if request.reverse {
+        points.reverse();
+    }
+    let Some(&(latitude, longitude)) = points.get(request.point_index) else {
+        return Err(ActionResponse::failed(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Point {} is beyond the route's {} points",
+                request.point_index,
+                points.len()
+            ),
+        ));
+    };
+
+    let course = {
+        let store = state.store.read();
+        let current = CourseInfo::load(&*store);
+        CourseInfo {
+            start_time: Some(now()),
+            target_arrival_time: None,
+            arrival_circle: request.arrival_circle.unwrap_or(current.arrival_circle),
+            active_route: Some(ActiveRoute {
+                href: request.href,
+                name: route.name,
+                point_index: request.point_index,
+                point_total: points.len(),
+                reverse: request.reverse,
+            }),
+            next_point: Some(CoursePoint {
+                href: None,
+                point_type: PointType::RoutePoint,
+                position: Position {
+                    latitude,
+                    longitude,
+                },
+            }),
+            previous_point: vessel_position(&*store),
+        }
+    };
+    save(&state, course).await
+}
+
+/// PUT /signalk/v2/api/vessels/self/navigation/course/arrivalCircle
+async fn set_arrival_circle(
+    State(state): State<AppState>,
+    Json(request): Json<SetArrivalCircle>,
+) -> ApiResult {
+    if !(request.value.is_finite() && request.value >= 0.0) {
+        return Err(ActionResponse::failed(
+            StatusCode::BAD_REQUEST,
+            "The arrival circle must be a radius in metres".to_string(),
+        ));
+    }
+    let course = CourseInfo {
+        arrival_circle: request.value,
+        ..CourseInfo::load(&*state.store.read())
+    };
+    save(&state, course).await
+}
+
+async fn save(state: &AppState, course: CourseInfo) -> ApiResult {
+    state.apply_delta(course.to_delta(&now())).await;
+    Ok(Json(ActionResponse::completed(StatusCode::OK)))
+}
+
+/// Where the vessel is now, as a course's previous point.
+fn vessel_position(store: &impl SignalKStore) -> Option<CoursePoint> {
+    let position = store
+        .get_self_path("navigation.position")
+        .and_then(|node| node.get("value").cloned())
+        .and_then(|value| serde_json::from_value::<Position>(value).ok())?;
+    Some(CoursePoint {
+        href: None,
+        point_type: PointType::VesselPosition,
+        position,
+    })
+}
+
+fn valid(position: Position) -> bool {
+    (-90.0..=90.0).contains(&position.latitude) && (-180.0..=180.0).contains(&position.longitude)
+}
+
+/// `value` as JSON; `null` if it can't be.
+fn json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn storage_error(e: signalk_core::ConfigError) -> (StatusCode, Json<ActionResponse>) {
+    ActionResponse::failed(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::v2::resources::ResourceType;
+    use crate::{WebConfig, WebState};
+    use serde_json::json;
+    use signalk_core::{MemoryStore, SharedStore};
+    use std::sync::{Arc, Mutex};
+
+    fn state() -> (AppState, Arc<Mutex<Vec<Delta>>>) {
+        let mut store = MemoryStore::new("vessels.urn:mrn:signalk:uuid:self");
+        store.apply_delta(&Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some("gps".to_string()),
+                source: None,
+                timestamp: Some("2024-01-17T10:00:00.000Z".to_string()),
+                values: vec![PathValue {
+                    path: "navigation.position".to_string(),
+                    value: json!({ "latitude": 52.1012, "longitude": 4.9841 }),
+                }],
+                meta: None,
+                server_timestamp: None,
+            }],
+        });
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let mut state = WebState::new(Arc::new(SharedStore::new(store)), WebConfig::default());
+        state.delta_sink = Some(sink.clone());
+        (Arc::new(state), sink)
+    }
+
+    async fn course(state: &AppState) -> CourseInfo {
+        get_course(State(state.clone())).await.0
+    }
+
+    #[tokio::test]
+    async fn test_set_destination_and_read_back() {
+        let (state, sink) = state();
+        let request = SetDestination {
+            href: None,
+            position: Some(Position {
+                latitude: 52.0987,
+                longitude: 4.9876,
+            }),
+            arrival_circle: Some(50.0),
+        };
+        let Json(response) = set_destination(State(state.clone()), Json(request))
+            .await
+            .unwrap();
+        assert_eq!(response.state, "COMPLETED");
+
+        let course = course(&state).await;
+        let next = course.next_point.unwrap();
+        assert_eq!(next.point_type, PointType::Location);
+        assert_eq!(next.position.latitude, 52.0987);
+        let previous = course.previous_point.unwrap();
+        assert_eq!(previous.point_type, PointType::VesselPosition);
+        assert_eq!(previous.position.longitude, 4.9841);
+        assert_eq!(course.arrival_circle, 50.0);
+        assert!(course.start_time.is_some());
+        assert_eq!(course.active_route, None);
+
+        // Submitted for broadcast as a delta on navigation.course
+        let update = sink.lock().unwrap()[0].updates[0].clone();
+        assert_eq!(update.source_ref.as_deref(), Some(COURSE_SOURCE));
+        assert!(update
+            .values
+            .iter()
+            .any(|pv| pv.path == "navigation.course.nextPoint"));
+
+        // The arrival circle outlives clearing the course
+        let _ = clear_course(State(state.clone())).await.unwrap();
+        let course = self::course(&state).await;
+        assert_eq!(course.next_point, None);
+        assert_eq!(course.previous_point, None);
+        assert_eq!(course.arrival_circle, 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_destination_from_waypoint() {
+        let (state, _) = state();
+        state
+            .resources
+            .put(
+                ResourceType::Waypoints,
+                "harbour",
+                json!({
+                    "feature": {
+                        "type": "Feature",
+                        "geometry": { "type": "Point", "coordinates": [4.9876, 52.0987] },
+                    },
+                }),
+            )
+            .await
+            .unwrap();
+
+        let request = SetDestination {
+            href: Some("/resources/waypoints/harbour".to_string()),
+            position: None,
+            arrival_circle: None,
+        };
+        let _ = set_destination(State(state.clone()), Json(request))
+            .await
+            .unwrap();
+        let next = course(&state).await.next_point.unwrap();
+        assert_eq!(next.point_type, PointType::Waypoint);
+        assert_eq!(next.href.as_deref(), Some("/resources/waypoints/harbour"));
+        assert_eq!(
+            (next.position.latitude, next.position.longitude),
+            (52.0987, 4.9876)
+        );
+
+        for request in [
+            SetDestination {
+                href: Some("/resources/waypoints/missing".to_string()),
+                position: None,
+                arrival_circle: None,
+            },
+            SetDestination {
+                href: None,
+                position: None,
+                arrival_circle: None,
+            },
+        ] {
+            let rejected = set_destination(State(state.clone()), Json(request)).await;
+            assert_eq!(rejected.unwrap_err().0, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_active_route() {
+        let (state, _) = state();
+        state
+            .resources
+            .put(
+                ResourceType::Routes,
+                "channel",
+                json!({
+                    "name": "Channel",
+                    "feature": {
+                        "type": "Feature",
+                        "geometry": {
+                            "type": "LineString",
+                            "coordinates": [[4.98, 52.09], [4.99, 52.10], [5.00, 52.11]],
+                        },
+                    },
+                }),
+            )
+            .await
+            .unwrap();
+
+        let request = SetActiveRoute {
+            href: "/resources/routes/channel".to_string(),
+            point_index: 1,
+            reverse: true,
+            arrival_circle: None,
+        };
+        let _ = set_active_route(State(state.clone()), Json(request))
+            .await
+            .unwrap();
+        let course = course(&state).await;
+        let route = course.active_route.unwrap();
+        assert_eq!(route.name.as_deref(), Some("Channel"));
+        assert_eq!((route.point_index, route.point_total), (1, 3));
+        assert!(route.reverse);
+        let next = course.next_point.unwrap();
+        assert_eq!(next.point_type, PointType::RoutePoint);
+        // Second point, counted from either end
+        assert_eq!(next.position.latitude, 52.10);
+
+        let request = SetActiveRoute {
+            href: "/resources/routes/channel".to_string(),
+            point_index: 3,
+            reverse: false,
+            arrival_circle: None,
+        };
+        let rejected = set_active_route(State(state), Json(request)).await;
+        assert_eq!(rejected.unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,74 @@
+//! Signal K v2 API routes, mounted at `/signalk/v2/api`.
+//!
+//! Two v2 groups are implemented: the course API
+//! (`/vessels/self/navigation/course`), kept in the store under
+//! `navigation.course`, and resources (`/resources/routes`,
+//! `/resources/waypoints`), kept in config storage.
+//!
+//! Requests that change something answer with an [`ActionResponse`]:
+//!
+//! ```json
+//! { "state": "COMPLETED", "statusCode": 200 }
+//! ```
+
+pub mod course;
+pub mod resources;
+
+use axum::{http::StatusCode, response::Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+
+/// Create the v2 API routes.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .nest("/vessels/self/navigation/course", course::routes())
+        .nest("/resources", resources::routes())
+}
+
+/// Outcome of a v2 request that changes something.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionResponse {
+    /// `COMPLETED` or `FAILED`.
+    pub state: String,
+    /// The HTTP status, repeated.
+    pub status_code: u16,
+    /// Why the request failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Id of the resource created or changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+impl ActionResponse {
+    /// A request that succeeded with `status`.
+    pub fn completed(status: StatusCode) -> Self {
+        Self {
+            state: "COMPLETED".to_string(),
+            status_code: status.as_u16(),
+            message: None,
+            id: None,
+        }
+    }
+
+    /// A request that failed with `status`, as a response.
+    pub fn failed(status: StatusCode, message: String) -> (StatusCode, Json<Self>) {
+        (
+            status,
+            Json(Self {
+                state: "FAILED".to_string(),
+                status_code: status.as_u16(),
+                message: Some(message),
+                id: None,
+            }),
+        )
+    }
+
+    /// Name the resource the request created or changed.
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = Some(id);
+        self
+    }
+}
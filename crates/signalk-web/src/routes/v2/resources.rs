@@ -0,0 +1,513 @@
+//! Signal K v2 resources: routes and waypoints.
+//!
+//! Resources are GeoJSON features kept by the server, each under an id.
+//! They are stored through a [`ResourceStore`], one JSON file per type
+//! (`resources/routes.json`, `resources/waypoints.json`).
+//!
+//! # Endpoints
+//!
+//! ### `GET /signalk/v2/api/resources/:type`
+//! All resources of a type, keyed by id.
+//!
+//! ### `POST /signalk/v2/api/resources/:type`
+//! Create a resource under a new id.
+//!
+//! **Request body (waypoint):**
+//! ```json
+//! {
+//!   "name": "Harbour entrance",
+//!   "feature": {
+//!     "type": "Feature",
+//!     "geometry": { "type": "Point", "coordinates": [4.9876, 52.0987] },
+//!     "properties": {}
+//!   }
+//! }
+//! ```
+//!
+//! **Response (201):**
+//! ```json
+//! { "state": "COMPLETED", "statusCode": 201, "id": "ac3a3b2d-07e8-4f25-92bc-98e7c92f7f1a" }
+//! ```
+//!
+//! ### `GET /signalk/v2/api/resources/:type/:id`
+//! One resource.
+//!
+//! ### `PUT /signalk/v2/api/resources/:type/:id`
+//! Create or replace a resource under the given id.
+//!
+//! ### `DELETE /signalk/v2/api/resources/:type/:id`
+//! Remove a resource.
+//!
+//! `:type` is `routes` or `waypoints`; routes have a `LineString` geometry,
+//! waypoints a `Point`. Coordinates are `[longitude, latitude]`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use signalk_core::{ConfigError, ConfigStorage};
+use tokio::sync::Mutex;
+
+use super::ActionResponse;
+use crate::AppState;
+
+/// Resource types the server keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceType {
+    Routes,
+    Waypoints,
+}
+
+impl ResourceType {
+    /// Config storage key of the resources of this type.
+    pub fn storage_key(self) -> String {
+        format!("resources/{self}.json")
+    }
+}
+
+impl fmt::Display for ResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Routes => "routes",
+            Self::Waypoints => "waypoints",
+        })
+    }
+}
+
+/// GeoJSON geometry of a resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    /// `[longitude, latitude]`
+    Point { coordinates: [f64; 2] },
+    /// `[[longitude, latitude], ...]`
+    LineString { coordinates: Vec<[f64; 2]> },
+}
+
+/// A GeoJSON feature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feature {
+    /// Always `Feature`.
+    #[serde(rename = "type", default = "feature_type")]
+    pub feature_type: String,
+    pub geometry: Geometry,
+    #[serde(default)]
+    pub properties: Map<String, Value>,
+}
+
+fn feature_type() -> String {
+    "Feature".to_string()
+}
+
+/// A waypoint resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waypoint {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Kind of waypoint, e.g. `PoI`.
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub waypoint_type: Option<String>,
+    /// A `Point` feature.
+    pub feature: Feature,
+}
+
+impl Waypoint {
+    /// `(latitude, longitude)` of the waypoint.
+    pub fn position(&self) -> Option<(f64, f64)> {
+        match self.feature.geometry {
+            Geometry::Point {
+                coordinates: [longitude, latitude],
+            } => Some((latitude, longitude)),
+            Geometry::LineString { .. } => None,
+        }
+    }
+}
+
+/// A route resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Total length in metres.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    /// A `LineString` feature.
+    pub feature: Feature,
+}
+
+impl Route {
+    /// `(latitude, longitude)` of each point, in order.
+    pub fn points(&self) -> Vec<(f64, f64)> {
+        match &self.feature.geometry {
+            Geometry::LineString { coordinates } => coordinates
+                .iter()
+                .map(|&[longitude, latitude]| (latitude, longitude))
+                .collect(),
+            Geometry::Point { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Where resources are persisted, one map of id to resource per type.
+///
+/// Implemented for every [`ConfigStorage`].
+pub trait ResourceStore: Send + Sync {
+    /// All resources of `kind`; empty if none were saved.
+    fn load_resources(&self, kind: ResourceType) -> Result<BTreeMap<String, Value>, ConfigError>;
+
+    /// Replace the resources of `kind`.
+    fn save_resources(
+        &self,
+        kind: ResourceType,
+        resources: &BTreeMap<String, Value>,
+    ) -> Result<(), ConfigError>;
+}
+
+impl<S: ConfigStorage> ResourceStore for S {
+    fn load_resources(&self, kind: ResourceType) -> Result<BTreeMap<String, Value>, ConfigError> {
+        match self.load_value(&kind.storage_key()) {
+            Err(ConfigError::NotFound(_)) => Ok(BTreeMap::new()),
+            loaded => loaded,
+        }
+    }
+
+    fn save_resources(
+        &self,
+        kind: ResourceType,
+        resources: &BTreeMap<String, Value>,
+    ) -> Result<(), ConfigError> {
+        self.save_value(&kind.storage_key(), resources)
+    }
+}
+
+/// The server's resources, over a [`ResourceStore`].
+pub struct Resources {
+    store: Arc<dyn ResourceStore>,
+    /// Serializes read-modify-write cycles on the store.
+    lock: Mutex<()>,
+}
+
+impl Resources {
+    /// Keep resources in `store`.
+    pub fn new(store: Arc<dyn ResourceStore>) -> Self {
+        Self {
+            store,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// All resources of `kind`, keyed by id.
+    pub async fn list(&self, kind: ResourceType) -> Result<BTreeMap<String, Value>, ConfigError> {
+        let _guard = self.lock.lock().await;
+        self.store.load_resources(kind)
+    }
+
+    /// The resource `id` of `kind`, if there is one.
+    pub async fn get(&self, kind: ResourceType, id: &str) -> Result<Option<Value>, ConfigError> {
+        Ok(self.list(kind).await?.remove(id))
+    }
+
+    /// Store `resource` as `id`, replacing any resource there was.
+    pub async fn put(
+        &self,
+        kind: ResourceType,
+        id: &str,
+        resource: Value,
+    ) -> Result<(), ConfigError> {
+        let _guard = self.lock.lock().await;
+        let mut resources = self.store.load_resources(kind)?;
+        resources.insert(id.to_string(), resource);
+        self.store.save_resources(kind, &resources)
+    }
+
+    /// Remove `id`; `false` if there was no such resource.
+    pub async fn delete(&self, kind: ResourceType, id: &str) -> Result<bool, ConfigError> {
+        let _guard = self.lock.lock().await;
+        let mut resources = self.store.load_resources(kind)?;
+        if resources.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.store.save_resources(kind, &resources)?;
+        Ok(true)
+    }
+
+    /// The waypoint an `href` such as `/resources/waypoints/<id>` names.
+    pub async fn waypoint(&self, href: &str) -> Result<Option<Waypoint>, ConfigError> {
+        self.resolve(href, ResourceType::Waypoints).await
+    }
+
+    /// The route an `href` such as `/resources/routes/<id>` names.
+    pub async fn route(&self, href: &str) -> Result<Option<Route>, ConfigError> {
+        self.resolve(href, ResourceType::Routes).await
+    }
+
+    async fn resolve<T: serde::de::DeserializeOwned>(
+        &self,
+        href: &str,
+        kind: ResourceType,
+    ) -> Result<Option<T>, ConfigError> {
+        let prefix = format!("/resources/{kind}/");
+        let Some(id) = href.strip_prefix(&prefix) else {
+            return Ok(None);
+        };
+        Ok(self
+            .get(kind, id)
+            .await?
+            .and_then(|resource| serde_json::from_value(resource).ok()))
+    }
+}
+
+/// Create the resource routes.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/:type", get(list_resources).post(create_resource))
+        .route(
+            "/:type/:id",
+            get(get_resource).put(put_resource).delete(delete_resource),
+        )
+}
+
+type ApiResult<T> = Result<T, (StatusCode, Json<ActionResponse>)>;
+
+/// GET /signalk/v2/api/resources/:type
+async fn list_resources(
+    State(state): State<AppState>,
+    Path(kind): Path<String>,
+) -> ApiResult<Json<BTreeMap<String, Value>>> {
+    let kind = resource_type(&kind)?;
+    let resources = state.resources.list(kind).await.map_err(storage_error)?;
+    Ok(Json(resources))
+}
+
+/// POST /signalk/v2/api/resources/:type
+async fn create_resource(
+    State(state): State<AppState>,
+    Path(kind): Path<String>,
+    Json(resource): Json<Value>,
+) -> ApiResult<(StatusCode, Json<ActionResponse>)> {
+    let kind = resource_type(&kind)?;
+    let resource = validate(kind, resource)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    state
+        .resources
+        .put(kind, &id, resource)
+        .await
+        .map_err(storage_error)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ActionResponse::completed(StatusCode::CREATED).with_id(id)),
+    ))
+}
+
+/// GET /signalk/v2/api/resources/:type/:id
+async fn get_resource(
+    State(state): State<AppState>,
+    Path((kind, id)): Path<(String, String)>,
+) -> ApiResult<Json<Value>> {
+    let kind = resource_type(&kind)?;
+    match state
+        .resources
+        .get(kind, &id)
+        .await
+        .map_err(storage_error)?
+    {
+        Some(resource) => Ok(Json(resource)),
+        None => Err(ActionResponse::failed(
+            StatusCode::NOT_FOUND,
+            format!("No {kind} resource {id}"),
+        )),
+    }
+}
+
+/// PUT /signalk/v2/api/resources/:type/:id
+async fn put_resource(
+    State(state): State<AppState>,
+    Path((kind, id)): Path<(String, String)>,
+    Json(resource): Json<Value>,
+) -> ApiResult<Json<ActionResponse>> {
+    let kind = resource_type(&kind)?;
+    let resource = validate(kind, resource)?;
+    state
+        .resources
+        .put(kind, &id, resource)
+        .await
+        .map_err(storage_error)?;
+    Ok(Json(ActionResponse::completed(StatusCode::OK).with_id(id)))
+}
+
+/// DELETE /signalk/v2/api/resources/:type/:id
+async fn delete_resource(
+    State(state): State<AppState>,
+    Path((kind, id)): Path<(String, String)>,
+) -> ApiResult<Json<ActionResponse>> {
+    let kind = resource_type(&kind)?;
+    if state
+        .resources
+        .delete(kind, &id)
+        .await
+        .map_err(storage_error)?
+    {
+        Ok(Json(ActionResponse::completed(StatusCode::OK)))
+    } else {
+        Err(ActionResponse::failed(
+            StatusCode::NOT_FOUND,
+            format!("No {kind} resource {id}"),
+        ))
+    }
+}
+
+fn resource_type(kind: &str) -> ApiResult<ResourceType> {
+    serde_json::from_value(Value::from(kind)).map_err(|_| {
+        ActionResponse::failed(
+            StatusCode::NOT_FOUND,
+            format!("Unsupported resource type {kind}"),
+        )
+    })
+}
+
+/// Check `resource` against the schema of `kind`, returning it normalized.
+fn validate(kind: ResourceType, resource: Value) -> ApiResult<Value> {
+    let invalid = |message: String| ActionResponse::failed(StatusCode::BAD_REQUEST, message);
+    let normalized = match kind {
+        ResourceType::Waypoints => {
+            let waypoint: Waypoint =
+                serde_json::from_value(resource).map_err(|e| invalid(e.to_string()))?;
+            if waypoint.position().is_none() {
+                return Err(invalid("A waypoint needs a Point geometry".to_string()));
+            }
+            serde_json::to_value(waypoint)
+        }
+        ResourceType::Routes => {
+            let route: Route =
+                serde_json::from_value(resource).map_err(|e| invalid(e.to_string()))?;
+            if route.points().len() < 2 {
+                return Err(invalid(
+                    "A route needs a LineString geometry of at least two points".to_string(),
+                ));
+            }
+            serde_json::to_value(route)
+        }
+    };
+    normalized.map_err(|e| invalid(e.to_string()))
+}
+
+fn storage_error(e: ConfigError) -> (StatusCode, Json<ActionResponse>) {
+    ActionResponse::failed(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{WebConfig, WebState};
+    use serde_json::json;
+    use signalk_core::{MemoryConfigStorage, MemoryStore, SharedStore};
+
+    fn state() -> (AppState, Arc<MemoryConfigStorage>) {
+        let storage = Arc::new(MemoryConfigStorage::default());
+        let store = Arc::new(SharedStore::new(MemoryStore::new("vessels.self")));
+        let mut state = WebState::new(store, WebConfig::default());
+        state.resources = Resources::new(storage.clone());
+        (Arc::new(state), storage)
+    }
+
+    fn waypoint() -> Value {
+        json!({
+            "name": "Harbour entrance",
+            "feature": {
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [4.9876, 52.0987] },
+            },
+        })
+    }
+
+    fn path(kind: &str) -> Path<String> {
+        Path(kind.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_create_and_list_waypoint() {
+        let (state, storage) = state();
+
+        let (status, Json(created)) =
+            create_resource(State(state.clone()), path("waypoints"), Json(waypoint()))
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created.state, "COMPLETED");
+        let id = created.id.unwrap();
+
+        let Json(waypoints) = list_resources(State(state.clone()), path("waypoints"))
+            .await
+            .unwrap();
+        assert_eq!(waypoints.len(), 1);
+        assert_eq!(waypoints[&id]["name"], "Harbour entrance");
+        assert_eq!(
+            waypoints[&id]["feature"]["geometry"]["coordinates"],
+            json!([4.9876, 52.0987])
+        );
+
+        // Persisted through the config storage
+        let saved: BTreeMap<String, Value> =
+            storage.load_value("resources/waypoints.json").unwrap();
+        assert!(saved.contains_key(&id));
+
+        let waypoint = state
+            .resources
+            .waypoint(&format!("/resources/waypoints/{id}"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(waypoint.position(), Some((52.0987, 4.9876)));
+
+        let Json(deleted) = delete_resource(
+            State(state.clone()),
+            Path(("waypoints".to_string(), id.clone())),
+        )
+        .await
+        .unwrap();
+        assert_eq!(deleted.status_code, 200);
+        let missing = get_resource(State(state), Path(("waypoints".to_string(), id))).await;
+        assert_eq!(missing.unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_resources_rejected() {
+        let (state, _) = state();
+
+        // A route with a single point
+        let route = json!({
+            "feature": {
+                "type": "Feature",
+                "geometry": { "type": "LineString", "coordinates": [[4.98, 52.09]] },
+            },
+        });
+        let rejected = create_resource(State(state.clone()), path("routes"), Json(route)).await;
+        assert_eq!(rejected.unwrap_err().0, StatusCode::BAD_REQUEST);
+
+        // A waypoint without a feature
+        let rejected = create_resource(
+            State(state.clone()),
+            path("waypoints"),
+            Json(json!({ "name": "Nowhere" })),
+        )
+        .await;
+        assert_eq!(rejected.unwrap_err().0, StatusCode::BAD_REQUEST);
+
+        let unsupported = list_resources(State(state), path("charts")).await;
+        assert_eq!(unsupported.unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+}
@@ -9,6 +9,7 @@
 //! - `DEBUG_SETTINGS` - Debug configuration
 //! - `RECEIVE_LOGIN_STATUS` - Authentication status
 //! - `SOURCEPRIORITIES` - Source priority settings
+//! - `SERVERSETTINGS` - Server settings (sent when they are saved)
 //! - `LOG` - Real-time log entries
 //!
 //! ## Message Formats
@@ -58,6 +59,12 @@ pub enum ServerEvent {
     #[serde(rename = "SOURCEPRIORITIES")]
     SourcePriorities { data: SourcePriorities },
 
+    /// Server settings, with defaults filled in (sent when they are saved).
+    #[serde(rename = "SERVERSETTINGS")]
+    ServerSettings {
+        data: Box<signalk_core::ServerSettings>,
+    },
+
     /// Log entry (sent in real-time).
     #[serde(rename = "LOG")]
     Log { data: LogEntry },
@@ -116,11 +123,9 @@ pub struct DebugSettings {
     pub remember_debug: bool,
 }
 
-/// Source priorities for SOURCEPRIORITIES event.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct SourcePriorities {
-    // Empty object for now - can be expanded later
-}
+/// Source priorities for SOURCEPRIORITIES event: the configured
+/// preferred sources per path.
+pub use signalk_core::SourcePriorities;
 
 /// Server performance statistics.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -141,6 +146,49 @@ pub struct ServerStatistics {
     /// Per-provider statistics.
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub provider_statistics: Vec<ProviderStatistics>,
+
+    /// Busiest paths by delta rate, busiest first.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub path_statistics: Vec<RateStatistics>,
+
+    /// Busiest sources by delta rate, busiest first.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub source_statistics: Vec<RateStatistics>,
+}
+
+/// A connected WebSocket client, as `GET /skServer/connections` lists it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientConnection {
+    /// Id assigned on connect.
+    pub id: u64,
+
+    /// Client address, when known.
+    pub remote_address: Option<String>,
+
+    /// When the client connected (RFC 3339).
+    pub connected_at: String,
+
+    /// Number of paths the client is subscribed to; 0 for a client
+    /// receiving deltas unfiltered.
+    pub subscriptions: usize,
+
+    /// Bytes sent to the client.
+    pub bytes_sent: u64,
+}
+
+/// Throughput of a single path or source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateStatistics {
+    /// Path or source reference.
+    pub id: String,
+
+    /// Updates in the last second.
+    pub delta_rate: f64,
+
+    /// Updates since it was first seen (or last evicted).
+    pub delta_count: u64,
 }
 
 /// Statistics for a single data provider.
@@ -170,6 +218,10 @@ pub struct ProviderStatus {
     /// Error message if any.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+
+    /// When the provider last delivered data (ISO 8601).
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub last_message: Option<String>,
 }
 
 /// Log entry for real-time log streaming.
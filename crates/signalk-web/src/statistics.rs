@@ -2,18 +2,36 @@
 //!
 //! This module collects and tracks server performance metrics:
 //! - Delta throughput (deltas per second)
+//! - Throughput per context, path and source
 //! - Active path count
-//! - WebSocket client count
+//! - WebSocket client count, and details of each connected client
 //! - Per-provider statistics
 //! - Server uptime
 //!
 //! Statistics are collected continuously and broadcast to Admin UI
-//! clients via the server events WebSocket.
+//! clients via the server events WebSocket, and can be rendered in the
+//! Prometheus text format for `GET /metrics`.
 
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use crate::server_events::{ProviderStatistics, ServerStatistics};
+use chrono::{DateTime, SecondsFormat, Utc};
+use signalk_core::Delta;
+
+use crate::server_events::{
+    ClientConnection, ProviderStatistics, RateStatistics, ServerStatistics,
+};
+
+/// Most contexts (and, separately, paths and sources) tracked for their
+/// rates; the least recently updated is evicted beyond this.
+pub const MAX_TRACKED_RATES: usize = 512;
+
+/// Number of busiest paths and sources in [`StatisticsCollector::snapshot_detailed`].
+pub const DETAILED_TOP_N: usize = 10;
 
 /// Collects and tracks server statistics.
 pub struct StatisticsCollector {
@@ -34,6 +52,180 @@ pub struct StatisticsCollector {
 
     /// Connected WebSocket clients.
     ws_clients: AtomicUsize,
+
+    /// Delta counts per context, bounded.
+    contexts: Mutex<RateTable>,
+
+    /// Value counts per path, bounded.
+    paths: Mutex<RateTable>,
+
+    /// Update counts per source, bounded.
+    sources: Mutex<RateTable>,
+
+    /// Deltas dropped before reaching the store.
+    dropped_deltas: AtomicU64,
+
+    /// Broadcast messages skipped by WebSocket clients that fell behind.
+    lagged_messages: AtomicU64,
+
+    /// Registered WebSocket clients by id.
+    clients: Mutex<BTreeMap<u64, Arc<ClientCounters>>>,
+
+    /// Id of the next registered client.
+    next_client_id: AtomicU64,
+}
+
+/// What is tracked of one registered WebSocket client.
+#[derive(Debug)]
+struct ClientCounters {
+    remote: Option<SocketAddr>,
+    connected_at: DateTime<Utc>,
+    subscriptions: AtomicUsize,
+    bytes_sent: AtomicU64,
+}
+
+/// A WebSocket client registered with
+/// [`StatisticsCollector::register_client`].
+///
+/// The client is listed until the handle is dropped, so a connection that
+/// ends in any way, including an aborted task, is cleaned up.
+pub struct ClientHandle {
+    id: u64,
+    counters: Arc<ClientCounters>,
+    statistics: Arc<StatisticsCollector>,
+}
+
+impl ClientHandle {
+    /// The client's id, unique for the server's lifetime.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Record `bytes` sent to the client.
+    pub fn record_sent(&self, bytes: usize) {
+        self.counters
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Set the number of paths the client is subscribed to.
+    pub fn set_subscriptions(&self, count: usize) {
+        self.counters.subscriptions.store(count, Ordering::Relaxed);
+    }
+
+    /// The number of paths the client is subscribed to.
+    pub fn subscriptions(&self) -> usize {
+        self.counters.subscriptions.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        lock(&self.statistics.clients).remove(&self.id);
+        self.statistics.client_disconnected();
+    }
+}
+
+/// Delta counts for one context, path or source.
+#[derive(Debug, Default)]
+struct DeltaRateCounter {
+    total: u64,
+    window: u64,
+    rate: f64,
+    /// [`RateTable`] clock at the last update, for eviction.
+    last_used: u64,
+}
+
+impl DeltaRateCounter {
+    fn record(&mut self) {
+        self.total += 1;
+        self.window += 1;
+    }
+
+    /// Close the one-second window.
+    fn roll(&mut self) {
+        self.rate = std::mem::take(&mut self.window) as f64;
+    }
+}
+
+/// Counters keyed by context, path or source, evicting the least recently
+/// updated once `capacity` keys are tracked.
+#[derive(Debug)]
+struct RateTable {
+    capacity: usize,
+    clock: u64,
+    counters: HashMap<String, DeltaRateCounter>,
+}
+
+impl RateTable {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            clock: 0,
+            counters: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, key: &str) {
+        self.clock += 1;
+        if !self.counters.contains_key(key) {
+            if self.capacity == 0 {
+                return;
+            }
+            if self.counters.len() >= self.capacity {
+                self.evict();
+            }
+            self.counters
+                .insert(key.to_string(), DeltaRateCounter::default());
+        }
+        let counter = self.counters.get_mut(key).expect("just inserted");
+        counter.last_used = self.clock;
+        counter.record();
+    }
+
+    /// Drop the least recently updated counter.
+    fn evict(&mut self) {
+        let oldest = self
+            .counters
+            .iter()
+            .min_by_key(|(_, counter)| counter.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            self.counters.remove(&oldest);
+        }
+    }
+
+    fn roll(&mut self) {
+        self.counters.values_mut().for_each(DeltaRateCounter::roll);
+    }
+
+    /// Every counter, by key.
+    fn sorted(&self) -> Vec<(&String, &DeltaRateCounter)> {
+        let mut counters: Vec<_> = self.counters.iter().collect();
+        counters.sort_by_key(|(key, _)| *key);
+        counters
+    }
+
+    /// The `n` counters with the highest rate, then the highest total.
+    fn top(&self, n: usize) -> Vec<RateStatistics> {
+        let mut top: Vec<RateStatistics> = self
+            .counters
+            .iter()
+            .map(|(id, counter)| RateStatistics {
+                id: id.clone(),
+                delta_rate: counter.rate,
+                delta_count: counter.total,
+            })
+            .collect();
+        top.sort_by(|a, b| {
+            b.delta_rate
+                .total_cmp(&a.delta_rate)
+                .then(b.delta_count.cmp(&a.delta_count))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        top.truncate(n);
+        top
+    }
 }
 
 impl StatisticsCollector {
@@ -46,13 +238,59 @@ impl StatisticsCollector {
             delta_rate: AtomicU64::new(0),
             active_paths: AtomicUsize::new(0),
             ws_clients: AtomicUsize::new(0),
+            contexts: Mutex::new(RateTable::new(MAX_TRACKED_RATES)),
+            paths: Mutex::new(RateTable::new(MAX_TRACKED_RATES)),
+            sources: Mutex::new(RateTable::new(MAX_TRACKED_RATES)),
+            dropped_deltas: AtomicU64::new(0),
+            lagged_messages: AtomicU64::new(0),
+            clients: Mutex::new(BTreeMap::new()),
+            next_client_id: AtomicU64::new(1),
         }
     }
 
     /// Record a delta being processed.
-    pub fn record_delta(&self) {
+    ///
+    /// Counts the delta for its context, each of its values for their path
+    /// and each of its updates for their source (`$source`, or the source
+    /// label when the update has none).
+    pub fn record_delta(&self, delta: &Delta) {
+        self.record_context_delta(delta.context.as_deref().unwrap_or("vessels.self"));
+        {
+            let mut paths = lock(&self.paths);
+            for update in &delta.updates {
+                for pv in &update.values {
+                    paths.record(&pv.path);
+                }
+            }
+        }
+        let mut sources = lock(&self.sources);
+        for update in &delta.updates {
+            let source = update
+                .source_ref
+                .as_deref()
+                .or_else(|| update.source.as_ref().map(|s| s.label.as_str()));
+            if let Some(source) = source {
+                sources.record(source);
+            }
+        }
+    }
+
+    /// Record a delta being processed for `context`, without per-path or
+    /// per-source details.
+    pub fn record_context_delta(&self, context: &str) {
         self.total_deltas.fetch_add(1, Ordering::Relaxed);
         self.window_deltas.fetch_add(1, Ordering::Relaxed);
+        lock(&self.contexts).record(context);
+    }
+
+    /// Record a delta dropped before reaching the store.
+    pub fn record_dropped(&self) {
+        self.dropped_deltas.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record `count` broadcast messages skipped by a lagging client.
+    pub fn record_lagged(&self, count: u64) {
+        self.lagged_messages.fetch_add(count, Ordering::Relaxed);
     }
 
     /// Update the delta rate calculation (call once per second).
@@ -60,6 +298,9 @@ impl StatisticsCollector {
         let window = self.window_deltas.swap(0, Ordering::Relaxed);
         self.delta_rate
             .store((window as f64).to_bits(), Ordering::Relaxed);
+        lock(&self.contexts).roll();
+        lock(&self.paths).roll();
+        lock(&self.sources).roll();
     }
 
     /// Set the number of active paths.
@@ -77,6 +318,42 @@ impl StatisticsCollector {
         self.ws_clients.fetch_sub(1, Ordering::Relaxed);
     }
 
+    /// Register a connected WebSocket client, counting it like
+    /// [`client_connected`](Self::client_connected) until the returned
+    /// handle is dropped.
+    pub fn register_client(self: &Arc<Self>, remote: Option<SocketAddr>) -> ClientHandle {
+        let id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        let counters = Arc::new(ClientCounters {
+            remote,
+            connected_at: Utc::now(),
+            subscriptions: AtomicUsize::new(0),
+            bytes_sent: AtomicU64::new(0),
+        });
+        lock(&self.clients).insert(id, counters.clone());
+        self.client_connected();
+        ClientHandle {
+            id,
+            counters,
+            statistics: self.clone(),
+        }
+    }
+
+    /// The registered WebSocket clients, oldest first.
+    pub fn connections(&self) -> Vec<ClientConnection> {
+        lock(&self.clients)
+            .iter()
+            .map(|(id, client)| ClientConnection {
+                id: *id,
+                remote_address: client.remote.map(|remote| remote.to_string()),
+                connected_at: client
+                    .connected_at
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+                subscriptions: client.subscriptions.load(Ordering::Relaxed),
+                bytes_sent: client.bytes_sent.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
     /// Get current statistics snapshot.
     pub fn snapshot(&self) -> ServerStatistics {
         ServerStatistics {
@@ -85,8 +362,114 @@ impl StatisticsCollector {
             ws_clients: self.ws_clients.load(Ordering::Relaxed),
             uptime: self.start_time.elapsed().as_secs(),
             provider_statistics: Vec::new(), // TODO: Collect per-provider stats
+            path_statistics: Vec::new(),
+            source_statistics: Vec::new(),
         }
     }
+
+    /// [`snapshot`](Self::snapshot) plus the [`DETAILED_TOP_N`] busiest
+    /// paths and sources, for the Admin UI Data Browser.
+    pub fn snapshot_detailed(&self) -> ServerStatistics {
+        ServerStatistics {
+            path_statistics: lock(&self.paths).top(DETAILED_TOP_N),
+            source_statistics: lock(&self.sources).top(DETAILED_TOP_N),
+            ..self.snapshot()
+        }
+    }
+
+    /// Render the statistics in the Prometheus text exposition format.
+    pub fn prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = write!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+            );
+        };
+        metric(
+            "signalk_deltas_total",
+            "counter",
+            "Deltas processed since start.",
+            self.total_deltas.load(Ordering::Relaxed) as f64,
+        );
+        metric(
+            "signalk_delta_rate",
+            "gauge",
+            "Deltas processed in the last second.",
+            snapshot.delta_rate,
+        );
+        metric(
+            "signalk_ws_clients",
+            "gauge",
+            "Connected WebSocket clients.",
+            snapshot.ws_clients as f64,
+        );
+        metric(
+            "signalk_active_paths",
+            "gauge",
+            "Paths with values in the store.",
+            snapshot.number_of_available_paths as f64,
+        );
+        metric(
+            "signalk_deltas_dropped_total",
+            "counter",
+            "Deltas dropped before reaching the store.",
+            self.dropped_deltas.load(Ordering::Relaxed) as f64,
+        );
+        metric(
+            "signalk_ws_lagged_messages_total",
+            "counter",
+            "Messages skipped by WebSocket clients that fell behind.",
+            self.lagged_messages.load(Ordering::Relaxed) as f64,
+        );
+        metric(
+            "signalk_uptime_seconds",
+            "gauge",
+            "Seconds since the server started.",
+            snapshot.uptime as f64,
+        );
+
+        let contexts = lock(&self.contexts);
+        let contexts = contexts.sorted();
+        out.push_str(
+            "# HELP signalk_context_deltas_total Deltas processed per context.\n\
+             # TYPE signalk_context_deltas_total counter\n",
+        );
+        for (context, counts) in &contexts {
+            let _ = writeln!(
+                out,
+                "signalk_context_deltas_total{{context=\"{}\"}} {}",
+                escape_label(context),
+                counts.total
+            );
+        }
+        out.push_str(
+            "# HELP signalk_context_delta_rate Deltas per context in the last second.\n\
+             # TYPE signalk_context_delta_rate gauge\n",
+        );
+        for (context, counts) in &contexts {
+            let _ = writeln!(
+                out,
+                "signalk_context_delta_rate{{context=\"{}\"}} {}",
+                escape_label(context),
+                counts.rate
+            );
+        }
+        out
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Escape a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
 }
 
 impl Default for StatisticsCollector {
@@ -98,15 +481,16 @@ impl Default for StatisticsCollector {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use signalk_core::{PathValue, Update};
 
     #[test]
     fn test_statistics_collection() {
         let stats = StatisticsCollector::new();
 
         // Record some deltas
-        stats.record_delta();
-        stats.record_delta();
-        stats.record_delta();
+        stats.record_context_delta("vessels.self");
+        stats.record_context_delta("vessels.self");
+        stats.record_context_delta("vessels.self");
 
         // Update rate
         stats.update_rate();
@@ -127,4 +511,175 @@ mod tests {
         stats.client_disconnected();
         assert_eq!(stats.snapshot().ws_clients, 1);
     }
+
+    #[test]
+    fn test_registered_clients() {
+        let stats = Arc::new(StatisticsCollector::new());
+        let remote: SocketAddr = "192.168.1.20:50412".parse().unwrap();
+
+        let first = stats.register_client(Some(remote));
+        let second = stats.register_client(None);
+        assert_ne!(first.id(), second.id());
+        first.record_sent(100);
+        first.record_sent(20);
+        first.set_subscriptions(3);
+        assert_eq!(stats.snapshot().ws_clients, 2);
+
+        let connections = stats.connections();
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections[0].id, first.id());
+        assert_eq!(
+            connections[0].remote_address.as_deref(),
+            Some("192.168.1.20:50412")
+        );
+        assert_eq!(connections[0].bytes_sent, 120);
+        assert_eq!(connections[0].subscriptions, 3);
+        assert_eq!(connections[1].remote_address, None);
+
+        drop(first);
+        assert_eq!(stats.snapshot().ws_clients, 1);
+        assert_eq!(stats.connections()[0].id, second.id());
+    }
+
+    #[test]
+    fn test_prometheus_output() {
+        let stats = StatisticsCollector::new();
+        stats.record_context_delta("vessels.self");
+        stats.record_context_delta("vessels.self");
+        stats.record_context_delta("vessels.urn:mrn:imo:mmsi:230099999");
+        stats.record_dropped();
+        stats.record_lagged(5);
+        stats.set_active_paths(12);
+        stats.client_connected();
+        stats.update_rate();
+
+        let text = stats.prometheus();
+        let mut samples = std::collections::HashMap::new();
+        for line in text.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                assert!(comment.starts_with("HELP ") || comment.starts_with("TYPE "));
+                continue;
+            }
+            // `name{labels} value`, with a numeric value
+            let (name, value) = line.rsplit_once(' ').expect(line);
+            let value: f64 = value.parse().expect(line);
+            let metric = name.split('{').next().unwrap();
+            assert!(metric
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_'));
+            samples.insert(name.to_string(), value);
+        }
+
+        assert_eq!(samples["signalk_deltas_total"], 3.0);
+        assert_eq!(samples["signalk_delta_rate"], 3.0);
+        assert_eq!(samples["signalk_ws_clients"], 1.0);
+        assert_eq!(samples["signalk_active_paths"], 12.0);
+        assert_eq!(samples["signalk_deltas_dropped_total"], 1.0);
+        assert_eq!(samples["signalk_ws_lagged_messages_total"], 5.0);
+        assert!(samples.contains_key("signalk_uptime_seconds"));
+        assert_eq!(
+            samples[r#"signalk_context_deltas_total{context="vessels.self"}"#],
+            2.0
+        );
+        assert_eq!(
+            samples[r#"signalk_context_delta_rate{context="vessels.urn:mrn:imo:mmsi:230099999"}"#],
+            1.0
+        );
+
+        assert_eq!(escape_label("a\"b\\c"), r#"a\"b\\c"#);
+    }
+
+    fn delta(source: &str, paths: &[&str]) -> Delta {
+        Delta {
+            context: Some("vessels.self".to_string()),
+            updates: vec![Update {
+                source_ref: Some(source.to_string()),
+                source: None,
+                timestamp: None,
+                values: paths
+                    .iter()
+                    .map(|path| PathValue {
+                        path: path.to_string(),
+                        value: serde_json::json!(1.0),
+                    })
+                    .collect(),
+                meta: None,
+                server_timestamp: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_per_path_and_source_rates() {
+        let stats = StatisticsCollector::new();
+        for _ in 0..5 {
+            stats.record_delta(&delta(
+                "gps",
+                &[
+                    "navigation.speedOverGround",
+                    "navigation.courseOverGroundTrue",
+                ],
+            ));
+        }
+        for _ in 0..2 {
+            stats.record_delta(&delta("wind", &["environment.wind.speedApparent"]));
+        }
+        stats.update_rate();
+
+        let snapshot = stats.snapshot_detailed();
+        assert_eq!(snapshot.delta_rate, 7.0);
+        let sources: Vec<(&str, f64, u64)> = snapshot
+            .source_statistics
+            .iter()
+            .map(|s| (s.id.as_str(), s.delta_rate, s.delta_count))
+            .collect();
+        assert_eq!(sources, [("gps", 5.0, 5), ("wind", 2.0, 2)]);
+        assert_eq!(snapshot.path_statistics.len(), 3);
+        assert_eq!(
+            snapshot.path_statistics[2].id,
+            "environment.wind.speedApparent"
+        );
+        assert_eq!(snapshot.path_statistics[2].delta_count, 2);
+
+        // The plain snapshot leaves the breakdown out
+        let json = serde_json::to_value(stats.snapshot()).unwrap();
+        assert!(json.get("sourceStatistics").is_none());
+
+        // Rates are per second
+        stats.update_rate();
+        assert_eq!(
+            stats.snapshot_detailed().source_statistics[0].delta_rate,
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_contexts_bounded() {
+        let stats = StatisticsCollector::new();
+        for mmsi in 0..MAX_TRACKED_RATES + 100 {
+            stats.record_context_delta(&format!("vessels.urn:mrn:imo:mmsi:{mmsi}"));
+        }
+        stats.record_context_delta("vessels.self");
+
+        let metrics = stats.prometheus();
+        let tracked = metrics
+            .lines()
+            .filter(|line| line.starts_with("signalk_context_deltas_total{"))
+            .count();
+        assert_eq!(tracked, MAX_TRACKED_RATES);
+        assert!(metrics.contains("signalk_context_deltas_total{context=\"vessels.self\"} 1"));
+        assert!(!metrics.contains("mmsi:0\""));
+    }
+
+    #[test]
+    fn test_rate_table_evicts_least_recently_updated() {
+        let mut table = RateTable::new(2);
+        table.record("a");
+        table.record("b");
+        table.record("a");
+        table.record("c");
+        let mut ids: Vec<String> = table.top(10).into_iter().map(|s| s.id).collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "c"]);
+    }
 }